@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use arrow::array::{Array, ArrayRef, FixedSizeListArray, Float32Array, Float64Array, Int64Array, ListArray, StringArray, UInt64Array};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+
+use segment::types::{PayloadKeyType, PointIdType, VectorElementType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::operations::payload_ops::{PayloadInterface, PayloadVariant};
+use crate::operations::point_ops::PointInsertOperations;
+use crate::operations::types::PointColumns;
+
+type Payload = HashMap<PayloadKeyType, PayloadInterface>;
+
+fn arrow_error(column: &str, err: ArrowError) -> CollectionError {
+    CollectionError::BadInput { description: format!("Can't convert column {}, error: {}", column, err) }
+}
+
+fn unsupported_type(column: &str, data_type: &DataType) -> CollectionError {
+    CollectionError::BadInput { description: format!("Unsupported type {:?} of column {}", data_type, column) }
+}
+
+fn null_value(column: &str, row: usize) -> CollectionError {
+    CollectionError::BadInput { description: format!("Column {} has null value in row {}", column, row) }
+}
+
+fn is_integer(data_type: &DataType) -> bool {
+    matches!(data_type,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 |
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64)
+}
+
+fn is_float(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Float16 | DataType::Float32 | DataType::Float64)
+}
+
+/// Values of an array of any integer, float or string type, nulls are `None`
+enum TypedValues {
+    Integer(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Keyword(Vec<Option<String>>),
+}
+
+fn typed_values(column: &str, array: &ArrayRef) -> CollectionResult<TypedValues> {
+    let data_type = array.data_type();
+    let cast_to = |to_type: &DataType| cast(array, to_type).map_err(|err| arrow_error(column, err));
+    if is_integer(data_type) {
+        let values = cast_to(&DataType::Int64)?;
+        Ok(TypedValues::Integer(values.as_any().downcast_ref::<Int64Array>().unwrap().iter().collect()))
+    } else if is_float(data_type) {
+        let values = cast_to(&DataType::Float64)?;
+        Ok(TypedValues::Float(values.as_any().downcast_ref::<Float64Array>().unwrap().iter().collect()))
+    } else if matches!(data_type, DataType::Utf8 | DataType::LargeUtf8) {
+        let values = cast_to(&DataType::Utf8)?;
+        Ok(TypedValues::Keyword(values.as_any().downcast_ref::<StringArray>().unwrap()
+            .iter()
+            .map(|value| value.map(str::to_owned))
+            .collect()))
+    } else {
+        Err(unsupported_type(column, data_type))
+    }
+}
+
+/// Ids are either non-negative integers or UUID strings
+fn column_ids(column: &str, array: &ArrayRef) -> CollectionResult<Vec<PointIdType>> {
+    let invalid_id = |row: usize| CollectionError::BadInput {
+        description: format!("Column {} has invalid id in row {}", column, row)
+    };
+    if let Some(ids) = array.as_any().downcast_ref::<UInt64Array>() {
+        return ids.iter().enumerate()
+            .map(|(row, id)| id.map(PointIdType::NumId).ok_or_else(|| null_value(column, row)))
+            .collect();
+    }
+    match typed_values(column, array)? {
+        TypedValues::Integer(ids) => ids.into_iter().enumerate()
+            .map(|(row, id)| match id {
+                None => Err(null_value(column, row)),
+                Some(id) if id < 0 => Err(invalid_id(row)),
+                Some(id) => Ok(PointIdType::NumId(id as u64)),
+            })
+            .collect(),
+        TypedValues::Keyword(ids) => ids.into_iter().enumerate()
+            .map(|(row, id)| id.ok_or_else(|| null_value(column, row))?
+                .parse()
+                .map_err(|_| invalid_id(row)))
+            .collect(),
+        TypedValues::Float(_) => Err(unsupported_type(column, array.data_type())),
+    }
+}
+
+/// Vectors are fixed-size lists of `Float32`. Values are copied from the buffer of the list without conversion
+fn column_vectors(column: &str, array: &ArrayRef) -> CollectionResult<Vec<Vec<VectorElementType>>> {
+    let list = array.as_any().downcast_ref::<FixedSizeListArray>()
+        .ok_or_else(|| unsupported_type(column, array.data_type()))?;
+    let list_values = list.values();
+    let values = list_values.as_any().downcast_ref::<Float32Array>()
+        .ok_or_else(|| unsupported_type(column, array.data_type()))?
+        .values();
+    let dim = list.value_length() as usize;
+    (0..list.len())
+        .map(|row| {
+            if list.is_null(row) {
+                return Err(null_value(column, row));
+            }
+            let offset = list.value_offset(row) as usize;
+            Ok(values[offset..offset + dim].to_vec())
+        })
+        .collect()
+}
+
+/// Payload of each row: scalars are converted into single values, lists into lists. Nulls are skipped
+fn column_payload(column: &str, array: &ArrayRef) -> CollectionResult<Vec<Option<PayloadInterface>>> {
+    if let Some(list) = array.as_any().downcast_ref::<ListArray>() {
+        return (0..list.len())
+            .map(|row| {
+                if list.is_null(row) {
+                    return Ok(None);
+                }
+                let payload = match typed_values(column, &list.value(row))? {
+                    TypedValues::Integer(values) =>
+                        PayloadInterface::Integer(PayloadVariant::List(values.into_iter().flatten().collect())),
+                    TypedValues::Float(values) =>
+                        PayloadInterface::Float(PayloadVariant::List(values.into_iter().flatten().collect())),
+                    TypedValues::Keyword(values) =>
+                        PayloadInterface::Keyword(PayloadVariant::List(values.into_iter().flatten().collect())),
+                };
+                Ok(Some(payload))
+            })
+            .collect();
+    }
+    Ok(match typed_values(column, array)? {
+        TypedValues::Integer(values) => values.into_iter()
+            .map(|value| value.map(|value| PayloadInterface::Integer(PayloadVariant::Value(value))))
+            .collect(),
+        TypedValues::Float(values) => values.into_iter()
+            .map(|value| value.map(|value| PayloadInterface::Float(PayloadVariant::Value(value))))
+            .collect(),
+        TypedValues::Keyword(values) => values.into_iter()
+            .map(|value| value.map(|value| PayloadInterface::Keyword(PayloadVariant::Value(value))))
+            .collect(),
+    })
+}
+
+/// Convert the record batch into an insert operation with a point for each row.
+/// Ids are integers or UUID strings, vectors are fixed-size lists of `Float32`,
+/// payload fields are integers, floats, strings or lists of them
+pub fn record_batch_points(batch: &RecordBatch, columns: &PointColumns) -> CollectionResult<PointInsertOperations> {
+    let schema = batch.schema();
+    let column = |name: &str| schema.index_of(name)
+        .map(|idx| batch.column(idx))
+        .map_err(|_| CollectionError::BadInput { description: format!("Column {} is missing", name) });
+
+    let ids = column_ids(&columns.id, column(&columns.id)?)?;
+    let vectors = column_vectors(&columns.vector, column(&columns.vector)?)?;
+
+    let mut payloads: Vec<Payload> = vec![Payload::new(); batch.num_rows()];
+    for (idx, field) in schema.fields().iter().enumerate() {
+        let name = field.name();
+        let is_payload = match &columns.payload {
+            None => name != &columns.id && name != &columns.vector,
+            Some(payload_columns) => payload_columns.contains(name),
+        };
+        if !is_payload {
+            continue;
+        }
+        for (payload, value) in payloads.iter_mut().zip(column_payload(name, batch.column(idx))?) {
+            if let Some(value) = value {
+                payload.insert(name.clone(), value);
+            }
+        }
+    }
+    let has_payload = payloads.iter().any(|payload| !payload.is_empty());
+
+    Ok(PointInsertOperations::BatchPoints {
+        ids,
+        vectors,
+        payloads: if has_payload {
+            Some(payloads.into_iter().map(|payload| if payload.is_empty() { None } else { Some(payload) }).collect())
+        } else {
+            None
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{FixedSizeListBuilder, Float32Builder, Int32Array};
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+
+    fn vectors(rows: &[[f32; 2]]) -> FixedSizeListArray {
+        let mut builder = FixedSizeListBuilder::new(Float32Builder::new(rows.len() * 2), 2);
+        for row in rows {
+            builder.values().append_slice(row).unwrap();
+            builder.append(true).unwrap();
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn test_record_batch_points() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new("embedding", DataType::FixedSizeList(Box::new(Field::new("item", DataType::Float32, true)), 2), false),
+            Field::new("city", DataType::Utf8, true),
+            Field::new("rank", DataType::Int32, true),
+        ]);
+        let batch = RecordBatch::try_new(Arc::new(schema), vec![
+            Arc::new(UInt64Array::from(vec![1, 2])),
+            Arc::new(vectors(&[[1.0, 0.0], [0.0, 1.0]])),
+            Arc::new(StringArray::from(vec![Some("Berlin"), None])),
+            Arc::new(Int32Array::from(vec![Some(5), Some(7)])),
+        ]).unwrap();
+
+        let columns = PointColumns { id: "id".to_owned(), vector: "embedding".to_owned(), payload: None };
+        match record_batch_points(&batch, &columns).unwrap() {
+            PointInsertOperations::BatchPoints { ids, vectors, payloads } => {
+                assert_eq!(ids, vec![1.into(), 2.into()]);
+                assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+                let payloads = payloads.unwrap();
+                assert_eq!(payloads[0].as_ref().unwrap().len(), 2);
+                assert!(!payloads[1].as_ref().unwrap().contains_key("city"));
+            }
+            _ => panic!("Batch of points expected"),
+        }
+
+        let columns = PointColumns { id: "city".to_owned(), vector: "embedding".to_owned(), payload: Some(vec![]) };
+        assert!(record_batch_points(&batch, &columns).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::runtime;
+use tokio::runtime::Runtime;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use segment::common::stop_condition::StopCondition;
+use segment::types::ScoredPoint;
+
+use crate::collection::{Collection, CollectionError, CollectionResult};
+use crate::config::OverloadPolicy;
+use crate::operations::CollectionUpdateOperations;
+use crate::search_cache::{SearchCache, SearchCacheKey};
+use crate::operations::types::{CountRequest, CountResult, ReadConsistency, ScrollRequest, ScrollResult, SearchRequest, SearchRequestBatch, UpdateResult};
+
+/// Pool of threads, which run blocking operations of collections on behalf of async callers.
+/// Disk access and waiting for search threads happen on the pool, so callers, e.g. request handlers, are never blocked
+pub struct BlockingPool {
+    runtime: Runtime,
+}
+
+impl BlockingPool {
+    pub fn new(threads: usize) -> Self {
+        // Jobs only run on the blocking threads, the single worker thread stays idle
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .max_threads(threads.max(1) + 1)
+            .thread_name("blocking-ops")
+            .build()
+            .expect("Can't create blocking pool");
+        BlockingPool { runtime }
+    }
+
+    /// Perform the job on a thread of the pool and wait for the result without blocking the caller
+    pub async fn run<R, F>(&self, job: F) -> CollectionResult<R>
+        where R: Send + 'static,
+              F: FnOnce() -> CollectionResult<R> + Send + 'static
+    {
+        self.runtime.spawn_blocking(job).await?
+    }
+}
+
+/// Limit of updates, which are submitted through the async interface, but not finished yet.
+/// Updates wait for a thread of the blocking pool, so without the limit every incoming update
+/// would stay in memory until it is processed. Shared by all collections
+pub struct UpdateAdmission {
+    /// No limit, if not set
+    permits: Option<Semaphore>,
+    policy: OverloadPolicy,
+    pending: AtomicUsize,
+}
+
+/// Admitted update. Frees the place of the update, once it is finished
+struct AdmittedUpdate<'a> {
+    admission: &'a UpdateAdmission,
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for AdmittedUpdate<'_> {
+    fn drop(&mut self) {
+        self.admission.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl UpdateAdmission {
+    /// Admit up to `max_pending` updates at a time, 0 means no limit.
+    /// Further updates wait or are rejected according to the `policy`
+    pub fn new(max_pending: usize, policy: OverloadPolicy) -> Self {
+        UpdateAdmission {
+            permits: if max_pending == 0 { None } else { Some(Semaphore::new(max_pending)) },
+            policy,
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of admitted updates, which are not finished yet
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    async fn admit(&self) -> CollectionResult<AdmittedUpdate<'_>> {
+        let permit = match &self.permits {
+            None => None,
+            Some(permits) => match self.policy {
+                OverloadPolicy::Block => Some(permits.acquire().await),
+                OverloadPolicy::Reject => Some(permits.try_acquire().or_else(|_| Err(CollectionError::Overloaded {
+                    description: format!("{} updates are in progress, retry later", self.pending())
+                }))?),
+            }
+        };
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        Ok(AdmittedUpdate { admission: self, _permit: permit })
+    }
+}
+
+/// Stops the request, if its future is dropped before the result is received, e.g. because the client is gone
+struct StopOnDrop {
+    stop: StopCondition,
+    finished: bool,
+}
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stop.stop();
+        }
+    }
+}
+
+/// Async interface of the collection. Operations are performed on the blocking pool.
+/// Read operations are cancelled, once their future is dropped
+#[derive(Clone)]
+pub struct AsyncCollection {
+    collection: Arc<Collection>,
+    pool: Arc<BlockingPool>,
+    admission: Arc<UpdateAdmission>,
+    /// Cache of search results together with the name, which the collection is addressed by
+    search_cache: Option<(String, Arc<SearchCache>)>,
+}
+
+impl AsyncCollection {
+    pub fn new(collection: Arc<Collection>, pool: Arc<BlockingPool>, admission: Arc<UpdateAdmission>) -> Self {
+        AsyncCollection { collection, pool, admission, search_cache: None }
+    }
+
+    /// Return results of repeated searches from the `cache`, while the collection is not changed
+    pub fn with_search_cache(mut self, name: &str, cache: Arc<SearchCache>) -> Self {
+        self.search_cache = Some((name.to_string(), cache));
+        self
+    }
+
+    pub fn collection(&self) -> &Arc<Collection> {
+        &self.collection
+    }
+
+    async fn read<R, F>(&self, stop: StopCondition, read: F) -> CollectionResult<R>
+        where R: Send + 'static,
+              F: FnOnce(&Collection, &StopCondition) -> CollectionResult<R> + Send + 'static
+    {
+        let mut guard = StopOnDrop { stop: stop.clone(), finished: false };
+        let collection = self.collection.clone();
+        let result = self.pool.run(move || read(&collection, &stop)).await;
+        guard.finished = true;
+        result
+    }
+
+    /// Search results are returned from the search cache, if it is enabled and the collection is not changed
+    pub async fn search(
+        &self,
+        request: Arc<SearchRequest>,
+        consistency: ReadConsistency,
+        stop: StopCondition,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
+        let (name, cache) = match &self.search_cache {
+            Some((name, cache)) => (name, cache),
+            None => return self.read(stop, move |collection, stop| collection.search(request, consistency, stop)).await,
+        };
+        let key = SearchCacheKey::new(name, &request, consistency, &self.collection.strict_mode());
+        // Version is taken before the search, so changes made during the search invalidate its result
+        let data_version = self.collection.data_version();
+        if let Some(result) = cache.get(&key, &self.collection, data_version) {
+            // Cached result is recorded as well, so the replay repeats every search of the recording
+            let recorder = self.collection.recorder.read().clone();
+            return match recorder {
+                Some(recorder) => recorder.apply_searches(&[request], || Ok(vec![result.clone()]))
+                    .map(|mut results| results.pop().unwrap_or_default()),
+                None => Ok(result),
+            };
+        }
+        let result = self.read(stop, move |collection, stop| collection.search(request, consistency, stop)).await?;
+        cache.insert(key, &self.collection, data_version, &result);
+        Ok(result)
+    }
+
+    pub async fn search_batch(
+        &self,
+        request: Arc<SearchRequestBatch>,
+        consistency: ReadConsistency,
+        stop: StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        self.read(stop, move |collection, stop| collection.search_batch(request, consistency, stop)).await
+    }
+
+    pub async fn scroll(
+        &self,
+        request: Arc<ScrollRequest>,
+        consistency: ReadConsistency,
+        stop: StopCondition,
+    ) -> CollectionResult<ScrollResult> {
+        self.read(stop, move |collection, stop| collection.scroll(request, consistency, stop)).await
+    }
+
+    pub async fn count(
+        &self,
+        request: Arc<CountRequest>,
+        consistency: ReadConsistency,
+        stop: StopCondition,
+    ) -> CollectionResult<CountResult> {
+        self.read(stop, move |collection, stop| collection.count(request, consistency, stop)).await
+    }
+
+    /// Updates are not cancelled, once submitted. If `wait_indexed`, also waits for optimizers to process the changes.
+    /// Update is only submitted, if it is admitted, see `UpdateAdmission`
+    pub async fn update(
+        &self,
+        operation: CollectionUpdateOperations,
+        wait: bool,
+        wait_indexed: bool,
+    ) -> CollectionResult<UpdateResult> {
+        let _admitted = self.admission.admit().await?;
+        let collection = self.collection.clone();
+        self.pool.run(move || {
+            let result = collection.update(operation, wait)?;
+            if wait_indexed {
+                collection.wait_optimized()?;
+            }
+            Ok(result)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_admission() {
+        let runtime = runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let admission = UpdateAdmission::new(1, OverloadPolicy::Reject);
+            let admitted = admission.admit().await.unwrap();
+            assert_eq!(admission.pending(), 1);
+            assert!(matches!(admission.admit().await, Err(CollectionError::Overloaded { .. })));
+
+            drop(admitted);
+            assert_eq!(admission.pending(), 0);
+            assert!(admission.admit().await.is_ok());
+
+            let unlimited = UpdateAdmission::new(0, OverloadPolicy::Reject);
+            let _admitted: Vec<_> = futures::future::join_all((0..10).map(|_| unlimited.admit())).await;
+            assert_eq!(unlimited.pending(), 10);
+        });
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{File, create_dir_all, remove_dir_all};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use segment::common::config::{field_path, ConfigProblem, ValidateConfig};
+use segment::segment::Segment;
+use segment::segment_constructor::readonly_bundle::{BundleManifest, create_readonly_bundle};
+use segment::segment_constructor::segment_builder::SegmentBuilder;
+use segment::types::{PayloadIndexType, PayloadKeyType, PayloadType, PointIdType, SegmentConfig, TheMap, VectorElementType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::npy_import::read_npy_points;
+use crate::operations::payload_ops::PayloadInterface;
+use crate::operations::point_ops::PointStruct;
+use crate::operations::types::PointColumns;
+use crate::parquet_import::{PARQUET_BATCH_SIZE, read_parquet};
+
+/// Number of points, collected before they are written into the building segment
+const BUILD_BATCH_SIZE: usize = 1024;
+
+/// Parameters of a read-only bundle, which is built from a data file outside of the service
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct BundleBuildConfig {
+    /// Config of the bundled segment. Struct payload index is always used
+    pub params: SegmentConfig,
+    /// Payload fields to build index for
+    #[serde(default)]
+    pub indexed_fields: Vec<PayloadKeyType>,
+    /// Columns, which define points of parquet files
+    #[serde(default)]
+    pub columns: Option<PointColumns>,
+}
+
+impl ValidateConfig for BundleBuildConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        self.params.check(&field_path(path, "params"), problems);
+        if self.indexed_fields.iter().any(|field| field.is_empty()) {
+            problems.push(ConfigProblem::new(&field_path(path, "indexed_fields"), "field names should not be empty"));
+        }
+    }
+}
+
+/// Data file with points of the bundle
+#[derive(Debug, Clone, Copy)]
+pub enum BundleSource<'a> {
+    /// Vectors of `.npy` file with optional JSON lines of ids and payloads, see `read_npy_points`
+    Npy { vectors_path: &'a Path, points_path: Option<&'a Path> },
+    /// Parquet file with columns, specified in the config
+    Parquet { path: &'a Path },
+    /// Points in JSON lines, as written by `export_jsonl`
+    Jsonl { path: &'a Path },
+}
+
+impl<'a> BundleSource<'a> {
+    /// Source by the extension of the data file. File of ids and payloads is only accepted for `.npy` vectors
+    pub fn from_path(path: &'a Path, points_path: Option<&'a Path>) -> CollectionResult<Self> {
+        let source = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("npy") => BundleSource::Npy { vectors_path: path, points_path },
+            Some("parquet") => BundleSource::Parquet { path },
+            Some("jsonl") => BundleSource::Jsonl { path },
+            _ => return Err(CollectionError::BadRequest {
+                description: format!("Unknown format of {:?}, expected .npy, .parquet or .jsonl file", path)
+            }),
+        };
+        match (source, points_path) {
+            (BundleSource::Npy { .. }, _) | (_, None) => Ok(source),
+            (_, Some(points_path)) => Err(CollectionError::BadRequest {
+                description: format!("Points file {:?} is only used with .npy vectors", points_path)
+            }),
+        }
+    }
+}
+
+/// Segment under construction with a batch of points, which are not written into it yet
+struct BundleWriter {
+    builder: SegmentBuilder,
+    batch: Vec<(PointIdType, Vec<VectorElementType>, TheMap<PayloadKeyType, PayloadType>)>,
+    points_count: usize,
+}
+
+impl BundleWriter {
+    fn add(
+        &mut self,
+        id: PointIdType,
+        vector: Vec<VectorElementType>,
+        payload: Option<HashMap<PayloadKeyType, PayloadInterface>>,
+    ) -> CollectionResult<()> {
+        let payload = payload.unwrap_or_default()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.to_payload()))
+            .collect();
+        self.batch.push((id, vector, payload));
+        self.points_count += 1;
+        if self.batch.len() >= BUILD_BATCH_SIZE {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> CollectionResult<()> {
+        self.builder.add_points(0, self.batch.drain(..))?;
+        Ok(())
+    }
+}
+
+fn read_jsonl(path: &Path, writer: &mut BundleWriter) -> CollectionResult<()> {
+    let read_error = |err: std::io::Error| CollectionError::BadInput {
+        description: format!("Can't read {:?}, error: {}", path, err)
+    };
+    let reader = BufReader::new(File::open(path).map_err(read_error)?);
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(read_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let point: PointStruct = serde_json::from_str(&line).map_err(|err| CollectionError::BadInput {
+            description: format!("Can't parse line {} of {:?}, error: {}", line_idx + 1, path, err)
+        })?;
+        writer.add(point.id, point.vector, point.payload)?;
+    }
+    Ok(())
+}
+
+/// Build a read-only bundle at `bundle_path` from the data file, see `create_readonly_bundle`.
+/// Points are written into a single segment, then its vector and payload indexes are built, so the bundle could be
+/// built on a separate machine and copied to the nodes, which serve it.
+/// If the same id occurs several times, the last point is kept
+pub fn build_bundle(source: BundleSource, config: &BundleBuildConfig, bundle_path: &Path) -> CollectionResult<BundleManifest> {
+    if bundle_path.exists() {
+        return Err(CollectionError::BadRequest {
+            description: format!("Bundle {:?} already exists", bundle_path)
+        });
+    }
+    let build_path = bundle_path.with_extension("tmp");
+    let segment_config = SegmentConfig {
+        payload_index: Some(PayloadIndexType::Struct),
+        ..config.params.clone()
+    };
+
+    let build = || -> CollectionResult<BundleManifest> {
+        let segments_path = build_path.join("segments");
+        create_dir_all(&segments_path).map_err(|err| CollectionError::ServiceError {
+            error: format!("Can't create directory {:?}, error: {}", segments_path, err)
+        })?;
+        let mut builder = SegmentBuilder::new(&segments_path, &build_path.join("temp"), &segment_config)?;
+        builder.indexed_fields = config.indexed_fields.iter().cloned().collect();
+        let mut writer = BundleWriter { builder, batch: Vec::with_capacity(BUILD_BATCH_SIZE), points_count: 0 };
+
+        match source {
+            BundleSource::Npy { vectors_path, points_path } => {
+                read_npy_points(vectors_path, points_path, segment_config.vector_size, |id, vector, payload| {
+                    writer.add(id, vector, Some(payload))
+                })?;
+            }
+            BundleSource::Parquet { path } => {
+                let columns = config.columns.as_ref().ok_or_else(|| CollectionError::BadRequest {
+                    description: "Columns of points should be specified in the config to read parquet files".to_string()
+                })?;
+                read_parquet(path, columns, PARQUET_BATCH_SIZE, |operation| {
+                    for point in operation.into_points()? {
+                        writer.add(point.id, point.vector, point.payload)?;
+                    }
+                    Ok(())
+                })?;
+            }
+            BundleSource::Jsonl { path } => read_jsonl(path, &mut writer)?,
+        }
+        if writer.points_count == 0 {
+            return Err(CollectionError::BadInput {
+                description: "Data file contains no points".to_string()
+            });
+        }
+        writer.flush_batch()?;
+
+        let segment: Segment = writer.builder.try_into()?;
+        Ok(create_readonly_bundle(segment, bundle_path)?)
+    };
+
+    let result = build();
+    remove_dir_all(&build_path).ok();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use segment::common::config::{parse_config, ConfigFormat};
+    use segment::entry::entry_point::SegmentEntry;
+    use segment::segment_constructor::readonly_bundle::open_readonly_bundle;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_build_bundle_from_jsonl() {
+        let dir = TempDir::new("bundle_builder").unwrap();
+        let config: BundleBuildConfig = parse_config(r#"
+params:
+  vector_size: 2
+  distance: Dot
+  index:
+    type: plain
+    options: {}
+  storage_type:
+    type: in_memory
+indexed_fields: [city]
+"#, ConfigFormat::Yaml).unwrap();
+
+        let points: String = (0..100)
+            .map(|id| format!(
+                "{{\"id\": {}, \"vector\": [{}, 1.0], \"payload\": {{\"city\": {{\"type\": \"keyword\", \"value\": \"city-{}\"}}}}}}\n",
+                id % 90, id, id % 3,
+            ))
+            .collect();
+        let data_path = dir.path().join("points.jsonl");
+        write(&data_path, points).unwrap();
+
+        let bundle_path = dir.path().join("bundle");
+        let source = BundleSource::from_path(&data_path, None).unwrap();
+        let manifest = build_bundle(source, &config, &bundle_path).unwrap();
+        assert_eq!(manifest.points_count, 90);
+        assert_eq!(manifest.indexed_fields, vec!["city".to_string()]);
+        assert!(!bundle_path.with_extension("tmp").exists());
+        assert!(build_bundle(source, &config, &bundle_path).is_err());
+
+        let (_, segment) = open_readonly_bundle(&bundle_path).unwrap();
+        // Last occurrence of the duplicated id is kept
+        assert_eq!(segment.vector(5.into()).unwrap(), vec![95.0, 1.0]);
+
+        let config = BundleBuildConfig {
+            params: SegmentConfig { vector_size: 3, ..config.params },
+            ..config
+        };
+        let result = build_bundle(source, &config, &dir.path().join("wrong_dim"));
+        assert!(matches!(result, Err(CollectionError::BadInput { .. })), "{:?}", result);
+        assert!(!dir.path().join("wrong_dim").exists());
+
+        assert!(BundleSource::from_path(&dir.path().join("points.csv"), None).is_err());
+        assert!(BundleSource::from_path(&data_path, Some(&data_path)).is_err());
+    }
+}
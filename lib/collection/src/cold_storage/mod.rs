@@ -0,0 +1,86 @@
+pub mod object_store;
+pub mod s3;
+pub mod segment_cache;
+
+use std::fs::{File, remove_file};
+use std::path::Path;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder};
+
+use segment::entry::entry_point::OperationResult;
+use segment::segment::Segment;
+
+use crate::cold_storage::object_store::ObjectStore;
+use crate::cold_storage::s3::{S3Config, S3ObjectStore};
+use crate::cold_storage::segment_cache::SegmentCache;
+
+/// Storage tier for rarely used collections: segments are stored in S3-compatible object storage
+/// and only recently used ones are kept on the local disk
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct ColdStorageConfig {
+    pub s3: S3Config,
+    /// Directory, where fetched segments are cached
+    pub cache_path: String,
+    /// Max size of cached segments. Least recently used segments are removed from the cache first
+    pub cache_size_mb: u64,
+}
+
+/// Segments, offloaded into object storage, with local cache of recently used ones
+pub struct ColdStorage {
+    store: Box<dyn ObjectStore + Send + Sync>,
+    cache: SegmentCache,
+}
+
+impl ColdStorage {
+    pub fn new(config: &ColdStorageConfig) -> OperationResult<Self> {
+        let store = S3ObjectStore::new(config.s3.clone())?;
+        Self::with_store(Box::new(store), Path::new(&config.cache_path), config.cache_size_mb * 1024 * 1024)
+    }
+
+    pub fn with_store(
+        store: Box<dyn ObjectStore + Send + Sync>,
+        cache_path: &Path,
+        cache_size: u64,
+    ) -> OperationResult<Self> {
+        Ok(ColdStorage { store, cache: SegmentCache::new(cache_path, cache_size)? })
+    }
+
+    /// Pack files of the segment and upload them under the `key`
+    pub fn upload(&self, segment_path: &Path, key: &str) -> OperationResult<()> {
+        let archive_path = self.cache.path().join(format!("{}.upload", key.replace('/', "_")));
+        let upload = || -> OperationResult<()> {
+            let mut builder = Builder::new(File::create(&archive_path)?);
+            builder.append_dir_all(".", segment_path)?;
+            builder.finish()?;
+            self.store.put(key, &archive_path)
+        };
+        let result = upload();
+        remove_file(&archive_path).ok();
+        result
+    }
+
+    /// Read-only copy of the segment, fetched from the object storage, if it is not cached yet
+    pub fn segment(&self, key: &str) -> OperationResult<Arc<Segment>> {
+        self.cache.get_or_fetch(key, |segment_path| {
+            let archive_path = segment_path.with_extension("download");
+            let fetched = self.store.get(key, &archive_path)
+                .and_then(|_| Ok(Archive::new(File::open(&archive_path)?).unpack(segment_path)?));
+            remove_file(&archive_path).ok();
+            fetched
+        })
+    }
+
+    /// Delete the segment from the object storage and the cache
+    pub fn delete(&self, key: &str) -> OperationResult<()> {
+        self.cache.remove(key);
+        self.store.delete(key)
+    }
+
+    /// Number of cached segments and their total size
+    pub fn cache_usage(&self) -> (usize, u64) {
+        self.cache.usage()
+    }
+}
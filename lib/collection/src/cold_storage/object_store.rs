@@ -0,0 +1,48 @@
+use std::fs::{copy, create_dir_all, remove_file};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use segment::entry::entry_point::OperationResult;
+
+/// Storage of immutable files, addressed by keys
+pub trait ObjectStore {
+    /// Upload the file at `path` under the `key`, replacing the existing object
+    fn put(&self, key: &str, path: &Path) -> OperationResult<()>;
+
+    /// Download the object into a new file at `path`
+    fn get(&self, key: &str, path: &Path) -> OperationResult<()>;
+
+    /// Deleting of a missing object is not an error
+    fn delete(&self, key: &str) -> OperationResult<()>;
+}
+
+/// Objects, stored as files of a directory, e.g. on a mounted network storage
+pub struct LocalObjectStore {
+    pub path: PathBuf,
+}
+
+impl LocalObjectStore {
+    pub fn new(path: &Path) -> OperationResult<Self> {
+        create_dir_all(path)?;
+        Ok(LocalObjectStore { path: path.to_owned() })
+    }
+}
+
+impl ObjectStore for LocalObjectStore {
+    fn put(&self, key: &str, path: &Path) -> OperationResult<()> {
+        copy(path, self.path.join(key))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str, path: &Path) -> OperationResult<()> {
+        copy(self.path.join(key), path)?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> OperationResult<()> {
+        match remove_file(self.path.join(key)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
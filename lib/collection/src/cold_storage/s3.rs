@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::copy;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use segment::entry::entry_point::{OperationError, OperationResult};
+
+use crate::cold_storage::object_store::ObjectStore;
+
+/// Payload is streamed from disk, so it is not included into the signature
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// S3-compatible storage, e.g. AWS S3 or MinIO
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct S3Config {
+    /// Address of the storage, e.g. `https://s3.eu-central-1.amazonaws.com`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Prefix of keys of uploaded objects
+    #[serde(default)]
+    pub prefix: String,
+}
+
+impl S3Config {
+    /// URL-encoded path of the object with the given key, relative to the endpoint
+    pub fn object_path(&self, key: &str) -> String {
+        uri_encode_path(&format!("/{}/{}{}", self.bucket, self.prefix, key))
+    }
+
+    /// Host and port of the endpoint, which are signed as the `host` header
+    pub fn host(&self) -> Option<&str> {
+        let address = self.endpoint.splitn(2, "://").nth(1)?;
+        address.split('/').next().filter(|host| !host.is_empty())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Encode the path of the request as required by AWS Signature Version 4:
+/// everything except unreserved characters and `/` is percent-encoded
+fn uri_encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Value of the `Authorization` header of the request with unsigned payload, see
+/// https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html
+pub fn authorization(config: &S3Config, method: &str, path: &str, host: &str, time: &DateTime<Utc>) -> String {
+    let amz_date = time.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", time.format("%Y%m%d"), config.region);
+
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method, path, host, UNSIGNED_PAYLOAD, amz_date, SIGNED_HEADERS, UNSIGNED_PAYLOAD
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let signing_key = [time.format("%Y%m%d").to_string().as_str(), config.region.as_str(), "s3", "aws4_request"].iter()
+        .fold(format!("AWS4{}", config.secret_key).into_bytes(), |key, data| hmac_sha256(&key, data));
+    let signature = hex::encode(hmac_sha256(&signing_key, &string_to_sign));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key, scope, SIGNED_HEADERS, signature
+    )
+}
+
+/// Objects of the S3 bucket, accessed with blocking requests. Files are streamed from and to disk
+pub struct S3ObjectStore {
+    config: S3Config,
+    agent: ureq::Agent,
+}
+
+impl S3ObjectStore {
+    pub fn new(config: S3Config) -> OperationResult<Self> {
+        if config.host().is_none() {
+            return Err(OperationError::WrongInput {
+                description: format!("Invalid S3 endpoint: {}", config.endpoint)
+            });
+        }
+        Ok(S3ObjectStore { config, agent: ureq::Agent::new() })
+    }
+
+    fn request(&self, method: &str, key: &str) -> ureq::Request {
+        let path = self.config.object_path(key);
+        let url = format!("{}{}", self.config.endpoint.trim_end_matches('/'), path);
+        let host = self.config.host().unwrap_or_default();
+        let time = Utc::now();
+        self.agent.request(method, &url)
+            .set("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .set("x-amz-date", &time.format("%Y%m%dT%H%M%SZ").to_string())
+            .set("authorization", &authorization(&self.config, method, &path, host, &time))
+    }
+}
+
+fn request_error(method: &str, key: &str, err: ureq::Error) -> OperationError {
+    OperationError::service_error(&format!("{} of object {} failed: {}", method, key, err))
+}
+
+impl ObjectStore for S3ObjectStore {
+    fn put(&self, key: &str, path: &Path) -> OperationResult<()> {
+        let size = path.metadata()?.len();
+        self.request("PUT", key)
+            .set("content-length", &size.to_string())
+            .send(File::open(path)?)
+            .map_err(|err| request_error("PUT", key, err))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str, path: &Path) -> OperationResult<()> {
+        let response = self.request("GET", key)
+            .call()
+            .map_err(|err| request_error("GET", key, err))?;
+        copy(&mut response.into_reader(), &mut File::create(path)?)?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> OperationResult<()> {
+        match self.request("DELETE", key).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(err) => Err(request_error("DELETE", key, err)),
+        }
+    }
+}
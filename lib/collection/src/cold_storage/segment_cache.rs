@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_dir, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::debug;
+use parking_lot::Mutex;
+
+use segment::entry::entry_point::OperationResult;
+use segment::segment::Segment;
+use segment::segment_constructor::segment_constructor::load_segment_read_only;
+
+struct CachedSegment {
+    segment: Arc<Segment>,
+    /// Disk space, occupied by the files of the segment
+    size: u64,
+    /// Value of the access counter at the last access
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    segments: HashMap<String, CachedSegment>,
+    /// Locks of the segments, which are being fetched. Requests of the same segment wait for the fetch
+    fetching: HashMap<String, Arc<Mutex<()>>>,
+    access_counter: u64,
+}
+
+/// Local copies of cold segments, which are opened read-only.
+/// Least recently used segments are removed, once total size of the cache exceeds its capacity.
+/// Segments, which are still used by some request, are never removed.
+///
+/// Whole segments are cached rather than separate blocks of their files:
+/// read-only segments are opened from local files, so all of them should be present.
+pub struct SegmentCache {
+    path: PathBuf,
+    capacity: u64,
+    state: Mutex<CacheState>,
+}
+
+fn dir_size(path: &Path) -> OperationResult<u64> {
+    let mut size = 0;
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(size)
+}
+
+impl SegmentCache {
+    /// Copies, left in the cache directory by the previous run, are removed
+    pub fn new(path: &Path, capacity: u64) -> OperationResult<Self> {
+        if path.exists() {
+            remove_dir_all(path)?;
+        }
+        create_dir_all(path)?;
+        Ok(SegmentCache {
+            path: path.to_owned(),
+            capacity,
+            state: Default::default(),
+        })
+    }
+
+    /// Directory of the cache, also used for temporary files of downloads and uploads
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn segment_path(&self, key: &str) -> PathBuf {
+        self.path.join(key.replace('/', "_"))
+    }
+
+    /// Cached segment, if there is one. Access time of the segment is updated
+    fn get_cached(&self, state: &mut CacheState, key: &str) -> Option<Arc<Segment>> {
+        state.access_counter += 1;
+        let access_counter = state.access_counter;
+        state.segments.get_mut(key).map(|cached| {
+            cached.last_used = access_counter;
+            cached.segment.clone()
+        })
+    }
+
+    /// Cached segment with the given key. Missing segment is loaded from the directory, prepared by `fetch`.
+    /// Fetching is performed without the lock of the cache, so other segments are served meanwhile.
+    /// The same segment is never fetched twice at once: other requests of it wait for the running fetch
+    pub fn get_or_fetch(
+        &self,
+        key: &str,
+        fetch: impl FnOnce(&Path) -> OperationResult<()>,
+    ) -> OperationResult<Arc<Segment>> {
+        let fetch_lock = {
+            let mut state = self.state.lock();
+            if let Some(segment) = self.get_cached(&mut state, key) {
+                return Ok(segment);
+            }
+            state.fetching.entry(key.to_owned()).or_default().clone()
+        };
+        let _fetch_guard = fetch_lock.lock();
+
+        // Segment might be fetched by another request, while this one was waiting
+        {
+            let mut state = self.state.lock();
+            if let Some(segment) = self.get_cached(&mut state, key) {
+                Self::release_fetch_lock(&mut state, key, &fetch_lock);
+                return Ok(segment);
+            }
+        }
+
+        let segment_path = self.segment_path(key);
+        let loaded = fetch(&segment_path)
+            .and_then(|_| Ok((load_segment_read_only(&segment_path)?, dir_size(&segment_path)?)));
+
+        let mut state = self.state.lock();
+        Self::release_fetch_lock(&mut state, key, &fetch_lock);
+        let (segment, size) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                remove_dir_all(&segment_path).ok();
+                return Err(err);
+            }
+        };
+        let segment = Arc::new(segment);
+        state.access_counter += 1;
+        let access_counter = state.access_counter;
+        state.segments.insert(key.to_owned(), CachedSegment {
+            segment: segment.clone(),
+            size,
+            last_used: access_counter,
+        });
+        self.evict(&mut state);
+        Ok(segment)
+    }
+
+    /// Fetch lock is kept, while other requests wait for it, so they never fetch the segment concurrently.
+    /// Copies of the lock are only taken under the lock of the cache, so the count is exact
+    fn release_fetch_lock(state: &mut CacheState, key: &str, fetch_lock: &Arc<Mutex<()>>) {
+        if Arc::strong_count(fetch_lock) == 2 {
+            state.fetching.remove(key);
+        }
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        let mut total_size: u64 = state.segments.values().map(|cached| cached.size).sum();
+        while total_size > self.capacity {
+            let evicted = state.segments.iter()
+                .filter(|(_, cached)| Arc::strong_count(&cached.segment) == 1)
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            let key = match evicted {
+                Some(key) => key,
+                None => break,
+            };
+            let cached = state.segments.remove(&key).unwrap();
+            total_size -= cached.size;
+            drop(cached);
+            debug!(segment = %key, "Evicted cold segment from cache");
+            remove_dir_all(self.segment_path(&key)).ok();
+        }
+    }
+
+    /// Remove local copy of the segment, e.g. when the segment is deleted
+    pub fn remove(&self, key: &str) {
+        let mut state = self.state.lock();
+        if state.segments.remove(key).is_some() {
+            remove_dir_all(self.segment_path(key)).ok();
+        }
+    }
+
+    /// Number of cached segments and their total size
+    pub fn usage(&self) -> (usize, u64) {
+        let state = self.state.lock();
+        (state.segments.len(), state.segments.values().map(|cached| cached.size).sum())
+    }
+}
+
+unsafe impl Sync for SegmentCache {}
+
+unsafe impl Send for SegmentCache {}
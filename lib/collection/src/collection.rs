@@ -1,22 +1,56 @@
 use thiserror::Error;
 use crate::operations::CollectionUpdateOperations;
-use segment::types::{PointIdType, ScoredPoint, SegmentConfig, VectorElementType, HasIdCondition};
+use crate::operations::point_ops::PointOperations;
+use segment::types::{PointIdType, ScoredPoint, VectorElementType, HasIdCondition, WithPayload, WithPayloadInterface, ScoreType, PayloadKeyType, SeqNumberType};
 use std::result;
-use crate::operations::types::{Record, CollectionInfo, UpdateResult, UpdateStatus, SearchRequest, RecommendRequest};
+use crate::operations::types::{Record, CollectionInfo, UpdateResult, SearchRequest, SearchRequestBatch, RecommendRequest, RecommendStrategy, DiscoverRequest, FusionSearchRequest, FormulaSearchRequest, CountRequest, CountResult, SearchGroupsRequest, PointGroup, ScrollRequest, ScrollResult, ReadConsistency, OptimizationsInfo, CollectionHealth, CollectionTelemetry, HealthStatus, PointColumns, CsvMapping};
+use crate::segment_manager::group_searcher::search_groups;
+use crate::segment_manager::fusion::fuse;
+use crate::segment_manager::formula::rescore;
 use std::sync::Arc;
-use crate::wal::{SerdeWal, WalError};
-use crate::segment_manager::segment_managers::{SegmentSearcher, SegmentUpdater};
+use crate::wal::WalError;
 use segment::entry::entry_point::OperationError;
+use segment::common::stop_condition::StopCondition;
 use tokio::task::JoinError;
-use crossbeam_channel::{Sender, SendError};
-use crate::update_handler::update_handler::{UpdateHandler, UpdateSignal};
-use parking_lot::{Mutex, RwLock};
-use crate::segment_manager::holders::segment_holder::SegmentHolder;
-use tokio::runtime::Runtime;
+use crossbeam_channel::SendError;
+use parking_lot::RwLock;
 use itertools::Itertools;
 use std::collections::HashMap;
 use segment::types::Filter;
 use segment::types::Condition;
+use std::path::{Path, PathBuf};
+use std::fs::{File, create_dir_all, remove_dir_all, remove_file};
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+use segment::spaces::tools::mertic_object;
+use crate::config::{CollectionConfig, CollectionConfigDiff};
+use crate::collection_builder::optimizers_builder::OptimizersConfig;
+use crate::shard::{ReplicaId, Shard, ShardId, ShardOperations};
+use crate::shard::local_shard::LocalShard;
+use crate::shard::shard_holder::ShardHolder;
+use crate::strict_mode::StrictModeConfig;
+use crate::snapshot_manifest::SnapshotManifest;
+use crate::npy_import::build_import_segments;
+use crate::parquet_import::{PARQUET_BATCH_SIZE, read_parquet};
+use crate::csv_import::{CSV_BATCH_SIZE, read_csv_file};
+use crate::cold_storage::ColdStorage;
+use crate::optimization_pool::OptimizationPool;
+use crate::numa::NumaPlacement;
+use crate::arrow_import::record_batch_points;
+use crate::operation_recorder::OperationRecorder;
+use arrow::record_batch::RecordBatch;
+use tokio::runtime::Runtime;
+use wal::WalOptions;
+use tar::Builder;
+use tracing::info_span;
+use std::convert::TryInto;
+use segment::segment::Segment;
+use segment::segment_constructor::segment_builder::SegmentBuilder;
+use segment::segment_constructor::readonly_bundle::{BundleManifest, create_readonly_bundle};
+use segment::types::{PayloadIndexType, SegmentConfig};
+
+/// Number of points, read at once during the export
+const EXPORT_PAGE_SIZE: usize = 256;
 
 
 #[derive(Error, Debug, Clone)]
@@ -30,6 +64,12 @@ pub enum CollectionError {
     ServiceError { error: String },
     #[error("Bad request: {description}")]
     BadRequest { description: String },
+    #[error("Strict mode violation of {limit}: {description}")]
+    StrictModeViolation { limit: String, description: String },
+    #[error("Operation cancelled: {description}")]
+    Cancelled { description: String },
+    #[error("Service is overloaded: {description}")]
+    Overloaded { description: String },
 }
 
 impl From<OperationError> for CollectionError {
@@ -39,6 +79,16 @@ impl From<OperationError> for CollectionError {
             OperationError::PointIdError { missed_point_id } => Self::NotFound { missed_point_id },
             OperationError::ServiceError { description } => Self::ServiceError { error: description },
             OperationError::TypeError { .. } => Self::BadInput { description: format!("{}", err) },
+            OperationError::ReadOnlyError => Self::BadRequest { description: format!("{}", err) },
+            OperationError::WrongInput { description } => Self::BadInput { description },
+            OperationError::Cancelled { description } => Self::Cancelled { description },
+            OperationError::OutOfMemory { .. }
+            | OperationError::Corrupted { .. } => Self::ServiceError { error: format!("{}", err) },
+            OperationError::SegmentError { .. } => if err.is_user_error() {
+                Self::BadInput { description: format!("{}", err) }
+            } else {
+                Self::ServiceError { error: format!("{}", err) }
+            },
         }
     }
 }
@@ -64,86 +114,521 @@ impl<T> From<SendError<T>> for CollectionError {
 pub type CollectionResult<T> = result::Result<T, CollectionError>;
 
 pub struct Collection {
-    pub segments: Arc<RwLock<SegmentHolder>>,
-    pub config: SegmentConfig,
-    pub wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
-    pub searcher: Arc<dyn SegmentSearcher + Sync + Send>,
-    pub update_handler: Arc<UpdateHandler>,
-    pub updater: Arc<dyn SegmentUpdater + Sync + Send>,
-    pub runtime_handle: Arc<Runtime>,
-    pub update_sender: Sender<UpdateSignal>,
+    pub shards: Arc<ShardHolder>,
+    pub config: RwLock<CollectionConfig>,
+    /// Directory of the collection, where config and shards are stored
+    pub path: PathBuf,
+    /// WAL parameters of the service, used for new replicas
+    pub wal_options: WalOptions,
+    pub search_runtime: Arc<Runtime>,
+    /// Service-wide pool, which performs optimizations of all shards
+    pub optimization_pool: Arc<OptimizationPool>,
+    /// Service-wide placement of segments on NUMA nodes
+    pub numa: Arc<NumaPlacement>,
+    /// Service-wide optimizers parameters, used unless collection-specific ones are configured
+    pub default_optimizers_config: OptimizersConfig,
+    /// Recorder of updates and searches, if recording is started
+    pub recorder: RwLock<Option<Arc<OperationRecorder>>>,
 }
 
 
-/// Collection holds information about segments and WAL.
+/// Collection holds information about shards and configuration.
 impl Collection {
     /// Imply interior mutability.
-    /// Performs update operation on this collection asynchronously.
+    /// Performs update operation on shards of this collection asynchronously.
     /// Explicitly waits for result to be updated.
     pub fn update(&self, operation: CollectionUpdateOperations, wait: bool) -> CollectionResult<UpdateResult> {
-        let operation_id = self.wal.lock().write(&operation)?;
+        if let CollectionUpdateOperations::VersionedPointOperation(_) = operation {
+            return Err(CollectionError::BadRequest {
+                description: "Versioned operations are only produced by the service".to_string()
+            });
+        }
+        self.apply_update(operation, None, wait)
+    }
 
-        let upd = self.updater.clone();
-        let sndr = self.update_sender.clone();
-        let update_future = async move {
-            let res = upd.update(operation_id, operation);
-            sndr.send(UpdateSignal::Operation(operation_id))?;
-            res
-        };
+    /// Apply the point operation, which might have been applied already, e.g. a message delivered again after a restart.
+    /// `next_operation_ids` should be taken with `next_operation_ids` after the previous operation of the same source.
+    /// Points, changed by operations after them, are not changed, so the repeated operation never overwrites newer changes,
+    /// see `VersionedPointOperation`
+    pub fn update_unchanged_since(
+        &self,
+        operation: PointOperations,
+        next_operation_ids: &[SeqNumberType],
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        self.apply_update(CollectionUpdateOperations::PointOperation(operation), Some(next_operation_ids), wait)
+    }
 
-        let update_handler = self.runtime_handle.spawn(update_future);
+    /// Id, which will be assigned to the next operation of each shard
+    pub fn next_operation_ids(&self) -> CollectionResult<Vec<SeqNumberType>> {
+        self.shards.next_operation_ids()
+    }
 
-        if !wait {
-            return Ok(UpdateResult { operation_id, status: UpdateStatus::Acknowledged });
+    fn apply_update(
+        &self,
+        operation: CollectionUpdateOperations,
+        next_operation_ids: Option<&[SeqNumberType]>,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        let _span = info_span!("collection_update", wait).entered();
+        let apply = |operation: CollectionUpdateOperations, wait: bool| {
+            self.strict_mode().check_update(&operation, || self.indexed_fields())?;
+            match (next_operation_ids, operation) {
+                (Some(next_operation_ids), CollectionUpdateOperations::PointOperation(operation)) =>
+                    self.shards.update_unchanged_since(operation, next_operation_ids, wait),
+                (_, operation) => self.shards.update(operation, wait),
+            }
+        };
+        let recorder = self.recorder.read().clone();
+        match recorder {
+            // Recorded updates are always waited for, so they are recorded in the order of application
+            Some(recorder) => recorder.apply_update(operation, |operation| apply(operation, true)),
+            None => apply(operation, wait),
         }
+    }
+
+    /// Record all following updates and searches into the file, see `OperationRecorder`.
+    /// Replaces the current recording, if any
+    pub fn start_recording(&self, path: &Path) -> CollectionResult<()> {
+        let recorder = OperationRecorder::open(path)?;
+        *self.recorder.write() = Some(Arc::new(recorder));
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        *self.recorder.write() = None;
+    }
 
-        let _res: usize = self.runtime_handle.block_on(update_handler)??;
-        Ok(UpdateResult { operation_id, status: UpdateStatus::Completed })
+    /// Wait until all completed operations are processed by optimizers of all shards,
+    /// so search does not fall back to the full scan over the changed points.
+    pub fn wait_optimized(&self) -> CollectionResult<()> {
+        self.shards.wait_optimized()
     }
 
     pub fn info(&self) -> CollectionResult<CollectionInfo> {
-        let segments = self.segments.read();
-        let mut vectors_count = 0;
-        let mut segments_count = 0;
-        let mut ram_size = 0;
-        let mut disk_size = 0;
-        for (_idx, segment) in segments.iter() {
-            segments_count += 1;
-            let segment_info = segment.get().read().info();
-            vectors_count += segment_info.num_vectors;
-            disk_size += segment_info.disk_usage_bytes;
-            ram_size += segment_info.ram_usage_bytes;
-        }
+        let shards_info = self.shards.info()?;
         Ok(CollectionInfo {
-            vectors_count,
-            segments_count,
-            disk_data_size: disk_size,
-            ram_data_size: ram_size,
-            config: self.config.clone(),
+            vectors_count: shards_info.vectors_count,
+            segments_count: shards_info.segments_count,
+            disk_data_size: shards_info.disk_data_size,
+            ram_data_size: shards_info.ram_data_size,
+            memory_usage: shards_info.memory_usage,
+            config: self.config.read().clone(),
+        })
+    }
+
+    /// Collection is degraded, if background processes of some shards are failing
+    pub fn health(&self) -> CollectionResult<CollectionHealth> {
+        let issues = self.shards.background_errors()?;
+        let status = if issues.is_empty() { HealthStatus::Ok } else { HealthStatus::Degraded };
+        Ok(CollectionHealth { status, issues })
+    }
+
+    /// Optimizers parameters of the collection with optimizations, which are running or pending in its shards
+    pub fn optimizations(&self) -> CollectionResult<OptimizationsInfo> {
+        Ok(OptimizationsInfo {
+            config: self.config.read().optimizers_config(&self.default_optimizers_config),
+            optimizations: self.shards.optimizations()?,
+        })
+    }
+
+    /// State of shards and replicas with pending optimizations
+    pub fn telemetry(&self) -> CollectionResult<CollectionTelemetry> {
+        Ok(CollectionTelemetry {
+            shards: self.shards.telemetry()?,
+            optimizations: self.shards.optimizations()?,
         })
     }
 
-    pub fn search(&self, request: Arc<SearchRequest>) -> CollectionResult<Vec<ScoredPoint>> {
-        return self.searcher.search(request);
+    /// Change configuration of the existing collection without re-creating it.
+    /// New config is persisted first, then optimizers of all shards are re-configured and started in background,
+    /// so segments which do not correspond to the new config are re-built.
+    pub fn update_config(&self, diff: &CollectionConfigDiff) -> CollectionResult<()> {
+        let mut config = self.config.write();
+        let new_config = config.update(diff, &self.default_optimizers_config);
+        new_config.validate_replication()?;
+        new_config.save(&self.path)?;
+        self.shards.reconfigure(&new_config)?;
+        *config = new_config;
+        Ok(())
+    }
+
+    /// Limits of requests to the collection
+    pub fn strict_mode(&self) -> StrictModeConfig {
+        self.config.read().strict_mode.clone()
+    }
+
+    /// Payload fields, which have index in all shards of the collection
+    fn indexed_fields(&self) -> CollectionResult<Vec<PayloadKeyType>> {
+        let mut indexed_fields: Option<Vec<PayloadKeyType>> = None;
+        for shard in self.shards.shards() {
+            let shard_fields = shard.indexed_fields()?;
+            indexed_fields = Some(match indexed_fields {
+                None => shard_fields,
+                Some(fields) => fields.into_iter().filter(|field| shard_fields.contains(field)).collect(),
+            });
+        }
+        Ok(indexed_fields.unwrap_or_default())
+    }
+
+    fn check_search(&self, strict_mode: &StrictModeConfig, request: &SearchRequest) -> CollectionResult<()> {
+        strict_mode.check_top(request.top, request.offset)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())
+    }
+
+    /// Read requests are served by replicas of each shard, chosen according to the `consistency`.
+    /// Long-running reads are interrupted with `Cancelled` error once the `stop` condition is met
+    pub fn search(
+        &self,
+        request: Arc<SearchRequest>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
+        let _span = info_span!("collection_search", top = request.top, filtered = request.filter.is_some()).entered();
+        let search = || self.check_search(&self.strict_mode(), &request)
+            .and_then(|_| self.shards.search_batch_consistent(vec![request.clone()], consistency, stop));
+        let recorder = self.recorder.read().clone();
+        let results = match recorder {
+            Some(recorder) => recorder.apply_searches(&[request.clone()], search),
+            None => search(),
+        };
+        results.map(|mut results| results.pop().unwrap_or_default())
+    }
+
+    /// Execute several searches at once. Results are returned in the order of requests
+    pub fn search_batch(
+        &self,
+        request: Arc<SearchRequestBatch>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        let _span = info_span!("collection_search_batch", searches = request.searches.len()).entered();
+        let strict_mode = self.strict_mode();
+        strict_mode.check_batch_size(request.searches.len())?;
+        let requests = request.resolve();
+        for search in &requests {
+            self.check_search(&strict_mode, search)?;
+        }
+        let requests: Vec<Arc<SearchRequest>> = requests.into_iter().map(Arc::new).collect();
+        let search = || self.shards.search_batch_consistent(requests.clone(), consistency, stop);
+        let recorder = self.recorder.read().clone();
+        match recorder {
+            Some(recorder) => recorder.apply_searches(&requests, search),
+            None => search(),
+        }
+    }
+
+    /// Execute several searches and merge their results into a single ranked list
+    pub fn search_fusion(&self, request: Arc<FusionSearchRequest>) -> CollectionResult<Vec<ScoredPoint>> {
+        if request.searches.is_empty() {
+            return Err(CollectionError::BadRequest {
+                description: format!("At least one search required for fusion")
+            });
+        }
+        let strict_mode = self.strict_mode();
+        strict_mode.check_batch_size(request.searches.len())?;
+        strict_mode.check_top(request.top, request.offset)?;
+
+        let searches = request.searches.iter()
+            .map(|search| SearchRequest {
+                with_payload: request.with_payload.clone(),
+                with_vector: request.with_vector,
+                top: search.top.max(request.top + request.offset),
+                offset: 0,
+                ..search.clone()
+            })
+            .collect();
+
+        let batch = SearchRequestBatch {
+            searches,
+            filter: request.filter.clone(),
+            params: request.params,
+        };
+
+        let results = self.search_batch(Arc::new(batch), ReadConsistency::Any, &StopCondition::default())?;
+
+        Ok(fuse(results, request.fusion)
+            .into_iter()
+            .skip(request.offset)
+            .take(request.top)
+            .collect())
+    }
+
+    /// Search candidates and re-rank them by the formula of the similarity score and payload
+    pub fn search_formula(&self, request: Arc<FormulaSearchRequest>) -> CollectionResult<Vec<ScoredPoint>> {
+        let candidates = request.candidates.unwrap_or(0).max(request.top + request.offset);
+        let strict_mode = self.strict_mode();
+        strict_mode.check_top(candidates, 0)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())?;
+
+        let with_payload = request.with_payload.as_ref()
+            .map(WithPayload::from)
+            .unwrap_or_default();
+        let search = SearchRequest {
+            vector: request.vector.clone(),
+            filter: request.filter.clone(),
+            params: request.params,
+            // Whole payload is required for the formula, requested fields are selected after the scoring
+            with_payload: Some(WithPayloadInterface::Bool(with_payload.enable || request.formula.uses_payload())),
+            with_vector: request.with_vector,
+            top: candidates,
+            offset: 0,
+        };
+        let found = self.shards.search_batch_consistent(vec![Arc::new(search)], ReadConsistency::Any, &StopCondition::default())?
+            .pop()
+            .unwrap_or_default();
+
+        Ok(rescore(found, &request.formula)
+            .into_iter()
+            .skip(request.offset)
+            .take(request.top)
+            .map(|mut point| {
+                point.payload = match (with_payload.enable, &with_payload.payload_selector) {
+                    (false, _) => None,
+                    (true, None) => point.payload,
+                    (true, Some(selector)) => point.payload.map(|payload| selector.process(payload)),
+                };
+                point
+            })
+            .collect())
     }
 
+    pub fn search_groups(&self, request: Arc<SearchGroupsRequest>, stop: &StopCondition) -> CollectionResult<Vec<PointGroup>> {
+        let strict_mode = self.strict_mode();
+        strict_mode.check_top(request.limit * request.group_size, 0)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())?;
+        search_groups(self.shards.as_ref(), request, stop)
+    }
+
+    pub fn count(
+        &self,
+        request: Arc<CountRequest>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<CountResult> {
+        self.strict_mode().check_filter(request.filter.as_ref(), || self.indexed_fields())?;
+        let count = self.shards.count_consistent(request, consistency, stop)?;
+        Ok(CountResult { count })
+    }
+
+    /// Get points by ids. Result preserves the order of requested ids, missing points are skipped.
     pub fn retrieve(
         &self,
         points: &Vec<PointIdType>,
-        with_payload: bool,
+        with_payload: &WithPayload,
         with_vector: bool,
+        consistency: ReadConsistency,
     ) -> CollectionResult<Vec<Record>> {
-        return self.searcher.retrieve(points, with_payload, with_vector);
+        self.strict_mode().check_batch_size(points.len())?;
+        self.shards.retrieve_consistent(points, with_payload, with_vector, consistency)
     }
 
-    pub fn stop(&self) -> CollectionResult<()> {
-        self.update_sender.send(UpdateSignal::Stop)?;
-        Ok(())
+    /// Read points in ascending order of ids. Order is stable, so it could be used to export
+    /// the whole collection page by page, using `next_page_offset` of the previous result.
+    pub fn scroll(
+        &self,
+        request: Arc<ScrollRequest>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<ScrollResult> {
+        if request.limit == 0 {
+            return Err(CollectionError::BadRequest {
+                description: format!("Limit should be positive")
+            });
+        }
+        let strict_mode = self.strict_mode();
+        strict_mode.check_top(request.limit, 0)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())?;
+
+        // One more point is requested to find out the offset of the next page
+        let mut point_ids = self.shards.read_filtered(request.offset, request.limit + 1, request.filter.as_ref(), consistency, stop)?;
+
+        let next_page_offset = point_ids.get(request.limit).cloned();
+        point_ids.truncate(request.limit);
+
+        let with_payload = request.with_payload.as_ref()
+            .map(WithPayload::from)
+            .unwrap_or(WithPayload::from(true));
+        let mut points = self.shards.retrieve_consistent(&point_ids, &with_payload, request.with_vector, consistency)?;
+        points.sort_by_key(|point| point.id);
+
+        Ok(ScrollResult { points, next_page_offset })
+    }
+
+    /// Counter, which changes whenever points of the collection are changed.
+    /// Results of reads could be cached until it changes
+    pub fn data_version(&self) -> u64 {
+        self.shards.data_version()
     }
 
+    /// Persist all shards and truncate WAL records, which are no longer required for recovery
     pub fn flush_all(&self) -> CollectionResult<()> {
-        self.segments.read().flush_all()?;
-        Ok(())
+        self.shards.flush()
+    }
+
+    /// Save a copy of all shards together with the collection config into a single tar archive at `snapshot_path`.
+    /// Each shard is saved at a single position of its WAL, see `ShardOperations::snapshot`.
+    /// Updates are blocked while shards are saved, so positions of all shards are consistent with each other.
+    /// They are recorded in the manifest of the snapshot, which is validated on restore.
+    /// Data is collected in a temporary directory next to the archive, which is removed afterwards.
+    /// Archive could be restored with `restore_snapshot`.
+    /// Snapshot is checked for cancellation between shards and before archiving, cancelled snapshot leaves no files.
+    pub fn create_snapshot(&self, snapshot_path: &Path, stop: &StopCondition) -> CollectionResult<()> {
+        let temp_path = snapshot_path.with_extension("tmp");
+        let service_error = |err: std::io::Error| CollectionError::ServiceError {
+            error: format!("Can't create snapshot {:?}, error: {}", snapshot_path, err)
+        };
+
+        let create = || -> CollectionResult<()> {
+            create_dir_all(&temp_path).map_err(service_error)?;
+            self.config.read().save(&temp_path)?;
+            let shards = self.shards.snapshot(&temp_path, stop)?;
+            SnapshotManifest::new(&temp_path, shards)?.save(&temp_path)?;
+            stop.check()?;
+
+            let mut builder = Builder::new(File::create(snapshot_path).map_err(service_error)?);
+            builder.append_dir_all(".", &temp_path).map_err(service_error)?;
+            builder.finish().map_err(service_error)?;
+            Ok(())
+        };
+
+        let result = create();
+        remove_dir_all(&temp_path).ok();
+        if result.is_err() {
+            remove_file(snapshot_path).ok();
+        }
+        result
+    }
+
+    /// Insert points from numpy vectors file and optional points file, see `build_import_segments`.
+    /// Segments are built aside of the shards and then added to them, bypassing WAL and optimizers.
+    /// Returns number of imported points
+    pub fn import_npy(&self, vectors_path: &Path, points_path: Option<&Path>) -> CollectionResult<usize> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let import_path = self.path.join(format!(".import-{}", timestamp));
+        let config = self.config.read().clone();
+
+        let import = || -> CollectionResult<usize> {
+            let (points_count, segments) = build_import_segments(vectors_path, points_path, &config, &import_path)?;
+            for (shard_id, segment_path) in segments {
+                self.shards.shards()[shard_id as usize].import_segment(&segment_path)?;
+            }
+            Ok(points_count)
+        };
+
+        let result = import();
+        remove_dir_all(&import_path).ok();
+        result
+    }
+
+    /// Upsert points from the parquet file, see `read_parquet`.
+    /// Decoded batches are applied as regular updates, so they pass strict mode checks and are written into WAL.
+    /// Returns number of imported points
+    pub fn import_parquet(&self, path: &Path, columns: &PointColumns) -> CollectionResult<usize> {
+        let batch_size = self.strict_mode().limit_batch_size(PARQUET_BATCH_SIZE);
+        read_parquet(path, columns, batch_size, |points| {
+            self.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(points)), true)?;
+            Ok(())
+        })
+    }
+
+    /// Upsert points from the CSV file, see `read_csv`.
+    /// Batches are applied as regular updates, so they pass strict mode checks and are written into WAL.
+    /// Returns number of imported points
+    pub fn import_csv(&self, path: &Path, mapping: &CsvMapping) -> CollectionResult<usize> {
+        let batch_size = self.strict_mode().limit_batch_size(CSV_BATCH_SIZE);
+        read_csv_file(path, mapping, batch_size, |points| {
+            self.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(points)), true)?;
+            Ok(())
+        })
+    }
+
+    /// Move optimized segments into the cold storage. Only metadata of offloaded segments is kept locally,
+    /// their data is fetched into the local cache of the storage on demand.
+    /// Returns number of offloaded segments
+    pub fn offload_segments(&self, storage: &Arc<ColdStorage>) -> CollectionResult<usize> {
+        self.shards.offload_segments(storage)
+    }
+
+    /// Read memory mapped vectors of all segments into the page cache, so first searches after open or restore
+    /// are not slowed down by page faults. Returns number of loaded bytes
+    pub fn warm_up(&self) -> CollectionResult<usize> {
+        self.shards.warm_up()
+    }
+
+    /// Write all points into a new read-only bundle at `bundle_path`, see `create_readonly_bundle`.
+    /// Points are compacted into a single segment with index of all payload fields, indexed in the collection.
+    /// Points, changed during the export, might be written in either state
+    pub fn export_bundle(&self, bundle_path: &Path) -> CollectionResult<BundleManifest> {
+        let build_path = bundle_path.with_extension("tmp");
+        let config = SegmentConfig {
+            payload_index: Some(PayloadIndexType::Struct),
+            ..self.config.read().params.clone()
+        };
+        let limit = self.strict_mode().limit_top(EXPORT_PAGE_SIZE);
+
+        let build = || -> CollectionResult<BundleManifest> {
+            let segments_path = build_path.join("segments");
+            create_dir_all(&segments_path).map_err(|err| CollectionError::ServiceError {
+                error: format!("Can't create directory {:?}, error: {}", segments_path, err)
+            })?;
+            let mut builder = SegmentBuilder::new(&segments_path, &build_path.join("temp"), &config)?;
+            builder.indexed_fields = self.indexed_fields()?.into_iter().collect();
+
+            let mut offset = None;
+            loop {
+                let page = self.scroll(Arc::new(ScrollRequest {
+                    offset,
+                    limit,
+                    filter: None,
+                    with_payload: None,
+                    with_vector: true,
+                }), ReadConsistency::Any, &builder.stop)?;
+                builder.add_points(0, page.points.into_iter()
+                    .map(|point| (point.id, point.vector.unwrap_or_default(), point.payload.unwrap_or_default())))?;
+                offset = page.next_page_offset;
+                if offset.is_none() {
+                    break;
+                }
+            }
+
+            let segment: Segment = builder.try_into()?;
+            Ok(create_readonly_bundle(segment, bundle_path)?)
+        };
+
+        let result = build();
+        remove_dir_all(&build_path).ok();
+        result
+    }
+
+    /// Upsert a point for each row of the Arrow record batch, e.g. produced by DataFusion or Polars pipeline
+    /// of the application, which embeds the engine. See `record_batch_points` for supported columns
+    pub fn upsert_record_batch(&self, batch: &RecordBatch, columns: &PointColumns, wait: bool) -> CollectionResult<UpdateResult> {
+        let points = record_batch_points(batch, columns)?;
+        self.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(points)), wait)
+    }
+
+    /// Replace replica of the shard with a fresh copy of the shard, while the shard keeps accepting updates.
+    /// Returns id of the new replica, see `ReplicaSet::transfer_replica`
+    pub fn transfer_replica(&self, shard_id: ShardId, replica_id: ReplicaId, stop: &StopCondition) -> CollectionResult<ReplicaId> {
+        let replica_set = self.shards.shards().get(shard_id as usize)
+            .ok_or(CollectionError::BadRequest { description: format!("No shard {} in collection", shard_id) })?;
+        let config = self.config.read().clone();
+        replica_set.transfer_replica(replica_id, stop, |path, source| {
+            let replica = LocalShard::build_from(
+                shard_id,
+                path,
+                &self.wal_options,
+                &config,
+                self.search_runtime.clone(),
+                self.optimization_pool.clone(),
+                self.numa.clone(),
+                &self.default_optimizers_config,
+                source,
+                stop,
+            )?;
+            Ok(Arc::new(replica) as Arc<Shard>)
+        })
     }
 
     fn avg_vectors<'a>(vectors: impl Iterator<Item=&'a Vec<VectorElementType>>) -> Vec<VectorElementType> {
@@ -167,12 +652,91 @@ impl Collection {
         avg_vector
     }
 
+    /// Retrieve vectors of the given example points. All examples are required to exist
+    fn example_vectors(&self, ids: &Vec<PointIdType>) -> CollectionResult<HashMap<PointIdType, Vec<VectorElementType>>> {
+        let vectors = self.retrieve(ids, &WithPayload::from(false), true, ReadConsistency::Any)?;
+        let vectors_map: HashMap<PointIdType, Vec<VectorElementType>> = vectors
+            .into_iter()
+            .map(|rec| (rec.id, rec.vector.unwrap()))
+            .collect();
+
+        for point_id in ids.iter().cloned() {
+            if !vectors_map.contains_key(&point_id) {
+                return Err(CollectionError::NotFound {
+                    missed_point_id: point_id
+                });
+            }
+        }
+        Ok(vectors_map)
+    }
+
+    /// Combine user-defined filter with exclusion of example points
+    fn exclude_examples_filter(filter: Option<Filter>, ids: &[PointIdType]) -> Filter {
+        Filter {
+            should: None,
+            must: match filter {
+                None => None,
+                Some(filter) => Some(vec![Condition::Filter(filter)])
+            },
+            min_should: None,
+            must_not: Some(vec![Condition::HasId(HasIdCondition { has_id: ids.iter().cloned().collect() })]),
+        }
+    }
+
+    /// Collect candidates with given searches and order them by a custom score function.
+    /// Searches are expected to return vectors, as they are required for scoring.
+    fn search_and_rescore(
+        &self,
+        batch: SearchRequestBatch,
+        top: usize,
+        offset: usize,
+        with_vector: bool,
+        score: impl Fn(&[VectorElementType]) -> ScoreType,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
+        let requests = batch.resolve().into_iter().map(Arc::new).collect();
+        let candidates = self.shards.search_batch_consistent(requests, ReadConsistency::Any, &StopCondition::default())?
+            .into_iter()
+            .flatten()
+            .unique_by(|point| point.id);
+
+        let mut scored_points = candidates
+            .map(|mut point| {
+                point.score = score(point.vector.as_ref().unwrap());
+                if !with_vector {
+                    point.vector = None;
+                }
+                point
+            })
+            .collect_vec();
+
+        scored_points.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+
+        Ok(scored_points
+            .into_iter()
+            .skip(offset)
+            .take(top)
+            .collect())
+    }
+
     pub fn recommend(&self, request: Arc<RecommendRequest>) -> CollectionResult<Vec<ScoredPoint>> {
+        self.recommend_with_lookup(request, self)
+    }
+
+    /// Recommend points of this collection by examples, which vectors are taken from `lookup_collection`.
+    /// Examples are only excluded from results, if they are taken from this collection
+    pub fn recommend_with_lookup(
+        &self,
+        request: Arc<RecommendRequest>,
+        lookup_collection: &Collection,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
         if request.positive.is_empty() {
             return Err(CollectionError::BadRequest {
                 description: format!("At least one positive vector ID required")
             });
         }
+        let strict_mode = self.strict_mode();
+        strict_mode.check_top(request.top, request.offset)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())?;
 
         let reference_vectors_ids = request.positive
             .iter()
@@ -180,20 +744,39 @@ impl Collection {
             .cloned()
             .collect_vec();
 
-        let vectors = self.retrieve(&reference_vectors_ids, false, true)?;
-        let vectors_map: HashMap<PointIdType, Vec<VectorElementType>> = vectors
-            .into_iter()
-            .map(|rec| (rec.id, rec.vector.unwrap()))
-            .collect();
-
-        for point_id in reference_vectors_ids.iter().cloned() {
-            if !vectors_map.contains_key(&point_id) {
-                return Err(CollectionError::NotFound {
-                    missed_point_id: point_id
+        let is_same_collection = std::ptr::eq(self, lookup_collection);
+        if !is_same_collection {
+            let vector_size = self.config.read().params.vector_size;
+            let lookup_vector_size = lookup_collection.config.read().params.vector_size;
+            if vector_size != lookup_vector_size {
+                return Err(CollectionError::BadRequest {
+                    description: format!(
+                        "Vector size of lookup collection {} differs from vector size of the collection {}",
+                        lookup_vector_size, vector_size
+                    )
                 });
             }
         }
 
+        let vectors_map = lookup_collection.example_vectors(&reference_vectors_ids)?;
+        let search_filter = if is_same_collection {
+            Some(Collection::exclude_examples_filter(request.filter.clone(), &reference_vectors_ids))
+        } else {
+            request.filter.clone()
+        };
+
+        match request.strategy {
+            RecommendStrategy::AverageVector => self.recommend_by_average_vector(&request, &vectors_map, search_filter),
+            RecommendStrategy::BestScore => self.recommend_by_best_score(&request, &vectors_map, search_filter),
+        }
+    }
+
+    fn recommend_by_average_vector(
+        &self,
+        request: &RecommendRequest,
+        vectors_map: &HashMap<PointIdType, Vec<VectorElementType>>,
+        search_filter: Option<Filter>,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
         let avg_positive = Collection::avg_vectors(request.positive
             .iter()
             .map(|vid| vectors_map.get(vid).unwrap()));
@@ -216,24 +799,147 @@ impl Collection {
 
         let search_request = SearchRequest {
             vector: search_vector,
-            filter: Some(Filter {
-                should: None,
-                must: match request.filter.clone() {
-                    None => None,
-                    Some(filter) => Some(vec![Condition::Filter(filter)])
-                },
-                must_not: Some(vec![Condition::HasId(HasIdCondition { has_id: reference_vectors_ids.iter().cloned().collect() })]),
-            }),
+            filter: search_filter,
             params: request.params.clone(),
+            with_payload: request.with_payload.clone(),
+            with_vector: request.with_vector,
             top: request.top,
+            offset: request.offset,
         };
 
-        self.search(Arc::new(search_request))
+        // Filter is extended with excluded examples, so strict mode is checked for the original request only
+        let mut results = self.shards.search_batch_consistent(vec![Arc::new(search_request)], ReadConsistency::Any, &StopCondition::default())?;
+        Ok(results.pop().unwrap_or_default())
     }
-}
 
-impl Drop for Collection {
-    fn drop(&mut self) {
-        self.stop().unwrap(); // Finishes update tasks right before destructor stucks to do so with runtime
+    /// Candidates are collected by searching near each positive example, and then scored by the
+    /// most similar example: similarity to the best positive example, if it is closer than any negative one,
+    /// and negated squared similarity to the best negative example otherwise.
+    fn recommend_by_best_score(
+        &self,
+        request: &RecommendRequest,
+        vectors_map: &HashMap<PointIdType, Vec<VectorElementType>>,
+        search_filter: Option<Filter>,
+    ) -> CollectionResult<Vec<ScoredPoint>> {
+        let (distance, vector_size) = {
+            let config = self.config.read();
+            (config.params.distance, config.params.vector_size)
+        };
+        let metric = mertic_object(&distance, vector_size);
+
+        let searches = request.positive
+            .iter()
+            .map(|vid| SearchRequest {
+                vector: vectors_map.get(vid).unwrap().clone(),
+                filter: None,
+                params: None,
+                with_payload: request.with_payload.clone(),
+                with_vector: true,
+                top: request.top + request.offset,
+                offset: 0,
+            })
+            .collect();
+
+        let batch = SearchRequestBatch {
+            searches,
+            filter: search_filter,
+            params: request.params.clone(),
+        };
+
+        let best_similarity = |ids: &Vec<PointIdType>, vector: &[VectorElementType]| ids
+            .iter()
+            .map(|vid| metric.similarity(vectors_map.get(vid).unwrap(), vector))
+            .fold(None, |best: Option<ScoreType>, score| Some(best.map_or(score, |best| best.max(score))));
+
+        self.search_and_rescore(batch, request.top, request.offset, request.with_vector, |vector| {
+            let best_positive = best_similarity(&request.positive, vector).unwrap();
+            match best_similarity(&request.negative, vector) {
+                Some(best_negative) if best_negative >= best_positive => -(best_negative * best_negative),
+                _ => best_positive,
+            }
+        })
     }
-}
\ No newline at end of file
+
+    /// Search for points similar to the target, but only in the part of the space defined by context pairs:
+    /// each pair prefers points, which are closer to its positive example than to the negative one.
+    ///
+    /// With target, score is the number of satisfied pairs minus the number of violated pairs,
+    /// plus sigmoid of similarity to the target, which orders points within the same rank.
+    /// Without target, score is the sum of negative differences of similarities to positive and negative
+    /// examples of violated pairs, so every point which satisfies all pairs gets zero score.
+    pub fn discover(&self, request: Arc<DiscoverRequest>) -> CollectionResult<Vec<ScoredPoint>> {
+        if request.target.is_none() && request.context.is_empty() {
+            return Err(CollectionError::BadRequest {
+                description: format!("Either target or at least one context pair required")
+            });
+        }
+        let strict_mode = self.strict_mode();
+        strict_mode.check_top(request.top, request.offset)?;
+        strict_mode.check_filter(request.filter.as_ref(), || self.indexed_fields())?;
+
+        let reference_vectors_ids = request.target
+            .iter()
+            .cloned()
+            .chain(request.context.iter().flat_map(|pair| vec![pair.positive, pair.negative]))
+            .collect_vec();
+
+        let vectors_map = self.example_vectors(&reference_vectors_ids)?;
+        let search_filter = Collection::exclude_examples_filter(request.filter.clone(), &reference_vectors_ids);
+
+        let (distance, vector_size) = {
+            let config = self.config.read();
+            (config.params.distance, config.params.vector_size)
+        };
+        let metric = mertic_object(&distance, vector_size);
+
+        let limit = request.top + request.offset;
+        let candidate_search = |vid: &PointIdType, top: usize| SearchRequest {
+            vector: vectors_map.get(vid).unwrap().clone(),
+            filter: None,
+            params: None,
+            with_payload: request.with_payload.clone(),
+            with_vector: true,
+            top,
+            offset: 0,
+        };
+
+        // Neighbourhood of the target is over-sampled, as some of the closest points might be outside of the context.
+        // Neighbourhoods of positive examples provide candidates, which satisfy the context.
+        let searches = request.target
+            .iter()
+            .map(|vid| candidate_search(vid, limit * (1 + request.context.len())))
+            .chain(request.context.iter().map(|pair| candidate_search(&pair.positive, limit)))
+            .collect();
+
+        let batch = SearchRequestBatch {
+            searches,
+            filter: Some(search_filter),
+            params: request.params.clone(),
+        };
+
+        let pair_similarities = |vector: &[VectorElementType]| request.context
+            .iter()
+            .map(|pair| (
+                metric.similarity(vectors_map.get(&pair.positive).unwrap(), vector),
+                metric.similarity(vectors_map.get(&pair.negative).unwrap(), vector),
+            ))
+            .collect_vec();
+
+        self.search_and_rescore(batch, request.top, request.offset, request.with_vector, |vector| {
+            match &request.target {
+                Some(target) => {
+                    let rank: ScoreType = pair_similarities(vector)
+                        .into_iter()
+                        .map(|(positive, negative)| if positive > negative { 1.0 } else { -1.0 })
+                        .sum();
+                    let target_similarity = metric.similarity(vectors_map.get(target).unwrap(), vector);
+                    rank + 1.0 / (1.0 + (-target_similarity).exp())
+                }
+                None => pair_similarities(vector)
+                    .into_iter()
+                    .map(|(positive, negative)| (positive - negative).min(0.0))
+                    .sum(),
+            }
+        })
+    }
+}
@@ -1,27 +1,107 @@
-use crate::collection::{Collection, CollectionError};
+use crate::collection::Collection;
 use std::path::Path;
 use tokio::runtime::Runtime;
-use crate::segment_manager::holders::segment_holder::SegmentHolder;
-use crate::wal::SerdeWal;
-use crate::operations::CollectionUpdateOperations;
 use wal::WalOptions;
-use std::fs::{read_dir, File};
-use segment::segment_constructor::segment_constructor::load_segment;
-use crate::collection_builder::collection_builder::{construct_collection, COLLECTION_CONFIG_FILE};
-use indicatif::ProgressBar;
+use std::fs::{File, copy, create_dir_all, read_dir, remove_file, rename};
+use std::io;
+use crate::collection_builder::collection_builder::construct_collection;
 use crate::collection_builder::optimizers_builder::OptimizersConfig;
-use crate::collection_builder::optimizers_builder::build_optimizers;
-use segment::types::SegmentConfig;
-use std::io::Read;
+use crate::config::CollectionConfig;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::shard::{ReplicaId, Shard, ShardId, replica_path, shard_path};
+use crate::shard::replica_set::ReplicaSet;
+use crate::shard::local_shard::LocalShard;
+use crate::cold_storage::ColdStorage;
+use crate::optimization_pool::OptimizationPool;
+use crate::numa::NumaPlacement;
+use crate::snapshot_manifest::{SNAPSHOT_MANIFEST_FILE, SnapshotManifest};
 use std::sync::Arc;
+use tracing::info;
+use tar::Archive;
 
 
-fn load_config(path: &Path) -> SegmentConfig {
-    let config_path = path.join(COLLECTION_CONFIG_FILE);
-    let mut contents = String::new();
-    let mut file = File::open(config_path).unwrap();
-    file.read_to_string(&mut contents).unwrap();
-    serde_json::from_str(&contents).unwrap()
+/// Move WAL and segments of a shard from `legacy_path` into `new_path`.
+/// Each directory is moved separately, so interrupted migration is continued on the next load.
+fn move_shard_data(legacy_path: &Path, new_path: &Path) -> io::Result<()> {
+    for dir in &["wal", "segments", "temp_segments"] {
+        let legacy_dir = legacy_path.join(dir);
+        let new_dir = new_path.join(dir);
+        if legacy_dir.exists() && !new_dir.exists() {
+            info!(from = %legacy_dir.display(), to = %new_dir.display(), "Moving shard data into replicated layout");
+            create_dir_all(new_path)?;
+            rename(&legacy_dir, &new_dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Collections created before sharding store the only shard directly in the collection directory,
+/// and collections created before replication store the only replica directly in the shard directory.
+/// Move them into the directory of the first replica.
+fn migrate_legacy_layout(collection_path: &Path, shard_number: usize) -> io::Result<()> {
+    move_shard_data(collection_path, &shard_path(collection_path, 0))?;
+    for shard_id in 0..shard_number as ShardId {
+        let path = shard_path(collection_path, shard_id);
+        move_shard_data(&path, &replica_path(&path, 0))?;
+    }
+    Ok(())
+}
+
+
+/// Recursively copy content of the directory into a new one
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    create_dir_all(to)?;
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.metadata()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpack the collection snapshot, created by `Collection::create_snapshot`, into `collection_path`,
+/// so it could be opened by `load_collection`.
+/// Snapshot contains a single replica of each shard, which is copied to all replicas of the shard,
+/// so the restored replicas are in sync with each other.
+/// Snapshot is rejected, if its manifest does not match the unpacked data.
+/// Snapshots of older formats are upgraded, see `SnapshotManifest`.
+pub fn restore_snapshot(snapshot_path: &Path, collection_path: &Path) -> CollectionResult<()> {
+    File::open(snapshot_path)
+        .and_then(|file| Archive::new(file).unpack(collection_path))
+        .or_else(|err| Err(CollectionError::BadInput {
+            description: format!("Can't unpack snapshot {:?}, error: {}", snapshot_path, err)
+        }))?;
+
+    // Format is checked first, so snapshots of newer versions are rejected with an explicit error
+    let mut manifest = SnapshotManifest::load(collection_path)?;
+    if let Some(manifest) = &mut manifest {
+        manifest.upgrade(collection_path)?;
+    }
+    let collection_config = CollectionConfig::load(collection_path)?;
+    if let Some(manifest) = manifest {
+        manifest.validate(collection_path, &collection_config)?;
+        remove_file(collection_path.join(SNAPSHOT_MANIFEST_FILE)).ok();
+    }
+    for shard_id in 0..collection_config.shard_number as ShardId {
+        let path = shard_path(collection_path, shard_id);
+        let first_replica_path = replica_path(&path, 0);
+        if !first_replica_path.exists() {
+            return Err(CollectionError::BadInput {
+                description: format!("Snapshot {:?} has no data of shard {}", snapshot_path, shard_id)
+            });
+        }
+        for replica_id in 1..collection_config.replication_factor as ReplicaId {
+            copy_dir(&first_replica_path, &replica_path(&path, replica_id))
+                .or_else(|err| Err(CollectionError::ServiceError {
+                    error: format!("Can't restore replica {} of shard {}, error: {}", replica_id, shard_id, err)
+                }))?;
+        }
+    }
+    Ok(())
 }
 
 
@@ -29,66 +109,50 @@ pub fn load_collection(
     collection_path: &Path,
     wal_options: &WalOptions,  // from config
     search_runtime: Arc<Runtime>,  // from service
+    optimization_pool: Arc<OptimizationPool>,  // from service
+    numa: Arc<NumaPlacement>,  // from service
     optimizers_config: &OptimizersConfig,
+    cold_storage: Option<&Arc<ColdStorage>>,
 ) -> Collection {
-    let wal_path = collection_path.join("wal");
-    let segments_path = collection_path.join("segments");
-    let mut segment_holder = SegmentHolder::new();
+    let collection_config = CollectionConfig::load(&collection_path).expect("Can't read collection config");
 
-    let wal: SerdeWal<CollectionUpdateOperations> = SerdeWal::new(wal_path.to_str().unwrap(), wal_options).expect("Can't read WAL");
+    migrate_legacy_layout(collection_path, collection_config.shard_number)
+        .expect("Can't migrate collection to replicated layout");
 
-    let segment_dirs = read_dir(segments_path.as_path())
-        .expect(&format!("Can't read segments directory {}", segments_path.to_str().unwrap()));
+    let shards: Vec<Arc<ReplicaSet>> = (0..collection_config.shard_number as ShardId)
+        .map(|shard_id| {
+            let replica_set = ReplicaSet::new(
+                shard_id,
+                &shard_path(collection_path, shard_id),
+                collection_config.replication_factor,
+                collection_config.write_consistency_factor,
+                |path| {
+                    let replica = LocalShard::load(
+                        shard_id,
+                        path,
+                        wal_options,
+                        &collection_config,
+                        search_runtime.clone(),
+                        optimization_pool.clone(),
+                        numa.clone(),
+                        optimizers_config,
+                        cold_storage,
+                    );
+                    Ok(Arc::new(replica) as Arc<Shard>)
+                },
+            ).expect("Can't load replicas of the shard");
+            Arc::new(replica_set)
+        })
+        .collect();
 
-    for entry in segment_dirs {
-        let segments_path = entry.unwrap().path();
-        let segment = match load_segment(segments_path.as_path()) {
-            Ok(x) => x,
-            Err(err) => panic!(
-                format!("Can't load segments from {}, error: {}", segments_path.to_str().unwrap(), err)
-            ),
-        };
-        segment_holder.add(segment);
-    };
-
-    let segment_config = load_config(&collection_path);
-
-    let optimizers = build_optimizers(
+    construct_collection(
+        shards,
+        collection_config,
         collection_path,
-        &segment_config,
-        &optimizers_config,
-    );
-
-    let collection = construct_collection(
-        segment_holder,
-        &segment_config,
-        wal,
+        wal_options,
         search_runtime,
-        optimizers,
-        optimizers_config.flush_interval_sec,
-    );
-
-    {
-        let wal = collection.wal.lock();
-        let bar = ProgressBar::new(wal.len());
-        bar.set_message("Recovering collection");
-
-        for (op_num, update) in wal.read_all() {
-            // Panic only in case of internal error. If wrong formatting - skip
-            match collection.updater.update(op_num, update) {
-                Ok(_) => {}
-                Err(err) => match err {
-                    CollectionError::ServiceError { error } => panic!(format!("Can't apply WAL operation: {}", error)),
-                    _ => {}
-                }
-            }
-            bar.inc(1);
-        }
-
-        collection.flush_all().unwrap();
-        bar.finish();
-    }
-
-    collection
+        optimization_pool,
+        numa,
+        optimizers_config,
+    )
 }
-
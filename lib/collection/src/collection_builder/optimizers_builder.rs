@@ -1,16 +1,19 @@
 use crate::update_handler::update_handler::Optimizer;
 use std::sync::Arc;
 use crate::segment_manager::optimizers::vacuum_optimizer::VacuumOptimizer;
-use segment::types::SegmentConfig;
+use segment::common::config::{check_range, field_path, ConfigProblem, ValidateConfig};
+use segment::types::{PayloadKeyType, SegmentConfig};
 use crate::segment_manager::optimizers::merge_optimizer::MergeOptimizer;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
 use crate::segment_manager::optimizers::indexing_optimizer::IndexingOptimizer;
 use crate::segment_manager::optimizers::segment_optimizer::OptimizerThresholds;
+use crate::segment_manager::optimizers::config_mismatch_optimizer::ConfigMismatchOptimizer;
+use crate::segment_manager::optimizers::defragmentation_optimizer::DefragmentationOptimizer;
 
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 pub struct OptimizersConfig {
     pub deleted_threshold: f64,
     pub vacuum_min_vector_number: usize,
@@ -19,6 +22,39 @@ pub struct OptimizersConfig {
     pub indexing_threshold: usize,
     pub payload_indexing_threshold: usize,
     pub flush_interval_sec: u64,
+    /// Maximum number of vectors in a segment. Merge optimizer does not join segments,
+    /// which would produce a larger one. If not specified - segment size is not limited
+    #[serde(default)]
+    pub max_segment_size: Option<usize>,
+    /// Maximum number of optimizations, which are performed by each shard at the same time
+    #[serde(default = "default_max_optimization_threads")]
+    pub max_optimization_threads: usize,
+    /// Payload field, which defines tenants of a multi-tenant collection. If specified - points of each tenant
+    /// are moved into the same segment and stored next to each other, which speeds up searches filtered by tenant
+    #[serde(default)]
+    pub defragmentation_key: Option<PayloadKeyType>,
+}
+
+fn default_max_optimization_threads() -> usize {
+    1
+}
+
+impl ValidateConfig for OptimizersConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        check_range(&field_path(path, "deleted_threshold"), self.deleted_threshold, 0.0, 1.0, problems);
+        if self.max_segment_number == 0 {
+            problems.push(ConfigProblem::new(&field_path(path, "max_segment_number"), "should be positive"));
+        }
+        if self.flush_interval_sec == 0 {
+            problems.push(ConfigProblem::new(&field_path(path, "flush_interval_sec"), "should be positive"));
+        }
+        if self.max_segment_size == Some(0) {
+            problems.push(ConfigProblem::new(&field_path(path, "max_segment_size"), "should be positive"));
+        }
+        if self.max_optimization_threads == 0 {
+            problems.push(ConfigProblem::new(&field_path(path, "max_optimization_threads"), "should be positive"));
+        }
+    }
 }
 
 
@@ -36,7 +72,7 @@ pub fn build_optimizers(
         payload_indexing_threshold: optimizers_config.payload_indexing_threshold
     };
 
-    Arc::new(vec![
+    let mut optimizers: Vec<Box<Optimizer>> = vec![
         Box::new(
             IndexingOptimizer::new(
                 threshold_config.clone(),
@@ -48,6 +84,7 @@ pub fn build_optimizers(
         Box::new(
             MergeOptimizer::new(
                 optimizers_config.max_segment_number,
+                optimizers_config.max_segment_size,
                 threshold_config.clone(),
                 segments_path.clone(),
                 temp_segments_path.clone(),
@@ -61,6 +98,24 @@ pub fn build_optimizers(
             segments_path.clone(),
             temp_segments_path.clone(),
             segment_config.clone(),
-        ))
-    ])
+        )),
+        Box::new(ConfigMismatchOptimizer::new(
+            threshold_config.clone(),
+            segments_path.clone(),
+            temp_segments_path.clone(),
+            segment_config.clone(),
+        )),
+    ];
+
+    if let Some(defragmentation_key) = &optimizers_config.defragmentation_key {
+        optimizers.push(Box::new(DefragmentationOptimizer::new(
+            defragmentation_key.clone(),
+            threshold_config.clone(),
+            segments_path.clone(),
+            temp_segments_path.clone(),
+            segment_config.clone(),
+        )));
+    }
+
+    Arc::new(optimizers)
 }
@@ -0,0 +1,378 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use atomicwrites::AtomicFile;
+use atomicwrites::OverwriteBehavior::AllowOverwrite;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use segment::common::config::{field_path, ConfigProblem, ValidateConfig};
+use segment::types::{FlushPolicy, Indexes, PayloadKeyType, SegmentConfig, StorageType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::collection_builder::optimizers_builder::OptimizersConfig;
+use crate::strict_mode::StrictModeConfig;
+
+pub const COLLECTION_CONFIG_FILE: &str = "config.json";
+
+/// Persistent configuration of the collection
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionConfig {
+    /// Parameters of vectors and segments of the collection
+    #[serde(flatten)]
+    pub params: SegmentConfig,
+    /// Collection-specific optimizers parameters. If not specified - service-wide configuration is used
+    #[serde(default)]
+    pub optimizers_config: Option<OptimizersConfig>,
+    /// Number of shards the collection is split into. Could not be changed after the collection is created
+    #[serde(default = "default_shard_number")]
+    pub shard_number: usize,
+    /// Number of copies of each shard. Every copy receives all operations of the shard and serves reads.
+    /// Could not be changed after the collection is created
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: usize,
+    /// Number of replicas of a shard, which should acknowledge an update for it to succeed.
+    /// Should not exceed the replication factor
+    #[serde(default = "default_write_consistency_factor")]
+    pub write_consistency_factor: usize,
+    /// Number of threads per shard, which apply updates to segments. Changes of the same point
+    /// are always applied by the same thread. Could not be changed after the collection is created
+    #[serde(default = "default_update_workers")]
+    pub update_workers: usize,
+    /// Number of operations, which might wait for each update thread. Further updates are blocked,
+    /// until queued operations are applied. Could not be changed after the collection is created
+    #[serde(default = "default_update_queue_size")]
+    pub update_queue_size: usize,
+    /// Behavior of updates, which arrive while the update queue is full.
+    /// Could not be changed after the collection is created
+    #[serde(default)]
+    pub update_overload_policy: OverloadPolicy,
+    /// Payload field, which defines the shard of each point instead of its id. Points should have
+    /// a single keyword or integer value of this field. Requests, which filter by a single value of the field,
+    /// only touch one shard. Could not be changed after the collection is created
+    #[serde(default)]
+    pub shard_key: Option<PayloadKeyType>,
+    /// Limits of requests to the collection
+    #[serde(default)]
+    pub strict_mode: StrictModeConfig,
+}
+
+/// Behavior of requests, which arrive while the queue they should be placed into is full
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverloadPolicy {
+    /// Wait until queued requests are processed
+    Block,
+    /// Reject the request with an overload error, so the client could retry it later
+    Reject,
+}
+
+impl Default for OverloadPolicy {
+    fn default() -> Self {
+        OverloadPolicy::Block
+    }
+}
+
+impl ValidateConfig for CollectionConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        // Parameters of segments are flattened into the collection config
+        self.params.check(path, problems);
+        self.optimizers_config.check(&field_path(path, "optimizers_config"), problems);
+        for (field, value) in [
+            ("shard_number", self.shard_number),
+            ("replication_factor", self.replication_factor),
+            ("update_workers", self.update_workers),
+            ("update_queue_size", self.update_queue_size),
+        ].iter() {
+            if *value == 0 {
+                problems.push(ConfigProblem::new(&field_path(path, field), "should be positive"));
+            }
+        }
+        if self.write_consistency_factor == 0 || self.write_consistency_factor > self.replication_factor {
+            problems.push(ConfigProblem::new(
+                &field_path(path, "write_consistency_factor"),
+                format!("value {} should be between 1 and replication_factor {}", self.write_consistency_factor, self.replication_factor),
+            ));
+        }
+    }
+}
+
+fn default_shard_number() -> usize {
+    1
+}
+
+fn default_replication_factor() -> usize {
+    1
+}
+
+fn default_write_consistency_factor() -> usize {
+    1
+}
+
+fn default_update_workers() -> usize {
+    1
+}
+
+fn default_update_queue_size() -> usize {
+    1024
+}
+
+/// Changes of the optimizers parameters. Only specified parameters are changed
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct OptimizersConfigDiff {
+    pub deleted_threshold: Option<f64>,
+    pub vacuum_min_vector_number: Option<usize>,
+    pub max_segment_number: Option<usize>,
+    pub memmap_threshold: Option<usize>,
+    pub indexing_threshold: Option<usize>,
+    pub payload_indexing_threshold: Option<usize>,
+    pub flush_interval_sec: Option<u64>,
+    pub max_segment_size: Option<usize>,
+    pub max_optimization_threads: Option<usize>,
+    pub defragmentation_key: Option<PayloadKeyType>,
+}
+
+impl OptimizersConfigDiff {
+    pub fn update(&self, config: &OptimizersConfig) -> OptimizersConfig {
+        OptimizersConfig {
+            deleted_threshold: self.deleted_threshold.unwrap_or(config.deleted_threshold),
+            vacuum_min_vector_number: self.vacuum_min_vector_number.unwrap_or(config.vacuum_min_vector_number),
+            max_segment_number: self.max_segment_number.unwrap_or(config.max_segment_number),
+            memmap_threshold: self.memmap_threshold.unwrap_or(config.memmap_threshold),
+            indexing_threshold: self.indexing_threshold.unwrap_or(config.indexing_threshold),
+            payload_indexing_threshold: self.payload_indexing_threshold.unwrap_or(config.payload_indexing_threshold),
+            flush_interval_sec: self.flush_interval_sec.unwrap_or(config.flush_interval_sec),
+            max_segment_size: self.max_segment_size.or(config.max_segment_size),
+            max_optimization_threads: self.max_optimization_threads.unwrap_or(config.max_optimization_threads),
+            defragmentation_key: self.defragmentation_key.clone().or(config.defragmentation_key.clone()),
+        }
+    }
+}
+
+/// Changes of the collection configuration. Only specified parameters are changed.
+/// Vector size and distance could not be changed, as it would require to re-create all stored vectors.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionConfigDiff {
+    /// New parameters of the vector index. Segments with different index parameters are re-built in background
+    pub index: Option<Indexes>,
+    /// New persistence policy of the collection
+    pub flush_policy: Option<FlushPolicy>,
+    /// New storage of vectors. Existing segments are re-built with the new storage in background, one at a time
+    pub storage_type: Option<StorageType>,
+    /// Changes of the optimizers parameters
+    pub optimizers_config: Option<OptimizersConfigDiff>,
+    /// New number of replicas, which should acknowledge an update
+    pub write_consistency_factor: Option<usize>,
+    /// New limits of requests, replace all previous limits
+    pub strict_mode: Option<StrictModeConfig>,
+}
+
+impl CollectionConfig {
+    pub fn new(params: SegmentConfig) -> Self {
+        CollectionConfig {
+            params,
+            optimizers_config: None,
+            shard_number: default_shard_number(),
+            replication_factor: default_replication_factor(),
+            write_consistency_factor: default_write_consistency_factor(),
+            update_workers: default_update_workers(),
+            update_queue_size: default_update_queue_size(),
+            update_overload_policy: Default::default(),
+            shard_key: None,
+            strict_mode: Default::default(),
+        }
+    }
+
+    /// Read config of the collection, stored in the given directory.
+    /// Configs of older versions, which only contain segment parameters, are also accepted.
+    pub fn load(path: &Path) -> CollectionResult<Self> {
+        let config_path = path.join(COLLECTION_CONFIG_FILE);
+        let mut contents = String::new();
+        let mut file = File::open(&config_path).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't read {:?}, error: {}", config_path, err)
+        }))?;
+        file.read_to_string(&mut contents).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't read {:?}, error: {}", config_path, err)
+        }))?;
+        serde_json::from_str(&contents).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't parse {:?}, error: {}", config_path, err)
+        }))
+    }
+
+    pub fn save(&self, path: &Path) -> CollectionResult<()> {
+        let config_path = path.join(COLLECTION_CONFIG_FILE);
+        let af = AtomicFile::new(&config_path, AllowOverwrite);
+        let state_bytes = serde_json::to_vec(self).unwrap();
+        af.write(|f| {
+            f.write_all(&state_bytes)
+        }).or_else(move |err|
+            Err(CollectionError::ServiceError {
+                error: format!("Can't write {:?}, error: {}", config_path, err)
+            })
+        )?;
+        Ok(())
+    }
+
+    /// Optimizers parameters of the collection, falling back to the service-wide ones
+    pub fn optimizers_config(&self, default_config: &OptimizersConfig) -> OptimizersConfig {
+        self.optimizers_config.clone().unwrap_or(default_config.clone())
+    }
+
+    /// Flush policy of the collection, falling back to the flush interval of the optimizers
+    pub fn flush_policy(&self, optimizers_config: &OptimizersConfig) -> FlushPolicy {
+        self.params.flush_policy
+            .unwrap_or(FlushPolicy::Interval { seconds: optimizers_config.flush_interval_sec })
+    }
+
+    /// Produce new config with given changes applied.
+    /// Changed optimizers parameters become collection-specific from now on.
+    pub fn update(&self, diff: &CollectionConfigDiff, default_optimizers_config: &OptimizersConfig) -> CollectionConfig {
+        let mut config = self.clone();
+        if let Some(index) = diff.index {
+            config.params.index = index;
+        }
+        if let Some(flush_policy) = diff.flush_policy {
+            config.params.flush_policy = Some(flush_policy);
+        }
+        if let Some(storage_type) = diff.storage_type {
+            config.params.storage_type = storage_type;
+        }
+        if let Some(optimizers_diff) = &diff.optimizers_config {
+            config.optimizers_config = Some(optimizers_diff.update(&self.optimizers_config(default_optimizers_config)));
+        }
+        if let Some(write_consistency_factor) = diff.write_consistency_factor {
+            config.write_consistency_factor = write_consistency_factor;
+        }
+        if let Some(strict_mode) = &diff.strict_mode {
+            config.strict_mode = strict_mode.clone();
+        }
+        config
+    }
+
+    /// Check parameters of replication, which could not be checked by deserialization
+    pub fn validate_replication(&self) -> CollectionResult<()> {
+        if self.replication_factor == 0 {
+            return Err(CollectionError::BadInput {
+                description: format!("Collection should have at least one replica of each shard")
+            });
+        }
+        if self.write_consistency_factor == 0 || self.write_consistency_factor > self.replication_factor {
+            return Err(CollectionError::BadInput {
+                description: format!(
+                    "Write consistency factor should be between 1 and replication factor {}",
+                    self.replication_factor
+                )
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use segment::common::config::{parse_config, ConfigFormat};
+    use segment::entry::entry_point::OperationError;
+    use segment::types::Distance;
+    use tempdir::TempDir;
+
+    fn default_optimizers_config() -> OptimizersConfig {
+        OptimizersConfig {
+            deleted_threshold: 0.9,
+            vacuum_min_vector_number: 1000,
+            max_segment_number: 10,
+            memmap_threshold: 100_000,
+            indexing_threshold: 50_000,
+            payload_indexing_threshold: 20_000,
+            flush_interval_sec: 30,
+            max_segment_size: None,
+            max_optimization_threads: 1,
+            defragmentation_key: None,
+        }
+    }
+
+    fn segment_config() -> SegmentConfig {
+        SegmentConfig {
+            vector_size: 4,
+            index: Indexes::Plain {},
+            payload_index: Some(Default::default()),
+            distance: Distance::Dot,
+            storage_type: Default::default(),
+            text_analyzers: Default::default(),
+            flush_policy: None,
+        }
+    }
+
+    #[test]
+    fn test_load_segment_config() {
+        let dir = TempDir::new("collection_dir").unwrap();
+        let params = segment_config();
+        std::fs::write(dir.path().join(COLLECTION_CONFIG_FILE), serde_json::to_vec(&params).unwrap()).unwrap();
+
+        let config = CollectionConfig::load(dir.path()).unwrap();
+        assert_eq!(config, CollectionConfig::new(params));
+    }
+
+    #[test]
+    fn test_update_config() {
+        let dir = TempDir::new("collection_dir").unwrap();
+        let defaults = default_optimizers_config();
+        let config = CollectionConfig::new(segment_config());
+        assert_eq!(config.flush_policy(&defaults), FlushPolicy::Interval { seconds: 30 });
+
+        let diff = CollectionConfigDiff {
+            index: Some(Indexes::Hnsw { m: 32, ef_construct: 200 }),
+            flush_policy: None,
+            storage_type: None,
+            optimizers_config: Some(OptimizersConfigDiff {
+                indexing_threshold: Some(100),
+                ..Default::default()
+            }),
+            write_consistency_factor: None,
+            strict_mode: None,
+        };
+        let updated = config.update(&diff, &defaults);
+        updated.save(dir.path()).unwrap();
+
+        let loaded = CollectionConfig::load(dir.path()).unwrap();
+        assert_eq!(loaded, updated);
+        assert_eq!(loaded.params.index, Indexes::Hnsw { m: 32, ef_construct: 200 });
+        assert_eq!(loaded.params.vector_size, 4);
+
+        let optimizers_config = loaded.optimizers_config(&defaults);
+        assert_eq!(optimizers_config.indexing_threshold, 100);
+        assert_eq!(optimizers_config.memmap_threshold, defaults.memmap_threshold);
+    }
+
+    #[test]
+    fn test_parse_collection_config() {
+        let mut config = CollectionConfig::new(segment_config());
+        config.optimizers_config = Some(default_optimizers_config());
+        let json = serde_json::to_value(&config).unwrap();
+        let parsed: CollectionConfig = parse_config(&json.to_string(), ConfigFormat::Json).unwrap();
+        assert_eq!(parsed, config);
+
+        let mut invalid = json.clone();
+        invalid["optimizers_config"]["max_segment_numbr"] = 5.into();
+        invalid["optimizers_config"]["deleted_threshold"] = 1.5.into();
+        invalid["replication_factor"] = 2.into();
+        invalid["write_consistency_factor"] = 3.into();
+        let description = match parse_config::<CollectionConfig>(&invalid.to_string(), ConfigFormat::Json) {
+            Err(OperationError::WrongInput { description }) => description,
+            other => panic!("config should be invalid: {:?}", other),
+        };
+        assert!(description.contains("optimizers_config.max_segment_numbr: unknown field"), "{}", description);
+        assert!(description.contains("optimizers_config.deleted_threshold: value 1.5 is out of range"), "{}", description);
+        assert!(description.contains("write_consistency_factor: value 3 should be between 1 and replication_factor 2"), "{}", description);
+
+        let mut problems = vec![];
+        let existing = SegmentConfig { vector_size: 8, ..segment_config() };
+        config.params.check_compatible("", &existing, &mut problems);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, "vector_size");
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord};
+
+use segment::types::{PayloadKeyType, PointIdType, VectorElementType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::operations::payload_ops::{PayloadInterface, PayloadVariant};
+use crate::operations::point_ops::PointInsertOperations;
+use crate::operations::types::{CsvColumnType, CsvMapping};
+
+/// Max number of rows, inserted with a single operation
+pub const CSV_BATCH_SIZE: usize = 1024;
+
+type Payload = HashMap<PayloadKeyType, PayloadInterface>;
+
+/// Positions of the mapped columns in the header row
+struct ColumnPositions {
+    id: usize,
+    vector: Vec<usize>,
+    payload: Vec<(usize, String, CsvColumnType)>,
+}
+
+impl ColumnPositions {
+    fn new(headers: &StringRecord, mapping: &CsvMapping) -> CollectionResult<Self> {
+        let position = |column: &str| headers.iter()
+            .position(|header| header == column)
+            .ok_or_else(|| CollectionError::BadInput { description: format!("Column {} is missing", column) });
+        if mapping.vector.is_empty() {
+            return Err(CollectionError::BadInput { description: format!("At least one vector column is required") });
+        }
+        let mut payload = mapping.payload.iter()
+            .map(|(column, column_type)| Ok((position(column)?, column.clone(), *column_type)))
+            .collect::<CollectionResult<Vec<_>>>()?;
+        payload.sort_by_key(|(position, _, _)| *position);
+        Ok(ColumnPositions {
+            id: position(&mapping.id)?,
+            vector: mapping.vector.iter().map(|column| position(column)).collect::<CollectionResult<_>>()?,
+            payload,
+        })
+    }
+}
+
+fn invalid_value(line: u64, column: &str, value: &str) -> CollectionError {
+    CollectionError::BadInput { description: format!("Invalid value {:?} of column {} at line {}", value, column, line) }
+}
+
+/// Ids are either non-negative integers or UUID strings
+fn parse_id(value: &str) -> Option<PointIdType> {
+    match value.parse::<u64>() {
+        Ok(id) => Some(PointIdType::NumId(id)),
+        Err(_) => value.parse().ok(),
+    }
+}
+
+fn parse_payload(value: &str, column_type: CsvColumnType) -> Option<PayloadInterface> {
+    Some(match column_type {
+        CsvColumnType::Integer => PayloadInterface::Integer(PayloadVariant::Value(value.parse().ok()?)),
+        CsvColumnType::Float => PayloadInterface::Float(PayloadVariant::Value(value.parse().ok()?)),
+        CsvColumnType::Keyword => PayloadInterface::Keyword(PayloadVariant::Value(value.to_owned())),
+    })
+}
+
+fn convert_record(
+    record: &StringRecord,
+    positions: &ColumnPositions,
+    mapping: &CsvMapping,
+) -> CollectionResult<(PointIdType, Vec<VectorElementType>, Option<Payload>)> {
+    let line = record.position().map_or(0, |position| position.line());
+    let value = |position: usize| record.get(position).unwrap_or("").trim();
+
+    let id = parse_id(value(positions.id)).ok_or_else(|| invalid_value(line, &mapping.id, value(positions.id)))?;
+    let vector = positions.vector.iter()
+        .zip(mapping.vector.iter())
+        .map(|(position, column)| value(*position).parse::<VectorElementType>()
+            .map_err(|_| invalid_value(line, column, value(*position))))
+        .collect::<CollectionResult<_>>()?;
+    let mut payload = Payload::new();
+    for (position, column, column_type) in positions.payload.iter() {
+        let raw_value = value(*position);
+        if raw_value.is_empty() {
+            continue;
+        }
+        let converted = parse_payload(raw_value, *column_type).ok_or_else(|| invalid_value(line, column, raw_value))?;
+        payload.insert(column.clone(), converted);
+    }
+    Ok((id, vector, if payload.is_empty() { None } else { Some(payload) }))
+}
+
+fn csv_error(err: csv::Error) -> CollectionError {
+    CollectionError::BadInput { description: format!("Can't decode CSV, error: {}", err) }
+}
+
+/// Read points from the CSV data with header row and pass them to `insert` in batches of at most `batch_size` points.
+/// Rows are decoded lazily, so memory usage does not depend on the size of the data.
+/// Decoding stops on the first error of decoding or insertion. Returns number of inserted points
+pub fn read_csv(
+    reader: impl Read,
+    mapping: &CsvMapping,
+    batch_size: usize,
+    mut insert: impl FnMut(PointInsertOperations) -> CollectionResult<()>,
+) -> CollectionResult<usize> {
+    let delimiter = mapping.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        return Err(CollectionError::BadInput { description: format!("Delimiter {:?} is not an ASCII character", delimiter) });
+    }
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .from_reader(reader);
+    let positions = ColumnPositions::new(reader.headers().map_err(csv_error)?, mapping)?;
+
+    let mut ids = vec![];
+    let mut vectors = vec![];
+    let mut payloads = vec![];
+    let mut points_count = 0;
+    let mut record = StringRecord::new();
+    loop {
+        let has_record = reader.read_record(&mut record).map_err(csv_error)?;
+        if has_record {
+            let (id, vector, payload) = convert_record(&record, &positions, mapping)?;
+            ids.push(id);
+            vectors.push(vector);
+            payloads.push(payload);
+        }
+        if ids.len() >= batch_size || (!has_record && !ids.is_empty()) {
+            let batch_payloads = std::mem::take(&mut payloads);
+            let has_payload = batch_payloads.iter().any(Option::is_some);
+            points_count += ids.len();
+            insert(PointInsertOperations::BatchPoints {
+                ids: std::mem::take(&mut ids),
+                vectors: std::mem::take(&mut vectors),
+                payloads: if has_payload { Some(batch_payloads) } else { None },
+            })?;
+        }
+        if !has_record {
+            break;
+        }
+    }
+    Ok(points_count)
+}
+
+/// Read points from the CSV file, see `read_csv`
+pub fn read_csv_file(
+    path: &Path,
+    mapping: &CsvMapping,
+    batch_size: usize,
+    insert: impl FnMut(PointInsertOperations) -> CollectionResult<()>,
+) -> CollectionResult<usize> {
+    let file = File::open(path).map_err(|err| CollectionError::BadInput {
+        description: format!("Can't read {:?}, error: {}", path, err)
+    })?;
+    read_csv(file, mapping, batch_size, insert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> CsvMapping {
+        CsvMapping {
+            id: "id".to_owned(),
+            vector: vec!["x".to_owned(), "y".to_owned()],
+            payload: vec![
+                ("city".to_owned(), CsvColumnType::Keyword),
+                ("population".to_owned(), CsvColumnType::Integer),
+            ].into_iter().collect(),
+            delimiter: None,
+        }
+    }
+
+    fn read_all(data: &str, mapping: &CsvMapping, batch_size: usize) -> CollectionResult<Vec<PointInsertOperations>> {
+        let mut batches = vec![];
+        read_csv(data.as_bytes(), mapping, batch_size, |batch| {
+            batches.push(batch);
+            Ok(())
+        })?;
+        Ok(batches)
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let data = "id,city,x,y,population,ignored\n\
+                    1,Berlin,0.5,1,3600000,a\n\
+                    2,,1.5,2,,b\n\
+                    550e8400-e29b-41d4-a716-446655440000,Paris,0,0,2100000,c\n";
+        let batches = read_all(data, &mapping(), 2).unwrap();
+        assert_eq!(batches.len(), 2);
+
+        match &batches[0] {
+            PointInsertOperations::BatchPoints { ids, vectors, payloads } => {
+                assert_eq!(ids, &vec![PointIdType::NumId(1), PointIdType::NumId(2)]);
+                assert_eq!(vectors, &vec![vec![0.5, 1.0], vec![1.5, 2.0]]);
+                let payloads = payloads.as_ref().unwrap();
+                let first = payloads[0].as_ref().unwrap();
+                assert!(matches!(first.get("population"), Some(PayloadInterface::Integer(PayloadVariant::Value(3600000)))));
+                assert!(!first.contains_key("ignored"));
+                assert!(payloads[1].is_none());
+            }
+            _ => panic!("Batch of points expected"),
+        }
+        assert!(matches!(&batches[1], PointInsertOperations::BatchPoints { ids, .. } if matches!(ids[0], PointIdType::Uuid(_))));
+    }
+
+    #[test]
+    fn test_read_csv_errors() {
+        let data = "id,city,x,y,population\n1,Berlin,0.5,one,3600000\n";
+        assert!(read_all(data, &mapping(), 10).is_err());
+
+        let data = "id,city,x,y,population\n1,Berlin,0.5,1,many\n";
+        assert!(read_all(data, &mapping(), 10).is_err());
+
+        let data = "id,city,x,population\n1,Berlin,0.5,3600000\n";
+        assert!(read_all(data, &mapping(), 10).is_err());
+
+        let data = "id;x;y\n-1;0.5;1\n";
+        let mapping = CsvMapping { payload: HashMap::new(), delimiter: Some(';'), ..mapping() };
+        assert!(read_all(data, &mapping, 10).is_err());
+    }
+}
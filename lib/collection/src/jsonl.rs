@@ -0,0 +1,88 @@
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use segment::common::stop_condition::StopCondition;
+
+use crate::collection::{Collection, CollectionError, CollectionResult};
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::point_ops::{PointInsertOperations, PointOperations, PointStruct};
+use crate::operations::types::{ReadConsistency, ScrollRequest};
+
+/// Max number of points, which are kept in memory during import or export
+pub const JSONL_BATCH_SIZE: usize = 256;
+
+/// Write all points of the collection in line-delimited JSON, e.g. to migrate them into another deployment.
+/// Each line contains a single point with vector and payload:
+/// `{"id": 1, "vector": [0.1, 0.2], "payload": {"city": {"type": "keyword", "value": ["Berlin"]}}}`.
+/// Points are written in ascending order of ids.
+/// Points are read page by page, so points, changed during the export, might be written in either state.
+/// Returns number of written points
+pub fn export_jsonl(collection: &Collection, mut writer: impl Write) -> CollectionResult<usize> {
+    let write_error = |err: std::io::Error| CollectionError::ServiceError {
+        error: format!("Can't write exported points, error: {}", err)
+    };
+    let limit = collection.strict_mode().limit_top(JSONL_BATCH_SIZE);
+
+    let mut exported = 0;
+    let mut offset = None;
+    loop {
+        let page = collection.scroll(Arc::new(ScrollRequest {
+            offset,
+            limit,
+            filter: None,
+            with_payload: None,
+            with_vector: true,
+        }), ReadConsistency::Any, &StopCondition::default())?;
+
+        for point in page.points.iter() {
+            serde_json::to_writer(&mut writer, point).map_err(|err| write_error(err.into()))?;
+            writer.write_all(b"\n").map_err(write_error)?;
+        }
+        exported += page.points.len();
+
+        offset = page.next_page_offset;
+        if offset.is_none() {
+            break;
+        }
+    }
+    writer.flush().map_err(write_error)?;
+    Ok(exported)
+}
+
+/// Upsert points, written by `export_jsonl`, in batches. Empty lines are skipped.
+/// Points of the batches, inserted before an invalid line, remain in the collection.
+/// Returns number of imported points
+pub fn import_jsonl(collection: &Collection, reader: impl BufRead) -> CollectionResult<usize> {
+    let batch_size = collection.strict_mode().limit_batch_size(JSONL_BATCH_SIZE);
+    let upsert = |points: Vec<PointStruct>| collection.update(
+        CollectionUpdateOperations::PointOperation(
+            PointOperations::UpsertPoints(PointInsertOperations::PointsList(points))
+        ),
+        true,
+    );
+
+    let mut imported = 0;
+    let mut batch = Vec::with_capacity(batch_size);
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| CollectionError::BadInput {
+            description: format!("Can't read line {}, error: {}", line_idx + 1, err)
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let point: PointStruct = serde_json::from_str(&line).map_err(|err| CollectionError::BadInput {
+            description: format!("Can't parse line {}, error: {}", line_idx + 1, err)
+        })?;
+        batch.push(point);
+
+        if batch.len() >= batch_size {
+            imported += batch.len();
+            upsert(std::mem::replace(&mut batch, Vec::with_capacity(batch_size)))?;
+        }
+    }
+    if !batch.is_empty() {
+        imported += batch.len();
+        upsert(batch)?;
+    }
+    Ok(imported)
+}
@@ -2,5 +2,21 @@ pub mod collection_builder;
 mod update_handler;
 pub mod operations;
 pub mod collection;
+pub mod config;
+pub mod shard;
+pub mod strict_mode;
+pub mod snapshot_manifest;
+pub mod npy_import;
+pub mod parquet_import;
+pub mod csv_import;
+pub mod arrow_import;
+pub mod jsonl;
+pub mod bundle_builder;
+pub mod cold_storage;
+pub mod optimization_pool;
+pub mod numa;
+pub mod async_collection;
+pub mod search_cache;
+pub mod operation_recorder;
 mod segment_manager;
 mod wal;
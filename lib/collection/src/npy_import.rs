@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{File, create_dir_all};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use segment::common::npy::NpyVectors;
+use segment::segment::Segment;
+use segment::segment_constructor::segment_builder::SegmentBuilder;
+use segment::types::{PayloadKeyType, PayloadType, PointIdType, TheMap, VectorElementType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::config::CollectionConfig;
+use crate::operations::payload_ops::PayloadInterface;
+use crate::shard::{ShardId, point_shard, shard_key_shard};
+
+/// Number of points, collected for each shard before they are written into its segment
+const IMPORT_BATCH_SIZE: usize = 1024;
+
+/// Line of the points file, which describes the row of the vectors file with the same number
+#[derive(Deserialize)]
+struct ImportedPoint {
+    id: PointIdType,
+    #[serde(default)]
+    payload: Option<HashMap<PayloadKeyType, PayloadInterface>>,
+}
+
+type ImportedRow = (PointIdType, Vec<VectorElementType>, TheMap<PayloadKeyType, PayloadType>);
+
+/// Segment under construction for a single shard
+struct ShardImport {
+    builder: SegmentBuilder,
+    batch: Vec<ImportedRow>,
+}
+
+impl ShardImport {
+    fn add(&mut self, row: ImportedRow) -> CollectionResult<()> {
+        self.batch.push(row);
+        if self.batch.len() >= IMPORT_BATCH_SIZE {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> CollectionResult<()> {
+        // Imported points get the smallest version, so any further operation is applied to them
+        self.builder.add_points(0, self.batch.drain(..))?;
+        Ok(())
+    }
+}
+
+/// Read points from the vectors file and optional points file and pass them to `add` one by one.
+/// Rows of the vectors file are paired with lines of the points file, which specify id and payload of each point.
+/// Without the points file, row numbers are used as ids. Returns number of read points
+pub fn read_npy_points(
+    vectors_path: &Path,
+    points_path: Option<&Path>,
+    vector_size: usize,
+    mut add: impl FnMut(PointIdType, Vec<VectorElementType>, HashMap<PayloadKeyType, PayloadInterface>) -> CollectionResult<()>,
+) -> CollectionResult<usize> {
+    let vectors = NpyVectors::open(vectors_path)?;
+    if vectors.dim() != vector_size {
+        return Err(CollectionError::BadRequest {
+            description: format!("Vectors of dimension {} expected, {:?} contains {}", vector_size, vectors_path, vectors.dim())
+        });
+    }
+
+    let read_error = |path: &Path, err: std::io::Error| CollectionError::BadInput {
+        description: format!("Can't read {:?}, error: {}", path, err)
+    };
+    let mut points_lines = match points_path {
+        None => None,
+        Some(path) => Some(BufReader::new(File::open(path).map_err(|err| read_error(path, err))?).lines()),
+    };
+
+    for (row, vector) in vectors.iter().enumerate() {
+        let (id, payload) = match (&mut points_lines, points_path) {
+            (Some(lines), Some(path)) => {
+                let line = lines.next()
+                    .ok_or_else(|| CollectionError::BadInput {
+                        description: format!("{:?} has no line for row {} of vectors", path, row)
+                    })?
+                    .map_err(|err| read_error(path, err))?;
+                let point: ImportedPoint = serde_json::from_str(&line).map_err(|err| CollectionError::BadInput {
+                    description: format!("Can't parse line {} of {:?}, error: {}", row + 1, path, err)
+                })?;
+                (point.id, point.payload.unwrap_or_default())
+            }
+            _ => (PointIdType::NumId(row as u64), HashMap::new()),
+        };
+        add(id, vector, payload)?;
+    }
+    Ok(vectors.rows())
+}
+
+/// Read points with `read_npy_points` and build a segment for each shard, which owns them.
+/// Segments are built in `import_path`. Returns number of read points and paths of built segments.
+pub fn build_import_segments(
+    vectors_path: &Path,
+    points_path: Option<&Path>,
+    config: &CollectionConfig,
+    import_path: &Path,
+) -> CollectionResult<(usize, Vec<(ShardId, PathBuf)>)> {
+    let create_error = |err: std::io::Error| CollectionError::BadInput {
+        description: format!("Can't create directory in {:?}, error: {}", import_path, err)
+    };
+    let segments_path = import_path.join("segments");
+    let temp_path = import_path.join("temp");
+    create_dir_all(&segments_path).map_err(create_error)?;
+    create_dir_all(&temp_path).map_err(create_error)?;
+
+    let mut shard_imports: Vec<Option<ShardImport>> = (0..config.shard_number).map(|_| None).collect();
+    let points_count = read_npy_points(vectors_path, points_path, config.params.vector_size, |id, vector, payload| {
+        config.strict_mode.check_payload(&payload)?;
+
+        let shard_id = match &config.shard_key {
+            None => point_shard(&id, config.shard_number),
+            Some(shard_key) => payload.get(shard_key)
+                .and_then(|value| value.shard_key_value())
+                .map(|value| shard_key_shard(value, config.shard_number))
+                .ok_or_else(|| CollectionError::BadInput {
+                    description: format!("Point {} has no single keyword or integer value of the shard key {}", id, shard_key)
+                })?,
+        };
+
+        let shard_import = &mut shard_imports[shard_id as usize];
+        if shard_import.is_none() {
+            *shard_import = Some(ShardImport {
+                builder: SegmentBuilder::new(&segments_path, &temp_path, &config.params)?,
+                batch: Vec::with_capacity(IMPORT_BATCH_SIZE),
+            });
+        }
+        let payload = payload.iter()
+            .map(|(key, value)| (key.clone(), value.to_payload()))
+            .collect();
+        shard_import.as_mut().unwrap().add((id, vector, payload))
+    })?;
+
+    let mut segments = vec![];
+    for (shard_id, shard_import) in shard_imports.into_iter().enumerate() {
+        if let Some(mut shard_import) = shard_import {
+            shard_import.flush_batch()?;
+            let segment: Segment = shard_import.builder.try_into()?;
+            segments.push((shard_id as ShardId, segment.current_path.clone()));
+        }
+    }
+    Ok((points_count, segments))
+}
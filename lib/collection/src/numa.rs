@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use tracing::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::runtime;
+use tokio::runtime::Runtime;
+
+use segment::common::numa::{NumaNode, bind_current_thread, numa_nodes};
+
+use crate::segment_manager::holders::segment_holder::SegmentId;
+
+/// Placement of segments on nodes of a NUMA machine
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NumaPolicy {
+    /// Segments are searched by any search thread, memory is placed by the OS
+    Disabled,
+    /// Segments of each shard are assigned to nodes in turn.
+    /// Vectors of a segment are placed in memory of its node and scored by threads, which run on CPUs of the node
+    RoundRobin,
+}
+
+impl Default for NumaPolicy {
+    fn default() -> Self {
+        NumaPolicy::Disabled
+    }
+}
+
+struct NodeRuntime {
+    node: NumaNode,
+    runtime: Arc<Runtime>,
+}
+
+/// Search runtimes of NUMA nodes, which are used for scoring of segments, placed on the nodes.
+/// Placement is disabled on machines with a single node
+pub struct NumaPlacement {
+    nodes: Vec<NodeRuntime>,
+}
+
+impl NumaPlacement {
+    pub fn disabled() -> Self {
+        NumaPlacement { nodes: vec![] }
+    }
+
+    /// Create runtimes for all nodes. Threads of each runtime are bound to CPUs of its node.
+    /// If `max_threads` is 0, each node gets a thread per CPU, otherwise threads are split between nodes equally
+    pub fn new(policy: NumaPolicy, max_threads: usize) -> Self {
+        if policy == NumaPolicy::Disabled {
+            return Self::disabled();
+        }
+        let nodes = numa_nodes();
+        if nodes.len() < 2 {
+            info!(nodes = nodes.len(), "NUMA placement is not used, machine has less than 2 nodes");
+            return Self::disabled();
+        }
+        let nodes_count = nodes.len();
+        let nodes = nodes.into_iter()
+            .map(|node| {
+                let threads = if max_threads == 0 { node.cpus.len() } else { (max_threads / nodes_count).max(1) };
+                let thread_node = node.clone();
+                let runtime = runtime::Builder::new_multi_thread()
+                    .max_threads(threads)
+                    .thread_name(format!("search-node-{}", node.id))
+                    .on_thread_start(move || {
+                        if let Err(err) = bind_current_thread(&thread_node) {
+                            warn!(numa_node = thread_node.id, error = %err, "Can't bind search thread to NUMA node");
+                        }
+                    })
+                    .build()
+                    .unwrap();
+                NodeRuntime { node, runtime: Arc::new(runtime) }
+            })
+            .collect();
+        NumaPlacement { nodes }
+    }
+
+    /// Node of the segment and runtime, which scores it, or `None` if placement is disabled
+    pub fn segment_node(&self, segment_id: SegmentId) -> Option<(usize, &Arc<Runtime>)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let node_runtime = &self.nodes[segment_id % self.nodes.len()];
+        Some((node_runtime.node.id, &node_runtime.runtime))
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use segment::common::stop_condition::StopCondition;
+use segment::types::ScoredPoint;
+
+use crate::collection::{Collection, CollectionError, CollectionResult};
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::types::{ReadConsistency, SearchRequest};
+
+/// Scores of the replayed search are compared with this precision, as they might be computed by another kernel
+const SCORE_EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum RecordedOperation {
+    Update {
+        operation: CollectionUpdateOperations,
+        /// Error of the update, if it failed
+        error: Option<String>,
+    },
+    Search {
+        request: SearchRequest,
+        result: Vec<ScoredPoint>,
+        /// Error of the search, if it failed
+        error: Option<String>,
+    },
+}
+
+/// Line of the recording
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct RecordEntry {
+    /// Number of the operation in the recording, starts from 0
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch, when the operation was completed
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub operation: RecordedOperation,
+}
+
+struct RecorderState {
+    writer: BufWriter<File>,
+    seq: u64,
+}
+
+/// Writes each update and search of the collection with its result into JSON lines, so the issue of the running
+/// service could be reproduced by `replay_recording` into an empty collection with the same parameters.
+/// Recorded operations are performed one at a time and each update is waited for, so the recorded order
+/// is the order, in which updates are applied and searches observe them, even with several update workers.
+/// Recording is a debugging tool, it slows down all operations of the collection
+pub struct OperationRecorder {
+    state: Mutex<RecorderState>,
+    /// Held while the recorded operation is performed
+    order_lock: Mutex<()>,
+}
+
+impl OperationRecorder {
+    /// Start recording into the file. Operations are appended to the existing recording
+    pub fn open(path: &Path) -> CollectionResult<Self> {
+        let open_error = |err: std::io::Error| CollectionError::ServiceError {
+            error: format!("Can't open recording {:?}, error: {}", path, err)
+        };
+        let seq = if path.exists() {
+            read_recording(std::io::BufReader::new(File::open(path).map_err(open_error)?))?
+                .last()
+                .map_or(0, |entry| entry.seq + 1)
+        } else {
+            0
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(open_error)?;
+        Ok(OperationRecorder {
+            state: Mutex::new(RecorderState { writer: BufWriter::new(file), seq }),
+            order_lock: Mutex::new(()),
+        })
+    }
+
+    /// Failure of the recording is only logged, so it does not affect the operation itself
+    fn record(&self, operation: RecordedOperation) {
+        let mut state = self.state.lock();
+        let entry = RecordEntry {
+            seq: state.seq,
+            timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            operation,
+        };
+        let written = serde_json::to_writer(&mut state.writer, &entry)
+            .map_err(std::io::Error::from)
+            .and_then(|_| state.writer.write_all(b"\n"))
+            // Flushed on each operation, so the recording is complete if the service crashes
+            .and_then(|_| state.writer.flush());
+        match written {
+            Ok(()) => state.seq += 1,
+            Err(err) => warn!(seq = entry.seq, error = %err, "Can't record operation"),
+        }
+    }
+
+    /// Perform the update with `apply` and record it. `apply` should wait for the update to be applied
+    pub fn apply_update<T>(
+        &self,
+        operation: CollectionUpdateOperations,
+        apply: impl FnOnce(CollectionUpdateOperations) -> CollectionResult<T>,
+    ) -> CollectionResult<T> {
+        let _order_guard = self.order_lock.lock();
+        let recorded_operation = operation.clone();
+        let result = apply(operation);
+        self.record(RecordedOperation::Update {
+            operation: recorded_operation,
+            error: result.as_ref().err().map(|err| err.to_string()),
+        });
+        result
+    }
+
+    /// Perform searches of the requests with `search` and record each of them.
+    /// Searches, served from the search cache, are recorded the same way
+    pub fn apply_searches(
+        &self,
+        requests: &[Arc<SearchRequest>],
+        search: impl FnOnce() -> CollectionResult<Vec<Vec<ScoredPoint>>>,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        let _order_guard = self.order_lock.lock();
+        let results = search();
+        for (idx, request) in requests.iter().enumerate() {
+            let (result, error) = match &results {
+                Ok(results) => (results.get(idx).cloned().unwrap_or_default(), None),
+                Err(err) => (vec![], Some(err.to_string())),
+            };
+            self.record(RecordedOperation::Search { request: request.as_ref().clone(), result, error });
+        }
+        results
+    }
+}
+
+/// Read all entries of the recording
+pub fn read_recording(reader: impl BufRead) -> CollectionResult<Vec<RecordEntry>> {
+    let mut entries = vec![];
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| CollectionError::BadInput {
+            description: format!("Can't read line {} of the recording, error: {}", line_idx + 1, err)
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line).map_err(|err| CollectionError::BadInput {
+            description: format!("Can't parse line {} of the recording, error: {}", line_idx + 1, err)
+        })?);
+    }
+    Ok(entries)
+}
+
+/// Difference between the recorded and the replayed operation
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct ReplayMismatch {
+    pub seq: u64,
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct ReplayReport {
+    pub updates: usize,
+    pub searches: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+fn compare_results(expected: &[ScoredPoint], actual: &[ScoredPoint]) -> Option<String> {
+    if expected.len() != actual.len() {
+        return Some(format!("expected {} points, found {}", expected.len(), actual.len()));
+    }
+    expected.iter().zip(actual).enumerate()
+        .find(|(_, (expected, actual))| expected.id != actual.id || (expected.score - actual.score).abs() > SCORE_EPSILON)
+        .map(|(idx, (expected, actual))| format!(
+            "expected point {} with score {} at position {}, found point {} with score {}",
+            expected.id, expected.score, idx, actual.id, actual.score,
+        ))
+}
+
+fn compare_errors(expected: &Option<String>, actual: Option<String>) -> Option<String> {
+    match (expected, actual) {
+        (None, Some(actual)) => Some(format!("expected success, found error: {}", actual)),
+        (Some(expected), None) => Some(format!("expected error: {}, found success", expected)),
+        _ => None,
+    }
+}
+
+/// Apply recorded updates to the collection in their order and repeat recorded searches after them,
+/// reporting searches, which return other points, and operations, which succeed or fail unlike recorded ones.
+/// Each update is waited for, so the state of the collection is the same for each search as during the recording.
+/// Collection should be empty and have the same parameters as the recorded one
+pub fn replay_recording(collection: &Collection, entries: impl IntoIterator<Item=RecordEntry>) -> CollectionResult<ReplayReport> {
+    let mut report = ReplayReport::default();
+    for entry in entries {
+        let mismatch = match entry.operation {
+            RecordedOperation::Update { operation, error } => {
+                report.updates += 1;
+                let result = collection.update(operation, true);
+                compare_errors(&error, result.err().map(|err| err.to_string()))
+            }
+            RecordedOperation::Search { request, result: expected, error } => {
+                report.searches += 1;
+                match collection.search(Arc::new(request), ReadConsistency::Any, &StopCondition::default()) {
+                    Ok(actual) => compare_errors(&error, None).or_else(|| compare_results(&expected, &actual)),
+                    Err(err) => compare_errors(&error, Some(err.to_string())),
+                }
+            }
+        };
+        if let Some(description) = mismatch {
+            report.mismatches.push(ReplayMismatch { seq: entry.seq, description });
+        }
+    }
+    Ok(report)
+}
@@ -4,8 +4,12 @@ pub mod payload_ops;
 
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
+use std::collections::HashMap;
+use segment::types::{PayloadKeyType, PointIdType, SeqNumberType};
+use crate::collection::CollectionResult;
+use crate::shard::{ShardId, broadcast};
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum FieldIndexOperations {
     /// Create index for payload field
@@ -14,13 +18,83 @@ pub enum FieldIndexOperations {
     DeleteIndex(String),
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(untagged)]
 pub enum CollectionUpdateOperations {
     PointOperation(point_ops::PointOperations),
     PayloadOperation(payload_ops::PayloadOps),
-    FieldIndexOperation(FieldIndexOperations)
+    FieldIndexOperation(FieldIndexOperations),
+    /// Only produced by the service itself, see `Collection::update_unchanged_since`
+    #[schemars(skip)]
+    VersionedPointOperation(VersionedPointOperation),
+}
+
+/// Point operation of a shard, which might have been applied already, e.g. a message delivered again after a restart.
+/// It is applied as the operation `version` rather than under its own id, so points changed after `version`
+/// are skipped by the version check of segments, and a repeated operation does not overwrite newer changes.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct VersionedPointOperation {
+    pub version: SeqNumberType,
+    pub operation: point_ops::PointOperations,
+}
+
+impl CollectionUpdateOperations {
+    /// Ids of points, affected by the operation. Empty for operations, which are not bound to specific points
+    pub fn point_ids(&self) -> Vec<PointIdType> {
+        match self {
+            CollectionUpdateOperations::PointOperation(operation) => operation.point_ids(),
+            CollectionUpdateOperations::PayloadOperation(operation) => operation.point_ids(),
+            CollectionUpdateOperations::FieldIndexOperation(_) => vec![],
+            CollectionUpdateOperations::VersionedPointOperation(versioned) => versioned.operation.point_ids(),
+        }
+    }
+
+    /// Shards of upserted points in collection, sharded by the payload field `shard_key`.
+    /// None for other operations, as they do not change the shard key of points.
+    pub fn shard_key_placement(
+        &self,
+        shard_key: &PayloadKeyType,
+        shard_number: usize,
+    ) -> CollectionResult<Option<HashMap<PointIdType, ShardId>>> {
+        match self {
+            CollectionUpdateOperations::PointOperation(point_ops::PointOperations::UpsertPoints(insert)) =>
+                Ok(Some(insert.shard_key_placement(shard_key, shard_number)?)),
+            CollectionUpdateOperations::PayloadOperation(operation) => {
+                operation.validate_shard_key(shard_key)?;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Split operation into parts, each of which only affects points of a single shard, defined by `shard_of`.
+    /// Operations, which are not bound to specific points, are sent to all shards.
+    pub fn split_by_shard(
+        self,
+        shard_number: usize,
+        shard_of: &dyn Fn(&PointIdType) -> ShardId,
+    ) -> Vec<(ShardId, CollectionUpdateOperations)> {
+        match self {
+            CollectionUpdateOperations::PointOperation(operation) => operation.split_by_shard(shard_number, shard_of)
+                .into_iter()
+                .map(|(shard_id, operation)| (shard_id, CollectionUpdateOperations::PointOperation(operation)))
+                .collect(),
+            CollectionUpdateOperations::PayloadOperation(operation) => operation.split_by_shard(shard_number, shard_of)
+                .into_iter()
+                .map(|(shard_id, operation)| (shard_id, CollectionUpdateOperations::PayloadOperation(operation)))
+                .collect(),
+            operation @ CollectionUpdateOperations::FieldIndexOperation(_) => broadcast(operation, shard_number),
+            CollectionUpdateOperations::VersionedPointOperation(VersionedPointOperation { version, operation }) =>
+                operation.split_by_shard(shard_number, shard_of)
+                    .into_iter()
+                    .map(|(shard_id, operation)| (shard_id, CollectionUpdateOperations::VersionedPointOperation(
+                        VersionedPointOperation { version, operation }
+                    )))
+                    .collect(),
+        }
+    }
 }
 
 
@@ -33,7 +107,7 @@ mod tests {
     fn test_deserialize() {
         let op = CollectionUpdateOperations::PayloadOperation(
             payload_ops::PayloadOps::ClearPayload {
-                points: vec![1, 2, 3],
+                points: vec![1, 2, 3].into_iter().map(|x| x.into()).collect(),
             }
         );
 
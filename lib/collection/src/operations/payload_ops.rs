@@ -3,11 +3,13 @@
 use serde;
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
-use segment::types::{PointIdType, PayloadKeyType, PayloadType, GeoPoint};
+use segment::types::{PointIdType, PayloadKeyType, PayloadType, GeoPoint, PayloadSchemaType};
 use std::collections::HashMap;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::shard::{ShardId, ShardKeyValue, split_by_shard, broadcast};
 
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(untagged)]
 pub enum PayloadVariant<T> {
@@ -24,7 +26,7 @@ impl<T: Clone> PayloadVariant<T> {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type",  content = "value")]
 pub enum PayloadInterface {
@@ -43,11 +45,22 @@ impl PayloadInterface {
             PayloadInterface::Geo(x) => PayloadType::Geo(x.to_list()),
         }
     }
+
+    /// Only a single keyword or integer value could define the shard of the point
+    pub fn shard_key_value(&self) -> Option<ShardKeyValue> {
+        match self {
+            PayloadInterface::Keyword(PayloadVariant::Value(keyword)) => Some(ShardKeyValue::Keyword(keyword)),
+            PayloadInterface::Keyword(PayloadVariant::List(keywords)) if keywords.len() == 1 => Some(ShardKeyValue::Keyword(&keywords[0])),
+            PayloadInterface::Integer(PayloadVariant::Value(value)) => Some(ShardKeyValue::Integer(*value)),
+            PayloadInterface::Integer(PayloadVariant::List(values)) if values.len() == 1 => Some(ShardKeyValue::Integer(values[0])),
+            _ => None,
+        }
+    }
 }
 
 
 /// Define operations description for point payloads manipulation
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum PayloadOps {
     /// Set payload value, overrides if it is already exists
@@ -65,6 +78,66 @@ pub enum PayloadOps {
     /// Drops all Payload values associated with given points.
     ClearPayload {
         points: Vec<PointIdType>,
+    },
+    /// Renames payload key and/or converts its values into another type for all points of the collection.
+    /// Key can't be renamed into an existing one. Points are migrated in the background:
+    /// payload is returned migrated right away, but filters and field index of the new key
+    /// reflect the migration only once it is finished.
+    MigrateKey {
+        key: PayloadKeyType,
+        /// New name of the key. Key is not renamed if not specified
+        rename_to: Option<PayloadKeyType>,
+        /// Type to convert values into. Values are not converted if not specified
+        convert_to: Option<PayloadSchemaType>,
+    },
+}
+
+impl PayloadOps {
+    /// Points are not moved between shards by payload changes, so the shard key could only be changed by upsert
+    pub fn validate_shard_key(&self, shard_key: &PayloadKeyType) -> CollectionResult<()> {
+        let changes_shard_key = match self {
+            PayloadOps::SetPayload { payload, .. } => payload.contains_key(shard_key),
+            PayloadOps::MigrateKey { key, rename_to, .. } => key == shard_key || rename_to.as_ref() == Some(shard_key),
+            PayloadOps::DeletePayload { .. } | PayloadOps::ClearPayload { .. } => false,
+        };
+        if changes_shard_key {
+            return Err(CollectionError::BadInput {
+                description: format!("Shard key {} could not be changed, upsert points with the new value instead", shard_key)
+            });
+        }
+        Ok(())
+    }
+
+    pub fn point_ids(&self) -> Vec<PointIdType> {
+        match self {
+            PayloadOps::SetPayload { points, .. }
+            | PayloadOps::DeletePayload { points, .. }
+            | PayloadOps::ClearPayload { points } => points.clone(),
+            PayloadOps::MigrateKey { .. } => vec![],
+        }
+    }
+
+    /// Split operation into parts, each of which only affects points of a single shard
+    pub fn split_by_shard(
+        self,
+        shard_number: usize,
+        shard_of: &dyn Fn(&PointIdType) -> ShardId,
+    ) -> Vec<(ShardId, PayloadOps)> {
+        match self {
+            PayloadOps::SetPayload { payload, points } => split_by_shard(points, |id| *id, shard_of)
+                .into_iter()
+                .map(|(shard_id, points)| (shard_id, PayloadOps::SetPayload { payload: payload.clone(), points }))
+                .collect(),
+            PayloadOps::DeletePayload { keys, points } => split_by_shard(points, |id| *id, shard_of)
+                .into_iter()
+                .map(|(shard_id, points)| (shard_id, PayloadOps::DeletePayload { keys: keys.clone(), points }))
+                .collect(),
+            PayloadOps::ClearPayload { points } => split_by_shard(points, |id| *id, shard_of)
+                .into_iter()
+                .map(|(shard_id, points)| (shard_id, PayloadOps::ClearPayload { points }))
+                .collect(),
+            operation @ PayloadOps::MigrateKey { .. } => broadcast(operation, shard_number),
+        }
     }
 }
 
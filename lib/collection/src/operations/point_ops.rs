@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
-use segment::types::{PointIdType, PayloadKeyType};
+use segment::types::{PointIdType, PayloadKeyType, Filter};
 use crate::operations::types::VectorType;
 use std::collections::HashMap;
 use crate::operations::payload_ops::PayloadInterface;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::shard::{ShardId, shard_key_shard, split_by_shard, broadcast};
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct PointStruct {
     /// Point id
@@ -16,8 +18,17 @@ pub struct PointStruct {
     pub payload: Option<HashMap<PayloadKeyType, PayloadInterface>>,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct PointVectors {
+    /// Point id
+    pub id: PointIdType,
+    /// New vector of the point
+    pub vector: VectorType,
+}
+
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum PointInsertOperations {
     #[serde(rename = "batch")]
@@ -33,13 +44,149 @@ pub enum PointInsertOperations {
 }
 
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum PointOperations {
     /// Insert or update points
     UpsertPoints(PointInsertOperations),
+    /// Replace vectors of existing points, keeping their payload
+    UpdateVectors {
+        points: Vec<PointVectors>,
+    },
     /// Delete point if exists
     DeletePoints {
         ids: Vec<PointIdType>,
     },
+    /// Delete all points, which satisfy the filter
+    DeletePointsByFilter {
+        filter: Filter,
+    },
+}
+
+impl PointInsertOperations {
+    pub fn point_ids(&self) -> Vec<PointIdType> {
+        match self {
+            PointInsertOperations::BatchPoints { ids, .. } => ids.clone(),
+            PointInsertOperations::PointsList(points) => points.iter().map(|point| point.id).collect(),
+        }
+    }
+
+    /// Inserted points as a list. Batch with different number of ids, vectors and payloads is rejected
+    pub fn into_points(self) -> CollectionResult<Vec<PointStruct>> {
+        match self {
+            PointInsertOperations::BatchPoints { ids, vectors, payloads } => {
+                let is_consistent = ids.len() == vectors.len()
+                    && payloads.as_ref().map_or(true, |payloads| payloads.len() == ids.len());
+                if !is_consistent {
+                    return Err(CollectionError::BadInput {
+                        description: "Number of ids, vectors and payloads of the batch should be the same".to_string()
+                    });
+                }
+                let mut payloads = payloads.unwrap_or_default().into_iter();
+                Ok(ids.into_iter()
+                    .zip(vectors)
+                    .map(|(id, vector)| PointStruct { id, vector, payload: payloads.next().flatten() })
+                    .collect())
+            }
+            PointInsertOperations::PointsList(points) => Ok(points),
+        }
+    }
+
+    /// Shards of inserted points, defined by values of the shard key in their payload
+    pub fn shard_key_placement(
+        &self,
+        shard_key: &PayloadKeyType,
+        shard_number: usize,
+    ) -> CollectionResult<HashMap<PointIdType, ShardId>> {
+        let points: Vec<(PointIdType, Option<&HashMap<PayloadKeyType, PayloadInterface>>)> = match self {
+            PointInsertOperations::BatchPoints { ids, payloads, .. } => ids.iter()
+                .enumerate()
+                .map(|(idx, id)| (*id, payloads.as_ref().and_then(|payloads| payloads.get(idx)?.as_ref())))
+                .collect(),
+            PointInsertOperations::PointsList(points) => points.iter()
+                .map(|point| (point.id, point.payload.as_ref()))
+                .collect(),
+        };
+        points.into_iter()
+            .map(|(id, payload)| {
+                match payload.and_then(|payload| payload.get(shard_key)?.shard_key_value()) {
+                    Some(value) => Ok((id, shard_key_shard(value, shard_number))),
+                    None => Err(CollectionError::BadInput {
+                        description: format!("Point {} should have a single keyword or integer value of shard key {}", id, shard_key)
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    pub fn split_by_shard(self, shard_of: &dyn Fn(&PointIdType) -> ShardId) -> Vec<(ShardId, PointInsertOperations)> {
+        match self {
+            PointInsertOperations::BatchPoints { ids, vectors, payloads } => {
+                let is_consistent = ids.len() == vectors.len()
+                    && payloads.as_ref().map_or(true, |payloads| payloads.len() == ids.len());
+                if !is_consistent {
+                    // Not split, so the whole batch is rejected by the shard
+                    return vec![(0, PointInsertOperations::BatchPoints { ids, vectors, payloads })];
+                }
+                let points = match payloads {
+                    None => ids.into_iter().zip(vectors).map(|(id, vector)| (id, vector, None)).collect(),
+                    Some(payloads) => ids.into_iter().zip(vectors).zip(payloads)
+                        .map(|((id, vector), payload)| (id, vector, payload))
+                        .collect(),
+                };
+                split_by_shard(points, |(id, _, _)| *id, shard_of)
+                    .into_iter()
+                    .map(|(shard_id, points)| {
+                        let mut ids = Vec::with_capacity(points.len());
+                        let mut vectors = Vec::with_capacity(points.len());
+                        let mut payloads = Vec::with_capacity(points.len());
+                        for (id, vector, payload) in points {
+                            ids.push(id);
+                            vectors.push(vector);
+                            payloads.push(payload);
+                        }
+                        (shard_id, PointInsertOperations::BatchPoints { ids, vectors, payloads: Some(payloads) })
+                    })
+                    .collect()
+            }
+            PointInsertOperations::PointsList(points) => split_by_shard(points, |point| point.id, shard_of)
+                .into_iter()
+                .map(|(shard_id, points)| (shard_id, PointInsertOperations::PointsList(points)))
+                .collect(),
+        }
+    }
+}
+
+impl PointOperations {
+    pub fn point_ids(&self) -> Vec<PointIdType> {
+        match self {
+            PointOperations::UpsertPoints(insert) => insert.point_ids(),
+            PointOperations::UpdateVectors { points } => points.iter().map(|point| point.id).collect(),
+            PointOperations::DeletePoints { ids } => ids.clone(),
+            PointOperations::DeletePointsByFilter { .. } => vec![],
+        }
+    }
+
+    /// Split operation into parts, each of which only affects points of a single shard
+    pub fn split_by_shard(
+        self,
+        shard_number: usize,
+        shard_of: &dyn Fn(&PointIdType) -> ShardId,
+    ) -> Vec<(ShardId, PointOperations)> {
+        match self {
+            PointOperations::UpsertPoints(insert) => insert.split_by_shard(shard_of)
+                .into_iter()
+                .map(|(shard_id, insert)| (shard_id, PointOperations::UpsertPoints(insert)))
+                .collect(),
+            PointOperations::UpdateVectors { points } => split_by_shard(points, |point| point.id, shard_of)
+                .into_iter()
+                .map(|(shard_id, points)| (shard_id, PointOperations::UpdateVectors { points }))
+                .collect(),
+            PointOperations::DeletePoints { ids } => split_by_shard(ids, |id| *id, shard_of)
+                .into_iter()
+                .map(|(shard_id, ids)| (shard_id, PointOperations::DeletePoints { ids }))
+                .collect(),
+            operation @ PointOperations::DeletePointsByFilter { .. } => broadcast(operation, shard_number),
+        }
+    }
 }
@@ -1,4 +1,9 @@
-use segment::types::{VectorElementType, PointIdType, TheMap, PayloadKeyType, PayloadType, SeqNumberType, Filter, SearchParams, SegmentConfig};
+use segment::types::{VectorElementType, PointIdType, TheMap, PayloadKeyType, PayloadType, SeqNumberType, Filter, SearchParams, ScoredPoint, SegmentMemoryUsage, WithPayloadInterface};
+use crate::config::CollectionConfig;
+use crate::collection_builder::optimizers_builder::OptimizersConfig;
+use crate::shard::{ReplicaId, ShardId};
+use crate::shard::replica_set::{ReplicaState, ShardStatus};
+use std::collections::{BTreeMap, HashMap};
 use serde;
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
@@ -6,6 +11,9 @@ use schemars::{JsonSchema};
 /// Type of vector in API
 pub type VectorType = Vec<VectorElementType>;
 
+/// Max allowed search offset. Deep pagination requires to select all skipped results in each segment
+pub const MAX_SEARCH_OFFSET: usize = 10_000;
+
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -30,8 +38,87 @@ pub struct CollectionInfo {
     pub disk_data_size: usize,
     /// RAM used by collection
     pub ram_data_size: usize,
+    /// RAM used by each component of segments of the collection
+    pub memory_usage: SegmentMemoryUsage,
     /// Collection settings
-    pub config: SegmentConfig,
+    pub config: CollectionConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// All background processes work as expected
+    Ok,
+    /// Some of background processes are failing, data might be not indexed or not persisted
+    Degraded,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+/// State of background processes of the collection
+pub struct CollectionHealth {
+    pub status: HealthStatus,
+    /// Failures of optimizations and flushes, dead replicas
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationStatus {
+    /// Optimization is performed at the moment
+    Running,
+    /// Optimization waits for a free optimization thread or for other optimizations of the same segments
+    Pending,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Optimization of segments of a single shard, performed in background
+pub struct OptimizationInfo {
+    /// Shard of the optimized segments
+    pub shard_id: ShardId,
+    /// Name of the optimizer, which performs the optimization
+    pub optimizer: String,
+    /// Number of segments, which are re-built by the optimization
+    pub segments_count: usize,
+    pub status: OptimizationStatus,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Background optimizations of the collection segments
+pub struct OptimizationsInfo {
+    /// Optimizers parameters, which are currently used by the collection
+    pub config: OptimizersConfig,
+    /// Running optimizations, followed by pending ones
+    pub optimizations: Vec<OptimizationInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+/// State and resource usage of a single shard, measured by its primary replica
+pub struct ShardTelemetry {
+    pub shard_id: ShardId,
+    pub status: ShardStatus,
+    /// Replica, which assigns ids to operations of the shard
+    pub primary: ReplicaId,
+    pub replicas: BTreeMap<ReplicaId, ReplicaState>,
+    pub vectors_count: usize,
+    pub segments_count: usize,
+    pub disk_data_size: usize,
+    pub ram_data_size: usize,
+    pub memory_usage: SegmentMemoryUsage,
+    /// Updates of the primary replica, which are written to WAL, but not applied to segments yet
+    pub pending_updates: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+/// State of shards and background optimizations of the collection
+pub struct CollectionTelemetry {
+    pub shards: Vec<ShardTelemetry>,
+    /// Running optimizations, followed by pending ones
+    pub optimizations: Vec<OptimizationInfo>,
 }
 
 
@@ -55,7 +142,7 @@ pub struct UpdateResult {
 }
 
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 /// Search request
 pub struct SearchRequest {
@@ -65,8 +152,227 @@ pub struct SearchRequest {
     pub filter: Option<Filter>,
     /// Additional search params
     pub params: Option<SearchParams>,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
     /// Max number of result to return
     pub top: usize,
+    /// Number of best results to skip. Allows to request next pages of the result.
+    /// Should not exceed `MAX_SEARCH_OFFSET`, as `top + offset` results are selected internally
+    #[serde(default)]
+    pub offset: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Several search requests, executed at once
+pub struct SearchRequestBatch {
+    /// Search requests. Results are returned in the same order
+    pub searches: Vec<SearchRequest>,
+    /// Filter, applied to all searches which do not specify their own filter
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    /// Search params, applied to all searches which do not specify their own params
+    #[serde(default)]
+    pub params: Option<SearchParams>,
+}
+
+impl SearchRequestBatch {
+    /// Search requests with shared filter and params applied
+    pub fn resolve(&self) -> Vec<SearchRequest> {
+        self.searches.iter()
+            .map(|search| SearchRequest {
+                filter: search.filter.clone().or_else(|| self.filter.clone()),
+                params: search.params.or(self.params),
+                ..search.clone()
+            })
+            .collect()
+    }
+}
+
+/// Method of merging results of several searches into a single ranked list
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fusion {
+    /// Reciprocal Rank Fusion: points are scored by their positions in results, scores are ignored
+    Rrf,
+    /// Scores of each search are scaled into `[0, 1]` range and summed up
+    ScoreNormalization,
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Rrf
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Several searches, which results are fused into one ranked list
+pub struct FusionSearchRequest {
+    /// Searches to fuse. Each search returns at least `top + offset` candidates
+    pub searches: Vec<SearchRequest>,
+    /// How to fuse results of searches. Default: `rrf`
+    #[serde(default)]
+    pub fusion: Fusion,
+    /// Filter, applied to all searches which do not specify their own filter
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    /// Search params, applied to all searches which do not specify their own params
+    #[serde(default)]
+    pub params: Option<SearchParams>,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
+    /// Max number of result to return
+    pub top: usize,
+    /// Number of best results to skip
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/// Expression, which computes the final score of a point from its similarity score and payload
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreFormula {
+    /// Similarity score of the point
+    Score,
+    /// Constant value
+    Constant(f64),
+    /// First numeric value of the payload field. Points without numeric value of the field get 0
+    Field(PayloadKeyType),
+    /// Sum of expressions
+    Sum(Vec<ScoreFormula>),
+    /// Product of expressions
+    Mult(Vec<ScoreFormula>),
+    /// Exponential decay of the distance between `x` and `target`: 1 at the target, 0.5 at the `scale` from it.
+    /// E.g. recency of a timestamp field
+    ExpDecay {
+        x: Box<ScoreFormula>,
+        target: f64,
+        scale: f64,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Search request, which results are re-ranked by a formula of the score and payload
+pub struct FormulaSearchRequest {
+    /// Look for vectors closest to this
+    pub vector: Vec<VectorElementType>,
+    /// Look only for points which satisfies this conditions
+    pub filter: Option<Filter>,
+    /// Additional search params
+    pub params: Option<SearchParams>,
+    /// Final score of found points, e.g. `{"sum": ["score", {"mult": [{"constant": 0.2}, {"field": "popularity"}]}]}`
+    pub formula: ScoreFormula,
+    /// Number of the closest points, which are re-ranked by the formula. Default: `top + offset`
+    #[serde(default)]
+    pub candidates: Option<usize>,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
+    /// Max number of result to return
+    pub top: usize,
+    /// Number of best results to skip
+    #[serde(default)]
+    pub offset: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Bulk import of points from files, located in the import directory of the service
+pub struct NpyImportRequest {
+    /// Path of `.npy` file, or `.npz` file with a single stored array, which contains 2-D array of vectors.
+    /// Relative to the import directory
+    pub vectors_path: String,
+    /// Path of JSON lines file with `{"id": ..., "payload": {...}}` for each row of vectors.
+    /// Relative to the import directory. Default: row numbers are used as ids, without payload
+    #[serde(default)]
+    pub points_path: Option<String>,
+}
+
+/// Columns of tabular data, e.g. parquet file or Arrow record batch, which define points
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct PointColumns {
+    /// Column with ids of points: non-negative integers or UUID strings
+    pub id: String,
+    /// Column with vectors: lists of floats
+    pub vector: String,
+    /// Columns, stored as payload fields with the same names. Default: all other columns
+    #[serde(default)]
+    pub payload: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Bulk import of points from a parquet file, located in the import directory of the service
+pub struct ParquetImportRequest {
+    /// Path of the parquet file, relative to the import directory
+    pub path: String,
+    pub columns: PointColumns,
+}
+
+/// Type, which values of a CSV column are converted into
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvColumnType {
+    Integer,
+    Float,
+    Keyword,
+}
+
+/// Mapping of CSV columns into points. Columns are referenced by names from the header row
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct CsvMapping {
+    /// Column with ids of points: non-negative integers or UUID strings
+    pub id: String,
+    /// Columns with components of vectors, in the order of dimensions
+    pub vector: Vec<String>,
+    /// Columns, stored as payload fields with the same names, and types of their values.
+    /// Empty values are skipped, other columns are ignored
+    #[serde(default)]
+    pub payload: HashMap<String, CsvColumnType>,
+    /// Field delimiter. Default: `,`
+    #[serde(default)]
+    pub delimiter: Option<char>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Bulk import of points from a CSV file with header row, located in the import directory of the service
+pub struct CsvImportRequest {
+    /// Path of the CSV file, relative to the import directory
+    pub path: String,
+    pub mapping: CsvMapping,
+}
+
+/// How positive and negative examples are combined into recommendation scores
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendStrategy {
+    /// Search with a single vector: average of positive examples, shifted away from the average of negative ones
+    AverageVector,
+    /// Search near each positive example separately and score candidates by the closest example.
+    /// Candidates closer to some negative example than to any positive one are ranked last
+    BestScore,
+}
+
+impl Default for RecommendStrategy {
+    fn default() -> Self {
+        RecommendStrategy::AverageVector
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -81,8 +387,186 @@ pub struct RecommendRequest {
     pub filter: Option<Filter>,
     /// Additional search params
     pub params: Option<SearchParams>,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
     /// Max number of result to return
     pub top: usize,
+    /// Number of best results to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// How to use positive and negative examples. Default: `average_vector`
+    #[serde(default)]
+    pub strategy: RecommendStrategy,
+    /// Name of the collection, where vectors of examples are taken from. It should have the same vector size.
+    /// Default: the same collection
+    #[serde(default)]
+    pub lookup_from: Option<String>,
+}
+
+
+
+/// Pair of example points, which prefers points closer to the positive example than to the negative one
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub struct ContextExamplePair {
+    pub positive: PointIdType,
+    pub negative: PointIdType,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Discovery request: search for points similar to the target within the space constrained by context pairs
+pub struct DiscoverRequest {
+    /// Look for vectors closest to this point. If not specified - points are scored by context only
+    pub target: Option<PointIdType>,
+    /// Pairs of examples, which define preferred part of the space
+    #[serde(default)]
+    pub context: Vec<ContextExamplePair>,
+    /// Look only for points which satisfies this conditions
+    pub filter: Option<Filter>,
+    /// Additional search params
+    pub params: Option<SearchParams>,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
+    /// Max number of result to return
+    pub top: usize,
+    /// Number of best results to skip
+    #[serde(default)]
+    pub offset: usize,
+}
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Search request with results grouped by payload field
+pub struct SearchGroupsRequest {
+    /// Look for vectors closest to this
+    pub vector: Vec<VectorElementType>,
+    /// Look only for points which satisfies this conditions
+    pub filter: Option<Filter>,
+    /// Additional search params
+    pub params: Option<SearchParams>,
+    /// Payload field to group by. Must be a keyword or integer field.
+    /// Points without this field are not included in result
+    pub group_by: PayloadKeyType,
+    /// Max number of points to return in each group
+    pub group_size: usize,
+    /// Payload of the found points to return. Default: no payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the found points. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
+    /// Max number of groups to return
+    pub limit: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+/// Value of the payload field, which identifies the group
+pub enum GroupId {
+    Keyword(String),
+    Integer(i64),
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PointGroup {
+    /// Value of the `group_by` field, shared by all points of the group
+    pub id: GroupId,
+    /// Closest points of the group, ordered by score
+    pub hits: Vec<ScoredPoint>,
+}
+
+/// Number of replicas of each shard, which should serve a read request.
+/// If several replicas are read, result of the most up-to-date one is returned.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// Any active replica. Fastest option, but a lagging replica might return stale results
+    Any,
+    /// Majority of the replicas of the shard
+    Majority,
+    /// All replicas of the shard. Fails, if some of them are not available
+    All,
+}
+
+impl Default for ReadConsistency {
+    fn default() -> Self {
+        ReadConsistency::Any
+    }
+}
+
+impl ReadConsistency {
+    /// Number of replicas, which should respond, out of `replica_number` replicas of the shard
+    pub fn required_replicas(&self, replica_number: usize) -> usize {
+        match self {
+            ReadConsistency::Any => 1,
+            ReadConsistency::Majority => replica_number / 2 + 1,
+            ReadConsistency::All => replica_number,
+        }
+    }
+}
+
+fn default_exact_count() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Count request
+pub struct CountRequest {
+    /// Look only for points which satisfies this conditions
+    pub filter: Option<Filter>,
+    /// If true - count each point once, even if it is temporarily stored in several segments.
+    /// If false - return approximate number of points, estimated by payload index without points iteration
+    #[serde(default = "default_exact_count")]
+    pub exact: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CountResult {
+    /// Number of points, which satisfy the conditions
+    pub count: usize,
+}
+
+fn default_scroll_limit() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Scroll request - paginate over all points which matches given condition
+pub struct ScrollRequest {
+    /// Start ID to read points from. Default: start from the lowest id
+    pub offset: Option<PointIdType>,
+    /// Page size. Default: 10
+    #[serde(default = "default_scroll_limit")]
+    pub limit: usize,
+    /// Look only for points which satisfies this conditions. If not provided - all points.
+    pub filter: Option<Filter>,
+    /// Payload of the points to return. Default: all payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return point vector with the result. Default: false
+    #[serde(default)]
+    pub with_vector: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+/// Result of the points read request
+pub struct ScrollResult {
+    /// List of retrieved points, ordered by id
+    pub points: Vec<Record>,
+    /// Offset which should be used to retrieve a next page result
+    pub next_page_offset: Option<PointIdType>,
+}
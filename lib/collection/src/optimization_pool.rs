@@ -0,0 +1,112 @@
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crossbeam_channel::{Receiver, bounded};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Size and load of a thread pool
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct PoolTelemetry {
+    pub threads: usize,
+    /// Number of jobs, which wait for a free thread
+    pub queued: usize,
+    /// Number of jobs, which are performed at the moment
+    pub running: usize,
+}
+
+/// Bounded pool of threads, shared by optimizers of all shards, including building of indexes.
+/// Optimizations never run on threads of the search runtime, so they can't take more threads than the pool has.
+pub struct OptimizationPool {
+    pool: ThreadPool,
+    queued: Arc<AtomicUsize>,
+    running: Arc<AtomicUsize>,
+}
+
+impl OptimizationPool {
+    pub fn new(threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .thread_name(|idx| format!("optimizer-{}", idx))
+            .build()
+            .expect("Can't create optimization pool");
+        OptimizationPool {
+            pool,
+            queued: Default::default(),
+            running: Default::default(),
+        }
+    }
+
+    /// Perform the job on a free thread of the pool. The result is sent into the returned channel,
+    /// panic of the job is returned as an error instead of terminating the pool
+    pub fn spawn<R, F>(&self, job: F) -> Receiver<thread::Result<R>>
+        where R: Send + 'static,
+              F: FnOnce() -> R + Send + 'static
+    {
+        let (sender, receiver) = bounded(1);
+        let queued = self.queued.clone();
+        let running = self.running.clone();
+        queued.fetch_add(1, Ordering::SeqCst);
+        self.pool.spawn(move || {
+            queued.fetch_sub(1, Ordering::SeqCst);
+            running.fetch_add(1, Ordering::SeqCst);
+            let result = catch_unwind(AssertUnwindSafe(job));
+            running.fetch_sub(1, Ordering::SeqCst);
+            // Waiting side might be gone already, which is fine
+            let _ = sender.send(result);
+        });
+        receiver
+    }
+
+    pub fn telemetry(&self) -> PoolTelemetry {
+        PoolTelemetry {
+            threads: self.pool.current_num_threads(),
+            queued: self.queued.load(Ordering::SeqCst),
+            running: self.running.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optimization_pool() {
+        let pool = OptimizationPool::new(2);
+        let (release_sender, release_receiver) = bounded::<()>(0);
+
+        let blocked: Vec<_> = (0..3)
+            .map(|idx| {
+                let release_receiver = release_receiver.clone();
+                pool.spawn(move || {
+                    release_receiver.recv().unwrap();
+                    idx
+                })
+            })
+            .collect();
+
+        // Third job waits for one of the threads
+        while pool.telemetry().running < 2 {
+            thread::yield_now();
+        }
+        let telemetry = pool.telemetry();
+        assert_eq!(telemetry.threads, 2);
+        assert_eq!(telemetry.queued, 1);
+
+        for _ in 0..3 {
+            release_sender.send(()).unwrap();
+        }
+        let results: Vec<usize> = blocked.into_iter().map(|result| result.recv().unwrap().unwrap()).collect();
+        assert_eq!(results, vec![0, 1, 2]);
+
+        let panicked = pool.spawn(|| panic!("Failed optimization"));
+        assert!(panicked.recv().unwrap().is_err());
+        assert_eq!(pool.spawn(|| 42).recv().unwrap().unwrap(), 42);
+        assert_eq!(pool.telemetry().running, 0);
+    }
+}
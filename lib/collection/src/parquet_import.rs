@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crossbeam_channel::{Sender, bounded};
+use parquet::errors::ParquetError;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::{Field, Row};
+
+use segment::types::{PayloadKeyType, PointIdType, VectorElementType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::operations::payload_ops::{PayloadInterface, PayloadVariant};
+use crate::operations::point_ops::PointInsertOperations;
+use crate::operations::types::PointColumns;
+
+/// Max number of rows, inserted with a single operation
+pub const PARQUET_BATCH_SIZE: usize = 1024;
+
+/// Max number of threads, which decode row groups of the file
+const DECODING_THREADS: usize = 4;
+
+/// Number of decoded batches, waiting to be inserted. Limits the memory, if decoding is faster than insertion
+const PENDING_BATCHES: usize = 2 * DECODING_THREADS;
+
+type Payload = HashMap<PayloadKeyType, PayloadInterface>;
+
+fn open_file(path: &Path) -> CollectionResult<SerializedFileReader<File>> {
+    let file = File::open(path).map_err(|err| CollectionError::BadInput {
+        description: format!("Can't read {:?}, error: {}", path, err)
+    })?;
+    SerializedFileReader::new(file).map_err(|err| parquet_error(path, err))
+}
+
+fn parquet_error(path: &Path, err: ParquetError) -> CollectionError {
+    CollectionError::BadInput { description: format!("Can't decode {:?}, error: {}", path, err) }
+}
+
+fn unsupported_value(column: &str, field: &Field) -> CollectionError {
+    CollectionError::BadInput { description: format!("Unsupported value {} in column {}", field, column) }
+}
+
+enum ScalarValue {
+    Integer(i64),
+    Float(f64),
+    Keyword(String),
+}
+
+fn scalar_value(field: &Field) -> Option<ScalarValue> {
+    match field {
+        Field::Byte(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::Short(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::Int(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::Long(value) => Some(ScalarValue::Integer(*value)),
+        Field::UByte(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::UShort(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::UInt(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::ULong(value) => Some(ScalarValue::Integer(*value as i64)),
+        Field::Float(value) => Some(ScalarValue::Float(*value as f64)),
+        Field::Double(value) => Some(ScalarValue::Float(*value)),
+        Field::Str(value) => Some(ScalarValue::Keyword(value.clone())),
+        _ => None,
+    }
+}
+
+/// Ids are either non-negative integers or UUID strings
+fn field_id(column: &str, field: &Field) -> CollectionResult<PointIdType> {
+    match scalar_value(field) {
+        Some(ScalarValue::Integer(id)) if id >= 0 => Ok(PointIdType::NumId(id as u64)),
+        Some(ScalarValue::Keyword(id)) => id.parse().map_err(|_| unsupported_value(column, field)),
+        _ => Err(unsupported_value(column, field)),
+    }
+}
+
+/// Vectors are lists of floats, e.g. fixed-size lists, written by arrow
+fn field_vector(column: &str, field: &Field) -> CollectionResult<Vec<VectorElementType>> {
+    match field {
+        Field::ListInternal(list) => list.elements().iter()
+            .map(|element| match scalar_value(element) {
+                Some(ScalarValue::Float(value)) => Ok(value as VectorElementType),
+                Some(ScalarValue::Integer(value)) => Ok(value as VectorElementType),
+                _ => Err(unsupported_value(column, field)),
+            })
+            .collect(),
+        _ => Err(unsupported_value(column, field)),
+    }
+}
+
+/// Scalars and lists of integers, floats or strings are converted into payload of the same type.
+/// Lists, which mix integers and floats, are converted into floats. Nulls and empty lists are skipped.
+fn field_payload(column: &str, field: &Field) -> CollectionResult<Option<PayloadInterface>> {
+    let values: Vec<ScalarValue> = match field {
+        Field::Null => return Ok(None),
+        Field::ListInternal(list) => list.elements().iter()
+            .map(|element| scalar_value(element).ok_or_else(|| unsupported_value(column, field)))
+            .collect::<CollectionResult<_>>()?,
+        _ => vec![scalar_value(field).ok_or_else(|| unsupported_value(column, field))?],
+    };
+    let is_list = matches!(field, Field::ListInternal(_));
+
+    fn variant<T>(mut values: Vec<T>, is_list: bool) -> PayloadVariant<T> {
+        if is_list { PayloadVariant::List(values) } else { PayloadVariant::Value(values.remove(0)) }
+    }
+
+    if values.is_empty() {
+        return Ok(None);
+    }
+    if values.iter().all(|value| matches!(value, ScalarValue::Integer(_))) {
+        let integers = values.into_iter()
+            .filter_map(|value| match value { ScalarValue::Integer(value) => Some(value), _ => None })
+            .collect();
+        return Ok(Some(PayloadInterface::Integer(variant(integers, is_list))));
+    }
+    if values.iter().all(|value| !matches!(value, ScalarValue::Keyword(_))) {
+        let floats = values.into_iter()
+            .filter_map(|value| match value {
+                ScalarValue::Integer(value) => Some(value as f64),
+                ScalarValue::Float(value) => Some(value),
+                ScalarValue::Keyword(_) => None,
+            })
+            .collect();
+        return Ok(Some(PayloadInterface::Float(variant(floats, is_list))));
+    }
+    if values.iter().all(|value| matches!(value, ScalarValue::Keyword(_))) {
+        let keywords = values.into_iter()
+            .filter_map(|value| match value { ScalarValue::Keyword(value) => Some(value), _ => None })
+            .collect();
+        return Ok(Some(PayloadInterface::Keyword(variant(keywords, is_list))));
+    }
+    Err(unsupported_value(column, field))
+}
+
+fn convert_row(row: &Row, columns: &PointColumns) -> CollectionResult<(PointIdType, Vec<VectorElementType>, Option<Payload>)> {
+    let mut id = None;
+    let mut vector = None;
+    let mut payload = Payload::new();
+    for (column, field) in row.get_column_iter() {
+        if column == &columns.id {
+            id = Some(field_id(column, field)?);
+        } else if column == &columns.vector {
+            vector = Some(field_vector(column, field)?);
+        } else if columns.payload.as_ref().map_or(true, |payload_columns| payload_columns.contains(column)) {
+            if let Some(value) = field_payload(column, field)? {
+                payload.insert(column.clone(), value);
+            }
+        }
+    }
+    let missing_column = |column: &str| CollectionError::BadInput {
+        description: format!("Column {} is missing or null", column)
+    };
+    Ok((
+        id.ok_or_else(|| missing_column(&columns.id))?,
+        vector.ok_or_else(|| missing_column(&columns.vector))?,
+        if payload.is_empty() { None } else { Some(payload) },
+    ))
+}
+
+#[derive(Default)]
+struct Batch {
+    ids: Vec<PointIdType>,
+    vectors: Vec<Vec<VectorElementType>>,
+    payloads: Vec<Option<Payload>>,
+}
+
+impl Batch {
+    fn into_operation(self) -> PointInsertOperations {
+        let has_payload = self.payloads.iter().any(Option::is_some);
+        PointInsertOperations::BatchPoints {
+            ids: self.ids,
+            vectors: self.vectors,
+            payloads: if has_payload { Some(self.payloads) } else { None },
+        }
+    }
+}
+
+/// Decode every `step`-th row group, starting from `first_row_group`, and send its rows in batches.
+/// Decoding stops, once batches are no longer received
+fn decode_row_groups(
+    path: &Path,
+    columns: &PointColumns,
+    batch_size: usize,
+    first_row_group: usize,
+    step: usize,
+    sender: &Sender<CollectionResult<PointInsertOperations>>,
+) -> CollectionResult<()> {
+    let reader = open_file(path)?;
+    let mut batch = Batch::default();
+    for row_group_idx in (first_row_group..reader.num_row_groups()).step_by(step) {
+        let row_group = reader.get_row_group(row_group_idx).map_err(|err| parquet_error(path, err))?;
+        for row in row_group.get_row_iter(None).map_err(|err| parquet_error(path, err))? {
+            let (id, vector, payload) = convert_row(&row, columns)?;
+            batch.ids.push(id);
+            batch.vectors.push(vector);
+            batch.payloads.push(payload);
+            if batch.ids.len() >= batch_size {
+                let full_batch = std::mem::take(&mut batch);
+                if sender.send(Ok(full_batch.into_operation())).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    if !batch.ids.is_empty() {
+        sender.send(Ok(batch.into_operation())).ok();
+    }
+    Ok(())
+}
+
+/// Read points from the parquet file and pass them to `insert` in batches of at most `batch_size` points.
+/// Row groups are decoded in parallel, so batches of different row groups are passed in arbitrary order.
+/// Decoding stops on the first error of decoding or insertion. Returns number of inserted points
+pub fn read_parquet(
+    path: &Path,
+    columns: &PointColumns,
+    batch_size: usize,
+    mut insert: impl FnMut(PointInsertOperations) -> CollectionResult<()>,
+) -> CollectionResult<usize> {
+    let row_groups = open_file(path)?.metadata().num_row_groups();
+    let threads = DECODING_THREADS.min(row_groups);
+
+    let (sender, receiver) = bounded(PENDING_BATCHES);
+    let workers: Vec<_> = (0..threads)
+        .map(|worker| {
+            let sender = sender.clone();
+            let path: PathBuf = path.to_owned();
+            let columns = columns.clone();
+            thread::Builder::new()
+                .name(format!("parquet-decoder-{}", worker))
+                .spawn(move || {
+                    if let Err(err) = decode_row_groups(&path, &columns, batch_size, worker, threads, &sender) {
+                        sender.send(Err(err)).ok();
+                    }
+                })
+                .unwrap()
+        })
+        .collect();
+    drop(sender);
+
+    let mut result = Ok(0);
+    for batch in receiver.iter() {
+        let inserted = batch.and_then(|operation| {
+            let points_count = operation.point_ids().len();
+            insert(operation)?;
+            Ok(points_count)
+        });
+        match inserted {
+            Ok(points_count) => result = result.map(|total| total + points_count),
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+    // Remaining workers stop, once they fail to send the next batch
+    drop(receiver);
+    for worker in workers {
+        worker.join().map_err(|_| CollectionError::ServiceError {
+            error: format!("Parquet decoding thread panicked")
+        })?;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_payload() {
+        let payload = |field: Field| field_payload("column", &field).unwrap();
+
+        assert!(matches!(payload(Field::Int(5)), Some(PayloadInterface::Integer(PayloadVariant::Value(5)))));
+        assert!(matches!(
+            payload(Field::Double(0.5)),
+            Some(PayloadInterface::Float(PayloadVariant::Value(value))) if value == 0.5
+        ));
+        assert!(matches!(
+            payload(Field::Str("Berlin".to_owned())),
+            Some(PayloadInterface::Keyword(PayloadVariant::Value(value))) if value == "Berlin"
+        ));
+        assert!(payload(Field::Null).is_none());
+        assert!(field_payload("column", &Field::Bytes(vec![1, 2].into())).is_err());
+    }
+
+    #[test]
+    fn test_field_id() {
+        assert_eq!(field_id("id", &Field::Long(7)).unwrap(), PointIdType::NumId(7));
+        assert!(field_id("id", &Field::Long(-1)).is_err());
+        assert!(matches!(
+            field_id("id", &Field::Str("550e8400-e29b-41d4-a716-446655440000".to_owned())).unwrap(),
+            PointIdType::Uuid(_)
+        ));
+        assert!(field_vector("vector", &Field::Float(1.0)).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use segment::types::{payload_memory_usage, ScoredPoint, VectorElementType};
+
+use crate::collection::Collection;
+use crate::operations::types::{ReadConsistency, SearchRequest};
+use crate::strict_mode::StrictModeConfig;
+
+/// Hash of the serialized value, so values with floats, e.g. query vectors, could be compared
+fn value_hash<T: Serialize>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(value).expect("Search request is serializable").hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Estimated memory, occupied by the search result
+fn result_memory_usage(result: &[ScoredPoint]) -> usize {
+    result.iter()
+        .map(|point| size_of::<ScoredPoint>()
+            + point.payload.as_ref().map(payload_memory_usage).unwrap_or(0)
+            + point.vector.as_ref().map(|vector| vector.len() * size_of::<VectorElementType>()).unwrap_or(0))
+        .sum()
+}
+
+/// Identifies repeated search requests to the same collection
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchCacheKey {
+    /// Name or alias, which the request is addressed to
+    collection: String,
+    query_hash: u64,
+    filter_hash: u64,
+    /// Hash of all other parameters, which affect the result.
+    /// Limits of the collection are included, so requests, which violate changed limits, are checked again
+    params_hash: u64,
+}
+
+impl SearchCacheKey {
+    pub fn new(collection: &str, request: &SearchRequest, consistency: ReadConsistency, strict_mode: &StrictModeConfig) -> Self {
+        SearchCacheKey {
+            collection: collection.to_string(),
+            query_hash: value_hash(&request.vector),
+            filter_hash: value_hash(&request.filter),
+            params_hash: value_hash(&(
+                &request.params,
+                &request.with_payload,
+                request.with_vector,
+                request.top,
+                request.offset,
+                consistency,
+                strict_mode,
+            )),
+        }
+    }
+}
+
+struct CachedResult {
+    /// Collection, which produced the result. It is compared by identity, so results of a deleted
+    /// or replaced collection with the same name are not returned. Weak reference keeps the identity unique
+    collection: Weak<Collection>,
+    /// Data version of the collection, which the result corresponds to
+    data_version: u64,
+    result: Vec<ScoredPoint>,
+    memory_usage: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchCacheTelemetry {
+    /// Max number of cached results
+    pub capacity: usize,
+    /// Number of cached results, including outdated ones
+    pub size: usize,
+    pub hits: usize,
+    pub misses: usize,
+    /// Estimated memory, occupied by cached results
+    pub memory_usage_bytes: usize,
+    /// Estimated memory of cached results by the collection name or alias, which they are addressed to
+    pub collections_memory_usage: BTreeMap<String, usize>,
+}
+
+/// LRU cache of search results, which is shared by all collections.
+/// Result is returned for the same request until the data version of the collection changes,
+/// so repeated requests, e.g. of dashboards, are not searched again while the collection is not updated
+pub struct SearchCache {
+    capacity: usize,
+    results: Mutex<LruCache<SearchCacheKey, CachedResult>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl SearchCache {
+    pub fn new(capacity: usize) -> Self {
+        SearchCache {
+            capacity,
+            results: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Cached result of the request, if it is produced by the `collection` at the given data version.
+    /// Outdated result is removed
+    pub fn get(&self, key: &SearchCacheKey, collection: &Arc<Collection>, data_version: u64) -> Option<Vec<ScoredPoint>> {
+        let mut results = self.results.lock();
+        let cached = match results.get_mut(key) {
+            Some(cached) => cached,
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+        if cached.data_version == data_version && Weak::ptr_eq(&cached.collection, &Arc::downgrade(collection)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(cached.result.clone());
+        }
+        results.remove(key);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Data version should be taken before the search, so changes made during the search invalidate the result
+    pub fn insert(&self, key: SearchCacheKey, collection: &Arc<Collection>, data_version: u64, result: &Vec<ScoredPoint>) {
+        self.results.lock().insert(key, CachedResult {
+            collection: Arc::downgrade(collection),
+            data_version,
+            result: result.clone(),
+            memory_usage: result_memory_usage(result),
+        });
+    }
+
+    pub fn telemetry(&self) -> SearchCacheTelemetry {
+        let results = self.results.lock();
+        let mut collections_memory_usage = BTreeMap::new();
+        for (key, cached) in results.iter() {
+            *collections_memory_usage.entry(key.collection.clone()).or_insert(0) += cached.memory_usage;
+        }
+        SearchCacheTelemetry {
+            capacity: self.capacity,
+            size: results.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            memory_usage_bytes: collections_memory_usage.values().sum(),
+            collections_memory_usage,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::cmp::Ordering;
+
+use segment::types::{PayloadKeyType, PayloadType, ScoredPoint, ScoreType, TheMap};
+
+use crate::operations::types::ScoreFormula;
+
+/// Numeric value of the payload field, used by the formula
+fn field_value(payload: Option<&TheMap<PayloadKeyType, PayloadType>>, key: &PayloadKeyType) -> f64 {
+    match payload.and_then(|payload| payload.get(key)) {
+        Some(PayloadType::Integer(values)) => values.first().map_or(0.0, |value| *value as f64),
+        Some(PayloadType::Float(values)) => values.first().cloned().unwrap_or(0.0),
+        _ => 0.0,
+    }
+}
+
+impl ScoreFormula {
+    pub fn evaluate(&self, point: &ScoredPoint) -> f64 {
+        match self {
+            ScoreFormula::Score => point.score as f64,
+            ScoreFormula::Constant(value) => *value,
+            ScoreFormula::Field(key) => field_value(point.payload.as_ref(), key),
+            ScoreFormula::Sum(formulas) => formulas.iter().map(|formula| formula.evaluate(point)).sum(),
+            ScoreFormula::Mult(formulas) => formulas.iter().map(|formula| formula.evaluate(point)).product(),
+            ScoreFormula::ExpDecay { x, target, scale } => {
+                let distance = (x.evaluate(point) - target).abs();
+                (0.5f64).powf(distance / scale)
+            }
+        }
+    }
+
+    /// Whether the formula reads payload, so candidates should be searched with it
+    pub fn uses_payload(&self) -> bool {
+        match self {
+            ScoreFormula::Score | ScoreFormula::Constant(_) => false,
+            ScoreFormula::Field(_) => true,
+            ScoreFormula::Sum(formulas) | ScoreFormula::Mult(formulas) =>
+                formulas.iter().any(|formula| formula.uses_payload()),
+            ScoreFormula::ExpDecay { x, .. } => x.uses_payload(),
+        }
+    }
+}
+
+/// Replaces scores of points with the value of the formula and orders them from the best to the worst
+pub fn rescore(points: Vec<ScoredPoint>, formula: &ScoreFormula) -> Vec<ScoredPoint> {
+    let mut rescored: Vec<ScoredPoint> = points.into_iter()
+        .map(|point| ScoredPoint { score: formula.evaluate(&point) as ScoreType, ..point })
+        .collect();
+    rescored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    rescored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(id: u64, score: ScoreType, popularity: Option<PayloadType>) -> ScoredPoint {
+        let payload = popularity.map(|value| {
+            let mut payload = TheMap::new();
+            payload.insert("popularity".to_owned(), value);
+            payload
+        });
+        ScoredPoint { id: id.into(), version: 0, score, payload, vector: None }
+    }
+
+    #[test]
+    fn test_weighted_sum() {
+        // score * 0.8 + popularity * 0.2
+        let formula: ScoreFormula = serde_json::from_str(r#"{"sum": [
+            {"mult": ["score", {"constant": 0.8}]},
+            {"mult": [{"field": "popularity"}, {"constant": 0.2}]}
+        ]}"#).unwrap();
+        assert!(formula.uses_payload());
+
+        let rescored = rescore(vec![
+            point(1, 0.9, Some(PayloadType::Integer(vec![0]))),
+            point(2, 0.5, Some(PayloadType::Float(vec![3.0]))),
+            point(3, 0.8, Some(PayloadType::Keyword(vec!["a".to_owned()]))),
+            point(4, 0.7, None),
+        ], &formula);
+
+        let ids: Vec<_> = rescored.iter().map(|point| point.id).collect();
+        assert_eq!(ids, vec![2.into(), 1.into(), 3.into(), 4.into()]);
+        assert!((rescored[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_exp_decay() {
+        let formula = ScoreFormula::ExpDecay {
+            x: Box::new(ScoreFormula::Field("popularity".to_owned())),
+            target: 10.0,
+            scale: 5.0,
+        };
+        assert!((formula.evaluate(&point(1, 0.0, Some(PayloadType::Integer(vec![10])))) - 1.0).abs() < 1e-9);
+        assert!((formula.evaluate(&point(1, 0.0, Some(PayloadType::Integer(vec![5])))) - 0.5).abs() < 1e-9);
+        assert!((formula.evaluate(&point(1, 0.0, Some(PayloadType::Integer(vec![20])))) - 0.25).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use segment::types::{PointIdType, ScoredPoint, ScoreType};
+
+use crate::operations::types::Fusion;
+
+/// Constant of Reciprocal Rank Fusion, which reduces influence of the very top ranks
+const RRF_K: ScoreType = 60.0;
+
+/// Merges several ranked lists of points into one.
+/// Each input list is expected to be ordered from the best to the worst point.
+/// Payload and vector of the fused point are taken from its first occurrence.
+pub fn fuse(results: Vec<Vec<ScoredPoint>>, fusion: Fusion) -> Vec<ScoredPoint> {
+    let mut points: HashMap<PointIdType, ScoredPoint> = HashMap::new();
+
+    for result in results {
+        let scores = match fusion {
+            Fusion::Rrf => rrf_scores(&result),
+            Fusion::ScoreNormalization => normalized_scores(&result),
+        };
+        for (point, score) in result.into_iter().zip(scores) {
+            points.entry(point.id)
+                .or_insert_with(|| ScoredPoint { score: 0.0, ..point })
+                .score += score;
+        }
+    }
+
+    let mut fused: Vec<ScoredPoint> = points.into_iter().map(|(_, point)| point).collect();
+    fused.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    fused
+}
+
+/// Score depends only on the position of the point: `1 / (k + rank)`
+fn rrf_scores(result: &[ScoredPoint]) -> Vec<ScoreType> {
+    (0..result.len())
+        .map(|rank| 1.0 / (RRF_K + rank as ScoreType + 1.0))
+        .collect()
+}
+
+/// Scores are linearly scaled into `[0, 1]` range, so results of different searches are comparable
+fn normalized_scores(result: &[ScoredPoint]) -> Vec<ScoreType> {
+    let min = result.iter().map(|point| point.score).fold(ScoreType::INFINITY, ScoreType::min);
+    let max = result.iter().map(|point| point.score).fold(ScoreType::NEG_INFINITY, ScoreType::max);
+    let range = max - min;
+    result.iter()
+        .map(|point| if range > 0.0 { (point.score - min) / range } else { 1.0 })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(ids_scores: &[(u64, ScoreType)]) -> Vec<ScoredPoint> {
+        ids_scores.iter()
+            .map(|(id, score)| ScoredPoint {
+                id: (*id).into(),
+                version: 0,
+                score: *score,
+                payload: None,
+                vector: None,
+            })
+            .collect()
+    }
+
+    fn ids(result: &[ScoredPoint]) -> Vec<PointIdType> {
+        result.iter().map(|point| point.id).collect()
+    }
+
+    #[test]
+    fn test_rrf() {
+        let fused = fuse(vec![
+            scored(&[(1, 0.9), (2, 0.8), (3, 0.7)]),
+            scored(&[(2, 100.0), (4, 50.0), (5, 10.0)]),
+        ], Fusion::Rrf);
+
+        assert_eq!(fused.len(), 5);
+        // Scores of different searches are ignored, only ranks matter
+        assert_eq!(ids(&fused)[..2], [2.into(), 1.into()]);
+        assert!((fused[0].score - (1.0 / 61.0 + 1.0 / 62.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_score_normalization() {
+        let fused = fuse(vec![
+            scored(&[(1, 0.9), (2, 0.5), (3, 0.1)]),
+            scored(&[(3, 100.0), (1, 90.0), (4, 0.0)]),
+        ], Fusion::ScoreNormalization);
+
+        assert_eq!(fused[0].id, 1.into());
+        assert!((fused[0].score - 1.9).abs() < 1e-6);
+        assert_eq!(fused[1].id, 3.into());
+        assert_eq!(fused.last().unwrap().id, 4.into());
+    }
+}
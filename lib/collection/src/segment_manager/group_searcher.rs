@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use segment::common::stop_condition::StopCondition;
+use segment::types::{PayloadType, PointIdType, ScoredPoint, WithPayload, PayloadSelector};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::operations::types::{GroupId, PointGroup, SearchGroupsRequest, SearchRequest};
+use crate::segment_manager::segment_managers::SegmentSearcher;
+
+/// Max number of times search is repeated with increased `top`, if there are not enough full groups
+const MAX_GROUP_SEARCH_ITERATIONS: usize = 5;
+
+fn group_ids(value: &PayloadType) -> Vec<GroupId> {
+    match value {
+        PayloadType::Keyword(keywords) => keywords.iter().cloned().map(GroupId::Keyword).collect(),
+        PayloadType::Integer(numbers) => numbers.iter().cloned().map(GroupId::Integer).collect(),
+        // Floats and geo points are not suitable for exact grouping
+        _ => vec![]
+    }
+}
+
+/// Collects best scored points into a limited number of groups with limited size.
+/// Expects points to be added from the best to the worst score.
+struct GroupsAggregator {
+    limit: usize,
+    group_size: usize,
+    groups: HashMap<GroupId, Vec<ScoredPoint>>,
+    /// Groups in order of their best hit
+    order: Vec<GroupId>,
+}
+
+impl GroupsAggregator {
+    fn new(limit: usize, group_size: usize) -> Self {
+        GroupsAggregator {
+            limit,
+            group_size,
+            groups: HashMap::new(),
+            order: vec![],
+        }
+    }
+
+    /// Point with multiple values of the group field is added into each of the groups
+    fn add(&mut self, point: ScoredPoint, group_ids: Vec<GroupId>) {
+        for group_id in group_ids {
+            match self.groups.get_mut(&group_id) {
+                Some(hits) => if hits.len() < self.group_size {
+                    hits.push(point.clone());
+                },
+                None => if self.groups.len() < self.limit {
+                    self.order.push(group_id.clone());
+                    self.groups.insert(group_id, vec![point.clone()]);
+                }
+            }
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.groups.len() >= self.limit
+            && self.groups.values().all(|hits| hits.len() >= self.group_size)
+    }
+
+    fn into_groups(mut self) -> Vec<PointGroup> {
+        let groups = &mut self.groups;
+        self.order
+            .into_iter()
+            .map(|id| {
+                let hits = groups.remove(&id).unwrap_or_default();
+                PointGroup { id, hits }
+            })
+            .collect()
+    }
+}
+
+/// Search for the closest points and group them by the value of `group_by` payload field.
+/// Search is repeated with increased number of candidates until all groups are filled,
+/// so a single group with many close points can't take the whole result.
+pub fn search_groups(
+    searcher: &(dyn SegmentSearcher + Sync + Send),
+    request: Arc<SearchGroupsRequest>,
+    stop: &StopCondition,
+) -> CollectionResult<Vec<PointGroup>> {
+    if request.limit == 0 || request.group_size == 0 {
+        return Err(CollectionError::BadRequest {
+            description: format!("Both `limit` and `group_size` should be positive")
+        });
+    }
+
+    let mut payloads: HashMap<PointIdType, Vec<GroupId>> = HashMap::new();
+    let mut top = request.limit * request.group_size;
+    let mut aggregator = GroupsAggregator::new(request.limit, request.group_size);
+    let group_payload = WithPayload {
+        enable: true,
+        payload_selector: Some(PayloadSelector::Include(vec![request.group_by.clone()])),
+    };
+
+    for _ in 0..MAX_GROUP_SEARCH_ITERATIONS {
+        let search_request = Arc::new(SearchRequest {
+            vector: request.vector.clone(),
+            filter: request.filter.clone(),
+            params: request.params.clone(),
+            with_payload: request.with_payload.clone(),
+            with_vector: request.with_vector,
+            top,
+            offset: 0,
+        });
+        let points = searcher.search(search_request, stop)?;
+
+        let new_ids = points.iter()
+            .map(|point| point.id)
+            .filter(|id| !payloads.contains_key(id))
+            .collect();
+        for record in searcher.retrieve(&new_ids, &group_payload, false)? {
+            let ids = record.payload
+                .and_then(|mut payload| payload.remove(&request.group_by))
+                .map(|value| group_ids(&value))
+                .unwrap_or_default();
+            payloads.insert(record.id, ids);
+        }
+
+        // Search results of the bigger `top` include previous ones, so the groups are collected from scratch
+        aggregator = GroupsAggregator::new(request.limit, request.group_size);
+        for point in points.iter() {
+            let ids = payloads.get(&point.id).cloned().unwrap_or_default();
+            aggregator.add(point.clone(), ids);
+        }
+
+        let is_exhausted = points.len() < top;
+        if is_exhausted || aggregator.is_full() {
+            break;
+        }
+        top *= 2;
+    }
+
+    Ok(aggregator.into_groups())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+    use crate::segment_manager::fixtures::build_searcher;
+
+    #[test]
+    fn test_groups_aggregator() {
+        let mut aggregator = GroupsAggregator::new(2, 2);
+        let red = GroupId::Keyword("red".to_owned());
+        let blue = GroupId::Keyword("blue".to_owned());
+        let green = GroupId::Keyword("green".to_owned());
+
+        aggregator.add(ScoredPoint { id: 1.into(), version: 0, score: 0.9, payload: None, vector: None }, vec![red.clone()]);
+        aggregator.add(ScoredPoint { id: 2.into(), version: 0, score: 0.8, payload: None, vector: None }, vec![red.clone(), blue.clone()]);
+        aggregator.add(ScoredPoint { id: 3.into(), version: 0, score: 0.7, payload: None, vector: None }, vec![red.clone()]);
+        aggregator.add(ScoredPoint { id: 4.into(), version: 0, score: 0.6, payload: None, vector: None }, vec![green.clone()]);
+        assert!(!aggregator.is_full());
+        aggregator.add(ScoredPoint { id: 5.into(), version: 0, score: 0.5, payload: None, vector: None }, vec![blue.clone()]);
+        assert!(aggregator.is_full());
+
+        let groups = aggregator.into_groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].id, red);
+        assert_eq!(groups[0].hits.iter().map(|x| x.id).collect::<Vec<_>>(), vec![1.into(), 2.into()]);
+        assert_eq!(groups[1].id, blue);
+        assert_eq!(groups[1].hits.iter().map(|x| x.id).collect::<Vec<_>>(), vec![2.into(), 5.into()]);
+    }
+
+    #[test]
+    fn test_search_groups() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let (_rt, searcher) = build_searcher(dir.path());
+
+        let request = Arc::new(SearchGroupsRequest {
+            vector: vec![1.0, 1.0, 1.0, 1.0],
+            filter: None,
+            params: None,
+            with_payload: None,
+            with_vector: false,
+            group_by: "color".to_string(),
+            group_size: 2,
+            limit: 2,
+        });
+
+        let groups = search_groups(&searcher, request, &StopCondition::default()).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].id, GroupId::Keyword("blue".to_owned()));
+        assert_eq!(groups[0].hits[0].id, 3.into());
+        assert_eq!(groups[1].id, GroupId::Keyword("red".to_owned()));
+        assert_eq!(groups[1].hits.len(), 2);
+    }
+}
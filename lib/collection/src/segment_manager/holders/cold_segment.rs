@@ -0,0 +1,434 @@
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use segment::common::file_operations::{atomic_save_json, read_json};
+use segment::common::stop_condition::StopCondition;
+use segment::entry::entry_point::{OperationError, OperationResult, SegmentEntry};
+use segment::segment::Segment;
+use segment::segment_constructor::segment_constructor::load_segment;
+use segment::telemetry::SegmentTelemetry;
+use segment::types::{BatchPoint, Filter, PayloadIndexInfo, PayloadKeyType, PayloadSchemaType, PayloadType, PointIdType, ScoredPoint, SearchParams, SegmentConfig, SegmentDiskUsage, SegmentInfo, SegmentMemoryUsage, SegmentType, SeqNumberType, TheMap, VectorElementType, WithPayload};
+
+use crate::cold_storage::ColdStorage;
+use crate::segment_manager::holders::proxy_segment::exclude_points;
+
+/// File in the directory of the cold segment, which is the only data of the segment stored locally
+pub const COLD_SEGMENT_FILE: &str = "cold_segment.json";
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct ColdSegmentState {
+    /// Key of the packed segment in the object storage
+    key: String,
+    /// Version of the segment at the moment of offloading
+    version: SeqNumberType,
+    info: SegmentInfo,
+    indexed_fields: Vec<PayloadKeyType>,
+    payload_index_info: HashMap<PayloadKeyType, Vec<PayloadIndexInfo>>,
+    /// Versions of all points of the offloaded segment
+    points: Vec<(PointIdType, SeqNumberType)>,
+    /// Points, deleted after offloading. The offloaded segment is never changed
+    deleted_points: Vec<PointIdType>,
+    /// Version of the last deletion
+    deleted_version: SeqNumberType,
+}
+
+/// Immutable segment, which files are stored in the cold storage and fetched on demand.
+/// Ids and versions of points are kept locally, so updates do not fetch the segment to find their points.
+/// Points are moved out of the segment as out of any other non-appendable segment: they are only marked as deleted.
+pub struct ColdSegment {
+    /// Directory with the state of the segment
+    path: PathBuf,
+    storage: Arc<ColdStorage>,
+    key: String,
+    version: SeqNumberType,
+    info: SegmentInfo,
+    indexed_fields: Vec<PayloadKeyType>,
+    payload_index_info: HashMap<PayloadKeyType, Vec<PayloadIndexInfo>>,
+    points: BTreeMap<PointIdType, SeqNumberType>,
+    deleted_points: RwLock<HashSet<PointIdType>>,
+    deleted_version: AtomicU64,
+    /// Deleted points are not persisted yet
+    is_changed: AtomicBool,
+}
+
+impl ColdSegment {
+    /// Directory contains a cold segment, which should be loaded with `ColdSegment::load`
+    pub fn is_cold(path: &Path) -> bool {
+        path.join(COLD_SEGMENT_FILE).exists()
+    }
+
+    /// Upload the segment into the cold storage and save its state into a new directory at `path`.
+    /// The segment should not be changed meanwhile. Original segment is not removed
+    pub fn offload(segment: &Segment, path: &Path, storage: Arc<ColdStorage>) -> OperationResult<Self> {
+        segment.flush()?;
+        let key = format!("{}.tar", path.file_name().unwrap().to_string_lossy());
+        storage.upload(&segment.current_path, &key)?;
+
+        let points = segment.iter_points()
+            .filter_map(|point_id| Some((point_id, segment.point_version(point_id)?)))
+            .collect();
+        let state = ColdSegmentState {
+            key,
+            version: segment.version(),
+            info: segment.info(),
+            indexed_fields: segment.get_indexed_fields(),
+            payload_index_info: segment.payload_index_info(),
+            points,
+            deleted_points: vec![],
+            deleted_version: 0,
+        };
+        create_dir_all(path)?;
+        atomic_save_json(&path.join(COLD_SEGMENT_FILE), &state)?;
+        Ok(Self::from_state(path, state, storage))
+    }
+
+    pub fn load(path: &Path, storage: Arc<ColdStorage>) -> OperationResult<Self> {
+        let state: ColdSegmentState = read_json(&path.join(COLD_SEGMENT_FILE))?;
+        Ok(Self::from_state(path, state, storage))
+    }
+
+    fn from_state(path: &Path, state: ColdSegmentState, storage: Arc<ColdStorage>) -> Self {
+        ColdSegment {
+            path: path.to_owned(),
+            storage,
+            key: state.key,
+            version: state.version,
+            info: state.info,
+            indexed_fields: state.indexed_fields,
+            payload_index_info: state.payload_index_info,
+            points: state.points.into_iter().collect(),
+            deleted_points: RwLock::new(state.deleted_points.into_iter().collect()),
+            deleted_version: AtomicU64::new(state.deleted_version),
+            is_changed: AtomicBool::new(false),
+        }
+    }
+
+    fn save_state(&self) -> OperationResult<()> {
+        let mut deleted_points: Vec<PointIdType> = self.deleted_points.read().iter().cloned().collect();
+        deleted_points.sort_unstable();
+        let state = ColdSegmentState {
+            key: self.key.clone(),
+            version: self.version,
+            info: self.info.clone(),
+            indexed_fields: self.indexed_fields.clone(),
+            payload_index_info: self.payload_index_info.clone(),
+            points: self.points.iter().map(|(point_id, version)| (*point_id, *version)).collect(),
+            deleted_points,
+            deleted_version: self.deleted_version.load(Ordering::SeqCst),
+        };
+        atomic_save_json(&self.path.join(COLD_SEGMENT_FILE), &state)
+    }
+
+    fn segment(&self) -> OperationResult<Arc<Segment>> {
+        self.storage.segment(&self.key)
+            .map_err(|err| err.with_segment_path(&self.path))
+    }
+
+    fn mark_deleted(&self, op_num: SeqNumberType, points: impl IntoIterator<Item=PointIdType>) -> usize {
+        let mut deleted_points = self.deleted_points.write();
+        let deleted = points.into_iter()
+            .filter(|point_id| self.points.contains_key(point_id))
+            .filter(|point_id| deleted_points.insert(*point_id))
+            .count();
+        if deleted > 0 {
+            self.deleted_version.fetch_max(op_num, Ordering::SeqCst);
+            self.is_changed.store(true, Ordering::SeqCst);
+        }
+        deleted
+    }
+
+    fn check_point(&self, point_id: PointIdType) -> OperationResult<()> {
+        if self.has_point(point_id) {
+            Ok(())
+        } else {
+            Err(OperationError::PointIdError { missed_point_id: point_id })
+        }
+    }
+}
+
+impl SegmentEntry for ColdSegment {
+    fn version(&self) -> SeqNumberType {
+        max(self.version, self.deleted_version.load(Ordering::SeqCst))
+    }
+
+    fn search(&self,
+              vector: &Vec<VectorElementType>,
+              with_payload: &WithPayload,
+              with_vector: bool,
+              filter: Option<&Filter>,
+              top: usize,
+              params: Option<&SearchParams>,
+              stop: &StopCondition,
+    ) -> OperationResult<Vec<ScoredPoint>> {
+        let wrapped_filter = exclude_points(filter, &self.deleted_points.read());
+        self.segment()?.search(vector, with_payload, with_vector, wrapped_filter.as_ref().or(filter), top, params, stop)
+    }
+
+    fn count(&self, filter: Option<&Filter>, exact: bool, stop: &StopCondition) -> OperationResult<usize> {
+        if filter.is_none() {
+            return Ok(self.vectors_count());
+        }
+        let wrapped_filter = exclude_points(filter, &self.deleted_points.read());
+        self.segment()?.count(wrapped_filter.as_ref().or(filter), exact, stop)
+    }
+
+    fn upsert_point(&mut self, _op_num: SeqNumberType, _point_id: PointIdType, _vector: &Vec<VectorElementType>) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn upsert_batch(&mut self, _op_num: SeqNumberType, _points: &[BatchPoint]) -> OperationResult<usize> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn update_point_vector(&mut self, _op_num: SeqNumberType, _point_id: PointIdType, _vector: &Vec<VectorElementType>) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn delete_point(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
+        if self.point_version(point_id).map_or(true, |version| version > op_num) {
+            return Ok(false);
+        }
+        Ok(self.mark_deleted(op_num, Some(point_id)) > 0)
+    }
+
+    fn delete_filtered(&self, op_num: SeqNumberType, filter: &Filter) -> OperationResult<usize> {
+        let matched_points = {
+            let wrapped_filter = exclude_points(Some(filter), &self.deleted_points.read());
+            self.segment()?.read_filtered(None, usize::MAX, wrapped_filter.as_ref().or(Some(filter)), &StopCondition::default())?
+        };
+        // Points, changed by newer operations, are kept
+        let matched_points = matched_points.into_iter()
+            .filter(|point_id| self.point_version(*point_id).map_or(false, |version| version <= op_num));
+        Ok(self.mark_deleted(op_num, matched_points))
+    }
+
+    fn set_full_payload(&self, _op_num: SeqNumberType, _point_id: PointIdType, _full_payload: TheMap<PayloadKeyType, PayloadType>) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn set_payload(&self, _op_num: SeqNumberType, _point_id: PointIdType, _key: &PayloadKeyType, _payload: PayloadType) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn delete_payload(&self, _op_num: SeqNumberType, _point_id: PointIdType, _key: &PayloadKeyType) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn clear_payload(&self, _op_num: SeqNumberType, _point_id: PointIdType) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    /// Payload of cold segments is never changed, collections with cold segments can't migrate payload keys
+    fn migrate_payload_key(&mut self,
+                           _op_num: SeqNumberType,
+                           _key: &PayloadKeyType,
+                           _new_key: &PayloadKeyType,
+                           _convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<bool> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn check_payload_migration(&self,
+                               _op_num: SeqNumberType,
+                               _key: &PayloadKeyType,
+                               _new_key: &PayloadKeyType,
+                               _convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>> {
+        Err(OperationError::ReadOnlyError)
+    }
+
+    fn pending_payload_migrations(&self) -> usize {
+        0
+    }
+
+    fn migrate_payload_batch(&self, _limit: usize) -> OperationResult<usize> {
+        Ok(0)
+    }
+
+    fn vector(&self, point_id: PointIdType) -> OperationResult<Vec<VectorElementType>> {
+        self.check_point(point_id)?;
+        self.segment()?.vector(point_id)
+    }
+
+    fn payload(&self, point_id: PointIdType) -> OperationResult<TheMap<PayloadKeyType, PayloadType>> {
+        self.check_point(point_id)?;
+        self.segment()?.payload(point_id)
+    }
+
+    fn iter_points(&self) -> Box<dyn Iterator<Item=PointIdType> + '_> {
+        let deleted_points = self.deleted_points.read().clone();
+        Box::new(self.points.keys().cloned().filter(move |point_id| !deleted_points.contains(point_id)))
+    }
+
+    fn read_filtered(&self,
+                     offset: Option<PointIdType>,
+                     limit: usize,
+                     filter: Option<&Filter>,
+                     stop: &StopCondition,
+    ) -> OperationResult<Vec<PointIdType>> {
+        if filter.is_none() {
+            let deleted_points = self.deleted_points.read();
+            let points = match offset {
+                None => self.points.range(..),
+                Some(offset) => self.points.range(offset..),
+            };
+            return Ok(points
+                .map(|(point_id, _)| *point_id)
+                .filter(|point_id| !deleted_points.contains(point_id))
+                .take(limit)
+                .collect());
+        }
+        let wrapped_filter = exclude_points(filter, &self.deleted_points.read());
+        self.segment()?.read_filtered(offset, limit, wrapped_filter.as_ref().or(filter), stop)
+    }
+
+    fn has_point(&self, point_id: PointIdType) -> bool {
+        self.points.contains_key(&point_id) && !self.deleted_points.read().contains(&point_id)
+    }
+
+    fn point_version(&self, point_id: PointIdType) -> Option<SeqNumberType> {
+        if self.deleted_points.read().contains(&point_id) {
+            return None;
+        }
+        self.points.get(&point_id).cloned()
+    }
+
+    /// Requests to cold segments are not measured
+    fn get_telemetry(&self) -> SegmentTelemetry {
+        SegmentTelemetry::default()
+    }
+
+    fn vectors_count(&self) -> usize {
+        self.points.len() - self.deleted_points.read().len()
+    }
+
+    fn deleted_count(&self) -> usize {
+        self.info.num_deleted_vectors + self.deleted_points.read().len()
+    }
+
+    fn segment_type(&self) -> SegmentType {
+        SegmentType::Special
+    }
+
+    /// Segment does not occupy local disk or memory, except its copy in the cache
+    fn info(&self) -> SegmentInfo {
+        SegmentInfo {
+            segment_type: SegmentType::Special,
+            num_vectors: self.vectors_count(),
+            num_deleted_vectors: self.deleted_count(),
+            ram_usage_bytes: 0,
+            memory_usage: SegmentMemoryUsage::default(),
+            disk_usage_bytes: 0,
+            disk_usage: SegmentDiskUsage::default(),
+            is_appendable: false,
+            ..self.info.clone()
+        }
+    }
+
+    fn config(&self) -> SegmentConfig {
+        self.info.config.clone()
+    }
+
+    fn is_appendable(&self) -> bool {
+        false
+    }
+
+    fn flush(&self) -> OperationResult<SeqNumberType> {
+        if self.is_changed.swap(false, Ordering::SeqCst) {
+            if let Err(err) = self.save_state() {
+                self.is_changed.store(true, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+        Ok(self.version())
+    }
+
+    /// Snapshot contains a regular segment, so it does not depend on objects of the cold storage
+    fn take_snapshot(&self, snapshot_dir: &Path) -> OperationResult<()> {
+        let copy_path = self.segment()?.take_snapshot(snapshot_dir)?;
+        let copy = load_segment(&copy_path)?;
+        let deleted_version = self.deleted_version.load(Ordering::SeqCst);
+        for point_id in self.deleted_points.read().iter() {
+            copy.delete_point(deleted_version, *point_id)?;
+        }
+        copy.flush()?;
+        Ok(())
+    }
+
+    fn drop_data(&mut self) -> OperationResult<()> {
+        self.storage.delete(&self.key)?;
+        Ok(remove_dir_all(&self.path)?)
+    }
+
+    /// Index of cold segments is never changed, so new indexes are only used by other segments
+    fn delete_field_index(&mut self, _op_num: u64, _key: &PayloadKeyType) -> OperationResult<bool> {
+        Ok(false)
+    }
+
+    fn create_field_index(&mut self, _op_num: u64, _key: &PayloadKeyType) -> OperationResult<bool> {
+        Ok(false)
+    }
+
+    fn get_indexed_fields(&self) -> Vec<PayloadKeyType> {
+        self.indexed_fields.clone()
+    }
+
+    fn payload_index_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>> {
+        self.payload_index_info.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::cold_storage::object_store::LocalObjectStore;
+    use crate::segment_manager::fixtures::build_segment_1;
+
+    #[test]
+    fn test_cold_segment() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let store_dir = TempDir::new("store_dir").unwrap();
+        let cache_dir = TempDir::new("cache_dir").unwrap();
+        let store = LocalObjectStore::new(store_dir.path()).unwrap();
+        let storage = Arc::new(ColdStorage::with_store(Box::new(store), cache_dir.path(), 1).unwrap());
+
+        let segment = build_segment_1(dir.path());
+        let cold_path = dir.path().join("cold");
+        let cold_segment = ColdSegment::offload(&segment, &cold_path, storage.clone()).unwrap();
+        assert!(ColdSegment::is_cold(&cold_path));
+        assert_eq!(cold_segment.vectors_count(), segment.vectors_count());
+
+        let query = vec![1.0, 1.0, 1.0, 1.0];
+        let expected = segment.search(&query, &WithPayload::default(), false, None, 10, None, &StopCondition::default()).unwrap();
+        let found = cold_segment.search(&query, &WithPayload::default(), false, None, 10, None, &StopCondition::default()).unwrap();
+        assert_eq!(found.len(), expected.len());
+        assert_eq!(found[0].id, expected[0].id);
+
+        // Unused segment is only evicted from the cache, once another segment is fetched
+        let (cached_segments, _) = storage.cache_usage();
+        assert_eq!(cached_segments, 1);
+
+        let deleted_id = expected[0].id;
+        assert!(cold_segment.delete_point(100, deleted_id).unwrap());
+        assert!(!cold_segment.has_point(deleted_id));
+        assert!(cold_segment.vector(deleted_id).is_err());
+        let found = cold_segment.search(&query, &WithPayload::default(), false, None, 10, None, &StopCondition::default()).unwrap();
+        assert!(found.iter().all(|point| point.id != deleted_id));
+        assert!(cold_segment.upsert_batch(101, &[]).is_err());
+
+        cold_segment.flush().unwrap();
+        let loaded = ColdSegment::load(&cold_path, storage.clone()).unwrap();
+        assert_eq!(loaded.version(), 100);
+        assert!(!loaded.has_point(deleted_id));
+        assert_eq!(loaded.read_filtered(None, 100, None, &StopCondition::default()).unwrap().len(), segment.vectors_count() - 1);
+    }
+}
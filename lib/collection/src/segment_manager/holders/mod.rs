@@ -1,2 +1,3 @@
 pub mod segment_holder;
 pub mod proxy_segment;
+pub mod cold_segment;
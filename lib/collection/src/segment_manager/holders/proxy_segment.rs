@@ -1,13 +1,17 @@
-use segment::entry::entry_point::{SegmentEntry, OperationResult};
-use segment::types::{Filter, Condition, SearchParams, ScoredPoint, PayloadKeyType, PayloadType, TheMap, SeqNumberType, VectorElementType, PointIdType, SegmentInfo, SegmentType, SegmentConfig};
+use segment::entry::entry_point::{SegmentEntry, OperationResult, OperationError};
+use segment::types::{Filter, Condition, SearchParams, ScoredPoint, PayloadKeyType, PayloadType, TheMap, SeqNumberType, VectorElementType, PointIdType, SegmentInfo, SegmentType, SegmentConfig, SegmentStatus, PayloadIndexInfo, PayloadSchemaType, WithPayload, BatchPoint, PayloadMigration};
 use std::cmp::max;
 use crate::segment_manager::holders::segment_holder::LockedSegment;
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap};
 use std::sync::Arc;
+use std::path::Path;
 use parking_lot::RwLock;
+use segment::telemetry::SegmentTelemetry;
+use segment::common::stop_condition::StopCondition;
 
 type LockedRmSet = Arc<RwLock<HashSet<PointIdType>>>;
 type LockedFieldsSet = Arc<RwLock<HashSet<PayloadKeyType>>>;
+type LockedMigrations = Arc<RwLock<Vec<PayloadMigration>>>;
 
 
 /// This object is a wrapper around read-only segment.
@@ -19,7 +23,10 @@ pub struct ProxySegment {
     /// Points which should not longer used from wrapped_segment
     deleted_points: LockedRmSet,
     deleted_indexes: LockedFieldsSet,
-    created_indexes: LockedFieldsSet
+    created_indexes: LockedFieldsSet,
+    /// Payload migrations of the wrapped segment, requested through the proxy.
+    /// Payload of the wrapped points is returned migrated, the migrations are continued by the optimized segment
+    payload_migrations: LockedMigrations,
 }
 
 
@@ -28,24 +35,55 @@ impl ProxySegment {
         segment: LockedSegment,
         write_segment: LockedSegment,
         deleted_points: LockedRmSet,
-        created_indexes: LockedFieldsSet,
         deleted_indexes: LockedFieldsSet,
+        created_indexes: LockedFieldsSet,
+        payload_migrations: LockedMigrations,
     ) -> Self {
         ProxySegment {
             write_segment,
             wrapped_segment: segment,
             deleted_points,
+            deleted_indexes,
             created_indexes,
-            deleted_indexes
+            payload_migrations,
         }
     }
 
+    /// Payload of the wrapped point with the migrations, requested through the proxy
+    fn wrapped_payload(&self, point_id: PointIdType) -> OperationResult<TheMap<PayloadKeyType, PayloadType>> {
+        let segment_arc = self.wrapped_segment.get();
+        let segment = segment_arc.read();
+        let point_version = segment.point_version(point_id).unwrap_or(0);
+        let mut payload = segment.payload(point_id)?;
+        PayloadMigration::apply_pending(&self.payload_migrations.read(), point_version, &mut payload);
+        Ok(payload)
+    }
+
+    /// Check that the migration could be applied to the wrapped segment.
+    /// Returns type of the migrated values or `None` if the wrapped segment has no values of the `key`
+    fn check_wrapped_migration(&self,
+                               op_num: SeqNumberType,
+                               key: &PayloadKeyType,
+                               new_key: &PayloadKeyType,
+                               convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>> {
+        {
+            let payload_migrations = self.payload_migrations.read();
+            if payload_migrations.iter().any(|migration| migration.version == op_num) {
+                return Ok(None);
+            }
+            if let Some(migration) = payload_migrations.iter().find(|migration| migration.affects(key, new_key)) {
+                return Err(OperationError::WrongInput {
+                    description: format!("Payload key {} is still being migrated into {}", migration.key, migration.new_key)
+                });
+            }
+        }
+        self.wrapped_segment.get().read().check_payload_migration(op_num, key, new_key, convert_to)
+    }
+
     fn move_point(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
-        let (vector, payload) = {
-            let segment_arc = self.wrapped_segment.get();
-            let segment = segment_arc.read();
-            (segment.vector(point_id)?, segment.payload(point_id)?)
-        };
+        let vector = self.wrapped_segment.get().read().vector(point_id)?;
+        let payload = self.wrapped_payload(point_id)?;
 
         let mut deleted_points = self.deleted_points.write();
         deleted_points.insert(point_id);
@@ -53,12 +91,17 @@ impl ProxySegment {
         let segment_arc = self.write_segment.get();
         let mut write_segment = segment_arc.write();
 
-        write_segment.upsert_point(op_num, point_id, &vector)?;
-        write_segment.set_full_payload(op_num, point_id, payload)?;
+        write_segment.upsert_batch(op_num, &[(point_id, vector, Some(payload))])?;
 
         Ok(true)
     }
 
+    /// Point is not changed by operations older than the last one applied to it.
+    /// Version of the proxy is not used, as operations with different points might be applied out of order.
+    fn skip_point_by_version(&self, op_num: SeqNumberType, point_id: PointIdType) -> bool {
+        self.point_version(point_id).map_or(false, |version| version > op_num)
+    }
+
     fn move_if_exists(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
         let wrapped_has_point = self.wrapped_segment.get().read().has_point(point_id);
         let already_deleted = self.deleted_points.read().contains(&point_id);
@@ -67,6 +110,40 @@ impl ProxySegment {
         }
         Ok(false)
     }
+
+    /// Some point might be deleted after temporary segment creation.
+    /// We need to prevent them from being found by read requests to the wrapped segment,
+    /// that is why additional filter for deleted points is required.
+    /// Returns `None` if original filter could be used as is.
+    fn wrapped_filter(&self, filter: Option<&Filter>) -> Option<Filter> {
+        exclude_points(filter, &self.deleted_points.read())
+    }
+}
+
+/// Filter, which additionally excludes `points`. Returns `None` if there is nothing to exclude
+pub fn exclude_points(filter: Option<&Filter>, points: &HashSet<PointIdType>) -> Option<Filter> {
+    if points.is_empty() {
+        return None;
+    }
+    // ToDo: Come up with better way to pass deleted points into Filter
+    // e.g. implement AtomicRefCell for Serializer.
+    // This copy might slow process down if there will be a lot of deleted points
+    let wrapper_condition = Condition::HasId(points.clone().into());
+    match filter {
+        None => Some(Filter::new_must_not(wrapper_condition)),
+        Some(f) => {
+            let mut new_filter = f.clone();
+            let new_must_not = match new_filter.must_not {
+                None => Some(vec![wrapper_condition]),
+                Some(mut conditions) => {
+                    conditions.push(wrapper_condition);
+                    Some(conditions)
+                }
+            };
+            new_filter.must_not = new_must_not;
+            Some(new_filter)
+        }
+    }
 }
 
 impl SegmentEntry for ProxySegment {
@@ -77,105 +154,199 @@ impl SegmentEntry for ProxySegment {
         )
     }
 
-    fn search(&self, vector: &Vec<VectorElementType>, filter: Option<&Filter>, top: usize, params: Option<&SearchParams>) -> OperationResult<Vec<ScoredPoint>> {
-        let deleted_points = self.deleted_points.read();
-
-        // Some point might be deleted after temporary segment creation
-        // We need to prevent them from being found by search request
-        // That is why we need to pass additional filter for deleted points
-        let do_update_filter = !deleted_points.is_empty();
-        let mut wrapped_result = if do_update_filter {
-            // ToDo: Come up with better way to pass deleted points into Filter
-            // e.g. implement AtomicRefCell for Serializer.
-            // This copy might slow process down if there will be a lot of deleted points
-            let wrapper_condition = Condition::HasId(deleted_points.clone().into());
-            let wrapped_filter = match filter {
-                None => {
-                    Some(Filter::new_must_not(wrapper_condition))
-                }
-                Some(f) => {
-                    let mut new_filter = f.clone();
-                    let must_not = new_filter.must_not;
-
-                    let new_must_not = match must_not {
-                        None => Some(vec![wrapper_condition]),
-                        Some(mut conditions) => {
-                            conditions.push(wrapper_condition);
-                            Some(conditions)
-                        }
-                    };
-                    new_filter.must_not = new_must_not;
-                    Some(new_filter)
-                }
-            };
-
-            self.wrapped_segment.get().read().search(
-                vector,
-                wrapped_filter.as_ref(),
-                top,
-                params,
-            )?
+    fn search(&self,
+              vector: &Vec<VectorElementType>,
+              with_payload: &WithPayload,
+              with_vector: bool,
+              filter: Option<&Filter>,
+              top: usize,
+              params: Option<&SearchParams>,
+              stop: &StopCondition,
+    ) -> OperationResult<Vec<ScoredPoint>> {
+        let wrapped_filter = self.wrapped_filter(filter);
+        let payload_migrations = self.payload_migrations.read().clone();
+        // Selector is applied to the migrated payload
+        let wrapped_with_payload = if payload_migrations.is_empty() {
+            with_payload.clone()
         } else {
-            self.wrapped_segment.get().read().search(
-                vector,
-                filter,
-                top,
-                params,
-            )?
+            WithPayload { enable: with_payload.enable, payload_selector: None }
         };
+        let mut wrapped_result = self.wrapped_segment.get().read().search(
+            vector,
+            &wrapped_with_payload,
+            with_vector,
+            wrapped_filter.as_ref().or(filter),
+            top,
+            params,
+            stop,
+        )?;
+        if !payload_migrations.is_empty() {
+            for scored_point in wrapped_result.iter_mut() {
+                if let Some(mut payload) = scored_point.payload.take() {
+                    PayloadMigration::apply_pending(&payload_migrations, scored_point.version, &mut payload);
+                    scored_point.payload = Some(match &with_payload.payload_selector {
+                        None => payload,
+                        Some(selector) => selector.process(payload)
+                    });
+                }
+            }
+        }
 
         let mut write_result = self.write_segment.get().read().search(
             vector,
+            with_payload,
+            with_vector,
             filter,
             top,
             params,
+            stop,
         )?;
 
         wrapped_result.append(&mut write_result);
         return Ok(wrapped_result);
     }
 
+    fn count(&self, filter: Option<&Filter>, exact: bool, stop: &StopCondition) -> OperationResult<usize> {
+        if filter.is_none() {
+            return Ok(self.vectors_count());
+        }
+        let wrapped_filter = self.wrapped_filter(filter);
+        let wrapped_count = self.wrapped_segment.get().read().count(wrapped_filter.as_ref().or(filter), exact, stop)?;
+        Ok(wrapped_count + self.write_segment.get().read().count(filter, exact, stop)?)
+    }
+
     fn upsert_point(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         self.move_if_exists(op_num, point_id)?;
         self.write_segment.get().write().upsert_point(op_num, point_id, vector)
     }
 
-    fn delete_point(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+    fn upsert_batch(&mut self, op_num: SeqNumberType, points: &[BatchPoint]) -> OperationResult<usize> {
+        let points: Vec<BatchPoint> = points.iter()
+            .filter(|(point_id, _, _)| !self.skip_point_by_version(op_num, *point_id))
+            .cloned()
+            .collect();
+        for (point_id, _, _) in points.iter() {
+            self.move_if_exists(op_num, *point_id)?;
+        }
+        self.write_segment.get().write().upsert_batch(op_num, &points)
+    }
+
+    fn update_point_vector(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
+        self.move_if_exists(op_num, point_id)?;
+        self.write_segment.get().write().update_point_vector(op_num, point_id, vector)
+    }
+
+    fn delete_point(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         let mut was_deleted = false;
         if self.wrapped_segment.get().read().has_point(point_id) {
             self.deleted_points.write().insert(point_id);
             was_deleted = true;
         }
-        let was_deleted_in_writable = self.write_segment.get().write().delete_point(op_num, point_id)?;
+        let was_deleted_in_writable = self.write_segment.get().read().delete_point(op_num, point_id)?;
 
         Ok(was_deleted || was_deleted_in_writable)
     }
 
-    fn set_full_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, full_payload: TheMap<PayloadKeyType, PayloadType>) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+    fn delete_filtered(&self, op_num: SeqNumberType, filter: &Filter) -> OperationResult<usize> {
+        // Matched points of the wrapped segment are only marked as deleted
+        let wrapped_points: Vec<PointIdType> = {
+            let wrapped_filter = self.wrapped_filter(Some(filter));
+            let wrapped_segment = self.wrapped_segment.get();
+            let wrapped_segment = wrapped_segment.read();
+            wrapped_segment
+                .read_filtered(None, usize::MAX, wrapped_filter.as_ref().or(Some(filter)), &StopCondition::default())?
+                .into_iter()
+                .filter(|point_id| wrapped_segment.point_version(*point_id).map_or(true, |version| version <= op_num))
+                .collect()
+        };
+        let wrapped_deleted = wrapped_points.len();
+        let write_segment = self.write_segment.get();
+        let write_segment = write_segment.read();
+        let write_deleted = write_segment.delete_filtered(op_num, filter)?;
+        // Deletions of the wrapped points are versioned in the write segment
+        for point_id in wrapped_points.iter() {
+            write_segment.delete_point(op_num, *point_id)?;
+        }
+        self.deleted_points.write().extend(wrapped_points);
+        Ok(wrapped_deleted + write_deleted)
+    }
+
+    fn set_full_payload(&self, op_num: SeqNumberType, point_id: PointIdType, full_payload: TheMap<PayloadKeyType, PayloadType>) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         self.move_if_exists(op_num, point_id)?;
 
-        self.write_segment.get().write().set_full_payload(op_num, point_id, full_payload)
+        self.write_segment.get().read().set_full_payload(op_num, point_id, full_payload)
     }
 
-    fn set_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType, payload: PayloadType) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+    fn set_payload(&self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType, payload: PayloadType) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         self.move_if_exists(op_num, point_id)?;
-        self.write_segment.get().write().set_payload(op_num, point_id, key, payload)
+        self.write_segment.get().read().set_payload(op_num, point_id, key, payload)
     }
 
-    fn delete_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+    fn delete_payload(&self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         self.move_if_exists(op_num, point_id)?;
-        self.write_segment.get().write().delete_payload(op_num, point_id, key)
+        self.write_segment.get().read().delete_payload(op_num, point_id, key)
     }
 
-    fn clear_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
-        if self.version() > op_num { return Ok(false); }
+    fn clear_payload(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
         self.move_if_exists(op_num, point_id)?;
-        self.write_segment.get().write().clear_payload(op_num, point_id)
+        self.write_segment.get().read().clear_payload(op_num, point_id)
+    }
+
+    fn migrate_payload_key(&mut self,
+                           op_num: SeqNumberType,
+                           key: &PayloadKeyType,
+                           new_key: &PayloadKeyType,
+                           convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<bool> {
+        if self.version() > op_num { return Ok(false); }
+
+        // Both segments are checked before any changes, so the migration is either registered or rejected completely
+        let wrapped_type = self.check_wrapped_migration(op_num, key, new_key, convert_to)?;
+        self.write_segment.get().write().migrate_payload_key(op_num, key, new_key, convert_to)?;
+
+        match wrapped_type {
+            Some(new_type) => self.payload_migrations.write().push(PayloadMigration {
+                version: op_num,
+                key: key.clone(),
+                new_key: new_key.clone(),
+                new_type,
+            }),
+            // There are no values to migrate, only the index is moved to the new key
+            None => if key != new_key && self.get_indexed_fields().contains(key) {
+                self.deleted_indexes.write().insert(key.clone());
+                self.created_indexes.write().remove(key);
+                self.created_indexes.write().insert(new_key.clone());
+                self.deleted_indexes.write().remove(new_key);
+            }
+        }
+        Ok(true)
+    }
+
+    fn check_payload_migration(&self,
+                               op_num: SeqNumberType,
+                               key: &PayloadKeyType,
+                               new_key: &PayloadKeyType,
+                               convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>> {
+        let wrapped_type = self.check_wrapped_migration(op_num, key, new_key, convert_to)?;
+        let write_type = self.write_segment.get().read().check_payload_migration(op_num, key, new_key, convert_to)?;
+        Ok(wrapped_type.or(write_type))
+    }
+
+    /// Migrations of the wrapped segment are continued by the optimized segment
+    fn pending_payload_migrations(&self) -> usize {
+        self.write_segment.get().read().pending_payload_migrations()
+    }
+
+    fn migrate_payload_batch(&self, limit: usize) -> OperationResult<usize> {
+        self.write_segment.get().read().migrate_payload_batch(limit)
     }
 
     fn vector(&self, point_id: PointIdType) -> OperationResult<Vec<VectorElementType>> {
@@ -190,17 +361,33 @@ impl SegmentEntry for ProxySegment {
         return if self.deleted_points.read().contains(&point_id) {
             self.write_segment.get().read().payload(point_id)
         } else {
-            self.wrapped_segment.get().read().payload(point_id)
+            self.wrapped_payload(point_id)
         };
     }
 
     /// Not implemented for proxy
-    fn iter_points(&self) -> Box<dyn Iterator<Item=u64> + '_> {
+    fn iter_points(&self) -> Box<dyn Iterator<Item=PointIdType> + '_> {
         // iter_points is not available for Proxy implementation
         // Due to internal locks it is almost impossible to return iterator with proper owning, lifetimes, e.t.c.
         unimplemented!()
     }
 
+    fn read_filtered(&self,
+                     offset: Option<PointIdType>,
+                     limit: usize,
+                     filter: Option<&Filter>,
+                     stop: &StopCondition,
+    ) -> OperationResult<Vec<PointIdType>> {
+        let wrapped_filter = self.wrapped_filter(filter);
+        let mut read_points = self.wrapped_segment.get().read()
+            .read_filtered(offset, limit, wrapped_filter.as_ref().or(filter), stop)?;
+        read_points.append(&mut self.write_segment.get().read().read_filtered(offset, limit, filter, stop)?);
+        read_points.sort_unstable();
+        read_points.dedup();
+        read_points.truncate(limit);
+        Ok(read_points)
+    }
+
     fn has_point(&self, point_id: PointIdType) -> bool {
         return if self.deleted_points.read().contains(&point_id) {
             self.write_segment.get().read().has_point(point_id)
@@ -209,6 +396,20 @@ impl SegmentEntry for ProxySegment {
         };
     }
 
+    fn point_version(&self, point_id: PointIdType) -> Option<SeqNumberType> {
+        return if self.deleted_points.read().contains(&point_id) {
+            self.write_segment.get().read().point_version(point_id)
+        } else {
+            self.wrapped_segment.get().read().point_version(point_id)
+        };
+    }
+
+    fn get_telemetry(&self) -> SegmentTelemetry {
+        let mut telemetry = self.wrapped_segment.get().read().get_telemetry();
+        telemetry.merge(&self.write_segment.get().read().get_telemetry());
+        telemetry
+    }
+
     fn vectors_count(&self) -> usize {
         let mut count = 0;
         count += self.wrapped_segment.get().read().vectors_count();
@@ -229,14 +430,23 @@ impl SegmentEntry for ProxySegment {
         let wrapped_info = self.wrapped_segment.get().read().info();
         let write_info = self.write_segment.get().read().info();
 
+        let mut disk_usage = wrapped_info.disk_usage;
+        disk_usage.merge(&write_info.disk_usage);
+        let mut memory_usage = wrapped_info.memory_usage;
+        memory_usage.merge(&write_info.memory_usage);
+
         return SegmentInfo {
             segment_type: SegmentType::Special,
+            status: SegmentStatus::Optimizing,
             num_vectors: self.vectors_count(),
             num_deleted_vectors: write_info.num_deleted_vectors,
-            ram_usage_bytes: wrapped_info.ram_usage_bytes + write_info.ram_usage_bytes,
-            disk_usage_bytes: wrapped_info.disk_usage_bytes + write_info.disk_usage_bytes,
+            ram_usage_bytes: memory_usage.total(),
+            memory_usage,
+            disk_usage_bytes: disk_usage.total(),
+            disk_usage,
             is_appendable: false,
-            schema: wrapped_info.schema
+            schema: wrapped_info.schema,
+            config: wrapped_info.config,
         };
     }
 
@@ -252,6 +462,12 @@ impl SegmentEntry for ProxySegment {
         Ok(self.wrapped_segment.get().read().version())
     }
 
+    /// Only the wrapped segment is saved. Changes made through the proxy are newer than the wrapped segment,
+    /// and WAL is not truncated beyond its version (see `flush`), so they are re-applied from WAL.
+    fn take_snapshot(&self, snapshot_dir: &Path) -> OperationResult<()> {
+        self.wrapped_segment.get().read().take_snapshot(snapshot_dir)
+    }
+
     fn drop_data(&mut self) -> OperationResult<()> {
         self.wrapped_segment.get().write().drop_data()?;
         Ok(())
@@ -279,6 +495,15 @@ impl SegmentEntry for ProxySegment {
             .filter(|x| !self.deleted_indexes.read().contains(x))
             .collect()
     }
+
+    fn payload_index_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>> {
+        let deleted_indexes = self.deleted_indexes.read();
+        self.wrapped_segment.get().read()
+            .payload_index_info()
+            .into_iter()
+            .filter(|(field, _)| !deleted_indexes.contains(field))
+            .collect()
+    }
 }
 
 
@@ -303,18 +528,19 @@ mod tests {
             write_segment,
             deleted_points,
             deleted_indexes.clone(),
-            created_indexes.clone()
+            created_indexes.clone(),
+            Default::default(),
         );
 
         let vec4 = vec![1.1, 1.0, 0.0, 1.0];
-        proxy_segment.upsert_point(100, 4, &vec4).unwrap();
+        proxy_segment.upsert_point(100, 4.into(), &vec4).unwrap();
         let vec6 = vec![1.0, 1.0, 0.5, 1.0];
-        proxy_segment.upsert_point(101, 6, &vec6).unwrap();
-        proxy_segment.delete_point(102, 1).unwrap();
+        proxy_segment.upsert_point(101, 6.into(), &vec6).unwrap();
+        proxy_segment.delete_point(102, 1.into()).unwrap();
 
 
         let query_vector = vec![1.0, 1.0, 1.0, 1.0];
-        let search_result = proxy_segment.search(&query_vector, None, 10, None).unwrap();
+        let search_result = proxy_segment.search(&query_vector, &WithPayload::default(), false, None, 10, None, &StopCondition::default()).unwrap();
 
 
         eprintln!("search_result = {:#?}", search_result);
@@ -327,15 +553,54 @@ mod tests {
             seen_points.insert(res.id);
         }
 
-        assert!(seen_points.contains(&4));
-        assert!(seen_points.contains(&6));
-        assert!(!seen_points.contains(&1));
+        assert!(seen_points.contains(&4.into()));
+        assert!(seen_points.contains(&6.into()));
+        assert!(!seen_points.contains(&1.into()));
 
-        assert!(!proxy_segment.write_segment.get().read().has_point(2));
+        assert_eq!(proxy_segment.read_filtered(None, 10, None, &StopCondition::default()).unwrap(), vec![2.into(), 3.into(), 4.into(), 5.into(), 6.into()]);
+        assert_eq!(proxy_segment.read_filtered(Some(3.into()), 2, None, &StopCondition::default()).unwrap(), vec![3.into(), 4.into()]);
+
+        let ids: HashSet<PointIdType> = vec![3, 6].into_iter().map(|x| x.into()).collect();
+        let deleted = proxy_segment.delete_filtered(103, &Filter::new_must(Condition::HasId(ids.into()))).unwrap();
+        assert_eq!(deleted, 2);
+        assert_eq!(proxy_segment.read_filtered(None, 10, None, &StopCondition::default()).unwrap(), vec![2.into(), 4.into(), 5.into()]);
+        assert!(!proxy_segment.has_point(3.into()));
+
+        assert!(!proxy_segment.write_segment.get().read().has_point(2.into()));
 
         let payload_key = "color".to_owned();
-        proxy_segment.delete_payload(103, 2, &payload_key).unwrap();
+        proxy_segment.delete_payload(103, 2.into(), &payload_key).unwrap();
+
+        assert!(proxy_segment.write_segment.get().read().has_point(2.into()))
+    }
+
+    #[test]
+    fn test_field_index_changes() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let original_segment = LockedSegment::new(build_segment_1(dir.path()));
+        let write_segment = LockedSegment::new(empty_segment(dir.path()));
+        let deleted_points = Arc::new(RwLock::new(HashSet::<PointIdType>::new()));
+
+        let deleted_indexes = Arc::new(RwLock::new(HashSet::<PayloadKeyType>::new()));
+        let created_indexes = Arc::new(RwLock::new(HashSet::<PayloadKeyType>::new()));
+
+        let mut proxy_segment = ProxySegment::new(
+            original_segment,
+            write_segment,
+            deleted_points,
+            deleted_indexes.clone(),
+            created_indexes.clone(),
+            Default::default(),
+        );
+
+        let color_key = "color".to_owned();
+        let size_key = "size".to_owned();
+        proxy_segment.create_field_index(100, &color_key).unwrap();
+        proxy_segment.delete_field_index(101, &size_key).unwrap();
 
-        assert!(proxy_segment.write_segment.get().read().has_point(2))
+        // Index changes should be tracked, so they could be replayed on the optimized segment
+        assert!(created_indexes.read().contains(&color_key));
+        assert!(deleted_indexes.read().contains(&size_key));
+        assert!(proxy_segment.get_indexed_fields().contains(&color_key));
     }
 }
\ No newline at end of file
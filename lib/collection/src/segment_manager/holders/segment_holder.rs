@@ -1,4 +1,4 @@
-use std::cmp::min;
+use std::cmp::{min, max};
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -10,6 +10,7 @@ use segment::entry::entry_point::{OperationError, OperationResult, SegmentEntry}
 use segment::segment::Segment;
 use segment::types::{PointIdType, SeqNumberType};
 
+use crate::segment_manager::holders::cold_segment::ColdSegment;
 use crate::segment_manager::holders::proxy_segment::ProxySegment;
 
 pub type SegmentId = usize;
@@ -17,6 +18,8 @@ pub type SegmentId = usize;
 pub enum LockedSegment {
     Original(Arc<RwLock<Segment>>),
     Proxy(Arc<RwLock<ProxySegment>>),
+    /// Segment, which data is stored in the cold storage
+    Cold(Arc<RwLock<ColdSegment>>),
 }
 
 
@@ -28,7 +31,8 @@ impl LockedSegment {
     pub fn get(&self) -> Arc<RwLock<dyn SegmentEntry>> {
         return match self {
             LockedSegment::Original(segment) => segment.clone(),
-            LockedSegment::Proxy(proxy) => proxy.clone()
+            LockedSegment::Proxy(proxy) => proxy.clone(),
+            LockedSegment::Cold(cold) => cold.clone(),
         };
     }
 
@@ -43,6 +47,7 @@ impl Clone for LockedSegment {
         match self {
             LockedSegment::Original(x) => LockedSegment::Original(x.clone()),
             LockedSegment::Proxy(x) => LockedSegment::Proxy(x.clone()),
+            LockedSegment::Cold(x) => LockedSegment::Cold(x.clone()),
         }
     }
 
@@ -64,11 +69,25 @@ impl From<ProxySegment> for LockedSegment {
     }
 }
 
+impl From<ColdSegment> for LockedSegment {
+    fn from(s: ColdSegment) -> Self {
+        LockedSegment::Cold(Arc::new(RwLock::new(s)))
+    }
+}
+
 
 unsafe impl Sync for LockedSegment {}
 
 unsafe impl Send for LockedSegment {}
 
+/// Copy of the point in the segment, which might be outdated by a copy in another segment
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StalePoint {
+    pub segment_id: SegmentId,
+    pub point_id: PointIdType,
+    pub version: SeqNumberType,
+}
+
 pub struct SegmentHolder {
     segments: HashMap<SegmentId, LockedSegment>,
 }
@@ -145,17 +164,24 @@ impl<'s> SegmentHolder {
         segment
     }
 
-    /// Selects point ids, which is stored in this segment
-    fn segment_points(&self, ids: &Vec<PointIdType>, segment: &LockedSegment) -> Vec<PointIdType> {
+    /// Selects point ids, which is stored in this segment and not changed by operations newer than `op_num`
+    fn segment_points(&self, op_num: SeqNumberType, ids: &Vec<PointIdType>, segment: &LockedSegment) -> Vec<PointIdType> {
         let segment_arc = segment.get();
         let entry = segment_arc.read();
         ids
             .iter()
             .cloned()
-            .filter(|id| entry.has_point(*id))
+            .filter(|id| entry.has_point(*id) && !Self::is_outdated(&*entry, op_num, *id))
             .collect()
     }
 
+    /// Check if the point in the segment was already changed by a newer operation (WAL recovery related).
+    /// Segment versions are not used for point operations: with several update workers operations are
+    /// applied out of order, so a segment might have a bigger version while older operations are still pending.
+    fn is_outdated(segment: &dyn SegmentEntry, op_num: SeqNumberType, point_id: PointIdType) -> bool {
+        segment.point_version(point_id).map_or(false, |version| version > op_num)
+    }
+
 
     pub fn apply_segments<F>(&self, op_num: SeqNumberType, mut f: F) -> OperationResult<usize>
         where F: FnMut(&mut RwLockWriteGuard<dyn SegmentEntry + 'static>) -> OperationResult<bool>
@@ -172,15 +198,32 @@ impl<'s> SegmentHolder {
     }
 
 
+    /// Same as `apply_segments`, but segments are only locked for reading.
+    /// Suitable for operations, which do not require exclusive access to the segment,
+    /// so searches in the same segment are not blocked.
+    /// Segments are not skipped by version, `f` is expected to check versions of the points it changes.
+    pub fn apply_segments_shared<F>(&self, _op_num: SeqNumberType, mut f: F) -> OperationResult<usize>
+        where F: FnMut(&dyn SegmentEntry) -> OperationResult<bool>
+    {
+        let mut processed_segments = 0;
+        for (_idx, segment) in self.segments.iter() {
+            let segment_arc = segment.get();
+            let read_segment = segment_arc.read();
+
+            let is_applied = f(&*read_segment)?;
+            processed_segments += is_applied as usize;
+        }
+        Ok(processed_segments)
+    }
+
+
     pub fn apply_points<F>(&self, op_num: SeqNumberType, ids: &Vec<PointIdType>, mut f: F) -> OperationResult<usize>
         where F: FnMut(PointIdType, &mut RwLockWriteGuard<dyn SegmentEntry>) -> OperationResult<bool>
     {
         let mut applied_points = 0;
         for (_idx, segment) in self.segments.iter() {
-            // Skip this segment if it already have bigger version (WAL recovery related)
-            if segment.get().read().version() > op_num { continue; }
             // Collect affected points first, we want to lock segment for writing as rare as possible
-            let segment_points = self.segment_points(ids, segment);
+            let segment_points = self.segment_points(op_num, ids, segment);
             if !segment_points.is_empty() {
                 let segment_arc = segment.get();
                 let mut write_segment = segment_arc.write();
@@ -193,6 +236,25 @@ impl<'s> SegmentHolder {
         Ok(applied_points)
     }
 
+    /// Same as `apply_points`, but segments are only locked for reading
+    pub fn apply_points_shared<F>(&self, op_num: SeqNumberType, ids: &Vec<PointIdType>, mut f: F) -> OperationResult<usize>
+        where F: FnMut(PointIdType, &dyn SegmentEntry) -> OperationResult<bool>
+    {
+        let mut applied_points = 0;
+        for (_idx, segment) in self.segments.iter() {
+            let segment_arc = segment.get();
+            let read_segment = segment_arc.read();
+            let segment_points = ids.iter()
+                .cloned()
+                .filter(|id| read_segment.has_point(*id) && !Self::is_outdated(&*read_segment, op_num, *id));
+            for point_id in segment_points {
+                let is_applied = f(point_id, &*read_segment)?;
+                applied_points += is_applied as usize;
+            }
+        }
+        Ok(applied_points)
+    }
+
     /// Update function wrapper, which ensures that updates are not applied written to un-appendable segment.
     /// In case of such attempt, this function will move data into a mutable segment and remove data from un-appendable.
     pub fn apply_points_to_appendable<F>(
@@ -217,8 +279,8 @@ impl<'s> SegmentHolder {
                     let vector = write_segment.vector(point_id)?;
                     let payload = write_segment.payload(point_id)?;
 
-                    default_segment_guard.upsert_point(op_num, point_id, &vector)?;
-                    default_segment_guard.set_full_payload(op_num, point_id, payload)?;
+                    // Vector and payload are published together, so the moved point is never seen without payload
+                    default_segment_guard.upsert_batch(op_num, &[(point_id, vector, Some(payload))])?;
 
                     write_segment.delete_point(op_num, point_id)?;
 
@@ -230,6 +292,42 @@ impl<'s> SegmentHolder {
         Ok(applied_points)
     }
 
+    /// Same as `apply_points_to_appendable`, but appendable segments are only locked for reading.
+    /// Exclusive lock is only required for moving points out of un-appendable segment.
+    pub fn apply_points_to_appendable_shared<F>(
+        &self,
+        op_num: SeqNumberType,
+        ids: &Vec<PointIdType>, mut f: F) -> OperationResult<usize>
+        where F: FnMut(PointIdType, &dyn SegmentEntry) -> OperationResult<bool>
+    {
+        let default_write_segment = self.random_appendable_segment()
+            .ok_or(OperationError::ServiceError { description: "No appendable segments exists, expected at least one".to_string() })?;
+
+        let applied_points = self.apply_points_shared(
+            op_num,
+            ids,
+            |point_id, segment| {
+                let is_applied = if segment.is_appendable() {
+                    f(point_id, segment)?
+                } else {
+                    let default_segment_lock = default_write_segment.get();
+                    let mut default_segment_guard = default_segment_lock.write();
+                    let vector = segment.vector(point_id)?;
+                    let payload = segment.payload(point_id)?;
+
+                    // Vector and payload are published together, so the moved point is never seen without payload
+                    default_segment_guard.upsert_batch(op_num, &[(point_id, vector, Some(payload))])?;
+
+                    segment.delete_point(op_num, point_id)?;
+
+                    f(point_id, &*default_segment_guard)?
+                };
+                Ok(is_applied)
+            }
+        )?;
+        Ok(applied_points)
+    }
+
 
     pub fn read_points<F>(&self, ids: &Vec<PointIdType>, mut f: F) -> OperationResult<usize>
         where F: FnMut(PointIdType, &RwLockReadGuard<dyn SegmentEntry>) -> OperationResult<bool>
@@ -251,6 +349,99 @@ impl<'s> SegmentHolder {
     }
 
 
+    /// Minimal version among all segments.
+    /// Operations with smaller numbers are already applied to every segment, they affect.
+    /// Returns `None` if there are no segments.
+    pub fn min_version(&self) -> Option<SeqNumberType> {
+        self.segments.values()
+            .map(|segment| segment.get().read().version())
+            .min()
+    }
+
+    /// Newest version of the point among all segments, including versions of deleted points.
+    /// Operations older than this version must not change the point, otherwise deleted points might reappear.
+    pub fn newest_point_version(&self, point_id: PointIdType) -> Option<SeqNumberType> {
+        self.segments.values()
+            .filter_map(|segment| segment.get().read().point_version(point_id))
+            .max()
+    }
+
+    /// Latest version of the point in the segment. Segment version is used, if point version is not tracked
+    pub fn point_version(segment: &dyn SegmentEntry, point_id: PointIdType) -> SeqNumberType {
+        segment.point_version(point_id).unwrap_or_else(|| segment.version())
+    }
+
+    /// Latest version of each given point among all segments, which contain it
+    pub fn latest_point_versions(&self, ids: &Vec<PointIdType>) -> OperationResult<HashMap<PointIdType, SeqNumberType>> {
+        let mut versions: HashMap<PointIdType, SeqNumberType> = HashMap::new();
+        self.read_points(ids, |id, segment| {
+            let version = Self::point_version(&**segment, id);
+            let latest = versions.entry(id).or_insert(version);
+            *latest = max(*latest, version);
+            Ok(true)
+        })?;
+        Ok(versions)
+    }
+
+    /// Check if some segment, except the given one, contains a copy of the point, which is not older than `version`
+    fn has_newer_copy(&self, point_id: PointIdType, version: SeqNumberType, except_segment_id: SegmentId) -> bool {
+        self.segments.iter()
+            .filter(|(segment_id, _)| **segment_id != except_segment_id)
+            .any(|(_, segment)| {
+                let segment_arc = segment.get();
+                let read_segment = segment_arc.read();
+                read_segment.has_point(point_id) && Self::point_version(&*read_segment, point_id) >= version
+            })
+    }
+
+    /// Delete given copies of the points, if another segment contains a copy, which is not older.
+    /// Copies, which were changed since they were found, are kept.
+    /// Returns number of deleted copies.
+    pub fn remove_stale_points(&self, stale_points: &[StalePoint]) -> OperationResult<usize> {
+        let mut removed = 0;
+        for stale in stale_points {
+            let segment = match self.segments.get(&stale.segment_id) {
+                Some(segment) => segment.get(),
+                None => continue
+            };
+            if !self.has_newer_copy(stale.point_id, stale.version, stale.segment_id) {
+                continue;
+            }
+            let read_segment = segment.read();
+            if read_segment.has_point(stale.point_id) && Self::point_version(&*read_segment, stale.point_id) == stale.version {
+                removed += read_segment.delete_point(stale.version, stale.point_id)? as usize;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Find points, which are stored in several segments, and delete all copies except the latest one.
+    /// Duplicates might appear if operation was applied partially, e.g. during WAL recovery.
+    /// Returns number of deleted copies.
+    pub fn deduplicate_points(&self) -> OperationResult<usize> {
+        let mut latest: HashMap<PointIdType, (SegmentId, SeqNumberType)> = HashMap::new();
+        let mut stale_points: Vec<StalePoint> = vec![];
+        for (segment_id, segment) in self.segments.iter() {
+            let segment_arc = segment.get();
+            let read_segment = segment_arc.read();
+            for point_id in read_segment.iter_points() {
+                let version = Self::point_version(&*read_segment, point_id);
+                match latest.get(&point_id).cloned() {
+                    None => {
+                        latest.insert(point_id, (*segment_id, version));
+                    }
+                    Some((latest_segment_id, latest_version)) => if latest_version >= version {
+                        stale_points.push(StalePoint { segment_id: *segment_id, point_id, version });
+                    } else {
+                        stale_points.push(StalePoint { segment_id: latest_segment_id, point_id, version: latest_version });
+                        latest.insert(point_id, (*segment_id, version));
+                    }
+                }
+            }
+        }
+        self.remove_stale_points(&stale_points)
+    }
+
     /// Flushes all segments and returns maximum persisted version
     pub fn flush_all(&self) -> OperationResult<SeqNumberType> {
         let mut persisted_version: SeqNumberType = SeqNumberType::MAX;
@@ -264,10 +455,12 @@ impl<'s> SegmentHolder {
 
 #[cfg(test)]
 mod tests {
+    use std::thread;
+
     use tempdir::TempDir;
 
     use segment::segment_constructor::simple_segment_constructor::build_simple_segment;
-    use segment::types::Distance;
+    use segment::types::{Distance, PayloadType};
 
     use crate::segment_manager::fixtures::{build_segment_1, build_segment_2};
 
@@ -290,5 +483,135 @@ mod tests {
 
         let _sid3 = holder.swap(segment3, &vec![sid1, sid2], true).unwrap();
     }
-}
 
+    #[test]
+    fn test_min_version() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let mut holder = SegmentHolder::new();
+        assert_eq!(holder.min_version(), None);
+
+        holder.add(build_segment_1(dir.path()));
+        holder.add(build_segment_2(dir.path()));
+
+        assert_eq!(holder.min_version(), Some(6));
+    }
+
+    #[test]
+    fn test_apply_shared() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+        let sid = holder.add(build_segment_1(dir.path()));
+
+        // Simulates search, which is running in the same segment
+        let segment_arc = holder.get(sid).unwrap().get();
+        let search_guard = segment_arc.read();
+
+        let deleted = holder.apply_points_shared(10, &vec![1.into(), 2.into()], |id, segment| {
+            segment.delete_point(10, id)
+        }).unwrap();
+        assert_eq!(deleted, 2);
+
+        let updated = holder.apply_points_to_appendable_shared(11, &vec![3.into()], |id, segment| {
+            segment.set_payload(11, id, &"color".to_owned(), PayloadType::Keyword(vec!["green".to_owned()]))
+        }).unwrap();
+        assert_eq!(updated, 1);
+
+        assert!(!search_guard.has_point(1.into()));
+        let payload = search_guard.payload(3.into()).unwrap();
+        match payload.get("color") {
+            Some(PayloadType::Keyword(values)) => assert_eq!(values, &vec!["green".to_owned()]),
+            _ => panic!("Payload is not updated")
+        }
+        assert_eq!(search_guard.version(), 11);
+    }
+
+    #[test]
+    fn test_apply_points_by_point_versions() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+        let sid = holder.add(build_segment_1(dir.path()));
+
+        // Newer operation is applied first, e.g. by another update worker
+        let deleted = holder.apply_points_shared(20, &vec![1.into()], |id, segment| {
+            segment.delete_point(20, id)
+        }).unwrap();
+        assert_eq!(deleted, 1);
+
+        // Older operation still changes points, which were not touched by newer ones
+        let updated = holder.apply_points_to_appendable_shared(10, &vec![2.into()], |id, segment| {
+            segment.set_payload(10, id, &"color".to_owned(), PayloadType::Keyword(vec!["green".to_owned()]))
+        }).unwrap();
+        assert_eq!(updated, 1);
+
+        // Deleted point keeps its version, so it is not restored by older operations
+        assert_eq!(holder.newest_point_version(1.into()), Some(20));
+        assert_eq!(holder.newest_point_version(2.into()), Some(10));
+        assert!(!holder.get(sid).unwrap().get().read().has_point(1.into()));
+    }
+
+    #[test]
+    fn test_deduplicate_points() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment1 = build_simple_segment(dir.path(), 4, Distance::Dot).unwrap();
+        let mut segment2 = build_simple_segment(dir.path(), 4, Distance::Dot).unwrap();
+
+        segment1.upsert_point(1, 1.into(), &vec![1.0, 0.0, 0.0, 0.0]).unwrap();
+        segment1.upsert_point(2, 2.into(), &vec![1.0, 0.0, 0.0, 0.0]).unwrap();
+        segment2.upsert_point(3, 1.into(), &vec![0.0, 1.0, 0.0, 0.0]).unwrap();
+        segment2.upsert_point(3, 3.into(), &vec![0.0, 1.0, 0.0, 0.0]).unwrap();
+
+        let mut holder = SegmentHolder::new();
+        let sid1 = holder.add(segment1);
+        let sid2 = holder.add(segment2);
+
+        // Copy is not removed, if there is no newer one
+        let not_stale = StalePoint { segment_id: sid1, point_id: 2.into(), version: 2 };
+        assert_eq!(holder.remove_stale_points(&[not_stale]).unwrap(), 0);
+
+        assert_eq!(holder.deduplicate_points().unwrap(), 1);
+        assert_eq!(holder.deduplicate_points().unwrap(), 0);
+
+        assert!(!holder.get(sid1).unwrap().get().read().has_point(1.into()));
+        assert!(holder.get(sid1).unwrap().get().read().has_point(2.into()));
+        assert_eq!(holder.get(sid2).unwrap().get().read().vector(1.into()).unwrap(), vec![0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_concurrent_point_updates() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let segment = LockedSegment::new(build_segment_1(dir.path()));
+        let key = "version".to_owned();
+
+        // Operations on the same point arrive from several threads out of order
+        let threads: Vec<_> = (0..4).map(|thread_idx| {
+            let segment = segment.clone();
+            let key = key.clone();
+            thread::spawn(move || {
+                for op_num in (100..500).filter(|op_num| op_num % 4 == thread_idx) {
+                    let payload = PayloadType::Integer(vec![op_num as i64]);
+                    let segment = segment.get();
+                    let segment = segment.read();
+                    let result = match op_num % 3 {
+                        0 => segment.set_payload(op_num, 1.into(), &key, payload),
+                        1 => segment.set_full_payload(op_num, 1.into(), vec![(key.clone(), payload)].into_iter().collect()),
+                        _ => segment.clear_payload(op_num, 1.into()),
+                    };
+                    result.unwrap();
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // The last operation wins, older ones never overwrite it
+        let segment = segment.get();
+        let segment = segment.read();
+        assert_eq!(segment.point_version(1.into()), Some(499));
+        match segment.payload(1.into()).unwrap().get(&key) {
+            Some(PayloadType::Integer(values)) => assert_eq!(values, &vec![499]),
+            other => panic!("unexpected payload {:?}", other),
+        }
+    }
+}
@@ -6,4 +6,6 @@ pub mod simple_segment_updater;
 mod fixtures;
 pub mod simple_segment_searcher;
 // pub mod simple_segment_manager;
-pub mod segment_managers;
\ No newline at end of file
+pub mod segment_managers;
+pub mod group_searcher;pub mod fusion;
+pub mod formula;
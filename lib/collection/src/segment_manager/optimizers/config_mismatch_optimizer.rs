@@ -0,0 +1,204 @@
+use crate::segment_manager::holders::segment_holder::{SegmentId, LockedSegment, LockedSegmentHolder};
+use segment::types::{SegmentConfig, Indexes, SegmentType};
+use crate::segment_manager::optimizers::segment_optimizer::{SegmentOptimizer, OptimizerThresholds, optimized_storage_type};
+use std::path::{PathBuf, Path};
+
+
+/// Re-builds indexed segments, which were built with index parameters different from the current
+/// collection config, and non-appendable segments with a different storage of vectors.
+/// Required to apply changes of the collection config to existing data.
+/// Segments are re-built one at a time through proxy segments, so the collection keeps serving meanwhile.
+pub struct ConfigMismatchOptimizer {
+    thresholds_config: OptimizerThresholds,
+    segments_path: PathBuf,
+    collection_temp_dir: PathBuf,
+    config: SegmentConfig,
+}
+
+
+impl ConfigMismatchOptimizer {
+    pub fn new(thresholds_config: OptimizerThresholds,
+               segments_path: PathBuf,
+               collection_temp_dir: PathBuf,
+               config: SegmentConfig) -> Self {
+        ConfigMismatchOptimizer {
+            thresholds_config,
+            segments_path,
+            collection_temp_dir,
+            config,
+        }
+    }
+
+    /// Index, which would be used for newly indexed segments
+    fn expected_index(&self) -> Indexes {
+        match self.config.index {
+            Indexes::Plain {} => Indexes::default_hnsw(),
+            index => index
+        }
+    }
+
+    fn worst_segment(&self, segments: LockedSegmentHolder) -> Option<(SegmentId, LockedSegment)> {
+        let expected_index = self.expected_index();
+        segments.read().iter()
+            .filter_map(|(idx, segment)| {
+                let segment_entry = segment.get();
+                let read_segment = segment_entry.read();
+                // Proxy segments are already under optimization, cold segments are never optimized
+                if read_segment.segment_type() == SegmentType::Special {
+                    return None;
+                }
+                // Plain segments are not indexed yet, they are handled by the indexing optimizer
+                let is_index_mismatched = match read_segment.config().index {
+                    Indexes::Plain {} => false,
+                    index => index != expected_index,
+                };
+                // Appendable segments are always kept in memory, they are moved to memmap by the indexing optimizer
+                let is_storage_mismatched = !read_segment.is_appendable() && read_segment.config().storage_type != optimized_storage_type(
+                    self.config.storage_type,
+                    read_segment.vectors_count(),
+                    self.thresholds_config.memmap_threshold,
+                );
+                let is_mismatched = is_index_mismatched || is_storage_mismatched;
+                match is_mismatched {
+                    true => Some((*idx, read_segment.vectors_count())),
+                    false => None
+                }
+            })
+            .max_by_key(|(_, num_vectors)| *num_vectors)
+            .and_then(|(idx, _)| Some((idx, segments.read().get(idx).unwrap().clone())))
+    }
+}
+
+
+impl SegmentOptimizer for ConfigMismatchOptimizer {
+    fn name(&self) -> &str {
+        "config_mismatch"
+    }
+
+    fn collection_path(&self) -> &Path {
+        self.segments_path.as_path()
+    }
+
+    fn temp_path(&self) -> &Path {
+        self.collection_temp_dir.as_path()
+    }
+
+    fn base_segment_config(&self) -> SegmentConfig {
+        self.config.clone()
+    }
+
+    fn threshold_config(&self) -> &OptimizerThresholds {
+        &self.thresholds_config
+    }
+
+    fn check_condition(&self, segments: LockedSegmentHolder) -> Vec<SegmentId> {
+        match self.worst_segment(segments) {
+            None => vec![],
+            Some((segment_id, _segment)) => vec![segment_id],
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use segment::common::stop_condition::StopCondition;
+    use crate::segment_manager::holders::segment_holder::SegmentHolder;
+    use std::sync::Arc;
+    use segment::types::{Distance, PayloadIndexType, StorageType};
+    use segment::segment_constructor::segment_constructor::build_segment;
+    use segment::entry::entry_point::SegmentEntry;
+    use tempdir::TempDir;
+    use parking_lot::RwLock;
+
+    fn hnsw_config(m: usize) -> SegmentConfig {
+        SegmentConfig {
+            vector_size: 4,
+            index: Indexes::Hnsw { m, ef_construct: 100 },
+            payload_index: Some(PayloadIndexType::Plain),
+            distance: Distance::Dot,
+            storage_type: StorageType::InMemory,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+        }
+    }
+
+    #[test]
+    fn test_config_mismatch() {
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+
+        let mut segment = build_segment(dir.path(), &hnsw_config(16)).unwrap();
+        segment.upsert_point(100, 1.into(), &vec![1.0, 0.0, 0.0, 0.0]).unwrap();
+        segment.upsert_point(101, 2.into(), &vec![0.0, 1.0, 0.0, 0.0]).unwrap();
+        let segment_id = holder.add(segment);
+        let locked_holder = Arc::new(RwLock::new(holder));
+
+        let thresholds = OptimizerThresholds {
+            memmap_threshold: 1000000,
+            indexing_threshold: 0,
+            payload_indexing_threshold: 1000000,
+        };
+
+        let same_config_optimizer = ConfigMismatchOptimizer::new(
+            thresholds.clone(),
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            hnsw_config(16),
+        );
+        assert!(same_config_optimizer.check_condition(locked_holder.clone()).is_empty());
+
+        let optimizer = ConfigMismatchOptimizer::new(
+            thresholds,
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            hnsw_config(32),
+        );
+        let suggested_to_optimize = optimizer.check_condition(locked_holder.clone());
+        assert_eq!(suggested_to_optimize, vec![segment_id]);
+
+        optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
+        assert!(optimizer.check_condition(locked_holder.clone()).is_empty());
+
+        let vectors_count: usize = locked_holder.read().iter()
+            .map(|(_sid, segment)| segment.get().read().vectors_count())
+            .sum();
+        assert_eq!(vectors_count, 2);
+    }
+
+    #[test]
+    fn test_storage_type_mismatch() {
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+
+        let mut segment = build_segment(dir.path(), &hnsw_config(16)).unwrap();
+        segment.upsert_point(100, 1.into(), &vec![1.0, 0.0, 0.0, 0.0]).unwrap();
+        holder.add(segment);
+        let locked_holder = Arc::new(RwLock::new(holder));
+
+        let optimizer = ConfigMismatchOptimizer::new(
+            OptimizerThresholds {
+                memmap_threshold: 1000000,
+                indexing_threshold: 0,
+                payload_indexing_threshold: 1000000,
+            },
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            SegmentConfig { storage_type: StorageType::Mmap, ..hnsw_config(16) },
+        );
+        let suggested_to_optimize = optimizer.check_condition(locked_holder.clone());
+        assert_eq!(suggested_to_optimize.len(), 1);
+
+        optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
+        assert!(optimizer.check_condition(locked_holder.clone()).is_empty());
+
+        let storage_types: Vec<_> = locked_holder.read().iter()
+            .filter(|(_sid, segment)| segment.get().read().vectors_count() > 0)
+            .map(|(_sid, segment)| segment.get().read().config().storage_type)
+            .collect();
+        assert_eq!(storage_types, vec![StorageType::Mmap]);
+    }
+}
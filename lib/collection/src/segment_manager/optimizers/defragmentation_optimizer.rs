@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use segment::entry::entry_point::SegmentEntry;
+use segment::segment::Segment;
+use segment::segment_constructor::segment_builder::stored_groups;
+use segment::types::{PayloadKeyType, SegmentConfig, SegmentType, SeqNumberType};
+
+use crate::segment_manager::holders::segment_holder::{LockedSegment, LockedSegmentHolder, SegmentId};
+use crate::segment_manager::optimizers::segment_optimizer::{OptimizerThresholds, SegmentOptimizer};
+
+/// Groups of points in a segment
+struct SegmentGroups {
+    groups: HashSet<String>,
+    /// Points of each group are stored next to each other, and points without group are stored after all groups
+    is_grouped: bool,
+}
+
+impl SegmentGroups {
+    fn new(segment: &Segment, group_key: &PayloadKeyType) -> Self {
+        let mut groups = HashSet::new();
+        let mut is_grouped = true;
+        let mut previous: Option<Option<String>> = None;
+        for group in stored_groups(segment, group_key) {
+            if previous.as_ref() == Some(&group) {
+                continue;
+            }
+            match &group {
+                // Group is started again, or started after points without group
+                Some(value) => if !groups.insert(value.clone()) || previous == Some(None) {
+                    is_grouped = false;
+                },
+                None => {}
+            }
+            previous = Some(group);
+        }
+        SegmentGroups { groups, is_grouped }
+    }
+}
+
+/// Co-locates points of the same tenant, defined by a payload field, in multi-tenant collections.
+/// Non-appendable segments, which share a tenant, are joined, and points of each tenant are stored next to each other,
+/// so searches, filtered by a single tenant, read a compact part of a single segment.
+/// Appendable segments are not optimized, as they still receive new points.
+pub struct DefragmentationOptimizer {
+    group_key: PayloadKeyType,
+    thresholds_config: OptimizerThresholds,
+    segments_path: PathBuf,
+    collection_temp_dir: PathBuf,
+    config: SegmentConfig,
+    /// Groups of checked segments with the version of the segment at the moment of the check,
+    /// so unchanged segments are not scanned again
+    checked_segments: Mutex<HashMap<SegmentId, (SeqNumberType, SegmentGroups)>>,
+}
+
+impl DefragmentationOptimizer {
+    pub fn new(group_key: PayloadKeyType,
+               thresholds_config: OptimizerThresholds,
+               segments_path: PathBuf,
+               collection_temp_dir: PathBuf,
+               config: SegmentConfig) -> Self {
+        DefragmentationOptimizer {
+            group_key,
+            thresholds_config,
+            segments_path,
+            collection_temp_dir,
+            config,
+            checked_segments: Default::default(),
+        }
+    }
+}
+
+impl SegmentOptimizer for DefragmentationOptimizer {
+    fn name(&self) -> &str {
+        "defragmentation"
+    }
+
+    fn collection_path(&self) -> &Path {
+        self.segments_path.as_path()
+    }
+
+    fn temp_path(&self) -> &Path {
+        self.collection_temp_dir.as_path()
+    }
+
+    fn base_segment_config(&self) -> SegmentConfig {
+        self.config.clone()
+    }
+
+    fn threshold_config(&self) -> &OptimizerThresholds {
+        &self.thresholds_config
+    }
+
+    fn check_condition(&self, segments: LockedSegmentHolder) -> Vec<SegmentId> {
+        let read_segments = segments.read();
+        let mut checked_segments = self.checked_segments.lock();
+
+        let mut candidates = vec![];
+        for (idx, segment) in read_segments.iter() {
+            // Proxy segments are already under optimization, cold segments are never optimized
+            let segment_arc = match segment {
+                LockedSegment::Original(segment_arc) => segment_arc,
+                LockedSegment::Proxy(_) | LockedSegment::Cold(_) => continue,
+            };
+            let read_segment = segment_arc.read();
+            if read_segment.is_appendable() || read_segment.segment_type() == SegmentType::Special {
+                continue;
+            }
+            let version = read_segment.version();
+            let is_checked = checked_segments.get(idx)
+                .map_or(false, |(checked_version, _)| *checked_version == version);
+            if !is_checked {
+                checked_segments.insert(*idx, (version, SegmentGroups::new(&read_segment, &self.group_key)));
+            }
+            candidates.push(*idx);
+        }
+        checked_segments.retain(|idx, _| candidates.contains(idx));
+
+        // Segments, which share the most fragmented group, are joined
+        let mut group_segments: HashMap<&String, Vec<SegmentId>> = HashMap::new();
+        for idx in candidates.iter() {
+            for group in checked_segments[idx].1.groups.iter() {
+                group_segments.entry(group).or_default().push(*idx);
+            }
+        }
+        if let Some(ids) = group_segments.into_iter()
+            .map(|(_, ids)| ids)
+            .filter(|ids| ids.len() > 1)
+            .max_by_key(|ids| ids.len()) {
+            return ids;
+        }
+
+        // Otherwise points are re-ordered within a single segment
+        candidates.into_iter()
+            .find(|idx| !checked_segments[idx].1.is_grouped)
+            .into_iter()
+            .collect()
+    }
+
+    fn group_key(&self) -> Option<&PayloadKeyType> {
+        Some(&self.group_key)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use segment::common::stop_condition::StopCondition;
+    use crate::segment_manager::holders::segment_holder::SegmentHolder;
+    use std::sync::Arc;
+    use segment::types::{Distance, Indexes, PayloadIndexType, PayloadType, StorageType};
+    use segment::segment_constructor::segment_constructor::build_segment;
+    use tempdir::TempDir;
+    use parking_lot::RwLock;
+
+    fn hnsw_config() -> SegmentConfig {
+        SegmentConfig {
+            vector_size: 4,
+            index: Indexes::Hnsw { m: 16, ef_construct: 100 },
+            payload_index: Some(PayloadIndexType::Plain),
+            distance: Distance::Dot,
+            storage_type: StorageType::InMemory,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+        }
+    }
+
+    fn tenant_segment(path: &Path, first_id: u64) -> Segment {
+        let mut segment = build_segment(path, &hnsw_config()).unwrap();
+        let tenant_key = "tenant".to_owned();
+        for (idx, point_id) in (first_id..first_id + 10).enumerate() {
+            let tenant = if idx % 2 == 0 { "a" } else { "b" };
+            segment.upsert_point(point_id, point_id.into(), &vec![point_id as f32, 0.0, 0.0, 1.0]).unwrap();
+            segment.set_payload(point_id, point_id.into(), &tenant_key, PayloadType::Keyword(vec![tenant.to_owned()])).unwrap();
+        }
+        segment
+    }
+
+    #[test]
+    fn test_defragmentation() {
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+
+        let segment_id_1 = holder.add(tenant_segment(dir.path(), 1));
+        let segment_id_2 = holder.add(tenant_segment(dir.path(), 100));
+        let locked_holder = Arc::new(RwLock::new(holder));
+
+        let optimizer = DefragmentationOptimizer::new(
+            "tenant".to_owned(),
+            OptimizerThresholds {
+                memmap_threshold: 1000000,
+                indexing_threshold: 0,
+                payload_indexing_threshold: 1000000,
+            },
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            hnsw_config(),
+        );
+
+        let mut suggested_to_optimize = optimizer.check_condition(locked_holder.clone());
+        suggested_to_optimize.sort();
+        assert_eq!(suggested_to_optimize, vec![segment_id_1, segment_id_2]);
+
+        optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
+
+        // Tenants are co-located in a single ordered segment
+        assert!(optimizer.check_condition(locked_holder.clone()).is_empty());
+
+        let vectors_count: usize = locked_holder.read().iter()
+            .map(|(_sid, segment)| segment.get().read().vectors_count())
+            .sum();
+        assert_eq!(vectors_count, 20);
+    }
+}
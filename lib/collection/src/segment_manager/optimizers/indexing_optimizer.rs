@@ -53,6 +53,10 @@ impl IndexingOptimizer {
 }
 
 impl SegmentOptimizer for IndexingOptimizer {
+    fn name(&self) -> &str {
+        "indexing"
+    }
+
     fn collection_path(&self) -> &Path {
         self.segments_path.as_path()
     }
@@ -80,6 +84,7 @@ impl SegmentOptimizer for IndexingOptimizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use segment::common::stop_condition::StopCondition;
     use tempdir::TempDir;
     use crate::segment_manager::holders::segment_holder::SegmentHolder;
     use crate::segment_manager::fixtures::random_segment;
@@ -134,6 +139,8 @@ mod tests {
                 payload_index: Some(Default::default()),
                 distance: segment_config.distance,
                 storage_type: StorageType::default(),
+                text_analyzers: Default::default(),
+                flush_policy: None,
             },
         );
 
@@ -156,12 +163,12 @@ mod tests {
         // ------ Plain -> Mmap & Indexed payload
         let suggested_to_optimize = index_optimizer.check_condition(locked_holder.clone());
         assert!(suggested_to_optimize.contains(&large_segment_id));
-        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize).unwrap();
+        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
 
          // ------ Plain -> Indexed payload
         let suggested_to_optimize = index_optimizer.check_condition(locked_holder.clone());
         assert!(suggested_to_optimize.contains(&middle_segment_id));
-        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize).unwrap();
+        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
 
         // ------- Keep smallest segment without changes
         let suggested_to_optimize = index_optimizer.check_condition(locked_holder.clone());
@@ -187,7 +194,7 @@ mod tests {
         }
 
         let insert_point_ops = PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
-            ids: vec![501, 502, 503],
+            ids: vec![501, 502, 503].into_iter().map(|x| x.into()).collect(),
             vectors: vec![
                 vec![1.0, 0.0, 0.5, 0.0],
                 vec![1.0, 0.0, 0.5, 0.5],
@@ -212,14 +219,14 @@ mod tests {
         index_optimizer.thresholds_config.payload_indexing_threshold = 20;
         let suggested_to_optimize = index_optimizer.check_condition(locked_holder.clone());
         assert!(suggested_to_optimize.contains(&small_segment_id));
-        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize).unwrap();
+        index_optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
 
         let new_infos2 = locked_holder.read().iter().map(|(_sid, segment)| segment.get().read().info()).collect_vec();
 
         assert!(new_infos2.len() > new_infos.len(), "Check that new appendable segment was created");
 
         let insert_point_ops = PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
-            ids: vec![601, 602, 603],
+            ids: vec![601, 602, 603].into_iter().map(|x| x.into()).collect(),
             vectors: vec![
                 vec![0.0, 1.0, 0.5, 0.0],
                 vec![0.0, 1.0, 0.5, 0.5],
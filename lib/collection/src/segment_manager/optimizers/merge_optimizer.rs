@@ -3,11 +3,13 @@ use crate::segment_manager::holders::segment_holder::{LockedSegmentHolder, Segme
 use segment::types::{SegmentType, SegmentConfig};
 use itertools::Itertools;
 use std::path::{PathBuf, Path};
+use std::cmp;
 
 
 /// Optimizer that tries to reduce number of segments until it fits configured value
 pub struct MergeOptimizer {
     max_segments: usize,
+    max_segment_size: Option<usize>,
     thresholds_config: OptimizerThresholds,
     segments_path: PathBuf,
     collection_temp_dir: PathBuf,
@@ -17,12 +19,14 @@ pub struct MergeOptimizer {
 impl MergeOptimizer {
     pub fn new(
         max_segments: usize,
+        max_segment_size: Option<usize>,
         thresholds_config: OptimizerThresholds,
         segments_path: PathBuf,
         collection_temp_dir: PathBuf,
         config: SegmentConfig) -> Self {
         return MergeOptimizer {
             max_segments,
+            max_segment_size,
             thresholds_config,
             segments_path,
             collection_temp_dir,
@@ -33,6 +37,10 @@ impl MergeOptimizer {
 
 
 impl SegmentOptimizer for MergeOptimizer {
+    fn name(&self) -> &str {
+        "merge"
+    }
+
     fn collection_path(&self) -> &Path {
         self.segments_path.as_path()
     }
@@ -57,10 +65,14 @@ impl SegmentOptimizer for MergeOptimizer {
             return vec![];
         }
 
-        // Find top-3 smallest segments to join.
-        // We need 3 segments because in this case we can guarantee that total segments number will be less
+        // Merging of N segments produces one new segment, plus temporary segment may be added for updates.
+        // So at least 3 segments are required to guarantee that total segments number will decrease,
+        // and `excess + 2` segments are required to fit into the limit in one optimization.
+        let merge_count = cmp::max(3, read_segments.len() - self.max_segments + 2);
+        let max_segment_size = self.max_segment_size.unwrap_or(usize::MAX);
 
-        read_segments.iter()
+        let mut merged_size: usize = 0;
+        let merged_segments: Vec<_> = read_segments.iter()
             .filter_map(|(idx, segment)| {
                 let segment_entry = segment.get();
                 let read_segment = segment_entry.read();
@@ -70,9 +82,19 @@ impl SegmentOptimizer for MergeOptimizer {
                 }
             })
             .sorted_by_key(|(_, size)| *size)
-            .take(3)
+            .take(merge_count)
+            .take_while(|(_, size)| {
+                merged_size = merged_size.saturating_add(*size);
+                merged_size <= max_segment_size
+            })
             .map(|x| x.0)
-            .collect()
+            .collect();
+
+        // Single segment is not merged with anything
+        if merged_segments.len() < 2 {
+            return vec![];
+        }
+        merged_segments
     }
 }
 
@@ -80,6 +102,8 @@ impl SegmentOptimizer for MergeOptimizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use segment::common::stop_condition::StopCondition;
+    use crate::collection::CollectionError;
     use crate::segment_manager::fixtures::{random_segment};
     use crate::segment_manager::holders::segment_holder::{SegmentHolder, LockedSegment};
     use segment::types::{Distance, Indexes};
@@ -111,7 +135,8 @@ mod tests {
 
 
         let merge_optimizer = MergeOptimizer::new(
-            5,
+            6,
+            None,
             OptimizerThresholds{
                 memmap_threshold: 1000000,
                 indexing_threshold: 1000000,
@@ -125,6 +150,8 @@ mod tests {
                 payload_index: Some(Default::default()),
                 distance: Distance::Dot,
                 storage_type: Default::default(),
+                text_analyzers: Default::default(),
+                flush_policy: None,
             });
 
         let locked_holder = Arc::new(RwLock::new(holder));
@@ -133,6 +160,38 @@ mod tests {
 
         assert_eq!(suggested_for_merge.len(), 3);
 
+        let aggressive_merge_optimizer = MergeOptimizer::new(
+            3,
+            None,
+            merge_optimizer.thresholds_config.clone(),
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            merge_optimizer.config.clone(),
+        );
+        // 7 segments should be merged into 1 + temporary one
+        assert_eq!(aggressive_merge_optimizer.check_condition(locked_holder.clone()).len(), 6);
+
+        let limited_merge_optimizer = MergeOptimizer::new(
+            3,
+            Some(20),
+            merge_optimizer.thresholds_config.clone(),
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            merge_optimizer.config.clone(),
+        );
+        // Only the smallest segments fit into the size limit together
+        assert_eq!(limited_merge_optimizer.check_condition(locked_holder.clone()).len(), 3);
+
+        let tiny_merge_optimizer = MergeOptimizer::new(
+            3,
+            Some(5),
+            merge_optimizer.thresholds_config.clone(),
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            merge_optimizer.config.clone(),
+        );
+        assert!(tiny_merge_optimizer.check_condition(locked_holder.clone()).is_empty());
+
         for segment_in in suggested_for_merge.iter() {
             assert!(segments_to_merge.contains(&segment_in));
         }
@@ -141,11 +200,11 @@ mod tests {
             .map(|sid| {
                 match locked_holder.read().get(*sid).unwrap() {
                     LockedSegment::Original(x) => x.read().current_path.clone(),
-                    LockedSegment::Proxy(_) => panic!("Not expected"),
+                    LockedSegment::Proxy(_) | LockedSegment::Cold(_) => panic!("Not expected"),
                 }
             }).collect_vec();
 
-        merge_optimizer.optimize(locked_holder.clone(), suggested_for_merge).unwrap();
+        merge_optimizer.optimize(locked_holder.clone(), suggested_for_merge, &StopCondition::default()).unwrap();
 
         let after_optimization_segments = locked_holder
             .read()
@@ -174,4 +233,55 @@ mod tests {
         // Check if optimized segments removed from disk
         old_path.into_iter().for_each(|x| assert!(!x.exists()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cancelled_merge() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+
+        let mut holder = SegmentHolder::new();
+        let mut segments_to_merge = vec![];
+        segments_to_merge.push(holder.add(random_segment(dir.path(), 100, 3, 4)));
+        segments_to_merge.push(holder.add(random_segment(dir.path(), 100, 3, 4)));
+        segments_to_merge.push(holder.add(random_segment(dir.path(), 100, 20, 4)));
+
+        let merge_optimizer = MergeOptimizer::new(
+            2,
+            None,
+            OptimizerThresholds {
+                memmap_threshold: 1000000,
+                indexing_threshold: 1000000,
+                payload_indexing_threshold: 1000000,
+            },
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            SegmentConfig {
+                vector_size: 4,
+                index: Indexes::Plain {},
+                payload_index: Some(Default::default()),
+                distance: Distance::Dot,
+                storage_type: Default::default(),
+                text_analyzers: Default::default(),
+                flush_policy: None,
+            });
+
+        let locked_holder = Arc::new(RwLock::new(holder));
+
+        let stop = StopCondition::default();
+        stop.stop();
+        let result = merge_optimizer.optimize(locked_holder.clone(), segments_to_merge, &stop);
+        assert!(matches!(result, Err(CollectionError::Cancelled { .. })));
+
+        // Original segments are back in place of proxies, nothing is lost or left behind
+        let holder_guard = locked_holder.read();
+        assert_eq!(holder_guard.iter().count(), 3);
+        for (_, segment) in holder_guard.iter() {
+            assert!(matches!(segment, LockedSegment::Original(_)));
+        }
+        let total_vectors: usize = holder_guard.iter()
+            .map(|(_, segment)| segment.get().read().vectors_count())
+            .sum();
+        assert_eq!(total_vectors, 3 + 3 + 20);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+}
@@ -2,3 +2,5 @@ pub mod merge_optimizer;
 pub mod segment_optimizer;
 pub mod vacuum_optimizer;
 pub mod indexing_optimizer;
+pub mod config_mismatch_optimizer;
+pub mod defragmentation_optimizer;
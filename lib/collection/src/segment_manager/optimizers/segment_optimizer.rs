@@ -1,5 +1,5 @@
-use segment::types::{PointIdType, PayloadKeyType, SegmentConfig, Indexes, StorageType, PayloadIndexType};
-use crate::collection::CollectionResult;
+use segment::types::{PointIdType, PayloadKeyType, SegmentConfig, Indexes, StorageType, PayloadIndexType, PayloadMigration};
+use crate::collection::{CollectionError, CollectionResult};
 use crate::segment_manager::holders::segment_holder::{SegmentId, LockedSegment, LockedSegmentHolder};
 use std::sync::Arc;
 use segment::segment::Segment;
@@ -12,6 +12,8 @@ use segment::segment_constructor::segment_builder::SegmentBuilder;
 use std::convert::TryInto;
 use std::path::Path;
 use segment::segment_constructor::simple_segment_constructor::build_simple_segment;
+use segment::common::stop_condition::StopCondition;
+use std::fs::remove_dir_all;
 
 
 #[derive(Debug, Clone)]
@@ -21,7 +23,20 @@ pub struct OptimizerThresholds {
     pub payload_indexing_threshold: usize,
 }
 
+/// Storage of vectors of an optimized segment.
+/// Vectors are kept in memory, unless the collection requires memmap or the segment is too large
+pub fn optimized_storage_type(config_storage_type: StorageType, vectors_count: usize, memmap_threshold: usize) -> StorageType {
+    if config_storage_type == StorageType::Mmap || vectors_count >= memmap_threshold {
+        StorageType::Mmap
+    } else {
+        StorageType::InMemory
+    }
+}
+
 pub trait SegmentOptimizer {
+    /// Name of the optimizer, which is reported in the optimizations status
+    fn name(&self) -> &str;
+
     /// Get path of the whole collection
     fn collection_path(&self) -> &Path;
 
@@ -37,6 +52,12 @@ pub trait SegmentOptimizer {
     /// Checks if segment optimization is required
     fn check_condition(&self, segments: LockedSegmentHolder) -> Vec<SegmentId>;
 
+    /// Payload field, which defines groups of points, stored next to each other in the optimized segment.
+    /// If not specified - points are stored in the order of optimized segments
+    fn group_key(&self) -> Option<&PayloadKeyType> {
+        None
+    }
+
     /// Build temp segment
     fn temp_segment(&self) -> CollectionResult<LockedSegment> {
         let config = self.base_segment_config();
@@ -59,11 +80,11 @@ pub trait SegmentOptimizer {
 
         let thresholds = self.threshold_config();
 
-        if total_vectors < thresholds.memmap_threshold {
-            optimized_config.storage_type = StorageType::InMemory;
-        } else {
-            optimized_config.storage_type = StorageType::Mmap;
-        }
+        optimized_config.storage_type = optimized_storage_type(
+            optimized_config.storage_type,
+            total_vectors,
+            thresholds.memmap_threshold,
+        );
 
         if total_vectors < thresholds.indexing_threshold {
             optimized_config.index = Indexes::Plain {};
@@ -89,10 +110,118 @@ pub trait SegmentOptimizer {
     }
 
 
+    /// Build optimized segment from the original segments and apply changes, made through the proxies meanwhile.
+    /// Returns the segment together with the points, which are already deleted from it
+    fn build_optimized_segment(
+        &self,
+        original_segments: &[Arc<RwLock<Segment>>],
+        mut segment_builder: SegmentBuilder,
+        proxy_deleted_points: &RwLock<HashSet<PointIdType>>,
+        proxy_deleted_indexes: &RwLock<HashSet<PayloadKeyType>>,
+        proxy_created_indexes: &RwLock<HashSet<PayloadKeyType>>,
+        stop: &StopCondition,
+    ) -> CollectionResult<(Segment, HashSet<PointIdType>)> {
+        segment_builder.stop = stop.clone();
+
+        match self.group_key() {
+            None => for segment_arc in original_segments.iter() {
+                let segment_guard = segment_arc.read();
+                segment_builder.update_from(&segment_guard)?;
+            },
+            Some(group_key) => {
+                let segment_guards = original_segments.iter().map(|segment_arc| segment_arc.read()).collect_vec();
+                let segment_refs = segment_guards.iter().map(|segment_guard| &**segment_guard).collect_vec();
+                segment_builder.update_from_grouped(&segment_refs, group_key)?;
+            }
+        }
+
+        for field in proxy_deleted_indexes.read().iter() { segment_builder.indexed_fields.remove(field); }
+        for field in proxy_created_indexes.read().iter().cloned() { segment_builder.indexed_fields.insert(field); }
+
+        let mut optimized_segment: Segment = segment_builder.try_into()?;
+
+        // Delete points in 2 steps
+        // First step - delete all points with read lock
+        // Second step - delete all the rest points with full write lock
+        let deleted_points_snapshot: HashSet<PointIdType> = proxy_deleted_points.read().iter().cloned().collect();
+
+        for point_id in deleted_points_snapshot.iter().cloned() {
+            optimized_segment.delete_point(
+                optimized_segment.version(),
+                point_id,
+            ).unwrap();
+        }
+
+        let deleted_indexes = proxy_deleted_indexes.read().iter().cloned().collect_vec();
+        let create_indexes = proxy_created_indexes.read().iter().cloned().collect_vec();
+
+        for delete_field_name in deleted_indexes.iter() {
+            optimized_segment.delete_field_index(optimized_segment.version(), delete_field_name)?;
+        }
+
+        for create_field_name in create_indexes.iter() {
+            optimized_segment.create_field_index(optimized_segment.version(), create_field_name)?;
+        }
+
+        // Last chance to cancel, the optimized segment is not visible to the collection yet
+        if let Err(err) = stop.check() {
+            optimized_segment.drop_data()?;
+            return Err(err.into());
+        }
+
+        Ok((optimized_segment, deleted_points_snapshot))
+    }
+
+    /// Put original segments back in place of their proxies, e.g. if the optimization is cancelled.
+    /// Deletions, index changes and payload migrations, made through the proxies, are applied to the original segments,
+    /// updated points stay in the temp segment
+    fn restore_original_segments(
+        &self,
+        segments: &LockedSegmentHolder,
+        proxy_ids: &[SegmentId],
+        original_segments: &[Arc<RwLock<Segment>>],
+        tmp_segment: LockedSegment,
+        proxy_deleted_points: &RwLock<HashSet<PointIdType>>,
+        proxy_deleted_indexes: &RwLock<HashSet<PayloadKeyType>>,
+        proxy_created_indexes: &RwLock<HashSet<PayloadKeyType>>,
+        proxy_payload_migrations: &[Arc<RwLock<Vec<PayloadMigration>>>],
+    ) -> CollectionResult<()> {
+        let mut write_segments = segments.write();
+
+        for ((proxy_id, segment_arc), payload_migrations) in proxy_ids.iter().zip(original_segments.iter()).zip(proxy_payload_migrations.iter()) {
+            {
+                let mut segment = segment_arc.write();
+                segment.register_payload_migrations(payload_migrations.read().clone())?;
+                let version = segment.version();
+                for point_id in proxy_deleted_points.read().iter() {
+                    segment.delete_point(version, *point_id)?;
+                }
+                for deleted_field_name in proxy_deleted_indexes.read().iter() {
+                    segment.delete_field_index(version, deleted_field_name)?;
+                }
+                for created_field_name in proxy_created_indexes.read().iter() {
+                    segment.create_field_index(version, created_field_name)?;
+                }
+            }
+            write_segments.swap(LockedSegment::Original(segment_arc.clone()), &vec![*proxy_id], false)?;
+        }
+
+        let has_appendable_segments = write_segments.random_appendable_segment().is_some();
+
+        if tmp_segment.get().read().vectors_count() > 0 || !has_appendable_segments {
+            write_segments.add_locked(tmp_segment);
+        } else {
+            tmp_segment.drop_data()?;
+        }
+        Ok(())
+    }
+
     /// Performs optimization of collections's segments, including:
     ///     - Segment rebuilding
     ///     - Segment joining
-    fn optimize(&self, segments: LockedSegmentHolder, ids: Vec<SegmentId>) -> CollectionResult<bool> {
+    /// Optimization is checked for cancellation at safe points. Cancelled optimization is rolled back:
+    /// the original segments are restored and the partially built segment is removed
+    fn optimize(&self, segments: LockedSegmentHolder, ids: Vec<SegmentId>, stop: &StopCondition) -> CollectionResult<bool> {
         let tmp_segment = self.temp_segment()?;
 
         let proxy_deleted_points = Arc::new(RwLock::new(HashSet::<PointIdType>::new()));
@@ -107,15 +236,23 @@ pub trait SegmentOptimizer {
                 .collect()
         };
 
-        let mut segment_builder = self.optimized_segment_builder(&optimizing_segments)?;
+        let segment_builder = self.optimized_segment_builder(&optimizing_segments)?;
+        let builder_temp_path = segment_builder.temp_path.clone();
+
+        // Payload migrations are requested for each wrapped segment separately, types of values might differ
+        let proxy_payload_migrations: Vec<Arc<RwLock<Vec<PayloadMigration>>>> = optimizing_segments.iter()
+            .map(|_| Default::default())
+            .collect();
 
         let proxies: Vec<_> = optimizing_segments.iter()
-            .map(|sg| ProxySegment::new(
+            .zip(proxy_payload_migrations.iter())
+            .map(|(sg, payload_migrations)| ProxySegment::new(
                 sg.clone(),
                 tmp_segment.clone(),
                 proxy_deleted_points.clone(),
                 proxy_deleted_indexes.clone(),
                 proxy_created_indexes.clone(),
+                payload_migrations.clone(),
             )).collect();
 
 
@@ -128,43 +265,45 @@ pub trait SegmentOptimizer {
         };
 
         // ---- SLOW PART -----
-        for segment in optimizing_segments {
-            match segment {
-                LockedSegment::Original(segment_arc) => {
-                    let segment_guard = segment_arc.read();
-                    segment_builder.update_from(&segment_guard)?;
-                }
+        let original_segments: Vec<_> = optimizing_segments.into_iter()
+            .map(|segment| match segment {
+                LockedSegment::Original(segment_arc) => segment_arc,
                 LockedSegment::Proxy(_) => panic!("Attempt to optimize segment which is already currently under optimization. Should never happen"),
+                LockedSegment::Cold(_) => panic!("Attempt to optimize cold segment. Should never happen"),
+            })
+            .collect();
+
+        let built = self.build_optimized_segment(
+            &original_segments,
+            segment_builder,
+            &proxy_deleted_points,
+            &proxy_deleted_indexes,
+            &proxy_created_indexes,
+            stop,
+        );
+
+        let (mut optimized_segment, deleted_points_snapshot) = match built {
+            Ok(built) => built,
+            Err(err @ CollectionError::Cancelled { .. }) => {
+                if builder_temp_path.exists() {
+                    remove_dir_all(&builder_temp_path).map_err(|err| CollectionError::ServiceError {
+                        error: format!("Can't remove data of cancelled optimization: {}", err)
+                    })?;
+                }
+                self.restore_original_segments(
+                    &segments,
+                    &proxy_ids,
+                    &original_segments,
+                    tmp_segment,
+                    &proxy_deleted_points,
+                    &proxy_deleted_indexes,
+                    &proxy_created_indexes,
+                    &proxy_payload_migrations,
+                )?;
+                return Err(err);
             }
-        }
-
-        for field in proxy_deleted_indexes.read().iter() { segment_builder.indexed_fields.remove(field); }
-        for field in proxy_created_indexes.read().iter().cloned() { segment_builder.indexed_fields.insert(field); }
-
-        let mut optimized_segment: Segment = segment_builder.try_into()?;
-
-        // Delete points in 2 steps
-        // First step - delete all points with read lock
-        // Second step - delete all the rest points with full write lock
-        let deleted_points_snapshot: HashSet<PointIdType> = proxy_deleted_points.read().iter().cloned().collect();
-
-        for point_id in deleted_points_snapshot.iter().cloned() {
-            optimized_segment.delete_point(
-                optimized_segment.version,
-                point_id,
-            ).unwrap();
-        }
-
-        let deleted_indexes = proxy_deleted_indexes.read().iter().cloned().collect_vec();
-        let create_indexes = proxy_created_indexes.read().iter().cloned().collect_vec();
-
-        for delete_field_name in deleted_indexes.iter() {
-            optimized_segment.delete_field_index(optimized_segment.version, delete_field_name)?;
-        }
-
-        for create_field_name in create_indexes.iter() {
-            optimized_segment.create_field_index(optimized_segment.version, create_field_name)?;
-        }
+            Err(err) => return Err(err),
+        };
         // ---- SLOW PART ENDS HERE -----
 
         { // This block locks all operations with collection. It should be fast
@@ -173,17 +312,22 @@ pub trait SegmentOptimizer {
             let points_diff = deleted_points_snapshot.difference(&deleted_points);
             for point_id in points_diff.into_iter() {
                 optimized_segment.delete_point(
-                    optimized_segment.version,
+                    optimized_segment.version(),
                     *point_id,
                 ).unwrap();
             }
 
+            // Points of the original segments are copied without migrations, requested through the proxies
+            for payload_migrations in proxy_payload_migrations.iter() {
+                optimized_segment.register_payload_migrations(payload_migrations.read().clone())?;
+            }
+
             for deleted_field_name in proxy_deleted_indexes.read().iter() {
-                optimized_segment.delete_field_index(optimized_segment.version, deleted_field_name)?;
+                optimized_segment.delete_field_index(optimized_segment.version(), deleted_field_name)?;
             }
 
             for created_field_name in proxy_created_indexes.read().iter() {
-                optimized_segment.create_field_index(optimized_segment.version, created_field_name)?;
+                optimized_segment.create_field_index(optimized_segment.version(), created_field_name)?;
             }
 
             write_segments.swap(optimized_segment, &proxy_ids, true)?;
@@ -38,9 +38,14 @@ impl VacuumOptimizer {
             .filter_map(|(idx, segment)| {
                 let segment_entry = segment.get();
                 let read_segment = segment_entry.read();
-                let littered_ratio = read_segment.deleted_count() as f64 / read_segment.vectors_count() as f64;
+                // Deleted vectors are still stored in the segment, but not counted in `vectors_count`
+                let total_vectors = read_segment.vectors_count() + read_segment.deleted_count();
+                if total_vectors == 0 {
+                    return None;
+                }
+                let littered_ratio = read_segment.deleted_count() as f64 / total_vectors as f64;
 
-                let is_big = read_segment.vectors_count() >= self.min_vectors_number;
+                let is_big = total_vectors >= self.min_vectors_number;
                 let is_not_special = read_segment.segment_type() != SegmentType::Special;
                 let is_littered = littered_ratio > self.deleted_threshold;
 
@@ -56,6 +61,10 @@ impl VacuumOptimizer {
 
 
 impl SegmentOptimizer for VacuumOptimizer {
+    fn name(&self) -> &str {
+        "vacuum"
+    }
+
     fn collection_path(&self) -> &Path {
         self.segments_path.as_path()
     }
@@ -84,6 +93,7 @@ impl SegmentOptimizer for VacuumOptimizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use segment::common::stop_condition::StopCondition;
     use crate::segment_manager::holders::segment_holder::SegmentHolder;
     use crate::segment_manager::fixtures::random_segment;
     use itertools::Itertools;
@@ -93,6 +103,50 @@ mod tests {
     use tempdir::TempDir;
     use parking_lot::RwLock;
 
+    #[test]
+    fn test_deleted_ratio() {
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut holder = SegmentHolder::new();
+        let segment_id = holder.add(random_segment(dir.path(), 100, 100, 4));
+        let segment = holder.get(segment_id).unwrap().clone();
+        let locked_holder = Arc::new(RwLock::new(holder));
+
+        let vacuum_optimizer = VacuumOptimizer::new(
+            0.35,
+            50,
+            OptimizerThresholds{
+                memmap_threshold: 1000000,
+                indexing_threshold: 1000000,
+                payload_indexing_threshold: 1000000
+            },
+            dir.path().to_owned(),
+            temp_dir.path().to_owned(),
+            SegmentConfig {
+                vector_size: 4,
+                index: Indexes::Plain {},
+                payload_index: Some(Default::default()),
+                distance: Distance::Dot,
+                storage_type: StorageType::InMemory,
+                text_analyzers: Default::default(),
+                flush_policy: None,
+            },
+        );
+
+        let points = segment.get().read().iter_points().collect_vec();
+
+        // 30% of deleted vectors is below the threshold
+        for point_id in points.iter().take(30) {
+            segment.get().write().delete_point(101, *point_id).unwrap();
+        }
+        assert!(vacuum_optimizer.check_condition(locked_holder.clone()).is_empty());
+
+        for point_id in points.iter().skip(30).take(10) {
+            segment.get().write().delete_point(102, *point_id).unwrap();
+        }
+        assert_eq!(vacuum_optimizer.check_condition(locked_holder.clone()), vec![segment_id]);
+    }
+
     #[test]
     fn test_vacuum_conditions() {
         let temp_dir = TempDir::new("segment_temp_dir").unwrap();
@@ -104,7 +158,7 @@ mod tests {
 
         let original_segment_path = match segment {
             LockedSegment::Original(s) => s.read().current_path.clone(),
-            LockedSegment::Proxy(_) => panic!("Not expected"),
+            LockedSegment::Proxy(_) | LockedSegment::Cold(_) => panic!("Not expected"),
         };
 
         let mut rnd = rand::thread_rng();
@@ -167,6 +221,8 @@ mod tests {
                 payload_index: Some(Default::default()),
                 distance: Distance::Dot,
                 storage_type: StorageType::InMemory,
+                text_analyzers: Default::default(),
+                flush_policy: None,
             },
         );
 
@@ -176,7 +232,7 @@ mod tests {
         // Check that only one segment is selected for optimization
         assert_eq!(suggested_to_optimize.len(), 1);
 
-        vacuum_optimizer.optimize(locked_holder.clone(), suggested_to_optimize).unwrap();
+        vacuum_optimizer.optimize(locked_holder.clone(), suggested_to_optimize, &StopCondition::default()).unwrap();
 
         let after_optimization_segments = locked_holder
             .read()
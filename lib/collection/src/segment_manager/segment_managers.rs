@@ -1,21 +1,36 @@
-use segment::types::{SeqNumberType, ScoredPoint, PointIdType};
+use segment::common::stop_condition::StopCondition;
+use segment::types::{SeqNumberType, ScoredPoint, PointIdType, WithPayload};
 use crate::collection::{CollectionResult};
 use crate::operations::CollectionUpdateOperations;
-use crate::operations::types::{Record, SearchRequest};
+use crate::operations::types::{Record, SearchRequest, CountRequest};
 use std::sync::Arc;
 
 pub trait SegmentSearcher {
     fn search(&self,
               // Request is supposed to be a read only, that is why no mutex used
               request: Arc<SearchRequest>,
+              stop: &StopCondition,
     ) -> CollectionResult<Vec<ScoredPoint>>;
 
+    /// Execute several search requests at once, results are returned in the order of requests.
+    /// Each segment is locked only once for the whole batch.
+    /// Search of all segments is interrupted once `stop` condition is met
+    fn search_batch(
+        &self,
+        requests: Vec<Arc<SearchRequest>>,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>>;
+
     fn retrieve(
         &self,
         points: &Vec<PointIdType>,
-        with_payload: bool,
+        with_payload: &WithPayload,
         with_vector: bool,
     ) -> CollectionResult<Vec<Record>>;
+
+    /// Number of points in all segments, which satisfy the request.
+    /// Exact count includes point, which is temporary stored in several segments, only once.
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize>;
 }
 
 
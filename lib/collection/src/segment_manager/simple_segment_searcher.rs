@@ -1,13 +1,17 @@
-use crate::segment_manager::holders::segment_holder::{LockedSegment, LockedSegmentHolder};
+use crate::segment_manager::holders::segment_holder::{LockedSegment, LockedSegmentHolder, SegmentHolder, SegmentId, StalePoint};
+use tracing::{debug, warn};
 use std::sync::Arc;
 use crate::segment_manager::segment_managers::{SegmentSearcher};
-use crate::collection::CollectionResult;
-use segment::types::{ScoredPoint, PointIdType, SeqNumberType};
+use crate::collection::{CollectionResult, CollectionError};
+use segment::common::stop_condition::StopCondition;
+use segment::types::{ScoredPoint, PointIdType, SeqNumberType, WithPayload};
 use tokio::runtime::Runtime;
 use std::collections::{HashSet, HashMap};
 use segment::spaces::tools::peek_top_scores_iterable;
 use futures::future::try_join_all;
-use crate::operations::types::{Record, SearchRequest};
+use crate::operations::types::{Record, SearchRequest, CountRequest, MAX_SEARCH_OFFSET};
+use crate::numa::NumaPlacement;
+use tracing::{debug_span, Instrument};
 
 /// Simple implementation of segment manager
 ///  - owens segments
@@ -17,28 +21,68 @@ use crate::operations::types::{Record, SearchRequest};
 pub struct SimpleSegmentSearcher {
     pub segments: LockedSegmentHolder,
     pub runtime_handle: Arc<Runtime>,
+    /// Segments, placed on NUMA nodes, are scored by runtimes of their nodes instead of `runtime_handle`
+    pub numa: Arc<NumaPlacement>,
 }
 
 impl SimpleSegmentSearcher {
     pub fn new(segments: LockedSegmentHolder, runtime_handle: Arc<Runtime>) -> Self {
+        Self::with_numa_placement(segments, runtime_handle, Arc::new(NumaPlacement::disabled()))
+    }
+
+    pub fn with_numa_placement(segments: LockedSegmentHolder, runtime_handle: Arc<Runtime>, numa: Arc<NumaPlacement>) -> Self {
         return SimpleSegmentSearcher {
             segments,
             runtime_handle,
+            numa,
         };
     }
 
-    pub async fn search_in_segment(
+    /// Remove outdated copies of the points in background, so search request is not delayed
+    fn schedule_stale_points_removal(&self, stale_points: Vec<StalePoint>) {
+        let segments = self.segments.clone();
+        self.runtime_handle.spawn(async move {
+            match segments.read().remove_stale_points(&stale_points) {
+                Ok(removed) => debug!(removed, "Removed outdated copies of points"),
+                Err(err) => warn!(error = %err, "Failed to remove outdated copies of points"),
+            }
+        });
+    }
+
+    /// Execute all requests of the batch in one segment.
+    /// Segment is locked only once for the whole batch.
+    /// If the segment is placed on a NUMA node, its vectors are moved to the node before the first search
+    pub async fn search_batch_in_segment(
         segment: LockedSegment,
-        request: Arc<SearchRequest>,
-    ) -> CollectionResult<Vec<ScoredPoint>> {
-        let res = segment.get().read().search(
-            &request.vector,
-            request.filter.as_ref(),
-            request.top,
-            request.params.as_ref(),
-        )?;
-
-        Ok(res)
+        requests: Arc<Vec<Arc<SearchRequest>>>,
+        numa_node: Option<usize>,
+        stop: StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        // Request might be stopped, while the search is waiting for a free thread
+        stop.check()?;
+        let segment_arc = segment.get();
+        let read_segment = segment_arc.read();
+        if let Some(node) = numa_node {
+            if let Err(err) = read_segment.bind_to_numa_node(node) {
+                warn!(numa_node = node, error = %err, "Can't place segment on NUMA node");
+            }
+        }
+        let mut results = Vec::with_capacity(requests.len());
+        for request in requests.iter() {
+            let with_payload = request.with_payload.as_ref()
+                .map(WithPayload::from)
+                .unwrap_or_default();
+            results.push(read_segment.search(
+                &request.vector,
+                &with_payload,
+                request.with_vector,
+                request.filter.as_ref(),
+                request.top + request.offset,
+                request.params.as_ref(),
+                &stop,
+            )?);
+        }
+        Ok(results)
     }
 }
 
@@ -46,23 +90,52 @@ impl SegmentSearcher for SimpleSegmentSearcher {
     fn search(
         &self,
         request: Arc<SearchRequest>,
+        stop: &StopCondition,
     ) -> CollectionResult<Vec<ScoredPoint>> {
+        let mut results = self.search_batch(vec![request], stop)?;
+        Ok(results.pop().unwrap_or_default())
+    }
+
+    fn search_batch(
+        &self,
+        requests: Vec<Arc<SearchRequest>>,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        for request in requests.iter() {
+            if request.offset > MAX_SEARCH_OFFSET {
+                return Err(CollectionError::BadRequest {
+                    description: format!("Search offset should not exceed {}", MAX_SEARCH_OFFSET)
+                });
+            }
+        }
+
         let segments = self.segments.read();
 
         let some_segment = segments.iter().next();
 
         if some_segment.is_none() {
-            return Ok(vec![]);
+            return Ok(requests.iter().map(|_| vec![]).collect());
         }
 
         let distance = some_segment.unwrap().1.get().read().config().distance;
 
+        let requests = Arc::new(requests);
+        let segment_ids: Vec<SegmentId> = segments.iter().map(|(id, _segment)| *id).collect();
         let searches: Vec<_> = segments
             .iter()
-            .map(|(_id, segment)|
-                SimpleSegmentSearcher::search_in_segment(segment.clone(), request.clone())
-            )
-            .map(|f| self.runtime_handle.spawn(f))
+            .map(|(id, segment)| {
+                let numa_node = self.numa.segment_node(*id);
+                let search = SimpleSegmentSearcher::search_batch_in_segment(
+                    segment.clone(),
+                    requests.clone(),
+                    numa_node.map(|(node, _runtime)| node),
+                    stop.clone(),
+                ).instrument(debug_span!("segment_search", segment_id = *id));
+                match numa_node {
+                    Some((_node, runtime)) => runtime.spawn(search),
+                    None => self.runtime_handle.spawn(search),
+                }
+            })
             .collect();
 
 
@@ -76,42 +149,114 @@ impl SegmentSearcher for SimpleSegmentSearcher {
             Some(error) => return Err(error),
         }
 
-        let mut seen_idx: HashSet<PointIdType> = HashSet::new();
-
-        let top_scores = peek_top_scores_iterable(
-            all_search_results
-                .into_iter()
-                .map(|x| x.unwrap())
-                .flatten()
-                .filter(|scored| {
-                    let res = seen_idx.contains(&scored.id);
-                    seen_idx.insert(scored.id);
-                    !res
-                }),
-            request.top,
-            &distance,
-        );
+        let segment_results: Vec<(SegmentId, Vec<Vec<ScoredPoint>>)> = segment_ids.into_iter()
+            .zip(all_search_results.into_iter().map(|x| x.unwrap()))
+            .collect();
+
+        // The same point might be stored in several segments, e.g. if operation was interrupted.
+        // Only the latest copy is returned, outdated ones are scheduled for removal.
+        let found_ids: Vec<PointIdType> = segment_results.iter()
+            .flat_map(|(_segment_id, batch_points)| batch_points.iter().flatten().map(|point| point.id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let latest_versions = segments.latest_point_versions(&found_ids)?;
+        drop(segments);
+
+        let mut stale_points: HashSet<StalePoint> = HashSet::new();
+        let mut batch_results: Vec<Vec<ScoredPoint>> = requests.iter().map(|_| vec![]).collect();
+
+        for (segment_id, batch_points) in segment_results {
+            for (request_results, points) in batch_results.iter_mut().zip(batch_points) {
+                for scored in points {
+                    let is_stale = latest_versions.get(&scored.id)
+                        .map_or(false, |latest_version| *latest_version > scored.version);
+                    if is_stale {
+                        stale_points.insert(StalePoint { segment_id, point_id: scored.id, version: scored.version });
+                    } else {
+                        request_results.push(scored);
+                    }
+                }
+            }
+        }
 
-        Ok(top_scores)
+        let results = requests.iter()
+            .zip(batch_results)
+            .map(|(request, latest_points)| {
+                let mut seen_idx: HashSet<PointIdType> = HashSet::new();
+                let top_scores = peek_top_scores_iterable(
+                    latest_points
+                        .into_iter()
+                        .filter(|scored| seen_idx.insert(scored.id)),
+                    request.top + request.offset,
+                    &distance,
+                );
+                top_scores.into_iter().skip(request.offset).collect()
+            })
+            .collect();
+
+        if !stale_points.is_empty() {
+            self.schedule_stale_points_removal(stale_points.into_iter().collect());
+        }
+
+        Ok(results)
     }
 
-    fn retrieve(&self, points: &Vec<PointIdType>, with_payload: bool, with_vector: bool) -> CollectionResult<Vec<Record>> {
+    fn retrieve(&self, points: &Vec<PointIdType>, with_payload: &WithPayload, with_vector: bool) -> CollectionResult<Vec<Record>> {
         let mut point_version: HashMap<PointIdType, SeqNumberType> = Default::default();
         let mut point_records: HashMap<PointIdType, Record> = Default::default();
 
         self.segments.read().read_points(points, |id, segment| {
-            // If this point was not found yet or this segment have later version
-            if !point_version.contains_key(&id) || point_version[&id] < segment.version() {
+            let version = SegmentHolder::point_version(&**segment, id);
+            // If this point was not found yet or this segment have later version of the point
+            if !point_version.contains_key(&id) || point_version[&id] < version {
+                let payload = if with_payload.enable {
+                    let payload = segment.payload(id)?;
+                    match &with_payload.payload_selector {
+                        None => Some(payload),
+                        Some(selector) => Some(selector.process(payload))
+                    }
+                } else {
+                    None
+                };
                 point_records.insert(id, Record {
                     id,
-                    payload: if with_payload { Some(segment.payload(id)?) } else { None },
+                    payload,
                     vector: if with_vector { Some(segment.vector(id)?) } else { None },
                 });
-                point_version.insert(id, segment.version());
+                point_version.insert(id, version);
             }
             Ok(true)
         })?;
-        Ok(point_records.into_iter().map(|(_, r)| r).collect())
+
+        // Keep the order of requested ids
+        let mut seen: HashSet<PointIdType> = Default::default();
+        let records = points.iter()
+            .filter(|id| seen.insert(**id))
+            .filter_map(|id| point_records.remove(id))
+            .collect();
+        Ok(records)
+    }
+
+    /// Exact count is deduplicated across segments, as the same point might be temporarily stored
+    /// in several segments: outdated copies are not removed yet or proxies share the same write segment.
+    /// Estimated count is a sum of segment estimations.
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize> {
+        let segments = self.segments.read();
+        let filter = request.filter.as_ref();
+        if !request.exact || segments.len() == 1 {
+            let mut count = 0;
+            for (_id, segment) in segments.iter() {
+                count += segment.get().read().count(filter, request.exact, stop)?;
+            }
+            return Ok(count);
+        }
+
+        let mut points: HashSet<PointIdType> = Default::default();
+        for (_id, segment) in segments.iter() {
+            points.extend(segment.get().read().read_filtered(None, usize::MAX, filter, stop)?);
+        }
+        Ok(points.len())
     }
 }
 
@@ -119,11 +264,15 @@ impl SegmentSearcher for SimpleSegmentSearcher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
     use tokio::runtime::Runtime;
     use tokio::runtime;
-    use crate::segment_manager::fixtures::build_test_holder;
+    use crate::segment_manager::fixtures::{build_test_holder, empty_segment};
+    use crate::segment_manager::holders::proxy_segment::ProxySegment;
+    use segment::entry::entry_point::SegmentEntry;
     use tempdir::TempDir;
     use parking_lot::RwLock;
+    use segment::types::{Filter, Condition, PayloadSelector};
 
     #[test]
     fn test_segments_search() {
@@ -144,20 +293,131 @@ mod tests {
         let query = vec![1.0, 1.0, 1.0, 1.0];
 
         let req = Arc::new(SearchRequest {
-            vector: query,
+            vector: query.clone(),
             filter: None,
             params: None,
+            with_payload: None,
+            with_vector: false,
             top: 5,
+            offset: 0,
         });
 
-        let result = searcher.search(req).unwrap();
+        let result = searcher.search(req, &StopCondition::default()).unwrap();
 
         // eprintln!("result = {:?}", &result);
 
         assert_eq!(result.len(), 5);
 
-        assert!(result[0].id == 3 || result[0].id == 11);
-        assert!(result[1].id == 3 || result[1].id == 11);
+        assert!(result[0].id == 3.into() || result[0].id == 11.into());
+        assert!(result[1].id == 3.into() || result[1].id == 11.into());
+
+        let req = Arc::new(SearchRequest {
+            vector: query,
+            filter: None,
+            params: None,
+            with_payload: None,
+            with_vector: false,
+            top: 3,
+            offset: 2,
+        });
+
+        let page = searcher.search(req, &StopCondition::default()).unwrap();
+
+        assert_eq!(page.len(), 3);
+        assert_eq!(page.iter().map(|x| x.score).collect::<Vec<_>>(), result[2..].iter().map(|x| x.score).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_search_batch() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let segment_holder = build_test_holder(dir.path());
+
+        let threaded_rt1: Runtime = runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap();
+
+        let searcher = SimpleSegmentSearcher::new(
+            Arc::new(RwLock::new(segment_holder)),
+            Arc::new(threaded_rt1),
+        );
+
+        let ids: HashSet<PointIdType> = vec![1, 2].into_iter().map(|x| x.into()).collect();
+        let requests = vec![
+            Arc::new(SearchRequest {
+                vector: vec![1.0, 1.0, 1.0, 1.0],
+                filter: None,
+                params: None,
+                with_payload: None,
+                with_vector: false,
+                top: 3,
+                offset: 0,
+            }),
+            Arc::new(SearchRequest {
+                vector: vec![1.0, 0.0, 0.0, 0.0],
+                filter: Some(Filter::new_must(Condition::HasId(ids.into()))),
+                params: None,
+                with_payload: None,
+                with_vector: false,
+                top: 5,
+                offset: 0,
+            }),
+        ];
+
+        let batch_results = searcher.search_batch(requests.clone(), &StopCondition::default()).unwrap();
+        assert_eq!(batch_results.len(), 2);
+        assert_eq!(batch_results[1].len(), 2);
+
+        for (request, batch_result) in requests.into_iter().zip(batch_results) {
+            let single_result = searcher.search(request, &StopCondition::default()).unwrap();
+            assert_eq!(
+                batch_result.iter().map(|x| x.id).collect_vec(),
+                single_result.iter().map(|x| x.id).collect_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_outdated_copies() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let mut segment1 = empty_segment(dir.path());
+        let mut segment2 = empty_segment(dir.path());
+        segment1.upsert_point(1, 1.into(), &vec![1.0, 1.0, 1.0, 1.0]).unwrap();
+        segment1.upsert_point(2, 2.into(), &vec![0.5, 0.5, 0.5, 0.5]).unwrap();
+        // Newer copy of the point, which is less similar to the query
+        segment2.upsert_point(3, 1.into(), &vec![0.1, 0.1, 0.1, 0.1]).unwrap();
+
+        let mut holder = SegmentHolder::new();
+        holder.add(segment1);
+        holder.add(segment2);
+
+        let threaded_rt1: Runtime = runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap();
+        let segments = Arc::new(RwLock::new(holder));
+        let searcher = SimpleSegmentSearcher::new(segments.clone(), Arc::new(threaded_rt1));
+
+        let req = Arc::new(SearchRequest {
+            vector: vec![1.0, 1.0, 1.0, 1.0],
+            filter: None,
+            params: None,
+            with_payload: None,
+            with_vector: false,
+            top: 5,
+            offset: 0,
+        });
+
+        let result = searcher.search(req, &StopCondition::default()).unwrap();
+
+        assert_eq!(result.iter().map(|x| x.id).collect_vec(), vec![2.into(), 1.into()]);
+        assert_eq!(result[1].version, 3);
+
+        // Outdated copy is removed in background, so at most one copy is left for explicit removal
+        assert!(segments.read().deduplicate_points().unwrap() <= 1);
+        let copies = segments.read().iter()
+            .filter(|(_id, segment)| segment.get().read().has_point(1.into()))
+            .count();
+        assert_eq!(copies, 1);
     }
 
     #[test]
@@ -174,8 +434,113 @@ mod tests {
             Arc::new(threaded_rt1),
         );
 
-        let records = searcher.retrieve(&vec![1, 2, 3], true, true).unwrap();
+        let records = searcher.retrieve(&vec![3.into(), 1.into(), 2.into(), 1000.into()], &WithPayload::from(true), true).unwrap();
+
+        assert_eq!(records.iter().map(|x| x.id).collect::<Vec<_>>(), vec![3.into(), 1.into(), 2.into()]);
+        assert!(records[0].payload.as_ref().unwrap().contains_key("color"));
+
+        let with_payload = WithPayload {
+            enable: true,
+            payload_selector: Some(PayloadSelector::Exclude(vec!["color".to_owned()])),
+        };
+        let records = searcher.retrieve(&vec![3.into()], &with_payload, false).unwrap();
+
+        assert!(records[0].payload.as_ref().unwrap().is_empty());
+        assert!(records[0].vector.is_none());
+    }
+
+    #[test]
+    fn test_retrieve_outdated_copies() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let mut segment1 = empty_segment(dir.path());
+        let mut segment2 = empty_segment(dir.path());
+        segment1.upsert_point(1, 1.into(), &vec![1.0, 1.0, 1.0, 1.0]).unwrap();
+        segment2.upsert_point(3, 1.into(), &vec![0.5, 0.5, 0.5, 0.5]).unwrap();
+        // Segment with the outdated copy of the point has later version
+        segment1.upsert_point(5, 2.into(), &vec![0.1, 0.1, 0.1, 0.1]).unwrap();
+        assert!(segment1.version() > segment2.version());
+
+        let mut holder = SegmentHolder::new();
+        holder.add(segment1);
+        holder.add(segment2);
+
+        let threaded_rt1: Runtime = runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap();
+        let searcher = SimpleSegmentSearcher::new(Arc::new(RwLock::new(holder)), Arc::new(threaded_rt1));
+
+        let records = searcher.retrieve(&vec![1.into()], &WithPayload::from(false), true).unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].vector, Some(vec![0.5, 0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn test_count() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let segment_holder = build_test_holder(dir.path());
+
+        let threaded_rt1: Runtime = runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap();
+
+        let searcher = SimpleSegmentSearcher::new(
+            Arc::new(RwLock::new(segment_holder)),
+            Arc::new(threaded_rt1),
+        );
+
+        let total = searcher.count(Arc::new(CountRequest { filter: None, exact: true }), &StopCondition::default()).unwrap();
+
+        let ids: HashSet<PointIdType> = vec![1, 2, 3, 11].into_iter().map(|x| x.into()).collect();
+        let filter = Filter::new_must(Condition::HasId(ids.into()));
+        let exact = searcher.count(Arc::new(CountRequest { filter: Some(filter.clone()), exact: true }), &StopCondition::default()).unwrap();
+        assert_eq!(exact, 4);
+
+        let approx = searcher.count(Arc::new(CountRequest { filter: Some(filter), exact: false }), &StopCondition::default()).unwrap();
+        assert!(approx <= total);
+    }
 
-        assert_eq!(records.len(), 3);
+    #[test]
+    fn test_count_overlapping_segments() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let segment_holder = build_test_holder(dir.path());
+
+        let threaded_rt1: Runtime = runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap();
+
+        let segments = Arc::new(RwLock::new(segment_holder));
+        let searcher = SimpleSegmentSearcher::new(segments.clone(), Arc::new(threaded_rt1));
+
+        // Points 4 and 5 are stored in both segments
+        let total = searcher.count(Arc::new(CountRequest { filter: None, exact: true }), &StopCondition::default()).unwrap();
+        assert_eq!(total, 10);
+
+        let ids: HashSet<PointIdType> = vec![1, 4, 5, 11].into_iter().map(|x| x.into()).collect();
+        let filter = Filter::new_must(Condition::HasId(ids.into()));
+        let exact = searcher.count(Arc::new(CountRequest { filter: Some(filter), exact: true }), &StopCondition::default()).unwrap();
+        assert_eq!(exact, 4);
+
+        // Proxies of optimized segments share the same write segment
+        let write_segment = LockedSegment::new(empty_segment(dir.path()));
+        write_segment.get().write().upsert_point(100, 20.into(), &vec![1.0, 1.0, 1.0, 1.0]).unwrap();
+        {
+            let mut holder = segments.write();
+            let segment_ids: Vec<SegmentId> = holder.iter().map(|(id, _)| *id).collect();
+            for segment_id in segment_ids {
+                let proxy = ProxySegment::new(
+                    holder.get(segment_id).unwrap().clone(),
+                    write_segment.clone(),
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                );
+                holder.swap(proxy, &vec![segment_id], false).unwrap();
+            }
+        }
+        let total = searcher.count(Arc::new(CountRequest { filter: None, exact: true }), &StopCondition::default()).unwrap();
+        assert_eq!(total, 11);
     }
 }
\ No newline at end of file
@@ -1,13 +1,14 @@
+use std::cmp::min;
 use std::sync::Mutex;
 use crate::segment_manager::holders::segment_holder::{LockedSegmentHolder};
 use crate::segment_manager::segment_managers::SegmentUpdater;
 use crate::operations::{CollectionUpdateOperations, FieldIndexOperations};
 use crate::collection::{CollectionResult, CollectionError};
-use segment::types::{SeqNumberType, PointIdType, PayloadKeyType};
+use segment::types::{SeqNumberType, PointIdType, PayloadKeyType, PayloadSchemaType, Filter, BatchPoint, TheMap, PayloadType};
 use std::collections::{HashSet, HashMap};
 use crate::operations::types::VectorType;
 
-use crate::operations::point_ops::{PointOperations, PointInsertOperations};
+use crate::operations::point_ops::{PointOperations, PointInsertOperations, PointVectors};
 use crate::operations::payload_ops::{PayloadOps, PayloadInterface};
 
 pub struct SimpleSegmentUpdater {
@@ -38,13 +39,24 @@ impl SimpleSegmentUpdater {
     /// Tries to delete points from all segments, returns number of actually deleted points
     fn delete_points(&self, op_num: SeqNumberType, ids: &Vec<PointIdType>) -> CollectionResult<usize> {
         let res = self.segments.read()
-            .apply_points(op_num, ids, |id, write_segment|
-                write_segment.delete_point(op_num, id),
+            .apply_points_shared(op_num, ids, |id, segment|
+                segment.delete_point(op_num, id),
             )?;
         Ok(res)
     }
 
 
+    /// Deletes points, matched by filter, from all segments. Returns number of deleted points
+    fn delete_points_by_filter(&self, op_num: SeqNumberType, filter: &Filter) -> CollectionResult<usize> {
+        let mut deleted_points = 0;
+        self.segments.read()
+            .apply_segments_shared(op_num, |segment| {
+                deleted_points += segment.delete_filtered(op_num, filter)?;
+                Ok(true)
+            })?;
+        Ok(deleted_points)
+    }
+
     /// Checks point id in each segment, update point if found.
     /// All not found points are inserted into random segment.
     /// Returns: number of updated points.
@@ -71,54 +83,80 @@ impl SimpleSegmentUpdater {
 
         let mut updated_points: HashSet<PointIdType> = Default::default();
         let points_map: HashMap<PointIdType, &VectorType> = ids.iter().cloned().zip(vectors).collect();
+        let payloads_map: HashMap<PointIdType, Option<TheMap<PayloadKeyType, PayloadType>>> = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| (*id, payloads.as_ref()
+                .and_then(|payload_vector| payload_vector[idx].as_ref())
+                .map(|payload| payload.iter()
+                    .map(|(key, value)| (key.clone(), value.to_payload()))
+                    .collect())))
+            .collect();
 
         let segments = self.segments.read();
 
-        // Get points, which presence in segments with higher version
-        self.segments.read().read_points(ids, |id, segment| {
-            if segment.version() > op_num {
-                updated_points.insert(id);
+        // Skip points, which were changed or deleted by newer operations (WAL recovery related)
+        for id in ids {
+            if segments.newest_point_version(*id).map_or(false, |version| version > op_num) {
+                updated_points.insert(*id);
             }
-            Ok(true)
-        })?;
+        }
 
-        // Update points in writable segments
+        // Update points in writable segments.
+        // Payload of the updated points is merged with the existing one and stored in the same batch as the vector,
+        // so the point is never seen with the new vector and the old payload.
         let res = segments.apply_points_to_appendable(
             op_num,
             ids,
             |id, write_segment| {
                 updated_points.insert(id);
-                write_segment.upsert_point(op_num, id, points_map[&id])
+                match &payloads_map[&id] {
+                    None => write_segment.upsert_point(op_num, id, points_map[&id]),
+                    Some(payload) => {
+                        let mut merged_payload = write_segment.payload(id)?;
+                        merged_payload.extend(payload.clone());
+                        let applied = write_segment.upsert_batch(
+                            op_num, &[(id, points_map[&id].clone(), Some(merged_payload))])?;
+                        Ok(applied > 0)
+                    }
+                }
             })?;
 
-        // Insert new points, which was not updated.
-        let new_point_ids = ids
+        // Insert new points, which was not updated, along with their payloads in a single batch.
+        let new_points: Vec<BatchPoint> = ids
             .iter()
-            .cloned()
-            .filter(|x| !updated_points.contains(x));
+            .enumerate()
+            .filter(|(_, id)| !updated_points.contains(id))
+            .map(|(idx, id)| (*id, vectors[idx].clone(), payloads_map[id].clone()))
+            .collect();
 
-        {
+        if !new_points.is_empty() {
             let default_write_segment = segments.random_appendable_segment()
                 .ok_or(CollectionError::ServiceError { error: "No segments exists, expected at least one".to_string() })?;
 
-            let segment_arc = default_write_segment.get();
-            let mut write_segment = segment_arc.write();
-            for point_id in new_point_ids {
-                write_segment.upsert_point(op_num, point_id, points_map[&point_id])?;
-            }
+            default_write_segment.get().write().upsert_batch(op_num, &new_points)?;
         }
 
-        match payloads {
-            Some(payload_vector) => {
-                for (point_id, payload) in ids.iter().zip(payload_vector.iter()) {
-                    if payload.is_some() {
-                        self.set_payload(op_num, payload.as_ref().unwrap(), &vec![*point_id])?;
-                    }
-                }
-            }
-            _ => {}
-        }
+        Ok(res)
+    }
+
+    /// Replace vectors of existing points. Returns error if any of the points is not found.
+    fn update_vectors(&self, op_num: SeqNumberType, points: &Vec<PointVectors>) -> CollectionResult<usize> {
+        let ids: Vec<PointIdType> = points.iter().map(|point| point.id).collect();
+        let points_map: HashMap<PointIdType, &VectorType> = points.iter()
+            .map(|point| (point.id, &point.vector))
+            .collect();
+        let mut updated_points: HashSet<PointIdType> = Default::default();
 
+        let res = self.segments.read().apply_points_to_appendable(
+            op_num,
+            &ids,
+            |id, write_segment| {
+                updated_points.insert(id);
+                write_segment.update_point_vector(op_num, id, points_map[&id])
+            })?;
+
+        SimpleSegmentUpdater::check_unprocessed_points(&ids, &updated_points)?;
         Ok(res)
     }
 
@@ -130,14 +168,14 @@ impl SimpleSegmentUpdater {
     ) -> CollectionResult<usize> {
         let mut updated_points: HashSet<PointIdType> = Default::default();
 
-        let res = self.segments.read().apply_points_to_appendable(
+        let res = self.segments.read().apply_points_to_appendable_shared(
             op_num,
             points,
-            |id, write_segment| {
+            |id, segment| {
                 updated_points.insert(id);
                 let mut res = true;
                 for (key, payload) in payload {
-                    res = write_segment.set_payload(op_num, id, key, payload.to_payload())? && res;
+                    res = segment.set_payload(op_num, id, key, payload.to_payload())? && res;
                 }
                 Ok(res)
             })?;
@@ -156,14 +194,14 @@ impl SimpleSegmentUpdater {
 
         let res = self.segments
             .read()
-            .apply_points_to_appendable(
+            .apply_points_to_appendable_shared(
                 op_num,
                 points,
-                |id, write_segment| {
+                |id, segment| {
                     updated_points.insert(id);
                     let mut res = true;
                     for key in keys {
-                        res = write_segment.delete_payload(op_num, id, key)? && res;
+                        res = segment.delete_payload(op_num, id, key)? && res;
                     }
                     Ok(res)
                 })?;
@@ -180,18 +218,39 @@ impl SimpleSegmentUpdater {
         let mut updated_points: HashSet<PointIdType> = Default::default();
         let res = self.segments
             .read()
-            .apply_points_to_appendable(
+            .apply_points_to_appendable_shared(
                 op_num,
                 points,
-                |id, write_segment| {
+                |id, segment| {
                     updated_points.insert(id);
-                    write_segment.clear_payload(op_num, id)
+                    segment.clear_payload(op_num, id)
                 })?;
 
         SimpleSegmentUpdater::check_unprocessed_points(points, &updated_points)?;
         Ok(res)
     }
 
+    fn migrate_payload_key(
+        &self,
+        op_num: SeqNumberType,
+        key: &PayloadKeyType,
+        rename_to: &Option<PayloadKeyType>,
+        convert_to: &Option<PayloadSchemaType>,
+    ) -> CollectionResult<usize> {
+        let new_key = rename_to.as_ref().unwrap_or(key);
+        let segments = self.segments.read();
+        // All segments are checked first, so the migration is either requested in all of them or rejected
+        segments.apply_segments_shared(op_num, |segment| {
+            if segment.version() > op_num { return Ok(false); }
+            segment.check_payload_migration(op_num, key, new_key, convert_to.as_ref())?;
+            Ok(true)
+        })?;
+        let res = segments.apply_segments(op_num, |write_segment| {
+            write_segment.migrate_payload_key(op_num, key, new_key, convert_to.as_ref())
+        })?;
+        Ok(res)
+    }
+
     fn create_field_index(&self, op_num: SeqNumberType, field_name: &PayloadKeyType) -> CollectionResult<usize> {
         let res = self.segments
             .read()
@@ -213,6 +272,8 @@ impl SimpleSegmentUpdater {
     pub fn process_point_operation(&self, op_num: SeqNumberType, point_operation: PointOperations) -> CollectionResult<usize> {
         match point_operation {
             PointOperations::DeletePoints { ids, .. } => self.delete_points(op_num, &ids),
+            PointOperations::DeletePointsByFilter { filter } => self.delete_points_by_filter(op_num, &filter),
+            PointOperations::UpdateVectors { points } => self.update_vectors(op_num, &points),
             PointOperations::UpsertPoints(operation) => {
                 let (ids, vectors, payloads) = match operation {
                     PointInsertOperations::BatchPoints { ids, vectors, payloads, .. } => {
@@ -252,6 +313,11 @@ impl SimpleSegmentUpdater {
             PayloadOps::ClearPayload {
                 points, ..
             } => self.clear_payload(op_num, points),
+            PayloadOps::MigrateKey {
+                key,
+                rename_to,
+                convert_to,
+            } => self.migrate_payload_key(op_num, key, rename_to, convert_to),
         }
     }
 
@@ -272,6 +338,9 @@ impl SegmentUpdater for SimpleSegmentUpdater {
             CollectionUpdateOperations::PointOperation(point_operation) => self.process_point_operation(op_num, point_operation),
             CollectionUpdateOperations::PayloadOperation(payload_operation) => self.process_payload_operation(op_num, &payload_operation),
             CollectionUpdateOperations::FieldIndexOperation(index_operation) => self.process_field_index_operation(op_num, &index_operation),
+            // Version is never newer than the operation itself, otherwise following operations would be skipped
+            CollectionUpdateOperations::VersionedPointOperation(versioned) =>
+                self.process_point_operation(min(op_num, versioned.version), versioned.operation),
         }
     }
 }
@@ -283,6 +352,7 @@ mod tests {
     use crate::segment_manager::fixtures::{build_searcher};
     use crate::segment_manager::segment_managers::SegmentSearcher;
     use crate::operations::payload_ops::PayloadVariant;
+    use segment::types::{Condition, WithPayload};
     use tempdir::TempDir;
 
     #[test]
@@ -295,7 +365,7 @@ mod tests {
             segments: searcher.segments.clone(),
             update_lock: Mutex::new(false),
         };
-        let points = vec![1, 500];
+        let points = vec![1.into(), 500.into()];
 
         let vectors = vec![
             vec![2., 2., 2., 2.],
@@ -314,32 +384,39 @@ mod tests {
             Err(_) => assert!(false),
         };
 
-        let records = searcher.retrieve(&vec![1, 2, 500], true, true).unwrap();
+        let records = searcher.retrieve(&vec![1.into(), 2.into(), 500.into()], &WithPayload::from(true), true).unwrap();
 
         assert_eq!(records.len(), 3);
 
         for record in records {
             let v = record.vector.unwrap();
 
-            if record.id == 1 {
+            if record.id == 1.into() {
                 assert_eq!(&v, &vec![2., 2., 2., 2.])
             }
-            if record.id == 500 {
+            if record.id == 500.into() {
                 assert_eq!(&v, &vec![2., 0., 2., 0.])
             }
         }
 
-        updater.delete_points(101, &vec![500]).unwrap();
+        updater.delete_points(101, &vec![500.into()]).unwrap();
 
-        let records = searcher.retrieve(&vec![1, 2, 500], true, true).unwrap();
+        let records = searcher.retrieve(&vec![1.into(), 2.into(), 500.into()], &WithPayload::from(true), true).unwrap();
 
         for record in records {
             let _v = record.vector.unwrap();
 
-            if record.id == 500 {
+            if record.id == 500.into() {
                 assert!(false)
             }
         }
+
+        let ids: HashSet<PointIdType> = vec![1, 2, 11].into_iter().map(|x| x.into()).collect();
+        let deleted = updater.delete_points_by_filter(102, &Filter::new_must(Condition::HasId(ids.into()))).unwrap();
+        assert_eq!(deleted, 3);
+
+        let records = searcher.retrieve(&vec![1.into(), 2.into(), 3.into(), 11.into()], &WithPayload::from(false), false).unwrap();
+        assert_eq!(records.len(), 1);
     }
 
     #[test]
@@ -359,14 +436,14 @@ mod tests {
             PayloadInterface::Keyword(PayloadVariant::Value("red".to_string())),
         );
 
-        let points = vec![1, 2, 3];
+        let points = vec![1.into(), 2.into(), 3.into()];
 
         updater.process_payload_operation(100, &PayloadOps::SetPayload {
             payload,
             points: points.clone(),
         }).unwrap();
 
-        let res = searcher.retrieve(&points, true, false).unwrap();
+        let res = searcher.retrieve(&points, &WithPayload::from(true), false).unwrap();
 
         assert_eq!(res.len(), 3);
 
@@ -382,19 +459,19 @@ mod tests {
 
         // Test payload delete
 
-        updater.delete_payload(101, &vec![3], &vec!["color".to_string(), "empty".to_string()]).unwrap();
-        let res = searcher.retrieve(&vec![3], true, false).unwrap();
+        updater.delete_payload(101, &vec![3.into()], &vec!["color".to_string(), "empty".to_string()]).unwrap();
+        let res = searcher.retrieve(&vec![3.into()], &WithPayload::from(true), false).unwrap();
         assert_eq!(res.len(), 1);
         assert!(!res[0].payload.as_ref().unwrap().contains_key("color"));
 
         // Test clear payload
 
-        let res = searcher.retrieve(&vec![2], true, false).unwrap();
+        let res = searcher.retrieve(&vec![2.into()], &WithPayload::from(true), false).unwrap();
         assert_eq!(res.len(), 1);
         assert!(res[0].payload.as_ref().unwrap().contains_key("color"));
 
-        updater.clear_payload(102, &vec![2]).unwrap();
-        let res = searcher.retrieve(&vec![2], true, false).unwrap();
+        updater.clear_payload(102, &vec![2.into()]).unwrap();
+        let res = searcher.retrieve(&vec![2.into()], &WithPayload::from(true), false).unwrap();
         assert_eq!(res.len(), 1);
         assert!(!res[0].payload.as_ref().unwrap().contains_key("color"))
     }
@@ -0,0 +1,669 @@
+use std::fs::{copy, create_dir_all, read_dir};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crossbeam_channel::{Sender, bounded, unbounded};
+use indicatif::ProgressBar;
+use tracing::info;
+use parking_lot::{Mutex, RwLock};
+use tokio::runtime;
+use tokio::runtime::Runtime;
+use tracing::info_span;
+use uuid::Uuid;
+use wal::WalOptions;
+
+use segment::common::stop_condition::StopCondition;
+use segment::entry::entry_point::SegmentEntry;
+use segment::segment_constructor::segment_constructor::{load_segment, load_segment_with_config};
+use segment::segment_constructor::simple_segment_constructor::build_simple_segment;
+use segment::types::{Filter, PayloadKeyType, PointIdType, ScoredPoint, SegmentType, SeqNumberType, WithPayload};
+
+use crate::cold_storage::ColdStorage;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::collection_builder::collection_loader::copy_dir;
+use crate::collection_builder::optimizers_builder::{build_optimizers, OptimizersConfig};
+use crate::config::CollectionConfig;
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::types::{CountRequest, OptimizationInfo, OptimizationStatus, Record, SearchRequest, UpdateResult, UpdateStatus};
+use crate::segment_manager::holders::cold_segment::ColdSegment;
+use crate::segment_manager::holders::segment_holder::{LockedSegment, LockedSegmentHolder, SegmentHolder};
+use crate::segment_manager::segment_managers::{SegmentSearcher, SegmentUpdater};
+use crate::segment_manager::simple_segment_searcher::SimpleSegmentSearcher;
+use crate::segment_manager::simple_segment_updater::SimpleSegmentUpdater;
+use crate::shard::{Shard, ShardId, ShardInfo, ShardOperations};
+use crate::optimization_pool::OptimizationPool;
+use crate::numa::NumaPlacement;
+use crate::update_handler::update_handler::{UpdateHandler, UpdateSignal};
+use crate::update_handler::update_workers::UpdateWorkers;
+use crate::wal::SerdeWal;
+
+const DEFAULT_SEGMENT_NUMBER: usize = 5;
+
+/// Number of points, copied at once during the shard transfer
+const TRANSFER_BATCH_SIZE: usize = 1000;
+
+/// Shard stored on this node. Holds segments and WAL of its points.
+pub struct LocalShard {
+    pub id: ShardId,
+    /// Directory of the shard, where WAL and segments are stored
+    pub path: PathBuf,
+    pub segments: LockedSegmentHolder,
+    /// Service-wide optimizers parameters, used unless collection-specific ones are configured
+    pub default_optimizers_config: OptimizersConfig,
+    pub wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
+    pub searcher: Arc<dyn SegmentSearcher + Sync + Send>,
+    pub update_handler: Arc<UpdateHandler>,
+    pub updater: Arc<dyn SegmentUpdater + Sync + Send>,
+    pub runtime_handle: Arc<Runtime>,
+    pub update_sender: Sender<UpdateSignal>,
+    /// Apply operations to segments after they are written to WAL
+    pub update_workers: UpdateWorkers,
+    /// Operations are queued for workers in the order of their ids
+    update_lock: Mutex<()>,
+}
+
+impl LocalShard {
+    pub fn new(
+        id: ShardId,
+        segment_holder: SegmentHolder,
+        config: &CollectionConfig,
+        shard_path: &Path,
+        wal: SerdeWal<CollectionUpdateOperations>,
+        search_runtime: Arc<Runtime>,  // from service
+        optimization_pool: Arc<OptimizationPool>,  // from service
+        numa: Arc<NumaPlacement>,  // from service
+        default_optimizers_config: &OptimizersConfig,  // from service
+    ) -> Self {
+        let segment_holder = Arc::new(RwLock::new(segment_holder));
+
+        let optimize_runtime = Arc::new(runtime::Builder::new_multi_thread()
+            .max_threads(2)
+            .build().unwrap());
+
+        let locked_wal = Arc::new(Mutex::new(wal));
+
+        let optimizers_config = config.optimizers_config(default_optimizers_config);
+        let flush_policy = config.flush_policy(&optimizers_config);
+
+        let optimizers = build_optimizers(
+            shard_path,
+            &config.params,
+            &optimizers_config,
+        );
+
+        let searcher = SimpleSegmentSearcher::with_numa_placement(
+            segment_holder.clone(),
+            search_runtime,
+            numa,
+        );
+
+        let updater: Arc<dyn SegmentUpdater + Sync + Send> = Arc::new(SimpleSegmentUpdater::new(segment_holder.clone()));
+
+        let (tx, rx) = unbounded();
+
+        let update_workers = UpdateWorkers::new(
+            config.update_workers,
+            config.update_queue_size,
+            config.update_overload_policy,
+            updater.clone(),
+            tx.clone(),
+        );
+
+        let update_handler = Arc::new(UpdateHandler::new(
+            optimizers,
+            rx,
+            optimize_runtime.clone(),
+            optimization_pool,
+            segment_holder.clone(),
+            locked_wal.clone(),
+            flush_policy,
+            optimizers_config.max_optimization_threads,
+            update_workers.pending_operations(),
+        ));
+
+        LocalShard {
+            id,
+            path: shard_path.to_owned(),
+            segments: segment_holder,
+            default_optimizers_config: default_optimizers_config.clone(),
+            wal: locked_wal,
+            searcher: Arc::new(searcher),
+            update_handler,
+            updater,
+            runtime_handle: optimize_runtime,
+            update_sender: tx,
+            update_workers,
+            update_lock: Mutex::new(()),
+        }
+    }
+
+    /// Creates new empty shard with given configuration
+    pub fn build(
+        id: ShardId,
+        shard_path: &Path,
+        wal_options: &WalOptions,
+        config: &CollectionConfig,
+        search_runtime: Arc<Runtime>,
+        optimization_pool: Arc<OptimizationPool>,
+        numa: Arc<NumaPlacement>,
+        default_optimizers_config: &OptimizersConfig,
+    ) -> CollectionResult<Self> {
+        let wal_path = shard_path.join("wal");
+
+        create_dir_all(&wal_path)
+            .or_else(|err| Err(CollectionError::ServiceError {
+                error: format!("Can't create shard directory. Error: {}", err)
+            }))?;
+
+        let segments_path = shard_path.join("segments");
+
+        create_dir_all(&segments_path)
+            .or_else(|err| Err(CollectionError::ServiceError {
+                error: format!("Can't create shard directory. Error: {}", err)
+            }))?;
+
+        let mut segment_holder = SegmentHolder::new();
+
+        for _sid in 0..DEFAULT_SEGMENT_NUMBER {
+            let segment = build_simple_segment(
+                segments_path.as_path(),
+                config.params.vector_size,
+                config.params.distance.clone())?;
+            segment_holder.add(segment);
+        }
+
+        let wal: SerdeWal<CollectionUpdateOperations> = SerdeWal::new(wal_path.to_str().unwrap(), wal_options)?;
+
+        Ok(LocalShard::new(
+            id,
+            segment_holder,
+            config,
+            shard_path,
+            wal,
+            search_runtime,
+            optimization_pool,
+            numa,
+            default_optimizers_config,
+        ))
+    }
+
+    /// Load shard from disk and recover operations, which were not persisted yet, from WAL
+    pub fn load(
+        id: ShardId,
+        shard_path: &Path,
+        wal_options: &WalOptions,
+        config: &CollectionConfig,
+        search_runtime: Arc<Runtime>,
+        optimization_pool: Arc<OptimizationPool>,
+        numa: Arc<NumaPlacement>,
+        default_optimizers_config: &OptimizersConfig,
+        cold_storage: Option<&Arc<ColdStorage>>,
+    ) -> Self {
+        let wal_path = shard_path.join("wal");
+        let segments_path = shard_path.join("segments");
+        let mut segment_holder = SegmentHolder::new();
+
+        let wal: SerdeWal<CollectionUpdateOperations> = SerdeWal::new(wal_path.to_str().unwrap(), wal_options).expect("Can't read WAL");
+
+        let segment_dirs = read_dir(segments_path.as_path())
+            .expect(&format!("Can't read segments directory {}", segments_path.to_str().unwrap()));
+
+        for entry in segment_dirs {
+            let segments_path = entry.unwrap().path();
+            if ColdSegment::is_cold(&segments_path) {
+                let storage = cold_storage.unwrap_or_else(|| panic!(
+                    "Segment {} is offloaded into the cold storage, which is not configured", segments_path.display()
+                ));
+                let segment = ColdSegment::load(&segments_path, storage.clone()).unwrap_or_else(|err| panic!(
+                    "Can't load cold segment from {}, error: {}", segments_path.display(), err
+                ));
+                segment_holder.add(segment);
+                continue;
+            }
+            let segment = match load_segment_with_config(segments_path.as_path(), &config.params) {
+                Ok(x) => x,
+                Err(err) => panic!(
+                    format!("Can't load segments from {}, error: {}", segments_path.to_str().unwrap(), err)
+                ),
+            };
+            segment_holder.add(segment);
+        };
+
+        let shard = LocalShard::new(
+            id,
+            segment_holder,
+            config,
+            shard_path,
+            wal,
+            search_runtime,
+            optimization_pool,
+            numa,
+            default_optimizers_config,
+        );
+
+        shard.replay_wal().unwrap_or_else(|err| panic!("{}", err));
+
+        // Interrupted operations might leave copies of the same point in several segments
+        let removed_duplicates = shard.segments.read().deduplicate_points()
+            .expect("Can't remove duplicated points");
+        if removed_duplicates > 0 {
+            info!(shard_id = shard.id, removed = removed_duplicates, "Removed outdated copies of points");
+        }
+
+        shard.flush().unwrap();
+
+        shard
+    }
+
+    /// Create a copy of the `source` shard, while the source keeps accepting updates.
+    /// WAL of the source is copied first, so the copy assigns the same ids to further operations.
+    /// Then points of the source are copied and WAL operations are replayed over them,
+    /// which brings points, changed during the copy, to the same state.
+    /// Operations received by the source after the WAL copy should be applied separately.
+    /// Copy is checked for cancellation before each batch of points.
+    pub fn build_from(
+        id: ShardId,
+        shard_path: &Path,
+        wal_options: &WalOptions,
+        config: &CollectionConfig,
+        search_runtime: Arc<Runtime>,
+        optimization_pool: Arc<OptimizationPool>,
+        numa: Arc<NumaPlacement>,
+        default_optimizers_config: &OptimizersConfig,
+        source: &Shard,
+        stop: &StopCondition,
+    ) -> CollectionResult<Self> {
+        source.snapshot_wal(&shard_path.join("wal"))?;
+        let shard = LocalShard::build(id, shard_path, wal_options, config, search_runtime, optimization_pool, numa, default_optimizers_config)?;
+
+        // Copied points are not older than any operation kept in the copied WAL
+        let op_num = shard.wal.lock().first_index();
+
+        for field in source.indexed_fields()? {
+            for (_idx, segment) in shard.segments.read().iter() {
+                segment.get().write().create_field_index(op_num, &field)?;
+            }
+        }
+
+        let with_payload = WithPayload::from(true);
+        let mut offset = None;
+        loop {
+            stop.check()?;
+            let mut point_ids = source.read_filtered(offset, TRANSFER_BATCH_SIZE + 1, None, stop)?;
+            offset = if point_ids.len() > TRANSFER_BATCH_SIZE { point_ids.pop() } else { None };
+
+            let segment = shard.segments.read().random_appendable_segment()
+                .ok_or(CollectionError::ServiceError { error: format!("No appendable segments in shard {}", id) })?
+                .get();
+            for record in source.retrieve(&point_ids, &with_payload, true)? {
+                let mut segment = segment.write();
+                segment.upsert_point(op_num, record.id, &record.vector.unwrap_or_default())?;
+                segment.set_full_payload(op_num, record.id, record.payload.unwrap_or_default())?;
+            }
+
+            if offset.is_none() {
+                break;
+            }
+        }
+
+        shard.replay_wal()?;
+        shard.flush()?;
+        Ok(shard)
+    }
+
+    /// Apply operations from WAL, which might be not persisted in segments yet
+    fn replay_wal(&self) -> CollectionResult<()> {
+        let wal = self.wal.lock();
+
+        // WAL is only acknowledged up to the oldest operation, which was pending or not flushed,
+        // so every remaining record is replayed. Operations, which are already applied,
+        // are skipped by versions of the points they change.
+        let replay_from = wal.first_index();
+
+        let bar = ProgressBar::new((wal.first_index() + wal.len()).saturating_sub(replay_from));
+        bar.set_message("Recovering collection");
+
+        for (op_num, update) in wal.read(replay_from) {
+            // Fail only in case of internal error. If wrong formatting - skip
+            match self.updater.update(op_num, update) {
+                Ok(_) => {}
+                Err(err) => match err {
+                    CollectionError::ServiceError { error } => return Err(CollectionError::ServiceError {
+                        error: format!("Can't apply WAL operation: {}", error)
+                    }),
+                    _ => {}
+                }
+            }
+            bar.inc(1);
+        }
+
+        bar.finish();
+        Ok(())
+    }
+
+    /// Copy files of WAL into `wal_path`. WAL should be locked by the caller
+    fn copy_wal(&self, wal_path: &Path) -> CollectionResult<()> {
+        let copy_wal = || -> io::Result<()> {
+            create_dir_all(wal_path)?;
+            for entry in read_dir(self.path.join("wal"))? {
+                let entry = entry?;
+                copy(entry.path(), wal_path.join(entry.file_name()))?;
+            }
+            Ok(())
+        };
+        copy_wal().or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't copy WAL of shard {}. Error: {}", self.id, err)
+        }))
+    }
+
+    /// Queued operations are applied before optimizers are stopped
+    pub fn stop(&self) -> CollectionResult<()> {
+        self.update_handler.stop_optimizations();
+        self.update_workers.stop();
+        self.update_sender.send(UpdateSignal::Stop)?;
+        Ok(())
+    }
+
+    /// Imply interior mutability.
+    /// Operation is written to WAL and queued for update workers, which apply it asynchronously.
+    /// Explicitly waits for result to be updated, if `wait` is requested.
+    /// If `expected_operation_id` is given, operation is only written if it gets exactly this id.
+    fn apply(
+        &self,
+        operation: CollectionUpdateOperations,
+        expected_operation_id: Option<SeqNumberType>,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        let _span = info_span!("shard_update", shard_id = self.id, wait).entered();
+        self.update_handler.check_flush_error()?;
+        // WAL is not locked while waiting for the queue, so flushes are not blocked by the full queue
+        let update_guard = self.update_lock.lock();
+        // Rejected operation should not get into WAL, otherwise it would be applied on the next load
+        self.update_workers.check_overload()?;
+        let operation_id = {
+            let mut wal = self.wal.lock();
+            if let Some(expected_id) = expected_operation_id {
+                let next_id = wal.first_index() + wal.len();
+                if next_id != expected_id {
+                    return Err(CollectionError::ServiceError {
+                        error: format!("Shard {} is out of sync: expected operation {}, received {}", self.id, next_id, expected_id)
+                    });
+                }
+            }
+            let operation_id = wal.write(&operation)?;
+            self.update_workers.mark_pending(operation_id);
+            operation_id
+        };
+        let result_receiver = self.update_workers.submit(operation_id, operation, wait)?;
+        drop(update_guard);
+
+        match result_receiver {
+            None => Ok(UpdateResult { operation_id, status: UpdateStatus::Acknowledged }),
+            Some(receiver) => {
+                let _res: usize = receiver.recv().or_else(|_| Err(CollectionError::ServiceError {
+                    error: format!("Operation {} of shard {} is not applied", operation_id, self.id)
+                }))??;
+                Ok(UpdateResult { operation_id, status: UpdateStatus::Completed })
+            }
+        }
+    }
+}
+
+impl ShardOperations for LocalShard {
+    fn id(&self) -> ShardId {
+        self.id
+    }
+
+    fn update(&self, operation: CollectionUpdateOperations, wait: bool) -> CollectionResult<UpdateResult> {
+        self.apply(operation, None, wait)
+    }
+
+    fn update_replicated(
+        &self,
+        operation_id: SeqNumberType,
+        operation: CollectionUpdateOperations,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        self.apply(operation, Some(operation_id), wait)
+    }
+
+    fn next_operation_id(&self) -> CollectionResult<SeqNumberType> {
+        let wal = self.wal.lock();
+        Ok(wal.first_index() + wal.len())
+    }
+
+    fn read_operations(
+        &self,
+        from: SeqNumberType,
+        limit: usize,
+    ) -> CollectionResult<Vec<(SeqNumberType, CollectionUpdateOperations)>> {
+        let wal = self.wal.lock();
+        if from < wal.first_index() {
+            return Err(CollectionError::ServiceError {
+                error: format!("Operations of shard {} starting from {} are already truncated", self.id, from)
+            });
+        }
+        Ok(wal.read(from).take(limit).collect())
+    }
+
+    /// WAL is locked during the copy, so no operation is written partially
+    fn snapshot_wal(&self, wal_path: &Path) -> CollectionResult<SeqNumberType> {
+        let wal = self.wal.lock();
+        self.copy_wal(wal_path)?;
+        Ok(wal.first_index() + wal.len())
+    }
+
+    /// WAL is locked while segments are saved, so no operation is written or truncated in between.
+    /// Segments might include operations in progress partially, but all of them are kept in the saved WAL.
+    fn snapshot(&self, snapshot_path: &Path) -> CollectionResult<SeqNumberType> {
+        // Same lock order as in flush
+        let segments = self.segments.read();
+        let wal = self.wal.lock();
+
+        let segments_path = snapshot_path.join("segments");
+        create_dir_all(&segments_path).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't create snapshot directory of shard {}. Error: {}", self.id, err)
+        }))?;
+        for (_idx, segment) in segments.iter() {
+            segment.get().read().take_snapshot(&segments_path)?;
+        }
+        self.copy_wal(&snapshot_path.join("wal"))?;
+        Ok(wal.first_index() + wal.len())
+    }
+
+    /// Stored copies of imported points are deleted before the segment is added,
+    /// so searches might miss those points for a moment, but never return them twice.
+    fn import_segment(&self, segment_path: &Path) -> CollectionResult<()> {
+        self.wait_optimized()?;
+
+        let target_path = self.path.join("segments").join(segment_path.file_name().unwrap());
+        copy_dir(segment_path, &target_path).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't copy imported segment into shard {}. Error: {}", self.id, err)
+        }))?;
+        let segment = load_segment(&target_path)?;
+
+        let op_num = self.next_operation_id()?;
+        for (_idx, stored_segment) in self.segments.read().iter() {
+            let stored_segment = stored_segment.get();
+            let stored_segment = stored_segment.read();
+            for point_id in segment.iter_points() {
+                if stored_segment.has_point(point_id) {
+                    stored_segment.delete_point(op_num, point_id)?;
+                }
+            }
+        }
+        self.segments.write().add(segment);
+        self.update_workers.mark_changed();
+        Ok(())
+    }
+
+    /// Segment is uploaded while it is still used, so uploaded data might miss changes made meanwhile.
+    /// Such segments are kept locally and could be offloaded by the next call.
+    fn offload_segments(&self, storage: &Arc<ColdStorage>) -> CollectionResult<usize> {
+        let candidates: Vec<_> = self.segments.read().iter()
+            .filter_map(|(idx, segment)| match segment {
+                LockedSegment::Original(segment_arc) => Some((*idx, segment_arc.clone())),
+                LockedSegment::Proxy(_) | LockedSegment::Cold(_) => None,
+            })
+            .filter(|(_, segment_arc)| {
+                let segment = segment_arc.read();
+                !segment.is_appendable() && segment.segment_type() != SegmentType::Special
+            })
+            .collect();
+
+        let mut offloaded = 0;
+        for (idx, segment_arc) in candidates {
+            let cold_path = self.path.join("segments").join(Uuid::new_v4().to_string());
+            let (mut cold_segment, version) = {
+                let segment = segment_arc.read();
+                (ColdSegment::offload(&segment, &cold_path, storage.clone())?, segment.version())
+            };
+            // Updates are applied under read lock of the holder, so none of them is in progress here
+            let mut segments = self.segments.write();
+            let is_changed = segment_arc.read().version() != version || segments.get(idx).is_none();
+            if is_changed {
+                drop(segments);
+                info!(shard_id = self.id, "Segment is changed during offloading, it is kept locally");
+                cold_segment.drop_data()?;
+                continue;
+            }
+            segments.swap(cold_segment, &vec![idx], true)?;
+            offloaded += 1;
+        }
+        Ok(offloaded)
+    }
+
+    fn warm_up(&self) -> CollectionResult<usize> {
+        let segments: Vec<_> = self.segments.read().iter()
+            .map(|(_, segment)| segment.get())
+            .collect();
+        let mut loaded = 0;
+        for segment in segments {
+            loaded += segment.read().warm_up()?;
+        }
+        Ok(loaded)
+    }
+
+    fn search_batch(&self, requests: Vec<Arc<SearchRequest>>, stop: &StopCondition) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        let _span = info_span!("shard_search", shard_id = self.id, requests = requests.len()).entered();
+        self.searcher.search_batch(requests, stop)
+    }
+
+    fn retrieve(
+        &self,
+        points: &Vec<PointIdType>,
+        with_payload: &WithPayload,
+        with_vector: bool,
+    ) -> CollectionResult<Vec<Record>> {
+        self.searcher.retrieve(points, with_payload, with_vector)
+    }
+
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize> {
+        self.searcher.count(request, stop)
+    }
+
+    fn read_filtered(
+        &self,
+        offset: Option<PointIdType>,
+        limit: usize,
+        filter: Option<&Filter>,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<PointIdType>> {
+        let mut point_ids: Vec<PointIdType> = vec![];
+        for (_idx, segment) in self.segments.read().iter() {
+            point_ids.append(&mut segment.get().read().read_filtered(offset, limit, filter, stop)?);
+        }
+        point_ids.sort_unstable();
+        point_ids.dedup();
+        point_ids.truncate(limit);
+        Ok(point_ids)
+    }
+
+    fn data_version(&self) -> u64 {
+        self.update_workers.data_version()
+    }
+
+    fn info(&self) -> CollectionResult<ShardInfo> {
+        let segments = self.segments.read();
+        let mut info = ShardInfo::default();
+        for (_idx, segment) in segments.iter() {
+            info.segments_count += 1;
+            let segment_info = segment.get().read().info();
+            info.vectors_count += segment_info.num_vectors;
+            info.disk_data_size += segment_info.disk_usage_bytes;
+            info.ram_data_size += segment_info.ram_usage_bytes;
+            info.memory_usage.merge(&segment_info.memory_usage);
+        }
+        info.pending_updates = self.update_workers.queue_depth();
+        Ok(info)
+    }
+
+    fn optimizations(&self) -> CollectionResult<Vec<OptimizationInfo>> {
+        let optimizations = self.update_handler.optimizations();
+        let running = optimizations.running.into_iter()
+            .map(|optimization| (optimization, OptimizationStatus::Running));
+        let pending = optimizations.pending.into_iter()
+            .map(|optimization| (optimization, OptimizationStatus::Pending));
+        Ok(running.chain(pending)
+            .map(|(optimization, status)| OptimizationInfo {
+                shard_id: self.id,
+                optimizer: optimization.optimizer,
+                segments_count: optimization.segments.len(),
+                status,
+            })
+            .collect())
+    }
+
+    fn background_errors(&self) -> CollectionResult<Vec<String>> {
+        Ok(self.update_handler.background_errors())
+    }
+
+    fn indexed_fields(&self) -> CollectionResult<Vec<PayloadKeyType>> {
+        let mut fields: Vec<PayloadKeyType> = vec![];
+        for (_idx, segment) in self.segments.read().iter() {
+            fields.extend(segment.get().read().get_indexed_fields());
+        }
+        fields.sort();
+        fields.dedup();
+        Ok(fields)
+    }
+
+    /// Optimizers are re-configured and started in background,
+    /// so segments which do not correspond to the new config are re-built.
+    fn reconfigure(&self, config: &CollectionConfig) -> CollectionResult<()> {
+        let optimizers_config = config.optimizers_config(&self.default_optimizers_config);
+        let optimizers = build_optimizers(&self.path, &config.params, &optimizers_config);
+        let flush_policy = config.flush_policy(&optimizers_config);
+
+        self.update_sender.send(UpdateSignal::Reconfigure {
+            optimizers,
+            flush_policy,
+            max_optimization_threads: optimizers_config.max_optimization_threads,
+        })?;
+        Ok(())
+    }
+
+    /// Persist all segments and truncate WAL records, which are no longer required for recovery
+    fn flush(&self) -> CollectionResult<()> {
+        UpdateHandler::flush(&self.segments, &self.wal, &self.update_workers.pending_operations())?;
+        self.update_handler.clear_flush_error();
+        Ok(())
+    }
+
+    /// Update worker processes signals in order, so the response to the wait signal
+    /// is only sent after the optimizations triggered by all preceding operations.
+    fn wait_optimized(&self) -> CollectionResult<()> {
+        let (sender, receiver) = bounded(1);
+        self.update_sender.send(UpdateSignal::Wait(sender))?;
+        receiver.recv().or_else(|_| Err(CollectionError::ServiceError {
+            error: format!("Update worker of shard {} is stopped", self.id)
+        }))
+    }
+}
+
+impl Drop for LocalShard {
+    fn drop(&mut self) {
+        self.stop().unwrap(); // Finishes update tasks right before destructor stucks to do so with runtime
+    }
+}
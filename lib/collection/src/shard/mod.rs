@@ -0,0 +1,272 @@
+pub mod local_shard;
+pub mod replica_set;
+pub mod shard_holder;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use segment::common::stop_condition::StopCondition;
+use segment::types::{Condition, Filter, PayloadKeyType, PointIdType, ScoredPoint, SegmentMemoryUsage, SeqNumberType, WithPayload};
+
+use crate::cold_storage::ColdStorage;
+use crate::collection::CollectionResult;
+use crate::config::CollectionConfig;
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::types::{CountRequest, OptimizationInfo, Record, SearchRequest, UpdateResult};
+
+pub type ShardId = u32;
+
+pub type ReplicaId = u32;
+
+/// Directory inside of the collection, where shards are stored
+pub const SHARDS_DIR: &str = "shards";
+
+/// Directory inside of the shard, where additional replicas of the shard are stored
+pub const REPLICAS_DIR: &str = "replicas";
+
+pub fn shard_path(collection_path: &Path, shard_id: ShardId) -> PathBuf {
+    collection_path.join(SHARDS_DIR).join(shard_id.to_string())
+}
+
+pub fn replica_path(shard_path: &Path, replica_id: ReplicaId) -> PathBuf {
+    shard_path.join(REPLICAS_DIR).join(replica_id.to_string())
+}
+
+/// Size statistics of a single shard
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShardInfo {
+    pub vectors_count: usize,
+    pub segments_count: usize,
+    pub disk_data_size: usize,
+    pub ram_data_size: usize,
+    /// RAM used by each component of segments of the shard
+    pub memory_usage: SegmentMemoryUsage,
+    /// Updates, which are accepted, but not applied to segments yet
+    pub pending_updates: usize,
+}
+
+/// Part of the collection, which owns a subset of points.
+/// The collection only interacts with shards through this trait.
+/// All shards are stored locally for now: there is no cluster membership or transport between nodes yet,
+/// so a shard on another node would be another implementation of this trait.
+pub trait ShardOperations {
+    fn id(&self) -> ShardId;
+
+    /// Apply operation, which only affects points of this shard
+    fn update(&self, operation: CollectionUpdateOperations, wait: bool) -> CollectionResult<UpdateResult>;
+
+    /// Apply operation, which was already assigned `operation_id` by another replica of the shard.
+    /// Fails with service error if the shard missed some of the preceding operations.
+    fn update_replicated(
+        &self,
+        operation_id: SeqNumberType,
+        operation: CollectionUpdateOperations,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult>;
+
+    /// Id, which will be assigned to the next operation of the shard
+    fn next_operation_id(&self) -> CollectionResult<SeqNumberType>;
+
+    /// Operations starting from `from`, which are still kept in the WAL.
+    /// Fails if some of them are already truncated.
+    fn read_operations(
+        &self,
+        from: SeqNumberType,
+        limit: usize,
+    ) -> CollectionResult<Vec<(SeqNumberType, CollectionUpdateOperations)>>;
+
+    /// Copy WAL of the shard into `wal_path`, so the copy assigns the same ids to further operations.
+    /// Returns id of the next operation after the copied ones.
+    fn snapshot_wal(&self, wal_path: &Path) -> CollectionResult<SeqNumberType>;
+
+    /// Save consistent copy of the shard data into `snapshot_path`, which could be loaded as a replica of the shard.
+    /// All operations, which might be missing in the saved segments, are saved in the WAL of the copy.
+    /// Returns id of the next operation after the saved ones.
+    fn snapshot(&self, snapshot_path: &Path) -> CollectionResult<SeqNumberType>;
+
+    /// Add a copy of the segment, built outside of the shard, e.g. by a bulk import, bypassing the WAL.
+    /// Points of the segment replace stored points with the same ids.
+    /// Operations, received before, are applied first.
+    fn import_segment(&self, segment_path: &Path) -> CollectionResult<()>;
+
+    /// Move optimized segments, which are no longer appended, into the cold storage.
+    /// Returns number of offloaded segments
+    fn offload_segments(&self, storage: &Arc<ColdStorage>) -> CollectionResult<usize>;
+
+    /// Load memory mapped data of the shard segments into the page cache, see `SegmentEntry::warm_up`.
+    /// Returns number of loaded bytes
+    fn warm_up(&self) -> CollectionResult<usize>;
+
+    /// Execute search requests in this shard only. `offset` of the requests is applied within the shard
+    fn search_batch(&self, requests: Vec<Arc<SearchRequest>>, stop: &StopCondition) -> CollectionResult<Vec<Vec<ScoredPoint>>>;
+
+    fn retrieve(
+        &self,
+        points: &Vec<PointIdType>,
+        with_payload: &WithPayload,
+        with_vector: bool,
+    ) -> CollectionResult<Vec<Record>>;
+
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize>;
+
+    /// Ids of points, starting from `offset` in ascending order, which satisfy the filter
+    fn read_filtered(
+        &self,
+        offset: Option<PointIdType>,
+        limit: usize,
+        filter: Option<&Filter>,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<PointIdType>>;
+
+    /// Counter, which changes whenever points of the shard are changed.
+    /// Results of reads could be reused, while the counter stays the same
+    fn data_version(&self) -> u64;
+
+    fn info(&self) -> CollectionResult<ShardInfo>;
+
+    /// Optimizations of the shard segments, which are running or pending at the moment
+    fn optimizations(&self) -> CollectionResult<Vec<OptimizationInfo>>;
+
+    /// Errors of background processes of the shard, which are not resolved yet
+    fn background_errors(&self) -> CollectionResult<Vec<String>>;
+
+    /// Payload fields, which have index in the shard
+    fn indexed_fields(&self) -> CollectionResult<Vec<PayloadKeyType>>;
+
+    /// Apply changed collection config to the shard
+    fn reconfigure(&self, config: &CollectionConfig) -> CollectionResult<()>;
+
+    /// Persist all changes of the shard
+    fn flush(&self) -> CollectionResult<()>;
+
+    /// Block until all operations, which are already applied to the shard, are processed by optimizers.
+    /// After that the changes are also indexed, if indexing is required by the optimizers config.
+    fn wait_optimized(&self) -> CollectionResult<()>;
+}
+
+pub type Shard = dyn ShardOperations + Sync + Send;
+
+/// Shard, which owns the point. Should not change for the same point and number of shards,
+/// as it defines the placement of already stored points.
+pub fn point_shard(point_id: &PointIdType, shard_number: usize) -> ShardId {
+    let hash = match point_id {
+        PointIdType::NumId(id) => *id,
+        PointIdType::Uuid(uuid) => {
+            let value = uuid.as_u128();
+            (value ^ (value >> 64)) as u64
+        }
+    };
+    (hash % shard_number as u64) as ShardId
+}
+
+/// Value of the shard key, which defines the shard of the point in collections sharded by a payload field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShardKeyValue<'a> {
+    Keyword(&'a str),
+    Integer(i64),
+}
+
+/// Shard, which owns points with the given shard key value. Uses FNV-1a hash of keywords,
+/// as the placement of stored points should not depend on the implementation of the standard hasher.
+pub fn shard_key_shard(value: ShardKeyValue, shard_number: usize) -> ShardId {
+    let hash = match value {
+        ShardKeyValue::Keyword(keyword) => keyword.bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3)),
+        ShardKeyValue::Integer(value) => value as u64,
+    };
+    (hash % shard_number as u64) as ShardId
+}
+
+/// Shard key value, which all points matching the filter have, if the filter requires it by a `must` condition
+pub fn filter_shard_key_value<'a>(filter: &'a Filter, shard_key: &PayloadKeyType) -> Option<ShardKeyValue<'a>> {
+    filter.must.as_ref()?.iter().find_map(|condition| match condition {
+        Condition::Field(field) if &field.key == shard_key => {
+            let field_match = field.r#match.as_ref()?;
+            match (&field_match.keyword, field_match.integer) {
+                (Some(keyword), _) => Some(ShardKeyValue::Keyword(keyword)),
+                (None, Some(value)) => Some(ShardKeyValue::Integer(value)),
+                (None, None) => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Group items by shards of their points, keeping the order of items within each shard.
+/// Empty list of items is assigned to the first shard, so the operation is still acknowledged by some shard.
+pub fn split_by_shard<T>(
+    items: Vec<T>,
+    point_id: impl Fn(&T) -> PointIdType,
+    shard_of: &dyn Fn(&PointIdType) -> ShardId,
+) -> Vec<(ShardId, Vec<T>)> {
+    if items.is_empty() {
+        return vec![(0, items)];
+    }
+    let mut shard_items: BTreeMap<ShardId, Vec<T>> = BTreeMap::new();
+    for item in items {
+        shard_items.entry(shard_of(&point_id(&item)))
+            .or_insert_with(Vec::new)
+            .push(item);
+    }
+    shard_items.into_iter().collect()
+}
+
+/// Copy of the operation for each shard, used for operations which are not bound to specific points
+pub fn broadcast<T: Clone>(operation: T, shard_number: usize) -> Vec<(ShardId, T)> {
+    (0..shard_number as ShardId)
+        .map(|shard_id| (shard_id, operation.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::point_ops::{PointInsertOperations, PointOperations};
+
+    #[test]
+    fn test_split_batch_by_shard() {
+        let operation = CollectionUpdateOperations::PointOperation(
+            PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
+                ids: vec![0, 1, 2, 3, 4].into_iter().map(|x| x.into()).collect(),
+                vectors: vec![vec![0.0], vec![1.0], vec![2.0], vec![3.0], vec![4.0]],
+                payloads: None,
+            })
+        );
+
+        let parts = operation.split_by_shard(2, &|id| point_shard(id, 2));
+        assert_eq!(parts.len(), 2);
+        for (shard_id, part) in parts {
+            match part {
+                CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(
+                    PointInsertOperations::BatchPoints { ids, vectors, .. }
+                )) => {
+                    assert!(ids.iter().all(|id| point_shard(id, 2) == shard_id));
+                    // Vectors follow their ids
+                    for (id, vector) in ids.iter().zip(vectors) {
+                        assert_eq!(*id, (vector[0] as u64).into());
+                    }
+                }
+                _ => panic!("Unexpected operation"),
+            }
+        }
+
+        let filter_operation = CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePointsByFilter { filter: Filter { should: None, must: None, min_should: None, must_not: None } }
+        );
+        assert_eq!(filter_operation.split_by_shard(3, &|id| point_shard(id, 3)).len(), 3);
+
+        let empty_operation = CollectionUpdateOperations::PointOperation(PointOperations::DeletePoints { ids: vec![] });
+        assert_eq!(empty_operation.split_by_shard(3, &|id| point_shard(id, 3)).len(), 1);
+    }
+
+    #[test]
+    fn test_shard_key_shard() {
+        let shard = shard_key_shard(ShardKeyValue::Keyword("tenant_1"), 4);
+        assert!(shard < 4);
+        // Placement is stable
+        assert_eq!(shard, shard_key_shard(ShardKeyValue::Keyword("tenant_1"), 4));
+        assert_eq!(shard_key_shard(ShardKeyValue::Keyword(""), 1), 0);
+        assert_eq!(shard_key_shard(ShardKeyValue::Integer(7), 4), 3);
+    }
+}
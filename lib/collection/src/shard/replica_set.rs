@@ -0,0 +1,631 @@
+use std::collections::BTreeMap;
+use std::fs::{File, remove_dir_all};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use atomicwrites::AtomicFile;
+use atomicwrites::OverwriteBehavior::AllowOverwrite;
+use tracing::warn;
+use parking_lot::{Mutex, RwLock};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use segment::common::stop_condition::StopCondition;
+use segment::types::{Filter, PayloadKeyType, PointIdType, ScoredPoint, SeqNumberType, WithPayload};
+
+use crate::cold_storage::ColdStorage;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::config::CollectionConfig;
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::types::{CountRequest, OptimizationInfo, ReadConsistency, Record, SearchRequest, UpdateResult};
+use crate::shard::{ReplicaId, Shard, ShardId, ShardInfo, ShardOperations, replica_path};
+
+/// File inside of the shard directory, which keeps the state of its replicas
+pub const REPLICA_SET_STATE_FILE: &str = "replica_set.json";
+
+/// Number of operations, read from WAL of the primary at once, while a new replica catches up with it
+const CATCH_UP_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicaState {
+    /// Replica received all operations of the shard and serves requests
+    Active,
+    /// Replica missed some operations. It is excluded from updates and reads until it is recovered
+    Dead,
+}
+
+/// State of the shard as a whole
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardStatus {
+    /// All replicas are active
+    Active,
+    /// Some replicas are dead and should be recovered by a transfer
+    Recovering,
+    /// One of replicas is being replaced with a fresh copy
+    Transferring,
+}
+
+/// Persisted state of the replica set
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+struct ReplicaSetState {
+    /// Replica, which assigns ids to operations of the shard
+    primary: ReplicaId,
+    replicas: BTreeMap<ReplicaId, ReplicaState>,
+}
+
+impl ReplicaSetState {
+    fn is_active(&self, replica_id: ReplicaId) -> bool {
+        self.replicas.get(&replica_id) == Some(&ReplicaState::Active)
+    }
+
+    fn active_replicas(&self) -> Vec<ReplicaId> {
+        self.replicas.iter()
+            .filter(|(_, state)| **state == ReplicaState::Active)
+            .map(|(replica_id, _)| *replica_id)
+            .collect()
+    }
+}
+
+/// Copies of the same shard. Primary replica assigns ids to operations and other replicas
+/// receive the same operations with the same ids, so all active replicas hold the same data.
+/// Update succeeds, if it is acknowledged by at least `write_consistency_factor` replicas.
+/// Reads are distributed between active replicas.
+pub struct ReplicaSet {
+    shard_id: ShardId,
+    /// Directory of the shard, where replicas and their state are stored
+    path: PathBuf,
+    /// Lock order: `state` is acquired before `replicas`
+    state: RwLock<ReplicaSetState>,
+    replicas: RwLock<BTreeMap<ReplicaId, Arc<Shard>>>,
+    /// Updates are applied one at a time, so every replica receives operations in the same order
+    update_lock: Mutex<()>,
+    /// Only one replica is transferred at a time
+    transfer_lock: Mutex<()>,
+    /// Replica transfer is in progress
+    transferring: AtomicBool,
+    read_counter: AtomicUsize,
+    write_consistency_factor: AtomicUsize,
+}
+
+impl ReplicaSet {
+    /// Open replicas of the shard, listed in its state. If there is no state yet,
+    /// `replication_factor` replicas are opened. Replica is stored in `replica_path` of its id.
+    /// Replicas, which do not have all operations of the primary, are marked as dead.
+    pub fn new(
+        shard_id: ShardId,
+        shard_path: &Path,
+        replication_factor: usize,
+        write_consistency_factor: usize,
+        open_replica: impl Fn(&Path) -> CollectionResult<Arc<Shard>>,
+    ) -> CollectionResult<Self> {
+        let state_path = shard_path.join(REPLICA_SET_STATE_FILE);
+        let mut state = if state_path.exists() {
+            Self::load_state(&state_path)?
+        } else {
+            ReplicaSetState {
+                primary: 0,
+                replicas: (0..replication_factor as ReplicaId).map(|replica_id| (replica_id, ReplicaState::Active)).collect(),
+            }
+        };
+
+        let mut replicas: BTreeMap<ReplicaId, Arc<Shard>> = BTreeMap::new();
+        for replica_id in state.replicas.keys() {
+            replicas.insert(*replica_id, open_replica(&replica_path(shard_path, *replica_id))?);
+        }
+
+        // Operations are written to the primary first, so replicas may lag behind after an interruption
+        let primary_next_id = replicas[&state.primary].next_operation_id()?;
+        for replica_id in state.active_replicas() {
+            let next_id = replicas[&replica_id].next_operation_id()?;
+            if next_id != primary_next_id {
+                warn!(replica_id, shard_id, expected_operation_id = primary_next_id, operation_id = next_id, "Replica is out of sync");
+                state.replicas.insert(replica_id, ReplicaState::Dead);
+            }
+        }
+
+        let replica_set = ReplicaSet {
+            shard_id,
+            path: shard_path.to_owned(),
+            state: RwLock::new(state),
+            replicas: RwLock::new(replicas),
+            update_lock: Mutex::new(()),
+            transfer_lock: Mutex::new(()),
+            transferring: AtomicBool::new(false),
+            read_counter: AtomicUsize::new(0),
+            write_consistency_factor: AtomicUsize::new(write_consistency_factor),
+        };
+        replica_set.save_state(&replica_set.state.read())?;
+        Ok(replica_set)
+    }
+
+    pub fn replicas(&self) -> BTreeMap<ReplicaId, Arc<Shard>> {
+        self.replicas.read().clone()
+    }
+
+    pub fn primary(&self) -> ReplicaId {
+        self.state.read().primary
+    }
+
+    pub fn replica_states(&self) -> BTreeMap<ReplicaId, ReplicaState> {
+        self.state.read().replicas.clone()
+    }
+
+    pub fn status(&self) -> ShardStatus {
+        if self.transferring.load(Ordering::SeqCst) {
+            ShardStatus::Transferring
+        } else if self.state.read().replicas.values().any(|state| *state == ReplicaState::Dead) {
+            ShardStatus::Recovering
+        } else {
+            ShardStatus::Active
+        }
+    }
+
+    /// State and replicas are read together, so the replica could not be removed by a transfer in between
+    fn primary_replica(&self) -> (ReplicaId, Arc<Shard>) {
+        let state = self.state.read();
+        let replicas = self.replicas.read();
+        (state.primary, replicas[&state.primary].clone())
+    }
+
+    fn active_replicas(&self) -> Vec<(ReplicaId, Arc<Shard>)> {
+        let state = self.state.read();
+        let replicas = self.replicas.read();
+        state.active_replicas().into_iter()
+            .map(|replica_id| (replica_id, replicas[&replica_id].clone()))
+            .collect()
+    }
+
+    /// Exclude replica from updates and reads. If it is the primary, another active replica is promoted.
+    /// The last active replica could not be excluded, as the shard would be lost.
+    pub fn mark_dead(&self, replica_id: ReplicaId) -> CollectionResult<()> {
+        let mut state = self.state.write();
+        if !state.is_active(replica_id) {
+            return Ok(());
+        }
+
+        let other_active = state.active_replicas().into_iter().find(|other_id| *other_id != replica_id);
+        let promoted = match other_active {
+            None => return Err(CollectionError::ServiceError {
+                error: format!("Replica {} is the last active replica of shard {}", replica_id, self.shard_id)
+            }),
+            Some(other_id) => other_id,
+        };
+
+        let mut new_state = state.clone();
+        new_state.replicas.insert(replica_id, ReplicaState::Dead);
+        if new_state.primary == replica_id {
+            warn!(replica_id = promoted, shard_id = self.shard_id, "Replica is promoted to primary");
+            new_state.primary = promoted;
+        }
+        self.save_state(&new_state)?;
+        *state = new_state;
+        Ok(())
+    }
+
+    /// Replace the replica with a fresh copy of the primary, e.g. to move the replica to another place
+    /// or to recover a dead replica. Returns id of the new replica.
+    ///
+    /// The copy is created by `build_replica` from the source replica in the given directory,
+    /// see `LocalShard::build_from`. Operations, received during the copy, are applied to the new replica
+    /// from WAL of the primary. Only the last of them are applied while updates of the shard are blocked,
+    /// right before the new replica becomes active and the old one is removed.
+    /// Transfer fails if the primary truncates WAL before the new replica catches up with it.
+    /// Transfer could be cancelled with `stop` until updates are blocked. Cancelled or failed transfer
+    /// leaves the old replica in place and removes the partial copy.
+    pub fn transfer_replica(
+        &self,
+        replica_id: ReplicaId,
+        stop: &StopCondition,
+        build_replica: impl FnOnce(&Path, &Shard) -> CollectionResult<Arc<Shard>>,
+    ) -> CollectionResult<ReplicaId> {
+        let _transfer_guard = self.transfer_lock.lock();
+
+        let new_replica_id = {
+            let state = self.state.read();
+            if !state.replicas.contains_key(&replica_id) {
+                return Err(CollectionError::BadRequest {
+                    description: format!("Shard {} has no replica {}", self.shard_id, replica_id)
+                });
+            }
+            state.replicas.keys().max().map_or(0, |max_id| max_id + 1)
+        };
+
+        let new_replica_path = replica_path(&self.path, new_replica_id);
+        // Data of an interrupted transfer
+        if new_replica_path.exists() {
+            remove_dir_all(&new_replica_path).or_else(|err| Err(CollectionError::ServiceError {
+                error: format!("Can't remove {:?}, error: {}", new_replica_path, err)
+            }))?;
+        }
+
+        let transfer = || -> CollectionResult<Option<Arc<Shard>>> {
+            let replica = build_replica(&new_replica_path, self.primary_replica().1.as_ref())?;
+            // Most of the operations, received during the copy, are applied without blocking updates
+            Self::catch_up(self.primary_replica().1.as_ref(), replica.as_ref(), stop)?;
+            stop.check()?;
+
+            // Only a few operations are left, so the transfer is not cancelled after this point
+            let _update_guard = self.update_lock.lock();
+            Self::catch_up(self.primary_replica().1.as_ref(), replica.as_ref(), &StopCondition::default())?;
+
+            let mut state = self.state.write();
+            let mut new_state = state.clone();
+            new_state.replicas.remove(&replica_id);
+            new_state.replicas.insert(new_replica_id, ReplicaState::Active);
+            if new_state.primary == replica_id {
+                new_state.primary = new_replica_id;
+            }
+            self.save_state(&new_state)?;
+            *state = new_state;
+
+            let mut replicas = self.replicas.write();
+            replicas.insert(new_replica_id, replica);
+            Ok(replicas.remove(&replica_id))
+        };
+
+        self.transferring.store(true, Ordering::SeqCst);
+        let transfer_result = transfer();
+        self.transferring.store(false, Ordering::SeqCst);
+        let (cleanup_path, result) = match transfer_result {
+            Ok(old_replica) => {
+                drop(old_replica);
+                (replica_path(&self.path, replica_id), Ok(new_replica_id))
+            }
+            Err(err) => (new_replica_path, Err(err)),
+        };
+        if let Err(err) = remove_dir_all(&cleanup_path) {
+            warn!(path = %cleanup_path.display(), error = %err, "Can't remove replica files");
+        }
+        result
+    }
+
+    /// Apply operations of the `source` replica, which `target` replica does not have yet
+    fn catch_up(source: &Shard, target: &Shard, stop: &StopCondition) -> CollectionResult<()> {
+        loop {
+            stop.check()?;
+            let operations = source.read_operations(target.next_operation_id()?, CATCH_UP_BATCH_SIZE)?;
+            if operations.is_empty() {
+                return Ok(());
+            }
+            for (operation_id, operation) in operations {
+                // Operations, rejected as invalid, are rejected by the source as well
+                if let Err(err @ CollectionError::ServiceError { .. }) = target.update_replicated(operation_id, operation, true) {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    fn load_state(state_path: &Path) -> CollectionResult<ReplicaSetState> {
+        let mut contents = String::new();
+        let mut file = File::open(state_path).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't read {:?}, error: {}", state_path, err)
+        }))?;
+        file.read_to_string(&mut contents).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't read {:?}, error: {}", state_path, err)
+        }))?;
+        serde_json::from_str(&contents).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't parse {:?}, error: {}", state_path, err)
+        }))
+    }
+
+    fn save_state(&self, state: &ReplicaSetState) -> CollectionResult<()> {
+        let state_path = self.path.join(REPLICA_SET_STATE_FILE);
+        let af = AtomicFile::new(&state_path, AllowOverwrite);
+        let state_bytes = serde_json::to_vec(state).unwrap();
+        af.write(|f| {
+            f.write_all(&state_bytes)
+        }).or_else(move |err|
+            Err(CollectionError::ServiceError {
+                error: format!("Can't write {:?}, error: {}", state_path, err)
+            })
+        )?;
+        Ok(())
+    }
+
+    /// Send operation, accepted by the primary under `operation_id`, to other active replicas.
+    /// Replicas, which fail to apply it for reasons other than invalid input, are marked as dead.
+    /// Returns number of replicas, which acknowledged the operation.
+    fn replicate(
+        &self,
+        primary: ReplicaId,
+        operation_id: SeqNumberType,
+        operation: &CollectionUpdateOperations,
+        wait: bool,
+    ) -> CollectionResult<usize> {
+        let mut acknowledged = 0;
+        for (replica_id, replica) in self.active_replicas() {
+            if replica_id == primary {
+                continue;
+            }
+            let result = replica.update_replicated(operation_id, operation.clone(), wait);
+            match result {
+                Err(CollectionError::ServiceError { error }) => {
+                    warn!(replica_id, shard_id = self.shard_id, operation_id, error = %error, "Replica failed to apply operation");
+                    self.mark_dead(replica_id)?;
+                }
+                _ => acknowledged += 1,
+            }
+        }
+        Ok(acknowledged)
+    }
+
+    /// Fail early, if there are not enough active replicas to acknowledge an update
+    fn check_active_replicas(&self) -> CollectionResult<()> {
+        let active_number = self.state.read().active_replicas().len();
+        let required = self.write_consistency_factor.load(Ordering::Relaxed);
+        if active_number < required {
+            return Err(CollectionError::ServiceError {
+                error: format!("Shard {} has {} active replicas, while {} are required to acknowledge an update",
+                               self.shard_id, active_number, required)
+            });
+        }
+        Ok(())
+    }
+
+    /// Operation is not reverted on replicas, which have already applied it
+    fn check_acknowledged(&self, operation_id: SeqNumberType, acknowledged: usize) -> CollectionResult<()> {
+        let required = self.write_consistency_factor.load(Ordering::Relaxed);
+        if acknowledged < required {
+            return Err(CollectionError::ServiceError {
+                error: format!("Operation {} is acknowledged by {} replicas of shard {}, while {} are required",
+                               operation_id, acknowledged, self.shard_id, required)
+            });
+        }
+        Ok(())
+    }
+
+    /// Execute read request on replicas, required by the `consistency`, trying others if some fail with service error.
+    /// If several replicas are read, result of the one with the most received operations is returned,
+    /// so replicas which lag behind do not hide recent changes.
+    pub fn read_consistent<T>(
+        &self,
+        consistency: ReadConsistency,
+        request: impl Fn(&Shard) -> CollectionResult<T>,
+    ) -> CollectionResult<T> {
+        let replica_number = self.state.read().replicas.len();
+        let required = consistency.required_replicas(replica_number);
+        let active_replicas = self.active_replicas();
+        let start = self.read_counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut responded = 0;
+        let mut freshest: Option<(SeqNumberType, T)> = None;
+        let mut last_error = None;
+        for i in 0..active_replicas.len() {
+            if responded == required {
+                break;
+            }
+            let (replica_id, replica) = &active_replicas[(start + i) % active_replicas.len()];
+            let response = request(replica.as_ref()).and_then(|result| {
+                // Freshness only matters, if results of several replicas are compared
+                let next_operation_id = if required > 1 { replica.next_operation_id()? } else { 0 };
+                Ok((next_operation_id, result))
+            });
+            match response {
+                Err(CollectionError::ServiceError { error }) => {
+                    warn!(replica_id, shard_id = self.shard_id, error = %error, "Replica failed to read");
+                    last_error = Some(CollectionError::ServiceError { error });
+                }
+                Err(err) => return Err(err),
+                Ok((next_operation_id, result)) => {
+                    responded += 1;
+                    if freshest.as_ref().map_or(true, |(freshest_id, _)| next_operation_id > *freshest_id) {
+                        freshest = Some((next_operation_id, result));
+                    }
+                }
+            }
+        }
+
+        match freshest {
+            Some((_, result)) if responded >= required => Ok(result),
+            _ if required == 1 => Err(last_error.unwrap_or(CollectionError::ServiceError {
+                error: format!("No active replicas of shard {}", self.shard_id)
+            })),
+            _ => Err(CollectionError::ServiceError {
+                error: format!("Only {} of {} required replicas of shard {} responded", responded, required, self.shard_id)
+            }),
+        }
+    }
+
+    fn read<T>(&self, request: impl Fn(&Shard) -> CollectionResult<T>) -> CollectionResult<T> {
+        self.read_consistent(ReadConsistency::Any, request)
+    }
+}
+
+impl ShardOperations for ReplicaSet {
+    fn id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    /// Operation is applied by the primary first. Operations rejected as invalid still get an id,
+    /// so they are sent to other replicas as well to keep ids of all replicas the same.
+    /// If the primary fails, it is replaced by another active replica and the operation is retried.
+    fn update(&self, operation: CollectionUpdateOperations, wait: bool) -> CollectionResult<UpdateResult> {
+        let _update_guard = self.update_lock.lock();
+        self.check_active_replicas()?;
+        loop {
+            let (primary_id, primary) = self.primary_replica();
+            let operation_id = primary.next_operation_id()?;
+            let can_promote = self.state.read().active_replicas().len() > 1;
+            match primary.update(operation.clone(), wait) {
+                Err(CollectionError::ServiceError { error }) if can_promote => {
+                    warn!(replica_id = primary_id, shard_id = self.shard_id, error = %error, "Primary replica failed");
+                    self.mark_dead(primary_id)?;
+                }
+                result => {
+                    if result.is_ok() || primary.next_operation_id()? > operation_id {
+                        let acknowledged = 1 + self.replicate(primary_id, operation_id, &operation, wait)?;
+                        if result.is_ok() {
+                            self.check_acknowledged(operation_id, acknowledged)?;
+                        }
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    fn update_replicated(
+        &self,
+        operation_id: SeqNumberType,
+        operation: CollectionUpdateOperations,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        let _update_guard = self.update_lock.lock();
+        self.check_active_replicas()?;
+        let (primary_id, primary) = self.primary_replica();
+        let result = primary.update_replicated(operation_id, operation.clone(), wait);
+        if result.is_ok() || primary.next_operation_id()? > operation_id {
+            let acknowledged = 1 + self.replicate(primary_id, operation_id, &operation, wait)?;
+            if result.is_ok() {
+                self.check_acknowledged(operation_id, acknowledged)?;
+            }
+        }
+        result
+    }
+
+    fn next_operation_id(&self) -> CollectionResult<SeqNumberType> {
+        self.primary_replica().1.next_operation_id()
+    }
+
+    fn read_operations(
+        &self,
+        from: SeqNumberType,
+        limit: usize,
+    ) -> CollectionResult<Vec<(SeqNumberType, CollectionUpdateOperations)>> {
+        self.primary_replica().1.read_operations(from, limit)
+    }
+
+    fn snapshot_wal(&self, wal_path: &Path) -> CollectionResult<SeqNumberType> {
+        self.primary_replica().1.snapshot_wal(wal_path)
+    }
+
+    /// Snapshot of the primary is saved as the first replica. Transfers are blocked meanwhile,
+    /// so the primary is not removed during the copy.
+    fn snapshot(&self, snapshot_path: &Path) -> CollectionResult<SeqNumberType> {
+        let _transfer_guard = self.transfer_lock.lock();
+        self.primary_replica().1.snapshot(&replica_path(snapshot_path, 0))
+    }
+
+    /// Segment is added to all active replicas, while updates of the shard are blocked.
+    /// Replicas, which fail to add it, are marked as dead, unless it is the primary.
+    fn import_segment(&self, segment_path: &Path) -> CollectionResult<()> {
+        let _update_guard = self.update_lock.lock();
+        let (primary_id, primary) = self.primary_replica();
+        primary.import_segment(segment_path)?;
+        for (replica_id, replica) in self.active_replicas() {
+            if replica_id == primary_id {
+                continue;
+            }
+            if let Err(err) = replica.import_segment(segment_path) {
+                warn!(replica_id, shard_id = self.shard_id, error = %err, "Replica failed to import segment");
+                self.mark_dead(replica_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Each active replica offloads its own segments, since segments of replicas are built independently
+    fn offload_segments(&self, storage: &Arc<ColdStorage>) -> CollectionResult<usize> {
+        let mut offloaded = 0;
+        for (_replica_id, replica) in self.active_replicas() {
+            offloaded += replica.offload_segments(storage)?;
+        }
+        Ok(offloaded)
+    }
+
+    fn warm_up(&self) -> CollectionResult<usize> {
+        let mut loaded = 0;
+        for (_replica_id, replica) in self.active_replicas() {
+            loaded += replica.warm_up()?;
+        }
+        Ok(loaded)
+    }
+
+    fn search_batch(&self, requests: Vec<Arc<SearchRequest>>, stop: &StopCondition) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        self.read(|replica| replica.search_batch(requests.clone(), stop))
+    }
+
+    fn retrieve(
+        &self,
+        points: &Vec<PointIdType>,
+        with_payload: &WithPayload,
+        with_vector: bool,
+    ) -> CollectionResult<Vec<Record>> {
+        self.read(|replica| replica.retrieve(points, with_payload, with_vector))
+    }
+
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize> {
+        self.read(|replica| replica.count(request.clone(), stop))
+    }
+
+    fn read_filtered(
+        &self,
+        offset: Option<PointIdType>,
+        limit: usize,
+        filter: Option<&Filter>,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<PointIdType>> {
+        self.read(|replica| replica.read_filtered(offset, limit, filter, stop))
+    }
+
+    /// Statistics of the primary replica, so copies of the same points are not counted several times
+    /// Reads are served by any of the replicas, so changes of each of them are counted
+    fn data_version(&self) -> u64 {
+        self.replicas.read().values().map(|replica| replica.data_version()).sum()
+    }
+
+    fn info(&self) -> CollectionResult<ShardInfo> {
+        self.primary_replica().1.info()
+    }
+
+    fn optimizations(&self) -> CollectionResult<Vec<OptimizationInfo>> {
+        self.primary_replica().1.optimizations()
+    }
+
+    /// Dead replicas are reported along with errors of active ones
+    fn background_errors(&self) -> CollectionResult<Vec<String>> {
+        let states = self.replica_states();
+        let mut errors = vec![];
+        for (replica_id, replica) in self.replicas() {
+            if states.get(&replica_id) != Some(&ReplicaState::Active) {
+                errors.push(format!("Replica {} is dead", replica_id));
+                continue;
+            }
+            errors.extend(replica.background_errors()?.into_iter()
+                .map(|err| format!("Replica {}: {}", replica_id, err)));
+        }
+        Ok(errors)
+    }
+
+    fn indexed_fields(&self) -> CollectionResult<Vec<PayloadKeyType>> {
+        self.read(|replica| replica.indexed_fields())
+    }
+
+    fn reconfigure(&self, config: &CollectionConfig) -> CollectionResult<()> {
+        self.write_consistency_factor.store(config.write_consistency_factor, Ordering::Relaxed);
+        for replica in self.replicas().values() {
+            replica.reconfigure(config)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> CollectionResult<()> {
+        for (_replica_id, replica) in self.active_replicas() {
+            replica.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Reads might be served by any active replica, so all of them should be optimized
+    fn wait_optimized(&self) -> CollectionResult<()> {
+        for (_replica_id, replica) in self.active_replicas() {
+            replica.wait_optimized()?;
+        }
+        Ok(())
+    }
+}
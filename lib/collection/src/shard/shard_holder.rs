@@ -0,0 +1,428 @@
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use segment::common::stop_condition::StopCondition;
+use segment::spaces::tools::peek_top_scores_iterable;
+use segment::types::{Distance, Filter, PayloadKeyType, PointIdType, ScoredPoint, SeqNumberType, WithPayload};
+
+use crate::cold_storage::ColdStorage;
+use crate::collection::{CollectionError, CollectionResult};
+use crate::config::CollectionConfig;
+use crate::operations::{CollectionUpdateOperations, VersionedPointOperation};
+use crate::operations::types::{CountRequest, MAX_SEARCH_OFFSET, OptimizationInfo, OptimizationStatus, ReadConsistency, Record, SearchRequest, ShardTelemetry, UpdateResult};
+use crate::segment_manager::segment_managers::SegmentSearcher;
+use crate::operations::point_ops::PointOperations;
+use crate::shard::{ShardId, ShardInfo, ShardOperations, filter_shard_key_value, point_shard, shard_key_shard, shard_path, split_by_shard};
+use crate::snapshot_manifest::ShardWatermark;
+use crate::shard::replica_set::ReplicaSet;
+
+/// All shards of the collection. Routes updates to shards, which own affected points,
+/// and combines results of read requests from all shards.
+pub struct ShardHolder {
+    shards: Vec<Arc<ReplicaSet>>,
+    distance: Distance,
+    /// Payload field, which defines shards of points. If not specified, points are placed by their ids
+    shard_key: Option<PayloadKeyType>,
+    /// Updates hold it shared while they are sent to shards, snapshots hold it exclusively,
+    /// so an update, which affects several shards, is either saved in all of them or in none
+    snapshot_lock: RwLock<()>,
+}
+
+impl ShardHolder {
+    pub fn new(shards: Vec<Arc<ReplicaSet>>, distance: Distance, shard_key: Option<PayloadKeyType>) -> Self {
+        ShardHolder { shards, distance, shard_key, snapshot_lock: RwLock::new(()) }
+    }
+
+    pub fn shards(&self) -> &Vec<Arc<ReplicaSet>> {
+        &self.shards
+    }
+
+    /// Send parts of the operation to shards, which own affected points.
+    /// Operation id is assigned by each shard independently, the largest one is reported.
+    pub fn update(&self, operation: CollectionUpdateOperations, wait: bool) -> CollectionResult<UpdateResult> {
+        self.update_parts(operation, None, wait)
+    }
+
+    /// Same as `update`, but parts of the point operation are applied as `VersionedPointOperation`s
+    /// with the given next operation id of their shard, so points, changed by later operations, are left as they are.
+    /// `next_operation_ids` are indexed by shard, see `next_operation_ids`
+    pub fn update_unchanged_since(
+        &self,
+        operation: PointOperations,
+        next_operation_ids: &[SeqNumberType],
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        if next_operation_ids.len() != self.shards.len() {
+            return Err(CollectionError::BadRequest {
+                description: format!(
+                    "Expected operation ids of {} shards, got {}",
+                    self.shards.len(),
+                    next_operation_ids.len()
+                )
+            });
+        }
+        self.update_parts(CollectionUpdateOperations::PointOperation(operation), Some(next_operation_ids), wait)
+    }
+
+    /// Id, which will be assigned to the next operation of each shard
+    pub fn next_operation_ids(&self) -> CollectionResult<Vec<SeqNumberType>> {
+        self.shards.iter().map(|shard| shard.next_operation_id()).collect()
+    }
+
+    fn update_parts(
+        &self,
+        operation: CollectionUpdateOperations,
+        next_operation_ids: Option<&[SeqNumberType]>,
+        wait: bool,
+    ) -> CollectionResult<UpdateResult> {
+        let _snapshot_guard = self.snapshot_lock.read();
+        let shard_number = self.shards.len();
+        let parts = match &self.shard_key {
+            None => operation.split_by_shard(shard_number, &|point_id| point_shard(point_id, shard_number)),
+            Some(shard_key) => self.split_by_shard_key(operation, shard_key)?,
+        };
+
+        let mut result: Option<UpdateResult> = None;
+        for (shard_id, shard_operation) in parts {
+            let shard_operation = match (next_operation_ids, shard_operation) {
+                (Some(next_operation_ids), CollectionUpdateOperations::PointOperation(operation)) =>
+                    CollectionUpdateOperations::VersionedPointOperation(VersionedPointOperation {
+                        version: next_operation_ids[shard_id as usize],
+                        operation,
+                    }),
+                (_, shard_operation) => shard_operation,
+            };
+            let shard_result = self.shards[shard_id as usize].update(shard_operation, wait)?;
+            result = Some(match result {
+                None => shard_result,
+                Some(prev_result) => UpdateResult {
+                    operation_id: max(prev_result.operation_id, shard_result.operation_id),
+                    status: shard_result.status,
+                }
+            });
+        }
+        Ok(result.expect("Operation is sent to at least one shard"))
+    }
+
+    /// Save all shards into `snapshot_path` at a single point of the collection history.
+    /// Updates are blocked until all shards are saved. Returns positions of the saved shards.
+    /// Cancellation is checked before each shard, saved shards are left to the caller to clean up
+    pub fn snapshot(&self, snapshot_path: &Path, stop: &StopCondition) -> CollectionResult<Vec<ShardWatermark>> {
+        let _snapshot_guard = self.snapshot_lock.write();
+        self.shards.iter()
+            .map(|shard| {
+                stop.check()?;
+                Ok(ShardWatermark {
+                    shard_id: shard.id(),
+                    next_operation_id: shard.snapshot(&shard_path(snapshot_path, shard.id()))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Shards of stored points are looked up, as they are defined by payload.
+    /// Upserted point is removed from its previous shard, if the value of its shard key is changed.
+    fn split_by_shard_key(
+        &self,
+        operation: CollectionUpdateOperations,
+        shard_key: &PayloadKeyType,
+    ) -> CollectionResult<Vec<(ShardId, CollectionUpdateOperations)>> {
+        let shard_number = self.shards.len();
+        let placement = operation.shard_key_placement(shard_key, shard_number)?;
+        let current_shards = self.locate_points(&operation.point_ids())?;
+
+        // Points, which are not stored yet, are sent to any shard, so it reports them as missing
+        let new_shards = match placement {
+            None => return Ok(operation.split_by_shard(
+                shard_number,
+                &|point_id| current_shards.get(point_id).copied().unwrap_or(0),
+            )),
+            Some(new_shards) => new_shards,
+        };
+
+        let mut moved_points: BTreeMap<ShardId, Vec<PointIdType>> = BTreeMap::new();
+        for (point_id, shard_id) in current_shards.iter() {
+            if new_shards.get(point_id) != Some(shard_id) {
+                moved_points.entry(*shard_id).or_insert_with(Vec::new).push(*point_id);
+            }
+        }
+
+        let mut parts = operation.split_by_shard(
+            shard_number,
+            &|point_id| new_shards.get(point_id).copied().unwrap_or(0),
+        );
+        // Previous copies are removed after the points are stored in new shards
+        parts.extend(moved_points.into_iter().map(|(shard_id, ids)| {
+            (shard_id, CollectionUpdateOperations::PointOperation(PointOperations::DeletePoints { ids }))
+        }));
+        Ok(parts)
+    }
+
+    /// Shard of each stored point among the given ones
+    fn locate_points(&self, point_ids: &Vec<PointIdType>) -> CollectionResult<HashMap<PointIdType, ShardId>> {
+        let mut point_shards: HashMap<PointIdType, ShardId> = HashMap::new();
+        if point_ids.is_empty() {
+            return Ok(point_shards);
+        }
+        for shard in self.shards.iter() {
+            for record in shard.retrieve(point_ids, &WithPayload::from(false), false)? {
+                point_shards.insert(record.id, shard.id());
+            }
+        }
+        Ok(point_shards)
+    }
+
+    /// The only shard, which might contain points matching the filter.
+    /// Defined if the filter requires a single value of the shard key.
+    fn filter_shard(&self, filter: Option<&Filter>) -> Option<ShardId> {
+        let shard_key = self.shard_key.as_ref()?;
+        let value = filter_shard_key_value(filter?, shard_key)?;
+        Some(shard_key_shard(value, self.shards.len()))
+    }
+
+    fn target_shards(&self, filter: Option<&Filter>) -> Vec<&Arc<ReplicaSet>> {
+        match self.filter_shard(filter) {
+            None => self.shards.iter().collect(),
+            Some(shard_id) => vec![&self.shards[shard_id as usize]],
+        }
+    }
+
+    /// Ids of points, starting from `offset` in ascending order, which satisfy the filter
+    pub fn read_filtered(
+        &self,
+        offset: Option<PointIdType>,
+        limit: usize,
+        filter: Option<&Filter>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<PointIdType>> {
+        let mut point_ids: Vec<PointIdType> = vec![];
+        for shard in self.target_shards(filter) {
+            point_ids.append(&mut shard.read_consistent(consistency, |replica| replica.read_filtered(offset, limit, filter, stop))?);
+        }
+        point_ids.sort_unstable();
+        point_ids.truncate(limit);
+        Ok(point_ids)
+    }
+
+    /// Changes whenever points of any shard are changed, see `ShardOperations::data_version`
+    pub fn data_version(&self) -> u64 {
+        self.shards.iter().map(|shard| shard.data_version()).sum()
+    }
+
+    pub fn info(&self) -> CollectionResult<ShardInfo> {
+        let mut info = ShardInfo::default();
+        for shard in self.shards.iter() {
+            let shard_info = shard.info()?;
+            info.vectors_count += shard_info.vectors_count;
+            info.segments_count += shard_info.segments_count;
+            info.disk_data_size += shard_info.disk_data_size;
+            info.ram_data_size += shard_info.ram_data_size;
+            info.memory_usage.merge(&shard_info.memory_usage);
+            info.pending_updates += shard_info.pending_updates;
+        }
+        Ok(info)
+    }
+
+    /// Optimizations of all shards. Running optimizations go first
+    pub fn optimizations(&self) -> CollectionResult<Vec<OptimizationInfo>> {
+        let mut optimizations = vec![];
+        for shard in self.shards.iter() {
+            optimizations.extend(shard.optimizations()?);
+        }
+        optimizations.sort_by_key(|optimization| optimization.status != OptimizationStatus::Running);
+        Ok(optimizations)
+    }
+
+    pub fn telemetry(&self) -> CollectionResult<Vec<ShardTelemetry>> {
+        self.shards.iter()
+            .map(|shard| {
+                let info = shard.info()?;
+                Ok(ShardTelemetry {
+                    shard_id: shard.id(),
+                    status: shard.status(),
+                    primary: shard.primary(),
+                    replicas: shard.replica_states(),
+                    vectors_count: info.vectors_count,
+                    segments_count: info.segments_count,
+                    disk_data_size: info.disk_data_size,
+                    ram_data_size: info.ram_data_size,
+                    memory_usage: info.memory_usage,
+                    pending_updates: info.pending_updates,
+                })
+            })
+            .collect()
+    }
+
+    pub fn background_errors(&self) -> CollectionResult<Vec<String>> {
+        let mut errors = vec![];
+        for shard in self.shards.iter() {
+            errors.extend(shard.background_errors()?.into_iter()
+                .map(|err| format!("Shard {}: {}", shard.id(), err)));
+        }
+        Ok(errors)
+    }
+
+    pub fn reconfigure(&self, config: &CollectionConfig) -> CollectionResult<()> {
+        for shard in self.shards.iter() {
+            shard.reconfigure(config)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&self) -> CollectionResult<()> {
+        for shard in self.shards.iter() {
+            shard.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn wait_optimized(&self) -> CollectionResult<()> {
+        for shard in self.shards.iter() {
+            shard.wait_optimized()?;
+        }
+        Ok(())
+    }
+
+    pub fn offload_segments(&self, storage: &Arc<ColdStorage>) -> CollectionResult<usize> {
+        let mut offloaded = 0;
+        for shard in self.shards.iter() {
+            offloaded += shard.offload_segments(storage)?;
+        }
+        Ok(offloaded)
+    }
+
+    pub fn warm_up(&self) -> CollectionResult<usize> {
+        let mut loaded = 0;
+        for shard in self.shards.iter() {
+            loaded += shard.warm_up()?;
+        }
+        Ok(loaded)
+    }
+}
+
+/// Read requests, served by replicas of each shard according to the read consistency
+impl ShardHolder {
+    pub fn search_batch_consistent(
+        &self,
+        requests: Vec<Arc<SearchRequest>>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        for request in requests.iter() {
+            if request.offset > MAX_SEARCH_OFFSET {
+                return Err(CollectionError::BadRequest {
+                    description: format!("Search offset should not exceed {}", MAX_SEARCH_OFFSET)
+                });
+            }
+        }
+
+        // Offset could only be applied after results of all shards are merged
+        let shard_requests: Vec<Arc<SearchRequest>> = requests.iter()
+            .map(|request| Arc::new(SearchRequest {
+                top: request.top + request.offset,
+                offset: 0,
+                ..request.as_ref().clone()
+            }))
+            .collect();
+
+        // Requests, which require a single value of the shard key, are only sent to the shard of this value
+        let request_shards: Vec<Option<ShardId>> = requests.iter()
+            .map(|request| self.filter_shard(request.filter.as_ref()))
+            .collect();
+
+        let mut batch_results: Vec<Vec<ScoredPoint>> = requests.iter().map(|_| vec![]).collect();
+        for shard in self.shards.iter() {
+            let request_indices: Vec<usize> = (0..requests.len())
+                .filter(|idx| request_shards[*idx].map_or(true, |shard_id| shard_id == shard.id()))
+                .collect();
+            if request_indices.is_empty() {
+                continue;
+            }
+            let batch: Vec<Arc<SearchRequest>> = request_indices.iter()
+                .map(|idx| shard_requests[*idx].clone())
+                .collect();
+            let shard_results = shard.read_consistent(consistency, |replica| replica.search_batch(batch.clone(), stop))?;
+            for (idx, mut points) in request_indices.into_iter().zip(shard_results) {
+                batch_results[idx].append(&mut points);
+            }
+        }
+
+        Ok(requests.iter()
+            .zip(batch_results)
+            .map(|(request, points)| {
+                peek_top_scores_iterable(points.into_iter(), request.top + request.offset, &self.distance)
+                    .into_iter()
+                    .skip(request.offset)
+                    .collect()
+            })
+            .collect())
+    }
+
+    pub fn retrieve_consistent(
+        &self,
+        points: &Vec<PointIdType>,
+        with_payload: &WithPayload,
+        with_vector: bool,
+        consistency: ReadConsistency,
+    ) -> CollectionResult<Vec<Record>> {
+        let shard_number = self.shards.len();
+        // Shards of points are only known from their ids, if the collection is not sharded by a payload field
+        let shard_points = match self.shard_key {
+            None => split_by_shard(points.clone(), |id| *id, &|point_id| point_shard(point_id, shard_number)),
+            Some(_) => self.shards.iter().map(|shard| (shard.id(), points.clone())).collect(),
+        };
+
+        let mut point_records: HashMap<PointIdType, Record> = Default::default();
+        for (shard_id, shard_points) in shard_points {
+            let shard = &self.shards[shard_id as usize];
+            let records = shard.read_consistent(consistency, |replica| replica.retrieve(&shard_points, with_payload, with_vector))?;
+            for record in records {
+                point_records.insert(record.id, record);
+            }
+        }
+
+        // Keep the order of requested ids
+        let mut seen: HashSet<PointIdType> = Default::default();
+        Ok(points.iter()
+            .filter(|id| seen.insert(**id))
+            .filter_map(|id| point_records.remove(id))
+            .collect())
+    }
+
+    pub fn count_consistent(
+        &self,
+        request: Arc<CountRequest>,
+        consistency: ReadConsistency,
+        stop: &StopCondition,
+    ) -> CollectionResult<usize> {
+        let mut count = 0;
+        for shard in self.target_shards(request.filter.as_ref()) {
+            count += shard.read_consistent(consistency, |replica| replica.count(request.clone(), stop))?;
+        }
+        Ok(count)
+    }
+}
+
+impl SegmentSearcher for ShardHolder {
+    fn search(&self, request: Arc<SearchRequest>, stop: &StopCondition) -> CollectionResult<Vec<ScoredPoint>> {
+        let mut results = self.search_batch(vec![request], stop)?;
+        Ok(results.pop().unwrap_or_default())
+    }
+
+    fn search_batch(&self, requests: Vec<Arc<SearchRequest>>, stop: &StopCondition) -> CollectionResult<Vec<Vec<ScoredPoint>>> {
+        self.search_batch_consistent(requests, ReadConsistency::Any, stop)
+    }
+
+    fn retrieve(&self, points: &Vec<PointIdType>, with_payload: &WithPayload, with_vector: bool) -> CollectionResult<Vec<Record>> {
+        self.retrieve_consistent(points, with_payload, with_vector, ReadConsistency::Any)
+    }
+
+    fn count(&self, request: Arc<CountRequest>, stop: &StopCondition) -> CollectionResult<usize> {
+        self.count_consistent(request, ReadConsistency::Any, stop)
+    }
+}
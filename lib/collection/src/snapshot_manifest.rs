@@ -0,0 +1,210 @@
+use std::fs::{File, read_dir};
+use std::io::{Read, Write, copy};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use segment::segment_constructor::segment_migrations::CURRENT_FORMAT_VERSION as CURRENT_SEGMENT_FORMAT_VERSION;
+use segment::types::SeqNumberType;
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::config::CollectionConfig;
+use crate::shard::{ShardId, replica_path, shard_path};
+
+pub const SNAPSHOT_MANIFEST_FILE: &str = "snapshot_manifest.json";
+
+/// Version of the snapshot format, produced by the current code.
+///
+/// Versions:
+/// - 0: manifest with shard watermarks only
+/// - 1: manifest with format versions and checksums of all files
+pub const CURRENT_SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Upgrades unpacked snapshot and its manifest from one format version to the next one
+type SnapshotMigration = fn(&Path, &mut SnapshotManifest) -> CollectionResult<()>;
+
+/// Migration with index `i` upgrades snapshot of format version `i` to version `i + 1`
+const MIGRATIONS: [SnapshotMigration; CURRENT_SNAPSHOT_FORMAT_VERSION as usize] = [
+    accept_without_checksums,
+];
+
+/// Position of the shard in the snapshot
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct ShardWatermark {
+    pub shard_id: ShardId,
+    /// Id of the next operation after the saved ones. All earlier operations are saved in the shard data
+    pub next_operation_id: SeqNumberType,
+}
+
+/// File of the snapshot with its SHA-256 checksum
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnapshotFile {
+    /// Path relative to the root of the snapshot, with `/` as separator
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Description of the collection snapshot, stored in `snapshot_manifest.json` at the root of the snapshot archive.
+///
+/// Snapshot is a tar archive with the following layout:
+/// - `config.json` - config of the collection
+/// - `<shard_id>/<replica_id>/segments`, `<shard_id>/<replica_id>/wal` - data of a single replica of each shard
+/// - `snapshot_manifest.json` - this manifest
+///
+/// All shards are saved while updates of the collection are blocked, so no operation is saved in one shard
+/// and missing in another.
+///
+/// Snapshots, created by the previous version of the service, are restored by the next one:
+/// the snapshot format is upgraded by `MIGRATIONS`, segments are upgraded by segment migrations on load,
+/// and collection configs of older versions are accepted by `CollectionConfig::load`.
+/// Snapshots of newer formats are rejected with an explicit error.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnapshotManifest {
+    /// Manifests of version 0 do not contain it
+    #[serde(default)]
+    pub format_version: u32,
+    /// Format version of segments in the snapshot
+    #[serde(default)]
+    pub segment_format_version: u32,
+    /// Version of the service, which created the snapshot
+    #[serde(default)]
+    pub created_by: String,
+    pub shard_number: usize,
+    pub shards: Vec<ShardWatermark>,
+    /// All files of the snapshot, except the manifest
+    #[serde(default)]
+    pub files: Vec<SnapshotFile>,
+}
+
+/// Manifests of version 0 have no list of files, so data is only checked to contain all shards
+fn accept_without_checksums(_path: &Path, _manifest: &mut SnapshotManifest) -> CollectionResult<()> {
+    Ok(())
+}
+
+fn file_checksum(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_files(root: &Path, relative_path: &str, files: &mut Vec<SnapshotFile>) -> std::io::Result<()> {
+    for entry in read_dir(root.join(relative_path))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = if relative_path.is_empty() { name } else { format!("{}/{}", relative_path, name) };
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if path != SNAPSHOT_MANIFEST_FILE {
+            files.push(SnapshotFile { sha256: file_checksum(&entry.path())?, size: metadata.len(), path });
+        }
+    }
+    Ok(())
+}
+
+impl SnapshotManifest {
+    /// Manifest of the snapshot at `path` in the current format, which lists all files of the snapshot
+    pub fn new(path: &Path, shards: Vec<ShardWatermark>) -> CollectionResult<Self> {
+        let mut files = vec![];
+        collect_files(path, "", &mut files).or_else(|err| Err(CollectionError::ServiceError {
+            error: format!("Can't describe files of snapshot {:?}, error: {}", path, err)
+        }))?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(SnapshotManifest {
+            format_version: CURRENT_SNAPSHOT_FORMAT_VERSION,
+            segment_format_version: CURRENT_SEGMENT_FORMAT_VERSION,
+            created_by: env!("CARGO_PKG_VERSION").to_string(),
+            shard_number: shards.len(),
+            shards,
+            files,
+        })
+    }
+
+    /// Manifest might be missing in snapshots, created before it was introduced
+    pub fn load(path: &Path) -> CollectionResult<Option<Self>> {
+        let manifest_path = path.join(SNAPSHOT_MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let mut contents = String::new();
+        File::open(&manifest_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .or_else(|err| Err(CollectionError::BadInput {
+                description: format!("Can't read {:?}, error: {}", manifest_path, err)
+            }))?;
+        serde_json::from_str(&contents).map(Some).or_else(|err| Err(CollectionError::BadInput {
+            description: format!("Can't parse {:?}, error: {}", manifest_path, err)
+        }))
+    }
+
+    pub fn save(&self, path: &Path) -> CollectionResult<()> {
+        let manifest_path = path.join(SNAPSHOT_MANIFEST_FILE);
+        File::create(&manifest_path)
+            .and_then(|mut file| file.write_all(&serde_json::to_vec(self).unwrap()))
+            .or_else(|err| Err(CollectionError::ServiceError {
+                error: format!("Can't write {:?}, error: {}", manifest_path, err)
+            }))
+    }
+
+    /// Upgrade the unpacked snapshot at `path` to the current format version.
+    /// Snapshots, created by a newer version of the service, are rejected
+    pub fn upgrade(&mut self, path: &Path) -> CollectionResult<()> {
+        let created_by = if self.created_by.is_empty() { "unknown version" } else { &self.created_by };
+        if self.format_version > CURRENT_SNAPSHOT_FORMAT_VERSION {
+            return Err(CollectionError::BadInput {
+                description: format!(
+                    "Snapshot format version {} of {} is newer than supported version {}",
+                    self.format_version, created_by, CURRENT_SNAPSHOT_FORMAT_VERSION,
+                )
+            });
+        }
+        if self.segment_format_version > CURRENT_SEGMENT_FORMAT_VERSION {
+            return Err(CollectionError::BadInput {
+                description: format!(
+                    "Segment format version {} of {} is newer than supported version {}",
+                    self.segment_format_version, created_by, CURRENT_SEGMENT_FORMAT_VERSION,
+                )
+            });
+        }
+        for version in self.format_version..CURRENT_SNAPSHOT_FORMAT_VERSION {
+            MIGRATIONS[version as usize](path, self)?;
+            self.format_version = version + 1;
+        }
+        Ok(())
+    }
+
+    /// Check, that the unpacked snapshot at `path` contains every shard of the manifest exactly once
+    /// and all listed files are not changed
+    pub fn validate(&self, path: &Path, config: &CollectionConfig) -> CollectionResult<()> {
+        let invalid = |description: String| Err(CollectionError::BadInput { description });
+
+        if self.shard_number != config.shard_number {
+            return invalid(format!(
+                "Snapshot manifest has {} shards, but collection config has {}",
+                self.shard_number, config.shard_number,
+            ));
+        }
+        let mut shard_ids: Vec<ShardId> = self.shards.iter().map(|shard| shard.shard_id).collect();
+        shard_ids.sort_unstable();
+        if shard_ids != (0..self.shard_number as ShardId).collect::<Vec<_>>() {
+            return invalid(format!("Snapshot manifest lists shards {:?}", shard_ids));
+        }
+        for shard in self.shards.iter() {
+            let data_path = replica_path(&shard_path(path, shard.shard_id), 0);
+            if !data_path.join("segments").exists() || !data_path.join("wal").exists() {
+                return invalid(format!("Snapshot has incomplete data of shard {}", shard.shard_id));
+            }
+        }
+        for file in self.files.iter() {
+            let file_path = path.join(&file.path);
+            let is_valid = file_path.metadata().map_or(false, |metadata| metadata.len() == file.size)
+                && file_checksum(&file_path).map_or(false, |checksum| checksum == file.sha256);
+            if !is_valid {
+                return invalid(format!("Snapshot file {} is missing or corrupted", file.path));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use segment::types::{Condition, Filter, PayloadKeyType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::operations::CollectionUpdateOperations;
+use crate::operations::payload_ops::{PayloadInterface, PayloadOps};
+use crate::operations::point_ops::{PointInsertOperations, PointOperations};
+
+/// Limits of requests to the collection. Requests, which exceed any of the limits, are rejected
+/// before they reach shards. Not specified limits are not enforced
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct StrictModeConfig {
+    /// Max number of conditions in a filter, including conditions of nested filters
+    #[serde(default)]
+    pub max_filter_conditions: Option<usize>,
+    /// Max number of results, requested by a search, recommendation or scroll, including the offset
+    #[serde(default)]
+    pub max_top: Option<usize>,
+    /// Max number of searches in a batch, points in a single update or ids in a single retrieve
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+    /// Reject filters by payload fields without index, as they require to check the payload of every point
+    #[serde(default)]
+    pub reject_unindexed_filter: bool,
+    /// Max size of the payload of a single point in bytes, measured as JSON
+    #[serde(default)]
+    pub max_payload_size: Option<usize>,
+}
+
+fn violation(limit: &str, description: String) -> CollectionError {
+    CollectionError::StrictModeViolation { limit: limit.to_string(), description }
+}
+
+/// Number of conditions in the filter, nested filters are counted by their conditions
+fn count_conditions(filter: &Filter) -> usize {
+    filter_conditions(filter)
+        .map(|condition| match condition {
+            Condition::Filter(nested) => count_conditions(nested),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn filter_conditions(filter: &Filter) -> impl Iterator<Item=&Condition> {
+    filter.should.iter().flatten()
+        .chain(filter.must.iter().flatten())
+        .chain(filter.min_should.iter().flat_map(|min_should| min_should.conditions.iter()))
+        .chain(filter.must_not.iter().flatten())
+}
+
+/// First payload field of the filter, which is not in `indexed_fields`
+fn unindexed_field<'a>(filter: &'a Filter, indexed_fields: &[PayloadKeyType]) -> Option<&'a PayloadKeyType> {
+    filter_conditions(filter)
+        .filter_map(|condition| match condition {
+            Condition::Field(field) => if indexed_fields.contains(&field.key) { None } else { Some(&field.key) },
+            Condition::HasId(_) => None,
+            Condition::Filter(nested) => unindexed_field(nested, indexed_fields),
+        })
+        .next()
+}
+
+impl StrictModeConfig {
+    pub fn check_top(&self, top: usize, offset: usize) -> CollectionResult<()> {
+        match self.max_top {
+            Some(max_top) if top + offset > max_top => Err(violation(
+                "max_top",
+                format!("{} results requested, but at most {} are allowed", top + offset, max_top),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_batch_size(&self, size: usize) -> CollectionResult<()> {
+        match self.max_batch_size {
+            Some(max_batch_size) if size > max_batch_size => Err(violation(
+                "max_batch_size",
+                format!("Batch of {} entries, but at most {} are allowed", size, max_batch_size),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Largest number of results, not greater than `top`, which could be requested at once
+    pub fn limit_top(&self, top: usize) -> usize {
+        self.max_top.map_or(top, |max_top| max_top.min(top)).max(1)
+    }
+
+    /// Largest batch, not greater than `batch_size`, which could be sent at once
+    pub fn limit_batch_size(&self, batch_size: usize) -> usize {
+        self.max_batch_size.map_or(batch_size, |max_batch_size| max_batch_size.min(batch_size)).max(1)
+    }
+
+    /// Payload indexes are only requested, if unindexed filters are rejected
+    pub fn check_filter(
+        &self,
+        filter: Option<&Filter>,
+        indexed_fields: impl FnOnce() -> CollectionResult<Vec<PayloadKeyType>>,
+    ) -> CollectionResult<()> {
+        let filter = match filter {
+            None => return Ok(()),
+            Some(filter) => filter,
+        };
+
+        if let Some(max_filter_conditions) = self.max_filter_conditions {
+            let conditions = count_conditions(filter);
+            if conditions > max_filter_conditions {
+                return Err(violation(
+                    "max_filter_conditions",
+                    format!("Filter has {} conditions, but at most {} are allowed", conditions, max_filter_conditions),
+                ));
+            }
+        }
+
+        if self.reject_unindexed_filter {
+            if let Some(field) = unindexed_field(filter, &indexed_fields()?) {
+                return Err(violation(
+                    "reject_unindexed_filter",
+                    format!("Filter by field {}, which has no payload index", field),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_payload(&self, payload: &HashMap<PayloadKeyType, PayloadInterface>) -> CollectionResult<()> {
+        let max_payload_size = match self.max_payload_size {
+            None => return Ok(()),
+            Some(max_payload_size) => max_payload_size,
+        };
+        let size = serde_json::to_vec(payload).map(|bytes| bytes.len()).unwrap_or(0);
+        if size > max_payload_size {
+            return Err(violation(
+                "max_payload_size",
+                format!("Payload of {} bytes, but at most {} are allowed", size, max_payload_size),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check size of the update, payloads of changed points and the filter of deletion
+    pub fn check_update(
+        &self,
+        operation: &CollectionUpdateOperations,
+        indexed_fields: impl FnOnce() -> CollectionResult<Vec<PayloadKeyType>>,
+    ) -> CollectionResult<()> {
+        self.check_batch_size(operation.point_ids().len())?;
+
+        match operation {
+            CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(insert)) => match insert {
+                PointInsertOperations::BatchPoints { payloads, .. } => payloads.iter()
+                    .flatten()
+                    .flatten()
+                    .try_for_each(|payload| self.check_payload(payload)),
+                PointInsertOperations::PointsList(points) => points.iter()
+                    .filter_map(|point| point.payload.as_ref())
+                    .try_for_each(|payload| self.check_payload(payload)),
+            },
+            CollectionUpdateOperations::PointOperation(PointOperations::DeletePointsByFilter { filter }) =>
+                self.check_filter(Some(filter), indexed_fields),
+            CollectionUpdateOperations::PayloadOperation(PayloadOps::SetPayload { payload, .. }) =>
+                self.check_payload(payload),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1 +1,2 @@
-pub mod update_handler;
\ No newline at end of file
+pub mod update_handler;
+pub mod update_workers;
@@ -1,31 +1,96 @@
-use crossbeam_channel::Receiver;
-use segment::types::SeqNumberType;
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use segment::types::{SeqNumberType, FlushPolicy};
+use std::collections::HashSet;
 use std::sync::{Arc};
+use std::thread;
 use tokio::task::JoinHandle;
 use crate::segment_manager::optimizers::segment_optimizer::SegmentOptimizer;
-use crate::segment_manager::holders::segment_holder::{LockedSegmentHolder};
+use crate::segment_manager::holders::segment_holder::{LockedSegment, LockedSegmentHolder, SegmentId};
 use parking_lot::Mutex;
 use crate::wal::SerdeWal;
 use crate::operations::CollectionUpdateOperations;
 use tokio::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use log::debug;
+use tracing::{debug, error};
+use crate::collection::{CollectionError, CollectionResult};
+use crate::update_handler::update_workers::PendingOperations;
+use crate::optimization_pool::OptimizationPool;
+use std::cmp::min;
+use segment::common::stop_condition::StopCondition;
 
 pub type Optimizer = dyn SegmentOptimizer + Sync + Send;
 
+/// Number of points, migrated at once. Point operations of the segment wait for the batch to finish
+const PAYLOAD_MIGRATION_BATCH: usize = 256;
+
 pub enum UpdateSignal {
     Operation(SeqNumberType),
+    /// Replace optimizers and flush policy after the collection config is changed
+    Reconfigure {
+        optimizers: Arc<Vec<Box<Optimizer>>>,
+        flush_policy: FlushPolicy,
+        max_optimization_threads: usize,
+    },
+    /// Notify the sender, once all previously sent operations are processed by optimizers
+    Wait(Sender<()>),
+    Stop,
+}
+
+/// Request to persist collection changes, processed by a dedicated flusher thread
+pub enum FlushSignal {
+    Flush,
     Stop,
 }
 
+/// Error of the last failed flush. It is kept until a flush succeeds
+pub type LockedFlushError = Arc<Mutex<Option<CollectionError>>>;
+
+/// Error of the last failed optimization. It is cleared once an optimization succeeds
+pub type LockedOptimizerError = Arc<Mutex<Option<CollectionError>>>;
+
+/// Segments, which are chosen for optimization by one of the optimizers
+#[derive(Debug, Clone)]
+pub struct PlannedOptimization {
+    pub optimizer: String,
+    pub segments: Vec<SegmentId>,
+}
+
+/// Optimizations of the shard, which are running or wait for a free optimization thread
+#[derive(Debug, Default, Clone)]
+pub struct OptimizationsState {
+    pub running: Vec<PlannedOptimization>,
+    pub pending: Vec<PlannedOptimization>,
+}
+
+pub type LockedOptimizationsState = Arc<Mutex<OptimizationsState>>;
+
 pub struct UpdateHandler {
     optimizers: Arc<Vec<Box<Optimizer>>>,
     segments: LockedSegmentHolder,
     receiver: Receiver<UpdateSignal>,
     worker: Option<JoinHandle<()>>,
     runtime_handle: Arc<Runtime>,
+    optimization_pool: Arc<OptimizationPool>,
     wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
-    flush_timeout_sec: u64,
+    flush_policy: FlushPolicy,
+    max_optimization_threads: usize,
+    optimizations: LockedOptimizationsState,
+    optimizer_error: LockedOptimizerError,
+    flush_sender: Option<Sender<FlushSignal>>,
+    flusher: Option<thread::JoinHandle<()>>,
+    flush_error: LockedFlushError,
+    pending_operations: PendingOperations,
+    /// Cancels running optimizations, e.g. once the shard is stopped
+    stop: StopCondition,
+}
+
+/// Check if collection should be flushed according to the policy
+fn is_flush_required(flush_policy: &FlushPolicy, since_last_flush: Duration, operations_since_flush: usize) -> bool {
+    match flush_policy {
+        FlushPolicy::Interval { seconds } => since_last_flush > Duration::from_secs(*seconds),
+        FlushPolicy::Operations { count } => operations_since_flush >= *count,
+        FlushPolicy::Manual => false,
+    }
 }
 
 
@@ -34,9 +99,12 @@ impl UpdateHandler {
         optimizers: Arc<Vec<Box<Optimizer>>>,
         receiver: Receiver<UpdateSignal>,
         runtime_handle: Arc<Runtime>,
+        optimization_pool: Arc<OptimizationPool>,
         segments: LockedSegmentHolder,
         wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
-        flush_timeout_sec: u64,
+        flush_policy: FlushPolicy,
+        max_optimization_threads: usize,
+        pending_operations: PendingOperations,
     ) -> UpdateHandler {
         let mut handler = UpdateHandler {
             optimizers,
@@ -44,61 +112,359 @@ impl UpdateHandler {
             receiver,
             worker: None,
             runtime_handle,
+            optimization_pool,
             wal,
-            flush_timeout_sec,
+            flush_policy,
+            max_optimization_threads,
+            optimizations: Default::default(),
+            optimizer_error: Default::default(),
+            flush_sender: None,
+            flusher: None,
+            flush_error: Default::default(),
+            pending_operations,
+            stop: Default::default(),
         };
+        handler.run_flusher();
         handler.run_worker();
         handler
     }
 
+    /// Return error of the last failed flush, unless some flush succeeded after it.
+    /// Should be checked before accepting new operations, so persistence problems are not ignored.
+    pub fn check_flush_error(&self) -> CollectionResult<()> {
+        match self.flush_error.lock().as_ref() {
+            None => Ok(()),
+            Some(err) => Err(err.clone())
+        }
+    }
+
+    /// Forget the error of the previous flush, once a flush outside of the flusher succeeds
+    pub fn clear_flush_error(&self) {
+        *self.flush_error.lock() = None;
+    }
+
+    /// Optimizations, which are performed or planned at the moment
+    pub fn optimizations(&self) -> OptimizationsState {
+        self.optimizations.lock().clone()
+    }
+
+    /// Cancel running optimizations and do not start new ones.
+    /// Cancelled optimizations are rolled back, so segments stay as they were before the optimization
+    pub fn stop_optimizations(&self) {
+        self.stop.stop();
+    }
+
+    /// Errors of background flushes and optimizations, which are not resolved yet
+    pub fn background_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if let Some(err) = self.flush_error.lock().as_ref() {
+            errors.push(format!("Flush failed: {}", err));
+        }
+        if let Some(err) = self.optimizer_error.lock().as_ref() {
+            errors.push(format!("Optimization failed: {}", err));
+        }
+        errors
+    }
+
+    fn run_flusher(&mut self) {
+        let (sender, receiver) = unbounded();
+        let segments = self.segments.clone();
+        let wal = self.wal.clone();
+        let flush_error = self.flush_error.clone();
+        let pending_operations = self.pending_operations.clone();
+        self.flush_sender = Some(sender);
+        self.flusher = Some(thread::Builder::new()
+            .name("collection-flusher".to_string())
+            .spawn(move || Self::flusher_fn(receiver, segments, wal, flush_error, pending_operations))
+            .unwrap());
+    }
+
+    /// Segments are flushed before WAL truncation, so only operations which are already persisted
+    /// in all segments could be removed from WAL.
+    /// Operations are applied by several workers, so segments might contain newer operations
+    /// than the first pending one. WAL is only truncated up to the first pending operation.
+    pub fn flush(
+        segments: &LockedSegmentHolder,
+        wal: &Mutex<SerdeWal<CollectionUpdateOperations>>,
+        pending_operations: &PendingOperations,
+    ) -> CollectionResult<SeqNumberType> {
+        let applied_before = {
+            let wal = wal.lock();
+            let next_operation = wal.first_index() + wal.len();
+            match pending_operations.lock().iter().next() {
+                Some(first_pending) => min(*first_pending, next_operation),
+                None => next_operation,
+            }
+        };
+        let flushed_operation = segments.read().flush_all()?;
+        wal.lock().ack(min(flushed_operation, applied_before))?;
+        Ok(flushed_operation)
+    }
+
+    fn flusher_fn(
+        receiver: Receiver<FlushSignal>,
+        segments: LockedSegmentHolder,
+        wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
+        flush_error: LockedFlushError,
+        pending_operations: PendingOperations,
+    ) {
+        loop {
+            match receiver.recv() {
+                Ok(FlushSignal::Flush) => {
+                    // Requests accumulated during the previous flush are served by a single flush
+                    let mut stop = false;
+                    for signal in receiver.try_iter() {
+                        if let FlushSignal::Stop = signal { stop = true; }
+                    }
+                    let timer = Instant::now();
+                    match Self::flush(&segments, &wal, &pending_operations) {
+                        Ok(flushed_operation) => {
+                            debug!(
+                                operation_id = flushed_operation,
+                                duration_ms = timer.elapsed().as_millis() as u64,
+                                "Flushed operations",
+                            );
+                            *flush_error.lock() = None;
+                        }
+                        Err(err) => {
+                            error!(error = %err, "Background flush failed");
+                            *flush_error.lock() = Some(err);
+                        }
+                    }
+                    if stop { break; }
+                }
+                Ok(FlushSignal::Stop) => break,
+                Err(_) => break, // Update worker is finished
+            }
+        }
+    }
+
     pub fn run_worker(&mut self) {
         self.worker = Some(self.runtime_handle.spawn(
             Self::worker_fn(
                 self.optimizers.clone(),
+                self.optimization_pool.clone(),
                 self.receiver.clone(),
                 self.segments.clone(),
-                self.wal.clone(),
-                self.flush_timeout_sec,
+                self.flush_sender.clone().unwrap(),
+                self.flush_policy,
+                self.max_optimization_threads,
+                self.optimizations.clone(),
+                self.optimizer_error.clone(),
+                self.stop.clone(),
             ),
         ));
     }
 
+    /// Each optimizer is applied at most once. Optimizations of different segments are performed
+    /// in parallel, up to `max_threads` at a time. Optimizers, which could not start right away,
+    /// are checked again after the running optimizations are finished.
+    /// Optimizations are performed by the shared pool, so they might also wait for optimizations of other shards.
+    /// Failed optimization stops the pass, its error is kept until some optimization succeeds.
+    /// Cancelled optimization also stops the pass, but it is not considered as a failure.
+    fn process_optimization(
+        optimizers: &Arc<Vec<Box<Optimizer>>>,
+        optimization_pool: &OptimizationPool,
+        segments: &LockedSegmentHolder,
+        max_threads: usize,
+        optimizations: &LockedOptimizationsState,
+        optimizer_error: &LockedOptimizerError,
+        stop: &StopCondition,
+    ) {
+        let mut remaining: Vec<usize> = (0..optimizers.len()).collect();
+        while !remaining.is_empty() && !stop.is_stopped() {
+            let mut scheduled: Vec<(usize, Vec<SegmentId>)> = vec![];
+            let mut postponed: Vec<(usize, Vec<SegmentId>)> = vec![];
+            let mut claimed_segments: HashSet<SegmentId> = HashSet::new();
+            for optimizer_idx in remaining {
+                let unoptimal_segment_ids = optimizers[optimizer_idx].check_condition(segments.clone());
+                if unoptimal_segment_ids.is_empty() {
+                    continue;
+                }
+                let is_claimed = unoptimal_segment_ids.iter().any(|id| claimed_segments.contains(id));
+                if is_claimed || scheduled.len() >= max_threads.max(1) {
+                    postponed.push((optimizer_idx, unoptimal_segment_ids));
+                } else {
+                    claimed_segments.extend(unoptimal_segment_ids.iter().cloned());
+                    scheduled.push((optimizer_idx, unoptimal_segment_ids));
+                }
+            }
+
+            {
+                let describe = |(optimizer_idx, segment_ids): &(usize, Vec<SegmentId>)| PlannedOptimization {
+                    optimizer: optimizers[*optimizer_idx].name().to_string(),
+                    segments: segment_ids.clone(),
+                };
+                let mut optimizations = optimizations.lock();
+                optimizations.running = scheduled.iter().map(describe).collect();
+                optimizations.pending = postponed.iter().map(describe).collect();
+            }
+
+            let handles: Vec<_> = scheduled.into_iter()
+                .map(|(optimizer_idx, segment_ids)| {
+                    let optimizers = optimizers.clone();
+                    let segments = segments.clone();
+                    let stop = stop.clone();
+                    optimization_pool.spawn(move || {
+                        debug!(segments = ?segment_ids, "Start optimization");
+                        let timer = Instant::now();
+                        let optimized_ids = segment_ids.clone();
+                        let result = optimizers[optimizer_idx].optimize(segments, segment_ids, &stop);
+                        debug!(
+                            segments = ?optimized_ids,
+                            duration_ms = timer.elapsed().as_millis() as u64,
+                            success = result.is_ok(),
+                            "Optimization finished",
+                        );
+                        result
+                    })
+                })
+                .collect();
+            let mut failed = false;
+            for handle in handles {
+                let result = match handle.recv() {
+                    Ok(Ok(result)) => result,
+                    _ => Err(CollectionError::ServiceError {
+                        error: format!("Optimization thread panicked")
+                    }),
+                };
+                match result {
+                    Ok(_) => *optimizer_error.lock() = None,
+                    Err(CollectionError::Cancelled { description }) => {
+                        debug!(reason = %description, "Optimization cancelled");
+                        failed = true;
+                    }
+                    Err(err) => {
+                        error!(error = %err, "Optimization failed");
+                        *optimizer_error.lock() = Some(err);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                break;
+            }
+
+            remaining = postponed.into_iter().map(|(optimizer_idx, _)| optimizer_idx).collect();
+        }
+        *optimizations.lock() = Default::default();
+    }
+
+    /// Apply pending payload migrations of all segments batch by batch, so point operations are not blocked for long.
+    /// Migrations are performed by the shared optimization pool, error of a failed migration is kept as optimizer error.
+    fn process_payload_migrations(
+        optimization_pool: &OptimizationPool,
+        segments: &LockedSegmentHolder,
+        optimizer_error: &LockedOptimizerError,
+        stop: &StopCondition,
+    ) {
+        let migrating_segments: Vec<LockedSegment> = segments.read().iter()
+            .map(|(_, segment)| segment.clone())
+            .filter(|segment| segment.get().read().pending_payload_migrations() > 0)
+            .collect();
+        if migrating_segments.is_empty() {
+            return;
+        }
+
+        let stop = stop.clone();
+        let handle = optimization_pool.spawn(move || -> CollectionResult<()> {
+            for segment in migrating_segments {
+                while !stop.is_stopped() && segment.get().read().pending_payload_migrations() > 0 {
+                    segment.get().read().migrate_payload_batch(PAYLOAD_MIGRATION_BATCH)?;
+                }
+            }
+            Ok(())
+        });
+        let result = match handle.recv() {
+            Ok(Ok(result)) => result,
+            _ => Err(CollectionError::ServiceError {
+                error: format!("Payload migration thread panicked")
+            }),
+        };
+        if let Err(err) = result {
+            error!(error = %err, "Payload migration failed");
+            *optimizer_error.lock() = Some(err);
+        }
+    }
+
     async fn worker_fn(
-        optimizers: Arc<Vec<Box<Optimizer>>>,
+        mut optimizers: Arc<Vec<Box<Optimizer>>>,
+        optimization_pool: Arc<OptimizationPool>,
         receiver: Receiver<UpdateSignal>,
         segments: LockedSegmentHolder,
-        wal: Arc<Mutex<SerdeWal<CollectionUpdateOperations>>>,
-        flush_timeout_sec: u64,
+        flush_sender: Sender<FlushSignal>,
+        mut flush_policy: FlushPolicy,
+        mut max_optimization_threads: usize,
+        optimizations: LockedOptimizationsState,
+        optimizer_error: LockedOptimizerError,
+        stop: StopCondition,
     ) -> () {
-        let flush_timeout = Duration::from_secs(flush_timeout_sec);
         let mut last_flushed = Instant::now();
+        let mut operations_since_flush: usize = 0;
+        // Migrations, which are not finished before the restart
+        Self::process_payload_migrations(&optimization_pool, &segments, &optimizer_error, &stop);
         loop {
             let recv_res = receiver.recv();
             match recv_res {
                 Ok(signal) => {
                     match signal {
                         UpdateSignal::Operation(operation_id) => {
-                            debug!("Performing update operation: {}", operation_id);
-                            for optimizer in optimizers.iter() {
-                                let unoptimal_segment_ids = optimizer.check_condition(segments.clone());
-                                if !unoptimal_segment_ids.is_empty() {
-                                    debug!("Start optimization on segments: {:?}", unoptimal_segment_ids);
-                                    optimizer.optimize(segments.clone(), unoptimal_segment_ids).unwrap();
-                                }
-                            }
-                            let elapsed = last_flushed.elapsed();
-                            if elapsed > flush_timeout {
-                                debug!("Performing flushing: {}", operation_id);
+                            debug!(operation_id, "Performing update operation");
+                            Self::process_optimization(&optimizers, &optimization_pool, &segments, max_optimization_threads, &optimizations, &optimizer_error, &stop);
+                            Self::process_payload_migrations(&optimization_pool, &segments, &optimizer_error, &stop);
+                            operations_since_flush += 1;
+                            if is_flush_required(&flush_policy, last_flushed.elapsed(), operations_since_flush) {
+                                debug!(operation_id, "Performing flushing");
                                 last_flushed = Instant::now();
-                                let flushed_operation = segments.read().flush_all().unwrap();
-                                wal.lock().ack(flushed_operation).unwrap();
+                                operations_since_flush = 0;
+                                if flush_sender.send(FlushSignal::Flush).is_err() {
+                                    error!("Flusher is not available");
+                                }
                             }
                         }
-                        UpdateSignal::Stop => break, // Stop gracefully
+                        UpdateSignal::Reconfigure {
+                            optimizers: new_optimizers,
+                            flush_policy: new_flush_policy,
+                            max_optimization_threads: new_max_optimization_threads,
+                        } => {
+                            debug!("Applying new collection config");
+                            optimizers = new_optimizers;
+                            flush_policy = new_flush_policy;
+                            max_optimization_threads = new_max_optimization_threads;
+                            // Existing segments might not correspond to the new config
+                            Self::process_optimization(&optimizers, &optimization_pool, &segments, max_optimization_threads, &optimizations, &optimizer_error, &stop);
+                        }
+                        UpdateSignal::Wait(sender) => {
+                            // Waiting side might be gone already, which is fine
+                            let _ = sender.send(());
+                        }
+                        UpdateSignal::Stop => {
+                            // Stop gracefully
+                            let _ = flush_sender.send(FlushSignal::Stop);
+                            break;
+                        }
                     }
                 }
                 Err(_) => break, // Transmitter was destroyed
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_policy() {
+        let interval = FlushPolicy::Interval { seconds: 10 };
+        assert!(!is_flush_required(&interval, Duration::from_secs(5), 1000));
+        assert!(is_flush_required(&interval, Duration::from_secs(11), 1));
+
+        let operations = FlushPolicy::Operations { count: 100 };
+        assert!(!is_flush_required(&operations, Duration::from_secs(3600), 99));
+        assert!(is_flush_required(&operations, Duration::from_secs(0), 100));
+
+        assert!(!is_flush_required(&FlushPolicy::Manual, Duration::from_secs(3600), 1000));
+    }
+}
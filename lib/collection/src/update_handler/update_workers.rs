@@ -0,0 +1,427 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Barrier};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender, bounded};
+use tracing::error;
+use parking_lot::Mutex;
+use tracing::{debug_span, Span};
+
+use segment::types::{PointIdType, SeqNumberType};
+
+use crate::collection::{CollectionError, CollectionResult};
+use crate::config::OverloadPolicy;
+use crate::operations::CollectionUpdateOperations;
+use crate::segment_manager::segment_managers::SegmentUpdater;
+use crate::shard::ShardId;
+use crate::update_handler::update_handler::UpdateSignal;
+
+pub type Updater = dyn SegmentUpdater + Sync + Send;
+
+/// Operations, which are written to WAL, but not completely applied to segments yet.
+/// WAL could not be truncated past the first of them, even if some segments are flushed with newer versions.
+pub type PendingOperations = Arc<Mutex<BTreeSet<SeqNumberType>>>;
+
+/// Progress of the operation, which might be split between several workers
+struct OperationState {
+    operation_id: SeqNumberType,
+    /// Number of parts, which are not applied yet
+    remaining: AtomicUsize,
+    /// Number of points, changed by the applied parts
+    changed: AtomicUsize,
+    error: Mutex<Option<CollectionError>>,
+    /// Receives result of the whole operation, if the caller waits for it
+    callback: Option<Sender<CollectionResult<usize>>>,
+    /// Span of the request, which submitted the operation. Parts are traced within it
+    span: Span,
+}
+
+impl OperationState {
+    fn new(operation_id: SeqNumberType, parts: usize, callback: Option<Sender<CollectionResult<usize>>>) -> Arc<Self> {
+        Arc::new(OperationState {
+            operation_id,
+            remaining: AtomicUsize::new(parts),
+            changed: AtomicUsize::new(0),
+            error: Mutex::new(None),
+            callback,
+            span: Span::current(),
+        })
+    }
+
+    /// Apply part of the operation within the span of the request, which submitted it
+    fn apply_part(&self, updater: &Updater, operation: CollectionUpdateOperations) -> CollectionResult<usize> {
+        let _span = debug_span!(parent: &self.span, "apply_update", operation_id = self.operation_id).entered();
+        updater.update(self.operation_id, operation)
+    }
+
+    /// The last applied part reports the result of the operation and notifies optimizers
+    fn finish_part(
+        &self,
+        result: CollectionResult<usize>,
+        update_sender: &Sender<UpdateSignal>,
+        pending: &PendingOperations,
+        data_version: &AtomicU64,
+    ) {
+        match result {
+            Ok(changed) => { self.changed.fetch_add(changed, Ordering::SeqCst); }
+            Err(err) => { self.error.lock().get_or_insert(err); }
+        }
+        if self.remaining.fetch_sub(1, Ordering::SeqCst) != 1 {
+            return;
+        }
+        pending.lock().remove(&self.operation_id);
+        data_version.fetch_add(1, Ordering::SeqCst);
+        if update_sender.send(UpdateSignal::Operation(self.operation_id)).is_err() {
+            error!(operation_id = self.operation_id, "Optimizers of operation are not available");
+        }
+        if let Some(callback) = &self.callback {
+            let result = match self.error.lock().take() {
+                None => Ok(self.changed.load(Ordering::SeqCst)),
+                Some(err) => Err(err),
+            };
+            // Caller might not wait for the result anymore
+            let _ = callback.send(result);
+        }
+    }
+}
+
+enum UpdateTask {
+    /// Part of the operation, which only affects points of the worker
+    Part {
+        operation: CollectionUpdateOperations,
+        state: Arc<OperationState>,
+    },
+    /// Operation, which is not bound to specific points. All workers receive it, but only one applies it,
+    /// while others wait. So it is ordered with operations of all workers.
+    Exclusive {
+        operation: Option<CollectionUpdateOperations>,
+        state: Arc<OperationState>,
+        barrier: Arc<Barrier>,
+    },
+}
+
+/// Worker, which applies all changes of the point.
+/// Ids are mixed, so points of a single shard are still spread between workers.
+fn point_worker(point_id: &PointIdType, workers_number: usize) -> ShardId {
+    let hash = match point_id {
+        PointIdType::NumId(id) => *id,
+        PointIdType::Uuid(uuid) => {
+            let value = uuid.as_u128();
+            (value ^ (value >> 64)) as u64
+        }
+    };
+    ((hash.wrapping_mul(0x9E3779B97F4A7C15) >> 32) % workers_number as u64) as ShardId
+}
+
+/// Pool of threads, which apply operations to segments after they are written to WAL.
+/// Each worker has a bounded queue. Callers are blocked while workers are behind,
+/// or their operations are rejected, if the overload policy says so.
+///
+/// Changes of the same point are always applied by the same worker in the order of operations.
+/// Operations, which are not bound to specific points (e.g. deletion by filter), are applied
+/// once all preceding operations are applied by all workers.
+pub struct UpdateWorkers {
+    senders: Mutex<Vec<Sender<UpdateTask>>>,
+    workers: Mutex<Vec<thread::JoinHandle<()>>>,
+    pending: PendingOperations,
+    /// Number of completely applied operations, see `data_version`
+    data_version: Arc<AtomicU64>,
+    overload_policy: OverloadPolicy,
+}
+
+impl UpdateWorkers {
+    pub fn new(
+        workers_number: usize,
+        queue_size: usize,
+        overload_policy: OverloadPolicy,
+        updater: Arc<Updater>,
+        update_sender: Sender<UpdateSignal>,
+    ) -> Self {
+        let pending: PendingOperations = Default::default();
+        let data_version: Arc<AtomicU64> = Default::default();
+        let mut senders = vec![];
+        let mut workers = vec![];
+        for worker_id in 0..workers_number.max(1) {
+            let (sender, receiver) = bounded(queue_size);
+            let updater = updater.clone();
+            let update_sender = update_sender.clone();
+            let pending = pending.clone();
+            let data_version = data_version.clone();
+            senders.push(sender);
+            workers.push(thread::Builder::new()
+                .name(format!("update-worker-{}", worker_id))
+                .spawn(move || Self::worker_fn(receiver, updater, update_sender, pending, data_version))
+                .unwrap());
+        }
+        UpdateWorkers {
+            senders: Mutex::new(senders),
+            workers: Mutex::new(workers),
+            pending,
+            data_version,
+            overload_policy,
+        }
+    }
+
+    pub fn pending_operations(&self) -> PendingOperations {
+        self.pending.clone()
+    }
+
+    /// Counter, which is changed once an operation is completely applied.
+    /// Results of reads, made before the change, might be outdated
+    pub fn data_version(&self) -> u64 {
+        self.data_version.load(Ordering::SeqCst)
+    }
+
+    /// Change the data version, if points are changed bypassing the workers
+    pub fn mark_changed(&self) {
+        self.data_version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Number of operations, which are written to WAL, but not applied yet
+    pub fn queue_depth(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Reject the next operation, if the queue of some worker is full and overloaded workers should not be waited for.
+    /// Should be called before the operation is written to WAL, while submissions are serialized,
+    /// so queues could only shrink until the operation is submitted.
+    pub fn check_overload(&self) -> CollectionResult<()> {
+        if self.overload_policy == OverloadPolicy::Block {
+            return Ok(());
+        }
+        if self.senders.lock().iter().any(|sender| sender.is_full()) {
+            return Err(CollectionError::Overloaded {
+                description: format!("{} updates are waiting to be applied, retry later", self.queue_depth())
+            });
+        }
+        Ok(())
+    }
+
+    /// Should be called right after the operation is written to WAL, while WAL is still locked.
+    /// Operation is pending until all of its parts are applied. If it is not submitted, it stays pending,
+    /// so it is kept in WAL until the shard is loaded again.
+    pub fn mark_pending(&self, operation_id: SeqNumberType) {
+        self.pending.lock().insert(operation_id);
+    }
+
+    /// Queue operation for the workers. Operations should be submitted in the order of their ids.
+    /// Blocks while the queues are full. Returns receiver of the operation result, if `wait` is requested.
+    pub fn submit(
+        &self,
+        operation_id: SeqNumberType,
+        operation: CollectionUpdateOperations,
+        wait: bool,
+    ) -> CollectionResult<Option<Receiver<CollectionResult<usize>>>> {
+        let senders = self.senders.lock().clone();
+        if senders.is_empty() {
+            return Err(CollectionError::ServiceError {
+                error: format!("Update workers are stopped")
+            });
+        }
+
+        let (callback, result_receiver) = match wait {
+            true => {
+                let (sender, receiver) = bounded(1);
+                (Some(sender), Some(receiver))
+            }
+            false => (None, None),
+        };
+
+        let workers_number = senders.len();
+        let tasks: Vec<(usize, UpdateTask)> = if workers_number == 1 {
+            vec![(0, UpdateTask::Part { operation, state: OperationState::new(operation_id, 1, callback) })]
+        } else if operation.point_ids().is_empty() {
+            let state = OperationState::new(operation_id, 1, callback);
+            let barrier = Arc::new(Barrier::new(workers_number));
+            let mut operation = Some(operation);
+            (0..workers_number)
+                .map(|worker_id| (worker_id, UpdateTask::Exclusive {
+                    operation: operation.take(),
+                    state: state.clone(),
+                    barrier: barrier.clone(),
+                }))
+                .collect()
+        } else {
+            let parts = operation.split_by_shard(workers_number, &|point_id| point_worker(point_id, workers_number));
+            let state = OperationState::new(operation_id, parts.len(), callback);
+            parts.into_iter()
+                .map(|(worker_id, operation)| (worker_id as usize, UpdateTask::Part { operation, state: state.clone() }))
+                .collect()
+        };
+
+        for (worker_id, task) in tasks {
+            senders[worker_id].send(task).or_else(|_| Err(CollectionError::ServiceError {
+                error: format!("Update worker {} is stopped", worker_id)
+            }))?;
+        }
+        Ok(result_receiver)
+    }
+
+    /// Apply all queued operations and stop workers
+    pub fn stop(&self) {
+        self.senders.lock().clear();
+        for worker in self.workers.lock().drain(..) {
+            if worker.join().is_err() {
+                error!("Update worker panicked");
+            }
+        }
+    }
+
+    fn worker_fn(
+        receiver: Receiver<UpdateTask>,
+        updater: Arc<Updater>,
+        update_sender: Sender<UpdateSignal>,
+        pending: PendingOperations,
+        data_version: Arc<AtomicU64>,
+    ) {
+        for task in receiver.iter() {
+            match task {
+                UpdateTask::Part { operation, state } => {
+                    let result = state.apply_part(updater.as_ref(), operation);
+                    state.finish_part(result, &update_sender, &pending, &data_version);
+                }
+                UpdateTask::Exclusive { operation, state, barrier } => {
+                    // All workers have applied preceding operations
+                    barrier.wait();
+                    if let Some(operation) = operation {
+                        let result = state.apply_part(updater.as_ref(), operation);
+                        state.finish_part(result, &update_sender, &pending, &data_version);
+                    }
+                    // Following operations are applied after this one
+                    barrier.wait();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UpdateWorkers {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crossbeam_channel::unbounded;
+
+    use segment::types::Filter;
+
+    use crate::operations::point_ops::PointOperations;
+
+    use super::*;
+
+    /// Records ids of applied operations together with their points
+    struct RecordingUpdater {
+        applied: Mutex<Vec<(SeqNumberType, Vec<PointIdType>)>>,
+    }
+
+    impl SegmentUpdater for RecordingUpdater {
+        fn update(&self, op_num: SeqNumberType, operation: CollectionUpdateOperations) -> CollectionResult<usize> {
+            let point_ids = operation.point_ids();
+            // Slow down some of the workers
+            if point_ids.iter().any(|point_id| point_worker(point_id, 4) == 0) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            self.applied.lock().push((op_num, point_ids.clone()));
+            Ok(point_ids.len())
+        }
+    }
+
+    fn delete_points(ids: Vec<u64>) -> CollectionUpdateOperations {
+        CollectionUpdateOperations::PointOperation(PointOperations::DeletePoints {
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_update_workers_order() {
+        let updater = Arc::new(RecordingUpdater { applied: Mutex::new(vec![]) });
+        let (update_sender, update_receiver) = unbounded();
+        let workers = UpdateWorkers::new(4, 2, OverloadPolicy::Block, updater.clone(), update_sender);
+
+        let mut operation_id = 0;
+        for _ in 0..10 {
+            for id in 0..10 {
+                workers.submit(operation_id, delete_points(vec![id, id + 10, id + 20]), false).unwrap();
+                operation_id += 1;
+            }
+            let filter_operation = CollectionUpdateOperations::PointOperation(
+                PointOperations::DeletePointsByFilter { filter: Filter { should: None, must: None, min_should: None, must_not: None } }
+            );
+            workers.submit(operation_id, filter_operation, false).unwrap();
+            operation_id += 1;
+        }
+
+        let result = workers.submit(operation_id, delete_points((0..30).collect()), true).unwrap();
+        assert_eq!(result.unwrap().recv().unwrap().unwrap(), 30);
+        workers.stop();
+        assert!(workers.submit(operation_id + 1, delete_points(vec![1]), false).is_err());
+
+        // Optimizers are notified about each operation once
+        let notified = update_receiver.try_iter().filter(|signal| matches!(signal, UpdateSignal::Operation(_))).count();
+        assert_eq!(notified as u64, operation_id + 1);
+
+        let applied = updater.applied.lock();
+        let mut last_point_operation: HashMap<PointIdType, SeqNumberType> = HashMap::new();
+        let mut last_filter_operation = None;
+        for (op_num, point_ids) in applied.iter() {
+            if point_ids.is_empty() {
+                // All preceding operations are already applied
+                assert!(last_point_operation.values().all(|last| last < op_num));
+                last_filter_operation = Some(*op_num);
+            }
+            for point_id in point_ids {
+                // Changes of the point are applied in order and after the preceding filter operation
+                if let Some(last) = last_point_operation.insert(*point_id, *op_num) {
+                    assert!(last < *op_num);
+                }
+                assert!(last_filter_operation.map_or(true, |last| last < *op_num));
+            }
+        }
+    }
+
+    /// Applies operations once they are let through the gate
+    struct GatedUpdater {
+        gate: Receiver<()>,
+    }
+
+    impl SegmentUpdater for GatedUpdater {
+        fn update(&self, _op_num: SeqNumberType, _operation: CollectionUpdateOperations) -> CollectionResult<usize> {
+            self.gate.recv().unwrap();
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_update_workers_overload() {
+        let (gate_sender, gate) = unbounded();
+        let (update_sender, _update_receiver) = unbounded();
+        let workers = UpdateWorkers::new(1, 1, OverloadPolicy::Reject, Arc::new(GatedUpdater { gate }), update_sender);
+
+        // The first operation is taken by the worker, the second one fills the queue
+        for operation_id in 0..2 {
+            workers.check_overload().unwrap();
+            workers.mark_pending(operation_id);
+            workers.submit(operation_id, delete_points(vec![operation_id]), false).unwrap();
+            while operation_id == 0 && !workers.senders.lock()[0].is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        assert_eq!(workers.queue_depth(), 2);
+        assert!(matches!(workers.check_overload(), Err(CollectionError::Overloaded { .. })));
+
+        for _ in 0..3 {
+            gate_sender.send(()).unwrap();
+        }
+        workers.check_overload().unwrap();
+        workers.mark_pending(2);
+        let result = workers.submit(2, delete_points(vec![2]), true).unwrap();
+        assert_eq!(result.unwrap().recv().unwrap().unwrap(), 1);
+        assert_eq!(workers.queue_depth(), 0);
+    }
+}
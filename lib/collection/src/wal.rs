@@ -73,6 +73,11 @@ impl<'s, R: DeserializeOwned + Serialize + Debug> SerdeWal<R> {
         self.read(self.wal.first_index())
     }
 
+    /// Index of the oldest operation, which is not yet truncated
+    pub fn first_index(&self) -> u64 {
+        self.wal.first_index()
+    }
+
     pub fn len(&self) -> u64 {
         self.wal.num_entries()
     }
@@ -17,7 +17,7 @@ fn test_collection_reloading() {
         let (_rt, collection) = load_collection_fixture(collection_dir.path());
         let insert_points = CollectionUpdateOperations::PointOperation(
             PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
-                ids: vec![0, 1],
+                ids: vec![0, 1].into_iter().map(|x| x.into()).collect(),
                 vectors: vec![
                     vec![1.0, 0.0, 1.0, 1.0],
                     vec![1.0, 0.0, 1.0, 0.0],
@@ -1,19 +1,33 @@
 mod common;
 
 use collection::operations::CollectionUpdateOperations;
-use collection::operations::point_ops::{PointOperations, PointStruct};
+use collection::operations::point_ops::{PointOperations, PointStruct, PointVectors};
 
-use crate::common::{simple_collection_fixture, TEST_OPTIMIZERS_CONFIG};
-use collection::operations::types::{UpdateStatus, SearchRequest, RecommendRequest};
+use crate::common::{simple_collection_fixture, custom_collection_fixture, load_collection_fixture, TEST_OPTIMIZERS_CONFIG};
+use collection::operations::types::{CountRequest, HealthStatus, ReadConsistency, UpdateStatus, SearchRequest, SearchRequestBatch, RecommendRequest, RecommendStrategy, DiscoverRequest, ContextExamplePair, FusionSearchRequest, Fusion, ScrollRequest};
 use std::sync::Arc;
 use collection::operations::payload_ops::{PayloadOps, PayloadInterface, PayloadVariant};
 use std::collections::HashMap;
-use segment::types::PayloadKeyType;
+use segment::types::{PayloadKeyType, WithPayload, WithPayloadInterface, PointIdType};
 use collection::collection_builder::collection_loader::load_collection;
+use collection::optimization_pool::OptimizationPool;
+use collection::numa::NumaPlacement;
+use collection::async_collection::{AsyncCollection, BlockingPool, UpdateAdmission};
+use collection::search_cache::SearchCache;
 use wal::WalOptions;
 use tempdir::TempDir;
 use tokio::runtime;
 use collection::operations::point_ops::PointInsertOperations::{BatchPoints, PointsList};
+use collection::config::{CollectionConfig, CollectionConfigDiff, OptimizersConfigDiff, OverloadPolicy};
+use segment::types::{Indexes, FlushPolicy, Filter, Condition, HasIdCondition, FieldCondition, Match};
+use collection::collection::CollectionError;
+use collection::operations::FieldIndexOperations;
+use collection::strict_mode::StrictModeConfig;
+use collection::jsonl::{export_jsonl, import_jsonl};
+use segment::entry::entry_point::{OperationError, SegmentEntry};
+use segment::common::stop_condition::StopCondition;
+use segment::segment_constructor::readonly_bundle::open_readonly_bundle;
+use collection::operation_recorder::{read_recording, replay_recording, RecordedOperation};
 
 
 #[test]
@@ -24,7 +38,7 @@ fn test_collection_updater() {
 
     let insert_points = CollectionUpdateOperations::PointOperation(
         PointOperations::UpsertPoints(BatchPoints {
-            ids: vec![0, 1, 2, 3, 4],
+            ids: vec![0, 1, 2, 3, 4].into_iter().map(|x| x.into()).collect(),
             vectors: vec![
                 vec![1.0, 0.0, 1.0, 1.0],
                 vec![1.0, 0.0, 1.0, 0.0],
@@ -49,22 +63,78 @@ fn test_collection_updater() {
         vector: vec![1.0, 1.0, 1.0, 1.0],
         filter: None,
         params: None,
+        with_payload: None,
+        with_vector: false,
         top: 3,
+        offset: 0,
     });
 
-    let search_res = collection.search(search_request);
+    let search_res = collection.search(search_request, ReadConsistency::Any, &StopCondition::default());
 
 
     match search_res {
         Ok(res) => {
             assert_eq!(res.len(), 3);
-            assert_eq!(res[0].id, 2);
+            assert_eq!(res[0].id, 2.into());
         }
         Err(err) => assert!(false, format!("search failed: {:?}", err)),
     }
 }
 
 
+#[test]
+fn test_update_vectors() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 1.0, 0.0],
+                vec![1.0, 1.0, 1.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+    collection.update(insert_points, true).unwrap();
+
+    let mut payload: HashMap<PayloadKeyType, PayloadInterface> = Default::default();
+    payload.insert(
+        "color".to_string(),
+        PayloadInterface::Keyword(PayloadVariant::Value("red".to_string())),
+    );
+    let assign_payload = CollectionUpdateOperations::PayloadOperation(
+        PayloadOps::SetPayload {
+            payload,
+            points: vec![1.into()],
+        }
+    );
+    collection.update(assign_payload, true).unwrap();
+
+    let update_vectors = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpdateVectors {
+            points: vec![PointVectors { id: 1.into(), vector: vec![0.0, 1.0, 0.0, 0.0] }],
+        }
+    );
+    collection.update(update_vectors, true).unwrap();
+
+    let retrieved = collection.retrieve(&vec![1.into()], &WithPayload::from(true), true, ReadConsistency::Any).unwrap();
+    assert_eq!(retrieved[0].vector, Some(vec![0.0, 1.0, 0.0, 0.0]));
+    assert_eq!(retrieved[0].payload.as_ref().unwrap().len(), 1);
+
+    let update_missing = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpdateVectors {
+            points: vec![PointVectors { id: 100.into(), vector: vec![0.0, 1.0, 0.0, 0.0] }],
+        }
+    );
+    assert!(collection.update(update_missing, true).is_err());
+    assert_eq!(collection.retrieve(&vec![100.into()], &WithPayload::from(true), false, ReadConsistency::Any).unwrap().len(), 0);
+}
+
+
 #[test]
 fn test_collection_loading() {
     let collection_dir = TempDir::new("collection").unwrap();
@@ -74,7 +144,7 @@ fn test_collection_loading() {
 
         let insert_points = CollectionUpdateOperations::PointOperation(
             PointOperations::UpsertPoints(BatchPoints {
-                ids: vec![0, 1, 2, 3, 4],
+                ids: vec![0, 1, 2, 3, 4].into_iter().map(|x| x.into()).collect(),
                 vectors: vec![
                     vec![1.0, 0.0, 1.0, 1.0],
                     vec![1.0, 0.0, 1.0, 0.0],
@@ -98,7 +168,7 @@ fn test_collection_loading() {
         let assign_payload = CollectionUpdateOperations::PayloadOperation(
             PayloadOps::SetPayload {
                 payload,
-                points: vec![2, 3],
+                points: vec![2, 3].into_iter().map(|x| x.into()).collect(),
             }
         );
 
@@ -122,15 +192,18 @@ fn test_collection_loading() {
         collection_dir.path(),
         &wal_options,
         rt.clone(),
+        Arc::new(OptimizationPool::new(2)),
+        Arc::new(NumaPlacement::disabled()),
         &TEST_OPTIMIZERS_CONFIG,
+        None,
     );
 
-    let retrieved = loaded_collection.retrieve(&vec![1, 2], true, true).unwrap();
+    let retrieved = loaded_collection.retrieve(&vec![1.into(), 2.into()], &WithPayload::from(true), true, ReadConsistency::Any).unwrap();
 
     assert_eq!(retrieved.len(), 2);
 
     for record in retrieved {
-        if record.id == 2 {
+        if record.id == 2.into() {
             let non_empty_payload = record.payload.unwrap();
 
             assert_eq!(non_empty_payload.len(), 1)
@@ -143,7 +216,7 @@ fn test_collection_loading() {
 fn test_deserialization() {
     let insert_points = CollectionUpdateOperations::PointOperation(
         PointOperations::UpsertPoints(BatchPoints {
-            ids: vec![0, 1],
+            ids: vec![0, 1].into_iter().map(|x| x.into()).collect(),
             vectors: vec![
                 vec![1.0, 0.0, 1.0, 1.0],
                 vec![1.0, 0.0, 1.0, 0.0],
@@ -174,12 +247,12 @@ fn test_deserialization2() {
     let insert_points = CollectionUpdateOperations::PointOperation(
         PointOperations::UpsertPoints(PointsList(vec![
             PointStruct {
-                id: 0,
+                id: 0.into(),
                 vector: vec![1.0, 0.0, 1.0, 1.0],
                 payload: None,
             },
             PointStruct {
-                id: 1,
+                id: 1.into(),
                 vector: vec![1.0, 0.0, 1.0, 0.0],
                 payload: None,
             }
@@ -203,6 +276,27 @@ fn test_deserialization2() {
 }
 
 
+#[test]
+fn test_uuid_ids_serialization() {
+    let ids: Vec<PointIdType> = serde_json::from_str(r#"[42, "550e8400-e29b-41d4-a716-446655440000"]"#).unwrap();
+
+    let delete_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::DeletePoints { ids: ids.clone() }
+    );
+
+    let json_str = serde_json::to_string(&delete_points).unwrap();
+    assert!(json_str.contains(r#"[42,"550e8400-e29b-41d4-a716-446655440000"]"#));
+
+    let crob_bytes = rmp_serde::to_vec(&delete_points).unwrap();
+    let read_obj: CollectionUpdateOperations = rmp_serde::from_read_ref(&crob_bytes).unwrap();
+
+    match read_obj {
+        CollectionUpdateOperations::PointOperation(PointOperations::DeletePoints { ids: read_ids }) => assert_eq!(read_ids, ids),
+        _ => panic!("Wrong operation deserialized"),
+    }
+}
+
+
 #[test]
 fn test_recommendation_api() {
     let collection_dir = TempDir::new("collection").unwrap();
@@ -210,7 +304,7 @@ fn test_recommendation_api() {
 
     let insert_points = CollectionUpdateOperations::PointOperation(
         PointOperations::UpsertPoints(BatchPoints {
-            ids: vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
+            ids: vec![0, 1, 2, 3, 4, 5, 6, 7, 8].into_iter().map(|x| x.into()).collect(),
             vectors: vec![
                 vec![0.0, 0.0, 1.0, 1.0],
                 vec![1.0, 0.0, 0.0, 0.0],
@@ -229,14 +323,875 @@ fn test_recommendation_api() {
     collection.update(insert_points, true).unwrap();
 
     let result = collection.recommend(Arc::new(RecommendRequest {
-        positive: vec![0],
-        negative: vec![8],
+        positive: vec![0].into_iter().map(|x| x.into()).collect(),
+        negative: vec![8].into_iter().map(|x| x.into()).collect(),
         filter: None,
         params: None,
-        top: 5
+        with_payload: None,
+        with_vector: false,
+        top: 5,
+        offset: 0,
+        strategy: Default::default(),
+        lookup_from: None,
     })).unwrap();
     assert!(result.len() > 0);
-    let top1 = result[0];
+    let top1 = &result[0];
+
+    assert!(top1.id == 5.into() || top1.id == 6.into());
+}
+
+#[test]
+fn test_recommendation_best_score() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2, 3, 4, 5, 6, 7, 8].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![0.0, 0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+
+    collection.update(insert_points, true).unwrap();
+
+    let result = collection.recommend(Arc::new(RecommendRequest {
+        positive: vec![1, 3].into_iter().map(|x| x.into()).collect(),
+        negative: vec![7].into_iter().map(|x| x.into()).collect(),
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 10,
+        offset: 0,
+        strategy: RecommendStrategy::BestScore,
+        lookup_from: None,
+    })).unwrap();
+
+    // Example points are excluded, candidates are not repeated
+    assert_eq!(result.len(), 6);
+    for excluded in vec![1, 3, 7] {
+        assert!(result.iter().all(|point| point.id != excluded.into()));
+    }
+
+    // Points close to any of positive examples go first
+    let top2: Vec<PointIdType> = result[..2].iter().map(|point| point.id).collect();
+    assert!(top2.contains(&2.into()) && top2.contains(&4.into()));
+    assert_eq!(result[0].score, 1.0);
+    assert!(result[0].vector.is_none());
+
+    // Points closer to the negative example go last
+    let last = result.last().unwrap();
+    assert!(last.id == 0.into() || last.id == 8.into());
+    assert!(last.score < 0.0);
+}
+
+#[test]
+fn test_recommendation_lookup() {
+    let items_dir = TempDir::new("collection").unwrap();
+    let profiles_dir = TempDir::new("collection").unwrap();
+    let (_rt, items) = simple_collection_fixture(items_dir.path());
+    let (_profiles_rt, profiles) = simple_collection_fixture(profiles_dir.path());
+
+    items.update(CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![1, 2, 3].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+            ],
+            payloads: None,
+        })
+    ), true).unwrap();
+
+    // Profile has the same id as one of items, but it refers to a different point
+    profiles.update(CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![2.into()],
+            vectors: vec![vec![1.0, 0.0, 0.0, 0.0]],
+            payloads: None,
+        })
+    ), true).unwrap();
+
+    let result = items.recommend_with_lookup(Arc::new(RecommendRequest {
+        positive: vec![2.into()],
+        negative: vec![],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 3,
+        offset: 0,
+        strategy: Default::default(),
+        lookup_from: Some("profiles".to_string()),
+    }), &profiles).unwrap();
+
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[0].id, 1.into());
+}
+
+#[test]
+fn test_discovery_api() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2, 3, 4, 5, 6, 7, 8].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![0.0, 0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+
+    collection.update(insert_points, true).unwrap();
+
+    let context = vec![ContextExamplePair { positive: 5.into(), negative: 3.into() }];
+
+    let result = collection.discover(Arc::new(DiscoverRequest {
+        target: Some(1.into()),
+        context: context.clone(),
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 10,
+        offset: 0,
+    })).unwrap();
+
+    assert_eq!(result.len(), 6);
+    for excluded in vec![1, 3, 5] {
+        assert!(result.iter().all(|point| point.id != excluded.into()));
+    }
+
+    // Points within the context go first, even if they are not similar to the target
+    let top2: Vec<PointIdType> = result[..2].iter().map(|point| point.id).collect();
+    assert!(top2.contains(&0.into()) && top2.contains(&6.into()));
+    // The closest point to the target among the rest of points
+    assert_eq!(result[2].id, 2.into());
+
+    let result = collection.discover(Arc::new(DiscoverRequest {
+        target: None,
+        context,
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 10,
+        offset: 0,
+    })).unwrap();
+
+    let last = result.last().unwrap();
+    assert_eq!(last.id, 4.into());
+    assert_eq!(last.score, -1.0);
+
+    let empty_request = collection.discover(Arc::new(DiscoverRequest {
+        target: None,
+        context: vec![],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 10,
+        offset: 0,
+    }));
+    assert!(empty_request.is_err());
+}
+
+
+#[test]
+fn test_scroll() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![7, 3, 5, 1, 9].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 1.0, 0.0],
+                vec![1.0, 1.0, 1.0, 1.0],
+                vec![1.0, 1.0, 0.0, 1.0],
+                vec![1.0, 0.0, 0.0, 0.0],
+            ],
+            payloads: None,
+        })
+    );
+
+    collection.update(insert_points, true).unwrap();
+
+    let page1 = collection.scroll(Arc::new(ScrollRequest {
+        offset: None,
+        limit: 3,
+        filter: None,
+        with_payload: Some(WithPayloadInterface::Bool(false)),
+        with_vector: true,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+
+    assert_eq!(page1.points.iter().map(|x| x.id).collect::<Vec<_>>(), vec![1.into(), 3.into(), 5.into()]);
+    assert!(page1.points[0].vector.is_some());
+    assert_eq!(page1.next_page_offset, Some(7.into()));
+
+    let page2 = collection.scroll(Arc::new(ScrollRequest {
+        offset: page1.next_page_offset,
+        limit: 3,
+        filter: None,
+        with_payload: Some(WithPayloadInterface::Bool(false)),
+        with_vector: false,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+
+    assert_eq!(page2.points.iter().map(|x| x.id).collect::<Vec<_>>(), vec![7.into(), 9.into()]);
+    assert_eq!(page2.next_page_offset, None);
+}
+
+
+#[test]
+fn test_search_batch() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2, 3].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+    collection.update(insert_points, true).unwrap();
+
+    let search = |vector: Vec<f32>, filter: Option<Filter>| SearchRequest {
+        vector,
+        filter,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 1,
+        offset: 0,
+    };
+    let first_point = || Condition::HasId(HasIdCondition { has_id: vec![0.into()].into_iter().collect() });
+
+    let results = collection.search_batch(Arc::new(SearchRequestBatch {
+        searches: vec![
+            search(vec![1.0, 0.0, 0.0, 0.0], None),
+            search(vec![0.0, 0.0, 1.0, 0.0], None),
+            // Own filter overrides the shared one
+            search(vec![1.0, 0.0, 0.0, 0.0], Some(Filter::new_must(first_point()))),
+        ],
+        filter: Some(Filter::new_must_not(first_point())),
+        params: None,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_ne!(results[0][0].id, 0.into());
+    assert_eq!(results[1][0].id, 2.into());
+    assert_eq!(results[2][0].id, 0.into());
+}
+
+#[test]
+fn test_search_fusion() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2, 3].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 0.0, 0.0],
+                vec![0.6, 0.6, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+    collection.update(insert_points, true).unwrap();
+
+    let search = |vector: Vec<f32>| SearchRequest {
+        vector,
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 1,
+        offset: 0,
+    };
+
+    let result = collection.search_fusion(Arc::new(FusionSearchRequest {
+        searches: vec![
+            search(vec![1.0, 0.0, 0.0, 0.0]),
+            search(vec![0.0, 1.0, 0.0, 0.0]),
+        ],
+        fusion: Fusion::Rrf,
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: true,
+        top: 2,
+        offset: 0,
+    })).unwrap();
+
+    // Point 1 is the second best in both searches
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].id, 1.into());
+    assert!(result.iter().all(|point| point.vector.is_some()));
+    assert!(result.iter().all(|point| point.id != 3.into()));
+}
+
+#[test]
+fn test_update_collection_config() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+        assert_eq!(collection.info().unwrap().config.optimizers_config, None);
+
+        collection.update_config(&CollectionConfigDiff {
+            index: Some(Indexes::Hnsw { m: 32, ef_construct: 256 }),
+            flush_policy: Some(FlushPolicy::Operations { count: 10 }),
+            storage_type: None,
+            optimizers_config: Some(OptimizersConfigDiff {
+                indexing_threshold: Some(1000),
+                ..Default::default()
+            }),
+            write_consistency_factor: None,
+            strict_mode: None,
+        }).unwrap();
+
+        let config = collection.info().unwrap().config;
+        assert_eq!(config.params.index, Indexes::Hnsw { m: 32, ef_construct: 256 });
+        assert_eq!(config.params.vector_size, 4);
+        assert_eq!(config.optimizers_config.unwrap().indexing_threshold, 1000);
+    }
+
+    // Changed config is persisted
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    let config = collection.info().unwrap().config;
+    assert_eq!(config.params.index, Indexes::Hnsw { m: 32, ef_construct: 256 });
+    assert_eq!(config.params.flush_policy, Some(FlushPolicy::Operations { count: 10 }));
+    let optimizers_config = config.optimizers_config.unwrap();
+    assert_eq!(optimizers_config.indexing_threshold, 1000);
+    assert_eq!(optimizers_config.memmap_threshold, TEST_OPTIMIZERS_CONFIG.memmap_threshold);
+}
+
+#[test]
+fn test_wait_optimized() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    collection.update_config(&CollectionConfigDiff {
+        index: None,
+        flush_policy: None,
+        storage_type: None,
+        optimizers_config: Some(OptimizersConfigDiff {
+            indexing_threshold: Some(10),
+            max_optimization_threads: Some(2),
+            ..Default::default()
+        }),
+        write_consistency_factor: None,
+        strict_mode: None,
+    }).unwrap();
+
+    let insert_points = |ids: Vec<u64>| CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            vectors: ids.iter().map(|id| vec![*id as f32, 1.0, 0.0, 0.0]).collect(),
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+            payloads: None,
+        })
+    );
+
+    // Operation is acknowledged right after it is written to WAL
+    let result = collection.update(insert_points((0..50).collect()), false).unwrap();
+    assert_eq!(result.status, UpdateStatus::Acknowledged);
+
+    let result = collection.update(insert_points((50..100).collect()), true).unwrap();
+    assert_eq!(result.status, UpdateStatus::Completed);
+
+    collection.wait_optimized().unwrap();
+    let search_result = collection.search(Arc::new(SearchRequest {
+        vector: vec![1.0, 0.0, 0.0, 0.0],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 1,
+        offset: 0,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    assert_eq!(search_result[0].id, 99.into());
+
+    // All optimizations are finished
+    let optimizations = collection.optimizations().unwrap();
+    assert_eq!(optimizations.config.max_optimization_threads, 2);
+    assert_eq!(optimizations.config.indexing_threshold, 10);
+    assert!(optimizations.optimizations.is_empty());
+
+    let health = collection.health().unwrap();
+    assert_eq!(health.status, HealthStatus::Ok);
+    assert!(health.issues.is_empty());
+}
+
+#[test]
+fn test_update_workers() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = custom_collection_fixture(collection_dir.path(), |config| CollectionConfig {
+            update_workers: 4,
+            update_queue_size: 2,
+            ..config
+        });
+
+        for id in 0..20u64 {
+            collection.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(BatchPoints {
+                ids: vec![id.into(), (id + 100).into()],
+                vectors: vec![vec![id as f32, 1.0, 0.0, 0.0], vec![id as f32, 0.0, 1.0, 0.0]],
+                payloads: None,
+            })), false).unwrap();
+        }
+        // Deletion by filter is applied after all preceding upserts
+        collection.update(CollectionUpdateOperations::PointOperation(PointOperations::DeletePointsByFilter {
+            filter: Filter::new_must_not(Condition::HasId(HasIdCondition {
+                has_id: (0..10u64).map(|id| id.into()).collect(),
+            })),
+        }), false).unwrap();
+        let result = collection.update(CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePoints { ids: vec![1.into(), 2.into()] }
+        ), true).unwrap();
+        assert_eq!(result.status, UpdateStatus::Completed);
+
+        let count_request = Arc::new(CountRequest { filter: None, exact: true });
+        assert_eq!(collection.count(count_request, ReadConsistency::Any, &StopCondition::default()).unwrap().count, 8);
+    }
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    assert_eq!(collection.config.read().update_workers, 4);
+    let count_request = Arc::new(CountRequest { filter: None, exact: true });
+    assert_eq!(collection.count(count_request, ReadConsistency::Any, &StopCondition::default()).unwrap().count, 8);
+}
+
+#[test]
+fn test_strict_mode() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    collection.update_config(&CollectionConfigDiff {
+        index: None,
+        flush_policy: None,
+        storage_type: None,
+        optimizers_config: None,
+        write_consistency_factor: None,
+        strict_mode: Some(StrictModeConfig {
+            max_filter_conditions: Some(1),
+            max_top: Some(10),
+            max_batch_size: Some(2),
+            reject_unindexed_filter: true,
+            max_payload_size: Some(64),
+        }),
+    }).unwrap();
+
+    let violated_limit = |err: CollectionError| match err {
+        CollectionError::StrictModeViolation { limit, .. } => limit,
+        err => panic!("Unexpected error: {}", err),
+    };
+
+    let point = |id: u64, city: &str| PointStruct {
+        id: id.into(),
+        vector: vec![1.0, 0.0, 1.0, 1.0],
+        payload: Some(vec![("city".to_string(), PayloadInterface::Keyword(PayloadVariant::Value(city.to_string())))]
+            .into_iter().collect()),
+    };
+    let upsert = |points| CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(PointsList(points)));
+
+    collection.update(upsert(vec![point(1, "Berlin"), point(2, "London")]), true).unwrap();
+
+    let err = collection.update(upsert(vec![point(3, "Paris"), point(4, "Paris"), point(5, "Paris")]), true).unwrap_err();
+    assert_eq!(violated_limit(err), "max_batch_size");
+
+    let err = collection.update(upsert(vec![point(3, &"Paris".repeat(20))]), true).unwrap_err();
+    assert_eq!(violated_limit(err), "max_payload_size");
+
+    let city_condition = |city: &str| Condition::Field(FieldCondition {
+        key: "city".to_string(),
+        r#match: Some(Match { keyword: Some(city.to_string()), integer: None, text: None }),
+        range: None,
+        geo_bounding_box: None,
+        geo_radius: None,
+    });
+    let search = |top: usize, filter: Option<Filter>| Arc::new(SearchRequest {
+        vector: vec![1.0, 0.0, 1.0, 1.0],
+        filter,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top,
+        offset: 0,
+    });
+
+    let err = collection.search(search(11, None), ReadConsistency::Any, &StopCondition::default()).unwrap_err();
+    assert_eq!(violated_limit(err), "max_top");
+
+    let err = collection.search(search(3, Some(Filter::new_must(city_condition("Berlin")))), ReadConsistency::Any, &StopCondition::default()).unwrap_err();
+    assert_eq!(violated_limit(err), "reject_unindexed_filter");
+
+    collection.update(CollectionUpdateOperations::FieldIndexOperation(
+        FieldIndexOperations::CreateIndex("city".to_string())
+    ), true).unwrap();
+
+    let result = collection.search(search(3, Some(Filter::new_must(city_condition("Berlin")))), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    assert_eq!(result.len(), 1);
+
+    let two_conditions = Filter {
+        should: Some(vec![city_condition("Berlin"), city_condition("London")]),
+        must: None,
+        min_should: None,
+        must_not: None,
+    };
+    let err = collection.search(search(3, Some(two_conditions)), ReadConsistency::Any, &StopCondition::default()).unwrap_err();
+    assert_eq!(violated_limit(err), "max_filter_conditions");
+}
+
+/// `.npy` file with 2-D array of `f32` vectors
+fn write_npy(path: &std::path::Path, vectors: &[Vec<f32>]) {
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", vectors.len(), vectors[0].len());
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for value in vectors.iter().flatten() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn test_import_npy() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let import_dir = TempDir::new("import").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    collection.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(PointsList(vec![
+        PointStruct { id: 10.into(), vector: vec![1.0, 0.0, 0.0, 0.0], payload: None },
+        PointStruct { id: 20.into(), vector: vec![0.0, 0.0, 0.0, 1.0], payload: None },
+    ]))), true).unwrap();
+
+    let vectors_path = import_dir.path().join("vectors.npy");
+    write_npy(&vectors_path, &[
+        vec![0.0, 1.0, 0.0, 0.0],
+        vec![0.0, 0.0, 1.0, 0.0],
+        vec![1.0, 1.0, 0.0, 0.0],
+    ]);
+    let points_path = import_dir.path().join("points.jsonl");
+    std::fs::write(&points_path, r#"{"id": 10}
+{"id": 11, "payload": {"city": {"type": "keyword", "value": "Berlin"}}}
+{"id": 12}
+"#).unwrap();
+
+    assert_eq!(collection.import_npy(&vectors_path, Some(&points_path)).unwrap(), 3);
+
+    let count_request = Arc::new(CountRequest { filter: None, exact: true });
+    assert_eq!(collection.count(count_request, ReadConsistency::Any, &StopCondition::default()).unwrap().count, 4);
 
-    assert!(top1.id == 5 || top1.id == 6);
-}
\ No newline at end of file
+    // Imported point replaces the existing one with the same id
+    let retrieved = collection.retrieve(&vec![10.into(), 11.into()], &WithPayload::from(true), true, ReadConsistency::Any).unwrap();
+    let point_10 = retrieved.iter().find(|point| point.id == 10.into()).unwrap();
+    assert_eq!(point_10.vector, Some(vec![0.0, 1.0, 0.0, 0.0]));
+    let point_11 = retrieved.iter().find(|point| point.id == 11.into()).unwrap();
+    assert!(point_11.payload.as_ref().unwrap().contains_key("city"));
+
+    // Dimension of vectors should match the collection
+    write_npy(&vectors_path, &[vec![1.0, 0.0]]);
+    assert!(collection.import_npy(&vectors_path, None).is_err());
+}
+
+#[test]
+fn test_jsonl_export_import() {
+    let source_dir = TempDir::new("source").unwrap();
+    let target_dir = TempDir::new("target").unwrap();
+    let (_rt, source) = simple_collection_fixture(source_dir.path());
+    let (_rt, target) = simple_collection_fixture(target_dir.path());
+
+    let points: Vec<PointStruct> = (0..600u64)
+        .map(|id| PointStruct {
+            id: id.into(),
+            vector: vec![id as f32, 0.0, 1.0, 0.0],
+            payload: Some(vec![("city".to_string(), PayloadInterface::Keyword(PayloadVariant::Value(format!("city-{}", id % 3))))]
+                .into_iter().collect()),
+        })
+        .collect();
+    source.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(PointsList(points))), true).unwrap();
+
+    let mut exported = vec![];
+    assert_eq!(export_jsonl(&source, &mut exported).unwrap(), 600);
+    assert_eq!(exported.iter().filter(|byte| **byte == b'\n').count(), 600);
+
+    assert_eq!(import_jsonl(&target, exported.as_slice()).unwrap(), 600);
+
+    let count_request = Arc::new(CountRequest { filter: None, exact: true });
+    assert_eq!(target.count(count_request, ReadConsistency::Any, &StopCondition::default()).unwrap().count, 600);
+
+    let retrieved = target.retrieve(&vec![599.into()], &WithPayload::from(true), true, ReadConsistency::Any).unwrap();
+    assert_eq!(retrieved[0].vector, Some(vec![599.0, 0.0, 1.0, 0.0]));
+    assert!(retrieved[0].payload.as_ref().unwrap().contains_key("city"));
+
+    assert!(import_jsonl(&target, "{\"id\": 1}\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_export_bundle() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let bundle_dir = TempDir::new("bundle").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let points: Vec<PointStruct> = (0..600u64)
+        .map(|id| PointStruct {
+            id: id.into(),
+            vector: vec![id as f32, 0.0, 1.0, 0.0],
+            payload: None,
+        })
+        .collect();
+    collection.update(CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(PointsList(points))), true).unwrap();
+
+    let bundle_path = bundle_dir.path().join("bundle");
+    let manifest = collection.export_bundle(&bundle_path).unwrap();
+    assert_eq!(manifest.points_count, 600);
+    assert!(collection.export_bundle(&bundle_path).is_err());
+    assert!(!bundle_path.with_extension("tmp").exists());
+
+    let (manifest, mut segment) = open_readonly_bundle(&bundle_path).unwrap();
+    assert_eq!(manifest.points_count, 600);
+    assert_eq!(segment.vectors_count(), 600);
+
+    let found = segment.search(&vec![1.0, 0.0, 0.0, 0.0], &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
+    assert_eq!(found[0].id, 599.into());
+
+    match segment.upsert_point(1000, 1000.into(), &vec![0.0, 0.0, 0.0, 1.0]) {
+        Err(OperationError::ReadOnlyError) => {}
+        result => panic!("Bundle segment should be read-only, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_cancelled_requests() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+
+    let stop = StopCondition::default();
+    stop.stop();
+    let count_request = Arc::new(CountRequest { filter: None, exact: true });
+    assert!(matches!(
+        collection.count(count_request.clone(), ReadConsistency::Any, &stop),
+        Err(CollectionError::Cancelled { .. })
+    ));
+    let search_request = Arc::new(SearchRequest {
+        vector: vec![1.0, 0.0, 1.0, 1.0],
+        filter: None,
+        params: None,
+        top: 3,
+        offset: 0,
+        with_payload: None,
+        with_vector: false,
+    });
+    assert!(matches!(
+        collection.search(search_request, ReadConsistency::Any, &stop),
+        Err(CollectionError::Cancelled { .. })
+    ));
+    assert!(collection.count(count_request, ReadConsistency::Any, &StopCondition::default()).is_ok());
+}
+
+#[test]
+fn test_async_collection() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+    let collection = AsyncCollection::new(
+        Arc::new(collection),
+        Arc::new(BlockingPool::new(2)),
+        Arc::new(UpdateAdmission::new(1, OverloadPolicy::Block)),
+    );
+
+    let caller_rt = runtime::Builder::new_multi_thread().worker_threads(1).build().unwrap();
+    caller_rt.block_on(async {
+        let upsert = CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0.into(), 1.into(), 2.into()],
+            vectors: vec![vec![1.0, 0.0, 1.0, 1.0], vec![1.0, 0.0, 1.0, 0.0], vec![1.0, 1.0, 1.0, 1.0]],
+            payloads: None,
+        }));
+        let result = collection.update(upsert, true, false).await.unwrap();
+        assert_eq!(result.status, UpdateStatus::Completed);
+
+        let found = collection.search(Arc::new(SearchRequest {
+            vector: vec![1.0, 1.0, 1.0, 1.0],
+            filter: None,
+            params: None,
+            with_payload: None,
+            with_vector: false,
+            top: 2,
+            offset: 0,
+        }), ReadConsistency::Any, StopCondition::default()).await.unwrap();
+        assert_eq!(found[0].id, 2.into());
+
+        let count_request = Arc::new(CountRequest { filter: None, exact: true });
+        let count = collection.count(count_request, ReadConsistency::Any, StopCondition::default()).await.unwrap();
+        assert_eq!(count.count, 3);
+
+        let page = collection.scroll(Arc::new(ScrollRequest {
+            offset: None,
+            limit: 10,
+            filter: None,
+            with_payload: None,
+            with_vector: false,
+        }), ReadConsistency::Any, StopCondition::default()).await.unwrap();
+        assert_eq!(page.points.len(), 3);
+    });
+}
+
+#[test]
+fn test_search_cache() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let other_collection_dir = TempDir::new("other_collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+    let (_other_rt, other_collection) = simple_collection_fixture(other_collection_dir.path());
+
+    let pool = Arc::new(BlockingPool::new(2));
+    let admission = Arc::new(UpdateAdmission::new(0, OverloadPolicy::Block));
+    let cache = Arc::new(SearchCache::new(10));
+    let collection = AsyncCollection::new(Arc::new(collection), pool.clone(), admission.clone())
+        .with_search_cache("test", cache.clone());
+    let other_collection = AsyncCollection::new(Arc::new(other_collection), pool, admission)
+        .with_search_cache("test", cache.clone());
+
+    let upsert = |ids: Vec<u64>, vectors: Vec<Vec<f32>>| CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: ids.into_iter().map(|id| id.into()).collect(),
+            vectors,
+            payloads: None,
+        })
+    );
+    let search_request = Arc::new(SearchRequest {
+        vector: vec![1.0, 1.0, 1.0, 1.0],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 1,
+        offset: 0,
+    });
+
+    let caller_rt = runtime::Builder::new_multi_thread().worker_threads(1).build().unwrap();
+    caller_rt.block_on(async {
+        collection.update(upsert(vec![0, 1], vec![vec![1.0, 0.0, 0.0, 0.0], vec![1.0, 1.0, 0.0, 0.0]]), true, false).await.unwrap();
+
+        let recording_path = collection_dir.path().join("recording.jsonl");
+        collection.collection().start_recording(&recording_path).unwrap();
+        for _ in 0..2 {
+            let found = collection.search(search_request.clone(), ReadConsistency::Any, StopCondition::default()).await.unwrap();
+            assert_eq!(found[0].id, 1.into());
+        }
+        assert_eq!(cache.telemetry().hits, 1);
+        collection.collection().stop_recording();
+
+        // Search, served from the cache, is recorded as well
+        let entries = read_recording(std::io::BufReader::new(std::fs::File::open(&recording_path).unwrap())).unwrap();
+        assert_eq!(entries.len(), 2);
+        for entry in &entries {
+            assert!(matches!(&entry.operation, RecordedOperation::Search { result, error: None, .. } if result[0].id == 1.into()));
+        }
+
+        // Update invalidates cached results
+        collection.update(upsert(vec![2], vec![vec![1.0, 1.0, 1.0, 1.0]]), true, false).await.unwrap();
+        let found = collection.search(search_request.clone(), ReadConsistency::Any, StopCondition::default()).await.unwrap();
+        assert_eq!(found[0].id, 2.into());
+
+        // Results are not shared with another collection of the same name
+        let found = other_collection.search(search_request.clone(), ReadConsistency::Any, StopCondition::default()).await.unwrap();
+        assert!(found.is_empty());
+
+        let telemetry = cache.telemetry();
+        assert_eq!(telemetry.hits, 1);
+        assert_eq!(telemetry.misses, 3);
+        assert!(telemetry.memory_usage_bytes > 0);
+        assert_eq!(telemetry.collections_memory_usage["test"], telemetry.memory_usage_bytes);
+    });
+}
+
+#[test]
+fn test_record_and_replay() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+    let recording_path = collection_dir.path().join("recording.jsonl");
+    collection.start_recording(&recording_path).unwrap();
+
+    let insert_points = CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(BatchPoints {
+            ids: vec![0, 1, 2].into_iter().map(|x| x.into()).collect(),
+            vectors: vec![
+                vec![1.0, 0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 1.0, 0.0],
+                vec![1.0, 1.0, 1.0, 1.0],
+            ],
+            payloads: None,
+        })
+    );
+    collection.update(insert_points, true).unwrap();
+    let search_request = SearchRequest {
+        vector: vec![1.0, 1.0, 1.0, 1.0],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 2,
+        offset: 0,
+    };
+    collection.search(Arc::new(search_request.clone()), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    let delete_points = CollectionUpdateOperations::PointOperation(PointOperations::DeletePoints { ids: vec![2.into()] });
+    collection.update(delete_points, true).unwrap();
+    let wrong_search = SearchRequest { vector: vec![1.0], ..search_request.clone() };
+    assert!(collection.search(Arc::new(wrong_search), ReadConsistency::Any, &StopCondition::default()).is_err());
+    collection.search(Arc::new(search_request), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    collection.stop_recording();
+
+    let entries = read_recording(std::io::BufReader::new(std::fs::File::open(&recording_path).unwrap())).unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries.iter().map(|entry| entry.seq).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+    assert!(matches!(&entries[3].operation, RecordedOperation::Search { error: Some(_), .. }));
+
+    let replay_dir = TempDir::new("replay").unwrap();
+    let (_replay_rt, replay_collection) = simple_collection_fixture(replay_dir.path());
+    let report = replay_recording(&replay_collection, entries.clone()).unwrap();
+    assert_eq!(report.updates, 2);
+    assert_eq!(report.searches, 3);
+    assert!(report.mismatches.is_empty(), "{:?}", report.mismatches);
+
+    // Replay into the collection with other points reports differing searches
+    let other_dir = TempDir::new("replay").unwrap();
+    let (_other_rt, other_collection) = simple_collection_fixture(other_dir.path());
+    let report = replay_recording(&other_collection, entries.into_iter().filter(|entry| entry.seq != 2)).unwrap();
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].seq, 4);
+}
@@ -8,6 +8,9 @@ use std::sync::Arc;
 use std::path::Path;
 use collection::collection_builder::optimizers_builder::OptimizersConfig;
 use collection::collection_builder::collection_loader::load_collection;
+use collection::config::CollectionConfig;
+use collection::optimization_pool::OptimizationPool;
+use collection::numa::NumaPlacement;
 
 
 pub const TEST_OPTIMIZERS_CONFIG: OptimizersConfig = OptimizersConfig {
@@ -18,6 +21,9 @@ pub const TEST_OPTIMIZERS_CONFIG: OptimizersConfig = OptimizersConfig {
     indexing_threshold: 50_000,
     payload_indexing_threshold: 20_000,
     flush_interval_sec: 30,
+    max_segment_size: None,
+    max_optimization_threads: 1,
+    defragmentation_key: None,
 };
 
 
@@ -37,13 +43,39 @@ pub fn load_collection_fixture(collection_path: &Path) -> (Arc<Runtime>, Collect
         collection_path,
         &wal_options,
         threaded_rt.clone(),
+        Arc::new(OptimizationPool::new(2)),
+        Arc::new(NumaPlacement::disabled()),
         &TEST_OPTIMIZERS_CONFIG,
+        None,
     );
 
     return (threaded_rt, collection);
 }
 
 pub fn simple_collection_fixture(collection_path: &Path) -> (Arc<Runtime>, Collection) {
+    sharded_collection_fixture(collection_path, 1)
+}
+
+#[allow(dead_code)]
+pub fn sharded_collection_fixture(collection_path: &Path, shard_number: usize) -> (Arc<Runtime>, Collection) {
+    replicated_collection_fixture(collection_path, shard_number, 1)
+}
+
+#[allow(dead_code)]
+pub fn replicated_collection_fixture(
+    collection_path: &Path,
+    shard_number: usize,
+    replication_factor: usize,
+) -> (Arc<Runtime>, Collection) {
+    custom_collection_fixture(collection_path, |config| CollectionConfig { shard_number, replication_factor, ..config })
+}
+
+/// Collection with default test parameters, changed by `configure`
+#[allow(dead_code)]
+pub fn custom_collection_fixture(
+    collection_path: &Path,
+    configure: impl FnOnce(CollectionConfig) -> CollectionConfig,
+) -> (Arc<Runtime>, Collection) {
     let wal_options = WalOptions {
         segment_capacity: 100,
         segment_queue_len: 0,
@@ -58,6 +90,8 @@ pub fn simple_collection_fixture(collection_path: &Path) -> (Arc<Runtime>, Colle
         payload_index: Some(Default::default()),
         distance: Distance::Dot,
         storage_type: Default::default(),
+        text_analyzers: Default::default(),
+        flush_policy: None,
     };
 
     let threaded_rt = Arc::new(runtime::Builder::new_multi_thread()
@@ -68,8 +102,10 @@ pub fn simple_collection_fixture(collection_path: &Path) -> (Arc<Runtime>, Colle
     let collection = build_collection(
         collection_path,
         &wal_options,
-        &collection_config,
+        &configure(CollectionConfig::new(collection_config)),
         threaded_rt.clone(),
+        Arc::new(OptimizationPool::new(2)),
+        Arc::new(NumaPlacement::disabled()),
         &TEST_OPTIMIZERS_CONFIG,
     ).unwrap();
 
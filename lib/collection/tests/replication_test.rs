@@ -0,0 +1,257 @@
+mod common;
+
+use std::sync::Arc;
+
+use tempdir::TempDir;
+
+use collection::collection::CollectionError;
+use collection::collection_builder::collection_builder::build_collection;
+use collection::config::{CollectionConfig, CollectionConfigDiff};
+use collection::optimization_pool::OptimizationPool;
+use collection::numa::NumaPlacement;
+use collection::operations::CollectionUpdateOperations;
+use collection::operations::point_ops::{PointInsertOperations, PointOperations};
+use collection::operations::types::{CountRequest, ReadConsistency, SearchRequest};
+use collection::shard::{ReplicaId, ShardOperations, replica_path, shard_path};
+use collection::shard::replica_set::ReplicaState;
+use segment::types::{PointIdType, WithPayload};
+use segment::common::stop_condition::StopCondition;
+use wal::WalOptions;
+
+use crate::common::{TEST_OPTIMIZERS_CONFIG, custom_collection_fixture, load_collection_fixture, replicated_collection_fixture};
+
+fn upsert_points(ids: Vec<u64>, dim: usize) -> CollectionUpdateOperations {
+    CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
+            vectors: ids.iter().map(|id| {
+                let mut vector = vec![0.0; dim];
+                vector[0] = *id as f32;
+                vector
+            }).collect(),
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+            payloads: None,
+        })
+    )
+}
+
+fn count_all() -> Arc<CountRequest> {
+    Arc::new(CountRequest { filter: None, exact: true })
+}
+
+#[test]
+fn test_replicas_receive_all_operations() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 2, 3);
+
+    collection.update(upsert_points((0..10).collect(), 4), true).unwrap();
+    // Rejected operation still consumes an id of the primary
+    assert!(collection.update(upsert_points(vec![10], 3), true).is_err());
+    collection.update(upsert_points(vec![11], 4), true).unwrap();
+
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 11);
+    assert_eq!(collection.info().unwrap().vectors_count, 11);
+
+    for shard in collection.shards.shards() {
+        let expected_count = shard.count(count_all(), &StopCondition::default()).unwrap();
+        let expected_next_id = shard.next_operation_id().unwrap();
+        for replica in shard.replicas().values() {
+            assert_eq!(replica.count(count_all(), &StopCondition::default()).unwrap(), expected_count);
+            assert_eq!(replica.next_operation_id().unwrap(), expected_next_id);
+        }
+        assert!(shard.replica_states().values().all(|state| *state == ReplicaState::Active));
+    }
+
+    let result = collection.search(Arc::new(SearchRequest {
+        vector: vec![1.0, 0.0, 0.0, 0.0],
+        filter: None,
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 3,
+        offset: 0,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    let found_ids: Vec<PointIdType> = result.iter().map(|point| point.id).collect();
+    assert_eq!(found_ids, vec![11.into(), 9.into(), 8.into()]);
+}
+
+#[test]
+fn test_promote_replica() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 1, 2);
+        collection.update(upsert_points(vec![1, 2, 3], 4), true).unwrap();
+
+        let shard = &collection.shards.shards()[0];
+        assert_eq!(shard.primary(), 0);
+        shard.mark_dead(0).unwrap();
+        assert_eq!(shard.primary(), 1);
+        // The last active replica is kept
+        assert!(shard.mark_dead(1).is_err());
+
+        collection.update(upsert_points(vec![4, 5], 4), true).unwrap();
+        assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 5);
+        assert_eq!(shard.replicas()[&0].count(count_all(), &StopCondition::default()).unwrap(), 3);
+    }
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    let shard = &collection.shards.shards()[0];
+    assert_eq!(shard.primary(), 1);
+    assert_eq!(shard.replica_states()[&0], ReplicaState::Dead);
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 5);
+}
+
+#[test]
+fn test_out_of_sync_replica_is_dead_after_load() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 1, 3);
+        collection.update(upsert_points(vec![1, 2, 3], 4), true).unwrap();
+
+        // Simulate interruption, after which only one replica got the operation
+        let shard = &collection.shards.shards()[0];
+        shard.replicas()[&2].update(upsert_points(vec![4], 4), true).unwrap();
+    }
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    let shard = &collection.shards.shards()[0];
+    assert_eq!(shard.replica_states()[&0], ReplicaState::Active);
+    assert_eq!(shard.replica_states()[&1], ReplicaState::Active);
+    assert_eq!(shard.replica_states()[&2], ReplicaState::Dead);
+
+    // Reads are only served by replicas in sync
+    for _ in 0..3 {
+        assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 3);
+    }
+}
+
+#[test]
+fn test_transfer_replica() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 1, 2);
+        collection.update(upsert_points((0..10).collect(), 4), true).unwrap();
+        collection.flush_all().unwrap();
+        collection.update(upsert_points((10..15).collect(), 4), true).unwrap();
+        collection.update(CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePoints { ids: vec![3.into()] }
+        ), true).unwrap();
+
+        let shard = &collection.shards.shards()[0];
+        shard.mark_dead(0).unwrap();
+        collection.update(upsert_points(vec![15], 4), true).unwrap();
+
+        // Cancelled transfer leaves replicas as they were
+        let cancelled = StopCondition::default();
+        cancelled.stop();
+        assert!(matches!(collection.transfer_replica(0, 0, &cancelled), Err(CollectionError::Cancelled { .. })));
+        let replica_ids: Vec<ReplicaId> = shard.replica_states().keys().copied().collect();
+        assert_eq!(replica_ids, vec![0, 1]);
+        assert!(!replica_path(&shard_path(collection_dir.path(), 0), 2).exists());
+
+        // Dead replica is replaced with a copy of the primary
+        let new_replica_id = collection.transfer_replica(0, 0, &StopCondition::default()).unwrap();
+        assert_eq!(new_replica_id, 2);
+        let replica_ids: Vec<ReplicaId> = shard.replica_states().keys().copied().collect();
+        assert_eq!(replica_ids, vec![1, 2]);
+        assert!(shard.replica_states().values().all(|state| *state == ReplicaState::Active));
+        assert!(!replica_path(&shard_path(collection_dir.path(), 0), 0).exists());
+
+        let new_replica = shard.replicas()[&2].clone();
+        assert_eq!(new_replica.count(count_all(), &StopCondition::default()).unwrap(), 15);
+        assert_eq!(new_replica.next_operation_id().unwrap(), shard.next_operation_id().unwrap());
+
+        // New replica receives further operations
+        collection.update(upsert_points(vec![16], 4), true).unwrap();
+        assert_eq!(new_replica.count(count_all(), &StopCondition::default()).unwrap(), 16);
+
+        // Primary could be moved as well
+        assert_eq!(collection.transfer_replica(0, 1, &StopCondition::default()).unwrap(), 3);
+        assert_eq!(shard.primary(), 3);
+        assert!(collection.transfer_replica(0, 1, &StopCondition::default()).is_err());
+    }
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    let shard = &collection.shards.shards()[0];
+    let replica_ids: Vec<ReplicaId> = shard.replica_states().keys().copied().collect();
+    assert_eq!(replica_ids, vec![2, 3]);
+    for replica in shard.replicas().values() {
+        assert_eq!(replica.count(count_all(), &StopCondition::default()).unwrap(), 16);
+    }
+}
+
+#[test]
+fn test_write_consistency_factor() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (rt, collection) = custom_collection_fixture(collection_dir.path(), |config| CollectionConfig {
+        replication_factor: 2,
+        write_consistency_factor: 2,
+        ..config
+    });
+
+    collection.update(upsert_points(vec![1, 2, 3], 4), true).unwrap();
+
+    // Not enough replicas are left to acknowledge updates
+    let shard = &collection.shards.shards()[0];
+    shard.mark_dead(1).unwrap();
+    assert!(collection.update(upsert_points(vec![4], 4), true).is_err());
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 3);
+
+    let diff = |write_consistency_factor| CollectionConfigDiff {
+        index: None,
+        flush_policy: None,
+        storage_type: None,
+        optimizers_config: None,
+        write_consistency_factor: Some(write_consistency_factor),
+        strict_mode: None,
+    };
+    assert!(collection.update_config(&diff(3)).is_err());
+    collection.update_config(&diff(1)).unwrap();
+    collection.update(upsert_points(vec![4], 4), true).unwrap();
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 4);
+
+    let other_dir = TempDir::new("collection").unwrap();
+    let invalid_config = CollectionConfig {
+        replication_factor: 2,
+        write_consistency_factor: 3,
+        ..collection.config.read().clone()
+    };
+    assert!(build_collection(
+        other_dir.path(),
+        &WalOptions { segment_capacity: 100, segment_queue_len: 0 },
+        &invalid_config,
+        rt.clone(),
+        Arc::new(OptimizationPool::new(2)),
+        Arc::new(NumaPlacement::disabled()),
+        &TEST_OPTIMIZERS_CONFIG,
+    ).is_err());
+}
+
+#[test]
+fn test_read_consistency() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 1, 3);
+    collection.update(upsert_points(vec![1, 2, 3], 4), true).unwrap();
+
+    // Only one replica received the latest operation
+    let shard = &collection.shards.shards()[0];
+    shard.replicas()[&1].update(upsert_points(vec![4], 4), true).unwrap();
+
+    // Results of the most up-to-date replica are returned, if several replicas are read
+    for _ in 0..3 {
+        assert_eq!(collection.count(count_all(), ReadConsistency::All, &StopCondition::default()).unwrap().count, 4);
+        let records = collection.retrieve(&vec![4.into()], &WithPayload::from(false), false, ReadConsistency::All).unwrap();
+        assert_eq!(records.len(), 1);
+    }
+    let counts: Vec<usize> = (0..3)
+        .map(|_| collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count)
+        .collect();
+    assert!(counts.contains(&3));
+
+    // Majority is still available with one replica out of three missing
+    shard.mark_dead(2).unwrap();
+    assert!(collection.count(count_all(), ReadConsistency::Majority, &StopCondition::default()).is_ok());
+    assert!(collection.count(count_all(), ReadConsistency::All, &StopCondition::default()).is_err());
+}
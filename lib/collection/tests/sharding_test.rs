@@ -0,0 +1,259 @@
+mod common;
+
+use std::fs::{read_dir, remove_dir_all, rename};
+use std::sync::Arc;
+
+use tempdir::TempDir;
+
+use collection::collection::CollectionError;
+use collection::operations::{CollectionUpdateOperations, VersionedPointOperation};
+use collection::config::CollectionConfig;
+use collection::operations::payload_ops::{PayloadInterface, PayloadOps, PayloadVariant};
+use collection::operations::point_ops::{PointInsertOperations, PointOperations, PointStruct};
+use collection::operations::types::{CountRequest, ReadConsistency, ScrollRequest, SearchRequest};
+use collection::shard::{SHARDS_DIR, ShardOperations, replica_path, shard_path};
+use segment::types::{Condition, FieldCondition, Filter, Match, PointIdType, WithPayload};
+use segment::common::stop_condition::StopCondition;
+
+use crate::common::{custom_collection_fixture, load_collection_fixture, sharded_collection_fixture, simple_collection_fixture};
+
+fn upsert_points(ids: Vec<u64>) -> CollectionUpdateOperations {
+    CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
+            vectors: ids.iter().map(|id| vec![*id as f32, 1.0, 0.0, 0.0]).collect(),
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+            payloads: None,
+        })
+    )
+}
+
+fn count_all() -> Arc<CountRequest> {
+    Arc::new(CountRequest { filter: None, exact: true })
+}
+
+#[test]
+fn test_sharded_collection() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = sharded_collection_fixture(collection_dir.path(), 3);
+        collection.update(upsert_points((0..10).collect()), true).unwrap();
+
+        assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+        assert_eq!(collection.info().unwrap().vectors_count, 10);
+        // Points are distributed over all shards
+        for shard in collection.shards.shards() {
+            assert!(shard.count(count_all(), &StopCondition::default()).unwrap() > 0);
+        }
+
+        let result = collection.search(Arc::new(SearchRequest {
+            vector: vec![1.0, 0.0, 0.0, 0.0],
+            filter: None,
+            params: None,
+            with_payload: None,
+            with_vector: false,
+            top: 3,
+            offset: 1,
+        }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+        let found_ids: Vec<PointIdType> = result.iter().map(|point| point.id).collect();
+        assert_eq!(found_ids, vec![8.into(), 7.into(), 6.into()]);
+
+        let requested_ids: Vec<PointIdType> = vec![5.into(), 1.into(), 42.into(), 3.into()];
+        let records = collection.retrieve(&requested_ids, &WithPayload::from(false), true, ReadConsistency::Any).unwrap();
+        let retrieved_ids: Vec<PointIdType> = records.iter().map(|record| record.id).collect();
+        assert_eq!(retrieved_ids, vec![5.into(), 1.into(), 3.into()]);
+
+        collection.update(CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePoints { ids: vec![0.into(), 4.into()] }
+        ), true).unwrap();
+    }
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    assert_eq!(collection.shards.shards().len(), 3);
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 8);
+
+    let page = collection.scroll(Arc::new(ScrollRequest {
+        offset: Some(2.into()),
+        limit: 4,
+        filter: None,
+        with_payload: None,
+        with_vector: false,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    let page_ids: Vec<PointIdType> = page.points.iter().map(|point| point.id).collect();
+    assert_eq!(page_ids, vec![2.into(), 3.into(), 5.into(), 6.into()]);
+    assert_eq!(page.next_page_offset, Some(7.into()));
+}
+
+#[test]
+fn test_load_unsharded_collection() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = simple_collection_fixture(collection_dir.path());
+        collection.update(upsert_points(vec![1, 2, 3]), true).unwrap();
+    }
+
+    // Restore layout of collections, created before sharding
+    let first_replica_path = replica_path(&shard_path(collection_dir.path(), 0), 0);
+    for entry in read_dir(&first_replica_path).unwrap() {
+        let path = entry.unwrap().path();
+        rename(&path, collection_dir.path().join(path.file_name().unwrap())).unwrap();
+    }
+    remove_dir_all(collection_dir.path().join(SHARDS_DIR)).unwrap();
+
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 3);
+    assert!(first_replica_path.join("segments").exists());
+    assert!(!collection_dir.path().join("segments").exists());
+}
+
+fn tenant_filter(tenant: &str) -> Filter {
+    Filter {
+        should: None,
+        must: Some(vec![Condition::Field(FieldCondition {
+            key: "tenant".to_string(),
+            r#match: Some(Match { keyword: Some(tenant.to_string()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        })]),
+        min_should: None,
+        must_not: None,
+    }
+}
+
+fn upsert_tenant_points(points: Vec<(u64, &str)>) -> CollectionUpdateOperations {
+    CollectionUpdateOperations::PointOperation(PointOperations::UpsertPoints(PointInsertOperations::PointsList(
+        points.into_iter()
+            .map(|(id, tenant)| PointStruct {
+                id: id.into(),
+                vector: vec![id as f32, 1.0, 0.0, 0.0],
+                payload: Some(vec![
+                    ("tenant".to_string(), PayloadInterface::Keyword(PayloadVariant::Value(tenant.to_string())))
+                ].into_iter().collect()),
+            })
+            .collect()
+    )))
+}
+
+fn count_tenant(tenant: &str) -> Arc<CountRequest> {
+    Arc::new(CountRequest { filter: Some(tenant_filter(tenant)), exact: true })
+}
+
+#[test]
+fn test_shard_key_routing() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let (_rt, collection) = custom_collection_fixture(collection_dir.path(), |config| CollectionConfig {
+        shard_number: 4,
+        shard_key: Some("tenant".to_string()),
+        ..config
+    });
+
+    let tenants = ["tenant_a", "tenant_b", "tenant_c"];
+    collection.update(upsert_tenant_points((0..12).map(|id| (id, tenants[id as usize % 3])).collect()), true).unwrap();
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 12);
+
+    // All points of a tenant are stored in a single shard
+    for tenant in tenants.iter() {
+        let shard_counts: Vec<usize> = collection.shards.shards().iter()
+            .map(|shard| shard.count(count_tenant(tenant), &StopCondition::default()).unwrap())
+            .collect();
+        assert_eq!(shard_counts.iter().filter(|count| **count > 0).count(), 1);
+        assert_eq!(collection.count(count_tenant(tenant), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 4);
+    }
+
+    let result = collection.search(Arc::new(SearchRequest {
+        vector: vec![1.0, 0.0, 0.0, 0.0],
+        filter: Some(tenant_filter("tenant_b")),
+        params: None,
+        with_payload: None,
+        with_vector: false,
+        top: 2,
+        offset: 0,
+    }), ReadConsistency::Any, &StopCondition::default()).unwrap();
+    let found_ids: Vec<PointIdType> = result.iter().map(|point| point.id).collect();
+    assert_eq!(found_ids, vec![10.into(), 7.into()]);
+
+    // Point moves to the shard of its new tenant
+    collection.update(upsert_tenant_points(vec![(0, "tenant_b")]), true).unwrap();
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 12);
+    assert_eq!(collection.count(count_tenant("tenant_a"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 3);
+    assert_eq!(collection.count(count_tenant("tenant_b"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 5);
+
+    // Points are found by ids in any shard
+    collection.update(CollectionUpdateOperations::PointOperation(
+        PointOperations::DeletePoints { ids: vec![0.into(), 1.into()] }
+    ), true).unwrap();
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+    let records = collection.retrieve(&vec![2.into(), 1.into(), 3.into()], &WithPayload::from(false), false, ReadConsistency::Any).unwrap();
+    let retrieved_ids: Vec<PointIdType> = records.iter().map(|record| record.id).collect();
+    assert_eq!(retrieved_ids, vec![2.into(), 3.into()]);
+
+    // Shard key is required and could only be changed by upsert
+    assert!(collection.update(upsert_points(vec![20]), true).is_err());
+    assert!(collection.update(CollectionUpdateOperations::PayloadOperation(PayloadOps::SetPayload {
+        payload: vec![
+            ("tenant".to_string(), PayloadInterface::Keyword(PayloadVariant::Value("tenant_c".to_string())))
+        ].into_iter().collect(),
+        points: vec![2.into()],
+    }), true).is_err());
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+}
+
+fn into_point_operation(operation: CollectionUpdateOperations) -> PointOperations {
+    match operation {
+        CollectionUpdateOperations::PointOperation(operation) => operation,
+        _ => panic!("not a point operation"),
+    }
+}
+
+#[test]
+fn test_repeated_update_keeps_newer_changes() {
+    let collection_dir = TempDir::new("collection").unwrap();
+
+    {
+        let (_rt, collection) = sharded_collection_fixture(collection_dir.path(), 3);
+        collection.update(upsert_points((0..10).collect()), true).unwrap();
+
+        // Operation is applied, but its source stops before it records that, e.g. a consumer of a message
+        let next_operation_ids = collection.next_operation_ids().unwrap();
+        assert_eq!(next_operation_ids.len(), 3);
+        let message = into_point_operation(upsert_tenant_points((0..12).map(|id| (id, "message")).collect()));
+        collection.update(CollectionUpdateOperations::PointOperation(message.clone()), true).unwrap();
+
+        // Newer changes of some of the points
+        collection.update(upsert_tenant_points(vec![(1, "newer"), (5, "newer"), (6, "newer")]), true).unwrap();
+        collection.update(CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePoints { ids: vec![7.into()] }
+        ), true).unwrap();
+
+        // Operation is delivered again
+        collection.update_unchanged_since(message, &next_operation_ids, true).unwrap();
+        assert_eq!(collection.count(count_tenant("newer"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 3);
+        assert_eq!(collection.count(count_tenant("message"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 8);
+        assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 11);
+
+        // Operation, which was not applied before, changes points as usual
+        let next_operation_ids = collection.next_operation_ids().unwrap();
+        let message = into_point_operation(upsert_tenant_points(vec![(1, "message"), (20, "message")]));
+        collection.update_unchanged_since(message, &next_operation_ids, true).unwrap();
+        assert_eq!(collection.count(count_tenant("newer"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 2);
+        assert_eq!(collection.count(count_tenant("message"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+    }
+
+    // Versions of the repeated operations are kept in WAL
+    let (_rt, collection) = load_collection_fixture(collection_dir.path());
+    assert_eq!(collection.count(count_tenant("newer"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 2);
+    assert_eq!(collection.count(count_tenant("message"), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+
+    let versioned = CollectionUpdateOperations::VersionedPointOperation(VersionedPointOperation {
+        version: 0,
+        operation: PointOperations::DeletePoints { ids: vec![1.into()] },
+    });
+    assert!(matches!(collection.update(versioned, true), Err(CollectionError::BadRequest { .. })));
+    assert!(collection.update_unchanged_since(
+        PointOperations::DeletePoints { ids: vec![1.into()] },
+        &collection.next_operation_ids().unwrap()[..2],
+        true,
+    ).is_err());
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use std::fs::{File, remove_dir_all};
+use std::path::Path;
+use std::sync::Arc;
+
+use tar::{Archive, Builder};
+use tempdir::TempDir;
+
+use collection::collection_builder::collection_loader::restore_snapshot;
+use collection::operations::CollectionUpdateOperations;
+use collection::operations::payload_ops::{PayloadInterface, PayloadOps, PayloadVariant};
+use collection::operations::point_ops::{PointInsertOperations, PointOperations};
+use collection::operations::types::{CountRequest, ReadConsistency};
+use collection::shard::{ShardOperations, replica_path, shard_path};
+use collection::shard::replica_set::ReplicaState;
+use collection::snapshot_manifest::{CURRENT_SNAPSHOT_FORMAT_VERSION, SNAPSHOT_MANIFEST_FILE, SnapshotManifest};
+use segment::types::{PayloadType, WithPayload};
+use segment::common::stop_condition::StopCondition;
+
+use crate::common::{load_collection_fixture, replicated_collection_fixture};
+
+fn upsert_points(ids: Vec<u64>) -> CollectionUpdateOperations {
+    CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
+            vectors: ids.iter().map(|id| vec![*id as f32, 1.0, 0.0, 0.0]).collect(),
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+            payloads: None,
+        })
+    )
+}
+
+fn count_all() -> Arc<CountRequest> {
+    Arc::new(CountRequest { filter: None, exact: true })
+}
+
+fn unpack_snapshot(snapshot_path: &Path) -> TempDir {
+    let unpacked_dir = TempDir::new("unpacked").unwrap();
+    Archive::new(File::open(snapshot_path).unwrap()).unpack(unpacked_dir.path()).unwrap();
+    unpacked_dir
+}
+
+fn pack_snapshot(unpacked_path: &Path, snapshot_path: &Path) {
+    let mut builder = Builder::new(File::create(snapshot_path).unwrap());
+    builder.append_dir_all(".", unpacked_path).unwrap();
+    builder.finish().unwrap();
+}
+
+#[test]
+fn test_collection_snapshot() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let snapshots_dir = TempDir::new("snapshots").unwrap();
+    let snapshot_path = snapshots_dir.path().join("test.snapshot");
+
+    {
+        let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 2, 2);
+        collection.update(upsert_points((0..10).collect()), true).unwrap();
+        // Some of the operations are persisted in segments, others are only kept in WAL
+        collection.flush_all().unwrap();
+        collection.update(CollectionUpdateOperations::PayloadOperation(PayloadOps::SetPayload {
+            payload: vec![
+                ("color".to_string(), PayloadInterface::Keyword(PayloadVariant::Value("red".to_string())))
+            ].into_iter().collect(),
+            points: vec![1.into(), 2.into()],
+        }), true).unwrap();
+        collection.update(CollectionUpdateOperations::PointOperation(
+            PointOperations::DeletePoints { ids: vec![3.into()] }
+        ), true).unwrap();
+
+        collection.create_snapshot(&snapshot_path, &StopCondition::default()).unwrap();
+        assert!(snapshot_path.exists());
+        assert!(!snapshot_path.with_extension("tmp").exists());
+
+        // Changes after the snapshot are not restored
+        collection.update(upsert_points(vec![10, 11]), true).unwrap();
+    }
+
+    let restored_dir = TempDir::new("restored").unwrap();
+    restore_snapshot(&snapshot_path, restored_dir.path()).unwrap();
+
+    let (_rt, collection) = load_collection_fixture(restored_dir.path());
+    assert_eq!(collection.config.read().replication_factor, 2);
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 9);
+
+    let records = collection.retrieve(&vec![1.into(), 3.into()], &WithPayload::from(true), false, ReadConsistency::Any).unwrap();
+    assert_eq!(records.len(), 1);
+    match records[0].payload.as_ref().unwrap().get("color") {
+        Some(PayloadType::Keyword(colors)) => assert_eq!(colors, &vec!["red".to_string()]),
+        _ => panic!("Payload is not restored"),
+    }
+
+    // Restored replicas are in sync and receive further operations
+    collection.update(upsert_points(vec![12]), true).unwrap();
+    for shard in collection.shards.shards() {
+        assert!(shard.replica_states().values().all(|state| *state == ReplicaState::Active));
+        let expected_count = shard.count(count_all(), &StopCondition::default()).unwrap();
+        for replica in shard.replicas().values() {
+            assert_eq!(replica.count(count_all(), &StopCondition::default()).unwrap(), expected_count);
+        }
+    }
+    assert_eq!(collection.count(count_all(), ReadConsistency::Any, &StopCondition::default()).unwrap().count, 10);
+}
+
+#[test]
+fn test_restore_invalid_snapshot() {
+    let snapshots_dir = TempDir::new("snapshots").unwrap();
+    let snapshot_path = snapshots_dir.path().join("invalid.snapshot");
+    std::fs::write(&snapshot_path, b"not an archive").unwrap();
+
+    let restored_dir = TempDir::new("restored").unwrap();
+    assert!(restore_snapshot(&snapshot_path, restored_dir.path()).is_err());
+}
+
+#[test]
+fn test_restore_incomplete_snapshot() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let snapshots_dir = TempDir::new("snapshots").unwrap();
+    let snapshot_path = snapshots_dir.path().join("test.snapshot");
+
+    let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 2, 1);
+    collection.update(upsert_points((0..10).collect()), true).unwrap();
+    collection.create_snapshot(&snapshot_path, &StopCondition::default()).unwrap();
+
+    // WAL of one of the shards is lost, so operations after its saved segments could not be restored
+    let unpacked_dir = unpack_snapshot(&snapshot_path);
+    remove_dir_all(replica_path(&shard_path(unpacked_dir.path(), 1), 0).join("wal")).unwrap();
+    let incomplete_path = snapshots_dir.path().join("incomplete.snapshot");
+    pack_snapshot(unpacked_dir.path(), &incomplete_path);
+
+    let restored_dir = TempDir::new("restored").unwrap();
+    assert!(restore_snapshot(&incomplete_path, restored_dir.path()).is_err());
+
+    let restored_dir = TempDir::new("restored").unwrap();
+    restore_snapshot(&snapshot_path, restored_dir.path()).unwrap();
+    assert!(!restored_dir.path().join(SNAPSHOT_MANIFEST_FILE).exists());
+}
+
+#[test]
+fn test_restore_snapshot_versions() {
+    let collection_dir = TempDir::new("collection").unwrap();
+    let snapshots_dir = TempDir::new("snapshots").unwrap();
+    let snapshot_path = snapshots_dir.path().join("test.snapshot");
+
+    let (_rt, collection) = replicated_collection_fixture(collection_dir.path(), 2, 1);
+    collection.update(upsert_points((0..10).collect()), true).unwrap();
+    collection.create_snapshot(&snapshot_path, &StopCondition::default()).unwrap();
+
+    let unpacked_dir = unpack_snapshot(&snapshot_path);
+    let manifest = SnapshotManifest::load(unpacked_dir.path()).unwrap().unwrap();
+    assert_eq!(manifest.format_version, CURRENT_SNAPSHOT_FORMAT_VERSION);
+    assert!(manifest.files.iter().any(|file| file.path == "config.json"));
+
+    let restore_modified = |modify: &dyn Fn(&Path)| {
+        let unpacked_dir = unpack_snapshot(&snapshot_path);
+        modify(unpacked_dir.path());
+        let modified_path = snapshots_dir.path().join("modified.snapshot");
+        pack_snapshot(unpacked_dir.path(), &modified_path);
+        let restored_dir = TempDir::new("restored").unwrap();
+        restore_snapshot(&modified_path, restored_dir.path())
+    };
+
+    // Manifest of the first version without format versions and checksums is upgraded
+    restore_modified(&|path| {
+        let mut manifest = SnapshotManifest::load(path).unwrap().unwrap();
+        manifest.format_version = 0;
+        manifest.files.clear();
+        manifest.save(path).unwrap();
+    }).unwrap();
+
+    // Snapshot of a newer format is rejected
+    let err = restore_modified(&|path| {
+        let mut manifest = SnapshotManifest::load(path).unwrap().unwrap();
+        manifest.format_version = CURRENT_SNAPSHOT_FORMAT_VERSION + 1;
+        manifest.save(path).unwrap();
+    }).unwrap_err();
+    assert!(err.to_string().contains("newer than supported"));
+
+    // Changed files are detected by checksums
+    assert!(restore_modified(&|path| {
+        let config_path = path.join("config.json");
+        let mut config = std::fs::read(&config_path).unwrap();
+        config.push(b' ');
+        std::fs::write(&config_path, config).unwrap();
+    }).is_err());
+}
@@ -0,0 +1,232 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use collection::collection_builder::optimizers_builder::OptimizersConfig;
+use collection::operations::CollectionUpdateOperations;
+use collection::operations::point_ops::{PointInsertOperations, PointOperations};
+use collection::operations::types::{CountRequest, ReadConsistency};
+use segment::common::stop_condition::StopCondition;
+use segment::types::WithPayload;
+use storage::content_manager::storage_ops::StorageOperations;
+use storage::content_manager::toc::TableOfContent;
+use storage::types::{PerformanceConfig, StorageConfig, WalConfig};
+
+use crate::errors::{Error, Result};
+use crate::types::{payload_from_internal, CollectionParams, Distance, Filter, Point, PointId, ScoredPoint, SearchRequest};
+
+/// Parameters of the storage, which are not persisted and could be changed on each opening
+#[derive(Debug, Clone)]
+pub struct DbOptions {
+    search_threads: usize,
+    optimization_threads: usize,
+    flush_interval_sec: u64,
+    wal_capacity_mb: usize,
+}
+
+impl Default for DbOptions {
+    fn default() -> Self {
+        DbOptions {
+            search_threads: 0,
+            optimization_threads: 0,
+            flush_interval_sec: 10,
+            wal_capacity_mb: 32,
+        }
+    }
+}
+
+impl DbOptions {
+    /// Number of threads, which perform searches. If 0 - auto selection
+    pub fn search_threads(mut self, threads: usize) -> Self {
+        self.search_threads = threads;
+        self
+    }
+
+    /// Number of threads, which optimize segments and build indexes in background. If 0 - auto selection
+    pub fn optimization_threads(mut self, threads: usize) -> Self {
+        self.optimization_threads = threads;
+        self
+    }
+
+    /// Changes are persisted at least once in the interval. Until then they are recovered from the write-ahead log
+    pub fn flush_interval_sec(mut self, interval: u64) -> Self {
+        self.flush_interval_sec = interval;
+        self
+    }
+
+    /// Size of a single segment of the write-ahead log
+    pub fn wal_capacity_mb(mut self, capacity: usize) -> Self {
+        self.wal_capacity_mb = capacity;
+        self
+    }
+
+    fn storage_config(&self, path: &Path) -> Result<StorageConfig> {
+        let storage_path = path.to_str()
+            .ok_or_else(|| Error::InvalidArgument(format!("Path {} is not valid UTF-8", path.display())))?;
+        if self.flush_interval_sec == 0 || self.wal_capacity_mb == 0 {
+            return Err(Error::InvalidArgument("Flush interval and WAL capacity should be positive".to_string()));
+        }
+        Ok(StorageConfig {
+            storage_path: storage_path.to_string(),
+            snapshots_path: path.join("snapshots").to_string_lossy().to_string(),
+            import_path: None,
+            cold_storage: None,
+            record_path: None,
+            optimizers: OptimizersConfig {
+                deleted_threshold: 0.2,
+                vacuum_min_vector_number: 1000,
+                max_segment_number: 5,
+                memmap_threshold: 50_000,
+                indexing_threshold: 20_000,
+                payload_indexing_threshold: 10_000,
+                flush_interval_sec: self.flush_interval_sec,
+                max_segment_size: None,
+                max_optimization_threads: 1,
+                defragmentation_key: None,
+            },
+            wal: WalConfig {
+                wal_capacity_mb: self.wal_capacity_mb,
+                wal_segments_ahead: 0,
+            },
+            performance: PerformanceConfig {
+                max_search_threads: self.search_threads,
+                max_optimization_threads: self.optimization_threads,
+                numa_policy: Default::default(),
+                warm_up_on_load: false,
+                max_blocking_threads: 0,
+                max_pending_updates: 0,
+                update_overload_policy: Default::default(),
+                search_cache_size: 0,
+            },
+        })
+    }
+}
+
+/// Storage of collections in a directory, embedded into the application process.
+/// Directory has the same layout as the storage of the service, so it could be served by the service later
+pub struct Db {
+    toc: TableOfContent,
+}
+
+impl Db {
+    /// Open the storage in the directory, which is created if it does not exist, and load all its collections
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, DbOptions::default())
+    }
+
+    pub fn open_with(path: &Path, options: DbOptions) -> Result<Self> {
+        let storage_config = options.storage_config(path)?;
+        std::fs::create_dir_all(path)
+            .map_err(|err| Error::Storage(format!("Can't create directory {}: {}", path.display(), err)))?;
+        Ok(Db { toc: TableOfContent::new(&storage_config) })
+    }
+
+    /// Create a new empty collection. Fails, if the collection already exists
+    pub fn create_collection(&self, name: &str, params: &CollectionParams) -> Result<Collection> {
+        self.toc.perform_collection_operation(StorageOperations::CreateCollection {
+            name: name.to_string(),
+            vector_size: params.dim,
+            distance: params.distance.into(),
+            index: None,
+            text_analyzers: None,
+            flush_policy: None,
+            shard_number: None,
+            replication_factor: None,
+            write_consistency_factor: None,
+            update_workers: None,
+            shard_key: None,
+            strict_mode: None,
+        })?;
+        self.collection(name)
+    }
+
+    /// Existing collection by its name or alias
+    pub fn collection(&self, name: &str) -> Result<Collection> {
+        Ok(Collection { collection: self.toc.get_collection(name)? })
+    }
+
+    /// Delete the collection with all its data. Returns false, if there is no such collection
+    pub fn delete_collection(&self, name: &str) -> Result<bool> {
+        Ok(self.toc.perform_collection_operation(StorageOperations::DeleteCollection(name.to_string()))?)
+    }
+
+    pub fn collection_names(&self) -> Vec<String> {
+        self.toc.all_collections()
+    }
+}
+
+/// Handle of a collection. It stays valid while the collection exists, cloning is cheap
+#[derive(Clone)]
+pub struct Collection {
+    collection: Arc<collection::collection::Collection>,
+}
+
+impl Collection {
+    pub fn dim(&self) -> Result<usize> {
+        Ok(self.collection.info()?.config.params.vector_size)
+    }
+
+    pub fn distance(&self) -> Result<Distance> {
+        Ok(self.collection.info()?.config.params.distance.into())
+    }
+
+    /// Insert or replace points. Changes are visible to searches once the call returns
+    pub fn upsert(&self, points: &[Point]) -> Result<()> {
+        let points = points.iter().map(Point::to_internal).collect::<Result<Vec<_>>>()?;
+        self.update(PointOperations::UpsertPoints(PointInsertOperations::PointsList(points)))
+    }
+
+    /// Delete points, missing ids are ignored
+    pub fn delete(&self, ids: &[PointId]) -> Result<()> {
+        let ids = ids.iter().map(PointId::to_internal).collect::<Result<Vec<_>>>()?;
+        self.update(PointOperations::DeletePoints { ids })
+    }
+
+    /// Point with its payload, or `None` if there is no such point
+    pub fn get(&self, id: &PointId) -> Result<Option<Point>> {
+        let records = self.collection.retrieve(
+            &vec![id.to_internal()?],
+            &WithPayload::from(true),
+            true,
+            ReadConsistency::Any,
+        )?;
+        Ok(records.into_iter().next().map(|record| Point {
+            id: PointId::from_internal(record.id),
+            vector: record.vector.unwrap_or_default(),
+            payload: record.payload.map(payload_from_internal).unwrap_or_default(),
+        }))
+    }
+
+    /// Closest points to the query vector, best first
+    pub fn search(&self, request: &SearchRequest) -> Result<Vec<ScoredPoint>> {
+        let search = collection::operations::types::SearchRequest {
+            vector: request.vector.clone(),
+            filter: request.filter.as_ref().map(|filter| filter.internal().clone()),
+            params: None,
+            with_payload: Some(segment::types::WithPayloadInterface::Bool(request.with_payload)),
+            with_vector: request.with_vector,
+            top: request.top,
+            offset: request.offset,
+        };
+        let points = self.collection.search(Arc::new(search), ReadConsistency::Any, &StopCondition::default())?;
+        Ok(points.into_iter().map(ScoredPoint::from_internal).collect())
+    }
+
+    /// Number of points, which satisfy the filter, or of all points
+    pub fn count(&self, filter: Option<&Filter>) -> Result<usize> {
+        let request = CountRequest {
+            filter: filter.map(|filter| filter.internal().clone()),
+            exact: true,
+        };
+        Ok(self.collection.count(Arc::new(request), ReadConsistency::Any, &StopCondition::default())?.count)
+    }
+
+    /// Persist all changes, so they are not recovered from the write-ahead log on the next opening
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.collection.flush_all()?)
+    }
+
+    fn update(&self, operation: PointOperations) -> Result<()> {
+        self.collection.update(CollectionUpdateOperations::PointOperation(operation), true)?;
+        Ok(())
+    }
+}
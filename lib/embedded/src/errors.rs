@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+use collection::collection::CollectionError;
+use storage::content_manager::errors::StorageError;
+
+/// Errors of the embedded storage. New variants might be added in minor releases
+#[derive(Error, Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Request is not valid, e.g. dimension of the vector does not match the collection
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    /// Request could not be processed right now and might be retried later
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+    /// Failure of the storage itself, e.g. of the file system
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<StorageError> for Error {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::BadInput { description } => Error::InvalidArgument(description),
+            StorageError::BadRequest { description } => Error::InvalidArgument(description),
+            StorageError::NotFound { description } => Error::NotFound(description),
+            StorageError::Cancelled { description } => Error::Unavailable(description),
+            StorageError::Overloaded { description } => Error::Unavailable(description),
+            StorageError::ServiceError { description } => Error::Storage(description),
+        }
+    }
+}
+
+impl From<CollectionError> for Error {
+    fn from(err: CollectionError) -> Self {
+        StorageError::from(err).into()
+    }
+}
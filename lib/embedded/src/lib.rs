@@ -0,0 +1,9 @@
+mod db;
+mod errors;
+mod types;
+
+pub use crate::db::{Collection, Db, DbOptions};
+pub use crate::errors::{Error, Result};
+pub use crate::types::{
+    CollectionParams, Distance, Filter, GeoPoint, Payload, PayloadValue, Point, PointId, ScoredPoint, SearchRequest,
+};
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use collection::operations::payload_ops::{PayloadInterface, PayloadVariant};
+use collection::operations::point_ops::PointStruct;
+use segment::types::{Condition, FieldCondition, Match, PayloadKeyType, PayloadType, PointIdType, TheMap};
+
+use crate::errors::{Error, Result};
+
+/// Similarity of vectors, which defines the order of search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Distance {
+    Cosine,
+    Euclid,
+    Dot,
+}
+
+impl From<Distance> for segment::types::Distance {
+    fn from(distance: Distance) -> Self {
+        match distance {
+            Distance::Cosine => segment::types::Distance::Cosine,
+            Distance::Euclid => segment::types::Distance::Euclid,
+            Distance::Dot => segment::types::Distance::Dot,
+        }
+    }
+}
+
+impl From<segment::types::Distance> for Distance {
+    fn from(distance: segment::types::Distance) -> Self {
+        match distance {
+            segment::types::Distance::Cosine => Distance::Cosine,
+            segment::types::Distance::Euclid => Distance::Euclid,
+            segment::types::Distance::Dot => Distance::Dot,
+        }
+    }
+}
+
+/// Point id is either an integer or a UUID in its string representation
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PointId {
+    Num(u64),
+    Uuid(String),
+}
+
+impl From<u64> for PointId {
+    fn from(id: u64) -> Self {
+        PointId::Num(id)
+    }
+}
+
+impl From<&str> for PointId {
+    fn from(id: &str) -> Self {
+        PointId::Uuid(id.to_string())
+    }
+}
+
+impl fmt::Display for PointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointId::Num(id) => id.fmt(f),
+            PointId::Uuid(uuid) => uuid.fmt(f),
+        }
+    }
+}
+
+impl PointId {
+    pub(crate) fn to_internal(&self) -> Result<PointIdType> {
+        match self {
+            PointId::Num(id) => Ok(PointIdType::NumId(*id)),
+            PointId::Uuid(uuid) => match uuid.parse() {
+                Ok(id @ PointIdType::Uuid(_)) => Ok(id),
+                _ => Err(Error::InvalidArgument(format!("Invalid UUID of point {}", uuid))),
+            },
+        }
+    }
+
+    pub(crate) fn from_internal(id: PointIdType) -> Self {
+        match id {
+            PointIdType::NumId(id) => PointId::Num(id),
+            PointIdType::Uuid(uuid) => PointId::Uuid(uuid.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Values of a payload field. Each field holds a list of values of the same type
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PayloadValue {
+    Keyword(Vec<String>),
+    Integer(Vec<i64>),
+    Float(Vec<f64>),
+    Geo(Vec<GeoPoint>),
+}
+
+impl From<&str> for PayloadValue {
+    fn from(value: &str) -> Self {
+        PayloadValue::Keyword(vec![value.to_string()])
+    }
+}
+
+impl From<i64> for PayloadValue {
+    fn from(value: i64) -> Self {
+        PayloadValue::Integer(vec![value])
+    }
+}
+
+impl From<f64> for PayloadValue {
+    fn from(value: f64) -> Self {
+        PayloadValue::Float(vec![value])
+    }
+}
+
+impl From<GeoPoint> for PayloadValue {
+    fn from(value: GeoPoint) -> Self {
+        PayloadValue::Geo(vec![value])
+    }
+}
+
+impl PayloadValue {
+    fn to_internal(&self) -> PayloadInterface {
+        match self {
+            PayloadValue::Keyword(values) => PayloadInterface::Keyword(PayloadVariant::List(values.clone())),
+            PayloadValue::Integer(values) => PayloadInterface::Integer(PayloadVariant::List(values.clone())),
+            PayloadValue::Float(values) => PayloadInterface::Float(PayloadVariant::List(values.clone())),
+            PayloadValue::Geo(values) => PayloadInterface::Geo(PayloadVariant::List(
+                values.iter().map(|point| segment::types::GeoPoint { lat: point.lat, lon: point.lon }).collect()
+            )),
+        }
+    }
+
+    fn from_internal(value: PayloadType) -> Self {
+        match value {
+            PayloadType::Keyword(values) => PayloadValue::Keyword(values),
+            PayloadType::Integer(values) => PayloadValue::Integer(values),
+            PayloadType::Float(values) => PayloadValue::Float(values),
+            PayloadType::Geo(values) => PayloadValue::Geo(
+                values.into_iter().map(|point| GeoPoint { lat: point.lat, lon: point.lon }).collect()
+            ),
+        }
+    }
+}
+
+pub type Payload = BTreeMap<String, PayloadValue>;
+
+pub(crate) fn payload_from_internal(payload: TheMap<PayloadKeyType, PayloadType>) -> Payload {
+    payload.into_iter()
+        .map(|(key, value)| (key, PayloadValue::from_internal(value)))
+        .collect()
+}
+
+/// Point of a collection: vector with an optional payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub id: PointId,
+    pub vector: Vec<f32>,
+    pub payload: Payload,
+}
+
+impl Point {
+    pub fn new(id: impl Into<PointId>, vector: Vec<f32>) -> Self {
+        Point { id: id.into(), vector, payload: Payload::new() }
+    }
+
+    pub fn with_payload(mut self, key: &str, value: impl Into<PayloadValue>) -> Self {
+        self.payload.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub(crate) fn to_internal(&self) -> Result<PointStruct> {
+        let payload: HashMap<_, _> = self.payload.iter()
+            .map(|(key, value)| (key.clone(), value.to_internal()))
+            .collect();
+        Ok(PointStruct {
+            id: self.id.to_internal()?,
+            vector: self.vector.clone(),
+            payload: if payload.is_empty() { None } else { Some(payload) },
+        })
+    }
+}
+
+/// Conditions on payload and ids of points.
+/// Besides the simple conditions of this type, any filter of the REST API could be used with `Filter::from_json`
+#[derive(Debug, Clone)]
+pub struct Filter {
+    filter: segment::types::Filter,
+}
+
+impl Filter {
+    /// Filter in the JSON format of the REST API, e.g. `{"must": [{"key": "city", "match": {"keyword": "Berlin"}}]}`
+    pub fn from_json(json: &str) -> Result<Self> {
+        let filter = serde_json::from_str(json)
+            .map_err(|err| Error::InvalidArgument(format!("Invalid filter: {}", err)))?;
+        Ok(Filter { filter })
+    }
+
+    /// Points, which have the `value` among keywords of the `key` field
+    pub fn match_keyword(key: &str, value: &str) -> Self {
+        Self::must_match(key, Match { keyword: Some(value.to_string()), integer: None, text: None })
+    }
+
+    /// Points, which have the `value` among integers of the `key` field
+    pub fn match_integer(key: &str, value: i64) -> Self {
+        Self::must_match(key, Match { keyword: None, integer: Some(value), text: None })
+    }
+
+    /// Points, which satisfy both filters
+    pub fn and(self, other: Filter) -> Self {
+        Filter {
+            filter: segment::types::Filter {
+                should: None,
+                must: Some(vec![Condition::Filter(self.filter), Condition::Filter(other.filter)]),
+                min_should: None,
+                must_not: None,
+            }
+        }
+    }
+
+    fn must_match(key: &str, r#match: Match) -> Self {
+        Filter {
+            filter: segment::types::Filter::new_must(Condition::Field(FieldCondition {
+                key: key.to_string(),
+                r#match: Some(r#match),
+                range: None,
+                geo_bounding_box: None,
+                geo_radius: None,
+            }))
+        }
+    }
+
+    pub(crate) fn internal(&self) -> &segment::types::Filter {
+        &self.filter
+    }
+}
+
+/// Search of points, closest to the query vector
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub(crate) vector: Vec<f32>,
+    pub(crate) top: usize,
+    pub(crate) offset: usize,
+    pub(crate) filter: Option<Filter>,
+    pub(crate) with_payload: bool,
+    pub(crate) with_vector: bool,
+}
+
+impl SearchRequest {
+    /// Request of `top` closest points without payload and vectors
+    pub fn new(vector: Vec<f32>, top: usize) -> Self {
+        SearchRequest {
+            vector,
+            top,
+            offset: 0,
+            filter: None,
+            with_payload: false,
+            with_vector: false,
+        }
+    }
+
+    /// Number of best results to skip, e.g. to request next pages
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn with_payload(mut self, with_payload: bool) -> Self {
+        self.with_payload = with_payload;
+        self
+    }
+
+    pub fn with_vector(mut self, with_vector: bool) -> Self {
+        self.with_vector = with_vector;
+        self
+    }
+}
+
+/// Found point. Payload and vector are present only if requested
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ScoredPoint {
+    pub id: PointId,
+    pub score: f32,
+    pub payload: Option<Payload>,
+    pub vector: Option<Vec<f32>>,
+}
+
+impl ScoredPoint {
+    pub(crate) fn from_internal(point: segment::types::ScoredPoint) -> Self {
+        ScoredPoint {
+            id: PointId::from_internal(point.id),
+            score: point.score,
+            payload: point.payload.map(payload_from_internal),
+            vector: point.vector,
+        }
+    }
+}
+
+/// Parameters of a new collection
+#[derive(Debug, Clone)]
+pub struct CollectionParams {
+    pub(crate) dim: usize,
+    pub(crate) distance: Distance,
+}
+
+impl CollectionParams {
+    pub fn new(dim: usize, distance: Distance) -> Self {
+        CollectionParams { dim, distance }
+    }
+}
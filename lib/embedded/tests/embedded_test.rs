@@ -0,0 +1,60 @@
+use tempdir::TempDir;
+
+use qdrant_embedded::{CollectionParams, Db, DbOptions, Distance, Error, Filter, PayloadValue, Point, PointId, SearchRequest};
+
+fn options() -> DbOptions {
+    DbOptions::default().search_threads(1).optimization_threads(1).wal_capacity_mb(1)
+}
+
+#[test]
+fn test_embedded_db() {
+    let dir = TempDir::new("embedded").unwrap();
+    {
+        let db = Db::open_with(dir.path(), options()).unwrap();
+        let collection = db.create_collection("cities", &CollectionParams::new(2, Distance::Dot)).unwrap();
+        assert!(matches!(
+            db.create_collection("cities", &CollectionParams::new(2, Distance::Dot)),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        collection.upsert(&[
+            Point::new(1, vec![1.0, 0.0]).with_payload("country", "de").with_payload("population", 3_600_000i64),
+            Point::new(2, vec![0.9, 0.1]).with_payload("country", "fr"),
+            Point::new("936da01f-9abd-4d9d-80c7-02af85c822a8", vec![0.0, 1.0]).with_payload("country", "de"),
+        ]).unwrap();
+        assert!(matches!(collection.upsert(&[Point::new(4, vec![1.0])]), Err(Error::InvalidArgument(_))));
+        assert!(matches!(collection.upsert(&[Point::new("berlin", vec![1.0, 1.0])]), Err(Error::InvalidArgument(_))));
+
+        let found = collection.search(&SearchRequest::new(vec![1.0, 0.0], 2)).unwrap();
+        assert_eq!(found.iter().map(|point| point.id.clone()).collect::<Vec<_>>(), vec![PointId::Num(1), PointId::Num(2)]);
+        assert!(found[0].payload.is_none());
+
+        let request = SearchRequest::new(vec![1.0, 0.0], 10)
+            .filter(Filter::match_keyword("country", "de"))
+            .with_payload(true);
+        let found = collection.search(&request).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].payload.as_ref().unwrap()["population"], PayloadValue::Integer(vec![3_600_000]));
+        assert_eq!(found[1].id, PointId::from("936da01f-9abd-4d9d-80c7-02af85c822a8"));
+
+        let filter = Filter::from_json(r#"{"must": [{"key": "country", "match": {"keyword": "fr"}}]}"#).unwrap();
+        assert_eq!(collection.count(Some(&filter)).unwrap(), 1);
+        assert!(Filter::from_json(r#"{"must": "fr"}"#).is_err());
+
+        collection.delete(&[PointId::Num(2)]).unwrap();
+        assert_eq!(collection.get(&PointId::Num(2)).unwrap(), None);
+        collection.flush().unwrap();
+    }
+
+    let db = Db::open_with(dir.path(), options()).unwrap();
+    assert_eq!(db.collection_names(), vec!["cities".to_string()]);
+    let collection = db.collection("cities").unwrap();
+    assert_eq!(collection.dim().unwrap(), 2);
+    assert_eq!(collection.count(None).unwrap(), 2);
+    let point = collection.get(&PointId::Num(1)).unwrap().unwrap();
+    assert_eq!(point.vector, vec![1.0, 0.0]);
+    assert_eq!(point.payload["country"], PayloadValue::from("de"));
+
+    assert!(db.delete_collection("cities").unwrap());
+    assert!(matches!(db.collection("cities"), Err(Error::NotFound(_))));
+}
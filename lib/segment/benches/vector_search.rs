@@ -4,6 +4,7 @@ use ndarray::{Array, Array1, Array2, ArrayBase, ShapeBuilder, Axis};
 use tempdir::TempDir;
 
 use segment::spaces::tools::{peek_top_scores, peek_top_scores_iterable};
+use segment::common::stop_condition::StopCondition;
 use segment::types::Distance;
 use segment::vector_storage::simple_vector_storage::SimpleVectorStorage;
 use segment::vector_storage::vector_storage::{ScoredPointOffset, VectorStorage};
@@ -39,7 +40,7 @@ fn benchmark_naive(c: &mut Criterion) {
     c.bench_function("storage vector search",
                      |b| b.iter(|| {
                          let vector = random_vector(DIM);
-                         storage.score_all(&vector, 10, &dist)
+                         storage.score_all(&vector, 10, &dist, &StopCondition::default())
                      }));
 }
 
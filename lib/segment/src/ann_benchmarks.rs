@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::create_dir_all;
+use std::path::Path;
+
+use hdf5::types::VarLenUnicode;
+use ndarray::Array2;
+
+use crate::benchmark::{BenchmarkReport, evaluate_search};
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::segment::Segment;
+use crate::segment_constructor::segment_builder::SegmentBuilder;
+use crate::types::{Distance, PointIdType, SegmentConfig, TheMap, VectorElementType};
+
+/// Dataset in the format of ann-benchmarks: https://github.com/erikbern/ann-benchmarks
+pub struct AnnDataset {
+    pub distance: Distance,
+    /// Vectors, which are stored in the segment. Id of each point is the number of its row
+    pub train: Array2<VectorElementType>,
+    /// Query vectors
+    pub test: Array2<VectorElementType>,
+    /// Ids of the closest train vectors for each query, from the closest to the farthest
+    pub neighbors: Array2<i64>,
+}
+
+fn hdf5_error(path: &Path, err: hdf5::Error) -> OperationError {
+    OperationError::WrongInput { description: format!("Can't read dataset {:?}, error: {}", path, err) }
+}
+
+impl AnnDataset {
+    pub fn load(path: &Path) -> OperationResult<Self> {
+        let file = hdf5::File::open(path).map_err(|err| hdf5_error(path, err))?;
+        let distance_name = file.attr("distance")
+            .and_then(|attr| attr.read_scalar::<VarLenUnicode>())
+            .map_err(|err| hdf5_error(path, err))?;
+        let distance = match distance_name.as_str() {
+            "angular" => Distance::Cosine,
+            "dot" | "ip" => Distance::Dot,
+            "euclidean" => return Err(OperationError::WrongInput {
+                description: format!(
+                    "Dataset {:?} is skipped: euclidean datasets are not supported as search with Euclid distance is not implemented",
+                    path,
+                )
+            }),
+            name => return Err(OperationError::WrongInput {
+                description: format!("Unsupported distance {} of dataset {:?}", name, path)
+            }),
+        };
+        let read = |name: &str| file.dataset(name).map_err(|err| hdf5_error(path, err));
+        Ok(AnnDataset {
+            distance,
+            train: read("train")?.read_2d().map_err(|err| hdf5_error(path, err))?,
+            test: read("test")?.read_2d().map_err(|err| hdf5_error(path, err))?,
+            neighbors: read("neighbors")?.read_2d().map_err(|err| hdf5_error(path, err))?,
+        })
+    }
+
+    pub fn dim(&self) -> usize {
+        self.train.ncols()
+    }
+}
+
+/// Build a segment with all train vectors of the dataset in `path`.
+/// Dimension and distance of the `config` are replaced with ones of the dataset
+pub fn build_benchmark_segment(dataset: &AnnDataset, config: &SegmentConfig, path: &Path) -> OperationResult<Segment> {
+    let segments_path = path.join("segments");
+    let temp_path = path.join("temp");
+    create_dir_all(&segments_path)?;
+    create_dir_all(&temp_path)?;
+
+    let config = SegmentConfig {
+        vector_size: dataset.dim(),
+        distance: dataset.distance,
+        ..config.clone()
+    };
+    let mut builder = SegmentBuilder::new(&segments_path, &temp_path, &config)?;
+    builder.add_points(0, dataset.train.outer_iter()
+        .enumerate()
+        .map(|(idx, vector)| (PointIdType::NumId(idx as u64), vector.to_vec(), TheMap::new())))?;
+    builder.try_into()
+}
+
+/// Search each query of the dataset sequentially and compare results with the true neighbors
+pub fn evaluate(
+    segment: &Segment,
+    dataset: &AnnDataset,
+    top: usize,
+) -> OperationResult<BenchmarkReport> {
+    if top > dataset.neighbors.ncols() {
+        return Err(OperationError::WrongInput {
+            description: format!("Dataset contains only {} neighbors of each query", dataset.neighbors.ncols())
+        });
+    }
+
+    let vectors: Vec<Vec<VectorElementType>> = dataset.test.outer_iter().map(|query| query.to_vec()).collect();
+    let expected: Vec<HashSet<PointIdType>> = dataset.neighbors.outer_iter()
+        .map(|neighbors| neighbors.iter()
+            .take(top)
+            .map(|idx| PointIdType::NumId(*idx as u64))
+            .collect())
+        .collect();
+    evaluate_search(segment, vectors.iter().zip(&expected), top)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::types::{Indexes, StorageType};
+
+    #[test]
+    fn test_evaluate_plain_search() {
+        let dataset = AnnDataset {
+            distance: Distance::Dot,
+            train: arr2(&[[1.0, 0.0], [0.0, 1.0], [0.7, 0.7]]),
+            test: arr2(&[[1.0, 0.1], [0.1, 1.0]]),
+            neighbors: arr2(&[[0, 2], [1, 2]]),
+        };
+        let config = SegmentConfig {
+            vector_size: 0,
+            index: Indexes::Plain {},
+            payload_index: None,
+            distance: Distance::Cosine,
+            storage_type: StorageType::InMemory,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+        };
+        let dir = TempDir::new("ann_benchmarks").unwrap();
+        let segment = build_benchmark_segment(&dataset, &config, dir.path()).unwrap();
+
+        let report = evaluate(&segment, &dataset, 2).unwrap();
+        assert_eq!(report.recall, 1.0);
+        assert!(evaluate(&segment, &dataset, 3).is_err());
+    }
+}
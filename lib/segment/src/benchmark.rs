@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::stop_condition::StopCondition;
+use crate::entry::entry_point::{OperationError, OperationResult, SegmentEntry};
+use crate::id_mapper::id_mapper::IdMapper;
+use crate::segment::Segment;
+use crate::types::{PointIdType, VectorElementType, WithPayload};
+use crate::vector_storage::vector_storage::VectorStorage;
+
+/// Query of the benchmark workload
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct BenchmarkQuery {
+    pub vector: Vec<VectorElementType>,
+    /// Ids of the true closest points, from the closest to the farthest.
+    /// If not specified, they are found by the exact search over all vectors of the segment
+    #[serde(default)]
+    pub neighbors: Option<Vec<PointIdType>>,
+}
+
+/// Read queries from JSON lines of `BenchmarkQuery`
+pub fn read_queries(path: &Path) -> OperationResult<Vec<BenchmarkQuery>> {
+    let read_error = |err: std::io::Error| OperationError::WrongInput {
+        description: format!("Can't read queries {:?}, error: {}", path, err)
+    };
+    let reader = BufReader::new(File::open(path).map_err(read_error)?);
+    let mut queries = vec![];
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(read_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        queries.push(serde_json::from_str(&line).map_err(|err| OperationError::WrongInput {
+            description: format!("Can't parse line {} of {:?}, error: {}", line_idx + 1, path, err)
+        })?);
+    }
+    Ok(queries)
+}
+
+/// Use vectors of `count` points of the segment as queries. Points are evenly spaced in the order of ids,
+/// so the same queries are selected from the same segment
+pub fn sample_queries(segment: &Segment, count: usize) -> OperationResult<Vec<BenchmarkQuery>> {
+    let points_count = segment.vectors_count();
+    if count == 0 || points_count == 0 {
+        return Ok(vec![]);
+    }
+    let step = (points_count / count).max(1);
+    segment.iter_points()
+        .step_by(step)
+        .take(count)
+        .map(|id| Ok(BenchmarkQuery { vector: segment.vector(id)?, neighbors: None }))
+        .collect()
+}
+
+/// Ids of the `top` closest points by scoring all vectors of the segment, ignoring its index
+fn exact_neighbors(segment: &Segment, query: &BenchmarkQuery, top: usize) -> Vec<PointIdType> {
+    let vector_storage = segment.vector_storage.borrow();
+    let id_mapper = segment.id_mapper.borrow();
+    vector_storage.score_all(&query.vector, top, &segment.segment_config.distance, &StopCondition::default())
+        .iter()
+        .filter_map(|scored| id_mapper.external_id(scored.idx))
+        .collect()
+}
+
+/// Search quality and latency of the segment
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct BenchmarkReport {
+    pub top: usize,
+    pub queries: usize,
+    /// Share of the true `top` neighbors, which are found, averaged over all queries
+    pub recall: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    /// Queries per second of the sequential search
+    pub qps: f64,
+}
+
+impl BenchmarkReport {
+    pub const CSV_HEADER: &'static str = "top,queries,recall,p50_latency_ms,p95_latency_ms,p99_latency_ms,qps";
+
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{:.4},{:.3},{:.3},{:.3},{:.1}",
+            self.top,
+            self.queries,
+            self.recall,
+            self.p50_latency_ms,
+            self.p95_latency_ms,
+            self.p99_latency_ms,
+            self.qps,
+        )
+    }
+}
+
+fn percentile_ms(sorted_latencies: &[Duration], percentile: usize) -> f64 {
+    let idx = (sorted_latencies.len() * percentile / 100).min(sorted_latencies.len().saturating_sub(1));
+    sorted_latencies.get(idx).cloned().unwrap_or_default().as_secs_f64() * 1000.0
+}
+
+/// Search all queries sequentially and compare results with the true neighbors.
+/// Only plain search is measured, as HNSW index is not implemented yet
+pub fn run_benchmark(
+    segment: &Segment,
+    queries: &[BenchmarkQuery],
+    top: usize,
+) -> OperationResult<BenchmarkReport> {
+    if queries.is_empty() || top == 0 {
+        return Err(OperationError::WrongInput {
+            description: "Benchmark requires at least one query and positive top".to_string()
+        });
+    }
+    let expected: Vec<HashSet<PointIdType>> = queries.iter()
+        .map(|query| match &query.neighbors {
+            Some(neighbors) => neighbors.iter().take(top).cloned().collect(),
+            None => exact_neighbors(segment, query, top).into_iter().collect(),
+        })
+        .collect();
+
+    evaluate_search(segment, queries.iter().map(|query| &query.vector).zip(&expected), top)
+}
+
+/// Search each query sequentially with default parameters and compare results with its true neighbors.
+/// Recall of a query is the share of its true neighbors, which are found. Query without true neighbors has recall 1
+pub fn evaluate_search<'a>(
+    segment: &Segment,
+    queries: impl IntoIterator<Item=(&'a Vec<VectorElementType>, &'a HashSet<PointIdType>)>,
+    top: usize,
+) -> OperationResult<BenchmarkReport> {
+    let mut latencies = vec![];
+    let mut recall_sum = 0.0;
+    for (vector, expected) in queries {
+        let timer = Instant::now();
+        let found = segment.search(vector, &WithPayload::default(), false, None, top, None, &StopCondition::default())?;
+        latencies.push(timer.elapsed());
+
+        if !expected.is_empty() {
+            let found_count = found.iter().filter(|point| expected.contains(&point.id)).count();
+            recall_sum += found_count as f64 / expected.len() as f64;
+        } else {
+            recall_sum += 1.0;
+        }
+    }
+    if latencies.is_empty() {
+        return Err(OperationError::WrongInput {
+            description: "Benchmark requires at least one query".to_string()
+        });
+    }
+
+    let queries = latencies.len();
+    let total: Duration = latencies.iter().sum();
+    latencies.sort();
+    Ok(BenchmarkReport {
+        top,
+        queries,
+        recall: recall_sum / queries as f64,
+        p50_latency_ms: percentile_ms(&latencies, 50),
+        p95_latency_ms: percentile_ms(&latencies, 95),
+        p99_latency_ms: percentile_ms(&latencies, 99),
+        qps: queries as f64 / total.as_secs_f64().max(f64::EPSILON),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::types::Distance;
+
+    #[test]
+    fn test_run_benchmark() {
+        let dir = TempDir::new("benchmark").unwrap();
+        let mut segment = Segment::builder(dir.path()).dim(2).distance(Distance::Dot).build().unwrap();
+        for id in 0..100u64 {
+            segment.upsert_point(id, id.into(), &vec![id as f32, 1.0]).unwrap();
+        }
+
+        let queries = sample_queries(&segment, 10).unwrap();
+        assert_eq!(queries.len(), 10);
+        assert_eq!(sample_queries(&segment, 10).unwrap()[3].vector, queries[3].vector);
+
+        // Plain index search is exact, so every true neighbor is found
+        let report = run_benchmark(&segment, &queries, 5).unwrap();
+        assert_eq!(report.queries, 10);
+        assert_eq!(report.recall, 1.0);
+        assert!(report.p50_latency_ms <= report.p99_latency_ms);
+        assert_eq!(report.to_csv_row().split(',').count(), BenchmarkReport::CSV_HEADER.split(',').count());
+
+        let queries_path = dir.path().join("queries.jsonl");
+        write(&queries_path, "{\"vector\": [1.0, 0.0], \"neighbors\": [99, 1]}\n\n{\"vector\": [1.0, 0.0]}\n").unwrap();
+        let queries = read_queries(&queries_path).unwrap();
+        assert_eq!(queries.len(), 2);
+        let report = run_benchmark(&segment, &queries, 2).unwrap();
+        assert_eq!(report.recall, 0.75);
+
+        assert!(run_benchmark(&segment, &[], 2).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::remove_dir_all;
+use std::path::Path;
+use std::process::exit;
+use std::time::Instant;
+
+use segment::ann_benchmarks::{AnnDataset, build_benchmark_segment, evaluate};
+use segment::types::{Distance, Indexes, SegmentConfig, StorageType};
+
+const USAGE: &str = "Usage: ann_benchmarks <dataset.hdf5> [--top 10] [--mmap]
+
+Builds a segment with train vectors of the ann-benchmarks dataset and reports recall and latency
+of the search for test vectors. Only plain search is measured, as HNSW index is not implemented yet.
+Datasets with angular and dot distances are supported. Euclidean datasets are skipped,
+as search with Euclid distance is not implemented";
+
+struct Args {
+    dataset_path: String,
+    top: usize,
+    storage_type: StorageType,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let dataset_path = args.next().ok_or("Dataset path is required")?;
+    let mut parsed = Args {
+        dataset_path,
+        top: 10,
+        storage_type: StorageType::InMemory,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Value of {} is required", arg));
+        let number = |value: String| value.parse::<usize>().map_err(|err| format!("Invalid number {}: {}", value, err));
+        match arg.as_str() {
+            "--top" => parsed.top = number(value()?)?,
+            "--mmap" => parsed.storage_type = StorageType::Mmap,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(2);
+    });
+
+    let dataset = AnnDataset::load(Path::new(&args.dataset_path)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    println!("dataset: {} train and {} test vectors of dimension {}, distance {:?}",
+             dataset.train.nrows(), dataset.test.nrows(), dataset.dim(), dataset.distance);
+
+    let config = SegmentConfig {
+        vector_size: dataset.dim(),
+        index: Indexes::Plain {},
+        payload_index: None,
+        distance: Distance::Dot,
+        storage_type: args.storage_type,
+        text_analyzers: Default::default(),
+        flush_policy: None,
+    };
+    let segment_path = std::env::temp_dir().join(format!("ann_benchmarks-{}", std::process::id()));
+    let timer = Instant::now();
+    let segment = build_benchmark_segment(&dataset, &config, &segment_path).unwrap_or_else(|err| {
+        remove_dir_all(&segment_path).ok();
+        eprintln!("{}", err);
+        exit(1);
+    });
+    println!("segment with {:?} built in {:.1}s", config.index, timer.elapsed().as_secs_f64());
+
+    match evaluate(&segment, &dataset, args.top) {
+        Ok(report) => {
+            println!("{:>10} {:>12} {:>12} {:>12} {:>10}",
+                     format!("recall@{}", args.top), "p50, ms", "p95, ms", "p99, ms", "qps");
+            println!("{:>10.4} {:>12.3} {:>12.3} {:>12.3} {:>10.1}",
+                     report.recall,
+                     report.p50_latency_ms,
+                     report.p95_latency_ms,
+                     report.p99_latency_ms,
+                     report.qps);
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    drop(segment);
+    remove_dir_all(&segment_path).ok();
+}
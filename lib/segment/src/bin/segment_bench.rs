@@ -0,0 +1,74 @@
+use std::path::Path;
+use std::process::exit;
+
+use segment::benchmark::{read_queries, run_benchmark, sample_queries, BenchmarkReport};
+use segment::segment_constructor::segment_constructor::load_segment_read_only;
+
+const USAGE: &str = "Usage: segment-bench <segment_dir> [--queries <queries.jsonl> | --sample 100] [--top 10] [--csv]
+
+Searches the segment with each query and reports recall@top, p50/p95/p99 latency and QPS.
+Only plain search is measured, as HNSW index is not implemented yet.
+Queries are JSON lines {\"vector\": [...], \"neighbors\": [<ids>]}, neighbors are optional.
+Without --queries, vectors of points of the segment are used as queries.
+True neighbors, which are not specified, are found by the exact search over all vectors of the segment";
+
+struct Args {
+    segment_path: String,
+    queries_path: Option<String>,
+    sample: usize,
+    top: usize,
+    csv: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let segment_path = args.next().ok_or("Segment directory is required")?;
+    let mut parsed = Args {
+        segment_path,
+        queries_path: None,
+        sample: 100,
+        top: 10,
+        csv: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Value of {} is required", arg));
+        let number = |value: String| value.parse::<usize>().map_err(|err| format!("Invalid number {}: {}", value, err));
+        match arg.as_str() {
+            "--queries" => parsed.queries_path = Some(value()?),
+            "--sample" => parsed.sample = number(value()?)?,
+            "--top" => parsed.top = number(value()?)?,
+            "--csv" => parsed.csv = true,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(2);
+    });
+
+    let segment = load_segment_read_only(Path::new(&args.segment_path)).unwrap_or_else(|err| {
+        eprintln!("Can't open segment: {}", err);
+        exit(1);
+    });
+    let queries = match &args.queries_path {
+        Some(path) => read_queries(Path::new(path)),
+        None => sample_queries(&segment, args.sample),
+    };
+    let report = queries
+        .and_then(|queries| run_benchmark(&segment, &queries, args.top))
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            exit(1);
+        });
+
+    if args.csv {
+        println!("{}", BenchmarkReport::CSV_HEADER);
+        println!("{}", report.to_csv_row());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+}
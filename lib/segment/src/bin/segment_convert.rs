@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::process::exit;
+
+use segment::segment_constructor::storage_conversion::convert_vector_storage;
+use segment::types::StorageType;
+
+const USAGE: &str = "Usage:
+    segment-convert <in_memory|mmap> <segment_dir>...
+
+Vector storage of each segment is converted in place, ids, payloads and indexes are kept.
+Segments should not be opened by a running service during the conversion";
+
+struct Args {
+    storage_type: StorageType,
+    segment_paths: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let storage_type = match args.next().as_deref() {
+        Some("in_memory") => StorageType::InMemory,
+        Some("mmap") => StorageType::Mmap,
+        Some(storage_type) => return Err(format!("Unknown storage type {}", storage_type)),
+        None => return Err("Storage type is required".to_string()),
+    };
+    let segment_paths: Vec<String> = args.collect();
+    if segment_paths.is_empty() {
+        return Err("Segment directory is required".to_string());
+    }
+    Ok(Args { storage_type, segment_paths })
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(2);
+    });
+
+    let mut failed = false;
+    for segment_path in &args.segment_paths {
+        match convert_vector_storage(Path::new(segment_path), args.storage_type) {
+            Ok(true) => println!("{}: converted", segment_path),
+            Ok(false) => println!("{}: already has requested storage", segment_path),
+            Err(err) => {
+                eprintln!("{}: {}", segment_path, err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::{stdout, BufWriter, Write};
+use std::path::Path;
+use std::process::exit;
+
+use serde_json::json;
+
+use segment::common::stop_condition::StopCondition;
+use segment::entry::entry_point::{OperationResult, SegmentEntry};
+use segment::segment::Segment;
+use segment::segment_constructor::segment_constructor::{load_segment_read_only, read_segment_state};
+use segment::types::{ConsistencyCheckMode, PointIdType};
+
+const USAGE: &str = "Usage:
+    segment-inspect <segment_dir> [info]    print config, version, counts, disk and memory usage, payload indexes
+    segment-inspect <segment_dir> check     validate files and agreement of storages, exit with 1 on problems
+    segment-inspect <segment_dir> dump [--ids 1,2,<uuid>] [--offset <id>] [--limit 10] [--vectors] [--no-payload]
+                                            print points as JSON lines, selected by ids or in ascending order of ids
+
+Segment is opened in read-only mode, so files are never changed, e.g. of a segment of a running service";
+
+enum Command {
+    Info,
+    Check,
+    Dump {
+        ids: Option<Vec<PointIdType>>,
+        offset: Option<PointIdType>,
+        limit: usize,
+        with_vector: bool,
+        with_payload: bool,
+    },
+}
+
+struct Args {
+    segment_path: String,
+    command: Command,
+}
+
+fn parse_id(id: &str) -> Result<PointIdType, String> {
+    id.parse().map_err(|_| format!("Invalid point id {}", id))
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let segment_path = args.next().ok_or("Segment directory is required")?;
+    let command = match args.next().as_deref() {
+        None | Some("info") => Command::Info,
+        Some("check") => Command::Check,
+        Some("dump") => {
+            let mut ids = None;
+            let mut offset = None;
+            let mut limit = 10;
+            let mut with_vector = false;
+            let mut with_payload = true;
+            while let Some(arg) = args.next() {
+                let mut value = || args.next().ok_or(format!("Value of {} is required", arg));
+                match arg.as_str() {
+                    "--ids" => ids = Some(value()?.split(',').map(parse_id).collect::<Result<_, _>>()?),
+                    "--offset" => offset = Some(parse_id(&value()?)?),
+                    "--limit" => {
+                        let value = value()?;
+                        limit = value.parse().map_err(|err| format!("Invalid number {}: {}", value, err))?;
+                    }
+                    "--vectors" => with_vector = true,
+                    "--no-payload" => with_payload = false,
+                    _ => return Err(format!("Unknown argument {}", arg)),
+                }
+            }
+            Command::Dump { ids, offset, limit, with_vector, with_payload }
+        }
+        Some(command) => return Err(format!("Unknown command {}", command)),
+    };
+    if let Some(arg) = args.next() {
+        return Err(format!("Unknown argument {}", arg));
+    }
+    Ok(Args { segment_path, command })
+}
+
+fn print_info(path: &Path, segment: &Segment) -> OperationResult<()> {
+    // State is read again, as the opened segment does not expose format version of its files
+    let state = read_segment_state(path)?;
+    let info = json!({
+        "path": path.display().to_string(),
+        "format_version": state.format_version,
+        "version": segment.version(),
+        "info": segment.info(),
+        "payload_index": segment.payload_index_info(),
+    });
+    println!("{}", serde_json::to_string_pretty(&info).unwrap());
+    Ok(())
+}
+
+fn dump(
+    segment: &Segment,
+    ids: Option<Vec<PointIdType>>,
+    offset: Option<PointIdType>,
+    limit: usize,
+    with_vector: bool,
+    with_payload: bool,
+) -> OperationResult<()> {
+    let ids = match ids {
+        Some(ids) => ids,
+        None => segment.read_filtered(offset, limit, None, &StopCondition::default())?,
+    };
+    let mut out = BufWriter::new(stdout());
+    for id in ids {
+        if !segment.has_point(id) {
+            eprintln!("No point with id {} found", id);
+            continue;
+        }
+        let mut point = json!({
+            "id": id,
+            "version": segment.point_version(id),
+        });
+        if with_vector {
+            point["vector"] = json!(segment.vector(id)?);
+        }
+        if with_payload {
+            point["payload"] = json!(segment.payload(id)?);
+        }
+        writeln!(out, "{}", point)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(2);
+    });
+    let path = Path::new(&args.segment_path);
+
+    let mut segment = load_segment_read_only(path).unwrap_or_else(|err| {
+        eprintln!("Can't open segment: {}", err);
+        exit(1);
+    });
+
+    let result = match args.command {
+        Command::Info => print_info(path, &segment),
+        Command::Check => segment.check_consistency(ConsistencyCheckMode::Check).map(|report| {
+            if report.is_consistent() {
+                println!("segment is consistent");
+            } else {
+                for problem in &report.problems {
+                    println!("{}", problem);
+                }
+                exit(1);
+            }
+        }),
+        Command::Dump { ids, offset, limit, with_vector, with_payload } =>
+            dump(&segment, ids, offset, limit, with_vector, with_payload),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::entry::entry_point::{OperationError, OperationResult};
+
+/// Invalid value of the config parameter at the `path`, e.g. `index.options.m`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        ConfigProblem { path: path.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Path of the `field` of the config parameter at the `path`
+pub fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+/// Report the parameter at the `path`, if its value is out of the inclusive range
+pub fn check_range<T: PartialOrd + fmt::Display>(
+    path: &str,
+    value: T,
+    min: T,
+    max: T,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    if value < min || value > max {
+        problems.push(ConfigProblem::new(
+            path,
+            format!("value {} is out of range, should be between {} and {}", value, min, max),
+        ));
+    }
+}
+
+/// Checks of config parameters, which could not be expressed by their types
+pub trait ValidateConfig {
+    /// Append problems of the config to `problems`, paths of parameters are prefixed with `path`
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>);
+
+    fn validate(&self) -> Result<(), Vec<ConfigProblem>> {
+        let mut problems = vec![];
+        self.check("", &mut problems);
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+}
+
+impl<T: ValidateConfig> ValidateConfig for Option<T> {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if let Some(config) = self {
+            config.check(path, problems)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Format of the config file by its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Error, which describes all problems of the config
+pub fn invalid_config(problems: &[ConfigProblem]) -> OperationError {
+    let problems: Vec<_> = problems.iter().map(|problem| problem.to_string()).collect();
+    OperationError::WrongInput { description: format!("Invalid config: {}", problems.join("; ")) }
+}
+
+/// Keys of the input, which are not present in the deserialized config. Keys with null values are ignored,
+/// as they are equal to not specified optional parameters
+fn unknown_fields(input: &Value, parsed: &Value, path: &str, problems: &mut Vec<ConfigProblem>) {
+    match (input, parsed) {
+        (Value::Object(input), Value::Object(parsed)) => {
+            for (key, value) in input {
+                let key_path = field_path(path, key);
+                match parsed.get(key) {
+                    Some(parsed_value) => unknown_fields(value, parsed_value, &key_path, problems),
+                    None if value.is_null() => {}
+                    None => {
+                        let mut expected: Vec<_> = parsed.keys().map(|key| format!("`{}`", key)).collect();
+                        expected.sort();
+                        problems.push(ConfigProblem::new(
+                            &key_path,
+                            format!("unknown field `{}`, expected one of {}", key, expected.join(", ")),
+                        ));
+                    }
+                }
+            }
+        }
+        (Value::Array(input), Value::Array(parsed)) => {
+            for (idx, (value, parsed_value)) in input.iter().zip(parsed).enumerate() {
+                unknown_fields(value, parsed_value, &format!("{}[{}]", path, idx), problems);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Deserialize and validate the config. Unlike plain deserialization, unknown parameters are reported
+/// instead of being silently ignored, so typos do not turn into default values
+pub fn parse_config<T>(text: &str, format: ConfigFormat) -> OperationResult<T>
+    where T: DeserializeOwned + Serialize + ValidateConfig {
+    let input: Value = match format {
+        ConfigFormat::Json => serde_json::from_str(text)
+            .map_err(|err| invalid_config(&[ConfigProblem::new("", err.to_string())]))?,
+        ConfigFormat::Yaml => serde_yaml::from_str(text)
+            .map_err(|err| invalid_config(&[ConfigProblem::new("", err.to_string())]))?,
+    };
+    let config: T = serde_json::from_value(input.clone())
+        .map_err(|err| invalid_config(&[ConfigProblem::new("", err.to_string())]))?;
+
+    let mut problems = vec![];
+    let parsed = serde_json::to_value(&config)
+        .map_err(|err| OperationError::ServiceError { description: format!("{}", err) })?;
+    unknown_fields(&input, &parsed, "", &mut problems);
+    config.check("", &mut problems);
+    if problems.is_empty() { Ok(config) } else { Err(invalid_config(&problems)) }
+}
+
+/// Read, deserialize and validate the config file. Format is defined by the extension of the file
+pub fn load_config<T>(path: &Path) -> OperationResult<T>
+    where T: DeserializeOwned + Serialize + ValidateConfig {
+    let format = ConfigFormat::from_path(path).ok_or_else(|| OperationError::WrongInput {
+        description: format!("Unknown format of config {}, expected .json, .yaml or .yml file", path.display())
+    })?;
+    let text = std::fs::read_to_string(path)?;
+    parse_config(&text, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::SegmentConfig;
+
+    use super::*;
+
+    fn problems(result: OperationResult<SegmentConfig>) -> String {
+        match result {
+            Err(OperationError::WrongInput { description }) => description,
+            other => panic!("config should be invalid: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let yaml = r#"
+vector_size: 4
+distance: Dot
+storage_type:
+  type: in_memory
+index:
+  type: hnsw
+  options:
+    m: 16
+    ef_construct: 100
+payload_index: null
+"#;
+        let config: SegmentConfig = parse_config(yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(config.vector_size, 4);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(parse_config::<SegmentConfig>(&json, ConfigFormat::Json).unwrap(), config);
+
+        let unknown = yaml.replace("ef_construct", "ef_constrcut");
+        let description = problems(parse_config(&unknown, ConfigFormat::Yaml));
+        assert!(description.contains("missing field `ef_construct`"), "{}", description);
+
+        let unknown = yaml.replace("payload_index: null", "payload_indx: struct");
+        let description = problems(parse_config(&unknown, ConfigFormat::Yaml));
+        assert!(description.contains("payload_indx: unknown field `payload_indx`"), "{}", description);
+
+        let out_of_range = yaml.replace("m: 16", "m: 0").replace("vector_size: 4", "vector_size: 0");
+        let description = problems(parse_config(&out_of_range, ConfigFormat::Yaml));
+        assert!(description.contains("vector_size: value 0 is out of range"), "{}", description);
+        assert!(description.contains("index.options.m: value 0 is out of range"), "{}", description);
+    }
+}
@@ -1,14 +1,16 @@
-use log::debug;
+use std::fmt::Display;
+
+use tracing::debug;
 
 pub trait LogError {
     fn describe(self, msg: &str) -> Self;
 }
 
 
-impl<T, E> LogError for Result<T, E> {
+impl<T, E: Display> LogError for Result<T, E> {
     fn describe(self, msg: &str) -> Self {
-        if self.is_err() {
-            debug!("Error while: {}", msg);
+        if let Err(err) = &self {
+            debug!(operation = msg, error = %err, "Operation failed");
         }
         self
     }
@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::fs::{File, read_dir, create_dir_all, copy, hard_link, rename};
 use std::io::{Read, Write};
 use crate::entry::entry_point::{OperationError, OperationResult};
 use serde::Serialize;
@@ -29,4 +29,68 @@ pub fn read_json<N: DeserializeOwned + Serialize>(path: &Path) -> OperationResul
     })?;
 
     Ok(result)
-}
\ No newline at end of file
+}
+
+/// Total size of all files in the directory, including nested ones.
+/// Files which could not be accessed are ignored.
+pub fn dir_size(path: &Path) -> usize {
+    let entries = match read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => 0
+        })
+        .sum()
+}
+
+/// Recursively copy content of the directory into a new one
+pub fn copy_dir(from: &Path, to: &Path) -> OperationResult<()> {
+    create_dir_all(to)?;
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.metadata()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Share the file with a new path. Falls back to copying if hard link is not possible,
+/// e.g. if paths are located on different file systems.
+pub fn link_or_copy(from: &Path, to: &Path) -> OperationResult<()> {
+    if hard_link(from, to).is_err() {
+        copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_shared(path: &Path) -> OperationResult<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(path.metadata()?.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn is_shared(_path: &Path) -> OperationResult<bool> {
+    Ok(true)
+}
+
+/// Make sure that the file is not shared with other paths via hard links before it is modified in place.
+/// Shared file is replaced with its own copy.
+pub fn unshare_file(path: &Path) -> OperationResult<()> {
+    if !path.exists() || !is_shared(path)? {
+        return Ok(());
+    }
+    let tmp_path = path.with_extension("unshare");
+    copy(path, &tmp_path)?;
+    rename(&tmp_path, path)?;
+    Ok(())
+}
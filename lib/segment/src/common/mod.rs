@@ -1,2 +1,12 @@
+pub mod config;
 pub mod file_operations;
-pub mod error_logging;
\ No newline at end of file
+pub mod error_logging;
+pub mod rocksdb_operations;
+pub mod rw_cell;
+pub mod npy;
+pub mod numa;
+pub mod page_cache;
+pub mod point_locks;
+pub mod readahead;
+pub mod search_arena;
+pub mod stop_condition;
@@ -0,0 +1,355 @@
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::path::Path;
+
+use memmap::{Mmap, MmapOptions};
+
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::types::VectorElementType;
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+const ZIP_LOCAL_HEADER: u32 = 0x04034b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x02014b50;
+const ZIP_END_OF_CENTRAL_DIR: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIR: u32 = 0x06064b50;
+const ZIP64_END_OF_CENTRAL_DIR_LOCATOR: u32 = 0x07064b50;
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+/// Max size of the end of central directory record with a comment
+const ZIP_MAX_END_SIZE: usize = 22 + u16::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NpyDtype {
+    Float32,
+    Float64,
+}
+
+impl NpyDtype {
+    fn size(&self) -> usize {
+        match self {
+            NpyDtype::Float32 => 4,
+            NpyDtype::Float64 => 8,
+        }
+    }
+}
+
+fn wrong_input(path: &Path, description: &str) -> OperationError {
+    OperationError::WrongInput { description: format!("Can't read vectors from {:?}: {}", path, description) }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?).map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset.checked_add(8)?).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Offset, read from the file, if it is addressable on this platform
+fn to_offset(value: u64) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+/// Value of the key in the header dictionary, e.g. `'<f4'` for `descr`
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}':", key))? + key.len() + 3;
+    let value = header[start..].trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')')? + 1
+    } else {
+        value.find(|c| c == ',' || c == '}')?
+    };
+    Some(value[..end].trim())
+}
+
+/// Offset of the data, dtype, number of rows and dimension of the 2-dimensional array, stored as `.npy` at `offset`
+fn parse_npy_header(data: &[u8], offset: usize) -> Option<Result<(usize, NpyDtype, usize, usize), &'static str>> {
+    if data.get(offset..offset.checked_add(NPY_MAGIC.len())?)? != NPY_MAGIC {
+        return Some(Err("not a .npy array"));
+    }
+    // Offset is followed by the magic string, so the header fields do not overflow
+    let major_version = *data.get(offset + 6)?;
+    let (header_start, header_len) = match major_version {
+        1 => (offset + 10, read_u16(data, offset + 8)? as usize),
+        _ => (offset + 12, usize::try_from(read_u32(data, offset + 8)?).ok()?),
+    };
+    let header_end = header_start.checked_add(header_len)?;
+    let header = std::str::from_utf8(data.get(header_start..header_end)?).ok()?;
+
+    let dtype = match header_value(header, "descr")? {
+        "'<f4'" => NpyDtype::Float32,
+        "'<f8'" => NpyDtype::Float64,
+        _ => return Some(Err("only little-endian float32 and float64 arrays are supported")),
+    };
+    if header_value(header, "fortran_order")? != "False" {
+        return Some(Err("arrays in Fortran order are not supported"));
+    }
+    let shape: Vec<usize> = header_value(header, "shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(|dim| dim.trim())
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().ok())
+        .collect::<Option<_>>()?;
+    match shape.as_slice() {
+        [rows, dim] => Some(Ok((header_end, dtype, *rows, *dim))),
+        _ => Some(Err("only 2-dimensional arrays are supported")),
+    }
+}
+
+/// Offset of the data of the first `.npy` entry of the zip archive.
+/// Entries should be stored without compression, as `np.savez` does, so they could be memory-mapped.
+fn find_npz_entry(data: &[u8]) -> Result<usize, &'static str> {
+    let malformed = "malformed .npz archive";
+    let search_start = data.len().saturating_sub(ZIP_MAX_END_SIZE);
+    let end_offset = (search_start..data.len().saturating_sub(21)).rev()
+        .find(|offset| read_u32(data, *offset) == Some(ZIP_END_OF_CENTRAL_DIR))
+        .ok_or(malformed)?;
+
+    let mut entries = read_u16(data, end_offset + 10).ok_or(malformed)? as u64;
+    let mut directory_offset = read_u32(data, end_offset + 16).ok_or(malformed)? as u64;
+    if directory_offset == u32::MAX as u64 || entries == u16::MAX as u64 {
+        let locator_offset = end_offset.checked_sub(20).ok_or(malformed)?;
+        if read_u32(data, locator_offset) != Some(ZIP64_END_OF_CENTRAL_DIR_LOCATOR) {
+            return Err(malformed);
+        }
+        let end64_offset = read_u64(data, locator_offset + 8).and_then(to_offset).ok_or(malformed)?;
+        if read_u32(data, end64_offset) != Some(ZIP64_END_OF_CENTRAL_DIR) {
+            return Err(malformed);
+        }
+        entries = end64_offset.checked_add(32).and_then(|field| read_u64(data, field)).ok_or(malformed)?;
+        directory_offset = end64_offset.checked_add(48).and_then(|field| read_u64(data, field)).ok_or(malformed)?;
+    }
+
+    let mut offset = to_offset(directory_offset).ok_or(malformed)?;
+    for _ in 0..entries {
+        if read_u32(data, offset) != Some(ZIP_CENTRAL_HEADER) {
+            return Err(malformed);
+        }
+        // Entry header is read only if it fits into the data, so offsets within it do not overflow
+        let compression = read_u16(data, offset + 10).ok_or(malformed)?;
+        let name_len = read_u16(data, offset + 28).ok_or(malformed)? as usize;
+        let extra_len = read_u16(data, offset + 30).ok_or(malformed)? as usize;
+        let comment_len = read_u16(data, offset + 32).ok_or(malformed)? as usize;
+        let name_start = offset.checked_add(46).ok_or(malformed)?;
+        let name = data.get(name_start..name_start.checked_add(name_len).ok_or(malformed)?).ok_or(malformed)?;
+
+        if name.ends_with(b".npy") {
+            if compression != 0 {
+                return Err("compressed .npz archives are not supported, use `np.savez` instead of `np.savez_compressed`");
+            }
+            let mut local_offset = read_u32(data, offset + 42).ok_or(malformed)? as u64;
+            if local_offset == u32::MAX as u64 {
+                local_offset = zip64_local_offset(data, offset, name_len, extra_len).ok_or(malformed)?;
+            }
+            let local_offset = to_offset(local_offset).ok_or(malformed)?;
+            if read_u32(data, local_offset) != Some(ZIP_LOCAL_HEADER) {
+                return Err(malformed);
+            }
+            let local_name_len = read_u16(data, local_offset + 26).ok_or(malformed)? as usize;
+            let local_extra_len = read_u16(data, local_offset + 28).ok_or(malformed)? as usize;
+            return local_offset.checked_add(30 + local_name_len + local_extra_len).ok_or(malformed);
+        }
+        offset = name_start.checked_add(name_len + extra_len + comment_len).ok_or(malformed)?;
+    }
+    Err("no .npy array in the .npz archive")
+}
+
+/// Offset of the local header from the zip64 extra field of the central directory entry.
+/// Sizes precede the offset in the field, if they do not fit into the entry as well.
+fn zip64_local_offset(data: &[u8], entry_offset: usize, name_len: usize, extra_len: usize) -> Option<u64> {
+    let mut offset = entry_offset.checked_add(46 + name_len)?;
+    let extra_end = offset.checked_add(extra_len)?;
+    while offset.checked_add(4)? <= extra_end {
+        let field_id = read_u16(data, offset)?;
+        let field_len = read_u16(data, offset + 2)? as usize;
+        if field_id == ZIP64_EXTRA_FIELD {
+            let mut value_offset = offset.checked_add(4)?;
+            for size_offset in &[24, 20] {
+                if read_u32(data, entry_offset.checked_add(*size_offset)?)? == u32::MAX {
+                    value_offset = value_offset.checked_add(8)?;
+                }
+            }
+            return read_u64(data, value_offset);
+        }
+        offset = offset.checked_add(4 + field_len)?;
+    }
+    None
+}
+
+/// Memory-mapped 2-dimensional float array, stored by numpy as `.npy` file or as the first array of `.npz` archive.
+/// Rows are read on demand, so arrays larger than RAM could be loaded.
+pub struct NpyVectors {
+    mmap: Mmap,
+    data_offset: usize,
+    dtype: NpyDtype,
+    rows: usize,
+    dim: usize,
+}
+
+impl NpyVectors {
+    pub fn open(path: &Path) -> OperationResult<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+        let is_npz = path.extension().map_or(false, |ext| ext == "npz");
+        let array_offset = if is_npz {
+            find_npz_entry(&mmap).map_err(|err| wrong_input(path, err))?
+        } else {
+            0
+        };
+        let (data_offset, dtype, rows, dim) = parse_npy_header(&mmap, array_offset)
+            .unwrap_or(Err("malformed .npy header"))
+            .map_err(|err| wrong_input(path, err))?;
+        let data_end = rows.checked_mul(dim)
+            .and_then(|elements| elements.checked_mul(dtype.size()))
+            .and_then(|data_size| data_offset.checked_add(data_size))
+            .ok_or_else(|| wrong_input(path, "array shape is too large"))?;
+        if data_end > mmap.len() {
+            return Err(wrong_input(path, "array is truncated"));
+        }
+
+        Ok(NpyVectors { mmap, data_offset, dtype, rows, dim })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn vector(&self, row: usize) -> Vec<VectorElementType> {
+        let row_size = self.dim * self.dtype.size();
+        let start = self.data_offset + row * row_size;
+        let bytes = &self.mmap[start..start + row_size];
+        match self.dtype {
+            NpyDtype::Float32 => bytes.chunks_exact(4)
+                .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+                .collect(),
+            NpyDtype::Float64 => bytes.chunks_exact(8)
+                .map(|value| f64::from_le_bytes(value.try_into().unwrap()) as VectorElementType)
+                .collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=Vec<VectorElementType>> + '_ {
+        (0..self.rows).map(move |row| self.vector(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn npy_bytes(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+        // Header is padded, so the data is aligned, as numpy does
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes = NPY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// Zip archive with a single stored entry
+    fn npz_bytes(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&ZIP_LOCAL_HEADER.to_le_bytes());
+        bytes.extend_from_slice(&[0; 22]);
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(content);
+
+        let directory_offset = bytes.len();
+        bytes.extend_from_slice(&ZIP_CENTRAL_HEADER.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        let directory_size = bytes.len() - directory_offset;
+
+        bytes.extend_from_slice(&ZIP_END_OF_CENTRAL_DIR.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&(directory_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&(directory_offset as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes
+    }
+
+    fn write_file(dir: &TempDir, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_npy() {
+        let dir = TempDir::new("npy").unwrap();
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+        let path = write_file(&dir, "vectors.npy", &npy_bytes("<f4", "(3, 2)", &data));
+
+        let vectors = NpyVectors::open(&path).unwrap();
+        assert_eq!(vectors.rows(), 3);
+        assert_eq!(vectors.dim(), 2);
+        assert_eq!(vectors.iter().collect::<Vec<_>>(), vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]);
+    }
+
+    #[test]
+    fn test_read_npz() {
+        let dir = TempDir::new("npy").unwrap();
+        let data: Vec<u8> = [1.0f64, 2.0].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+        let path = write_file(&dir, "vectors.npz", &npz_bytes("arr_0.npy", &npy_bytes("<f8", "(1, 2)", &data)));
+
+        let vectors = NpyVectors::open(&path).unwrap();
+        assert_eq!(vectors.iter().collect::<Vec<_>>(), vec![vec![1.0, 2.0]]);
+    }
+
+    #[test]
+    fn test_unsupported_npy() {
+        let dir = TempDir::new("npy").unwrap();
+        let path = write_file(&dir, "ints.npy", &npy_bytes("<i8", "(1, 1)", &[0; 8]));
+        assert!(NpyVectors::open(&path).is_err());
+
+        let path = write_file(&dir, "truncated.npy", &npy_bytes("<f4", "(10, 2)", &[0; 8]));
+        assert!(NpyVectors::open(&path).is_err());
+
+        let shape = format!("({}, 2)", usize::MAX / 2);
+        let path = write_file(&dir, "huge.npy", &npy_bytes("<f4", &shape, &[0; 8]));
+        assert!(matches!(NpyVectors::open(&path), Err(OperationError::WrongInput { .. })));
+    }
+
+    #[test]
+    fn test_malformed_npz() {
+        let dir = TempDir::new("npy").unwrap();
+        let mut bytes = npz_bytes("arr_0.npy", &npy_bytes("<f4", "(1, 1)", &[0; 4]));
+
+        // Zip64 locator points to the end of the address space
+        let end_offset = bytes.len() - 22;
+        bytes[end_offset + 16..end_offset + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut locator = ZIP64_END_OF_CENTRAL_DIR_LOCATOR.to_le_bytes().to_vec();
+        locator.extend_from_slice(&[0; 4]);
+        locator.extend_from_slice(&u64::MAX.to_le_bytes());
+        locator.extend_from_slice(&1u32.to_le_bytes());
+        bytes.splice(end_offset..end_offset, locator);
+
+        let path = write_file(&dir, "malformed.npz", &bytes);
+        assert!(matches!(NpyVectors::open(&path), Err(OperationError::WrongInput { .. })));
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::{read_dir, read_to_string};
+use std::io;
+
+/// Node of a NUMA machine and CPUs, which belong to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumaNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+const NODES_PATH: &str = "/sys/devices/system/node";
+
+/// Memory of the node is used first, other nodes are used once it is exhausted
+#[cfg(target_os = "linux")]
+const MPOL_PREFERRED: libc::c_int = 1;
+
+/// Pages, which are already allocated on other nodes, are moved
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+/// Parse list of CPUs in the sysfs format, e.g. `0-3,8,10-11`
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.trim()
+        .split(',')
+        .filter(|range| !range.is_empty())
+        .flat_map(|range| {
+            let mut bounds = range.splitn(2, '-').map(|bound| bound.parse::<usize>().ok());
+            let start = bounds.next().flatten();
+            let end = bounds.next().unwrap_or(start);
+            match (start, end) {
+                (Some(start), Some(end)) => start..end + 1,
+                _ => 0..0,
+            }
+        })
+        .collect()
+}
+
+/// Nodes with CPUs, sorted by ids. Empty, if the platform does not expose NUMA topology
+pub fn numa_nodes() -> Vec<NumaNode> {
+    let entries = match read_dir(NODES_PATH) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut nodes: Vec<NumaNode> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+            let cpus = parse_cpu_list(&read_to_string(entry.path().join("cpulist")).ok()?);
+            // Nodes without CPUs only provide memory
+            if cpus.is_empty() {
+                return None;
+            }
+            Some(NumaNode { id, cpus })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+#[cfg(target_os = "linux")]
+const MASK_BITS: usize = 8 * std::mem::size_of::<libc::c_ulong>();
+
+/// Node mask with the single node and its size in bits, as expected by memory policy syscalls
+#[cfg(target_os = "linux")]
+fn node_mask(node: usize) -> (Vec<libc::c_ulong>, libc::c_ulong) {
+    let mut mask = vec![0; node / MASK_BITS + 1];
+    mask[node / MASK_BITS] |= 1 << (node % MASK_BITS);
+    let max_node = (mask.len() * MASK_BITS + 1) as libc::c_ulong;
+    (mask, max_node)
+}
+
+/// Run the current thread on CPUs of the node only and allocate its memory on the node.
+/// Pages of memory mapped files, which are first read by the thread, are also cached on the node
+#[cfg(target_os = "linux")]
+pub fn bind_current_thread(node: &NumaNode) -> io::Result<()> {
+    unsafe {
+        let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in node.cpus.iter() {
+            libc::CPU_SET(*cpu, &mut cpu_set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (mask, max_node) = node_mask(node.id);
+        if libc::syscall(libc::SYS_set_mempolicy, MPOL_PREFERRED, mask.as_ptr(), max_node) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_current_thread(_node: &NumaNode) -> io::Result<()> {
+    Ok(())
+}
+
+/// Place memory of the mapping on the node and move its pages, which are already allocated elsewhere
+#[cfg(target_os = "linux")]
+pub fn bind_memory(data: &[u8], node: usize) -> io::Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    let (mask, max_node) = node_mask(node);
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            data.as_ptr(),
+            data.len(),
+            MPOL_PREFERRED,
+            mask.as_ptr(),
+            max_node,
+            MPOL_MF_MOVE,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_memory(_data: &[u8], _node: usize) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5"), vec![5]);
+        assert!(parse_cpu_list("\n").is_empty());
+    }
+}
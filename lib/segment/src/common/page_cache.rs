@@ -0,0 +1,65 @@
+use std::ops::Range;
+
+/// Size of memory pages, which are read by the warm-up
+const PAGE_SIZE: usize = 4096;
+
+/// Hint about the expected access to the memory mapped data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Advice {
+    /// Default readahead of the kernel
+    Normal,
+    /// Pages are read in random order, so the kernel should not read ahead
+    Random,
+    /// Pages will be read soon, so the kernel should read them ahead
+    WillNeed,
+}
+
+/// Pass the advice about the `range` of the mapped `data` to the kernel.
+/// Range is extended to page boundaries. The advice is only a hint, so errors are ignored
+#[cfg(target_os = "linux")]
+pub fn advise(data: &[u8], range: Range<usize>, advice: Advice) {
+    let range = range.start.min(data.len())..range.end.min(data.len());
+    if range.start >= range.end {
+        return;
+    }
+    let start = data.as_ptr() as usize + range.start;
+    let aligned_start = start - start % PAGE_SIZE;
+    let advice = match advice {
+        Advice::Normal => libc::MADV_NORMAL,
+        Advice::Random => libc::MADV_RANDOM,
+        Advice::WillNeed => libc::MADV_WILLNEED,
+    };
+    unsafe {
+        libc::madvise(aligned_start as *mut libc::c_void, range.end - range.start + start - aligned_start, advice);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn advise(_data: &[u8], _range: Range<usize>, _advice: Advice) {}
+
+/// Load pages of the memory mapped file into the page cache by reading a byte of each page,
+/// so the first requests to the data do not wait for page faults.
+/// Returns number of bytes in touched pages
+pub fn warm_up(data: &[u8]) -> usize {
+    if data.is_empty() {
+        return 0;
+    }
+    advise(data, 0..data.len(), Advice::WillNeed);
+    for offset in (0..data.len()).step_by(PAGE_SIZE) {
+        // Volatile read is not removed by the compiler, even though the value is not used
+        unsafe { std::ptr::read_volatile(data.as_ptr().add(offset)) };
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_up() {
+        let data = vec![1u8; PAGE_SIZE * 3 + 10];
+        assert_eq!(warm_up(&data), data.len());
+        assert_eq!(warm_up(&[]), 0);
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use parking_lot::{Mutex, MutexGuard};
+
+use crate::types::PointIdType;
+
+/// Number of locks, shared by all points of the segment
+const POINT_LOCKS_NUMBER: usize = 64;
+
+/// Locks of segment points.
+/// Operations, which change a point under shared access to the segment, hold its lock from the version check
+/// until the new version of the point is set, so concurrent operations on the same point are applied one by one
+/// and the older one never overwrites the newer one. Points are mapped onto a fixed number of locks,
+/// so operations on different points rarely wait for each other.
+pub struct PointLocks {
+    locks: Vec<Mutex<()>>,
+}
+
+impl Default for PointLocks {
+    fn default() -> Self {
+        PointLocks { locks: (0..POINT_LOCKS_NUMBER).map(|_| Mutex::new(())).collect() }
+    }
+}
+
+impl PointLocks {
+    pub fn lock(&self, point_id: PointIdType) -> MutexGuard<'_, ()> {
+        let mut hasher = DefaultHasher::new();
+        point_id.hash(&mut hasher);
+        self.locks[hasher.finish() as usize % self.locks.len()].lock()
+    }
+}
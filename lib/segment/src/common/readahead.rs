@@ -0,0 +1,123 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
+
+use crate::common::page_cache::{advise, Advice};
+
+/// Window, which is read ahead once reads become sequential. It is doubled every time the next window is requested
+pub const MIN_READAHEAD_WINDOW: usize = 256 * 1024;
+/// Largest window, which is read ahead
+pub const MAX_READAHEAD_WINDOW: usize = 16 * 1024 * 1024;
+/// Read, which starts within this distance after the end of the previous one, is considered sequential
+const MAX_SEQUENTIAL_GAP: usize = 64 * 1024;
+/// Number of consecutive reads of the same kind, after which the access pattern is considered changed
+const PATTERN_STREAK: isize = 2;
+
+/// Detects sequential reads of memory mapped data, e.g. of full scans and exports, and asks the kernel to read
+/// growing windows ahead of them. Once reads become random, e.g. of scoring of selected points,
+/// the window is dropped and kernel readahead is disabled for the mapping, so unused pages are not loaded.
+/// State is shared by all readers of the mapping, so interleaved reads of concurrent scans are treated as random
+#[derive(Debug, Default)]
+pub struct Readahead {
+    /// End of the previous read
+    last_end: AtomicUsize,
+    /// Number of consecutive sequential reads if positive, of random reads if negative
+    streak: AtomicIsize,
+    /// Current readahead window, 0 if reads are not sequential
+    window: AtomicUsize,
+    /// End of the range, which is already advised to be read ahead
+    advised_until: AtomicUsize,
+    /// Mapping is advised to be read randomly
+    random: AtomicBool,
+}
+
+impl Readahead {
+    pub fn window(&self) -> usize {
+        self.window.load(Ordering::Relaxed)
+    }
+
+    pub fn is_random(&self) -> bool {
+        self.random.load(Ordering::Relaxed)
+    }
+
+    /// Register read of the `range` of the mapped `data`
+    pub fn observe(&self, data: &[u8], range: Range<usize>) {
+        let last_end = self.last_end.swap(range.end, Ordering::Relaxed);
+        if range.start < last_end || range.start - last_end > MAX_SEQUENTIAL_GAP {
+            self.observe_random(data);
+            return;
+        }
+        let streak = self.streak.load(Ordering::Relaxed).max(0) + 1;
+        self.streak.store(streak, Ordering::Relaxed);
+        if streak < PATTERN_STREAK {
+            return;
+        }
+        if self.random.swap(false, Ordering::Relaxed) {
+            advise(data, 0..data.len(), Advice::Normal);
+        }
+
+        // New window is requested, once half of the advised one is read
+        let window = self.window();
+        let advised_until = self.advised_until.load(Ordering::Relaxed);
+        if advised_until >= range.end + window / 2 {
+            return;
+        }
+        let window = (window * 2).clamp(MIN_READAHEAD_WINDOW, MAX_READAHEAD_WINDOW);
+        self.window.store(window, Ordering::Relaxed);
+        let ahead = advised_until.max(range.end)..(range.end + window).min(data.len());
+        if ahead.start < ahead.end {
+            advise(data, ahead.clone(), Advice::WillNeed);
+            self.advised_until.store(ahead.end, Ordering::Relaxed);
+        }
+    }
+
+    /// Register read, which is not sequential, e.g. of unordered or sparse points
+    pub fn observe_random(&self, data: &[u8]) {
+        self.window.store(0, Ordering::Relaxed);
+        self.advised_until.store(0, Ordering::Relaxed);
+        let streak = self.streak.load(Ordering::Relaxed).min(0) - 1;
+        self.streak.store(streak, Ordering::Relaxed);
+        if streak <= -PATTERN_STREAK && !self.random.swap(true, Ordering::Relaxed) {
+            advise(data, 0..data.len(), Advice::Random);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readahead() {
+        let data = vec![0u8; 4 * MAX_READAHEAD_WINDOW];
+        let readahead = Readahead::default();
+
+        readahead.observe(&data, 0..4096);
+        assert_eq!(readahead.window(), 0);
+        readahead.observe(&data, 4096..8192);
+        assert_eq!(readahead.window(), MIN_READAHEAD_WINDOW);
+        // Window is not requested again, until half of it is read
+        readahead.observe(&data, 8192..12288);
+        assert_eq!(readahead.window(), MIN_READAHEAD_WINDOW);
+
+        let chunk = 1024 * 1024;
+        for offset in (12288..data.len() - chunk).step_by(chunk) {
+            readahead.observe(&data, offset..offset + chunk);
+        }
+        assert_eq!(readahead.window(), MAX_READAHEAD_WINDOW);
+        assert!(!readahead.is_random());
+
+        // Reads going backwards are random
+        for offset in (0..10).rev() {
+            readahead.observe(&data, offset * chunk..offset * chunk + 4096);
+        }
+        assert_eq!(readahead.window(), 0);
+        assert!(readahead.is_random());
+
+        // Sequential scan enables readahead again
+        for offset in (0..10).map(|idx| idx * 4096) {
+            readahead.observe(&data, offset..offset + 4096);
+        }
+        assert!(readahead.window() > 0);
+        assert!(!readahead.is_random());
+    }
+}
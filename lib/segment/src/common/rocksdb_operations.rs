@@ -0,0 +1,26 @@
+use std::path::Path;
+
+use rocksdb::{DB, Options};
+use rocksdb::checkpoint::Checkpoint;
+
+use crate::entry::entry_point::OperationResult;
+
+/// Open RocksDB storage with given column families.
+/// Storage opened in read-only mode does not take the database lock and rejects all writes,
+/// so it could be safely opened alongside the process which owns the data.
+pub fn open_db(options: &Options, path: &Path, column_families: &[&str], read_only: bool) -> OperationResult<DB> {
+    let db = match (read_only, column_families.is_empty()) {
+        (false, true) => DB::open(options, path)?,
+        (false, false) => DB::open_cf(options, path, column_families.iter())?,
+        (true, true) => DB::open_for_read_only(options, path, false)?,
+        (true, false) => DB::open_cf_for_read_only(options, path, column_families.iter(), false)?,
+    };
+    Ok(db)
+}
+
+/// Create a consistent copy of the storage in a new directory.
+/// Immutable table files are hard-linked, so the copy takes almost no additional space.
+pub fn checkpoint_db(db: &DB, path: &Path) -> OperationResult<()> {
+    Checkpoint::new(db)?.create_checkpoint(path)?;
+    Ok(())
+}
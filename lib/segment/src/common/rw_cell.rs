@@ -0,0 +1,91 @@
+use std::ops::{Deref, DerefMut};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Lock of a single segment component.
+/// Allows to access components of the same segment from different threads:
+/// e.g. search could read vector storage while payload storage is updated.
+///
+/// Reading never waits for the queued writers, so nested reads of the same component are safe.
+/// Readers might hold several components at once, so under shared access to the segment
+/// a writer holds only one component at a time and takes no other lock while holding it.
+/// Locks of the whole segment (update lock, point locks, pending payload migrations) are taken before
+/// the components. Exclusive operations might hold several components, as no reader accesses the segment meanwhile.
+#[derive(Debug, Default)]
+pub struct RwCell<T: ?Sized> {
+    lock: RwLock<T>,
+}
+
+impl<T> RwCell<T> {
+    pub fn new(value: T) -> Self {
+        RwCell { lock: RwLock::new(value) }
+    }
+}
+
+impl<T: ?Sized> RwCell<T> {
+    pub fn borrow(&self) -> RwCellRef<'_, T> {
+        RwCellRef { guard: self.lock.read_recursive() }
+    }
+
+    pub fn borrow_mut(&self) -> RwCellRefMut<'_, T> {
+        RwCellRefMut { guard: self.lock.write() }
+    }
+}
+
+/// Shared access to the component.
+/// Guards are wrapped, so methods of the component are not shadowed by the `Drop` of the lock guard.
+pub struct RwCellRef<'a, T: ?Sized> {
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<'a, T: ?Sized> Deref for RwCellRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+/// Exclusive access to the component
+pub struct RwCellRefMut<'a, T: ?Sized> {
+    guard: RwLockWriteGuard<'a, T>,
+}
+
+impl<'a, T: ?Sized> Deref for RwCellRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwCellRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_access() {
+        let cell = Arc::new(RwCell::new(vec![1, 2, 3]));
+        let read_guard = cell.borrow();
+        let nested_read_guard = cell.borrow();
+
+        let writer_cell = cell.clone();
+        let writer = thread::spawn(move || writer_cell.borrow_mut().push(4));
+
+        assert_eq!(read_guard.len(), 3);
+        assert_eq!(nested_read_guard.len(), 3);
+        drop(read_guard);
+        drop(nested_read_guard);
+
+        writer.join().unwrap();
+        assert_eq!(cell.borrow().len(), 4);
+    }
+}
@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+
+use bumpalo::Bump;
+
+/// Memory, which the arena of a thread keeps between searches.
+/// Arena, which has grown larger during a search, is released instead of being reused
+pub const MAX_RETAINED_ARENA_BYTES: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    static SEARCH_ARENA: RefCell<Bump> = RefCell::new(Bump::new());
+}
+
+/// Run `f` with the bump arena of the current thread.
+/// Temporaries of a search, e.g. candidates and scores, are allocated in the arena instead of the global allocator.
+/// Nested calls share the arena, which is reset once the outermost call is finished
+pub fn with_search_arena<R>(f: impl FnOnce(&Bump) -> R) -> R {
+    SEARCH_ARENA.with(|arena| {
+        let result = f(&arena.borrow());
+        // Arena is still borrowed, if the call is nested into another one
+        if let Ok(mut arena) = arena.try_borrow_mut() {
+            if arena.allocated_bytes() > MAX_RETAINED_ARENA_BYTES {
+                *arena = Bump::new();
+            } else {
+                arena.reset();
+            }
+        }
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::collections::Vec as BumpVec;
+
+    use super::*;
+
+    #[test]
+    fn test_search_arena() {
+        let sum: usize = with_search_arena(|arena| {
+            let mut values = BumpVec::with_capacity_in(1000, arena);
+            values.extend(0..1000usize);
+            let nested = with_search_arena(|arena| BumpVec::from_iter_in(0..10usize, arena).len());
+            assert_eq!(nested, 10);
+            // Values of the outer call are not freed by the nested one
+            values.iter().sum()
+        });
+        assert_eq!(sum, 499500);
+
+        let allocated = with_search_arena(|arena| arena.allocated_bytes());
+        let reused = with_search_arena(|arena| {
+            BumpVec::from_iter_in(0..1000usize, arena);
+            arena.allocated_bytes()
+        });
+        assert_eq!(allocated, reused);
+    }
+}
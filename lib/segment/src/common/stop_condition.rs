@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::entry::entry_point::{OperationError, OperationResult};
+
+/// Number of items, processed by long loops between checks of the stop condition
+pub const STOP_CHECK_INTERVAL: usize = 1024;
+
+/// Deadline of a request and flag of its cancellation, shared by all operations of the request.
+/// Long-running operations check the condition periodically and stop early, once it is met.
+/// Default condition is never met
+#[derive(Debug, Clone, Default)]
+pub struct StopCondition {
+    is_stopped: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl StopCondition {
+    pub fn with_deadline(deadline: Instant) -> Self {
+        StopCondition { is_stopped: Default::default(), deadline: Some(deadline) }
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    /// Cancel all operations, which share the condition, e.g. if the client is gone
+    pub fn stop(&self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        if self.is_stopped.load(Ordering::Relaxed) {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /// Error, if the operation should not be continued
+    pub fn check(&self) -> OperationResult<()> {
+        if !self.is_stopped() {
+            return Ok(());
+        }
+        let description = match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => "deadline of the request is exceeded",
+            _ => "request is cancelled",
+        };
+        Err(OperationError::Cancelled { description: description.to_string() })
+    }
+
+    /// Items of the iterator until the condition is met.
+    /// The condition is checked every `STOP_CHECK_INTERVAL` items, so the check does not slow down tight loops.
+    /// Result of the truncated iteration is incomplete, so the caller should `check` the condition afterwards
+    pub fn take_until_stopped<'a, I: Iterator + 'a>(&'a self, iter: I) -> impl Iterator<Item=I::Item> + 'a {
+        iter.enumerate()
+            .take_while(move |(idx, _)| idx % STOP_CHECK_INTERVAL != 0 || !self.is_stopped())
+            .map(|(_, item)| item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_condition() {
+        let condition = StopCondition::default();
+        assert!(condition.check().is_ok());
+        assert_eq!(condition.take_until_stopped(0..5000).count(), 5000);
+
+        let shared = condition.clone();
+        shared.stop();
+        assert!(condition.check().is_err());
+        assert_eq!(condition.take_until_stopped(0..5000).count(), 0);
+
+        let expired = StopCondition::with_deadline(Instant::now());
+        assert!(matches!(expired.check(), Err(OperationError::Cancelled { .. })));
+        assert!(StopCondition::with_timeout(Duration::from_secs(60)).check().is_ok());
+    }
+}
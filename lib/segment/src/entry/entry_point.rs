@@ -1,10 +1,13 @@
 use thiserror::Error;
-use std::path::Path;
-use crate::types::{SeqNumberType, VectorElementType, Filter, PointIdType, PayloadKeyType, PayloadType, SearchParams, ScoredPoint, TheMap, SegmentInfo, SegmentConfig, SegmentType};
+use std::path::{Path, PathBuf};
+use crate::types::{SeqNumberType, VectorElementType, Filter, PointIdType, PayloadKeyType, PayloadType, SearchParams, ScoredPoint, TheMap, SegmentInfo, SegmentConfig, SegmentType, PayloadIndexInfo, PayloadSchemaType, WithPayload, BatchPoint};
+use std::collections::HashMap;
 use std::result;
 use std::io::Error as IoError;
 use atomicwrites::Error as AtomicIoError;
 use rocksdb::Error;
+use crate::telemetry::SegmentTelemetry;
+use crate::common::stop_condition::StopCondition;
 
 
 /// Trait for versionable & saveable objects.
@@ -26,8 +29,61 @@ pub enum OperationError {
     PointIdError { missed_point_id: PointIdType },
     #[error("Payload type does not match with previously given for field {field_name}. Expected: {expected_type}")]
     TypeError { field_name: PayloadKeyType, expected_type: String },
+    /// Parameters of the request are not applicable to the segment
+    #[error("Wrong input: {description}")]
+    WrongInput { description: String },
     #[error("Service runtime error: {description}")]
     ServiceError { description: String },
+    /// Not enough memory to complete the operation, e.g. to map storage file
+    #[error("Out of memory: {description}")]
+    OutOfMemory { description: String },
+    #[error("Operation cancelled: {description}")]
+    Cancelled { description: String },
+    /// Stored data can't be interpreted. Segment should be recovered or re-created
+    #[error("Data of {} is corrupted: {description}", .path.display())]
+    Corrupted { path: PathBuf, description: String },
+    /// Error, which occurred in the specific segment
+    #[error("Error in segment {}: {source}", .segment_path.display())]
+    SegmentError { segment_path: PathBuf, source: Box<OperationError> },
+    #[error("Segment is opened in read-only mode")]
+    ReadOnlyError,
+}
+
+impl OperationError {
+    pub fn service_error(description: &str) -> Self {
+        OperationError::ServiceError { description: description.to_owned() }
+    }
+
+    /// Attach path of the segment to the error, if it is not attached yet
+    pub fn with_segment_path(self, segment_path: &Path) -> Self {
+        match self {
+            OperationError::SegmentError { .. } => self,
+            _ => OperationError::SegmentError { segment_path: segment_path.to_owned(), source: Box::new(self) }
+        }
+    }
+
+    /// Error is caused by the request itself, not by the state of the service.
+    /// Repeating the same request will produce the same error.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            OperationError::WrongVector { .. }
+            | OperationError::PointIdError { .. }
+            | OperationError::TypeError { .. }
+            | OperationError::WrongInput { .. }
+            | OperationError::ReadOnlyError => true,
+            OperationError::SegmentError { source, .. } => source.is_user_error(),
+            _ => false
+        }
+    }
+
+    /// Error is caused by the inconsistent or damaged data on disk
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            OperationError::Corrupted { .. } => true,
+            OperationError::SegmentError { source, .. } => source.is_corruption(),
+            _ => false
+        }
+    }
 }
 
 impl<E> From<AtomicIoError<E>> for OperationError {
@@ -41,9 +97,15 @@ impl<E> From<AtomicIoError<E>> for OperationError {
     }
 }
 
+/// OS error code of failed memory allocation, e.g. on mapping of a file
+const ENOMEM: i32 = 12;
+
 impl From<IoError> for OperationError {
     fn from(err: IoError) -> Self {
-        OperationError::ServiceError { description: format!("{}", err) }
+        match err.raw_os_error() {
+            Some(ENOMEM) => OperationError::OutOfMemory { description: format!("{}", err) },
+            _ => OperationError::ServiceError { description: format!("{}", err) }
+        }
     }
 }
 
@@ -63,24 +125,82 @@ pub trait SegmentEntry {
     /// Get current update version of the segment
     fn version(&self) -> SeqNumberType;
 
+    /// Search for the closest points.
+    /// Requested payload and vectors are attached to the found points within the same segment access.
+    /// Returns `Cancelled` error, if `stop` condition is met before the search is finished.
     fn search(&self,
               vector: &Vec<VectorElementType>,
+              with_payload: &WithPayload,
+              with_vector: bool,
               filter: Option<&Filter>,
               top: usize,
               params: Option<&SearchParams>,
+              stop: &StopCondition,
     ) -> OperationResult<Vec<ScoredPoint>>;
 
-    fn upsert_point(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>) -> OperationResult<bool>;
-
-    fn delete_point(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool>;
-
-    fn set_full_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, full_payload: TheMap<PayloadKeyType, PayloadType>) -> OperationResult<bool>;
+    /// Count points, which satisfy filtering condition.
+    /// If `exact` is false, returns expected number of points from the payload index estimation,
+    /// which does not require to iterate over points.
+    fn count(&self, filter: Option<&Filter>, exact: bool, stop: &StopCondition) -> OperationResult<usize>;
 
-    fn set_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType, payload: PayloadType) -> OperationResult<bool>;
-
-    fn delete_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool>;
+    fn upsert_point(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>) -> OperationResult<bool>;
 
-    fn clear_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool>;
+    /// Insert or replace all given points at once.
+    /// Vectors, id mappings and payloads are written under a single lock of each storage instead of per-point calls.
+    /// Payload, if given, replaces the full payload of the point, otherwise the existing payload is preserved.
+    /// If the same id occurs several times, the last occurrence is used.
+    /// Returns number of applied points.
+    fn upsert_batch(&mut self, op_num: SeqNumberType, points: &[BatchPoint]) -> OperationResult<usize>;
+
+    /// Replace vector of the existing point. Payload and internal offset of the point are preserved, if possible.
+    /// Returns error if the point does not exist.
+    fn update_point_vector(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>) -> OperationResult<bool>;
+
+    /// Deletes and payload updates require only shared access to the segment,
+    /// so they could be applied concurrently with searches.
+    fn delete_point(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool>;
+
+    /// Delete all points, which satisfy filtering condition.
+    /// Returns number of deleted points.
+    fn delete_filtered(&self, op_num: SeqNumberType, filter: &Filter) -> OperationResult<usize>;
+
+    fn set_full_payload(&self, op_num: SeqNumberType, point_id: PointIdType, full_payload: TheMap<PayloadKeyType, PayloadType>) -> OperationResult<bool>;
+
+    fn set_payload(&self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType, payload: PayloadType) -> OperationResult<bool>;
+
+    fn delete_payload(&self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool>;
+
+    fn clear_payload(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool>;
+
+    /// Request renaming of payload `key` into `new_key` for all points of the segment.
+    /// If `convert_to` is specified, values are also converted into a new type.
+    /// Points are migrated in the background by `migrate_payload_batch`, payload is returned migrated immediately.
+    /// Filters and field index of the new key reflect the migration only once it is finished.
+    fn migrate_payload_key(&mut self,
+                           op_num: SeqNumberType,
+                           key: &PayloadKeyType,
+                           new_key: &PayloadKeyType,
+                           convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<bool>;
+
+    /// Check that the payload migration of the operation `op_num` could be applied to the segment:
+    /// `new_key` is not used yet, all values are convertible and the keys are not migrated by other operations.
+    /// Returns type of the migrated values or `None` if there are no values of the `key`
+    /// or the migration is already registered.
+    fn check_payload_migration(&self,
+                               op_num: SeqNumberType,
+                               key: &PayloadKeyType,
+                               new_key: &PayloadKeyType,
+                               convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>>;
+
+    /// Number of payload migrations, which are not applied to all points yet
+    fn pending_payload_migrations(&self) -> usize;
+
+    /// Apply the first pending payload migration to the next `limit` points.
+    /// Migration is finished, once all points are visited.
+    /// Returns number of visited points.
+    fn migrate_payload_batch(&self, limit: usize) -> OperationResult<usize>;
 
     fn vector(&self, point_id: PointIdType) -> OperationResult<Vec<VectorElementType>>;
 
@@ -88,9 +208,25 @@ pub trait SegmentEntry {
 
     fn iter_points(&self) -> Box<dyn Iterator<Item=PointIdType> + '_>;
 
+    /// Paginate over points, which satisfy filtering condition.
+    /// Returns up to `limit` ids in ascending order, starting from `offset` inclusive.
+    fn read_filtered(&self,
+                     offset: Option<PointIdType>,
+                     limit: usize,
+                     filter: Option<&Filter>,
+                     stop: &StopCondition,
+    ) -> OperationResult<Vec<PointIdType>>;
+
     /// Check if there is point with `point_id` in this segment.
     fn has_point(&self, point_id: PointIdType) -> bool;
 
+    /// Version of the last operation, applied to the point.
+    /// `None` if there is no such point in the segment.
+    fn point_version(&self, point_id: PointIdType) -> Option<SeqNumberType>;
+
+    /// Get statistics of operations, performed with the segment since it was loaded
+    fn get_telemetry(&self) -> SegmentTelemetry;
+
     /// Return number of vectors in this segment
     fn vectors_count(&self) -> usize;
 
@@ -113,6 +249,10 @@ pub trait SegmentEntry {
     /// Returns maximum version number which is guaranteed to be persisted.
     fn flush(&self) -> OperationResult<SeqNumberType>;
 
+    /// Save a copy of the segment data into a new sub-directory of `snapshot_dir`.
+    /// Copy might include updates in progress partially, so it should be loaded together with WAL.
+    fn take_snapshot(&self, snapshot_dir: &Path) -> OperationResult<()>;
+
     /// Removes all persisted data and forces to destroy segment
     fn drop_data(&mut self) -> OperationResult<()>;
 
@@ -124,5 +264,44 @@ pub trait SegmentEntry {
 
     /// Get indexed fields
     fn get_indexed_fields(&self) -> Vec<PayloadKeyType>;
+
+    /// Get statistics of payload field indexes: number of values, memory usage and distribution
+    fn payload_index_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>>;
+
+    /// Place vectors of the segment on the NUMA node, where it is searched.
+    /// Does nothing for segments, which data is not memory mapped
+    fn bind_to_numa_node(&self, _node: usize) -> OperationResult<()> {
+        Ok(())
+    }
+
+    /// Load memory mapped data of the segment into the page cache, so first searches do not wait for disk.
+    /// Payload, its indexes and id mapping are read into memory on open and need no warm-up.
+    /// Returns number of loaded bytes
+    fn warm_up(&self) -> OperationResult<usize> {
+        Ok(0)
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_context() {
+        let error = OperationError::PointIdError { missed_point_id: 10.into() }
+            .with_segment_path(Path::new("/segments/a"))
+            .with_segment_path(Path::new("/segments/b"));
+        assert!(error.is_user_error());
+        assert!(!error.is_corruption());
+        assert_eq!(format!("{}", error), "Error in segment /segments/a: No point with id 10 found");
+
+        let error = OperationError::Corrupted { path: PathBuf::from("/segments/a/segment.json"), description: "EOF".to_owned() }
+            .with_segment_path(Path::new("/segments/a"));
+        assert!(error.is_corruption());
+        assert!(!error.is_user_error());
+
+        let error = OperationError::from(IoError::from_raw_os_error(ENOMEM));
+        assert!(matches!(error, OperationError::OutOfMemory { .. }));
+    }
+}
@@ -1,5 +1,6 @@
-use crate::types::{PointIdType, PointOffsetType};
+use crate::types::{PointIdType, PointOffsetType, SeqNumberType};
 use crate::entry::entry_point::OperationResult;
+use std::path::Path;
 
 
 /// Trait for point ids mapper.
@@ -16,13 +17,33 @@ pub trait IdMapper {
     /// Set mapping
     fn set_link(&mut self, external_id: PointIdType, internal_id: PointOffsetType) -> OperationResult<()>;
 
-    /// Drop mapping
+    /// Drop mapping of the point.
+    /// Version is kept as a tombstone, so operations older than the deletion are not applied to the point again
     fn drop(&mut self, external_id: PointIdType) -> OperationResult<()>;
 
+    /// Version of the last operation, applied to the point
+    fn point_version(&self, external_id: PointIdType) -> Option<SeqNumberType>;
+
+    /// Store version of the last operation, applied to the point
+    fn set_point_version(&mut self, external_id: PointIdType, version: SeqNumberType) -> OperationResult<()>;
+
+    /// Estimated memory, occupied by the mapping and versions
+    fn memory_usage_bytes(&self) -> usize;
+
+    /// Iterate over versions of all known points, including deleted ones
+    fn iter_versions(&self) -> Box<dyn Iterator<Item=(PointIdType, SeqNumberType)> + '_>;
+
     /// Iterate over all external ids
     fn iter_external(&self) -> Box<dyn Iterator<Item=PointIdType> + '_>;
 
+    /// Iterate over (external, internal) id pairs in order of external ids,
+    /// starting from `external_id` inclusive, if specified
+    fn iter_from(&self, external_id: Option<PointIdType>) -> Box<dyn Iterator<Item=(PointIdType, PointOffsetType)> + '_>;
+
     /// Force persistence of current mapper state.
     fn flush(&self) -> OperationResult<()>;
 
+    /// Write a copy of the persisted mapper into a new directory, sharing immutable files with the original
+    fn fork(&self, path: &Path) -> OperationResult<()>;
+
 }
@@ -1,41 +1,83 @@
-use std::collections::HashMap;
-use crate::types::{PointOffsetType, PointIdType};
+use std::collections::{HashMap, BTreeMap};
+use std::mem::size_of;
+use crate::types::{PointOffsetType, PointIdType, SeqNumberType, ExtendedPointId};
 use crate::id_mapper::id_mapper::IdMapper;
 use crate::entry::entry_point::OperationResult;
 use bincode;
 use std::path::Path;
 use rocksdb::{Options, DB, IteratorMode};
+use crate::common::rocksdb_operations::{open_db, checkpoint_db};
+use uuid::Uuid;
 
 /// Since sled is used for reading only during the initialization, large read cache is not required
 const DB_CACHE_SIZE: usize = 10 * 1024 * 1024; // 10 mb
+/// Column family for versions of the last operations applied to each point
+const VERSIONS_CF: &'static str = "versions";
+
+/// Numeric ids are stored in the same format as before UUID ids were introduced,
+/// so key length is enough to distinguish id types
+fn stored_key(external_id: PointIdType) -> Vec<u8> {
+    match external_id {
+        ExtendedPointId::NumId(id) => bincode::serialize(&id).unwrap(),
+        ExtendedPointId::Uuid(uuid) => uuid.as_bytes().to_vec(),
+    }
+}
+
+fn parse_stored_key(key: &[u8]) -> PointIdType {
+    if key.len() == 16 {
+        ExtendedPointId::Uuid(Uuid::from_slice(key).unwrap())
+    } else {
+        ExtendedPointId::NumId(bincode::deserialize(key).unwrap())
+    }
+}
 
 pub struct SimpleIdMapper {
     internal_to_external: HashMap<PointOffsetType, PointIdType>,
-    external_to_internal: HashMap<PointIdType, PointOffsetType>,
+    external_to_internal: BTreeMap<PointIdType, PointOffsetType>,
+    versions: HashMap<PointIdType, SeqNumberType>,
     store: DB,
 }
 
 impl SimpleIdMapper {
 
     pub fn open(path: &Path) -> OperationResult<Self> {
+        Self::open_with_mode(path, false)
+    }
+
+    /// Open existing mapper without ability to modify it
+    pub fn open_read_only(path: &Path) -> OperationResult<Self> {
+        Self::open_with_mode(path, true)
+    }
+
+    fn open_with_mode(path: &Path, read_only: bool) -> OperationResult<Self> {
         let mut options: Options = Options::default();
         options.set_write_buffer_size(DB_CACHE_SIZE);
         options.create_if_missing(true);
-        let store = DB::open(&options, path)?;
+        options.create_missing_column_families(true);
+        let store = open_db(&options, path, &[VERSIONS_CF], read_only)?;
 
         let mut internal_to_external: HashMap<PointOffsetType, PointIdType> = Default::default();
-        let mut external_to_internal: HashMap<PointIdType, PointOffsetType> = Default::default();
+        let mut external_to_internal: BTreeMap<PointIdType, PointOffsetType> = Default::default();
 
         for (key, val) in store.iterator(IteratorMode::Start) {
-            let external_id: PointIdType = bincode::deserialize(&key).unwrap();
+            let external_id = parse_stored_key(&key);
             let internal_id: PointOffsetType = bincode::deserialize(&val).unwrap();
             internal_to_external.insert(internal_id, external_id);
             external_to_internal.insert(external_id, internal_id);
         }
 
+        let mut versions: HashMap<PointIdType, SeqNumberType> = Default::default();
+        let versions_cf = store.cf_handle(VERSIONS_CF).unwrap();
+        for (key, val) in store.iterator_cf(versions_cf, IteratorMode::Start) {
+            let external_id = parse_stored_key(&key);
+            let version: SeqNumberType = bincode::deserialize(&val).unwrap();
+            versions.insert(external_id, version);
+        }
+
         Ok(SimpleIdMapper {
             internal_to_external,
             external_to_internal,
+            versions,
             store,
         })
     }
@@ -43,6 +85,12 @@ impl SimpleIdMapper {
 
 
 impl IdMapper for SimpleIdMapper {
+    fn memory_usage_bytes(&self) -> usize {
+        let link_size = size_of::<PointIdType>() + size_of::<PointOffsetType>();
+        (self.internal_to_external.len() + self.external_to_internal.len()) * link_size
+            + self.versions.len() * (size_of::<PointIdType>() + size_of::<SeqNumberType>())
+    }
+
     fn internal_id(&self, external_id: PointIdType) -> Option<PointOffsetType> {
         self.external_to_internal.get(&external_id).cloned()
     }
@@ -56,7 +104,7 @@ impl IdMapper for SimpleIdMapper {
         self.internal_to_external.insert(internal_id, external_id);
 
         self.store.put(
-            bincode::serialize(&external_id).unwrap(),
+            stored_key(external_id),
             bincode::serialize(&internal_id).unwrap())?;
         Ok(())
     }
@@ -67,16 +115,49 @@ impl IdMapper for SimpleIdMapper {
             Some(x) => self.internal_to_external.remove(&x),
             None => None
         };
-        self.store.delete(bincode::serialize(&external_id).unwrap())?;
+        self.store.delete(stored_key(external_id))?;
         Ok(())
     }
 
+    fn point_version(&self, external_id: PointIdType) -> Option<SeqNumberType> {
+        self.versions.get(&external_id).cloned()
+    }
+
+    fn set_point_version(&mut self, external_id: PointIdType, version: SeqNumberType) -> OperationResult<()> {
+        if self.versions.insert(external_id, version) != Some(version) {
+            let versions_cf = self.store.cf_handle(VERSIONS_CF).unwrap();
+            self.store.put_cf(
+                versions_cf,
+                stored_key(external_id),
+                bincode::serialize(&version).unwrap())?;
+        }
+        Ok(())
+    }
+
+    fn iter_versions(&self) -> Box<dyn Iterator<Item=(PointIdType, SeqNumberType)> + '_> {
+        Box::new(self.versions.iter().map(|(external_id, version)| (*external_id, *version)))
+    }
+
     fn iter_external(&self) -> Box<dyn Iterator<Item=PointIdType> + '_> {
         Box::new(self.external_to_internal.keys().cloned())
     }
 
+    fn iter_from(&self, external_id: Option<PointIdType>) -> Box<dyn Iterator<Item=(PointIdType, PointOffsetType)> + '_> {
+        let range = match external_id {
+            None => self.external_to_internal.range(..),
+            Some(offset) => self.external_to_internal.range(offset..)
+        };
+        Box::new(range.map(|(external_id, internal_id)| (*external_id, *internal_id)))
+    }
+
     fn flush(&self) -> OperationResult<()> {
-        Ok(self.store.flush()?)
+        self.store.flush()?;
+        let versions_cf = self.store.cf_handle(VERSIONS_CF).unwrap();
+        Ok(self.store.flush_cf(versions_cf)?)
+    }
+
+    fn fork(&self, path: &Path) -> OperationResult<()> {
+        checkpoint_db(&self.store, path)
     }
 }
 
@@ -2,13 +2,18 @@ use serde::{Deserialize, Serialize};
 use crate::index::field_index::CardinalityEstimation;
 use crate::index::field_index::map_index::PersistedMapIndex;
 use crate::index::field_index::numeric_index::PersistedNumericIndex;
-use crate::types::{FieldCondition, FloatPayloadType, IntPayloadType, PayloadType, PointOffsetType};
+#[cfg(feature = "full_text")]
+use crate::index::field_index::full_text_index::FullTextIndex;
+use crate::types::{FieldCondition, FloatPayloadType, IntPayloadType, PayloadType, PointOffsetType, PayloadIndexInfo};
 
 pub trait PayloadFieldIndex {
     /// Get iterator over points fitting given `condition`
     fn filter(&self, condition: &FieldCondition) -> Option<Box<dyn Iterator<Item=PointOffsetType> + '_>>;
 
     fn estimate_cardinality(&self, condition: &FieldCondition) -> Option<CardinalityEstimation>;
+
+    /// Get statistics of the index
+    fn info(&self) -> PayloadIndexInfo;
 }
 
 pub trait PayloadFieldIndexBuilder {
@@ -24,6 +29,8 @@ pub enum FieldIndex {
     IntMapIndex(PersistedMapIndex<IntPayloadType>),
     KeywordIndex(PersistedMapIndex<String>),
     FloatIndex(PersistedNumericIndex<FloatPayloadType>),
+    #[cfg(feature = "full_text")]
+    FullTextIndex(FullTextIndex),
 }
 
 impl FieldIndex {
@@ -33,6 +40,8 @@ impl FieldIndex {
             FieldIndex::IntMapIndex(payload_field_index) => payload_field_index,
             FieldIndex::KeywordIndex(payload_field_index) => payload_field_index,
             FieldIndex::FloatIndex(payload_field_index) => payload_field_index,
+            #[cfg(feature = "full_text")]
+            FieldIndex::FullTextIndex(payload_field_index) => payload_field_index,
         }
     }
 }
@@ -46,4 +55,8 @@ impl PayloadFieldIndex for FieldIndex {
     fn estimate_cardinality(&self, condition: &FieldCondition) -> Option<CardinalityEstimation> {
         self.get_payload_field_index().estimate_cardinality(condition)
     }
+
+    fn info(&self) -> PayloadIndexInfo {
+        self.get_payload_field_index().info()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::{iter, mem};
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::field_index::{CardinalityEstimation, PrimaryCondition};
+use crate::index::field_index::field_index::{FieldIndex, PayloadFieldIndex, PayloadFieldIndexBuilder};
+use crate::index::field_index::text_analyzer::TextAnalyzer;
+use crate::types::{FieldCondition, PayloadType, PointOffsetType, TextAnalyzerConfig, PayloadIndexInfo, FieldIndexType};
+
+/// Inverted index of text tokens. Used for full-text match of keyword payload.
+#[derive(Serialize, Deserialize)]
+pub struct FullTextIndex {
+    /// Analyzer config, used to build index. Query text should be parsed with the same rules
+    config: TextAnalyzerConfig,
+    /// Sorted ids of points for each token
+    postings: HashMap<String, Vec<PointOffsetType>>,
+    points_count: usize,
+}
+
+impl FullTextIndex {
+    pub fn new(config: TextAnalyzerConfig) -> Self {
+        FullTextIndex {
+            config,
+            postings: Default::default(),
+            points_count: 0,
+        }
+    }
+
+    fn query_postings(&self, text: &str) -> Vec<&Vec<PointOffsetType>> {
+        let analyzer = TextAnalyzer::new(&self.config);
+        let mut postings = vec![];
+        for token in analyzer.tokenize(text) {
+            match self.postings.get(&token) {
+                None => return vec![], // Nothing could match
+                Some(posting) => postings.push(posting)
+            }
+        }
+        postings.sort_by_key(|posting| posting.len());
+        postings
+    }
+
+    fn text_cardinality(&self, text: &str) -> CardinalityEstimation {
+        let postings = self.query_postings(text);
+        let max = postings.first().map(|posting| posting.len()).unwrap_or(0);
+        if postings.len() <= 1 {
+            return CardinalityEstimation::exact(max);
+        }
+        // Assume tokens are independent
+        let exp = postings.iter()
+            .fold(self.points_count as f64, |acc, posting| acc * posting.len() as f64 / self.points_count as f64);
+        CardinalityEstimation {
+            primary_clauses: vec![],
+            min: 0,
+            exp: exp as usize,
+            max,
+        }
+    }
+
+    fn text_iterator(&self, text: &str) -> Box<dyn Iterator<Item=PointOffsetType> + '_> {
+        let postings = self.query_postings(text);
+        match postings.split_first() {
+            None => Box::new(iter::empty()),
+            Some((smallest, others)) => {
+                let others = others.to_vec();
+                Box::new(smallest.iter().cloned().filter(move |id| {
+                    others.iter().all(|posting| posting.binary_search(id).is_ok())
+                }))
+            }
+        }
+    }
+}
+
+impl PayloadFieldIndex for FullTextIndex {
+    fn filter(&self, condition: &FieldCondition) -> Option<Box<dyn Iterator<Item=PointOffsetType> + '_>> {
+        condition.r#match.as_ref().and_then(|match_condition|
+            match_condition.text.as_ref().map(|text| self.text_iterator(text))
+        )
+    }
+
+    fn estimate_cardinality(&self, condition: &FieldCondition) -> Option<CardinalityEstimation> {
+        condition.r#match.as_ref().and_then(|match_condition|
+            match_condition.text
+                .as_ref()
+                .map(|text| {
+                    let mut estimation = self.text_cardinality(text);
+                    estimation.primary_clauses.push(PrimaryCondition::Condition(condition.clone()));
+                    estimation
+                })
+        )
+    }
+
+    fn info(&self) -> PayloadIndexInfo {
+        let values_count = self.postings.values().map(|posting| posting.len()).sum();
+        let memory_usage_bytes = self.postings.iter()
+            .map(|(token, posting)| mem::size_of::<String>() + token.capacity()
+                + mem::size_of::<Vec<PointOffsetType>>()
+                + posting.capacity() * mem::size_of::<PointOffsetType>())
+            .sum();
+        PayloadIndexInfo {
+            index_type: FieldIndexType::FullText,
+            points_count: self.points_count,
+            values_count,
+            distinct_values: self.postings.len(),
+            memory_usage_bytes,
+            histogram: None,
+        }
+    }
+}
+
+impl PayloadFieldIndexBuilder for FullTextIndex {
+    fn add(&mut self, id: PointOffsetType, value: &PayloadType) {
+        match value {
+            PayloadType::Keyword(texts) => {
+                let analyzer = TextAnalyzer::new(&self.config);
+                for token in analyzer.tokenize_all(texts.iter()) {
+                    self.postings.entry(token).or_insert_with(Vec::new).push(id);
+                }
+                self.points_count += 1;
+            }
+            _ => panic!("Unexpected payload type: {:?}", value)
+        }
+    }
+
+    fn build(&mut self) -> FieldIndex {
+        let mut postings = mem::replace(&mut self.postings, Default::default());
+        for posting in postings.values_mut() {
+            posting.sort_unstable();
+        }
+        FieldIndex::FullTextIndex(FullTextIndex {
+            config: self.config.clone(),
+            postings,
+            points_count: self.points_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Match, StemmerLanguage};
+    use itertools::Itertools;
+
+    fn text_condition(text: &str) -> FieldCondition {
+        FieldCondition {
+            key: "description".to_string(),
+            r#match: Some(Match { keyword: None, integer: None, text: Some(text.to_owned()) }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        }
+    }
+
+    #[test]
+    fn test_full_text_filter() {
+        let mut builder = FullTextIndex::new(TextAnalyzerConfig {
+            stemmer: Some(StemmerLanguage::English),
+            stopwords: vec!["the".to_owned()],
+            ..Default::default()
+        });
+        builder.add(3, &PayloadType::Keyword(vec!["The cats are sleeping".to_owned()]));
+        builder.add(1, &PayloadType::Keyword(vec!["A cat".to_owned(), "sleeps on a sofa".to_owned()]));
+        builder.add(2, &PayloadType::Keyword(vec!["Dogs are barking".to_owned()]));
+        let index = builder.build();
+
+        let matched = index.filter(&text_condition("sleeping Cat")).unwrap().collect_vec();
+        assert_eq!(matched, vec![1, 3]);
+
+        let matched = index.filter(&text_condition("sleeping dog")).unwrap().collect_vec();
+        assert!(matched.is_empty());
+
+        let estimation = index.estimate_cardinality(&text_condition("cat")).unwrap();
+        assert_eq!(estimation.exp, 2);
+        assert_eq!(estimation.primary_clauses.len(), 1);
+
+        assert_eq!(index.info().distinct_values, 8);
+    }
+}
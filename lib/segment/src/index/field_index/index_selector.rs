@@ -1,11 +1,19 @@
-use crate::types::{PayloadSchemaType, IntPayloadType, FloatPayloadType};
+use crate::types::{PayloadSchemaType, IntPayloadType, FloatPayloadType, TextAnalyzerConfig};
 use crate::index::field_index::field_index::PayloadFieldIndexBuilder;
 use crate::index::field_index::map_index::PersistedMapIndex;
 use crate::index::field_index::numeric_index::PersistedNumericIndex;
+#[cfg(feature = "full_text")]
+use crate::index::field_index::full_text_index::FullTextIndex;
 
-pub fn index_selector(payload_type: &PayloadSchemaType) -> Vec<Box<dyn PayloadFieldIndexBuilder>> {
+/// Select index builders for the field.
+/// `text_analyzer` - if specified, keyword field is additionally indexed for full-text match
+pub fn index_selector(payload_type: &PayloadSchemaType, text_analyzer: Option<&TextAnalyzerConfig>) -> Vec<Box<dyn PayloadFieldIndexBuilder>> {
     match payload_type {
-        PayloadSchemaType::Keyword => vec![Box::new(PersistedMapIndex::<String>::new())],
+        PayloadSchemaType::Keyword => {
+            let mut builders: Vec<Box<dyn PayloadFieldIndexBuilder>> = vec![Box::new(PersistedMapIndex::<String>::new())];
+            builders.extend(full_text_index(text_analyzer));
+            builders
+        }
         PayloadSchemaType::Integer => vec![
             Box::new(PersistedMapIndex::<IntPayloadType>::new()),
             Box::new(PersistedNumericIndex::<IntPayloadType>::new())
@@ -15,4 +23,15 @@ pub fn index_selector(payload_type: &PayloadSchemaType) -> Vec<Box<dyn PayloadFi
         ],
         PayloadSchemaType::Geo => vec![]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "full_text")]
+fn full_text_index(text_analyzer: Option<&TextAnalyzerConfig>) -> Option<Box<dyn PayloadFieldIndexBuilder>> {
+    text_analyzer.map(|config| Box::new(FullTextIndex::new(config.clone())) as Box<dyn PayloadFieldIndexBuilder>)
+}
+
+/// Without `full_text` feature, text conditions are checked against the payload of each point
+#[cfg(not(feature = "full_text"))]
+fn full_text_index(_text_analyzer: Option<&TextAnalyzerConfig>) -> Option<Box<dyn PayloadFieldIndexBuilder>> {
+    None
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::{mem, iter};
 
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::index::field_index::{CardinalityEstimation, PrimaryCondition};
 use crate::index::field_index::field_index::{FieldIndex, PayloadFieldIndex, PayloadFieldIndexBuilder};
-use crate::types::{IntPayloadType, PayloadType, PointOffsetType, FieldCondition};
+use crate::types::{IntPayloadType, PayloadType, PointOffsetType, FieldCondition, PayloadIndexInfo, FieldIndexType};
 
 #[derive(Serialize, Deserialize)]
 pub struct PersistedMapIndex<N: Hash + Eq + Clone> {
@@ -49,6 +49,30 @@ impl<N: Hash + Eq + Clone> PersistedMapIndex<N> {
         }
     }
 
+    /// Collect index statistics. `value_size` - amount of heap memory occupied by value
+    fn get_info<F>(&self, index_type: FieldIndexType, value_size: F) -> PayloadIndexInfo
+        where F: Fn(&N) -> usize {
+        let mut points: HashSet<PointOffsetType> = HashSet::new();
+        let mut values_count = 0;
+        let mut memory_usage_bytes = 0;
+        for (value, ids) in self.map.iter() {
+            points.extend(ids.iter().cloned());
+            values_count += ids.len();
+            memory_usage_bytes += mem::size_of::<N>() + value_size(value)
+                + mem::size_of::<Vec<PointOffsetType>>()
+                + ids.capacity() * mem::size_of::<PointOffsetType>();
+        }
+
+        PayloadIndexInfo {
+            index_type,
+            points_count: points.len(),
+            values_count,
+            distinct_values: self.map.len(),
+            memory_usage_bytes,
+            histogram: None,
+        }
+    }
+
     fn get_iterator(&self, value: &N) -> Box<dyn Iterator<Item=PointOffsetType> + '_> {
         self.map
             .get(value)
@@ -75,6 +99,10 @@ impl PayloadFieldIndex for PersistedMapIndex<String> {
                 })
         )
     }
+
+    fn info(&self) -> PayloadIndexInfo {
+        self.get_info(FieldIndexType::Keyword, |keyword| keyword.capacity())
+    }
 }
 
 impl PayloadFieldIndex for PersistedMapIndex<IntPayloadType> {
@@ -94,6 +122,10 @@ impl PayloadFieldIndex for PersistedMapIndex<IntPayloadType> {
                     estimation
                 }))
     }
+
+    fn info(&self) -> PayloadIndexInfo {
+        self.get_info(FieldIndexType::IntMap, |_| 0)
+    }
 }
 
 impl PayloadFieldIndexBuilder for PersistedMapIndex<String> {
@@ -129,3 +161,27 @@ impl PayloadFieldIndexBuilder for PersistedMapIndex<IntPayloadType> {
         })
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_info() {
+        let mut builder = PersistedMapIndex::<String>::new();
+        builder.add(0, &PayloadType::Keyword(vec!["red".to_owned(), "green".to_owned()]));
+        builder.add(1, &PayloadType::Keyword(vec!["red".to_owned()]));
+        builder.add(2, &PayloadType::Keyword(vec!["blue".to_owned()]));
+
+        let index = builder.build();
+        let info = index.info();
+
+        assert_eq!(info.index_type, FieldIndexType::Keyword);
+        assert_eq!(info.points_count, 3);
+        assert_eq!(info.values_count, 4);
+        assert_eq!(info.distinct_values, 3);
+        assert!(info.memory_usage_bytes > 0);
+        assert!(info.histogram.is_none());
+    }
+}
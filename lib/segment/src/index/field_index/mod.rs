@@ -1,13 +1,22 @@
 use crate::types::{FieldCondition, PointOffsetType};
 use std::collections::HashSet;
 
+#[cfg(feature = "payload_index")]
 pub mod numeric_index;
+#[cfg(feature = "geo")]
 pub mod geo_index;
+#[cfg(feature = "payload_index")]
 pub mod map_index;
+#[cfg(feature = "payload_index")]
 pub mod field_index;
+#[cfg(feature = "payload_index")]
 pub mod index_selector;
+pub mod text_analyzer;
+#[cfg(feature = "full_text")]
+pub mod full_text_index;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "payload_index"), allow(dead_code))]
 pub enum PrimaryCondition {
     Condition(FieldCondition),
     Ids(HashSet<PointOffsetType>),
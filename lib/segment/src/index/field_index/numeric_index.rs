@@ -2,13 +2,17 @@ use std::cmp::{max, min};
 use std::cmp::Ordering::{Greater, Less};
 use std::mem;
 
+use itertools::Itertools;
 use num_traits::ToPrimitive;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
 use crate::index::field_index::{CardinalityEstimation, PrimaryCondition};
 use crate::index::field_index::field_index::{FieldIndex, PayloadFieldIndex, PayloadFieldIndexBuilder};
-use crate::types::{FloatPayloadType, IntPayloadType, PayloadType, PointOffsetType, Range, FieldCondition};
+use crate::types::{FloatPayloadType, IntPayloadType, PayloadType, PointOffsetType, Range, FieldCondition, PayloadIndexInfo, FieldIndexType, HistogramBucket};
+
+/// Number of equal-width buckets in index histogram
+pub const HISTOGRAM_BUCKETS: usize = 10;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Element<N> {
@@ -102,6 +106,49 @@ impl<N: ToPrimitive + Clone> PersistedNumericIndex<N> {
         self.points_count += 1
     }
 
+    /// Split range of indexed values into equal-width buckets and count values in each.
+    /// Requires elements to be sorted, which is guaranteed after build.
+    fn histogram(&self) -> Vec<HistogramBucket> {
+        let (first, last) = match (self.elements.first(), self.elements.last()) {
+            (Some(first), Some(last)) => (first.value.to_f64().unwrap(), last.value.to_f64().unwrap()),
+            _ => return vec![]
+        };
+        let width = (last - first) / HISTOGRAM_BUCKETS as f64;
+        if width <= 0. {
+            return vec![HistogramBucket { from: first, to: last, count: self.elements.len() }];
+        }
+
+        let mut buckets = (0..HISTOGRAM_BUCKETS)
+            .map(|i| HistogramBucket {
+                from: first + width * i as f64,
+                to: first + width * (i + 1) as f64,
+                count: 0,
+            })
+            .collect_vec();
+        for element in self.elements.iter() {
+            let bucket_idx = ((element.value.to_f64().unwrap() - first) / width) as usize;
+            // Largest value belongs to the last bucket
+            buckets[min(bucket_idx, HISTOGRAM_BUCKETS - 1)].count += 1;
+        }
+        buckets
+    }
+
+    fn get_info(&self, index_type: FieldIndexType) -> PayloadIndexInfo {
+        let distinct_values = self.elements.iter()
+            .map(|element| element.value.to_f64().unwrap())
+            .dedup()
+            .count();
+
+        PayloadIndexInfo {
+            index_type,
+            points_count: self.points_count,
+            values_count: self.elements.len(),
+            distinct_values,
+            memory_usage_bytes: self.elements.capacity() * mem::size_of::<Element<N>>(),
+            histogram: Some(self.histogram()),
+        }
+    }
+
     fn condition_iter(&self, range: &Range) -> Box<dyn Iterator<Item=PointOffsetType> + '_> {
         let (lower_index, upper_index) = self.search_range(range);
         Box::new((&self.elements[lower_index..upper_index]).iter().map(|element| element.id))
@@ -109,7 +156,20 @@ impl<N: ToPrimitive + Clone> PersistedNumericIndex<N> {
 }
 
 
-impl<N: ToPrimitive + Clone> PayloadFieldIndex for PersistedNumericIndex<N> {
+/// Numeric types, which could be stored in numeric index
+pub trait NumericIndexType {
+    fn index_type() -> FieldIndexType;
+}
+
+impl NumericIndexType for IntPayloadType {
+    fn index_type() -> FieldIndexType { FieldIndexType::Int }
+}
+
+impl NumericIndexType for FloatPayloadType {
+    fn index_type() -> FieldIndexType { FieldIndexType::Float }
+}
+
+impl<N: ToPrimitive + Clone + NumericIndexType> PayloadFieldIndex for PersistedNumericIndex<N> {
     fn filter(&self, condition: &FieldCondition) -> Option<Box<dyn Iterator<Item=PointOffsetType> + '_>> {
         condition.range
             .as_ref()
@@ -125,6 +185,10 @@ impl<N: ToPrimitive + Clone> PayloadFieldIndex for PersistedNumericIndex<N> {
                 cardinality
             })
     }
+
+    fn info(&self) -> PayloadIndexInfo {
+        self.get_info(N::index_type())
+    }
 }
 
 impl PayloadFieldIndexBuilder for PersistedNumericIndex<FloatPayloadType> {
@@ -255,4 +319,30 @@ mod tests {
         let json = serde_json::to_string_pretty(&index).unwrap();
         println!("{}", json)
     }
+
+    #[test]
+    fn test_index_info() {
+        let index = PersistedNumericIndex {
+            points_count: 5,
+            elements: vec![
+                Element { id: 1, value: 0 },
+                Element { id: 2, value: 5 },
+                Element { id: 3, value: 5 },
+                Element { id: 4, value: 9 },
+                Element { id: 5, value: 100 },
+            ],
+        };
+
+        let info = index.info();
+        assert_eq!(info.index_type, FieldIndexType::Int);
+        assert_eq!(info.points_count, 5);
+        assert_eq!(info.values_count, 5);
+        assert_eq!(info.distinct_values, 4);
+
+        let histogram = info.histogram.unwrap();
+        assert_eq!(histogram.len(), HISTOGRAM_BUCKETS);
+        assert_eq!(histogram[0].count, 4);
+        assert_eq!(histogram[HISTOGRAM_BUCKETS - 1].count, 1);
+        assert_eq!(histogram.iter().map(|bucket| bucket.count).sum::<usize>(), 5);
+    }
 }
\ No newline at end of file
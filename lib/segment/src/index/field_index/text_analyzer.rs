@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+
+#[cfg(feature = "full_text")]
+use rust_stemmers::{Algorithm, Stemmer};
+
+#[cfg(feature = "full_text")]
+use crate::types::StemmerLanguage;
+use crate::types::TextAnalyzerConfig;
+
+#[cfg(feature = "full_text")]
+fn stemmer_algorithm(language: &StemmerLanguage) -> Algorithm {
+    match language {
+        StemmerLanguage::Arabic => Algorithm::Arabic,
+        StemmerLanguage::Danish => Algorithm::Danish,
+        StemmerLanguage::Dutch => Algorithm::Dutch,
+        StemmerLanguage::English => Algorithm::English,
+        StemmerLanguage::Finnish => Algorithm::Finnish,
+        StemmerLanguage::French => Algorithm::French,
+        StemmerLanguage::German => Algorithm::German,
+        StemmerLanguage::Greek => Algorithm::Greek,
+        StemmerLanguage::Hungarian => Algorithm::Hungarian,
+        StemmerLanguage::Italian => Algorithm::Italian,
+        StemmerLanguage::Norwegian => Algorithm::Norwegian,
+        StemmerLanguage::Portuguese => Algorithm::Portuguese,
+        StemmerLanguage::Romanian => Algorithm::Romanian,
+        StemmerLanguage::Russian => Algorithm::Russian,
+        StemmerLanguage::Spanish => Algorithm::Spanish,
+        StemmerLanguage::Swedish => Algorithm::Swedish,
+        StemmerLanguage::Tamil => Algorithm::Tamil,
+        StemmerLanguage::Turkish => Algorithm::Turkish,
+    }
+}
+
+/// Splits text into tokens according to `TextAnalyzerConfig`.
+/// Should be used both for indexing and for query parsing, so the tokens are comparable.
+pub struct TextAnalyzer {
+    config: TextAnalyzerConfig,
+    /// Stemming requires `full_text` feature, configs with stemmer are rejected without it
+    #[cfg(feature = "full_text")]
+    stemmer: Option<Stemmer>,
+    stopwords: HashSet<String>,
+}
+
+impl TextAnalyzer {
+    pub fn new(config: &TextAnalyzerConfig) -> Self {
+        let stopwords = config.stopwords.iter()
+            .map(|word| if config.lowercase { word.to_lowercase() } else { word.clone() })
+            .collect();
+        TextAnalyzer {
+            config: config.clone(),
+            #[cfg(feature = "full_text")]
+            stemmer: config.stemmer.as_ref().map(|language| Stemmer::create(stemmer_algorithm(language))),
+            stopwords,
+        }
+    }
+
+    fn is_len_allowed(&self, token: &str) -> bool {
+        let len = token.chars().count();
+        self.config.min_token_len.map_or(true, |min_len| len >= min_len)
+            && self.config.max_token_len.map_or(true, |max_len| len <= max_len)
+    }
+
+    #[cfg(feature = "full_text")]
+    fn stem(&self, token: String) -> String {
+        match &self.stemmer {
+            None => token,
+            Some(stemmer) => stemmer.stem(&token).into_owned()
+        }
+    }
+
+    #[cfg(not(feature = "full_text"))]
+    fn stem(&self, token: String) -> String {
+        token
+    }
+
+    /// Split text into a set of unique tokens
+    pub fn tokenize(&self, text: &str) -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .map(|token| if self.config.lowercase { token.to_lowercase() } else { token.to_owned() })
+            .filter(|token| self.is_len_allowed(token))
+            .filter(|token| !self.stopwords.contains(token))
+            .map(|token| self.stem(token))
+            .collect()
+    }
+
+    /// Collect tokens of all given values
+    pub fn tokenize_all<'a>(&self, texts: impl Iterator<Item=&'a String>) -> HashSet<String> {
+        texts.flat_map(|text| self.tokenize(text)).collect()
+    }
+}
+
+impl Default for TextAnalyzer {
+    fn default() -> Self {
+        TextAnalyzer::new(&TextAnalyzerConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "full_text")]
+    fn test_tokenize() {
+        let analyzer = TextAnalyzer::new(&TextAnalyzerConfig {
+            lowercase: true,
+            min_token_len: Some(2),
+            max_token_len: Some(10),
+            stemmer: Some(StemmerLanguage::English),
+            stopwords: vec!["The".to_owned()],
+        });
+
+        let tokens = analyzer.tokenize("The quick, brown foxes jumped over a lazy dog: incomprehensibilities");
+        let expected: HashSet<String> = vec!["quick", "brown", "fox", "jump", "over", "lazi", "dog"]
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_default_analyzer() {
+        let analyzer = TextAnalyzer::default();
+        let tokens = analyzer.tokenize("Hello, World! hello");
+        assert_eq!(tokens.len(), 2);
+        assert!(tokens.contains("hello"));
+        assert!(tokens.contains("world"));
+    }
+}
@@ -1,21 +1,26 @@
-use crate::types::{Filter, PointOffsetType, VectorElementType, SearchParams, PayloadKeyType};
+use crate::types::{Filter, PointOffsetType, VectorElementType, SearchParams, PayloadKeyType, PayloadIndexInfo, PayloadMigration, PayloadType};
 use crate::vector_storage::vector_storage::ScoredPointOffset;
 use crate::entry::entry_point::OperationResult;
 use crate::index::field_index::CardinalityEstimation;
+use crate::common::stop_condition::StopCondition;
+use std::collections::HashMap;
 
 /// Trait for vector searching
 pub trait Index {
-    /// Return list of Ids with fitting
+    /// Return list of Ids with fitting.
+    /// Search stops early once `stop` condition is met, so the result might be incomplete
     fn search(&self,
               vector: &Vec<VectorElementType>,
               filter: Option<&Filter>,
               top: usize,
               params: Option<&SearchParams>,
+              stop: &StopCondition,
     ) -> Vec<ScoredPointOffset>;
 
 
     /// Force internal index rebuild.
-    fn build_index(&mut self) -> OperationResult<()>;
+    /// Building is interrupted with `Cancelled` error once `stop` condition is met
+    fn build_index(&mut self, stop: &StopCondition) -> OperationResult<()>;
 }
 
 pub trait PayloadIndex {
@@ -33,4 +38,22 @@ pub trait PayloadIndex {
 
     /// Return list of all point ids, which satisfy filtering criteria
     fn query_points(&self, query: &Filter) -> Box<dyn Iterator<Item=PointOffsetType> + '_>;
+
+    /// Get statistics of all built field indexes
+    fn indexes_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>>;
+
+    /// Return fields, which indexes do not agree with the current state of payload storage
+    fn inconsistent_fields(&self) -> OperationResult<Vec<PayloadKeyType>>;
+
+    /// Rebuild index of the field from payload storage
+    fn rebuild_field_index(&mut self, field: &PayloadKeyType) -> OperationResult<()>;
+
+    /// Prepare index of the new key, if the migrated key is indexed
+    fn start_migration(&mut self, migration: &PayloadMigration);
+
+    /// Add value of the new key of the point, visited by the migration
+    fn add_migrated_value(&mut self, migration: &PayloadMigration, point_id: PointOffsetType, value: &PayloadType);
+
+    /// Replace index of the migrated key with the index of the new key, once all points are visited
+    fn finish_migration(&mut self, migration: &PayloadMigration) -> OperationResult<()>;
 }
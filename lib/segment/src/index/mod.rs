@@ -1,6 +1,8 @@
 pub mod plain_payload_index;
 pub mod index;
+#[cfg(feature = "payload_index")]
 pub mod struct_payload_index;
-mod field_index;
+pub(crate) mod field_index;
 mod payload_config;
+#[cfg(feature = "payload_index")]
 pub mod query_estimator;
@@ -1,21 +1,24 @@
 use crate::vector_storage::vector_storage::{ScoredPointOffset, VectorStorage};
 use crate::index::index::{Index, PayloadIndex};
-use crate::types::{Filter, VectorElementType, Distance, SearchParams, PointOffsetType, PayloadKeyType};
+use crate::types::{Filter, VectorElementType, Distance, SearchParams, PointOffsetType, PayloadKeyType, PayloadIndexInfo, PayloadMigration, PayloadType};
 use crate::payload_storage::payload_storage::{ConditionChecker};
 
 use std::sync::Arc;
-use atomic_refcell::AtomicRefCell;
+use crate::common::rw_cell::RwCell;
+use crate::common::stop_condition::StopCondition;
+use crate::common::search_arena::with_search_arena;
+use bumpalo::collections::Vec as BumpVec;
 use crate::entry::entry_point::OperationResult;
 use crate::index::payload_config::PayloadConfig;
 use std::path::{Path, PathBuf};
 use std::fs::create_dir_all;
 use crate::index::field_index::CardinalityEstimation;
-use itertools::Itertools;
+use std::collections::HashMap;
 
 
 pub struct PlainPayloadIndex {
-    condition_checker: Arc<AtomicRefCell<dyn ConditionChecker>>,
-    vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
+    condition_checker: Arc<RwCell<dyn ConditionChecker>>,
+    vector_storage: Arc<RwCell<dyn VectorStorage>>,
     config: PayloadConfig,
     path: PathBuf
 }
@@ -33,8 +36,8 @@ impl PlainPayloadIndex {
     }
 
     pub fn open(
-        condition_checker: Arc<AtomicRefCell<dyn ConditionChecker>>,
-        vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
+        condition_checker: Arc<RwCell<dyn ConditionChecker>>,
+        vector_storage: Arc<RwCell<dyn VectorStorage>>,
         path: &Path,
     ) -> OperationResult<Self> {
         create_dir_all(path)?;
@@ -82,8 +85,9 @@ impl PayloadIndex for PlainPayloadIndex {
     fn estimate_cardinality(&self, query: &Filter) -> CardinalityEstimation {
         let mut matched_points = 0;
         let condition_checker = self.condition_checker.borrow();
+        let plan = condition_checker.plan(query);
         for i in self.vector_storage.borrow().iter_ids() {
-            if condition_checker.check(i, query) {
+            if condition_checker.check_plan(i, &plan) {
                 matched_points += 1;
             }
         }
@@ -98,26 +102,52 @@ impl PayloadIndex for PlainPayloadIndex {
     fn query_points(&self, query: &Filter) -> Box<dyn Iterator<Item=PointOffsetType> + '_> {
         let mut matched_points = vec![];
         let condition_checker = self.condition_checker.borrow();
+        let plan = condition_checker.plan(query);
         for i in self.vector_storage.borrow().iter_ids() {
-            if condition_checker.check(i, query) {
+            if condition_checker.check_plan(i, &plan) {
                 matched_points.push(i);
             }
         }
         return Box::new(matched_points.into_iter());
     }
+
+    fn indexes_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>> {
+        // Plain index does not build any field indexes
+        HashMap::new()
+    }
+
+    fn inconsistent_fields(&self) -> OperationResult<Vec<PayloadKeyType>> {
+        Ok(vec![])
+    }
+
+    fn rebuild_field_index(&mut self, _field: &PayloadKeyType) -> OperationResult<()> {
+        Ok(())
+    }
+
+    fn start_migration(&mut self, _migration: &PayloadMigration) {}
+
+    fn add_migrated_value(&mut self, _migration: &PayloadMigration, _point_id: PointOffsetType, _value: &PayloadType) {}
+
+    fn finish_migration(&mut self, migration: &PayloadMigration) -> OperationResult<()> {
+        if migration.key != migration.new_key && self.config.indexed_fields.contains(&migration.key) {
+            self.drop_index(&migration.key)?;
+            self.set_indexed(&migration.new_key)?;
+        }
+        Ok(())
+    }
 }
 
 
 pub struct PlainIndex {
-    vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
-    payload_index: Arc<AtomicRefCell<dyn PayloadIndex>>,
+    vector_storage: Arc<RwCell<dyn VectorStorage>>,
+    payload_index: Arc<RwCell<dyn PayloadIndex>>,
     distance: Distance,
 }
 
 impl PlainIndex {
     pub fn new(
-        vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
-        payload_index: Arc<AtomicRefCell<dyn PayloadIndex>>,
+        vector_storage: Arc<RwCell<dyn VectorStorage>>,
+        payload_index: Arc<RwCell<dyn PayloadIndex>>,
         distance: Distance,
     ) -> PlainIndex {
         return PlainIndex {
@@ -136,17 +166,19 @@ impl Index for PlainIndex {
         filter: Option<&Filter>,
         top: usize,
         _params: Option<&SearchParams>,
+        stop: &StopCondition,
     ) -> Vec<ScoredPointOffset> {
         match filter {
-            Some(filter) => {
-                let filtered_ids = self.payload_index.borrow().query_points(filter).collect_vec();
-                self.vector_storage.borrow().score_points(vector, &filtered_ids, top, &self.distance)
-            }
-            None => self.vector_storage.borrow().score_all(vector, top, &self.distance)
+            Some(filter) => with_search_arena(|arena| {
+                let mut filtered_ids = BumpVec::new_in(arena);
+                filtered_ids.extend(stop.take_until_stopped(self.payload_index.borrow().query_points(filter)));
+                self.vector_storage.borrow().score_points(vector, &filtered_ids, top, &self.distance, stop)
+            }),
+            None => self.vector_storage.borrow().score_all(vector, top, &self.distance, stop)
         }
     }
 
-    fn build_index(&mut self) -> OperationResult<()> {
-        Ok(())
+    fn build_index(&mut self, stop: &StopCondition) -> OperationResult<()> {
+        stop.check()
     }
 }
\ No newline at end of file
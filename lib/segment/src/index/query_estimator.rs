@@ -1,4 +1,4 @@
-use crate::types::{Filter, Condition};
+use crate::types::{Filter, Condition, MinShould};
 use crate::index::field_index::{CardinalityEstimation, PrimaryCondition};
 use itertools::Itertools;
 use std::cmp::{max, min};
@@ -57,6 +57,12 @@ pub fn estimate_filter<F>(estimator: &F, filter: &Filter, total: usize) -> Cardi
             filter_estimations.push(estimate_should(estimator, conditions, total))
         }
     }
+    match &filter.min_should {
+        None => {}
+        Some(min_should) => if !min_should.conditions.is_empty() {
+            filter_estimations.push(estimate_min_should(estimator, min_should, total))
+        }
+    }
     match &filter.must_not {
         None => {}
         Some(conditions) => if !conditions.is_empty() {
@@ -98,6 +104,57 @@ fn estimate_should<F>(estimator: &F, conditions: &Vec<Condition>, total: usize)
     }
 }
 
+fn estimate_min_should<F>(estimator: &F, min_should: &MinShould, total: usize) -> CardinalityEstimation
+    where F: Fn(&Condition) -> CardinalityEstimation {
+    let min_count = min_should.min_count;
+    if min_count <= 1 {
+        return if min_count == 0 {
+            CardinalityEstimation::exact(total)
+        } else {
+            estimate_should(estimator, &min_should.conditions, total)
+        };
+    }
+    if min_count > min_should.conditions.len() {
+        return CardinalityEstimation::exact(0);
+    }
+
+    let estimate = |x| estimate_condition(estimator, x, total);
+    let estimations = min_should.conditions.iter().map(estimate).collect_vec();
+
+    // Any point, which matches at least `min_count` conditions, matches at least one of them
+    let mut clauses: Vec<PrimaryCondition> = vec![];
+    for estimation in estimations.iter() {
+        if estimation.primary_clauses.is_empty() {
+            clauses = vec![];
+            break;
+        } else {
+            clauses.append(&mut estimation.primary_clauses.clone());
+        }
+    }
+
+    // Assume conditions are independent: `hits_prob[k]` is a probability of exactly `k` matched conditions
+    let mut hits_prob = vec![0.0; estimations.len() + 1];
+    hits_prob[0] = 1.0;
+    for (checked, estimation) in estimations.iter().enumerate() {
+        let prob = estimation.exp as f64 / total as f64;
+        for hits in (0..=checked + 1).rev() {
+            let hit = if hits > 0 { hits_prob[hits - 1] * prob } else { 0.0 };
+            hits_prob[hits] = hit + hits_prob[hits] * (1.0 - prob);
+        }
+    }
+    let match_prob: f64 = hits_prob[min_count..].iter().sum();
+
+    // Each matched point is counted in at least `min_count` conditions
+    let max_hits: usize = estimations.iter().map(|x| x.max).sum();
+
+    CardinalityEstimation {
+        primary_clauses: clauses,
+        min: 0,
+        exp: (match_prob * total as f64) as usize,
+        max: min(max_hits / min_count, total),
+    }
+}
+
 fn estimate_must<F>(estimator: &F, conditions: &Vec<Condition>, total: usize) -> CardinalityEstimation
     where F: Fn(&Condition) -> CardinalityEstimation {
     let estimate = |x| estimate_condition(estimator, x, total);
@@ -126,7 +183,7 @@ fn estimate_must_not<F>(estimator: &F, conditions: &Vec<Condition>, total: usize
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{FieldCondition, HasIdCondition};
+    use crate::types::{FieldCondition, HasIdCondition, ExtendedPointId, PointOffsetType};
 
     const TOTAL: usize = 1000;
 
@@ -165,7 +222,10 @@ mod tests {
                 _ => CardinalityEstimation::unknown(TOTAL)
             },
             Condition::HasId(has_id) => CardinalityEstimation {
-                primary_clauses: vec![PrimaryCondition::Ids(has_id.has_id.iter().map(|x| *x as usize).collect())],
+                primary_clauses: vec![PrimaryCondition::Ids(has_id.has_id.iter().filter_map(|x| match x {
+                    ExtendedPointId::NumId(id) => Some(*id as PointOffsetType),
+                    ExtendedPointId::Uuid(_) => None,
+                }).collect())],
                 min: has_id.has_id.len(),
                 exp: has_id.has_id.len(),
                 max: has_id.has_id.len(),
@@ -190,6 +250,7 @@ mod tests {
                 test_condition("size".to_owned()),
                 test_condition("un-indexed".to_owned()),
             ]),
+            min_should: None,
             must_not: None,
         };
 
@@ -212,6 +273,7 @@ mod tests {
                 test_condition("size".to_owned()),
             ]),
             must: None,
+            min_should: None,
             must_not: None,
         };
 
@@ -231,6 +293,7 @@ mod tests {
                 test_condition("un-indexed".to_owned()),
             ]),
             must: None,
+            min_should: None,
             must_not: None,
         };
 
@@ -242,6 +305,46 @@ mod tests {
         assert!(estimation.min <= estimation.exp);
     }
 
+    #[test]
+    fn min_should_estimation_query_test() {
+        let query = Filter {
+            should: None,
+            must: None,
+            min_should: Some(MinShould {
+                conditions: vec![
+                    test_condition("color".to_owned()),
+                    test_condition("size".to_owned()),
+                    test_condition("price".to_owned()),
+                ],
+                min_count: 2,
+            }),
+            must_not: None,
+        };
+
+        let estimation = estimate_filter(&test_estimator, &query, TOTAL);
+        assert_eq!(estimation.primary_clauses.len(), 3);
+        assert_eq!(estimation.max, 210);
+        assert!(estimation.exp <= estimation.max);
+        assert!(estimation.min <= estimation.exp);
+
+        let query = Filter {
+            should: None,
+            must: None,
+            min_should: Some(MinShould {
+                conditions: vec![
+                    test_condition("color".to_owned()),
+                    test_condition("un-indexed".to_owned()),
+                ],
+                min_count: 2,
+            }),
+            must_not: None,
+        };
+
+        let estimation = estimate_filter(&test_estimator, &query, TOTAL);
+        assert!(estimation.primary_clauses.is_empty());
+        assert_eq!(estimation.max, 650);
+    }
+
     #[test]
     fn complex_estimation_query_test() {
         let query = Filter {
@@ -252,6 +355,7 @@ mod tests {
                         test_condition("color".to_owned()),
                         test_condition("size".to_owned()),
                     ]),
+                    min_should: None,
                     must_not: None,
                 }),
                 Condition::Filter(Filter {
@@ -260,12 +364,14 @@ mod tests {
                         test_condition("price".to_owned()),
                         test_condition("size".to_owned()),
                     ]),
+                    min_should: None,
                     must_not: None,
                 }),
             ]),
             must: None,
+            min_should: None,
             must_not: Some(vec![
-                Condition::HasId(HasIdCondition { has_id: vec![1, 2, 3, 4, 5].iter().cloned().collect() })
+                Condition::HasId(HasIdCondition { has_id: vec![1, 2, 3, 4, 5].into_iter().map(|x| x.into()).collect() })
             ]),
         };
 
@@ -287,6 +393,7 @@ mod tests {
                         test_condition("color".to_owned()),
                         test_condition("size".to_owned()),
                     ]),
+                    min_should: None,
                     must_not: None,
                 }),
                 Condition::Filter(Filter {
@@ -295,11 +402,13 @@ mod tests {
                         test_condition("price".to_owned()),
                         test_condition("size".to_owned()),
                     ]),
+                    min_should: None,
                     must_not: None,
                 }),
             ]),
+            min_should: None,
             must_not: Some(vec![
-                Condition::HasId(HasIdCondition { has_id: vec![1, 2, 3, 4, 5].iter().cloned().collect() })
+                Condition::HasId(HasIdCondition { has_id: vec![1, 2, 3, 4, 5].into_iter().map(|x| x.into()).collect() })
             ]),
         };
 
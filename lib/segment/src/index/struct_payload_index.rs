@@ -3,17 +3,18 @@ use std::fs::{create_dir_all, File, remove_file};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use atomic_refcell::AtomicRefCell;
+use crate::common::rw_cell::RwCell;
 use itertools::Itertools;
-use log::debug;
+use tracing::debug;
 
 use crate::entry::entry_point::{OperationError, OperationResult};
-use crate::index::field_index::field_index::{FieldIndex, PayloadFieldIndex};
+use crate::index::field_index::field_index::{FieldIndex, PayloadFieldIndex, PayloadFieldIndexBuilder};
 use crate::index::field_index::index_selector::index_selector;
 use crate::index::index::PayloadIndex;
 use crate::index::payload_config::PayloadConfig;
+use crate::payload_storage::filter_plan::{ConditionEstimation, FilterPlanCache, IndexHandle};
 use crate::payload_storage::payload_storage::{ConditionChecker, PayloadStorage};
-use crate::types::{Filter, PayloadKeyType, FieldCondition, Condition, PointOffsetType};
+use crate::types::{Filter, PayloadKeyType, FieldCondition, Condition, PointOffsetType, PayloadIndexInfo, TextAnalyzerConfig, PayloadMigration, PayloadType, SeqNumberType};
 use crate::index::field_index::{CardinalityEstimation, PrimaryCondition};
 use crate::index::query_estimator::estimate_filter;
 use crate::vector_storage::vector_storage::VectorStorage;
@@ -24,41 +25,75 @@ pub const PAYLOAD_FIELD_INDEX_PATH: &str = "fields";
 type IndexesMap = HashMap<PayloadKeyType, Vec<FieldIndex>>;
 
 pub struct StructPayloadIndex {
-    condition_checker: Arc<AtomicRefCell<dyn ConditionChecker>>,
-    vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
-    payload: Arc<AtomicRefCell<dyn PayloadStorage>>,
-    id_mapper: Arc<AtomicRefCell<dyn IdMapper>>,
+    condition_checker: Arc<RwCell<dyn ConditionChecker>>,
+    vector_storage: Arc<RwCell<dyn VectorStorage>>,
+    payload: Arc<RwCell<dyn PayloadStorage>>,
+    id_mapper: Arc<RwCell<dyn IdMapper>>,
     field_indexes: IndexesMap,
     config: PayloadConfig,
+    text_analyzers: HashMap<PayloadKeyType, TextAnalyzerConfig>,
+    /// Plans reference field indexes, so the cache is dropped whenever indexes are replaced
+    plan_cache: FilterPlanCache,
+    /// Builders of the new key indexes of payload migrations in progress, by the version of the migration
+    migrating_fields: HashMap<SeqNumberType, Vec<Box<dyn PayloadFieldIndexBuilder>>>,
     path: PathBuf,
 }
 
 impl StructPayloadIndex {
     pub fn estimate_field_condition(&self, condition: &FieldCondition) -> Option<CardinalityEstimation> {
+        self.estimate_field_condition_with_index(condition).map(|(_, estimation)| estimation)
+    }
+
+    /// Estimation of the first field index, which is able to serve the condition, with the handle of this index
+    fn estimate_field_condition_with_index(&self, condition: &FieldCondition) -> Option<(IndexHandle, CardinalityEstimation)> {
         self.field_indexes.get(&condition.key).and_then(|indexes| {
-            let mut result_estimation: Option<CardinalityEstimation> = None;
-            for index in indexes {
-                result_estimation = index.estimate_cardinality(condition);
-                if result_estimation.is_some() {
-                    break;
+            indexes.iter()
+                .enumerate()
+                .find_map(|(handle, index)| index.estimate_cardinality(condition).map(|estimation| (handle, estimation)))
+        })
+    }
+
+    fn estimate_condition(&self, condition: &Condition) -> CardinalityEstimation {
+        match condition {
+            Condition::Filter(_) => panic!("Unexpected branching"),
+            Condition::HasId(has_id) => {
+                let id_mapper_ref = self.id_mapper.borrow();
+                let mapped_ids: HashSet<PointOffsetType> = has_id.has_id.iter()
+                    .filter_map(|external_id| id_mapper_ref.internal_id(*external_id))
+                    .collect();
+                let num_ids = mapped_ids.len();
+                CardinalityEstimation {
+                    primary_clauses: vec![PrimaryCondition::Ids(mapped_ids)],
+                    min: num_ids,
+                    exp: num_ids,
+                    max: num_ids,
                 }
             }
-            result_estimation
-        })
+            Condition::Field(field_condition) => self
+                .estimate_field_condition(field_condition)
+                .unwrap_or(CardinalityEstimation::unknown(self.total_points())),
+        }
     }
 
-    fn query_field(&self, field_condition: &FieldCondition) -> Option<Box<dyn Iterator<Item=PointOffsetType> + '_>> {
-        let indexes = self.field_indexes
-            .get(&field_condition.key)
-            .and_then(|indexes|
-                indexes
-                    .iter()
-                    .map(|field_index| field_index.filter(field_condition))
-                    .skip_while(|filter_iter| filter_iter.is_none())
-                    .next()
-                    .map(|filter_iter| filter_iter.unwrap())
-            );
-        indexes
+    /// Estimation, by which conditions of the filter plan are ordered
+    fn estimate_plan_condition(&self, condition: &Condition) -> Option<ConditionEstimation> {
+        match condition {
+            Condition::Filter(_) => None,
+            Condition::Field(field_condition) => match self.estimate_field_condition_with_index(field_condition) {
+                Some((handle, estimation)) => Some(ConditionEstimation { cardinality: estimation.exp, index: Some(handle) }),
+                None => Some(ConditionEstimation { cardinality: CardinalityEstimation::unknown(self.total_points()).exp, index: None }),
+            },
+            Condition::HasId(_) => Some(ConditionEstimation { cardinality: self.estimate_condition(condition).exp, index: None }),
+        }
+    }
+
+    /// Points, selected by the field index. Index from the plan is used directly, if it is known
+    fn query_field(&self, field_condition: &FieldCondition, handle: Option<IndexHandle>) -> Option<Box<dyn Iterator<Item=PointOffsetType> + '_>> {
+        let indexes = self.field_indexes.get(&field_condition.key)?;
+        match handle.and_then(|handle| indexes.get(handle)) {
+            Some(field_index) => field_index.filter(field_condition),
+            None => indexes.iter().find_map(|field_index| field_index.filter(field_condition)),
+        }
     }
 
     fn config_path(&self) -> PathBuf {
@@ -97,14 +132,14 @@ impl StructPayloadIndex {
     fn load_or_build_field_index(&self, field: &PayloadKeyType) -> OperationResult<Vec<FieldIndex>> {
         let field_index_path = Self::get_field_index_path(&self.path, field);
         if field_index_path.exists() {
-            debug!("Loading field `{}` index from {}", field, field_index_path.to_str().unwrap());
+            debug!(field = %field, path = %field_index_path.display(), "Loading field index");
             let file = File::open(field_index_path)?;
             let field_indexes: Vec<FieldIndex> = serde_cbor::from_reader(file)
                 .map_err(|err| OperationError::ServiceError { description: format!("Unable to load index: {:?}", err) })?;
 
             Ok(field_indexes)
         } else {
-            debug!("Index for field `{}` not found in {}, building now", field, field_index_path.to_str().unwrap());
+            debug!(field = %field, path = %field_index_path.display(), "Field index not found, building now");
             let res = self.build_field_index(field)?;
             self.save_field_index(field)?;
             Ok(res)
@@ -118,14 +153,16 @@ impl StructPayloadIndex {
             field_indexes.insert(field.clone(), field_index);
         }
         self.field_indexes = field_indexes;
+        self.plan_cache.clear();
         Ok(())
     }
 
 
-    pub fn open(condition_checker: Arc<AtomicRefCell<dyn ConditionChecker>>,
-                vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
-                payload: Arc<AtomicRefCell<dyn PayloadStorage>>,
-                id_mapper: Arc<AtomicRefCell<dyn IdMapper>>,
+    pub fn open(condition_checker: Arc<RwCell<dyn ConditionChecker>>,
+                vector_storage: Arc<RwCell<dyn VectorStorage>>,
+                payload: Arc<RwCell<dyn PayloadStorage>>,
+                id_mapper: Arc<RwCell<dyn IdMapper>>,
+                text_analyzers: HashMap<PayloadKeyType, TextAnalyzerConfig>,
                 path: &Path,
     ) -> OperationResult<Self> {
         create_dir_all(path)?;
@@ -143,6 +180,9 @@ impl StructPayloadIndex {
             id_mapper,
             field_indexes: Default::default(),
             config,
+            text_analyzers,
+            plan_cache: FilterPlanCache::default(),
+            migrating_fields: Default::default(),
             path: path.to_owned()
         };
 
@@ -169,7 +209,7 @@ impl StructPayloadIndex {
 
         let field_type = field_type_opt.unwrap();
 
-        let mut builders = index_selector(field_type);
+        let mut builders = index_selector(field_type, self.text_analyzers.get(field));
 
         for point_id in payload_ref.iter_ids() {
             let point_payload = payload_ref.payload(point_id);
@@ -200,6 +240,7 @@ impl StructPayloadIndex {
             field.clone(),
             field_indexes,
         );
+        self.plan_cache.clear();
 
         self.save_field_index(field)?;
 
@@ -230,6 +271,7 @@ impl PayloadIndex for StructPayloadIndex {
         self.config.indexed_fields = self.config.indexed_fields.iter().cloned().filter(|x| x != field).collect();
         self.save_config()?;
         self.field_indexes.remove(field);
+        self.plan_cache.clear();
 
         let field_index_path = Self::get_field_index_path(&self.path, field);
 
@@ -242,29 +284,7 @@ impl PayloadIndex for StructPayloadIndex {
 
     fn estimate_cardinality(&self, query: &Filter) -> CardinalityEstimation {
         let total = self.total_points();
-
-        let estimator = |condition: &Condition| {
-            match condition {
-                Condition::Filter(_) => panic!("Unexpected branching"),
-                Condition::HasId(has_id) => {
-                    let id_mapper_ref = self.id_mapper.borrow();
-                    let mapped_ids: HashSet<PointOffsetType> = has_id.has_id.iter()
-                        .filter_map(|external_id| id_mapper_ref.internal_id(*external_id))
-                        .collect();
-                    let num_ids = mapped_ids.len();
-                    CardinalityEstimation {
-                        primary_clauses: vec![PrimaryCondition::Ids(mapped_ids)],
-                        min: num_ids,
-                        exp: num_ids,
-                        max: num_ids,
-                    }
-                }
-                Condition::Field(field_condition) => self
-                    .estimate_field_condition(field_condition)
-                    .unwrap_or(CardinalityEstimation::unknown(self.total_points())),
-            }
-        };
-
+        let estimator = |condition: &Condition| self.estimate_condition(condition);
         estimate_filter(&estimator, query, total)
     }
 
@@ -272,12 +292,14 @@ impl PayloadIndex for StructPayloadIndex {
         // Assume query is already estimated to be small enough so we can iterate over all matched ids
         let query_cardinality = self.estimate_cardinality(query);
         let condition_checker = self.condition_checker.borrow();
+        // Estimations only define the order of checks, so plans with outdated estimations are still correct
+        let plan = self.plan_cache.get_or_compile_with(query, &|condition| self.estimate_plan_condition(condition));
         let vector_storage_ref = self.vector_storage.borrow();
         let full_scan_iterator = vector_storage_ref.iter_ids(); // Should not be used if filter restricted by indexed fields
         return if query_cardinality.primary_clauses.is_empty() {
             // Worst case: query expected to return few matches, but index can't be used
             let matched_points = full_scan_iterator
-                .filter(|i| condition_checker.check(*i, query))
+                .filter(|i| condition_checker.check_plan(*i, &plan))
                 .collect_vec();
 
             Box::new(matched_points.into_iter())
@@ -286,7 +308,8 @@ impl PayloadIndex for StructPayloadIndex {
             let preselected: HashSet<PointOffsetType> = query_cardinality.primary_clauses.iter()
                 .map(|clause| {
                     match clause {
-                        PrimaryCondition::Condition(field_condition) => self.query_field(field_condition)
+                        PrimaryCondition::Condition(field_condition) => self
+                            .query_field(field_condition, plan.step_estimation(field_condition).and_then(|estimation| estimation.index))
                             .unwrap_or(vector_storage_ref.iter_ids() /* index is not built */),
                         PrimaryCondition::Ids(ids) => Box::new(ids.iter().cloned())
                     }
@@ -294,9 +317,82 @@ impl PayloadIndex for StructPayloadIndex {
                 .flat_map(|x| x)
                 .collect();
             let matched_points = preselected.into_iter()
-                .filter(|i| condition_checker.check(*i, query))
+                .filter(|i| condition_checker.check_plan(*i, &plan))
                 .collect_vec();
             Box::new(matched_points.into_iter())
         };
     }
+
+    fn indexes_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>> {
+        self.field_indexes.iter()
+            .map(|(field, indexes)| (field.clone(), indexes.iter().map(|index| index.info()).collect()))
+            .collect()
+    }
+
+    fn inconsistent_fields(&self) -> OperationResult<Vec<PayloadKeyType>> {
+        // Memory usage depends on the history of the index, so only counters are compared
+        let counters = |indexes: &Vec<FieldIndex>| indexes.iter()
+            .map(|index| {
+                let info = index.info();
+                (info.index_type, info.points_count, info.values_count, info.distinct_values)
+            })
+            .collect_vec();
+
+        let mut inconsistent = vec![];
+        for field in self.config.indexed_fields.iter() {
+            let expected = self.build_field_index(field)?;
+            let is_consistent = self.field_indexes.get(field)
+                .map(|indexes| counters(indexes) == counters(&expected))
+                .unwrap_or(false);
+            if !is_consistent {
+                inconsistent.push(field.clone());
+            }
+        }
+        Ok(inconsistent)
+    }
+
+    fn rebuild_field_index(&mut self, field: &PayloadKeyType) -> OperationResult<()> {
+        self.build_and_save(field)
+    }
+
+    fn start_migration(&mut self, migration: &PayloadMigration) {
+        let is_indexed = self.config.indexed_fields.contains(&migration.key)
+            || self.config.indexed_fields.contains(&migration.new_key);
+        if !is_indexed {
+            return;
+        }
+        let builders = index_selector(&migration.new_type, self.text_analyzers.get(&migration.new_key));
+        self.migrating_fields.insert(migration.version, builders);
+        if migration.key == migration.new_key {
+            // Index of converted key contains values of the old type, filters fall back to the payload check
+            self.field_indexes.remove(&migration.key);
+            self.plan_cache.clear();
+        }
+    }
+
+    fn add_migrated_value(&mut self, migration: &PayloadMigration, point_id: PointOffsetType, value: &PayloadType) {
+        if let Some(builders) = self.migrating_fields.get_mut(&migration.version) {
+            for builder in builders.iter_mut() {
+                builder.add(point_id, value)
+            }
+        }
+    }
+
+    fn finish_migration(&mut self, migration: &PayloadMigration) -> OperationResult<()> {
+        let mut builders = match self.migrating_fields.remove(&migration.version) {
+            None => return Ok(()),
+            Some(builders) => builders
+        };
+        if migration.key != migration.new_key {
+            self.drop_index(&migration.key)?;
+        }
+        if !self.config.indexed_fields.contains(&migration.new_key) {
+            self.config.indexed_fields.push(migration.new_key.clone());
+            self.save_config()?;
+        }
+        let field_indexes = builders.iter_mut().map(|builder| builder.build()).collect_vec();
+        self.field_indexes.insert(migration.new_key.clone(), field_indexes);
+        self.plan_cache.clear();
+        self.save_field_index(&migration.new_key)
+    }
 }
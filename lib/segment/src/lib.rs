@@ -8,7 +8,11 @@ pub mod spaces;
 pub mod segment_constructor;
 pub mod entry;
 pub mod types;
-mod common;
+pub mod telemetry;
+pub mod common;
+pub mod benchmark;
+#[cfg(feature = "ann_benchmarks")]
+pub mod ann_benchmarks;
 
 
 #[cfg(test)]
@@ -1,6 +1,9 @@
 use crate::types::{PayloadType, Match, Range, GeoBoundingBox, GeoRadius};
+#[cfg(feature = "geo")]
 use geo::Point;
+#[cfg(feature = "geo")]
 use geo::algorithm::haversine_distance::HaversineDistance;
+use crate::index::field_index::text_analyzer::TextAnalyzer;
 
 pub fn match_payload(payload: &PayloadType, condition_match: &Match) -> bool {
     match payload {
@@ -19,6 +22,22 @@ pub fn match_payload(payload: &PayloadType, condition_match: &Match) -> bool {
     }
 }
 
+/// Check if all tokens of the `text` are present in keyword payload.
+/// Payload and text are tokenized with the same analyzer.
+pub fn match_text(payload: &PayloadType, text: &str, analyzer: &TextAnalyzer) -> bool {
+    match payload {
+        PayloadType::Keyword(payload_texts) => {
+            let query_tokens = analyzer.tokenize(text);
+            if query_tokens.is_empty() {
+                return false;
+            }
+            let payload_tokens = analyzer.tokenize_all(payload_texts.iter());
+            query_tokens.is_subset(&payload_tokens)
+        }
+        _ => false
+    }
+}
+
 pub fn match_range(
     payload: &PayloadType,
     num_range: &Range,
@@ -51,6 +70,7 @@ pub fn match_geo(
     };
 }
 
+#[cfg(feature = "geo")]
 pub fn match_geo_radius(
     payload: &PayloadType,
     geo_radius_query: &GeoRadius,
@@ -73,7 +93,16 @@ pub fn match_geo_radius(
     };
 }
 
-#[cfg(test)]
+/// Filters with geo radius conditions are rejected by `check_filter_support` without `geo` feature
+#[cfg(not(feature = "geo"))]
+pub fn match_geo_radius(
+    _payload: &PayloadType,
+    _geo_radius_query: &GeoRadius,
+) -> bool {
+    false
+}
+
+#[cfg(all(test, feature = "geo"))]
 mod tests {
     use super::*;
     use crate::types::GeoPoint;
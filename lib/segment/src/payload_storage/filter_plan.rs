@@ -0,0 +1,485 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::types::{Condition, FieldCondition, Filter, GeoPoint, MinShould, Range};
+
+/// Max number of distinct filters kept in plan cache, least recently used plans are evicted first
+pub const DEFAULT_PLAN_CACHE_SIZE: usize = 1024;
+
+/// Position of the field index among the indexes of the condition field, which is able to serve the condition
+pub type IndexHandle = usize;
+
+/// Expected number of points, which match the condition, and the field index, which serves it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConditionEstimation {
+    pub cardinality: usize,
+    pub index: Option<IndexHandle>,
+}
+
+/// Field condition of the plan with its estimation, if the plan is compiled with an estimator
+#[derive(Debug, Clone)]
+pub struct PlanStep {
+    pub condition: FieldCondition,
+    pub estimation: Option<ConditionEstimation>,
+}
+
+/// Filter, normalized once for repeated evaluation over many points.
+///
+/// Normalization does not change the semantics of the filter:
+/// * Empty `must` and `must_not` clauses are removed
+/// * Nested filters, which consist only of `must` conditions, are merged into parent `must`
+/// * Conditions within each clause are ordered so that `all` and `any` evaluation short-circuits early:
+///   `must` starts from the conditions with the least expected matches, other clauses from the most.
+///   Conditions without estimation, or with equal ones, are ordered by the cost of the check
+#[derive(Debug, Clone)]
+pub struct FilterPlan {
+    pub filter: Filter,
+    /// Field conditions of the filter with their estimations and indexes
+    pub steps: Vec<PlanStep>,
+}
+
+/// Relative cost of the condition check. Lower is cheaper.
+fn field_condition_cost(condition: &FieldCondition) -> usize {
+    if let Some(condition_match) = &condition.r#match {
+        // Full-text match requires tokenization of the payload
+        if condition_match.text.is_some() { 5 } else { 1 }
+    } else if condition.range.is_some() {
+        2
+    } else if condition.geo_bounding_box.is_some() {
+        3
+    } else {
+        4
+    }
+}
+
+fn condition_cost(condition: &Condition) -> usize {
+    match condition {
+        // Does not require payload access
+        Condition::HasId(_) => 0,
+        Condition::Field(field_condition) => field_condition_cost(field_condition),
+        Condition::Filter(_) => 10
+    }
+}
+
+fn is_must_only(filter: &Filter) -> bool {
+    filter.should.is_none() && filter.min_should.is_none() && filter.must_not.is_none() && filter.must.is_some()
+}
+
+/// Evaluation of the clause: `must` stops at the first failed condition, other clauses stop at the first matched one
+#[derive(Clone, Copy)]
+enum ClauseKind {
+    Must,
+    Matching,
+}
+
+struct PlanCompiler<'a> {
+    estimator: &'a dyn Fn(&Condition) -> Option<ConditionEstimation>,
+    /// Estimations of the already seen conditions, so each condition is estimated once
+    estimations: Vec<(Condition, Option<ConditionEstimation>)>,
+}
+
+impl<'a> PlanCompiler<'a> {
+    fn estimate(&mut self, condition: &Condition) -> Option<ConditionEstimation> {
+        if let Condition::Filter(_) = condition {
+            return None;
+        }
+        if let Some((_, estimation)) = self.estimations.iter().find(|(estimated, _)| estimated == condition) {
+            return *estimation;
+        }
+        let estimation = (self.estimator)(condition);
+        self.estimations.push((condition.clone(), estimation));
+        estimation
+    }
+
+    fn order(&mut self, conditions: Vec<Condition>, kind: ClauseKind) -> Vec<Condition> {
+        let mut estimated: Vec<_> = conditions.into_iter()
+            .map(|condition| (self.estimate(&condition), condition))
+            .collect();
+        // Stable sort keeps original order for equal keys
+        match kind {
+            ClauseKind::Must => estimated.sort_by_key(|(estimation, condition)| (
+                estimation.map_or(usize::MAX, |estimation| estimation.cardinality),
+                condition_cost(condition),
+            )),
+            ClauseKind::Matching => estimated.sort_by_key(|(estimation, condition)| (
+                Reverse(estimation.map(|estimation| estimation.cardinality)),
+                condition_cost(condition),
+            )),
+        }
+        estimated.into_iter().map(|(_, condition)| condition).collect()
+    }
+
+    fn compile_condition(&mut self, condition: &Condition) -> Condition {
+        match condition {
+            Condition::Filter(filter) => Condition::Filter(self.compile_filter(filter)),
+            _ => condition.clone()
+        }
+    }
+
+    fn compile_clause(&mut self, conditions: &[Condition], kind: ClauseKind) -> Vec<Condition> {
+        let compiled = conditions.iter().map(|condition| self.compile_condition(condition)).collect();
+        self.order(compiled, kind)
+    }
+
+    fn compile_must(&mut self, conditions: &[Condition]) -> Option<Vec<Condition>> {
+        let mut flatten: Vec<Condition> = vec![];
+        for condition in conditions {
+            match self.compile_condition(condition) {
+                Condition::Filter(nested) if is_must_only(&nested) => flatten.extend(nested.must.unwrap()),
+                compiled => flatten.push(compiled)
+            }
+        }
+        if flatten.is_empty() {
+            None
+        } else {
+            Some(self.order(flatten, ClauseKind::Must))
+        }
+    }
+
+    fn compile_filter(&mut self, filter: &Filter) -> Filter {
+        Filter {
+            // Empty `should` means that nothing matches, so it is preserved as is
+            should: filter.should.as_ref().map(|conditions| self.compile_clause(conditions, ClauseKind::Matching)),
+            must: filter.must.as_ref().and_then(|conditions| self.compile_must(conditions)),
+            min_should: filter.min_should.as_ref().map(|min_should| MinShould {
+                conditions: self.compile_clause(&min_should.conditions, ClauseKind::Matching),
+                min_count: min_should.min_count,
+            }),
+            must_not: filter.must_not.as_ref()
+                .map(|conditions| self.compile_clause(conditions, ClauseKind::Matching))
+                .filter(|conditions| !conditions.is_empty()),
+        }
+    }
+
+    /// Field conditions of the compiled filter in the order of evaluation
+    fn collect_steps(&mut self, filter: &Filter, steps: &mut Vec<PlanStep>) {
+        let conditions = filter.should.iter().flatten()
+            .chain(filter.must.iter().flatten())
+            .chain(filter.min_should.iter().flat_map(|min_should| min_should.conditions.iter()))
+            .chain(filter.must_not.iter().flatten());
+        for condition in conditions {
+            match condition {
+                Condition::Field(field_condition) => steps.push(PlanStep {
+                    condition: field_condition.clone(),
+                    estimation: self.estimate(condition),
+                }),
+                Condition::Filter(nested) => self.collect_steps(nested, steps),
+                Condition::HasId(_) => {}
+            }
+        }
+    }
+}
+
+impl FilterPlan {
+    /// Compile filter, ordering conditions by the cost of their check only
+    pub fn compile(filter: &Filter) -> Self {
+        FilterPlan::compile_with(filter, &|_| None)
+    }
+
+    /// Compile filter, ordering conditions by the estimated number of matched points.
+    /// Estimator is not called for nested filters
+    pub fn compile_with(filter: &Filter, estimator: &dyn Fn(&Condition) -> Option<ConditionEstimation>) -> Self {
+        let mut compiler = PlanCompiler { estimator, estimations: vec![] };
+        let filter = compiler.compile_filter(filter);
+        let mut steps = vec![];
+        compiler.collect_steps(&filter, &mut steps);
+        FilterPlan { filter, steps }
+    }
+
+    /// Estimation of the field condition, if it is a step of the plan
+    pub fn step_estimation(&self, condition: &FieldCondition) -> Option<ConditionEstimation> {
+        self.steps.iter()
+            .find(|step| &step.condition == condition)
+            .and_then(|step| step.estimation)
+    }
+}
+
+fn hash_geo_point<H: Hasher>(point: &GeoPoint, state: &mut H) {
+    point.lon.to_bits().hash(state);
+    point.lat.to_bits().hash(state);
+}
+
+fn hash_range<H: Hasher>(range: &Range, state: &mut H) {
+    for bound in &[range.lt, range.gt, range.gte, range.lte] {
+        bound.map(f64::to_bits).hash(state);
+    }
+}
+
+fn hash_field_condition<H: Hasher>(condition: &FieldCondition, state: &mut H) {
+    condition.key.hash(state);
+    if let Some(condition_match) = &condition.r#match {
+        condition_match.keyword.hash(state);
+        condition_match.integer.hash(state);
+        condition_match.text.hash(state);
+    }
+    if let Some(range) = &condition.range {
+        hash_range(range, state);
+    }
+    if let Some(geo_bounding_box) = &condition.geo_bounding_box {
+        hash_geo_point(&geo_bounding_box.top_left, state);
+        hash_geo_point(&geo_bounding_box.bottom_right, state);
+    }
+    if let Some(geo_radius) = &condition.geo_radius {
+        hash_geo_point(&geo_radius.center, state);
+        geo_radius.radius.to_bits().hash(state);
+    }
+}
+
+fn hash_conditions<H: Hasher>(conditions: &Option<Vec<Condition>>, state: &mut H) {
+    conditions.as_ref().map(|conditions| conditions.len()).hash(state);
+    for condition in conditions.iter().flatten() {
+        hash_condition(condition, state);
+    }
+}
+
+fn hash_condition<H: Hasher>(condition: &Condition, state: &mut H) {
+    match condition {
+        Condition::Field(field_condition) => {
+            0u8.hash(state);
+            hash_field_condition(field_condition, state);
+        }
+        Condition::HasId(has_id) => {
+            1u8.hash(state);
+            // Independent of the iteration order of the set
+            let ids_hash = has_id.has_id.iter()
+                .map(|id| {
+                    let mut id_state = DefaultHasher::new();
+                    id.hash(&mut id_state);
+                    id_state.finish()
+                })
+                .fold(0u64, u64::wrapping_add);
+            has_id.has_id.len().hash(state);
+            ids_hash.hash(state);
+        }
+        Condition::Filter(filter) => {
+            2u8.hash(state);
+            hash_filter(filter, state);
+        }
+    }
+}
+
+fn hash_filter<H: Hasher>(filter: &Filter, state: &mut H) {
+    hash_conditions(&filter.should, state);
+    hash_conditions(&filter.must, state);
+    filter.min_should.as_ref().map(|min_should| min_should.min_count).hash(state);
+    for condition in filter.min_should.iter().flat_map(|min_should| min_should.conditions.iter()) {
+        hash_condition(condition, state);
+    }
+    hash_conditions(&filter.must_not, state);
+}
+
+struct CachedPlan {
+    filter: Filter,
+    plan: Arc<FilterPlan>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct PlanCacheState {
+    plans: HashMap<u64, CachedPlan>,
+    /// Hashes of the cached filters by the time of their last use
+    recency: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+/// LRU cache of compiled filter plans, keyed by the hash of the filter.
+/// Allows to skip filter analysis for repeated identical queries.
+pub struct FilterPlanCache {
+    max_size: usize,
+    state: Mutex<PlanCacheState>,
+}
+
+impl FilterPlanCache {
+    pub fn new(max_size: usize) -> Self {
+        FilterPlanCache {
+            max_size,
+            state: Mutex::new(PlanCacheState::default()),
+        }
+    }
+
+    /// Get plan from cache or compile a new one
+    pub fn get_or_compile(&self, filter: &Filter) -> Arc<FilterPlan> {
+        self.get_or_compile_with(filter, &|_| None)
+    }
+
+    /// Get plan from cache or compile a new one with the estimator, see `FilterPlan::compile_with`
+    pub fn get_or_compile_with(&self, filter: &Filter, estimator: &dyn Fn(&Condition) -> Option<ConditionEstimation>) -> Arc<FilterPlan> {
+        let mut state = DefaultHasher::new();
+        hash_filter(filter, &mut state);
+        let key = state.finish();
+
+        {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            state.clock += 1;
+            if let Some(cached) = state.plans.get_mut(&key).filter(|cached| &cached.filter == filter) {
+                state.recency.remove(&cached.last_used);
+                cached.last_used = state.clock;
+                state.recency.insert(state.clock, key);
+                return cached.plan.clone();
+            }
+        }
+
+        // Compiled without the lock, so other filters are not blocked by the estimation
+        let plan = Arc::new(FilterPlan::compile_with(filter, estimator));
+
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        // Filter with the same hash is replaced
+        if let Some(replaced) = state.plans.remove(&key) {
+            state.recency.remove(&replaced.last_used);
+        }
+        while state.plans.len() >= self.max_size {
+            let (&last_used, &evicted) = match state.recency.iter().next() {
+                None => break,
+                Some(oldest) => oldest,
+            };
+            state.recency.remove(&last_used);
+            state.plans.remove(&evicted);
+        }
+        state.clock += 1;
+        state.recency.insert(state.clock, key);
+        state.plans.insert(key, CachedPlan { filter: filter.clone(), plan: plan.clone(), last_used: state.clock });
+        plan
+    }
+
+    /// Drop all plans, e.g. when indexes, which are referenced by plans, change
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.plans.clear();
+        state.recency.clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().plans.len()
+    }
+}
+
+impl Default for FilterPlanCache {
+    fn default() -> Self {
+        FilterPlanCache::new(DEFAULT_PLAN_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Match, Range, PointIdType};
+    use std::collections::HashSet;
+
+    fn keyword_condition(key: &str, keyword: &str) -> Condition {
+        Condition::Field(FieldCondition {
+            key: key.to_string(),
+            r#match: Some(Match { keyword: Some(keyword.to_owned()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        })
+    }
+
+    fn range_condition(key: &str) -> Condition {
+        Condition::Field(FieldCondition {
+            key: key.to_string(),
+            r#match: None,
+            range: Some(Range { lt: Some(10.), gt: None, gte: None, lte: None }),
+            geo_bounding_box: None,
+            geo_radius: None,
+        })
+    }
+
+    #[test]
+    fn test_compile_filter() {
+        let ids: HashSet<PointIdType> = vec![1, 2, 3].into_iter().map(|x| x.into()).collect();
+        let filter = Filter {
+            should: None,
+            must: Some(vec![
+                range_condition("price"),
+                Condition::Filter(Filter::new_must(keyword_condition("color", "red"))),
+                Condition::HasId(ids.into()),
+            ]),
+            min_should: None,
+            must_not: Some(vec![]),
+        };
+
+        let plan = FilterPlan::compile(&filter);
+
+        assert!(plan.filter.must_not.is_none());
+        let must = plan.filter.must.unwrap();
+        assert_eq!(must.len(), 3);
+        assert!(matches!(must[0], Condition::HasId(_)));
+        match &must[1] {
+            Condition::Field(condition) => assert_eq!(condition.key, "color"),
+            _ => panic!("Nested filter is not merged")
+        }
+        match &must[2] {
+            Condition::Field(condition) => assert_eq!(condition.key, "price"),
+            _ => panic!("Wrong condition order")
+        }
+    }
+
+    #[test]
+    fn test_compile_filter_with_estimations() {
+        let filter = Filter {
+            should: Some(vec![keyword_condition("color", "red"), keyword_condition("color", "blue")]),
+            must: Some(vec![keyword_condition("city", "Berlin"), range_condition("price"), keyword_condition("city", "Moscow")]),
+            min_should: None,
+            must_not: None,
+        };
+        // Cardinality is the length of the matched value, index serves only the `city` field
+        let estimator = |condition: &Condition| match condition {
+            Condition::Field(FieldCondition { key, r#match: Some(condition_match), .. }) => Some(ConditionEstimation {
+                cardinality: condition_match.keyword.as_ref().unwrap().len(),
+                index: if key == "city" { Some(0) } else { None },
+            }),
+            _ => None,
+        };
+
+        let plan = FilterPlan::compile_with(&filter, &estimator);
+
+        let keywords = |conditions: &[Condition]| conditions.iter()
+            .map(|condition| match condition {
+                Condition::Field(FieldCondition { r#match: Some(condition_match), .. }) => condition_match.keyword.clone().unwrap(),
+                Condition::Field(field_condition) => field_condition.key.clone(),
+                _ => panic!("Unexpected condition"),
+            })
+            .collect::<Vec<_>>();
+        // Most selective conditions are checked first in `must`, most matching in `should`
+        assert_eq!(keywords(plan.filter.must.as_ref().unwrap()), vec!["Berlin", "Moscow", "price"]);
+        assert_eq!(keywords(plan.filter.should.as_ref().unwrap()), vec!["blue", "red"]);
+
+        assert_eq!(plan.steps.len(), 5);
+        let berlin = match keyword_condition("city", "Berlin") {
+            Condition::Field(condition) => condition,
+            _ => unreachable!(),
+        };
+        assert_eq!(plan.step_estimation(&berlin), Some(ConditionEstimation { cardinality: 6, index: Some(0) }));
+    }
+
+    #[test]
+    fn test_plan_cache() {
+        let cache = FilterPlanCache::new(2);
+        let filter1 = Filter::new_must(keyword_condition("color", "red"));
+        let filter2 = Filter::new_must(keyword_condition("color", "blue"));
+        let filter3 = Filter::new_must(keyword_condition("color", "green"));
+
+        let plan1 = cache.get_or_compile(&filter1);
+        let plan1_cached = cache.get_or_compile(&filter1);
+        assert!(Arc::ptr_eq(&plan1, &plan1_cached));
+
+        let plan2 = cache.get_or_compile(&filter2);
+        assert_eq!(cache.len(), 2);
+        // Least recently used plan is evicted
+        cache.get_or_compile(&filter1);
+        cache.get_or_compile(&filter3);
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&plan1, &cache.get_or_compile(&filter1)));
+        assert!(!Arc::ptr_eq(&plan2, &cache.get_or_compile(&filter2)));
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+}
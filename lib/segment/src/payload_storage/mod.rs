@@ -1,6 +1,7 @@
 pub mod query_checker;
 pub mod simple_payload_storage;
 pub mod payload_storage;
+pub mod filter_plan;
 mod condition_checker;
 
 
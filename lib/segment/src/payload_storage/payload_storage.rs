@@ -1,6 +1,9 @@
 
-use crate::types::{PointOffsetType, PayloadKeyType, PayloadType, Filter, TheMap, PayloadSchemaType};
+use crate::types::{PointOffsetType, PayloadKeyType, PayloadType, Filter, TheMap, PayloadSchemaType, PayloadMigration};
 use crate::entry::entry_point::OperationResult;
+use crate::payload_storage::filter_plan::FilterPlan;
+use std::sync::Arc;
+use std::path::Path;
 
 
 /// Trait for payload data storage. Should allow filter checks
@@ -24,6 +27,25 @@ pub trait PayloadStorage {
     /// Delete payload by key
     fn delete(&mut self, point_id: PointOffsetType, key: &PayloadKeyType) -> OperationResult<Option<PayloadType>>;
 
+    /// Check that values of `key` could be moved into `new_key`, converting them into `convert_to` type if specified.
+    /// Key can't be renamed into an existing one.
+    /// Returns type of the migrated values or `None` if no point has the key.
+    fn check_migration(&self,
+                       key: &PayloadKeyType,
+                       new_key: &PayloadKeyType,
+                       convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>>;
+
+    /// Register type of the new key, so values of other types are rejected while the points are migrated
+    fn start_migration(&mut self, migration: &PayloadMigration);
+
+    /// Move value of the migrated key of the point into the new key.
+    /// Returns the new value, if the point has the migrated key
+    fn migrate_point(&mut self, point_id: PointOffsetType, migration: &PayloadMigration) -> OperationResult<Option<PayloadType>>;
+
+    /// Update schema once all points are migrated
+    fn finish_migration(&mut self, migration: &PayloadMigration);
+
     /// Drop all payload of the point
     fn drop(&mut self, point_id: PointOffsetType) -> OperationResult<Option<TheMap<PayloadKeyType, PayloadType>>>;
 
@@ -33,9 +55,15 @@ pub trait PayloadStorage {
     /// Force persistence of current storage state.
     fn flush(&self) -> OperationResult<()>;
 
+    /// Write a copy of the persisted storage into a new directory, sharing immutable files with the original
+    fn fork(&self, path: &Path) -> OperationResult<()>;
+
     /// Get payload schema, automatically generated from payload
     fn schema(&self) -> TheMap<PayloadKeyType, PayloadSchemaType>;
 
+    /// Estimated memory, occupied by the payload
+    fn memory_usage_bytes(&self) -> usize;
+
     /// Iterate all point ids with payload
     fn iter_ids(&self) -> Box<dyn Iterator<Item=PointOffsetType> + '_>;
 }
@@ -44,4 +72,15 @@ pub trait PayloadStorage {
 pub trait ConditionChecker {
     /// Check if point satisfies filter condition
     fn check(&self, point_id: PointOffsetType, query: &Filter) -> bool;
+
+    /// Check if point satisfies pre-compiled filter plan
+    fn check_plan(&self, point_id: PointOffsetType, plan: &FilterPlan) -> bool {
+        self.check(point_id, &plan.filter)
+    }
+
+    /// Prepare filter for checking multiple points.
+    /// Should be called once per query, not per point.
+    fn plan(&self, query: &Filter) -> Arc<FilterPlan> {
+        Arc::new(FilterPlan::compile(query))
+    }
 }
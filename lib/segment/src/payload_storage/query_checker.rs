@@ -1,12 +1,41 @@
 use crate::payload_storage::payload_storage::{ConditionChecker};
-use crate::types::{Filter, PayloadKeyType, PayloadType, Condition, TheMap, PointOffsetType};
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::types::{Filter, PayloadKeyType, PayloadType, Condition, TheMap, PointOffsetType, TextAnalyzerConfig, MinShould};
 use crate::payload_storage::simple_payload_storage::SimplePayloadStorage;
 use std::sync::Arc;
-use atomic_refcell::AtomicRefCell;
+use crate::common::rw_cell::RwCell;
 use crate::id_mapper::id_mapper::IdMapper;
-use crate::payload_storage::condition_checker::{match_payload, match_range, match_geo_radius, match_geo};
+use crate::payload_storage::condition_checker::{match_payload, match_range, match_geo_radius, match_geo, match_text};
+use crate::index::field_index::text_analyzer::TextAnalyzer;
+use std::collections::HashMap;
+use crate::payload_storage::filter_plan::{FilterPlan, FilterPlanCache};
 
 
+/// Check if any condition of the filter or of its nested filters satisfies the predicate
+fn has_condition<F>(filter: &Filter, predicate: &F) -> bool
+    where F: Fn(&Condition) -> bool {
+    filter.should.iter().flatten()
+        .chain(filter.must.iter().flatten())
+        .chain(filter.min_should.iter().flat_map(|min_should| min_should.conditions.iter()))
+        .chain(filter.must_not.iter().flatten())
+        .any(|condition| match condition {
+            Condition::Filter(nested) => has_condition(nested, predicate),
+            _ => predicate(condition)
+        })
+}
+
+/// Reject filters with conditions, which could not be checked by this build of the segment.
+/// Geo radius conditions require the `geo` feature
+pub fn check_filter_support(filter: Option<&Filter>) -> OperationResult<()> {
+    let is_geo_radius = |condition: &Condition| matches!(condition, Condition::Field(field) if field.geo_radius.is_some());
+    match filter {
+        Some(filter) if !cfg!(feature = "geo") && has_condition(filter, &is_geo_radius) => Err(OperationError::WrongInput {
+            description: "Geo radius conditions are not supported, segment is built without `geo` feature".to_string()
+        }),
+        _ => Ok(())
+    }
+}
+
 fn check_condition<F>(checker: &F, condition: &Condition) -> bool
     where F: Fn(&Condition) -> bool {
     match condition {
@@ -19,6 +48,7 @@ fn check_filter<F>(checker: &F, filter: &Filter) -> bool
     where F: Fn(&Condition) -> bool {
     return check_should(checker, &filter.should)
         && check_must(checker, &filter.must)
+        && check_min_should(checker, &filter.min_should)
         && check_must_not(checker, &filter.must_not);
 }
 
@@ -31,6 +61,28 @@ fn check_should<F>(checker: &F, should: &Option<Vec<Condition>>) -> bool
     }
 }
 
+fn check_min_should<F>(checker: &F, min_should: &Option<MinShould>) -> bool
+    where F: Fn(&Condition) -> bool {
+    match min_should {
+        None => true,
+        Some(MinShould { conditions, min_count }) => {
+            let mut matched = 0;
+            for (checked, condition) in conditions.iter().enumerate() {
+                if matched >= *min_count {
+                    break;
+                }
+                // Not enough conditions left to reach `min_count`
+                if matched + (conditions.len() - checked) < *min_count {
+                    return false;
+                }
+                if check_condition(checker, condition) {
+                    matched += 1;
+                }
+            }
+            matched >= *min_count
+        }
+    }
+}
 
 fn check_must<F>(checker: &F, must: &Option<Vec<Condition>>) -> bool
     where F: Fn(&Condition) -> bool {
@@ -52,18 +104,31 @@ fn check_must_not<F>(checker: &F, must: &Option<Vec<Condition>>) -> bool
 
 
 pub struct SimpleConditionChecker {
-    payload_storage: Arc<AtomicRefCell<SimplePayloadStorage>>,
-    id_mapper: Arc<AtomicRefCell<dyn IdMapper>>,
+    payload_storage: Arc<RwCell<SimplePayloadStorage>>,
+    id_mapper: Arc<RwCell<dyn IdMapper>>,
+    plan_cache: FilterPlanCache,
+    text_analyzers: HashMap<PayloadKeyType, TextAnalyzer>,
+    default_text_analyzer: TextAnalyzer,
 }
 
 impl SimpleConditionChecker {
-    pub fn new(payload_storage: Arc<AtomicRefCell<SimplePayloadStorage>>,
-               id_mapper: Arc<AtomicRefCell<dyn IdMapper>>) -> Self {
+    pub fn new(payload_storage: Arc<RwCell<SimplePayloadStorage>>,
+               id_mapper: Arc<RwCell<dyn IdMapper>>,
+               text_analyzers: &HashMap<PayloadKeyType, TextAnalyzerConfig>) -> Self {
         SimpleConditionChecker {
             payload_storage,
             id_mapper,
+            plan_cache: FilterPlanCache::default(),
+            text_analyzers: text_analyzers.iter()
+                .map(|(field, config)| (field.clone(), TextAnalyzer::new(config)))
+                .collect(),
+            default_text_analyzer: TextAnalyzer::default(),
         }
     }
+
+    fn text_analyzer(&self, field: &PayloadKeyType) -> &TextAnalyzer {
+        self.text_analyzers.get(field).unwrap_or(&self.default_text_analyzer)
+    }
 }
 
 // Uncomment when stabilized
@@ -89,6 +154,10 @@ impl ConditionChecker for SimpleConditionChecker
                         let mut res = false;
                         // ToDo: Convert onto iterator over checkers, so it would be impossible to forget a condition
                         res = res || field_condition.r#match.as_ref().map(|condition| match_payload(p, condition)).unwrap_or(false);
+                        res = res || field_condition.r#match.as_ref()
+                            .and_then(|condition| condition.text.as_ref())
+                            .map(|text| match_text(p, text, self.text_analyzer(&field_condition.key)))
+                            .unwrap_or(false);
                         res = res || field_condition.range.as_ref().map(|condition| match_range(p, condition)).unwrap_or(false);
                         res = res || field_condition.geo_radius.as_ref().map(|condition| match_geo_radius(p, condition)).unwrap_or(false);
                         res = res || field_condition.geo_bounding_box.as_ref().map(|condition| match_geo(p, condition)).unwrap_or(false);
@@ -108,12 +177,16 @@ impl ConditionChecker for SimpleConditionChecker
 
         check_filter(&checker, query)
     }
+
+    fn plan(&self, query: &Filter) -> Arc<FilterPlan> {
+        self.plan_cache.get_or_compile(query)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{PayloadType, FieldCondition, Match, GeoBoundingBox, Range};
+    use crate::types::{PayloadType, FieldCondition, Match, GeoBoundingBox, Range, PointIdType};
     use crate::types::GeoPoint;
     use std::collections::HashSet;
     use tempdir::TempDir;
@@ -137,15 +210,16 @@ mod tests {
         let mut payload_storage = SimplePayloadStorage::open(dir.path()).unwrap();
         let mut id_mapper = SimpleIdMapper::open(dir_id_mapper.path()).unwrap();
 
-        id_mapper.set_link(0, 0).unwrap();
-        id_mapper.set_link(1, 1).unwrap();
-        id_mapper.set_link(2, 2).unwrap();
-        id_mapper.set_link(10, 10).unwrap();
+        id_mapper.set_link(0.into(), 0).unwrap();
+        id_mapper.set_link(1.into(), 1).unwrap();
+        id_mapper.set_link(2.into(), 2).unwrap();
+        id_mapper.set_link(10.into(), 10).unwrap();
         payload_storage.assign_all(0, payload).unwrap();
 
         let payload_checker = SimpleConditionChecker::new(
-            Arc::new(AtomicRefCell::new(payload_storage)),
-            Arc::new(AtomicRefCell::new(id_mapper)),
+            Arc::new(RwCell::new(payload_storage)),
+            Arc::new(RwCell::new(id_mapper)),
+            &HashMap::new(),
         );
 
         let match_red = Condition::Field(FieldCondition {
@@ -153,6 +227,7 @@ mod tests {
             r#match: Some(Match {
                 keyword: Some("red".to_owned()),
                 integer: None,
+                text: None,
             }),
             range: None,
             geo_bounding_box: None,
@@ -164,6 +239,7 @@ mod tests {
             r#match: Some(Match {
                 keyword: Some("blue".to_owned()),
                 integer: None,
+                text: None,
             }),
             range: None,
             geo_bounding_box: None,
@@ -175,6 +251,7 @@ mod tests {
             r#match: Some(Match {
                 keyword: None,
                 integer: Some(1),
+                text: None,
             }),
             range: None,
             geo_bounding_box: None,
@@ -219,6 +296,7 @@ mod tests {
         let query = Filter {
             should: None,
             must: Some(vec![match_red.clone()]),
+            min_should: None,
             must_not: None,
         };
         assert!(payload_checker.check(0, &query));
@@ -226,6 +304,7 @@ mod tests {
         let query = Filter {
             should: None,
             must: Some(vec![match_blue.clone()]),
+            min_should: None,
             must_not: None,
         };
         assert!(!payload_checker.check(0, &query));
@@ -233,6 +312,7 @@ mod tests {
         let query = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![match_blue.clone()]),
         };
         assert!(payload_checker.check(0, &query));
@@ -240,6 +320,7 @@ mod tests {
         let query = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![match_red.clone()]),
         };
         assert!(!payload_checker.check(0, &query));
@@ -247,6 +328,7 @@ mod tests {
         let query = Filter {
             should: Some(vec![match_red.clone(), match_blue.clone()]),
             must: Some(vec![with_delivery.clone(), in_berlin.clone()]),
+            min_should: None,
             must_not: None,
         };
         assert!(payload_checker.check(0, &query));
@@ -254,6 +336,7 @@ mod tests {
         let query = Filter {
             should: Some(vec![match_red.clone(), match_blue.clone()]),
             must: Some(vec![with_delivery.clone(), in_moscow.clone()]),
+            min_should: None,
             must_not: None,
         };
         assert!(!payload_checker.check(0, &query));
@@ -263,15 +346,18 @@ mod tests {
                 Condition::Filter(Filter {
                     should: None,
                     must: Some(vec![match_red.clone(), in_moscow.clone()]),
+                    min_should: None,
                     must_not: None,
                 }),
                 Condition::Filter(Filter {
                     should: None,
                     must: Some(vec![match_blue.clone(), in_berlin.clone()]),
+                    min_should: None,
                     must_not: None,
                 }),
             ]),
             must: None,
+            min_should: None,
             must_not: None,
         };
         assert!(!payload_checker.check(0, &query));
@@ -281,53 +367,82 @@ mod tests {
                 Condition::Filter(Filter {
                     should: None,
                     must: Some(vec![match_blue.clone(), in_moscow.clone()]),
+                    min_should: None,
                     must_not: None,
                 }),
                 Condition::Filter(Filter {
                     should: None,
                     must: Some(vec![match_red.clone(), in_berlin.clone()]),
+                    min_should: None,
                     must_not: None,
                 }),
             ]),
             must: None,
+            min_should: None,
+            must_not: None,
+        };
+        assert!(payload_checker.check(0, &query));
+
+        let query = Filter {
+            should: None,
+            must: None,
+            min_should: Some(MinShould {
+                conditions: vec![match_blue.clone(), match_red.clone(), with_delivery.clone(), in_moscow.clone()],
+                min_count: 2,
+            }),
             must_not: None,
         };
         assert!(payload_checker.check(0, &query));
 
+        let query = Filter {
+            should: None,
+            must: None,
+            min_should: Some(MinShould {
+                conditions: vec![match_blue.clone(), match_red.clone(), in_moscow.clone()],
+                min_count: 2,
+            }),
+            must_not: None,
+        };
+        assert!(!payload_checker.check(0, &query));
+
 
         let query = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![with_bad_rating.clone()]),
         };
         assert!(!payload_checker.check(0, &query));
 
 
-        let ids: HashSet<_> = vec![1, 2, 3].into_iter().collect();
+        let ids: HashSet<PointIdType> = vec![1, 2, 3].into_iter().map(|x| x.into()).collect();
 
 
         let query = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![Condition::HasId(ids.into())]),
         };
         assert!(!payload_checker.check(2, &query));
 
-        let ids: HashSet<_> = vec![1, 2, 3].into_iter().collect();
+        let ids: HashSet<PointIdType> = vec![1, 2, 3].into_iter().map(|x| x.into()).collect();
 
 
         let query = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![Condition::HasId(ids.into())]),
         };
         assert!(payload_checker.check(10, &query));
 
-        let ids: HashSet<_> = vec![1, 2, 3].into_iter().collect();
+        let ids: HashSet<PointIdType> = vec![1, 2, 3].into_iter().map(|x| x.into()).collect();
 
         let query = Filter {
             should: None,
             must: Some(vec![Condition::HasId(ids.into())]),
+            min_should: None,
             must_not: None,
         };
         assert!(payload_checker.check(2, &query));
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::path::Path;
-use crate::types::{PayloadKeyType, PayloadType, PointOffsetType, TheMap, PayloadSchemaType};
+use std::mem::size_of;
+use crate::types::{PayloadKeyType, PayloadType, PointOffsetType, TheMap, PayloadSchemaType, PayloadMigration, payload_memory_usage};
 
 use rocksdb::{DB, IteratorMode, Options};
 
 use crate::entry::entry_point::{OperationResult, OperationError};
 use crate::payload_storage::payload_storage::PayloadStorage;
+use crate::common::rocksdb_operations::{open_db, checkpoint_db};
 
 /// Since sled is used for reading only during the initialization, large read cache is not required
 const DB_CACHE_SIZE: usize = 10 * 1024 * 1024;
@@ -22,11 +24,20 @@ pub struct SimplePayloadStorage {
 
 impl SimplePayloadStorage {
     pub fn open(path: &Path) -> OperationResult<Self> {
+        Self::open_with_mode(path, false)
+    }
+
+    /// Open existing storage without ability to modify it
+    pub fn open_read_only(path: &Path) -> OperationResult<Self> {
+        Self::open_with_mode(path, true)
+    }
+
+    fn open_with_mode(path: &Path, read_only: bool) -> OperationResult<Self> {
         let mut options: Options = Options::default();
         options.set_write_buffer_size(DB_CACHE_SIZE);
         options.create_if_missing(true);
         options.create_missing_column_families(true);
-        let store = DB::open_cf(&options, path, vec![DB_NAME])?;
+        let store = open_db(&options, path, &[DB_NAME], read_only)?;
 
         let mut payload_map: HashMap<PointOffsetType, TheMap<PayloadKeyType, PayloadType>> = Default::default();
         let mut schema: TheMap<PayloadKeyType, PayloadSchemaType> = Default::default();
@@ -35,7 +46,11 @@ impl SimplePayloadStorage {
         for (key, val) in store.iterator_cf(cf_handle, IteratorMode::Start) {
             let point_id: PointOffsetType = serde_cbor::from_slice(&key).unwrap();
             let payload: TheMap<PayloadKeyType, PayloadType> = serde_cbor::from_slice(&val).unwrap();
-            SimplePayloadStorage::update_schema(&mut schema, &payload).unwrap();
+            // Values of different types are stored under the same key while it is converted by a payload migration.
+            // Type of the migration is registered once the segment is loaded.
+            for (key, value) in payload.iter() {
+                schema.entry(key.to_owned()).or_insert_with(|| value.into());
+            }
             payload_map.insert(point_id, payload);
         }
 
@@ -64,15 +79,6 @@ impl SimplePayloadStorage {
         }
     }
 
-    fn update_schema(
-        schema: &mut TheMap<PayloadKeyType, PayloadSchemaType>,
-        payload: &TheMap<PayloadKeyType, PayloadType>) -> OperationResult<()> {
-        for (key, value) in payload.iter() {
-            SimplePayloadStorage::update_schema_value(schema, key, value)?;
-        }
-        Ok(())
-    }
-
     fn update_storage(&self, point_id: &PointOffsetType) -> OperationResult<()> {
         let cf_handle = self.store.cf_handle(DB_NAME).unwrap();
         match self.payload.get(point_id) {
@@ -122,6 +128,65 @@ impl PayloadStorage for SimplePayloadStorage {
         Ok(res)
     }
 
+    fn check_migration(&self,
+                       key: &PayloadKeyType,
+                       new_key: &PayloadKeyType,
+                       convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>> {
+        if new_key != key && self.schema.contains_key(new_key) {
+            return Err(OperationError::WrongInput {
+                description: format!("Payload key {} already exists, {} can't be renamed into it", new_key, key)
+            });
+        }
+
+        let new_type = match self.schema.get(key) {
+            None => return Ok(None), // No points with this key
+            Some(key_type) => *convert_to.unwrap_or(key_type)
+        };
+
+        // Values are only checked here, they are converted later point by point
+        for point_payload in self.payload.values() {
+            if let Some(value) = point_payload.get(key) {
+                if value.convert(&new_type).is_none() {
+                    return Err(OperationError::TypeError {
+                        field_name: key.to_owned(),
+                        expected_type: format!("{:?}", new_type),
+                    });
+                }
+            }
+        }
+        Ok(Some(new_type))
+    }
+
+    fn start_migration(&mut self, migration: &PayloadMigration) {
+        self.schema.insert(migration.new_key.to_owned(), migration.new_type);
+    }
+
+    fn migrate_point(&mut self, point_id: PointOffsetType, migration: &PayloadMigration) -> OperationResult<Option<PayloadType>> {
+        let new_value = match self.payload.get_mut(&point_id) {
+            None => return Ok(None),
+            Some(point_payload) => migration.apply(point_payload)?
+        };
+        if new_value.is_some() {
+            self.update_storage(&point_id)?;
+        }
+        Ok(new_value)
+    }
+
+    fn finish_migration(&mut self, migration: &PayloadMigration) {
+        // Renamed key is only kept, if some points got it after the migration was started.
+        // New key is only kept, if some points had the migrated key.
+        for key in [&migration.key, &migration.new_key].iter() {
+            self.schema.remove(*key);
+            let key_type = self.payload.values()
+                .find_map(|point_payload| point_payload.get(*key))
+                .map(PayloadSchemaType::from);
+            if let Some(key_type) = key_type {
+                self.schema.insert(key.to_string(), key_type);
+            }
+        }
+    }
+
     fn drop(&mut self, point_id: PointOffsetType) -> OperationResult<Option<TheMap<PayloadKeyType, PayloadType>>> {
         let res = self.payload.remove(&point_id);
         self.update_storage(&point_id)?;
@@ -144,10 +209,20 @@ impl PayloadStorage for SimplePayloadStorage {
         Ok(self.store.flush_cf(cf_handle)?)
     }
 
+    fn fork(&self, path: &Path) -> OperationResult<()> {
+        checkpoint_db(&self.store, path)
+    }
+
     fn schema(&self) -> TheMap<PayloadKeyType, PayloadSchemaType> {
         return self.schema.clone()
     }
 
+    fn memory_usage_bytes(&self) -> usize {
+        self.payload.values()
+            .map(|payload| size_of::<PointOffsetType>() + payload_memory_usage(payload))
+            .sum()
+    }
+
     fn iter_ids(&self) -> Box<dyn Iterator<Item=PointOffsetType> + '_> {
         return Box::new(self.payload.keys().cloned())
     }
@@ -1,18 +1,21 @@
 use crate::types::{VectorElementType, Filter, SearchParams};
 use crate::vector_storage::vector_storage::ScoredPointOffset;
 use crate::entry::entry_point::OperationResult;
+use crate::common::stop_condition::StopCondition;
 
 /// Similar to `Index`, but should operate with multiple possible indexes + post-filtering
 pub trait QueryPlanner {
-    /// Performs search of vector in the most efficient way according to heuristics
+    /// Performs search of vector in the most efficient way according to heuristics.
+    /// Search stops early once `stop` condition is met, so the result might be incomplete
     fn search(&self,
               vector: &Vec<VectorElementType>,
               filter: Option<&Filter>,
               top: usize,
               params: Option<&SearchParams>,
+              stop: &StopCondition,
     ) -> Vec<ScoredPointOffset>;
 
 
     /// Force internal index rebuild.
-    fn build_index(&mut self) -> OperationResult<()>;
+    fn build_index(&mut self, stop: &StopCondition) -> OperationResult<()>;
 }
\ No newline at end of file
@@ -3,12 +3,13 @@ use crate::query_planner::query_planner::QueryPlanner;
 use crate::types::{Filter, VectorElementType, SearchParams};
 
 use crate::vector_storage::vector_storage::ScoredPointOffset;
-use atomic_refcell::AtomicRefCell;
+use crate::common::rw_cell::RwCell;
 use std::sync::Arc;
 use crate::entry::entry_point::OperationResult;
+use crate::common::stop_condition::StopCondition;
 
 pub struct SimpleQueryPlanner {
-    index: Arc<AtomicRefCell<dyn Index>>
+    index: Arc<RwCell<dyn Index>>
 }
 
 impl QueryPlanner for SimpleQueryPlanner {
@@ -17,17 +18,18 @@ impl QueryPlanner for SimpleQueryPlanner {
               filter: Option<&Filter>,
               top: usize,
               params: Option<&SearchParams>,
+              stop: &StopCondition,
     ) -> Vec<ScoredPointOffset> {
-        self.index.borrow().search(vector, filter, top, params)
+        self.index.borrow().search(vector, filter, top, params, stop)
     }
 
-    fn build_index(&mut self) -> OperationResult<()> {
-        self.index.borrow_mut().build_index()
+    fn build_index(&mut self, stop: &StopCondition) -> OperationResult<()> {
+        self.index.borrow_mut().build_index(stop)
     }
 }
 
 impl SimpleQueryPlanner {
-    pub fn new(index: Arc<AtomicRefCell<dyn Index>>) -> Self {
+    pub fn new(index: Arc<RwCell<dyn Index>>) -> Self {
         SimpleQueryPlanner {
             index
         }
@@ -2,37 +2,141 @@ use crate::id_mapper::id_mapper::IdMapper;
 use crate::vector_storage::vector_storage::VectorStorage;
 use crate::payload_storage::payload_storage::{PayloadStorage};
 use crate::entry::entry_point::{SegmentEntry, OperationResult, OperationError};
-use crate::types::{Filter, PayloadKeyType, PayloadType, SeqNumberType, VectorElementType, PointIdType, PointOffsetType, SearchParams, ScoredPoint, TheMap, SegmentInfo, SegmentType, SegmentConfig, SegmentState, PayloadSchemaInfo};
+use crate::types::{Filter, PayloadKeyType, PayloadType, SeqNumberType, VectorElementType, PointIdType, PointOffsetType, SearchParams, ScoredPoint, TheMap, SegmentInfo, SegmentType, SegmentConfig, SegmentState, SegmentStatus, SegmentDiskUsage, SegmentMemoryUsage, StorageType, PayloadSchemaInfo, PayloadIndexInfo, PayloadSchemaType, WithPayload, ConsistencyCheckMode, ConsistencyReport, BatchPoint, PayloadMigration};
+use std::collections::{HashMap, HashSet};
+use std::cmp::min;
 use crate::query_planner::query_planner::QueryPlanner;
-use std::sync::{Arc, Mutex};
-use atomic_refcell::{AtomicRefCell};
-use std::path::PathBuf;
-use std::fs::{remove_dir_all};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::common::point_locks::PointLocks;
+use crate::common::rw_cell::RwCell;
+use crate::common::stop_condition::StopCondition;
+use std::path::{Path, PathBuf};
+use std::fs::{remove_dir_all, create_dir_all};
 use std::io::Write;
 use atomicwrites::{AtomicFile, AllowOverwrite};
 use crate::index::index::PayloadIndex;
+use crate::payload_storage::query_checker::check_filter_support;
+use crate::common::file_operations::{dir_size, copy_dir, atomic_save_json};
+use crate::segment_constructor::segment_migrations::CURRENT_FORMAT_VERSION;
+use crate::segment_constructor::segment_constructor::load_segment;
+use crate::segment_constructor::new_segment_builder::NewSegmentBuilder;
+use std::mem::size_of;
+use uuid::Uuid;
+use crate::telemetry::{TelemetryCollector, ScopeDurationMeasurer, TelemetryOperation, SegmentTelemetry};
+use tracing::debug_span;
+use parking_lot::RwLock;
 
 
 pub const SEGMENT_STATE_FILE: &str = "segment.json";
+pub const ID_MAPPER_PATH: &str = "id_mapper";
+pub const PAYLOAD_STORAGE_PATH: &str = "payload_storage";
+pub const PAYLOAD_INDEX_PATH: &str = "payload_index";
+pub const VECTOR_STORAGE_PATH: &str = "vector_storage";
 
 /// Simple segment implementation
 pub struct Segment {
-    pub version: SeqNumberType,
+    /// Version of the last operation, applied to the segment. Updated by operations, which require only shared access
+    pub version: AtomicU64,
     pub persisted_version: Arc<Mutex<SeqNumberType>>,
+    /// Held for reading by operations, which require only shared access, for the whole operation.
+    /// Flush takes it for writing, so the saved version never covers partially written operations.
+    pub update_lock: RwLock<()>,
     pub current_path: PathBuf,
-    pub id_mapper: Arc<AtomicRefCell<dyn IdMapper>>,
-    pub vector_storage: Arc<AtomicRefCell<dyn VectorStorage>>,
-    pub payload_storage: Arc<AtomicRefCell<dyn PayloadStorage>>,
-    pub payload_index: Arc<AtomicRefCell<dyn PayloadIndex>>,
+    pub id_mapper: Arc<RwCell<dyn IdMapper>>,
+    pub vector_storage: Arc<RwCell<dyn VectorStorage>>,
+    pub payload_storage: Arc<RwCell<dyn PayloadStorage>>,
+    pub payload_index: Arc<RwCell<dyn PayloadIndex>>,
     /// User for writing only here.
-    pub query_planner: Arc<AtomicRefCell<dyn QueryPlanner>>,
+    pub query_planner: Arc<RwCell<dyn QueryPlanner>>,
     pub appendable_flag: bool,
     pub segment_type: SegmentType,
     pub segment_config: SegmentConfig,
+    pub telemetry: Arc<TelemetryCollector>,
+    /// Segment rejects all modifications and does not write anything on disk
+    pub read_only: bool,
+    /// Payload migrations, which are applied to the points in the background, ordered by version.
+    /// Held by point operations, so the background migration never changes the point concurrently.
+    pub payload_migrations: Mutex<Vec<PendingPayloadMigration>>,
+    /// Held by operations, which change a point under shared access, before the payload migrations
+    pub point_locks: PointLocks,
+}
+
+/// Payload migration, which is not applied to all points of the segment yet
+#[derive(Debug, Clone)]
+pub struct PendingPayloadMigration {
+    pub migration: PayloadMigration,
+    /// Points before this one are already visited by the background migration
+    pub next_point: Option<PointIdType>,
 }
 
 
 impl Segment {
+    /// Builder of a new empty segment, which will be created in a new sub-directory of the collection directory `path`
+    pub fn builder(path: &Path) -> NewSegmentBuilder {
+        NewSegmentBuilder::new(path)
+    }
+
+    /// Open existing segment. All parameters are read from the config, persisted in the segment directory
+    pub fn load(path: &Path) -> OperationResult<Segment> {
+        load_segment(path)
+    }
+
+    /// Create a writable copy of the segment in a new sub-directory of `dest_dir`.
+    /// Large immutable files are shared with the original segment using hard links, other files are copied,
+    /// so the copy could be used for experiments with the data (e.g. trying a different index config)
+    /// without duplicating all the vectors. Changes of the copy do not affect the original segment and vice versa.
+    ///
+    /// Updates of the original segment, which are in progress during the fork, might be included partially.
+    pub fn fork(&self, dest_dir: &Path) -> OperationResult<Segment> {
+        self.check_writable()?;
+        let fork_path = self.copy_into(dest_dir)?;
+        load_segment(&fork_path).map_err(|err| {
+            remove_dir_all(&fork_path).ok();
+            err
+        })
+    }
+
+    /// Save a copy of the segment into a new sub-directory of `snapshot_dir`, which could be loaded later
+    /// as a regular segment. Unlike `fork`, the copy is not opened, so read-only segments could be saved as well.
+    ///
+    /// Updates in progress might be included partially, same as with `fork`.
+    /// They should be re-applied from WAL after the snapshot is loaded.
+    pub fn take_snapshot(&self, snapshot_dir: &Path) -> OperationResult<PathBuf> {
+        self.copy_into(snapshot_dir)
+    }
+
+    fn copy_into(&self, dest_dir: &Path) -> OperationResult<PathBuf> {
+        let copy_path = dest_dir.join(Uuid::new_v4().to_string());
+        create_dir_all(&copy_path)?;
+
+        let copy = || -> OperationResult<()> {
+            // State is read first: point operations lock pending payload migrations before the components
+            let state = self.get_state();
+            // Writers never hold several component locks at once, so it is safe to lock all of them
+            let id_mapper = self.id_mapper.borrow();
+            let vector_storage = self.vector_storage.borrow();
+            let payload_storage = self.payload_storage.borrow();
+            let _payload_index = self.payload_index.borrow();
+
+            if !self.read_only {
+                vector_storage.flush()?;
+            }
+            vector_storage.fork(&copy_path.join(VECTOR_STORAGE_PATH))?;
+            payload_storage.fork(&copy_path.join(PAYLOAD_STORAGE_PATH))?;
+            id_mapper.fork(&copy_path.join(ID_MAPPER_PATH))?;
+            copy_dir(&self.current_path.join(PAYLOAD_INDEX_PATH), &copy_path.join(PAYLOAD_INDEX_PATH))?;
+            atomic_save_json(&copy_path.join(SEGMENT_STATE_FILE), &state)?;
+            Ok(())
+        };
+        match copy() {
+            Ok(()) => Ok(copy_path),
+            Err(err) => {
+                remove_dir_all(&copy_path).ok();
+                Err(err)
+            }
+        }
+    }
 
     fn update_vector(&mut self,
                      old_internal_id: PointOffsetType,
@@ -55,13 +159,98 @@ impl Segment {
         Ok(new_internal_index)
     }
 
-    fn skip_by_version(&mut self, op_num: SeqNumberType) -> bool {
-        return if self.version > op_num {
-            true
-        } else {
-            self.version = op_num;
-            false
+    fn skip_by_version(&self, op_num: SeqNumberType) -> bool {
+        // Version is only increased, so the operation is skipped if the segment already has a newer one
+        self.version() > op_num
+    }
+
+    /// Per-point version check. Point is only changed by operations, which are not older than the last one applied to it.
+    /// Operations with the same version are applied, because single operation might consist of several point updates.
+    /// If the point is not in the segment, the operation is applied, as operations with different points
+    /// might be applied to the same segment out of order.
+    fn skip_point_by_version(&self, op_num: SeqNumberType, point_id: PointIdType) -> bool {
+        match self.id_mapper.borrow().point_version(point_id) {
+            Some(point_version) => point_version > op_num,
+            None => false
+        }
+    }
+
+    /// Segment version is increased only after all changes of the operation are written,
+    /// so a flush never persists the version of an operation without its data.
+    fn bump_version(&self, op_num: SeqNumberType) {
+        self.version.fetch_max(op_num, Ordering::SeqCst);
+    }
+
+    /// Continue migrations, which are not finished yet, e.g. once the segment is loaded.
+    /// Already registered migrations are ignored. Returns true if any migration is added.
+    pub fn resume_payload_migrations(&self, migrations: Vec<PayloadMigration>) -> bool {
+        let mut pending = self.payload_migrations.lock().unwrap();
+        let mut added = false;
+        for migration in migrations {
+            if pending.iter().any(|known| known.migration.version == migration.version) {
+                continue;
+            }
+            self.payload_storage.borrow_mut().start_migration(&migration);
+            self.payload_index.borrow_mut().start_migration(&migration);
+            pending.push(PendingPayloadMigration { migration, next_point: None });
+            added = true;
+        }
+        pending.sort_by_key(|known| known.migration.version);
+        added
+    }
+
+    /// Register migrations, requested by an operation or continued from other segments.
+    /// Migrated points are persisted independently of the segment flush,
+    /// so the list of migrations is saved right away to be continued after a restart.
+    pub fn register_payload_migrations(&self, migrations: Vec<PayloadMigration>) -> OperationResult<()> {
+        self.check_writable()?;
+        if self.resume_payload_migrations(migrations) {
+            self.save_current_state()?;
+        }
+        Ok(())
+    }
+
+    /// Payload migrations, which are not applied to all points of the segment yet
+    pub fn payload_migrations(&self) -> Vec<PayloadMigration> {
+        self.payload_migrations.lock().unwrap().iter()
+            .map(|pending| pending.migration.clone())
+            .collect()
+    }
+
+    /// Apply pending migrations, requested not later than `op_num`, to the point before it is changed by the operation,
+    /// so the operation sees the migrated payload.
+    /// Returned guard should be held until the operation is finished, otherwise the background migration
+    /// might be applied to the values written by the operation.
+    fn migrate_point_payload(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<Option<MutexGuard<Vec<PendingPayloadMigration>>>> {
+        let migrations = self.payload_migrations.lock().unwrap();
+        if migrations.is_empty() {
+            // Migrations are only registered by exclusive operations, so the list can't change meanwhile
+            return Ok(None);
+        }
+        let (point_version, internal_id) = {
+            let id_mapper = self.id_mapper.borrow();
+            (id_mapper.point_version(point_id).unwrap_or(0), id_mapper.internal_id(point_id))
         };
+        if let Some(internal_id) = internal_id {
+            let mut migrated_version = None;
+            for pending in migrations.iter()
+                .filter(|pending| pending.migration.version > point_version && pending.migration.version <= op_num) {
+                self.payload_storage.borrow_mut().migrate_point(internal_id, &pending.migration)?;
+                migrated_version = Some(pending.migration.version);
+            }
+            if let Some(migrated_version) = migrated_version {
+                self.id_mapper.borrow_mut().set_point_version(point_id, migrated_version)?;
+            }
+        }
+        Ok(Some(migrations))
+    }
+
+    fn check_writable(&self) -> OperationResult<()> {
+        if self.read_only {
+            Err(OperationError::ReadOnlyError)
+        } else {
+            Ok(())
+        }
     }
 
     fn lookup_internal_id(&self, point_id: PointIdType) -> OperationResult<PointOffsetType> {
@@ -74,8 +263,10 @@ impl Segment {
 
     fn get_state(&self) -> SegmentState {
         SegmentState {
-            version: self.version,
+            version: self.version(),
             config: self.segment_config.clone(),
+            format_version: CURRENT_FORMAT_VERSION,
+            payload_migrations: self.payload_migrations(),
         }
     }
 
@@ -92,18 +283,107 @@ impl Segment {
     pub fn save_current_state(&self) -> OperationResult<()> {
         self.save_state(&self.get_state())
     }
+
+    /// Validate agreement between vector storage, id mapper, payload storage and payload index.
+    /// In `Repair` mode dangling id mappings, orphan vectors and payloads are dropped
+    /// and inconsistent field indexes are rebuilt.
+    pub fn check_consistency(&mut self, mode: ConsistencyCheckMode) -> OperationResult<ConsistencyReport> {
+        let mut report = ConsistencyReport::default();
+        let repair = mode == ConsistencyCheckMode::Repair;
+        if repair {
+            self.check_writable()?;
+        }
+
+        report.problems.extend(self.vector_storage.borrow().check_consistency());
+
+        let mut dangling_links: Vec<PointIdType> = vec![];
+        let mut orphan_vectors: Vec<PointOffsetType> = vec![];
+        let mut orphan_payloads: Vec<PointOffsetType> = vec![];
+        {
+            let id_mapper = self.id_mapper.borrow();
+            let vector_storage = self.vector_storage.borrow();
+            let payload_storage = self.payload_storage.borrow();
+
+            for (external_id, internal_id) in id_mapper.iter_from(None) {
+                if id_mapper.external_id(internal_id) != Some(external_id) {
+                    report.problems.push(format!("Point {} is mapped to offset {}, which is mapped to {:?}",
+                                                 external_id, internal_id, id_mapper.external_id(internal_id)));
+                }
+                if vector_storage.get_vector(internal_id).is_none() {
+                    report.problems.push(format!("Point {} is mapped to missing vector {}", external_id, internal_id));
+                    dangling_links.push(external_id);
+                }
+            }
+
+            for internal_id in vector_storage.iter_ids() {
+                let is_linked = id_mapper.external_id(internal_id)
+                    .and_then(|external_id| id_mapper.internal_id(external_id)) == Some(internal_id);
+                if !is_linked {
+                    report.problems.push(format!("Vector {} is not mapped to any point", internal_id));
+                    orphan_vectors.push(internal_id);
+                }
+            }
+
+            for internal_id in payload_storage.iter_ids() {
+                if vector_storage.get_vector(internal_id).is_none() {
+                    report.problems.push(format!("Payload of offset {} does not belong to any vector", internal_id));
+                    orphan_payloads.push(internal_id);
+                }
+            }
+        }
+
+        if repair {
+            for external_id in dangling_links {
+                self.id_mapper.borrow_mut().drop(external_id)?;
+                report.repaired += 1;
+            }
+            for internal_id in orphan_vectors {
+                self.vector_storage.borrow_mut().delete(internal_id)?;
+                self.payload_storage.borrow_mut().drop(internal_id)?;
+                report.repaired += 1;
+            }
+            for internal_id in orphan_payloads {
+                self.payload_storage.borrow_mut().drop(internal_id)?;
+                report.repaired += 1;
+            }
+        }
+
+        // Indexes are checked after repair of the payload storage, so they are rebuilt from valid data
+        let inconsistent_fields = self.payload_index.borrow().inconsistent_fields()?;
+        for field in inconsistent_fields {
+            report.problems.push(format!("Index of field `{}` does not match payload storage", field));
+            if repair {
+                self.payload_index.borrow_mut().rebuild_field_index(&field)?;
+                report.repaired += 1;
+            }
+        }
+
+        if report.repaired > 0 {
+            // Repair does not change segment version, so regular flush would skip it
+            self.vector_storage.borrow().flush()?;
+            self.payload_storage.borrow().flush()?;
+            self.id_mapper.borrow().flush()?;
+        }
+
+        Ok(report)
+    }
 }
 
 
 impl SegmentEntry for Segment {
-    fn version(&self) -> SeqNumberType { self.version }
+    fn version(&self) -> SeqNumberType { self.version.load(Ordering::SeqCst) }
 
     fn search(&self,
               vector: &Vec<VectorElementType>,
+              with_payload: &WithPayload,
+              with_vector: bool,
               filter: Option<&Filter>,
               top: usize,
               params: Option<&SearchParams>,
+              stop: &StopCondition,
     ) -> OperationResult<Vec<ScoredPoint>> {
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Search);
+        check_filter_support(filter)?;
         let expected_vector_dim = self.vector_storage.borrow().vector_dim();
         if expected_vector_dim != vector.len() {
             return Err(OperationError::WrongVector {
@@ -112,32 +392,81 @@ impl SegmentEntry for Segment {
             });
         }
 
-        let internal_result = self.query_planner.borrow().search(vector, filter, top, params);
-
+        let internal_result = {
+            let _span = debug_span!("index_search", top, filtered = filter.is_some()).entered();
+            self.query_planner.borrow().search(vector, filter, top, params, stop)
+        };
+        // Search is interrupted by the condition, so found points are not the closest ones
+        stop.check()?;
 
+        let _span = debug_span!("payload_hydration", points = internal_result.len(), with_payload = with_payload.enable).entered();
+        let segment_version = self.version();
+        let payload_migrations = self.payload_migrations();
         let id_mapper = self.id_mapper.borrow();
+        let payload_storage = self.payload_storage.borrow();
+        let vector_storage = self.vector_storage.borrow();
         let res = internal_result.iter()
-            .map(|&scored_point_offset|
-                (
-                    ScoredPoint {
-                        id: id_mapper
-                            .external_id(scored_point_offset.idx)
-                            .unwrap_or_else(|| panic!("Corrupter id_mapper, no external value for {}", scored_point_offset.idx)),
-                        score: scored_point_offset.score,
+            .map(|&scored_point_offset| {
+                let point_id = id_mapper
+                    .external_id(scored_point_offset.idx)
+                    .unwrap_or_else(|| panic!("Corrupter id_mapper, no external value for {}", scored_point_offset.idx));
+                // Version is read before the payload, so the background migration is never missed
+                let point_version = id_mapper.point_version(point_id);
+                let payload = if with_payload.enable {
+                    let mut payload = payload_storage.payload(scored_point_offset.idx);
+                    PayloadMigration::apply_pending(&payload_migrations, point_version.unwrap_or(0), &mut payload);
+                    match &with_payload.payload_selector {
+                        None => Some(payload),
+                        Some(selector) => Some(selector.process(payload))
                     }
-                )
-            ).collect();
+                } else {
+                    None
+                };
+                ScoredPoint {
+                    id: point_id,
+                    version: point_version.unwrap_or(segment_version),
+                    score: scored_point_offset.score,
+                    payload,
+                    vector: if with_vector { vector_storage.get_vector(scored_point_offset.idx) } else { None },
+                }
+            }).collect::<Vec<_>>();
+        self.telemetry.add_search_results(res.len(), filter.is_some());
         return Ok(res);
     }
 
+    fn count(&self, filter: Option<&Filter>, exact: bool, stop: &StopCondition) -> OperationResult<usize> {
+        check_filter_support(filter)?;
+        let total = self.vectors_count();
+        let filter = match filter {
+            None => return Ok(total),
+            Some(filter) => filter
+        };
+        let payload_index = self.payload_index.borrow();
+        if exact {
+            let id_mapper = self.id_mapper.borrow();
+            // Field indexes might still contain points, which are already deleted
+            let count = stop.take_until_stopped(payload_index.query_points(filter))
+                .filter(|internal_id| id_mapper.external_id(*internal_id).is_some())
+                .count();
+            stop.check()?;
+            Ok(count)
+        } else {
+            Ok(min(payload_index.estimate_cardinality(filter).exp, total))
+        }
+    }
+
     fn upsert_point(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>,
     ) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); }
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Upsert);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
 
         let vector_dim = self.vector_storage.borrow().vector_dim();
         if vector_dim != vector.len() {
             return Err(OperationError::WrongVector { expected_dim: vector_dim, received_dim: vector.len() });
         }
+        // Payload is kept, so it should be migrated before the version of the point is updated
+        self.migrate_point_payload(op_num, point_id)?;
 
         let stored_internal_point = {
             let id_mapped = self.id_mapper.borrow();
@@ -151,83 +480,404 @@ impl SegmentEntry for Segment {
                 (false, self.vector_storage.borrow_mut().put_vector(vector)?)
         };
 
-        self.id_mapper.borrow_mut().set_link(point_id, new_index)?;
+        {
+            let mut id_mapper = self.id_mapper.borrow_mut();
+            id_mapper.set_link(point_id, new_index)?;
+            id_mapper.set_point_version(point_id, op_num)?;
+        }
+        self.bump_version(op_num);
         Ok(was_replaced)
     }
 
-    fn delete_point(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); };
-        let mut mapper = self.id_mapper.borrow_mut();
-        let internal_id = mapper.internal_id(point_id);
-        match internal_id {
+    fn upsert_batch(&mut self, op_num: SeqNumberType, points: &[BatchPoint]) -> OperationResult<usize> {
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Upsert);
+
+        let vector_dim = self.vector_storage.borrow().vector_dim();
+        if let Some((_, vector, _)) = points.iter().find(|(_, vector, _)| vector.len() != vector_dim) {
+            return Err(OperationError::WrongVector { expected_dim: vector_dim, received_dim: vector.len() });
+        }
+
+        // Only the last occurrence of each point is applied
+        let mut seen_points: HashSet<PointIdType> = HashSet::new();
+        let mut batch: Vec<&BatchPoint> = points.iter().rev()
+            .filter(|(point_id, _, _)| seen_points.insert(*point_id))
+            .filter(|(point_id, _, _)| !self.skip_point_by_version(op_num, *point_id))
+            .collect();
+        batch.reverse();
+
+        // Kept payloads should be migrated before the versions of the points are updated
+        for (point_id, _, payload) in batch.iter() {
+            if payload.is_none() {
+                self.migrate_point_payload(op_num, *point_id)?;
+            }
+        }
+
+        let stored_internal_ids: Vec<Option<PointOffsetType>> = {
+            let id_mapper = self.id_mapper.borrow();
+            batch.iter().map(|(point_id, _, _)| id_mapper.internal_id(*point_id)).collect()
+        };
+
+        // Vectors and payloads are staged first. Point becomes visible with the new data only when
+        // the id mapper is updated, so readers never observe the new vector without its payload or vice versa.
+        // Existing points with new payload are written into a new offset (copy-on-write),
+        // the old offset is released after the switch.
+        let new_internal_ids = {
+            let mut vector_storage = self.vector_storage.borrow_mut();
+            batch.iter().zip(stored_internal_ids.iter())
+                .map(|((_, vector, payload), stored_internal_id)| match (stored_internal_id, payload) {
+                    (Some(internal_id), None) => vector_storage.update_vector(*internal_id, vector),
+                    _ => vector_storage.put_vector(vector),
+                })
+                .collect::<OperationResult<Vec<PointOffsetType>>>()?
+        };
+
+        // Offsets, which are not used by the points anymore
+        let released_internal_ids: Vec<PointOffsetType> = stored_internal_ids.iter()
+            .zip(new_internal_ids.iter())
+            .filter_map(|(stored_internal_id, new_internal_id)| match stored_internal_id {
+                Some(internal_id) if internal_id != new_internal_id => Some(*internal_id),
+                _ => None
+            })
+            .collect();
+
+        {
+            let mut payload_storage = self.payload_storage.borrow_mut();
+            for (((_, _, payload), stored_internal_id), new_internal_id) in batch.iter()
+                .zip(stored_internal_ids.iter())
+                .zip(new_internal_ids.iter()) {
+                match (payload, stored_internal_id) {
+                    (Some(payload), _) => payload_storage.assign_all(*new_internal_id, payload.clone())?,
+                    (None, Some(internal_id)) if internal_id != new_internal_id => {
+                        let moved_payload = payload_storage.payload(*internal_id);
+                        payload_storage.assign_all(*new_internal_id, moved_payload)?
+                    }
+                    (None, _) => ()
+                }
+            }
+        }
+
+        {
+            let mut id_mapper = self.id_mapper.borrow_mut();
+            for ((point_id, _, _), new_internal_id) in batch.iter().zip(new_internal_ids.iter()) {
+                id_mapper.set_link(*point_id, *new_internal_id)?;
+                id_mapper.set_point_version(*point_id, op_num)?;
+            }
+        }
+
+        for internal_id in released_internal_ids {
+            self.vector_storage.borrow_mut().delete(internal_id)?;
+            self.payload_storage.borrow_mut().drop(internal_id)?;
+        }
+        self.bump_version(op_num);
+        Ok(batch.len())
+    }
+
+    fn update_point_vector(&mut self, op_num: SeqNumberType, point_id: PointIdType, vector: &Vec<VectorElementType>,
+    ) -> OperationResult<bool> {
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Upsert);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); }
+
+        let vector_dim = self.vector_storage.borrow().vector_dim();
+        if vector_dim != vector.len() {
+            return Err(OperationError::WrongVector { expected_dim: vector_dim, received_dim: vector.len() });
+        }
+
+        self.migrate_point_payload(op_num, point_id)?;
+        let internal_id = self.lookup_internal_id(point_id)?;
+        let new_index = self.update_vector(internal_id, vector)?;
+
+        {
+            let mut id_mapper = self.id_mapper.borrow_mut();
+            if new_index != internal_id {
+                id_mapper.set_link(point_id, new_index)?;
+            }
+            id_mapper.set_point_version(point_id, op_num)?;
+        }
+        self.bump_version(op_num);
+        Ok(true)
+    }
+
+    fn delete_point(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Delete);
+        let _update_guard = self.update_lock.read_recursive();
+        let _point_guard = self.point_locks.lock(point_id);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); };
+        let _migration_guard = self.migrate_point_payload(op_num, point_id)?;
+        // Only one component is locked for writing at a time, so concurrent searches are not blocked
+        let internal_id = self.id_mapper.borrow().internal_id(point_id);
+        let deleted = match internal_id {
             Some(internal_id) => {
                 self.vector_storage.borrow_mut().delete(internal_id)?;
-                mapper.drop(point_id)?;
-                Ok(true)
+                self.id_mapper.borrow_mut().drop(point_id)?;
+                true
             }
-            None => Ok(false)
+            None => false
+        };
+        // Tombstone prevents older operations from inserting the point again during WAL recovery
+        self.id_mapper.borrow_mut().set_point_version(point_id, op_num)?;
+        self.bump_version(op_num);
+        Ok(deleted)
+    }
+
+    fn delete_filtered(&self, op_num: SeqNumberType, filter: &Filter) -> OperationResult<usize> {
+        self.check_writable()?;
+        check_filter_support(Some(filter))?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Delete);
+        // Versions are checked for each point, so points inserted by older operations are still deleted
+        let _update_guard = self.update_lock.read_recursive();
+        // Resolve all matched points first, index can't be used while points are deleted
+        let matched_points: Vec<PointIdType> = {
+            let id_mapper = self.id_mapper.borrow();
+            let payload_index = self.payload_index.borrow();
+            payload_index.query_points(filter)
+                .filter_map(|internal_id| id_mapper.external_id(internal_id))
+                .collect()
+        };
+        let mut deleted_points = 0;
+        for point_id in matched_points {
+            deleted_points += self.delete_point(op_num, point_id)? as usize;
         }
+        self.bump_version(op_num);
+        Ok(deleted_points)
     }
 
-    fn set_full_payload(&mut self,
+    fn set_full_payload(&self,
                         op_num: SeqNumberType,
                         point_id: PointIdType,
                         full_payload: TheMap<PayloadKeyType, PayloadType>,
     ) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); };
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Payload);
+        let _update_guard = self.update_lock.read_recursive();
+        let _point_guard = self.point_locks.lock(point_id);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); };
+        let _migration_guard = self.migrate_point_payload(op_num, point_id)?;
         let internal_id = self.lookup_internal_id(point_id)?;
         self.payload_storage.borrow_mut().assign_all(internal_id, full_payload)?;
+        self.id_mapper.borrow_mut().set_point_version(point_id, op_num)?;
+        self.bump_version(op_num);
         Ok(true)
     }
 
-    fn set_payload(&mut self,
+    fn set_payload(&self,
                    op_num: SeqNumberType,
                    point_id: PointIdType,
                    key: &PayloadKeyType,
                    payload: PayloadType,
     ) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); };
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Payload);
+        let _update_guard = self.update_lock.read_recursive();
+        let _point_guard = self.point_locks.lock(point_id);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); };
+        let _migration_guard = self.migrate_point_payload(op_num, point_id)?;
         let internal_id = self.lookup_internal_id(point_id)?;
         self.payload_storage.borrow_mut().assign(internal_id, key, payload)?;
+        self.id_mapper.borrow_mut().set_point_version(point_id, op_num)?;
+        self.bump_version(op_num);
         Ok(true)
     }
 
-    fn delete_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); };
+    fn delete_payload(&self, op_num: SeqNumberType, point_id: PointIdType, key: &PayloadKeyType) -> OperationResult<bool> {
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Payload);
+        let _update_guard = self.update_lock.read_recursive();
+        let _point_guard = self.point_locks.lock(point_id);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); };
+        let _migration_guard = self.migrate_point_payload(op_num, point_id)?;
         let internal_id = self.lookup_internal_id(point_id)?;
         self.payload_storage.borrow_mut().delete(internal_id, key)?;
+        self.id_mapper.borrow_mut().set_point_version(point_id, op_num)?;
+        self.bump_version(op_num);
         Ok(true)
     }
 
-    fn clear_payload(&mut self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
-        if self.skip_by_version(op_num) { return Ok(false); };
+    fn clear_payload(&self, op_num: SeqNumberType, point_id: PointIdType) -> OperationResult<bool> {
+        self.check_writable()?;
+        let _measurer = ScopeDurationMeasurer::new(&self.telemetry, TelemetryOperation::Payload);
+        let _update_guard = self.update_lock.read_recursive();
+        let _point_guard = self.point_locks.lock(point_id);
+        if self.skip_point_by_version(op_num, point_id) { return Ok(false); };
+        let _migration_guard = self.migrate_point_payload(op_num, point_id)?;
         let internal_id = self.lookup_internal_id(point_id)?;
         self.payload_storage.borrow_mut().drop(internal_id)?;
+        self.id_mapper.borrow_mut().set_point_version(point_id, op_num)?;
+        self.bump_version(op_num);
+        Ok(true)
+    }
+
+    fn migrate_payload_key(&mut self,
+                           op_num: SeqNumberType,
+                           key: &PayloadKeyType,
+                           new_key: &PayloadKeyType,
+                           convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<bool> {
+        self.check_writable()?;
+        // Migration is requested by an exclusive operation, so it is already registered if the segment is newer
+        if self.skip_by_version(op_num) { return Ok(false); };
+        // Registration is persisted before the segment version, e.g. if the segment was not flushed before a restart
+        let is_registered = self.payload_migrations.lock().unwrap().iter()
+            .any(|pending| pending.migration.version == op_num);
+        if is_registered {
+            self.bump_version(op_num);
+            return Ok(true);
+        }
+
+        match self.check_payload_migration(op_num, key, new_key, convert_to)? {
+            None => {
+                // There are no values to migrate, only the index is moved to the new key
+                let is_indexed = self.payload_index.borrow().indexed_fields().contains(key);
+                if is_indexed && key != new_key {
+                    let mut payload_index = self.payload_index.borrow_mut();
+                    payload_index.drop_index(key)?;
+                    payload_index.set_indexed(new_key)?;
+                }
+            }
+            Some(new_type) => self.register_payload_migrations(vec![PayloadMigration {
+                version: op_num,
+                key: key.to_owned(),
+                new_key: new_key.to_owned(),
+                new_type,
+            }])?,
+        }
+        self.bump_version(op_num);
         Ok(true)
     }
 
+    fn check_payload_migration(&self,
+                               op_num: SeqNumberType,
+                               key: &PayloadKeyType,
+                               new_key: &PayloadKeyType,
+                               convert_to: Option<&PayloadSchemaType>,
+    ) -> OperationResult<Option<PayloadSchemaType>> {
+        let pending_migrations = self.payload_migrations();
+        if pending_migrations.iter().any(|migration| migration.version == op_num) {
+            return Ok(None);
+        }
+        if let Some(migration) = pending_migrations.iter().find(|migration| migration.affects(key, new_key)) {
+            return Err(OperationError::WrongInput {
+                description: format!("Payload key {} is still being migrated into {}", migration.key, migration.new_key)
+            });
+        }
+        self.payload_storage.borrow().check_migration(key, new_key, convert_to)
+    }
+
+    fn pending_payload_migrations(&self) -> usize {
+        self.payload_migrations.lock().unwrap().len()
+    }
+
+    fn migrate_payload_batch(&self, limit: usize) -> OperationResult<usize> {
+        self.check_writable()?;
+        let _update_guard = self.update_lock.read_recursive();
+        let mut migrations = self.payload_migrations.lock().unwrap();
+        let (migration, next_point) = match migrations.first() {
+            None => return Ok(0),
+            Some(pending) => (pending.migration.clone(), pending.next_point)
+        };
+
+        let points: Vec<(PointIdType, PointOffsetType)> = self.id_mapper.borrow()
+            .iter_from(next_point)
+            .take(limit.saturating_add(1))
+            .collect();
+        let (batch, next_point) = if points.len() > limit {
+            (&points[..limit], Some(points[limit].0))
+        } else {
+            (&points[..], None)
+        };
+
+        for (point_id, internal_id) in batch.iter().cloned() {
+            let is_outdated = self.id_mapper.borrow().point_version(point_id)
+                .map_or(true, |point_version| point_version < migration.version);
+            let new_value = if is_outdated {
+                let new_value = self.payload_storage.borrow_mut().migrate_point(internal_id, &migration)?;
+                if new_value.is_some() {
+                    self.id_mapper.borrow_mut().set_point_version(point_id, migration.version)?;
+                }
+                new_value
+            } else {
+                // Point is already migrated by an operation, which changed it
+                self.payload_storage.borrow().payload(internal_id).remove(&migration.new_key)
+            };
+            if let Some(new_value) = new_value {
+                self.payload_index.borrow_mut().add_migrated_value(&migration, internal_id, &new_value);
+            }
+        }
+
+        match next_point {
+            Some(next_point) => migrations[0].next_point = Some(next_point),
+            None => {
+                self.payload_index.borrow_mut().finish_migration(&migration)?;
+                self.payload_storage.borrow_mut().finish_migration(&migration);
+                migrations.remove(0);
+            }
+        }
+        Ok(batch.len())
+    }
+
     fn vector(&self, point_id: PointIdType) -> OperationResult<Vec<VectorElementType>> {
         let internal_id = self.lookup_internal_id(point_id)?;
         Ok(self.vector_storage.borrow().get_vector(internal_id).unwrap())
     }
 
     fn payload(&self, point_id: PointIdType) -> OperationResult<TheMap<PayloadKeyType, PayloadType>> {
+        let payload_migrations = self.payload_migrations();
         let internal_id = self.lookup_internal_id(point_id)?;
-        Ok(self.payload_storage.borrow().payload(internal_id))
+        // Version is read before the payload, so the background migration is never missed
+        let point_version = self.point_version(point_id).unwrap_or(0);
+        let mut payload = self.payload_storage.borrow().payload(internal_id);
+        PayloadMigration::apply_pending(&payload_migrations, point_version, &mut payload);
+        Ok(payload)
     }
 
     fn iter_points(&self) -> Box<dyn Iterator<Item=PointIdType> + '_> {
-        // Sorry for that, but I didn't find any way easier.
-        // If you try simply return iterator - it won't work because AtomicRef should exist
-        // If you try to make callback instead - you won't be able to create <dyn SegmentEntry>
-        // Attempt to create return borrowed value along with iterator failed because of insane lifetimes
-        unsafe { self.id_mapper.as_ptr().as_ref().unwrap().iter_external() }
+        // Id mapper might be changed by concurrent deletes, so ids are copied instead of holding the lock
+        let point_ids: Vec<PointIdType> = self.id_mapper.borrow().iter_external().collect();
+        Box::new(point_ids.into_iter())
+    }
+
+    fn read_filtered(&self,
+                     offset: Option<PointIdType>,
+                     limit: usize,
+                     filter: Option<&Filter>,
+                     stop: &StopCondition,
+    ) -> OperationResult<Vec<PointIdType>> {
+        check_filter_support(filter)?;
+        let id_mapper = self.id_mapper.borrow();
+        let points = match filter {
+            None => stop.take_until_stopped(id_mapper.iter_from(offset))
+                .map(|(external_id, _)| external_id)
+                .take(limit)
+                .collect(),
+            Some(filter) => {
+                let payload_index = self.payload_index.borrow();
+                let mut matched: Vec<PointIdType> = stop.take_until_stopped(payload_index.query_points(filter))
+                    .filter_map(|internal_id| id_mapper.external_id(internal_id))
+                    .filter(|external_id| offset.map_or(true, |offset| *external_id >= offset))
+                    .collect();
+                matched.sort_unstable();
+                matched.truncate(limit);
+                matched
+            }
+        };
+        stop.check()?;
+        Ok(points)
     }
 
     fn has_point(&self, point_id: PointIdType) -> bool {
         self.id_mapper.borrow().internal_id(point_id).is_some()
     }
 
+    fn point_version(&self, point_id: PointIdType) -> Option<SeqNumberType> {
+        self.id_mapper.borrow().point_version(point_id)
+    }
+
+    fn get_telemetry(&self) -> SegmentTelemetry {
+        self.telemetry.get_telemetry()
+    }
+
     fn vectors_count(&self) -> usize {
         self.vector_storage.borrow().vector_count()
     }
@@ -253,14 +903,48 @@ impl SegmentEntry for Segment {
                 })
             }).collect();
 
+        let status = match self.segment_type {
+            SegmentType::Plain => SegmentStatus::Plain,
+            SegmentType::Indexed | SegmentType::Special => SegmentStatus::Indexed,
+        };
+
+        let (vectors_ram_usage, num_deleted_vectors) = {
+            let vector_storage = self.vector_storage.borrow();
+            let stored_vectors = vector_storage.vector_count() + vector_storage.deleted_count();
+            let ram_usage = match self.segment_config.storage_type {
+                StorageType::InMemory => stored_vectors * vector_storage.vector_dim() * size_of::<VectorElementType>(),
+                StorageType::Mmap => 0, // Loaded into memory by OS on demand
+            };
+            (ram_usage, vector_storage.deleted_count())
+        };
+        let memory_usage = SegmentMemoryUsage {
+            vector_storage_bytes: vectors_ram_usage,
+            id_mapper_bytes: self.id_mapper.borrow().memory_usage_bytes(),
+            payload_storage_bytes: self.payload_storage.borrow().memory_usage_bytes(),
+            payload_index_bytes: self.payload_index.borrow().indexes_info().values()
+                .flat_map(|indexes| indexes.iter().map(|info| info.memory_usage_bytes))
+                .sum(),
+        };
+
+        let disk_usage = SegmentDiskUsage {
+            vector_storage_bytes: dir_size(&self.current_path.join(VECTOR_STORAGE_PATH)),
+            id_mapper_bytes: dir_size(&self.current_path.join(ID_MAPPER_PATH)),
+            payload_storage_bytes: dir_size(&self.current_path.join(PAYLOAD_STORAGE_PATH)),
+            payload_index_bytes: dir_size(&self.current_path.join(PAYLOAD_INDEX_PATH)),
+        };
+
         SegmentInfo {
             segment_type: self.segment_type,
+            status,
             num_vectors: self.vectors_count(),
-            num_deleted_vectors: self.vector_storage.borrow().deleted_count(),
-            ram_usage_bytes: 0, // ToDo: Implement
-            disk_usage_bytes: 0,  // ToDo: Implement
+            num_deleted_vectors,
+            ram_usage_bytes: memory_usage.total(),
+            memory_usage,
+            disk_usage_bytes: disk_usage.total(),
+            disk_usage,
             is_appendable: self.appendable_flag,
             schema,
+            config: self.segment_config.clone(),
         }
     }
 
@@ -273,39 +957,66 @@ impl SegmentEntry for Segment {
     }
 
     fn flush(&self) -> OperationResult<SeqNumberType> {
-        let persisted_version = self.persisted_version.lock().unwrap();
-        if *persisted_version == self.version {
+        let mut persisted_version = self.persisted_version.lock().unwrap();
+        // Wait for in-flight shared operations and block new ones until the state is saved
+        let _update_guard = self.update_lock.write();
+        if *persisted_version == self.version() {
             return Ok(*persisted_version);
         }
 
         let state = self.get_state();
 
-        self.id_mapper.borrow().flush()?;
-        self.payload_storage.borrow().flush()?;
+        // Id mapper is flushed last: persisted links and point versions must only refer to persisted data,
+        // otherwise operations, which are not persisted completely, would be skipped on WAL recovery.
         self.vector_storage.borrow().flush()?;
+        self.payload_storage.borrow().flush()?;
+        self.id_mapper.borrow().flush()?;
 
         self.save_state(&state)?;
+        *persisted_version = state.version;
 
         Ok(state.version)
     }
 
+    fn take_snapshot(&self, snapshot_dir: &Path) -> OperationResult<()> {
+        Segment::take_snapshot(self, snapshot_dir)?;
+        Ok(())
+    }
+
     fn drop_data(&mut self) -> OperationResult<()> {
+        self.check_writable()?;
         Ok(remove_dir_all(&self.current_path)?)
     }
 
     fn delete_field_index(&mut self, op_num: u64, key: &PayloadKeyType) -> OperationResult<bool> {
+        self.check_writable()?;
         if self.skip_by_version(op_num) { return Ok(false); };
         self.payload_index.borrow_mut().drop_index(key)?;
+        self.bump_version(op_num);
         Ok(true)
     }
 
     fn create_field_index(&mut self, op_num: u64, key: &PayloadKeyType) -> OperationResult<bool> {
+        self.check_writable()?;
         if self.skip_by_version(op_num) { return Ok(false); };
         self.payload_index.borrow_mut().set_indexed(key)?;
+        self.bump_version(op_num);
         Ok(true)
     }
 
     fn get_indexed_fields(&self) -> Vec<PayloadKeyType> {
         self.payload_index.borrow().indexed_fields()
     }
+
+    fn payload_index_info(&self) -> HashMap<PayloadKeyType, Vec<PayloadIndexInfo>> {
+        self.payload_index.borrow().indexes_info()
+    }
+
+    fn bind_to_numa_node(&self, node: usize) -> OperationResult<()> {
+        self.vector_storage.borrow().bind_to_numa_node(node)
+    }
+
+    fn warm_up(&self) -> OperationResult<usize> {
+        self.vector_storage.borrow().warm_up()
+    }
 }
\ No newline at end of file
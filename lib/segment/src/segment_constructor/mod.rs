@@ -1,3 +1,7 @@
 pub mod segment_constructor;
 pub mod simple_segment_constructor;
-pub mod segment_builder;
\ No newline at end of file
+pub mod segment_builder;
+pub mod new_segment_builder;
+pub mod segment_migrations;
+pub mod readonly_bundle;
+pub mod storage_conversion;
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::common::config::{invalid_config, ValidateConfig};
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::segment::Segment;
+use crate::segment_constructor::segment_constructor::build_segment;
+use crate::types::{Distance, FlushPolicy, Indexes, PayloadIndexType, PayloadKeyType, SegmentConfig, StorageType, TextAnalyzerConfig};
+
+/// Fluent builder of a new empty segment, see `Segment::builder`.
+/// Combination of parameters is validated before anything is written to disk
+#[derive(Debug, Clone)]
+pub struct NewSegmentBuilder {
+    path: PathBuf,
+    dim: Option<usize>,
+    distance: Option<Distance>,
+    storage_type: StorageType,
+    index: Indexes,
+    payload_index: Option<PayloadIndexType>,
+    text_analyzers: HashMap<PayloadKeyType, TextAnalyzerConfig>,
+    flush_policy: Option<FlushPolicy>,
+    appendable: Option<bool>,
+}
+
+fn wrong_input(description: &str) -> OperationError {
+    OperationError::WrongInput { description: description.to_string() }
+}
+
+impl NewSegmentBuilder {
+    /// Segment directory will be created inside of the collection directory `path`
+    pub fn new(path: &Path) -> Self {
+        NewSegmentBuilder {
+            path: path.to_owned(),
+            dim: None,
+            distance: None,
+            storage_type: Default::default(),
+            index: Default::default(),
+            payload_index: None,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+            appendable: None,
+        }
+    }
+
+    /// Take all parameters from the existing config
+    pub fn config(mut self, config: &SegmentConfig) -> Self {
+        self.dim = Some(config.vector_size);
+        self.distance = Some(config.distance);
+        self.storage_type = config.storage_type;
+        self.index = config.index;
+        self.payload_index = config.payload_index;
+        self.text_analyzers = config.text_analyzers.clone();
+        self.flush_policy = config.flush_policy;
+        self
+    }
+
+    pub fn dim(mut self, dim: usize) -> Self {
+        self.dim = Some(dim);
+        self
+    }
+
+    pub fn distance(mut self, distance: Distance) -> Self {
+        self.distance = Some(distance);
+        self
+    }
+
+    pub fn storage(mut self, storage_type: StorageType) -> Self {
+        self.storage_type = storage_type;
+        self
+    }
+
+    pub fn index(mut self, index: Indexes) -> Self {
+        self.index = index;
+        self
+    }
+
+    pub fn payload_index(mut self, payload_index: PayloadIndexType) -> Self {
+        self.payload_index = Some(payload_index);
+        self
+    }
+
+    pub fn text_analyzer(mut self, key: &str, analyzer: TextAnalyzerConfig) -> Self {
+        self.text_analyzers.insert(key.to_string(), analyzer);
+        self
+    }
+
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = Some(flush_policy);
+        self
+    }
+
+    /// Require the segment to accept new points, or to be read-only.
+    /// Only segments with in-memory storage and without indexes accept new points
+    pub fn appendable(mut self, appendable: bool) -> Self {
+        self.appendable = Some(appendable);
+        self
+    }
+
+    /// Config of the segment, if the combination of parameters is valid
+    pub fn validate(&self) -> OperationResult<SegmentConfig> {
+        let vector_size = self.dim.ok_or_else(|| wrong_input("Vector dimension is not specified"))?;
+        let distance = self.distance.ok_or_else(|| wrong_input("Distance is not specified"))?;
+        let config = SegmentConfig {
+            vector_size,
+            index: self.index,
+            payload_index: self.payload_index,
+            distance,
+            storage_type: self.storage_type,
+            text_analyzers: self.text_analyzers.clone(),
+            flush_policy: self.flush_policy,
+        };
+        config.validate().map_err(|problems| invalid_config(&problems))?;
+
+        let is_appendable = self.storage_type == StorageType::InMemory
+            && self.index == Indexes::Plain {}
+            && self.payload_index.unwrap_or_default() == PayloadIndexType::Plain;
+        match self.appendable {
+            Some(true) if !is_appendable => return Err(wrong_input(
+                "Only segments with in-memory storage, plain index and plain payload index are appendable"
+            )),
+            Some(false) if is_appendable => return Err(wrong_input(
+                "Segment with in-memory storage, plain index and plain payload index is always appendable"
+            )),
+            _ => {}
+        }
+
+        Ok(config)
+    }
+
+    /// Validate parameters and create the segment in a new sub-directory of the path
+    pub fn build(self) -> OperationResult<Segment> {
+        let config = self.validate()?;
+        build_segment(&self.path, &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::common::config::{invalid_config, ValidateConfig};
+use crate::entry::entry_point::SegmentEntry;
+
+    use super::*;
+
+    #[test]
+    fn test_new_segment_builder() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = Segment::builder(dir.path())
+            .dim(4)
+            .distance(Distance::Cosine)
+            .appendable(true)
+            .build()
+            .unwrap();
+        assert!(segment.is_appendable());
+        assert_eq!(segment.config().vector_size, 4);
+
+        let segment = Segment::builder(dir.path())
+            .dim(4)
+            .distance(Distance::Dot)
+            .storage(StorageType::Mmap)
+            .index(Indexes::Hnsw { m: 16, ef_construct: 128 })
+            .build()
+            .unwrap();
+        assert!(!segment.is_appendable());
+        assert_eq!(segment.config().storage_type, StorageType::Mmap);
+
+        let invalid = vec![
+            Segment::builder(dir.path()).distance(Distance::Dot),
+            Segment::builder(dir.path()).dim(4),
+            Segment::builder(dir.path()).dim(4).distance(Distance::Euclid),
+            Segment::builder(dir.path()).dim(4).distance(Distance::Dot).index(Indexes::Hnsw { m: 0, ef_construct: 100 }),
+            Segment::builder(dir.path()).dim(4).distance(Distance::Dot).storage(StorageType::Mmap).appendable(true),
+            Segment::builder(dir.path()).dim(4).distance(Distance::Dot).appendable(false),
+        ];
+        for builder in invalid {
+            assert!(matches!(builder.build(), Err(OperationError::WrongInput { .. })));
+        }
+        // Only the two valid segments are created
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
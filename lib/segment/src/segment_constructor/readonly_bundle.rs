@@ -0,0 +1,69 @@
+use std::fs::{create_dir_all, rename};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::file_operations::{atomic_save_json, read_json};
+use crate::entry::entry_point::{OperationError, OperationResult, SegmentEntry};
+use crate::segment::Segment;
+use crate::segment_constructor::segment_constructor::load_segment_read_only;
+use crate::segment_constructor::segment_migrations::CURRENT_FORMAT_VERSION;
+use crate::types::{PayloadKeyType, SegmentConfig};
+
+pub const BUNDLE_MANIFEST_FILE: &str = "bundle.json";
+
+/// Directory of the bundle with files of the segment
+pub const BUNDLE_SEGMENT_DIR: &str = "segment";
+
+/// Description of a read-only bundle: a single compacted and indexed segment, which is never modified.
+/// Bundle has no WAL, so it could be copied to other nodes and served without the rest of the collection.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BundleManifest {
+    /// Format version of the bundled segment
+    pub format_version: u32,
+    pub points_count: usize,
+    pub config: SegmentConfig,
+    /// Payload fields with built index
+    pub indexed_fields: Vec<PayloadKeyType>,
+}
+
+/// Move the built segment into a new bundle at `bundle_path`.
+/// Segment should not be modified or used after that, bundle is opened with `open_readonly_bundle`
+pub fn create_readonly_bundle(segment: Segment, bundle_path: &Path) -> OperationResult<BundleManifest> {
+    if bundle_path.exists() {
+        return Err(OperationError::WrongInput {
+            description: format!("Bundle {} already exists", bundle_path.display())
+        });
+    }
+    segment.flush()?;
+    let mut indexed_fields: Vec<PayloadKeyType> = segment.get_indexed_fields().into_iter().collect();
+    indexed_fields.sort();
+    let manifest = BundleManifest {
+        format_version: CURRENT_FORMAT_VERSION,
+        points_count: segment.vectors_count(),
+        config: segment.config(),
+        indexed_fields,
+    };
+    let segment_path = segment.current_path.clone();
+    drop(segment);
+
+    create_dir_all(bundle_path)?;
+    rename(&segment_path, bundle_path.join(BUNDLE_SEGMENT_DIR))?;
+    atomic_save_json(&bundle_path.join(BUNDLE_MANIFEST_FILE), &manifest)?;
+    Ok(manifest)
+}
+
+/// Open the segment of the bundle, which rejects all modifications and never writes into its files
+pub fn open_readonly_bundle(bundle_path: &Path) -> OperationResult<(BundleManifest, Segment)> {
+    let manifest: BundleManifest = read_json(&bundle_path.join(BUNDLE_MANIFEST_FILE))?;
+    if manifest.format_version != CURRENT_FORMAT_VERSION {
+        return Err(OperationError::WrongInput {
+            description: format!(
+                "Bundle {} has format version {}, but only version {} could be opened",
+                bundle_path.display(), manifest.format_version, CURRENT_FORMAT_VERSION,
+            )
+        });
+    }
+    let segment = load_segment_read_only(&bundle_path.join(BUNDLE_SEGMENT_DIR))?;
+    Ok((manifest, segment))
+}
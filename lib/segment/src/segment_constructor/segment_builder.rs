@@ -1,13 +1,68 @@
 use crate::segment::Segment;
 use crate::entry::entry_point::{OperationResult, SegmentEntry, OperationError};
-use core::cmp;
-use crate::types::{PayloadKeyType, SegmentConfig};
+use std::sync::atomic::Ordering;
+use crate::types::{PayloadKeyType, SegmentConfig, PointIdType, PointOffsetType, VectorElementType, TheMap, PayloadType, SeqNumberType, PayloadMigration};
 use std::collections::HashSet;
 use std::convert::TryInto;
 use crate::segment_constructor::segment_constructor::{build_segment, load_segment};
 use std::path::{Path, PathBuf};
 use std::fs;
 use crate::common::error_logging::LogError;
+use crate::common::stop_condition::StopCondition;
+use itertools::Itertools;
+use crate::id_mapper::id_mapper::IdMapper;
+
+/// Number of points, which are written into the storage at once during bulk loading
+const BULK_CHUNK_SIZE: usize = 1024;
+
+/// Group of the point, defined by the first keyword or integer value of the payload field
+pub fn payload_group(payload: &PayloadType) -> Option<String> {
+    match payload {
+        PayloadType::Keyword(values) => values.first().cloned(),
+        PayloadType::Integer(values) => values.first().map(|value| value.to_string()),
+        _ => None,
+    }
+}
+
+/// Groups of all (not deleted) points of the segment in the order of the storage
+pub fn stored_groups(segment: &Segment, group_key: &PayloadKeyType) -> Vec<Option<String>> {
+    let payload_storage = segment.payload_storage.borrow();
+    let vector_storage = segment.vector_storage.borrow();
+    vector_storage.iter_ids()
+        .map(|internal_id| payload_storage.payload(internal_id).get(group_key).and_then(payload_group))
+        .collect()
+}
+
+/// Copy versions of points, deleted from `other`, unless the point is already known with a newer version.
+/// Tombstones are kept, so operations older than the deletion are not applied again during WAL recovery.
+fn copy_tombstones(id_mapper: &mut dyn IdMapper, other: &dyn IdMapper) -> OperationResult<()> {
+    for (external_id, version) in other.iter_versions() {
+        if other.internal_id(external_id).is_some() || id_mapper.internal_id(external_id).is_some() {
+            continue;
+        }
+        if id_mapper.point_version(external_id).map_or(true, |known_version| known_version < version) {
+            id_mapper.set_point_version(external_id, version)?;
+        }
+    }
+    Ok(())
+}
+
+/// Payload of the point of `other` segment with all its unfinished migrations applied
+fn migrated_payload(other: &Segment, migrations: &[PayloadMigration], point_id: PointIdType, internal_id: PointOffsetType) -> TheMap<PayloadKeyType, PayloadType> {
+    let point_version = other.id_mapper.borrow().point_version(point_id).unwrap_or(0);
+    let mut payload = other.payload_storage.borrow().payload(internal_id);
+    PayloadMigration::apply_pending(migrations, point_version, &mut payload);
+    payload
+}
+
+/// Payload of unfinished migrations is copied already migrated, so indexes are built for the new keys
+fn migrate_indexed_fields(indexed_fields: &mut HashSet<PayloadKeyType>, migrations: &[PayloadMigration]) {
+    for migration in migrations {
+        if indexed_fields.remove(&migration.key) {
+            indexed_fields.insert(migration.new_key.clone());
+        }
+    }
+}
 
 /// Structure for constructing segment out of several other segments
 pub struct SegmentBuilder {
@@ -15,6 +70,8 @@ pub struct SegmentBuilder {
     pub destination_path: PathBuf,
     pub temp_path: PathBuf,
     pub indexed_fields: HashSet<PayloadKeyType>,
+    /// Building is interrupted with `Cancelled` error, once the condition is met
+    pub stop: StopCondition,
 }
 
 impl SegmentBuilder {
@@ -29,22 +86,24 @@ impl SegmentBuilder {
             destination_path,
             temp_path,
             indexed_fields: Default::default(),
+            stop: Default::default(),
         })
     }
 
     /// Update current segment builder with all (not deleted) vectors and payload form `other` segment
     /// Perform index building at the end of update
     pub fn update_from(&mut self, other: &Segment) -> OperationResult<()> {
+        self.stop.check()?;
         match &mut self.segment {
             None => Err(OperationError::ServiceError {
                 description: "Segment building error: created segment not found".to_owned()
             }),
             Some(self_segment) => {
-                self_segment.version = cmp::max(self_segment.version, other.version());
+                self_segment.version.fetch_max(other.version(), Ordering::SeqCst);
+                let migrations = other.payload_migrations();
 
                 let other_id_mapper = other.id_mapper.borrow();
                 let other_vector_storage = other.vector_storage.borrow();
-                let other_payload_storage = other.payload_storage.borrow();
 
                 let new_internal_range = self_segment.vector_storage.borrow_mut().update_from(&*other_vector_storage)?;
 
@@ -54,17 +113,142 @@ impl SegmentBuilder {
                 for (new_internal_id, old_internal_id) in new_internal_range.zip(other.vector_storage.borrow().iter_ids()) {
                     let other_external_id = other_id_mapper.external_id(old_internal_id).unwrap();
                     id_mapper.set_link(other_external_id, new_internal_id)?;
-                    payload_storage.assign_all(new_internal_id, other_payload_storage.payload(old_internal_id))?;
+                    if let Some(point_version) = other_id_mapper.point_version(other_external_id) {
+                        id_mapper.set_point_version(other_external_id, point_version)?;
+                    }
+                    payload_storage.assign_all(new_internal_id, migrated_payload(other, &migrations, other_external_id, old_internal_id))?;
                 }
+                copy_tombstones(&mut *id_mapper, &*other_id_mapper)?;
 
                 for field in other.payload_index.borrow().indexed_fields().into_iter() {
                     self.indexed_fields.insert(field);
                 }
+                migrate_indexed_fields(&mut self.indexed_fields, &migrations);
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Update current segment builder with all (not deleted) points of `others` segments,
+    /// so points of the same group, defined by `group_key` payload field, are stored next to each other.
+    /// Points without the group are stored after all groups.
+    pub fn update_from_grouped(&mut self, others: &[&Segment], group_key: &PayloadKeyType) -> OperationResult<()> {
+        match &mut self.segment {
+            None => Err(OperationError::ServiceError {
+                description: "Segment building error: created segment not found".to_owned()
+            }),
+            Some(self_segment) => {
+                let mut points = vec![];
+                let migrations = others.iter().map(|other| other.payload_migrations()).collect_vec();
+                for (segment_idx, other) in others.iter().enumerate() {
+                    self_segment.version.fetch_max(other.version(), Ordering::SeqCst);
+                    let other_ids = other.vector_storage.borrow().iter_ids().collect_vec();
+                    for (internal_id, group) in other_ids.into_iter().zip(stored_groups(other, group_key)) {
+                        points.push((group, segment_idx, internal_id));
+                    }
+                    for field in other.payload_index.borrow().indexed_fields().into_iter() {
+                        self.indexed_fields.insert(field);
+                    }
+                    migrate_indexed_fields(&mut self.indexed_fields, &migrations[segment_idx]);
+                }
+
+                // Stable sort keeps the original order of points within a group
+                points.sort_by(|(group_a, _, _), (group_b, _, _)| {
+                    (group_a.is_none(), group_a).cmp(&(group_b.is_none(), group_b))
+                });
+
+                let mut vector_storage = self_segment.vector_storage.borrow_mut();
+                let mut id_mapper = self_segment.id_mapper.borrow_mut();
+                let mut payload_storage = self_segment.payload_storage.borrow_mut();
+
+                for chunk in &points.into_iter().chunks(BULK_CHUNK_SIZE) {
+                    let chunk = chunk.collect_vec();
+                    let mut vectors = chunk.iter()
+                        .map(|(_, segment_idx, internal_id)| others[*segment_idx].vector_storage.borrow()
+                            .get_vector(*internal_id)
+                            .unwrap());
+                    let internal_range = vector_storage.append_vectors(&mut vectors)?;
+
+                    for ((_, segment_idx, old_internal_id), new_internal_id) in chunk.into_iter().zip(internal_range) {
+                        let other = others[segment_idx];
+                        let other_id_mapper = other.id_mapper.borrow();
+                        let external_id = other_id_mapper.external_id(old_internal_id).unwrap();
+                        id_mapper.set_link(external_id, new_internal_id)?;
+                        if let Some(point_version) = other_id_mapper.point_version(external_id) {
+                            id_mapper.set_point_version(external_id, point_version)?;
+                        }
+                        payload_storage.assign_all(new_internal_id, migrated_payload(other, &migrations[segment_idx], external_id, old_internal_id))?;
+                    }
+                }
+                for other in others {
+                    copy_tombstones(&mut *id_mapper, &*other.id_mapper.borrow())?;
+                }
 
                 Ok(())
             }
         }
     }
+
+    /// Sequentially write points from the stream into the building segment.
+    /// Indexes are not updated here, they are built once during the conversion into `Segment`.
+    /// If the same id occurs several times, the last vector and payload are kept.
+    ///
+    /// Returns number of consumed points.
+    pub fn add_points<I>(&mut self, version: SeqNumberType, points: I) -> OperationResult<usize>
+        where I: IntoIterator<Item=(PointIdType, Vec<VectorElementType>, TheMap<PayloadKeyType, PayloadType>)> {
+        self.stop.check()?;
+        match &mut self.segment {
+            None => Err(OperationError::ServiceError {
+                description: "Segment building error: created segment not found".to_owned()
+            }),
+            Some(self_segment) => {
+                let mut vector_storage = self_segment.vector_storage.borrow_mut();
+                let mut id_mapper = self_segment.id_mapper.borrow_mut();
+                let mut payload_storage = self_segment.payload_storage.borrow_mut();
+
+                let vector_dim = vector_storage.vector_dim();
+                let mut count = 0;
+
+                for chunk in &points.into_iter().chunks(BULK_CHUNK_SIZE) {
+                    let mut ids = vec![];
+                    let mut vectors = vec![];
+                    let mut payloads = vec![];
+                    for (point_id, vector, payload) in chunk {
+                        ids.push(point_id);
+                        vectors.push(vector);
+                        payloads.push(payload);
+                    }
+
+                    if let Some(vector) = vectors.iter().find(|vector| vector.len() != vector_dim) {
+                        return Err(OperationError::WrongVector { expected_dim: vector_dim, received_dim: vector.len() });
+                    }
+
+                    let internal_range = vector_storage.append_vectors(&mut vectors.into_iter())?;
+
+                    for ((point_id, payload), internal_id) in ids.into_iter().zip(payloads).zip(internal_range) {
+                        // Vector storages are append-only here, so the previous version of the point is marked as deleted
+                        if let Some(old_internal_id) = id_mapper.internal_id(point_id) {
+                            vector_storage.delete(old_internal_id)?;
+                            payload_storage.drop(old_internal_id)?;
+                            id_mapper.drop(point_id)?;
+                        }
+                        id_mapper.set_link(point_id, internal_id)?;
+                        id_mapper.set_point_version(point_id, version)?;
+                        payload_storage.assign_all(internal_id, payload)?;
+                        count += 1;
+                    }
+                }
+
+                drop(vector_storage);
+                drop(id_mapper);
+                drop(payload_storage);
+                self_segment.version.fetch_max(version, Ordering::SeqCst);
+
+                Ok(count)
+            }
+        }
+    }
 }
 
 impl TryInto<Segment> for SegmentBuilder {
@@ -78,10 +262,10 @@ impl TryInto<Segment> for SegmentBuilder {
             self.segment = None;
 
             for field in self.indexed_fields.iter() {
-                segment.create_field_index(segment.version, field)?;
+                segment.create_field_index(segment.version(), field)?;
             }
 
-            segment.query_planner.borrow_mut().build_index()?;
+            segment.query_planner.borrow_mut().build_index(&self.stop)?;
 
             segment.flush()?;
             // Now segment is going to be evicted from RAM
@@ -1,4 +1,4 @@
-use crate::segment::{Segment, SEGMENT_STATE_FILE};
+use crate::segment::{Segment, SEGMENT_STATE_FILE, ID_MAPPER_PATH, PAYLOAD_STORAGE_PATH, PAYLOAD_INDEX_PATH, VECTOR_STORAGE_PATH};
 use crate::id_mapper::simple_id_mapper::SimpleIdMapper;
 use crate::vector_storage::simple_vector_storage::SimpleVectorStorage;
 use crate::payload_storage::simple_payload_storage::SimplePayloadStorage;
@@ -6,52 +6,79 @@ use crate::index::plain_payload_index::{PlainPayloadIndex, PlainIndex};
 use crate::query_planner::simple_query_planner::SimpleQueryPlanner;
 use crate::types::{SegmentType, SegmentConfig, Indexes, SegmentState, SeqNumberType, StorageType, PayloadIndexType};
 use std::sync::{Arc, Mutex};
-use atomic_refcell::AtomicRefCell;
+use std::sync::atomic::AtomicU64;
+use crate::common::rw_cell::RwCell;
 use crate::payload_storage::query_checker::SimpleConditionChecker;
 use std::path::Path;
 use uuid::Uuid;
 use std::fs::{File, create_dir_all};
-use crate::entry::entry_point::{OperationResult, OperationError};
+use crate::entry::entry_point::{OperationResult, OperationError, SegmentEntry};
 use std::io::Read;
+use std::time::Instant;
+use tracing::debug;
 use crate::vector_storage::memmap_vector_storage::MemmapVectorStorage;
 use crate::vector_storage::vector_storage::VectorStorage;
+#[cfg(feature = "payload_index")]
 use crate::index::struct_payload_index::StructPayloadIndex;
 use crate::index::index::PayloadIndex;
+use crate::common::file_operations::atomic_save_json;
+use crate::segment_constructor::segment_migrations::{migrate_segment, is_migration_required};
+use crate::segment_constructor::storage_conversion::{is_conversion_interrupted, recover_storage_conversion};
+use crate::spaces::dispatch::select_kernel;
 
 
-fn sp<T>(t: T) -> Arc<AtomicRefCell<T>> { Arc::new(AtomicRefCell::new(t)) }
+fn sp<T>(t: T) -> Arc<RwCell<T>> { Arc::new(RwCell::new(t)) }
 
 
-fn create_segment(version: SeqNumberType, segment_path: &Path, config: &SegmentConfig) -> OperationResult<Segment> {
-    let mapper_path = segment_path.join("id_mapper");
-    let payload_storage_path = segment_path.join("payload_storage");
-    let payload_index_path = segment_path.join("payload_index");
-    let vector_storage_path = segment_path.join("vector_storage");
+fn create_segment(version: SeqNumberType, segment_path: &Path, config: &SegmentConfig, read_only: bool) -> OperationResult<Segment> {
+    let mapper_path = segment_path.join(ID_MAPPER_PATH);
+    let payload_storage_path = segment_path.join(PAYLOAD_STORAGE_PATH);
+    let payload_index_path = segment_path.join(PAYLOAD_INDEX_PATH);
+    let vector_storage_path = segment_path.join(VECTOR_STORAGE_PATH);
 
-    let id_mapper = sp(SimpleIdMapper::open(mapper_path.as_path())?);
+    let id_mapper = sp(if read_only {
+        SimpleIdMapper::open_read_only(mapper_path.as_path())?
+    } else {
+        SimpleIdMapper::open(mapper_path.as_path())?
+    });
 
 
-    let vector_storage: Arc<AtomicRefCell<dyn VectorStorage>> = match config.storage_type {
-        StorageType::InMemory => sp(SimpleVectorStorage::open(vector_storage_path.as_path(), config.vector_size)?),
-        StorageType::Mmap => sp(MemmapVectorStorage::open(vector_storage_path.as_path(), config.vector_size)?),
+    let vector_storage: Arc<RwCell<dyn VectorStorage>> = match (config.storage_type, read_only) {
+        (StorageType::InMemory, false) => sp(SimpleVectorStorage::open(vector_storage_path.as_path(), config.vector_size)?),
+        (StorageType::InMemory, true) => sp(SimpleVectorStorage::open_read_only(vector_storage_path.as_path(), config.vector_size)?),
+        (StorageType::Mmap, false) => sp(MemmapVectorStorage::open(vector_storage_path.as_path(), config.vector_size)?),
+        (StorageType::Mmap, true) => sp(MemmapVectorStorage::open_read_only(vector_storage_path.as_path(), config.vector_size)?),
     };
+    // Kernel is selected while the segment is loaded, so the first search does not wait for the benchmark
+    select_kernel(config.distance, config.vector_size);
 
-    let payload_storage = sp(SimplePayloadStorage::open(payload_storage_path.as_path())?);
+    let payload_storage = sp(if read_only {
+        SimplePayloadStorage::open_read_only(payload_storage_path.as_path())?
+    } else {
+        SimplePayloadStorage::open(payload_storage_path.as_path())?
+    });
 
 
     let condition_checker = sp(SimpleConditionChecker::new(
         payload_storage.clone(),
         id_mapper.clone(),
+        &config.text_analyzers,
     ));
 
-    let payload_index: Arc<AtomicRefCell<dyn PayloadIndex>> = match config.payload_index.unwrap_or_default() {
+    let payload_index: Arc<RwCell<dyn PayloadIndex>> = match config.payload_index.unwrap_or_default() {
         PayloadIndexType::Plain => sp(PlainPayloadIndex::open(condition_checker, vector_storage.clone(), &payload_index_path)?),
+        #[cfg(feature = "payload_index")]
         PayloadIndexType::Struct => sp(StructPayloadIndex::open(
             condition_checker,
             vector_storage.clone(),
             payload_storage.clone(),
             id_mapper.clone(),
+            config.text_analyzers.clone(),
             &payload_index_path)?),
+        #[cfg(not(feature = "payload_index"))]
+        PayloadIndexType::Struct => return Err(OperationError::WrongInput {
+            description: "Struct payload index is not supported, segment is built without `payload_index` feature".to_string()
+        }),
     };
 
     let index = sp(match config.index {
@@ -77,13 +104,14 @@ fn create_segment(version: SeqNumberType, segment_path: &Path, config: &SegmentC
         Indexes::Hnsw { .. } => SegmentType::Indexed,
     };
 
-    let appendable = segment_type == SegmentType::Plain {} && config.storage_type == StorageType::InMemory;
+    let appendable = !read_only && segment_type == SegmentType::Plain {} && config.storage_type == StorageType::InMemory;
 
     let query_planer = SimpleQueryPlanner::new(index);
 
     return Ok(Segment {
-        version,
+        version: AtomicU64::new(version),
         persisted_version: Arc::new(Mutex::new(version)),
+        update_lock: Default::default(),
         current_path: segment_path.to_owned(),
         id_mapper: id_mapper.clone(),
         vector_storage,
@@ -93,24 +121,86 @@ fn create_segment(version: SeqNumberType, segment_path: &Path, config: &SegmentC
         appendable_flag: appendable,
         segment_type,
         segment_config: config.clone(),
+        telemetry: Default::default(),
+        read_only,
+        payload_migrations: Default::default(),
+        point_locks: Default::default(),
     });
 }
 
 
-pub fn load_segment(path: &Path) -> OperationResult<Segment> {
+/// Persisted version and config of the segment, read without opening its storages
+pub fn read_segment_state(path: &Path) -> OperationResult<SegmentState> {
     let segment_config_path = path.join(SEGMENT_STATE_FILE);
     let mut contents = String::new();
 
-    let mut file = File::open(segment_config_path)?;
+    let mut file = File::open(&segment_config_path)?;
     file.read_to_string(&mut contents)?;
 
     let segment_state: SegmentState = serde_json::from_str(&contents).or_else(|err| {
-        Err(OperationError::ServiceError {
-            description: format!("Failed to read segment {}. Error: {}", path.to_str().unwrap(), err)
+        Err(OperationError::Corrupted {
+            path: segment_config_path.clone(),
+            description: format!("Failed to parse segment state: {}", err),
         })
     })?;
 
-    create_segment(segment_state.version, path, &segment_state.config)
+    Ok(segment_state)
+}
+
+
+/// Load existing segment. Segments of older format versions are migrated to the current one,
+/// interrupted conversion of the vector storage is finished.
+pub fn load_segment(path: &Path) -> OperationResult<Segment> {
+    let load = || {
+        recover_storage_conversion(path)?;
+        let mut segment_state = read_segment_state(path)?;
+        if migrate_segment(path, &mut segment_state)? {
+            atomic_save_json(&path.join(SEGMENT_STATE_FILE), &segment_state)?;
+        }
+        let segment = create_segment(segment_state.version, path, &segment_state.config, false)?;
+        // Points, which are not migrated yet, are visited again by the background migration
+        segment.resume_payload_migrations(segment_state.payload_migrations);
+        Ok(segment)
+    };
+    let timer = Instant::now();
+    let segment = load().map_err(|err| err.with_segment_path(path))?;
+    debug!(
+        segment = %path.display(),
+        vectors = segment.vectors_count(),
+        duration_ms = timer.elapsed().as_millis() as u64,
+        "Loaded segment",
+    );
+    Ok(segment)
+}
+
+/// Load existing segment and validate, that its persisted config agrees with the expected one
+pub fn load_segment_with_config(path: &Path, expected_config: &SegmentConfig) -> OperationResult<Segment> {
+    let segment = load_segment(path)?;
+    let mismatches = segment.segment_config.mismatches(expected_config);
+    if !mismatches.is_empty() {
+        return Err(OperationError::WrongInput {
+            description: format!("Config of segment {} does not match expected: {}", path.display(), mismatches.join(", "))
+        });
+    }
+    Ok(segment)
+}
+
+/// Load existing segment, which rejects all modifications and never writes into its files.
+/// Useful for serving immutable copies of the data or for inspecting data of a running service.
+pub fn load_segment_read_only(path: &Path) -> OperationResult<Segment> {
+    let load = || {
+        let segment_state = read_segment_state(path)?;
+        if is_migration_required(&segment_state)? {
+            return Err(OperationError::service_error("Segment requires migration and can't be opened in read-only mode"));
+        }
+        if is_conversion_interrupted(path) {
+            return Err(OperationError::service_error("Segment has interrupted storage conversion and can't be opened in read-only mode"));
+        }
+        let segment = create_segment(segment_state.version, path, &segment_state.config, true)?;
+        segment.resume_payload_migrations(segment_state.payload_migrations);
+        Ok(segment)
+    };
+    load().map_err(|err| err.with_segment_path(path))
 }
 
 
@@ -128,7 +218,7 @@ pub fn build_segment(path: &Path, config: &SegmentConfig) -> OperationResult<Seg
 
     create_dir_all(&segment_path)?;
 
-    let segment = create_segment(0, segment_path.as_path(), config)?;
+    let segment = create_segment(0, segment_path.as_path(), config, false)?;
     segment.save_current_state()?;
 
     Ok(segment)
@@ -0,0 +1,156 @@
+use std::fs::{File, OpenOptions, rename};
+use std::io::{Read, Write, copy};
+use std::mem::size_of;
+use std::path::Path;
+
+use tracing::info;
+
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::segment::VECTOR_STORAGE_PATH;
+use crate::types::{SegmentConfig, SegmentState, StorageType, VectorElementType};
+use crate::vector_storage::memmap_vector_storage::{DATA_FILE, DELETED_FILE};
+
+/// Version of the on-disk segment layout, produced by the current code
+pub const CURRENT_FORMAT_VERSION: u32 = 1;
+
+/// Upgrades files of the segment from one format version to the next one
+type Migration = fn(&Path, &SegmentConfig) -> OperationResult<()>;
+
+/// Migration with index `i` upgrades segment of format version `i` to version `i + 1`
+const MIGRATIONS: [Migration; CURRENT_FORMAT_VERSION as usize] = [
+    add_mmap_headers,
+];
+
+/// Check if the segment requires migration before it could be opened by the current code
+pub fn is_migration_required(state: &SegmentState) -> OperationResult<bool> {
+    if state.format_version > CURRENT_FORMAT_VERSION {
+        return Err(OperationError::ServiceError {
+            description: format!(
+                "Segment format version {} is newer than supported version {}",
+                state.format_version, CURRENT_FORMAT_VERSION
+            )
+        });
+    }
+    Ok(state.format_version < CURRENT_FORMAT_VERSION)
+}
+
+/// Upgrade files of the segment to the current format version.
+/// Updates `format_version` of the given state, saving the state is up to the caller.
+///
+/// Returns `true` if any migration was applied.
+pub fn migrate_segment(path: &Path, state: &mut SegmentState) -> OperationResult<bool> {
+    if !is_migration_required(state)? {
+        return Ok(false);
+    }
+    for version in state.format_version..CURRENT_FORMAT_VERSION {
+        info!(segment = %path.display(), from_version = version, to_version = version + 1, "Migrating segment format");
+        MIGRATIONS[version as usize](path, &state.config)?;
+        state.format_version = version + 1;
+    }
+    Ok(true)
+}
+
+/// Rewrite file with given header prepended, unless the file already starts with it.
+/// New content is written into a temporary file first, so interrupted migration could be repeated.
+fn prepend_header(path: &Path, header: &[u8], record_size: usize) -> OperationResult<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len() as usize;
+
+    let mut prefix = vec![0u8; header.len().min(file_len)];
+    file.read_exact(&mut prefix)?;
+    if prefix == header {
+        return Ok(());
+    }
+    if file_len % record_size != 0 {
+        return Err(OperationError::Corrupted {
+            path: path.to_owned(),
+            description: format!("unable to migrate file of unexpected size {}", file_len),
+        });
+    }
+
+    let tmp_path = path.with_extension("migration");
+    {
+        let mut tmp_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        tmp_file.write_all(header)?;
+        tmp_file.write_all(&prefix)?;
+        copy(&mut file, &mut tmp_file)?;
+        tmp_file.sync_all()?;
+    }
+    rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Format version 0: mmap vector storage files were written without `data` and `drop` headers
+fn add_mmap_headers(path: &Path, config: &SegmentConfig) -> OperationResult<()> {
+    if config.storage_type != StorageType::Mmap {
+        return Ok(());
+    }
+    let storage_path = path.join(VECTOR_STORAGE_PATH);
+    prepend_header(&storage_path.join(DATA_FILE), b"data", config.vector_size * size_of::<VectorElementType>())?;
+    prepend_header(&storage_path.join(DELETED_FILE), b"drop", 1)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir_all;
+    use tempdir::TempDir;
+    use crate::types::{Distance, Indexes, PayloadIndexType};
+    use crate::vector_storage::memmap_vector_storage::MemmapVectorStorage;
+    use crate::vector_storage::vector_storage::VectorStorage;
+
+    fn mmap_state(format_version: u32) -> SegmentState {
+        SegmentState {
+            version: 0,
+            config: SegmentConfig {
+                vector_size: 2,
+                index: Indexes::Plain {},
+                payload_index: Some(PayloadIndexType::Plain),
+                distance: Distance::Dot,
+                storage_type: StorageType::Mmap,
+                text_analyzers: Default::default(),
+                flush_policy: None,
+            },
+            format_version,
+            payload_migrations: vec![],
+        }
+    }
+
+    #[test]
+    fn test_migrate_headerless_mmap() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let storage_path = dir.path().join(VECTOR_STORAGE_PATH);
+        create_dir_all(&storage_path).unwrap();
+
+        let vectors: Vec<VectorElementType> = vec![1.0, 2.0, 3.0, 4.0];
+        let bytes: Vec<u8> = vectors.iter().flat_map(|x| x.to_ne_bytes().to_vec()).collect();
+        File::create(storage_path.join("matrix.dat")).unwrap().write_all(&bytes).unwrap();
+        File::create(storage_path.join("deleted.dat")).unwrap().write_all(&[0, 1]).unwrap();
+
+        let mut state = mmap_state(0);
+        assert!(migrate_segment(dir.path(), &mut state).unwrap());
+        assert_eq!(state.format_version, CURRENT_FORMAT_VERSION);
+        // Repeated migration does nothing
+        assert!(!migrate_segment(dir.path(), &mut state).unwrap());
+
+        let storage = MemmapVectorStorage::open(&storage_path, 2).unwrap();
+        assert_eq!(storage.vector_count(), 1);
+        assert_eq!(storage.deleted_count(), 1);
+        assert_eq!(storage.get_vector(0).unwrap(), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_reject_newer_format() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut state = mmap_state(CURRENT_FORMAT_VERSION + 1);
+        assert!(migrate_segment(dir.path(), &mut state).is_err());
+    }
+}
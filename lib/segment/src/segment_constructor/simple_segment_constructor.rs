@@ -1,9 +1,8 @@
 use crate::segment::Segment;
 
-use crate::types::{Distance, SegmentConfig, Indexes};
+use crate::types::Distance;
 
 use std::path::Path;
-use crate::segment_constructor::segment_constructor::build_segment;
 use crate::entry::entry_point::OperationResult;
 
 
@@ -14,16 +13,10 @@ use crate::entry::entry_point::OperationResult;
 /// * `path` - path to collection`s segment directory
 ///
 pub fn build_simple_segment(path: &Path, dim: usize, distance: Distance) -> OperationResult<Segment> {
-    build_segment(
-        path,
-        &SegmentConfig {
-            vector_size: dim,
-            index: Indexes::Plain {},
-            payload_index: None,
-            distance,
-            storage_type: Default::default()
-        },
-    )
+    Segment::builder(path)
+        .dim(dim)
+        .distance(distance)
+        .build()
 }
 
 
@@ -38,7 +31,7 @@ mod tests {
     fn test_create_simple_segment() {
         let dir = TempDir::new("segment_dir").unwrap();
         let segment = build_simple_segment(dir.path(), 100, Distance::Dot).unwrap();
-        eprintln!(" = {:?}", segment.version);
+        eprintln!(" = {:?}", segment.version());
     }
 
     #[test]
@@ -54,7 +47,7 @@ mod tests {
         let vec4 = vec![1.0, 1.0, 0.0, 1.0];
         let vec5 = vec![1.0, 0.0, 0.0, 0.0];
 
-        match segment.upsert_point(1, 120, &wrong_vec) {
+        match segment.upsert_point(1, 120.into(), &wrong_vec) {
             Err(err) => match err {
                 OperationError::WrongVector { .. } => (),
                 _ => assert!(false, "Wrong error"),
@@ -62,17 +55,17 @@ mod tests {
             Ok(_) => assert!(false, "Operation with wrong vector should fail")
         };
 
-        segment.upsert_point(2, 1, &vec1).unwrap();
-        segment.upsert_point(2, 2, &vec2).unwrap();
-        segment.upsert_point(2, 3, &vec3).unwrap();
-        segment.upsert_point(2, 4, &vec4).unwrap();
-        segment.upsert_point(2, 5, &vec5).unwrap();
+        segment.upsert_point(2, 1.into(), &vec1).unwrap();
+        segment.upsert_point(2, 2.into(), &vec2).unwrap();
+        segment.upsert_point(2, 3.into(), &vec3).unwrap();
+        segment.upsert_point(2, 4.into(), &vec4).unwrap();
+        segment.upsert_point(2, 5.into(), &vec5).unwrap();
 
         let payload_key = "color".to_string();
 
         segment.set_payload(
             3,
-            1,
+            1.into(),
             &payload_key,
             PayloadType::Keyword(vec![
                 "red".to_owned(),
@@ -82,7 +75,7 @@ mod tests {
 
         segment.set_payload(
             3,
-            2,
+            2.into(),
             &payload_key,
             PayloadType::Keyword(vec![
                 "red".to_owned(),
@@ -92,7 +85,7 @@ mod tests {
 
         segment.set_payload(
             3,
-            3,
+            3.into(),
             &payload_key,
             PayloadType::Keyword(vec![
                 "red".to_owned(),
@@ -102,7 +95,7 @@ mod tests {
 
         segment.set_payload(
             3,
-            4,
+            4.into(),
             &payload_key,
             PayloadType::Keyword(vec![
                 "red".to_owned(),
@@ -111,16 +104,16 @@ mod tests {
         ).unwrap();
 
         // Replace vectors
-        segment.upsert_point(4, 1, &vec1).unwrap();
-        segment.upsert_point(5, 2, &vec2).unwrap();
-        segment.upsert_point(6, 3, &vec3).unwrap();
-        segment.upsert_point(7, 4, &vec4).unwrap();
-        segment.upsert_point(8, 5, &vec5).unwrap();
+        segment.upsert_point(4, 1.into(), &vec1).unwrap();
+        segment.upsert_point(5, 2.into(), &vec2).unwrap();
+        segment.upsert_point(6, 3.into(), &vec3).unwrap();
+        segment.upsert_point(7, 4.into(), &vec4).unwrap();
+        segment.upsert_point(8, 5.into(), &vec5).unwrap();
 
 
         assert_eq!(segment.version(), 8);
 
-        let declined = segment.upsert_point(3, 5, &vec5).unwrap();
+        let declined = segment.upsert_point(3, 5.into(), &vec5).unwrap();
         // Should not be processed due to operation number
         assert!(!declined);
     }
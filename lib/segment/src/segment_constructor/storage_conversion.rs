@@ -0,0 +1,174 @@
+use std::fs::{remove_dir_all, remove_file, rename};
+use std::path::Path;
+
+use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::common::config::{invalid_config, ValidateConfig};
+use crate::common::file_operations::{atomic_save_json, read_json};
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::segment::{SEGMENT_STATE_FILE, VECTOR_STORAGE_PATH};
+use crate::segment_constructor::segment_constructor::read_segment_state;
+use crate::segment_constructor::segment_migrations::is_migration_required;
+use crate::types::{SegmentConfig, StorageType};
+use crate::vector_storage::memmap_vector_storage::MemmapVectorStorage;
+use crate::vector_storage::simple_vector_storage::SimpleVectorStorage;
+use crate::vector_storage::vector_storage::VectorStorage;
+
+/// Marker of the conversion, which has written the new storage completely and should be finished on the next load
+pub const CONVERSION_FILE: &str = "storage_conversion.json";
+
+/// Directory, in which the converted storage is written before it replaces the current one
+const CONVERTED_STORAGE_PATH: &str = "vector_storage.new";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ConversionMarker {
+    storage_type: StorageType,
+}
+
+fn open_vector_storage(path: &Path, storage_type: StorageType, dim: usize, read_only: bool) -> OperationResult<Box<dyn VectorStorage>> {
+    Ok(match (storage_type, read_only) {
+        (StorageType::InMemory, false) => Box::new(SimpleVectorStorage::open(path, dim)?),
+        (StorageType::InMemory, true) => Box::new(SimpleVectorStorage::open_read_only(path, dim)?),
+        (StorageType::Mmap, false) => Box::new(MemmapVectorStorage::open(path, dim)?),
+        (StorageType::Mmap, true) => Box::new(MemmapVectorStorage::open_read_only(path, dim)?),
+    })
+}
+
+/// Check if the segment has a converted storage, which has not replaced the current one yet
+pub fn is_conversion_interrupted(path: &Path) -> bool {
+    path.join(CONVERSION_FILE).exists()
+}
+
+/// Finish the conversion, interrupted after the new storage was written, or drop the partially written storage.
+/// Each step could be repeated, so the recovery itself could be interrupted too
+pub fn recover_storage_conversion(path: &Path) -> OperationResult<()> {
+    let converted_path = path.join(CONVERTED_STORAGE_PATH);
+    let marker_path = path.join(CONVERSION_FILE);
+    if !marker_path.exists() {
+        if converted_path.exists() {
+            warn!(segment = %path.display(), "Dropping incomplete vector storage conversion");
+            remove_dir_all(&converted_path)?;
+        }
+        return Ok(());
+    }
+
+    let marker: ConversionMarker = read_json(&marker_path)?;
+    let storage_path = path.join(VECTOR_STORAGE_PATH);
+    // Converted storage is only removed by the rename, so its absence means the storage is already replaced
+    if converted_path.exists() {
+        if storage_path.exists() {
+            remove_dir_all(&storage_path)?;
+        }
+        rename(&converted_path, &storage_path)?;
+    }
+    let mut state = read_segment_state(path)?;
+    state.config.storage_type = marker.storage_type;
+    atomic_save_json(&path.join(SEGMENT_STATE_FILE), &state)?;
+    remove_file(&marker_path)?;
+    Ok(())
+}
+
+/// Write a copy of the vector storage of the segment with given storage type next to the current one
+fn write_converted_storage(path: &Path, config: &SegmentConfig, storage_type: StorageType) -> OperationResult<()> {
+    let dim = config.vector_size;
+    let storage = open_vector_storage(&path.join(VECTOR_STORAGE_PATH), config.storage_type, dim, true)?;
+    let mut converted = open_vector_storage(&path.join(CONVERTED_STORAGE_PATH), storage_type, dim, false)?;
+
+    // Deleted vectors are copied as well, so offsets of all vectors are preserved
+    let total_count = storage.vector_count() + storage.deleted_count();
+    converted.append_vectors(&mut (0..total_count)
+        .map(|offset| storage.get_vector(offset).unwrap_or_else(|| vec![0.0; dim])))?;
+    for offset in 0..total_count {
+        if storage.get_vector(offset).is_none() {
+            converted.delete(offset)?;
+        }
+    }
+    converted.flush()
+}
+
+/// Convert vector storage of the segment at `path` into `storage_type` in place.
+/// Vectors keep their offsets, so id mapping, payloads and indexes remain valid and are not rewritten.
+/// New storage is written next to the current one, which is replaced only once the new one is complete.
+/// If the conversion is interrupted, it is finished or rolled back on the next load of the segment.
+/// Segment should not be opened by anyone during the conversion. Segments with mmap storage are not appendable.
+///
+/// Returns `false` if the segment already has the requested storage.
+pub fn convert_vector_storage(path: &Path, storage_type: StorageType) -> OperationResult<bool> {
+    recover_storage_conversion(path)?;
+    let state = read_segment_state(path)?;
+    if is_migration_required(&state)? {
+        return Err(OperationError::service_error("Segment requires migration, it should be loaded once before conversion"));
+    }
+    if state.config.storage_type == storage_type {
+        return Ok(false);
+    }
+    let config = SegmentConfig { storage_type, ..state.config.clone() };
+    config.validate().map_err(|problems| invalid_config(&problems))?;
+
+    info!(segment = %path.display(), from = ?state.config.storage_type, to = ?storage_type, "Converting vector storage");
+    write_converted_storage(path, &state.config, storage_type)?;
+
+    atomic_save_json(&path.join(CONVERSION_FILE), &ConversionMarker { storage_type })?;
+    recover_storage_conversion(path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::common::stop_condition::StopCondition;
+    use crate::entry::entry_point::SegmentEntry;
+    use crate::segment::Segment;
+    use crate::segment_constructor::segment_constructor::load_segment;
+    use crate::types::{Distance, PayloadType, WithPayload};
+
+    use super::*;
+
+    #[test]
+    fn test_convert_vector_storage() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = Segment::builder(dir.path()).dim(2).distance(Distance::Dot).build().unwrap();
+        for id in 0..10u64 {
+            segment.upsert_point(id, id.into(), &vec![id as f32, 1.0]).unwrap();
+        }
+        segment.set_payload(10, 3.into(), &"city".to_string(), PayloadType::Keyword(vec!["Berlin".to_string()])).unwrap();
+        segment.delete_point(11, 5.into()).unwrap();
+        segment.upsert_point(12, 3.into(), &vec![30.0, 1.0]).unwrap();
+        segment.flush().unwrap();
+        let path = segment.current_path.clone();
+        drop(segment);
+
+        assert!(convert_vector_storage(&path, StorageType::Mmap).unwrap());
+        assert!(!convert_vector_storage(&path, StorageType::Mmap).unwrap());
+
+        let check = |storage_type: StorageType| {
+            let segment = load_segment(&path).unwrap();
+            assert_eq!(segment.config().storage_type, storage_type);
+            assert_eq!(segment.vectors_count(), 9);
+            assert_eq!(segment.vector(3.into()).unwrap(), vec![30.0, 1.0]);
+            assert!(segment.vector(5.into()).is_err());
+            assert_eq!(segment.payload(3.into()).unwrap().len(), 1);
+            let found = segment.search(&vec![1.0, 0.0], &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
+            assert_eq!(found[0].id, 3.into());
+        };
+        check(StorageType::Mmap);
+
+        assert!(convert_vector_storage(&path, StorageType::InMemory).unwrap());
+        check(StorageType::InMemory);
+
+        // Partially written storage is dropped on load
+        open_vector_storage(&path.join(CONVERTED_STORAGE_PATH), StorageType::Mmap, 2, false).unwrap();
+        check(StorageType::InMemory);
+        assert!(!path.join(CONVERTED_STORAGE_PATH).exists());
+
+        // Conversion, interrupted after the new storage is written, is finished on load
+        let state = read_segment_state(&path).unwrap();
+        write_converted_storage(&path, &state.config, StorageType::Mmap).unwrap();
+        atomic_save_json(&path.join(CONVERSION_FILE), &ConversionMarker { storage_type: StorageType::Mmap }).unwrap();
+        assert!(is_conversion_interrupted(&path));
+        check(StorageType::Mmap);
+        assert!(!is_conversion_interrupted(&path));
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::{const_mutex, Mutex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::spaces::simple::SCORE_BLOCK_SIZE;
+use crate::types::{Distance, ScoreType, VectorElementType};
+
+/// Number of vectors, which are scored by a single round of the kernel benchmark
+const BENCHMARK_VECTORS: usize = 64;
+/// Approximate number of multiplications performed by the benchmark of each kernel
+const BENCHMARK_WORK: usize = 1 << 20;
+/// Kernel is measured several times and the best time is taken, so a preemption does not affect the choice
+const BENCHMARK_RUNS: usize = 3;
+/// Largest dimension, for which fully unrolled kernels are provided
+pub const MAX_UNROLLED_DIM: usize = 16;
+/// Smallest dimension, for which the cache-blocked kernel is considered
+pub const MIN_BLOCKED_DIM: usize = 1024;
+/// Number of query elements, which are multiplied with every vector of the block, before the next ones are read.
+/// 2 KiB of the query stay in L1 cache, while vectors are streamed
+const BLOCKED_TILE: usize = 512;
+/// Number of independent accumulators of the blocked kernel
+const BLOCKED_LANES: usize = 16;
+
+/// Implementation of distance kernels, which uses instructions of a specific CPU architecture
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelKind {
+    /// Portable implementation, which relies on the auto-vectorization of the compiler
+    Scalar,
+    Avx2,
+    Avx512,
+    Neon,
+    /// Fully unrolled loop for the exact dimension, up to `MAX_UNROLLED_DIM`, e.g. of learned hashes
+    Unrolled,
+    /// Loop over tiles of the query with multiple accumulators, for dimensions from `MIN_BLOCKED_DIM`
+    Blocked,
+}
+
+type DotFn = fn(&[VectorElementType], &[VectorElementType]) -> ScoreType;
+type DotBlockFn = fn(&[VectorElementType], &[&[VectorElementType]], &mut [ScoreType]);
+
+/// Distance kernel, which is selected for a metric and dimension
+#[derive(Clone, Copy)]
+pub struct Kernel {
+    pub kind: KernelKind,
+    dot: DotFn,
+    dot_block: DotBlockFn,
+}
+
+impl Kernel {
+    pub fn scalar() -> Self {
+        Kernel { kind: KernelKind::Scalar, dot: dot_scalar, dot_block: dot_block_scalar }
+    }
+
+    pub fn dot(&self, v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        (self.dot)(v1, v2)
+    }
+
+    /// Dot products of the query and a block of vectors
+    pub fn dot_block(&self, v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        (self.dot_block)(v1, block, out)
+    }
+}
+
+fn dot_scalar(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+    v1.iter().zip(v2).map(|(a, b)| a * b).sum()
+}
+
+/// Full blocks are accumulated in independent lanes, so the loop over dimensions is vectorized
+fn dot_block_scalar(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+    if block.len() != SCORE_BLOCK_SIZE {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = dot_scalar(v1, v2);
+        }
+        return;
+    }
+    let dim = v1.len();
+    let mut vectors: [&[VectorElementType]; SCORE_BLOCK_SIZE] = [&[]; SCORE_BLOCK_SIZE];
+    for (vector, v2) in vectors.iter_mut().zip(block) {
+        *vector = &v2[..dim];
+    }
+    let mut acc = [0.0 as ScoreType; SCORE_BLOCK_SIZE];
+    for (i, a) in v1.iter().enumerate() {
+        for (lane, vector) in acc.iter_mut().zip(vectors.iter()) {
+            *lane += a * vector[i];
+        }
+    }
+    out[..SCORE_BLOCK_SIZE].copy_from_slice(&acc);
+}
+
+/// Trip count of the loop is known, so the compiler unrolls it completely
+fn dot_unrolled<const N: usize>(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+    v1[..N].iter().zip(&v2[..N]).map(|(a, b)| a * b).sum()
+}
+
+/// Full blocks are accumulated in lanes of vectors, like by the scalar kernel, but with the known dimension
+fn dot_block_unrolled<const N: usize>(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+    if block.len() != SCORE_BLOCK_SIZE {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = dot_unrolled::<N>(v1, v2);
+        }
+        return;
+    }
+    let query = &v1[..N];
+    let mut vectors: [&[VectorElementType]; SCORE_BLOCK_SIZE] = [&[]; SCORE_BLOCK_SIZE];
+    for (vector, v2) in vectors.iter_mut().zip(block) {
+        *vector = &v2[..N];
+    }
+    let mut acc = [0.0 as ScoreType; SCORE_BLOCK_SIZE];
+    for (i, a) in query.iter().enumerate() {
+        for (lane, vector) in acc.iter_mut().zip(vectors.iter()) {
+            *lane += a * vector[i];
+        }
+    }
+    out[..SCORE_BLOCK_SIZE].copy_from_slice(&acc);
+}
+
+fn unrolled_kernel<const N: usize>() -> Kernel {
+    Kernel { kind: KernelKind::Unrolled, dot: dot_unrolled::<N>, dot_block: dot_block_unrolled::<N> }
+}
+
+/// Unrolled kernel for the dimension up to `MAX_UNROLLED_DIM`
+fn unrolled_kernel_for(dim: usize) -> Option<Kernel> {
+    let kernel = match dim {
+        1 => unrolled_kernel::<1>(),
+        2 => unrolled_kernel::<2>(),
+        3 => unrolled_kernel::<3>(),
+        4 => unrolled_kernel::<4>(),
+        5 => unrolled_kernel::<5>(),
+        6 => unrolled_kernel::<6>(),
+        7 => unrolled_kernel::<7>(),
+        8 => unrolled_kernel::<8>(),
+        9 => unrolled_kernel::<9>(),
+        10 => unrolled_kernel::<10>(),
+        11 => unrolled_kernel::<11>(),
+        12 => unrolled_kernel::<12>(),
+        13 => unrolled_kernel::<13>(),
+        14 => unrolled_kernel::<14>(),
+        15 => unrolled_kernel::<15>(),
+        16 => unrolled_kernel::<16>(),
+        _ => return None,
+    };
+    Some(kernel)
+}
+
+/// Accumulate products of `v1` and `v2` into independent lanes, so the loop is vectorized
+fn accumulate_lanes(acc: &mut [ScoreType; BLOCKED_LANES], v1: &[VectorElementType], v2: &[VectorElementType]) {
+    let chunks = v1.chunks_exact(BLOCKED_LANES).zip(v2.chunks_exact(BLOCKED_LANES));
+    for (c1, c2) in chunks {
+        for ((lane, a), b) in acc.iter_mut().zip(c1).zip(c2) {
+            *lane += a * b;
+        }
+    }
+    let tail = v1.len() - v1.len() % BLOCKED_LANES;
+    for ((lane, a), b) in acc.iter_mut().zip(&v1[tail..]).zip(&v2[tail..]) {
+        *lane += a * b;
+    }
+}
+
+fn dot_blocked(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+    let len = v1.len().min(v2.len());
+    let mut acc = [0.0; BLOCKED_LANES];
+    accumulate_lanes(&mut acc, &v1[..len], &v2[..len]);
+    acc.iter().sum()
+}
+
+/// Tile of the query is multiplied with the same tile of every vector of the block,
+/// so the query is read from memory once per block instead of once per vector
+fn dot_block_blocked(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+    let dim = v1.len();
+    let mut acc = [[0.0; BLOCKED_LANES]; SCORE_BLOCK_SIZE];
+    for tile_start in (0..dim).step_by(BLOCKED_TILE) {
+        let tile = tile_start..(tile_start + BLOCKED_TILE).min(dim);
+        let query = &v1[tile.clone()];
+        for (acc, v2) in acc.iter_mut().zip(block) {
+            accumulate_lanes(acc, query, &v2[tile.clone()]);
+        }
+    }
+    for (score, acc) in out.iter_mut().zip(acc.iter()).take(block.len()) {
+        *score = acc.iter().sum();
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use crate::types::{ScoreType, VectorElementType};
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn dot_avx2_impl(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        let len = v1.len().min(v2.len());
+        let (p1, p2) = (v1.as_ptr(), v2.as_ptr());
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+        let mut i = 0;
+        while i + 16 <= len {
+            acc0 = _mm256_fmadd_ps(_mm256_loadu_ps(p1.add(i)), _mm256_loadu_ps(p2.add(i)), acc0);
+            acc1 = _mm256_fmadd_ps(_mm256_loadu_ps(p1.add(i + 8)), _mm256_loadu_ps(p2.add(i + 8)), acc1);
+            i += 16;
+        }
+        let mut lanes = [0.0; 8];
+        _mm256_storeu_ps(lanes.as_mut_ptr(), _mm256_add_ps(acc0, acc1));
+        let tail: ScoreType = v1[i..len].iter().zip(&v2[i..len]).map(|(a, b)| a * b).sum();
+        lanes.iter().sum::<ScoreType>() + tail
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn dot_avx512_impl(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        let len = v1.len().min(v2.len());
+        let (p1, p2) = (v1.as_ptr(), v2.as_ptr());
+        let mut acc0 = _mm512_setzero_ps();
+        let mut acc1 = _mm512_setzero_ps();
+        let mut i = 0;
+        while i + 32 <= len {
+            acc0 = _mm512_fmadd_ps(_mm512_loadu_ps(p1.add(i)), _mm512_loadu_ps(p2.add(i)), acc0);
+            acc1 = _mm512_fmadd_ps(_mm512_loadu_ps(p1.add(i + 16)), _mm512_loadu_ps(p2.add(i + 16)), acc1);
+            i += 32;
+        }
+        let tail: ScoreType = v1[i..len].iter().zip(&v2[i..len]).map(|(a, b)| a * b).sum();
+        _mm512_reduce_add_ps(_mm512_add_ps(acc0, acc1)) + tail
+    }
+
+    // Functions below are only called once the features are detected, see `available_kernels`
+
+    pub fn dot_avx2(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        unsafe { dot_avx2_impl(v1, v2) }
+    }
+
+    pub fn dot_block_avx2(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = unsafe { dot_avx2_impl(v1, v2) };
+        }
+    }
+
+    pub fn dot_avx512(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        unsafe { dot_avx512_impl(v1, v2) }
+    }
+
+    pub fn dot_block_avx512(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = unsafe { dot_avx512_impl(v1, v2) };
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::*;
+
+    use crate::types::{ScoreType, VectorElementType};
+
+    #[target_feature(enable = "neon")]
+    unsafe fn dot_neon_impl(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        let len = v1.len().min(v2.len());
+        let (p1, p2) = (v1.as_ptr(), v2.as_ptr());
+        let mut acc0 = vdupq_n_f32(0.0);
+        let mut acc1 = vdupq_n_f32(0.0);
+        let mut i = 0;
+        while i + 8 <= len {
+            acc0 = vfmaq_f32(acc0, vld1q_f32(p1.add(i)), vld1q_f32(p2.add(i)));
+            acc1 = vfmaq_f32(acc1, vld1q_f32(p1.add(i + 4)), vld1q_f32(p2.add(i + 4)));
+            i += 8;
+        }
+        let tail: ScoreType = v1[i..len].iter().zip(&v2[i..len]).map(|(a, b)| a * b).sum();
+        vaddvq_f32(vaddq_f32(acc0, acc1)) + tail
+    }
+
+    // Functions below are only called once the features are detected, see `available_kernels`
+
+    pub fn dot_neon(v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
+        unsafe { dot_neon_impl(v1, v2) }
+    }
+
+    pub fn dot_block_neon(v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = unsafe { dot_neon_impl(v1, v2) };
+        }
+    }
+}
+
+/// Features of the CPU, which are relevant to distance kernels
+pub fn cpu_features() -> Vec<String> {
+    #[allow(unused_mut)]
+    let mut features: Vec<&str> = vec![];
+    #[cfg(target_arch = "x86_64")]
+    {
+        for (feature, detected) in [
+            ("sse4.1", is_x86_feature_detected!("sse4.1")),
+            ("avx", is_x86_feature_detected!("avx")),
+            ("avx2", is_x86_feature_detected!("avx2")),
+            ("fma", is_x86_feature_detected!("fma")),
+            ("avx512f", is_x86_feature_detected!("avx512f")),
+        ].iter() {
+            if *detected {
+                features.push(*feature);
+            }
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        for (feature, detected) in [
+            ("neon", std::arch::is_aarch64_feature_detected!("neon")),
+            // Detected, but not used: there are no SVE intrinsics in stable Rust yet
+            ("sve", std::arch::is_aarch64_feature_detected!("sve")),
+        ].iter() {
+            if *detected {
+                features.push(*feature);
+            }
+        }
+    }
+    features.into_iter().map(|feature| feature.to_string()).collect()
+}
+
+/// Kernels, which are supported by the CPU of the host, starting with the scalar one
+pub fn available_kernels() -> Vec<Kernel> {
+    #[allow(unused_mut)]
+    let mut kernels = vec![Kernel::scalar()];
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            kernels.push(Kernel { kind: KernelKind::Avx2, dot: x86::dot_avx2, dot_block: x86::dot_block_avx2 });
+        }
+        if is_x86_feature_detected!("avx512f") {
+            kernels.push(Kernel { kind: KernelKind::Avx512, dot: x86::dot_avx512, dot_block: x86::dot_block_avx512 });
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            kernels.push(Kernel { kind: KernelKind::Neon, dot: arm::dot_neon, dot_block: arm::dot_block_neon });
+        }
+    }
+    kernels
+}
+
+/// Kernels, which are considered for the dimension: available ones and the ones specialized for the dimension
+pub fn candidate_kernels(dim: usize) -> Vec<Kernel> {
+    let mut kernels = available_kernels();
+    kernels.extend(unrolled_kernel_for(dim));
+    if dim >= MIN_BLOCKED_DIM {
+        kernels.push(Kernel { kind: KernelKind::Blocked, dot: dot_blocked, dot_block: dot_block_blocked });
+    }
+    kernels
+}
+
+/// Deterministic vectors, so the benchmark does not depend on a random generator
+fn benchmark_vectors(dim: usize) -> (Vec<VectorElementType>, Vec<Vec<VectorElementType>>) {
+    let value = |seed: usize| ((seed * 7919 % 1000) as VectorElementType) / 1000.0 - 0.5;
+    let query = (0..dim).map(value).collect();
+    let vectors = (0..BENCHMARK_VECTORS)
+        .map(|vector| (0..dim).map(|i| value(vector * dim + i + 1)).collect())
+        .collect();
+    (query, vectors)
+}
+
+/// Best time of scoring the block of benchmark vectors by the kernel
+fn benchmark(kernel: &Kernel, query: &[VectorElementType], vectors: &[Vec<VectorElementType>]) -> Duration {
+    let block: Vec<&[VectorElementType]> = vectors.iter().map(|vector| vector.as_slice()).collect();
+    let mut scores = vec![0.0; block.len()];
+    let rounds = (BENCHMARK_WORK / (query.len() * block.len()).max(1)).max(1);
+    (0..BENCHMARK_RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..rounds {
+                for chunk in block.chunks(SCORE_BLOCK_SIZE) {
+                    kernel.dot_block(query, chunk, &mut scores);
+                }
+                std::hint::black_box(&scores);
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// Benchmark kernels, which are considered for the dimension, and return the fastest one.
+/// Kernel, which does not match the scalar implementation, is never selected
+fn fastest_kernel(dim: usize) -> Kernel {
+    let (query, vectors) = benchmark_vectors(dim);
+    let scalar = Kernel::scalar();
+    candidate_kernels(dim)
+        .into_iter()
+        .filter(|kernel| vectors.iter().all(|vector| {
+            let expected = scalar.dot(&query, vector);
+            (kernel.dot(&query, vector) - expected).abs() <= 1e-3 * expected.abs().max(1.0)
+        }))
+        .min_by_key(|kernel| benchmark(kernel, &query, &vectors))
+        .unwrap_or(scalar)
+}
+
+/// Kernels, which are selected so far, by metric and dimension
+static SELECTED_KERNELS: Mutex<Option<HashMap<(Distance, usize), Kernel>>> = const_mutex(None);
+
+/// Fastest kernel of the host for the metric and dimension.
+/// Kernels are benchmarked on the first request, the choice is reused afterwards
+pub fn select_kernel(distance: Distance, dim: usize) -> Kernel {
+    let mut selected = SELECTED_KERNELS.lock();
+    let selected = selected.get_or_insert_with(HashMap::new);
+    if let Some(kernel) = selected.get(&(distance, dim)) {
+        return *kernel;
+    }
+    let kernel = fastest_kernel(dim);
+    tracing::debug!(kernel = ?kernel.kind, distance = ?distance, dim, "Selected kernel");
+    selected.insert((distance, dim), kernel);
+    kernel
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct SelectedKernel {
+    pub distance: Distance,
+    pub dim: usize,
+    pub kernel: KernelKind,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct KernelTelemetry {
+    /// Features of the CPU, which are relevant to distance kernels
+    pub cpu_features: Vec<String>,
+    /// Kernels, which are supported by the CPU
+    pub available: Vec<KernelKind>,
+    /// Kernels, which are used by metrics of each dimension
+    pub selected: Vec<SelectedKernel>,
+}
+
+pub fn kernel_telemetry() -> KernelTelemetry {
+    let mut selected: Vec<_> = SELECTED_KERNELS.lock()
+        .iter()
+        .flatten()
+        .map(|((distance, dim), kernel)| SelectedKernel { distance: *distance, dim: *dim, kernel: kernel.kind })
+        .collect();
+    selected.sort_by_key(|selected| (selected.dim, format!("{:?}", selected.distance)));
+    KernelTelemetry {
+        cpu_features: cpu_features(),
+        available: available_kernels().iter().map(|kernel| kernel.kind).collect(),
+        selected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        let scalar = Kernel::scalar();
+        for dim in [1, 7, 16, 33, 100, 257, 1024, 1500].iter() {
+            let (query, vectors) = benchmark_vectors(*dim);
+            // Full and partial blocks are scored differently
+            for len in [SCORE_BLOCK_SIZE, 3].iter() {
+                let block: Vec<&[VectorElementType]> = vectors[..*len].iter().map(|v| v.as_slice()).collect();
+                let mut expected = vec![0.0; *len];
+                scalar.dot_block(&query, &block, &mut expected);
+                for kernel in candidate_kernels(*dim) {
+                    let mut scores = vec![0.0; *len];
+                    kernel.dot_block(&query, &block, &mut scores);
+                    for (score, expected) in scores.iter().zip(expected.iter()) {
+                        assert!((score - expected).abs() < 1e-3 * expected.abs().max(1.0), "{:?} kernel of dim {}", kernel.kind, dim);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_specialized_kernels() {
+        let kinds = |dim| candidate_kernels(dim).iter().map(|kernel| kernel.kind).collect::<Vec<_>>();
+        assert!(kinds(8).contains(&KernelKind::Unrolled));
+        assert!(!kinds(8).contains(&KernelKind::Blocked));
+        assert!(!kinds(100).contains(&KernelKind::Unrolled));
+        assert!(!kinds(100).contains(&KernelKind::Blocked));
+        assert!(kinds(2048).contains(&KernelKind::Blocked));
+
+        let kernel = select_kernel(Distance::Cosine, 2048);
+        assert!(kinds(2048).contains(&kernel.kind));
+    }
+
+    #[test]
+    fn test_select_kernel() {
+        let kernel = select_kernel(Distance::Dot, 100);
+        assert!(candidate_kernels(100).iter().any(|candidate| candidate.kind == kernel.kind));
+        assert_eq!(select_kernel(Distance::Dot, 100).kind, kernel.kind);
+
+        let telemetry = kernel_telemetry();
+        assert_eq!(telemetry.available[0], KernelKind::Scalar);
+        assert!(telemetry.selected.contains(&SelectedKernel { distance: Distance::Dot, dim: 100, kernel: kernel.kind }));
+    }
+}
@@ -8,6 +8,14 @@ pub trait Metric {
     /// Greater the value - closer the vectors
     fn similarity(&self, v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType;
 
+    /// Similarity of `v1` to each vector of the block, written into `out`.
+    /// Scoring a block at once lets vectorized kernels process several vectors per pass over the query
+    fn similarity_block(&self, v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        for (score, v2) in out.iter_mut().zip(block) {
+            *score = self.similarity(v1, v2);
+        }
+    }
+
     /// Same as similarity, but using BLAS-supported functions
     fn blas_similarity(&self, v1: &Array1<VectorElementType>, v2: &Array1<VectorElementType>) -> ScoreType;
 
@@ -1,3 +1,4 @@
 pub mod tools;
 pub mod metric;
 pub mod simple;
+pub mod dispatch;
@@ -2,11 +2,33 @@ use ndarray::Array1;
 
 use crate::types::{Distance, ScoreType, VectorElementType};
 
+use super::dispatch::{select_kernel, Kernel};
 use super::metric::Metric;
 
-pub struct DotProductMetric {}
+/// Number of vectors, which are scored by a single pass over the query
+pub const SCORE_BLOCK_SIZE: usize = 8;
 
-pub struct CosineMetric {}
+pub struct DotProductMetric {
+    kernel: Kernel,
+}
+
+pub struct CosineMetric {
+    kernel: Kernel,
+}
+
+impl DotProductMetric {
+    /// Metric, which uses the fastest kernel of the host for vectors of the `dim`
+    pub fn new(dim: usize) -> Self {
+        DotProductMetric { kernel: select_kernel(Distance::Dot, dim) }
+    }
+}
+
+impl CosineMetric {
+    /// Metric, which uses the fastest kernel of the host for vectors of the `dim`
+    pub fn new(dim: usize) -> Self {
+        CosineMetric { kernel: select_kernel(Distance::Cosine, dim) }
+    }
+}
 
 impl Metric for DotProductMetric {
     fn distance(&self) -> Distance {
@@ -14,8 +36,11 @@ impl Metric for DotProductMetric {
     }
 
     fn similarity(&self, v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
-        let ip: f32 = v1.iter().zip(v2).map(|(a, b)| a * b).sum();
-        return ip;
+        self.kernel.dot(v1, v2)
+    }
+
+    fn similarity_block(&self, v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        self.kernel.dot_block(v1, block, out)
     }
 
     fn blas_similarity(&self, v1: &Array1<VectorElementType>, v2: &Array1<VectorElementType>) -> ScoreType {
@@ -33,8 +58,11 @@ impl Metric for CosineMetric {
     }
 
     fn similarity(&self, v1: &[VectorElementType], v2: &[VectorElementType]) -> ScoreType {
-        let cos: VectorElementType = v1.iter().zip(v2).map(|(a, b)| a * b).sum();
-        return cos;
+        self.kernel.dot(v1, v2)
+    }
+
+    fn similarity_block(&self, v1: &[VectorElementType], block: &[&[VectorElementType]], out: &mut [ScoreType]) {
+        self.kernel.dot_block(v1, block, out)
     }
 
     fn blas_similarity(&self, v1: &Array1<VectorElementType>, v2: &Array1<VectorElementType>) -> ScoreType {
@@ -47,3 +75,25 @@ impl Metric for CosineMetric {
         return norm_vector;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity_block() {
+        let metric = DotProductMetric::new(3);
+        let query = vec![1.0, 2.0, 3.0];
+        let vectors: Vec<Vec<VectorElementType>> = (0..SCORE_BLOCK_SIZE + 3)
+            .map(|i| vec![i as VectorElementType, 1.0, -1.0])
+            .collect();
+        for len in [SCORE_BLOCK_SIZE, 3].iter() {
+            let block: Vec<&[VectorElementType]> = vectors[..*len].iter().map(|v| v.as_slice()).collect();
+            let mut scores = vec![0.0; *len];
+            metric.similarity_block(&query, &block, &mut scores);
+            for (score, vector) in scores.iter().zip(vectors.iter()) {
+                assert_eq!(*score, metric.similarity(&query, vector));
+            }
+        }
+    }
+}
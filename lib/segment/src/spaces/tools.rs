@@ -71,11 +71,11 @@ pub fn peek_top_scores<E: Ord + Clone>(scores: &[E], top: usize, distance: &Dist
     return peek_top_scores_iterable(scores.iter().cloned(), top, distance)
 }
 
-pub fn mertic_object(distance: &Distance) -> Box<dyn Metric> {
+pub fn mertic_object(distance: &Distance, dim: usize) -> Box<dyn Metric> {
     match distance {
-        Distance::Cosine => Box::new(CosineMetric {}),
+        Distance::Cosine => Box::new(CosineMetric::new(dim)),
         Distance::Euclid => unimplemented!(),
-        Distance::Dot => Box::new(DotProductMetric {}),
+        Distance::Dot => Box::new(DotProductMetric::new(dim)),
     }
 }
 
@@ -0,0 +1,195 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Upper bounds of latency histogram buckets, in microseconds
+pub const DURATION_BUCKETS_MICROS: [u64; 8] = [100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 1_000_000];
+
+/// Latency statistics of a single operation type
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct OperationDurationStatistics {
+    pub count: usize,
+    pub total_duration_micros: u64,
+    pub max_duration_micros: u64,
+    /// Number of operations per bucket of `DURATION_BUCKETS_MICROS`.
+    /// Last element counts operations, which are slower than the largest bucket bound
+    pub histogram: Vec<usize>,
+}
+
+impl Default for OperationDurationStatistics {
+    fn default() -> Self {
+        OperationDurationStatistics {
+            count: 0,
+            total_duration_micros: 0,
+            max_duration_micros: 0,
+            histogram: vec![0; DURATION_BUCKETS_MICROS.len() + 1],
+        }
+    }
+}
+
+impl OperationDurationStatistics {
+    pub fn add(&mut self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = DURATION_BUCKETS_MICROS.iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(DURATION_BUCKETS_MICROS.len());
+        self.count += 1;
+        self.total_duration_micros += micros;
+        self.max_duration_micros = self.max_duration_micros.max(micros);
+        self.histogram[bucket] += 1;
+    }
+
+    pub fn avg_duration_micros(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.total_duration_micros as f64 / self.count as f64)
+        }
+    }
+
+    pub fn merge(&mut self, other: &OperationDurationStatistics) {
+        self.count += other.count;
+        self.total_duration_micros += other.total_duration_micros;
+        self.max_duration_micros = self.max_duration_micros.max(other.max_duration_micros);
+        for (bucket, other_bucket) in self.histogram.iter_mut().zip(other.histogram.iter()) {
+            *bucket += other_bucket;
+        }
+    }
+}
+
+/// Operations, which are measured by segment telemetry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TelemetryOperation {
+    Search,
+    Upsert,
+    Delete,
+    Payload,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct SegmentTelemetry {
+    pub search: OperationDurationStatistics,
+    pub upsert: OperationDurationStatistics,
+    pub delete: OperationDurationStatistics,
+    pub payload: OperationDurationStatistics,
+    /// Total number of points, returned by search requests
+    pub search_results_count: usize,
+    /// Number of search requests with filter
+    pub filtered_search_count: usize,
+}
+
+impl SegmentTelemetry {
+    fn operation_mut(&mut self, operation: TelemetryOperation) -> &mut OperationDurationStatistics {
+        match operation {
+            TelemetryOperation::Search => &mut self.search,
+            TelemetryOperation::Upsert => &mut self.upsert,
+            TelemetryOperation::Delete => &mut self.delete,
+            TelemetryOperation::Payload => &mut self.payload,
+        }
+    }
+
+    /// Combine telemetry of several segments, e.g. for collection-level report
+    pub fn merge(&mut self, other: &SegmentTelemetry) {
+        self.search.merge(&other.search);
+        self.upsert.merge(&other.upsert);
+        self.delete.merge(&other.delete);
+        self.payload.merge(&other.payload);
+        self.search_results_count += other.search_results_count;
+        self.filtered_search_count += other.filtered_search_count;
+    }
+}
+
+/// Thread-safe accumulator of segment telemetry
+#[derive(Debug, Default)]
+pub struct TelemetryCollector {
+    data: Mutex<SegmentTelemetry>,
+}
+
+impl TelemetryCollector {
+    pub fn add_operation(&self, operation: TelemetryOperation, duration: Duration) {
+        self.data.lock().unwrap().operation_mut(operation).add(duration);
+    }
+
+    pub fn add_search_results(&self, results_count: usize, filtered: bool) {
+        let mut data = self.data.lock().unwrap();
+        data.search_results_count += results_count;
+        if filtered {
+            data.filtered_search_count += 1;
+        }
+    }
+
+    pub fn get_telemetry(&self) -> SegmentTelemetry {
+        self.data.lock().unwrap().clone()
+    }
+}
+
+/// Measures duration of the operation until dropped
+pub struct ScopeDurationMeasurer {
+    collector: Arc<TelemetryCollector>,
+    operation: TelemetryOperation,
+    start: Instant,
+}
+
+impl ScopeDurationMeasurer {
+    pub fn new(collector: &Arc<TelemetryCollector>, operation: TelemetryOperation) -> Self {
+        ScopeDurationMeasurer {
+            collector: collector.clone(),
+            operation,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for ScopeDurationMeasurer {
+    fn drop(&mut self) {
+        self.collector.add_operation(self.operation, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_statistics() {
+        let mut stats = OperationDurationStatistics::default();
+        assert_eq!(stats.avg_duration_micros(), None);
+
+        stats.add(Duration::from_micros(50));
+        stats.add(Duration::from_micros(700));
+        stats.add(Duration::from_secs(2));
+
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.max_duration_micros, 2_000_000);
+        assert_eq!(stats.histogram[0], 1);
+        assert_eq!(stats.histogram[2], 1);
+        assert_eq!(stats.histogram[DURATION_BUCKETS_MICROS.len()], 1);
+
+        let mut merged = OperationDurationStatistics::default();
+        merged.add(Duration::from_micros(60));
+        merged.merge(&stats);
+        assert_eq!(merged.count, 4);
+        assert_eq!(merged.histogram[0], 2);
+        assert_eq!(merged.max_duration_micros, 2_000_000);
+    }
+
+    #[test]
+    fn test_scope_measurer() {
+        let collector = Arc::new(TelemetryCollector::default());
+        {
+            let _measurer = ScopeDurationMeasurer::new(&collector, TelemetryOperation::Upsert);
+        }
+        collector.add_search_results(10, true);
+        collector.add_search_results(5, false);
+
+        let telemetry = collector.get_telemetry();
+        assert_eq!(telemetry.upsert.count, 1);
+        assert_eq!(telemetry.search.count, 0);
+        assert_eq!(telemetry.search_results_count, 15);
+        assert_eq!(telemetry.filtered_search_count, 1);
+    }
+}
@@ -3,8 +3,56 @@ use schemars::{JsonSchema};
 use std::cmp::{Ordering};
 use ordered_float::OrderedFloat;
 use std::collections::{BTreeMap, HashSet, HashMap};
+use std::fmt;
+use std::mem::size_of;
+use std::str::FromStr;
+use uuid::Uuid;
 
-pub type PointIdType = u64;
+use crate::common::config::{check_range, field_path, ConfigProblem, ValidateConfig};
+use crate::entry::entry_point::{OperationError, OperationResult};
+
+/// Type, used for specifying point ID in user interface
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(untagged)]
+pub enum ExtendedPointId {
+    NumId(u64),
+    Uuid(#[schemars(with = "String")] Uuid),
+}
+
+impl From<u64> for ExtendedPointId {
+    fn from(id: u64) -> Self {
+        ExtendedPointId::NumId(id)
+    }
+}
+
+impl From<Uuid> for ExtendedPointId {
+    fn from(uuid: Uuid) -> Self {
+        ExtendedPointId::Uuid(uuid)
+    }
+}
+
+impl fmt::Display for ExtendedPointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtendedPointId::NumId(id) => id.fmt(f),
+            ExtendedPointId::Uuid(uuid) => uuid.fmt(f),
+        }
+    }
+}
+
+impl FromStr for ExtendedPointId {
+    type Err = uuid::Error;
+
+    /// Numeric representation is preferred, so ids from URL path are parsed the same way as in JSON
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u64>() {
+            Ok(id) => Ok(ExtendedPointId::NumId(id)),
+            Err(_) => Uuid::parse_str(s).map(ExtendedPointId::Uuid)
+        }
+    }
+}
+
+pub type PointIdType = ExtendedPointId;
 /// Type of point index across all segments
 pub type PointOffsetType = usize;
 /// Type of point index inside a segment
@@ -22,7 +70,7 @@ pub type FloatPayloadType = f64;
 pub type IntPayloadType = i64;
 
 /// Type of internal tags, build from payload
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
 /// Distance function types used to compare vectors
 pub enum Distance {
     /// https://en.wikipedia.org/wiki/Cosine_similarity
@@ -38,12 +86,24 @@ pub enum Order {
     SmallBetter,
 }
 
-#[derive(Deserialize, Serialize, JsonSchema, Copy, Clone, PartialEq, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct ScoredPoint {
     /// Point id
     pub id: PointIdType,
+    /// Version of the point: number of the last operation, which changed it
+    pub version: SeqNumberType,
     /// Points vector distance to the query vector
     pub score: ScoreType,
+    /// Payload - values assigned to the point. Only present if requested
+    pub payload: Option<TheMap<PayloadKeyType, PayloadType>>,
+    /// Vector of the point. Only present if requested
+    pub vector: Option<Vec<VectorElementType>>,
+}
+
+impl PartialEq for ScoredPoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.score == other.score
+    }
 }
 
 impl Eq for ScoredPoint {}
@@ -79,16 +139,148 @@ pub struct PayloadSchemaInfo {
     pub indexed: bool,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Current state of the segment
+pub enum SegmentStatus {
+    /// Segment without any index, optimized for fast updates
+    Plain,
+    /// Segment with built index, optimized for search
+    Indexed,
+    /// Segment is being optimized. New changes are stored in a temporary segment
+    Optimizing,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+/// Disk space, occupied by each component of the segment
+pub struct SegmentDiskUsage {
+    pub vector_storage_bytes: usize,
+    pub id_mapper_bytes: usize,
+    pub payload_storage_bytes: usize,
+    pub payload_index_bytes: usize,
+}
+
+impl SegmentDiskUsage {
+    pub fn total(&self) -> usize {
+        self.vector_storage_bytes + self.id_mapper_bytes + self.payload_storage_bytes + self.payload_index_bytes
+    }
+
+    pub fn merge(&mut self, other: &SegmentDiskUsage) {
+        self.vector_storage_bytes += other.vector_storage_bytes;
+        self.id_mapper_bytes += other.id_mapper_bytes;
+        self.payload_storage_bytes += other.payload_storage_bytes;
+        self.payload_index_bytes += other.payload_index_bytes;
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+/// Estimated RAM, occupied by each component of the segment. Memory mapped data, which is loaded by the OS
+/// on demand, and overhead of the allocator are not included
+pub struct SegmentMemoryUsage {
+    pub vector_storage_bytes: usize,
+    pub id_mapper_bytes: usize,
+    pub payload_storage_bytes: usize,
+    pub payload_index_bytes: usize,
+}
+
+impl SegmentMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.vector_storage_bytes + self.id_mapper_bytes + self.payload_storage_bytes + self.payload_index_bytes
+    }
+
+    pub fn merge(&mut self, other: &SegmentMemoryUsage) {
+        self.vector_storage_bytes += other.vector_storage_bytes;
+        self.id_mapper_bytes += other.id_mapper_bytes;
+        self.payload_storage_bytes += other.payload_storage_bytes;
+        self.payload_index_bytes += other.payload_index_bytes;
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct SegmentInfo {
     pub segment_type: SegmentType,
+    pub status: SegmentStatus,
     pub num_vectors: usize,
     pub num_deleted_vectors: usize,
+    /// Estimated size of all components, which are kept in memory
     pub ram_usage_bytes: usize,
+    pub memory_usage: SegmentMemoryUsage,
     pub disk_usage_bytes: usize,
+    pub disk_usage: SegmentDiskUsage,
     pub is_appendable: bool,
     pub schema: HashMap<PayloadKeyType, PayloadSchemaInfo>,
+    pub config: SegmentConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Behaviour of the segment consistency check
+pub enum ConsistencyCheckMode {
+    /// Only report found problems
+    Check,
+    /// Report found problems and fix those which could be fixed without data loss of valid points
+    Repair,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+/// Result of the segment consistency check
+pub struct ConsistencyReport {
+    /// Human-readable description of each found problem
+    pub problems: Vec<String>,
+    /// Number of problems, which were fixed during the check
+    pub repaired: usize,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Type of the structure, used to index payload field
+pub enum FieldIndexType {
+    /// Sorted list of integer values, used for range queries
+    Int,
+    /// Map of integer values, used for exact match
+    IntMap,
+    /// Map of keyword values, used for exact match
+    Keyword,
+    /// Sorted list of float values, used for range queries
+    Float,
+    /// Map of text tokens, used for full-text match
+    FullText,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Number of indexed values within the `[from, to)` interval
+pub struct HistogramBucket {
+    pub from: f64,
+    pub to: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Statistics of a single payload field index
+pub struct PayloadIndexInfo {
+    pub index_type: FieldIndexType,
+    /// Number of points with at least one indexed value
+    pub points_count: usize,
+    /// Total number of indexed values. Each point can have several values
+    pub values_count: usize,
+    /// Number of unique values in index
+    pub distinct_values: usize,
+    /// Approximate amount of memory, occupied by the index
+    pub memory_usage_bytes: usize,
+    /// Distribution of values. Only available for numeric indexes
+    pub histogram: Option<Vec<HistogramBucket>>,
 }
 
 
@@ -103,6 +295,84 @@ pub enum SearchParams {
     }
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Select which payload fields should be returned
+pub enum PayloadSelector {
+    /// Only return these fields
+    Include(Vec<PayloadKeyType>),
+    /// Return all fields except these
+    Exclude(Vec<PayloadKeyType>),
+}
+
+impl PayloadSelector {
+    pub fn process(&self, payload: TheMap<PayloadKeyType, PayloadType>) -> TheMap<PayloadKeyType, PayloadType> {
+        match self {
+            PayloadSelector::Include(keys) => payload.into_iter()
+                .filter(|(key, _)| keys.contains(key))
+                .collect(),
+            PayloadSelector::Exclude(keys) => payload.into_iter()
+                .filter(|(key, _)| !keys.contains(key))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+/// Options for specifying which payload to include or not
+pub enum WithPayloadInterface {
+    /// If `true` - return all payload, if `false` - do not return payload
+    Bool(bool),
+    /// Specify which fields to return
+    Fields(Vec<PayloadKeyType>),
+    /// Specify included or excluded fields
+    Selector(PayloadSelector),
+}
+
+impl Default for WithPayloadInterface {
+    fn default() -> Self {
+        WithPayloadInterface::Bool(false)
+    }
+}
+
+/// Normalized representation of `WithPayloadInterface`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WithPayload {
+    /// Enable return payloads or not
+    pub enable: bool,
+    /// Filter include and exclude payloads
+    pub payload_selector: Option<PayloadSelector>,
+}
+
+impl From<bool> for WithPayload {
+    fn from(enable: bool) -> Self {
+        WithPayload {
+            enable,
+            payload_selector: None,
+        }
+    }
+}
+
+impl From<&WithPayloadInterface> for WithPayload {
+    fn from(interface: &WithPayloadInterface) -> Self {
+        match interface {
+            WithPayloadInterface::Bool(enable) => WithPayload {
+                enable: *enable,
+                payload_selector: None,
+            },
+            WithPayloadInterface::Fields(fields) => WithPayload {
+                enable: true,
+                payload_selector: Some(PayloadSelector::Include(fields.clone())),
+            },
+            WithPayloadInterface::Selector(selector) => WithPayload {
+                enable: true,
+                payload_selector: Some(selector.clone()),
+            },
+        }
+    }
+}
+
 /// This function only stores mapping between distance and preferred result order
 pub fn distance_order(distance: &Distance) -> Order {
     match distance {
@@ -144,6 +414,24 @@ impl Default for Indexes {
     }
 }
 
+/// Largest dimension of vectors
+pub const MAX_VECTOR_SIZE: usize = 65536;
+/// Allowed number of edges per node of the HNSW graph
+pub const MIN_HNSW_M: usize = 2;
+pub const MAX_HNSW_M: usize = 512;
+/// Largest number of neighbours, considered during the HNSW index building
+pub const MAX_HNSW_EF_CONSTRUCT: usize = 10_000;
+
+impl ValidateConfig for Indexes {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if let Indexes::Hnsw { m, ef_construct } = *self {
+            let options_path = field_path(path, "options");
+            check_range(&field_path(&options_path, "m"), m, MIN_HNSW_M, MAX_HNSW_M, problems);
+            check_range(&field_path(&options_path, "ef_construct"), ef_construct, 1, MAX_HNSW_EF_CONSTRUCT, problems);
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type", content = "options")]
@@ -179,7 +467,86 @@ impl Default for StorageType {
 }
 
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Language of the stemming algorithm
+pub enum StemmerLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+}
+
+fn default_lowercase() -> bool { true }
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Rules of splitting text payload into tokens.
+/// Same rules are applied to indexed values and to the query text.
+pub struct TextAnalyzerConfig {
+    /// Convert all tokens to lowercase. Default: true
+    #[serde(default = "default_lowercase")]
+    pub lowercase: bool,
+    /// Tokens shorter than this value are ignored
+    pub min_token_len: Option<usize>,
+    /// Tokens longer than this value are ignored
+    pub max_token_len: Option<usize>,
+    /// Reduce tokens to their word stem using the algorithm for a given language
+    pub stemmer: Option<StemmerLanguage>,
+    /// Tokens which are ignored during analysis
+    #[serde(default)]
+    pub stopwords: Vec<String>,
+}
+
+impl ValidateConfig for TextAnalyzerConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if !cfg!(feature = "full_text") && self.stemmer.is_some() {
+            problems.push(ConfigProblem::new(
+                &field_path(path, "stemmer"),
+                "stemming is not supported, segment is built without `full_text` feature",
+            ));
+        }
+        if self.min_token_len == Some(0) {
+            problems.push(ConfigProblem::new(&field_path(path, "min_token_len"), "should be positive"));
+        }
+        if let (Some(min_token_len), Some(max_token_len)) = (self.min_token_len, self.max_token_len) {
+            if min_token_len > max_token_len {
+                problems.push(ConfigProblem::new(
+                    &field_path(path, "max_token_len"),
+                    format!("{} is less than min_token_len {}, all tokens would be ignored", max_token_len, min_token_len),
+                ));
+            }
+        }
+    }
+}
+
+impl Default for TextAnalyzerConfig {
+    fn default() -> Self {
+        TextAnalyzerConfig {
+            lowercase: default_lowercase(),
+            min_token_len: None,
+            max_token_len: None,
+            stemmer: None,
+            stopwords: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct SegmentConfig {
     /// Size of a vectors used
@@ -192,6 +559,91 @@ pub struct SegmentConfig {
     pub distance: Distance,
     /// Type of vector storage
     pub storage_type: StorageType,
+    /// Text analysis rules for keyword payload fields, which should be searchable by full-text match
+    #[serde(default)]
+    pub text_analyzers: HashMap<PayloadKeyType, TextAnalyzerConfig>,
+    /// When changes should be persisted. If not specified - service-wide flush interval is used
+    #[serde(default)]
+    pub flush_policy: Option<FlushPolicy>,
+}
+
+impl SegmentConfig {
+    /// Describe parameters of the persisted config, which do not match parameters expected by the caller.
+    /// Only parameters required for the correct interpretation of stored vectors are compared:
+    /// index and storage type may differ between segments of the same collection.
+    pub fn mismatches(&self, expected: &SegmentConfig) -> Vec<String> {
+        let mut mismatches = vec![];
+        if self.vector_size != expected.vector_size {
+            mismatches.push(format!("vector size {} != {}", self.vector_size, expected.vector_size));
+        }
+        if self.distance != expected.distance {
+            mismatches.push(format!("distance {:?} != {:?}", self.distance, expected.distance));
+        }
+        mismatches
+    }
+
+    /// Report parameters of the config, which do not match the config of existing vectors, see `mismatches`
+    pub fn check_compatible(&self, path: &str, existing: &SegmentConfig, problems: &mut Vec<ConfigProblem>) {
+        if self.vector_size != existing.vector_size {
+            problems.push(ConfigProblem::new(
+                &field_path(path, "vector_size"),
+                format!("dimension {} does not match dimension {} of existing vectors", self.vector_size, existing.vector_size),
+            ));
+        }
+        if self.distance != existing.distance {
+            problems.push(ConfigProblem::new(
+                &field_path(path, "distance"),
+                format!("{:?} does not match {:?} distance of existing vectors", self.distance, existing.distance),
+            ));
+        }
+    }
+}
+
+impl ValidateConfig for SegmentConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        check_range(&field_path(path, "vector_size"), self.vector_size, 1, MAX_VECTOR_SIZE, problems);
+        if self.distance == Distance::Euclid {
+            problems.push(ConfigProblem::new(&field_path(path, "distance"), "Euclid distance is not supported yet"));
+        }
+        self.index.check(&field_path(path, "index"), problems);
+        if !cfg!(feature = "payload_index") && self.payload_index == Some(PayloadIndexType::Struct) {
+            problems.push(ConfigProblem::new(
+                &field_path(path, "payload_index"),
+                "struct payload index is not supported, segment is built without `payload_index` feature",
+            ));
+        }
+        for (key, analyzer) in &self.text_analyzers {
+            analyzer.check(&field_path(&field_path(path, "text_analyzers"), key), problems);
+        }
+        self.flush_policy.check(&field_path(path, "flush_policy"), problems);
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Defines when segments are flushed to disk. Applied to vector storage, deleted flags and payload storage at once,
+/// WAL is truncated right after the flush.
+pub enum FlushPolicy {
+    /// Flush if at least given number of seconds passed since the last flush
+    Interval { seconds: u64 },
+    /// Flush after given number of update operations
+    Operations { count: usize },
+    /// Flush only on explicit request
+    Manual,
+}
+
+impl ValidateConfig for FlushPolicy {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        match *self {
+            FlushPolicy::Interval { seconds: 0 } => problems.push(
+                ConfigProblem::new(&field_path(path, "interval.seconds"), "should be positive")
+            ),
+            FlushPolicy::Operations { count: 0 } => problems.push(
+                ConfigProblem::new(&field_path(path, "operations.count"), "should be positive")
+            ),
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
@@ -199,9 +651,58 @@ pub struct SegmentConfig {
 pub struct SegmentState {
     pub version: SeqNumberType,
     pub config: SegmentConfig,
+    /// Version of the on-disk layout of the segment. Segments created before versioning have version 0
+    #[serde(default)]
+    pub format_version: u32,
+    /// Payload migrations, which are not applied to all points of the segment yet
+    #[serde(default)]
+    pub payload_migrations: Vec<PayloadMigration>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+/// Rename of the payload key and/or conversion of its values, which is applied to the points in the background
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PayloadMigration {
+    /// Number of the operation, which requested the migration.
+    /// Points with this or newer version are already migrated
+    pub version: SeqNumberType,
+    pub key: PayloadKeyType,
+    pub new_key: PayloadKeyType,
+    /// Type of the migrated values
+    pub new_type: PayloadSchemaType,
+}
+
+impl PayloadMigration {
+    /// Apply migration to the payload of a single point.
+    /// Returns the new value, if the point has the migrated key
+    pub fn apply(&self, payload: &mut TheMap<PayloadKeyType, PayloadType>) -> OperationResult<Option<PayloadType>> {
+        let new_value = match payload.get(&self.key) {
+            None => return Ok(None),
+            Some(value) => value.convert(&self.new_type).ok_or_else(|| OperationError::TypeError {
+                field_name: self.key.to_owned(),
+                expected_type: format!("{:?}", self.new_type),
+            })?
+        };
+        payload.remove(&self.key);
+        payload.insert(self.new_key.to_owned(), new_value.clone());
+        Ok(Some(new_value))
+    }
+
+    /// Apply migrations, which are newer than the point, to the payload of the point.
+    /// Values are validated when the migration is requested, so the conversion never fails here
+    pub fn apply_pending(migrations: &[PayloadMigration], point_version: SeqNumberType, payload: &mut TheMap<PayloadKeyType, PayloadType>) {
+        for migration in migrations.iter().filter(|migration| migration.version > point_version) {
+            migration.apply(payload).ok();
+        }
+    }
+
+    /// Check if the migration changes any of the given keys
+    pub fn affects(&self, key: &PayloadKeyType, new_key: &PayloadKeyType) -> bool {
+        [&self.key, &self.new_key].iter().any(|affected| *affected == key || *affected == new_key)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct GeoPoint {
     pub lon: f64,
@@ -218,6 +719,26 @@ pub enum PayloadType {
     Geo(Vec<GeoPoint>),
 }
 
+impl PayloadType {
+    /// Estimated heap memory, occupied by the values
+    pub fn memory_usage_bytes(&self) -> usize {
+        match self {
+            PayloadType::Keyword(values) => values.capacity() * size_of::<String>()
+                + values.iter().map(|value| value.capacity()).sum::<usize>(),
+            PayloadType::Integer(values) => values.capacity() * size_of::<IntPayloadType>(),
+            PayloadType::Float(values) => values.capacity() * size_of::<FloatPayloadType>(),
+            PayloadType::Geo(values) => values.capacity() * size_of::<GeoPoint>(),
+        }
+    }
+}
+
+/// Estimated memory, occupied by the payload of a point
+pub fn payload_memory_usage(payload: &TheMap<PayloadKeyType, PayloadType>) -> usize {
+    payload.iter()
+        .map(|(key, value)| size_of::<PayloadKeyType>() + key.capacity() + size_of::<PayloadType>() + value.memory_usage_bytes())
+        .sum()
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type", content = "value")]
@@ -239,17 +760,54 @@ impl From<&PayloadType> for PayloadSchemaType {
     }
 }
 
+impl PayloadType {
+    /// Convert payload values into another type.
+    /// Returns `None` if at least one of the values can't be converted without loss.
+    pub fn convert(&self, target: &PayloadSchemaType) -> Option<PayloadType> {
+        match (self, target) {
+            (PayloadType::Keyword(values), PayloadSchemaType::Keyword) => Some(PayloadType::Keyword(values.clone())),
+            (PayloadType::Keyword(values), PayloadSchemaType::Integer) => values.iter()
+                .map(|x| x.trim().parse().ok())
+                .collect::<Option<_>>()
+                .map(PayloadType::Integer),
+            (PayloadType::Keyword(values), PayloadSchemaType::Float) => values.iter()
+                .map(|x| x.trim().parse().ok())
+                .collect::<Option<_>>()
+                .map(PayloadType::Float),
+            (PayloadType::Integer(values), PayloadSchemaType::Keyword) => Some(PayloadType::Keyword(
+                values.iter().map(|x| x.to_string()).collect()
+            )),
+            (PayloadType::Integer(values), PayloadSchemaType::Integer) => Some(PayloadType::Integer(values.clone())),
+            (PayloadType::Integer(values), PayloadSchemaType::Float) => Some(PayloadType::Float(
+                values.iter().map(|x| *x as FloatPayloadType).collect()
+            )),
+            (PayloadType::Float(values), PayloadSchemaType::Keyword) => Some(PayloadType::Keyword(
+                values.iter().map(|x| x.to_string()).collect()
+            )),
+            (PayloadType::Float(values), PayloadSchemaType::Integer) => values.iter()
+                .map(|x| if x.fract() == 0.0 { Some(*x as IntPayloadType) } else { None })
+                .collect::<Option<_>>()
+                .map(PayloadType::Integer),
+            (PayloadType::Float(values), PayloadSchemaType::Float) => Some(PayloadType::Float(values.clone())),
+            (PayloadType::Geo(values), PayloadSchemaType::Geo) => Some(PayloadType::Geo(values.clone())),
+            _ => None
+        }
+    }
+}
+
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct Match {
     /// Keyword value to match
     pub keyword: Option<String>,
     /// Integer value to match
     pub integer: Option<IntPayloadType>,
+    /// Full-text match: each token of the text should be present in the keyword payload value
+    pub text: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct Range {
     /// point.key < range.lt
@@ -262,7 +820,7 @@ pub struct Range {
     pub lte: Option<FloatPayloadType>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct GeoBoundingBox {
     /// Coordinates of the top left point of the area rectangle
@@ -271,7 +829,7 @@ pub struct GeoBoundingBox {
     pub bottom_right: GeoPoint,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct GeoRadius {
     /// Coordinates of the top left point of the area rectangle
@@ -280,7 +838,7 @@ pub struct GeoRadius {
     pub radius: f64,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct FieldCondition {
     pub key: PayloadKeyType,
@@ -294,7 +852,7 @@ pub struct FieldCondition {
     pub geo_radius: Option<GeoRadius>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 pub struct HasIdCondition {
     pub has_id: HashSet<PointIdType>
 }
@@ -307,7 +865,7 @@ impl Into<HasIdCondition> for HashSet<PointIdType> {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum Condition {
     /// Check if field satisfies provided condition
@@ -318,7 +876,15 @@ pub enum Condition {
     Filter(Filter),
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct MinShould {
+    pub conditions: Vec<Condition>,
+    /// Minimal number of conditions, which should match
+    pub min_count: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "snake_case")]
 pub struct Filter {
@@ -326,6 +892,8 @@ pub struct Filter {
     pub should: Option<Vec<Condition>>,
     /// All conditions must match
     pub must: Option<Vec<Condition>>,
+    /// At least `min_count` of conditions should match
+    pub min_should: Option<MinShould>,
     /// All conditions must NOT match
     pub must_not: Option<Vec<Condition>>,
 }
@@ -335,6 +903,7 @@ impl Filter {
         Filter {
             should: None,
             must: Some(vec![condition]),
+            min_should: None,
             must_not: None,
         }
     }
@@ -343,6 +912,7 @@ impl Filter {
         Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![condition]),
         }
     }
@@ -355,6 +925,38 @@ mod tests {
 
     use serde_json;
 
+    #[test]
+    fn test_point_id_parsing() {
+        let uuid_str = "550e8400-e29b-41d4-a716-446655440000";
+        let ids: Vec<PointIdType> = serde_json::from_str(&format!(r#"[10, "{}"]"#, uuid_str)).unwrap();
+        assert_eq!(ids[0], ExtendedPointId::NumId(10));
+        assert_eq!(ids[1], ExtendedPointId::Uuid(Uuid::parse_str(uuid_str).unwrap()));
+
+        assert_eq!("10".parse::<PointIdType>().unwrap(), ids[0]);
+        assert_eq!(uuid_str.parse::<PointIdType>().unwrap(), ids[1]);
+        assert_eq!(ids[1].to_string(), uuid_str);
+        assert!("abc".parse::<PointIdType>().is_err());
+    }
+
+    #[test]
+    fn test_with_payload_interface() {
+        let with_payload: WithPayloadInterface = serde_json::from_str("true").unwrap();
+        assert_eq!(WithPayload::from(&with_payload), WithPayload { enable: true, payload_selector: None });
+
+        let with_payload: WithPayloadInterface = serde_json::from_str(r#"["color"]"#).unwrap();
+        let selector = WithPayload::from(&with_payload).payload_selector.unwrap();
+        assert_eq!(selector, PayloadSelector::Include(vec!["color".to_owned()]));
+
+        let with_payload: WithPayloadInterface = serde_json::from_str(r#"{"exclude": ["color"]}"#).unwrap();
+        let selector = WithPayload::from(&with_payload).payload_selector.unwrap();
+
+        let mut payload: TheMap<PayloadKeyType, PayloadType> = TheMap::new();
+        payload.insert("color".to_owned(), PayloadType::Keyword(vec!["red".to_owned()]));
+        payload.insert("price".to_owned(), PayloadType::Integer(vec![10]));
+        let processed = selector.process(payload);
+        assert_eq!(processed.keys().collect::<Vec<_>>(), vec!["price"]);
+    }
+
     #[test]
     fn test_name() {
         let label = PayloadType::Keyword(vec!["Hello".to_owned()]);
@@ -370,11 +972,13 @@ mod tests {
                 r#match: Some(Match {
                     keyword: Some("world".to_owned()),
                     integer: None,
+                    text: None,
                 }),
                 range: None,
                 geo_bounding_box: None,
                 geo_radius: None,
             })]),
+            min_should: None,
             must_not: None,
             should: None,
         };
@@ -382,6 +986,22 @@ mod tests {
         println!("{}", json)
     }
 
+    #[test]
+    fn test_payload_conversion() {
+        let keywords = PayloadType::Keyword(vec!["10".to_owned(), " 20".to_owned()]);
+        match keywords.convert(&PayloadSchemaType::Integer) {
+            Some(PayloadType::Integer(values)) => assert_eq!(values, vec![10, 20]),
+            _ => panic!("Keywords should be converted to integers")
+        }
+        assert!(PayloadType::Keyword(vec!["red".to_owned()]).convert(&PayloadSchemaType::Float).is_none());
+        assert!(PayloadType::Float(vec![1.5]).convert(&PayloadSchemaType::Integer).is_none());
+        match PayloadType::Float(vec![2.0]).convert(&PayloadSchemaType::Integer) {
+            Some(PayloadType::Integer(values)) => assert_eq!(values, vec![2]),
+            _ => panic!("Float without fractional part should be converted to integer")
+        }
+        assert!(PayloadType::Integer(vec![1]).convert(&PayloadSchemaType::Geo).is_none());
+    }
+
     #[test]
     fn test_deny_unknown_fields() {
          let query1 = r#"
@@ -449,3 +1069,6 @@ mod tests {
 
 pub type TheMap<K, V> = BTreeMap<K, V>;
 
+/// Point of the batch upsert: id, vector and optional full payload
+pub type BatchPoint = (PointIdType, Vec<VectorElementType>, Option<TheMap<PayloadKeyType, PayloadType>>);
+
@@ -1,14 +1,22 @@
 use crate::vector_storage::vector_storage::{VectorStorage, ScoredPointOffset};
-use crate::entry::entry_point::OperationResult;
+use crate::entry::entry_point::{OperationResult, OperationError};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::fs::{File, OpenOptions, create_dir_all};
+use std::fs::{File, OpenOptions, create_dir_all, copy};
 use memmap::{MmapOptions, Mmap, MmapMut};
 use std::mem::{size_of, transmute};
 use crate::types::{VectorElementType, PointOffsetType, Distance};
 use std::io::Write;
-use crate::spaces::tools::{mertic_object, peek_top_scores};
+use crate::spaces::metric::Metric;
+use crate::spaces::simple::{CosineMetric, DotProductMetric, SCORE_BLOCK_SIZE};
 use crate::common::error_logging::LogError;
+use crate::common::file_operations::{link_or_copy, unshare_file};
+use crate::common::numa::bind_memory;
+use crate::common::page_cache;
+use crate::common::readahead::Readahead;
+use crate::common::stop_condition::StopCondition;
+use crate::vector_storage::raw_scorer::{RawScorer, score_top, score_top_all};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct MemmapVectorStorage {
     dim: usize,
@@ -18,10 +26,22 @@ pub struct MemmapVectorStorage {
     data_path: PathBuf,
     deleted_path: PathBuf,
     deleted_count: usize,
+    /// NUMA node, on which vectors are placed. Mapping is not bound, if equals to `NO_NUMA_NODE`
+    numa_node: AtomicUsize,
+    /// Readahead of sequential reads of vectors
+    readahead: Readahead,
 }
 
 const HEADER_SIZE: usize = 4;
 
+const NO_NUMA_NODE: usize = usize::MAX;
+
+/// Reads of points, which are spread over more than this number of vectors per point, do not benefit from readahead
+const MAX_SEQUENTIAL_SPARSITY: usize = 4;
+
+pub const DATA_FILE: &str = "matrix.dat";
+pub const DELETED_FILE: &str = "deleted.dat";
+
 fn vf_to_u8<T>(v: &Vec<T>) -> &[u8] {
     unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * size_of::<T>()) }
 }
@@ -70,19 +90,50 @@ impl MemmapVectorStorage {
     }
 
 
-    pub fn open(path: &Path, dim: usize) -> OperationResult<Self> {
-        create_dir_all(path)?;
+    /// Private copy-on-write mapping of the file opened for reading only.
+    /// Changes of the mapping are never written back to the file.
+    fn open_copy(path: &Path) -> OperationResult<MmapMut> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)?;
 
-        let data_path = path.join("matrix.dat");
-        let deleted_path = path.join("deleted.dat");
+        let mmap = unsafe { MmapOptions::new().map_copy(&file)? };
+        return Ok(mmap);
+    }
 
-        MemmapVectorStorage::ensure_data_file_exists(data_path.as_path()).describe("Create mmap data file")?;
-        MemmapVectorStorage::ensure_deleted_file_exists(deleted_path.as_path()).describe("Create mmap deleted flags file")?;
+    pub fn open(path: &Path, dim: usize) -> OperationResult<Self> {
+        Self::open_with_mode(path, dim, false)
+    }
 
-        let mmap = MemmapVectorStorage::open_read(&data_path).describe("Open mmap for reading")?;
-        let num_vectors = (mmap.len() - HEADER_SIZE) / dim / size_of::<VectorElementType>();
+    /// Open existing storage without write access to its files
+    pub fn open_read_only(path: &Path, dim: usize) -> OperationResult<Self> {
+        Self::open_with_mode(path, dim, true)
+    }
 
-        let deleted_mmap = MemmapVectorStorage::open_write(&deleted_path).describe("Open mmap for writing")?;
+    fn open_with_mode(path: &Path, dim: usize, read_only: bool) -> OperationResult<Self> {
+        let data_path = path.join(DATA_FILE);
+        let deleted_path = path.join(DELETED_FILE);
+
+        let (mmap, deleted_mmap) = if read_only {
+            let mmap = unsafe { MmapOptions::new().map(&File::open(&data_path)?)? };
+            let deleted_mmap = MemmapVectorStorage::open_copy(&deleted_path).describe("Open mmap for reading")?;
+            (mmap, deleted_mmap)
+        } else {
+            create_dir_all(path)?;
+            MemmapVectorStorage::ensure_data_file_exists(data_path.as_path()).describe("Create mmap data file")?;
+            MemmapVectorStorage::ensure_deleted_file_exists(deleted_path.as_path()).describe("Create mmap deleted flags file")?;
+
+            let mmap = MemmapVectorStorage::open_read(&data_path).describe("Open mmap for reading")?;
+            let deleted_mmap = MemmapVectorStorage::open_write(&deleted_path).describe("Open mmap for writing")?;
+            (mmap, deleted_mmap)
+        };
+        if !mmap.starts_with(b"data") {
+            return Err(OperationError::Corrupted { path: data_path, description: "missing data header".to_owned() });
+        }
+        if !deleted_mmap.starts_with(b"drop") {
+            return Err(OperationError::Corrupted { path: deleted_path, description: "missing deleted flags header".to_owned() });
+        }
+        let num_vectors = (mmap.len() - HEADER_SIZE) / dim / size_of::<VectorElementType>();
 
         let deleted_count = (HEADER_SIZE..deleted_mmap.len())
             .map(|idx| *deleted_mmap.get(idx).unwrap() as usize).sum();
@@ -95,6 +146,8 @@ impl MemmapVectorStorage {
             data_path,
             deleted_path,
             deleted_count,
+            numa_node: AtomicUsize::new(NO_NUMA_NODE),
+            readahead: Readahead::default(),
         })
     }
 
@@ -124,6 +177,76 @@ impl MemmapVectorStorage {
     fn deleted(&self, key: PointOffsetType) -> Option<bool> {
         self.deleted_mmap.as_ref().unwrap().get(HEADER_SIZE + key).map(|x| *x > 0)
     }
+
+    /// Register read of `count` vectors from `first` to `last` for the readahead.
+    /// Unordered or sparse reads are random, so kernel readahead is dropped for them
+    fn observe_reads(&self, first: PointOffsetType, last: PointOffsetType, count: usize) {
+        let mmap = match &self.mmap {
+            Some(mmap) => mmap,
+            None => return,
+        };
+        let range = match (self.data_offset(first), self.data_offset(last)) {
+            (Some(start), Some(end)) => start..end + self.raw_size(),
+            _ => return,
+        };
+        if first <= last && last - first < count * MAX_SEQUENTIAL_SPARSITY {
+            self.readahead.observe(mmap, range);
+        } else {
+            self.readahead.observe_random(mmap);
+        }
+    }
+}
+
+
+struct MemmapRawScorer<'a, M: Metric> {
+    storage: &'a MemmapVectorStorage,
+    metric: M,
+    query: Vec<VectorElementType>,
+}
+
+impl<'a, M: Metric> MemmapRawScorer<'a, M> {
+    fn new(storage: &'a MemmapVectorStorage, metric: M, vector: &Vec<VectorElementType>) -> Self {
+        let query = metric.preprocess(vector.clone());
+        MemmapRawScorer { storage, metric, query }
+    }
+}
+
+impl<'a, M: Metric> MemmapRawScorer<'a, M> {
+    fn score_block(&self, ids: &[PointOffsetType], block: &[&[VectorElementType]], out: &mut [ScoredPointOffset]) {
+        let mut scores = [0.0; SCORE_BLOCK_SIZE];
+        self.metric.similarity_block(&self.query, block, &mut scores[..block.len()]);
+        for ((scored, &idx), &score) in out.iter_mut().zip(ids).zip(scores.iter()) {
+            *scored = ScoredPointOffset { idx, score };
+        }
+    }
+}
+
+impl<M: Metric> RawScorer for MemmapRawScorer<'_, M> {
+    /// Vectors of not deleted points are gathered into blocks, which are scored by a single metric call
+    fn score_points(&self, ids: &[PointOffsetType], out: &mut [ScoredPointOffset]) -> usize {
+        if let (Some(&first), Some(&last)) = (ids.first(), ids.last()) {
+            self.storage.observe_reads(first, last, ids.len());
+        }
+        let mut count = 0;
+        let mut block_ids = [0; SCORE_BLOCK_SIZE];
+        let mut block: [&[VectorElementType]; SCORE_BLOCK_SIZE] = [&[]; SCORE_BLOCK_SIZE];
+        let mut block_len = 0;
+        for &idx in ids {
+            if self.storage.deleted(idx).unwrap_or(true) {
+                continue;
+            }
+            block_ids[block_len] = idx;
+            block[block_len] = self.storage.raw_vector(idx).unwrap();
+            block_len += 1;
+            if block_len == SCORE_BLOCK_SIZE {
+                self.score_block(&block_ids, &block, &mut out[count..]);
+                count += block_len;
+                block_len = 0;
+            }
+        }
+        self.score_block(&block_ids[..block_len], &block[..block_len], &mut out[count..]);
+        count + block_len
+    }
 }
 
 
@@ -144,6 +267,7 @@ impl VectorStorage for MemmapVectorStorage {
         match self.deleted(key) {
             None => None,
             Some(false) => self.data_offset(key).map(|offset| {
+                self.observe_reads(key, key, 1);
                 self.raw_vector_offset(offset).to_vec()
             }),
             Some(true) => None
@@ -159,9 +283,17 @@ impl VectorStorage for MemmapVectorStorage {
     }
 
     fn update_from(&mut self, other: &dyn VectorStorage) -> OperationResult<Range<PointOffsetType>> {
+        let mut vectors = other.iter_ids().map(|id| other.get_vector(id).unwrap());
+        self.append_vectors(&mut vectors)
+    }
+
+    fn append_vectors(&mut self, vectors: &mut dyn Iterator<Item=Vec<VectorElementType>>) -> OperationResult<Range<PointOffsetType>> {
         self.mmap = None;
         self.deleted_mmap = None;
 
+        // Data file might be shared with forks of the segment
+        unshare_file(&self.data_path)?;
+
         let start_index = self.num_vectors;
         let mut end_index = self.num_vectors;
 
@@ -173,9 +305,8 @@ impl VectorStorage for MemmapVectorStorage {
                 .create(false)
                 .open(self.data_path.as_path())?;
 
-            for id in other.iter_ids() {
-                let vector = &other.get_vector(id).unwrap();
-                let raw_bites = vf_to_u8(vector);
+            for vector in vectors {
+                let raw_bites = vf_to_u8(&vector);
                 file.write(raw_bites)?;
                 end_index += 1;
             }
@@ -203,6 +334,9 @@ impl VectorStorage for MemmapVectorStorage {
         self.deleted_mmap = tmp_storage.deleted_mmap;
         self.num_vectors = tmp_storage.num_vectors;
         self.deleted_count = tmp_storage.deleted_count;
+        // New mapping is not bound yet
+        *self.numa_node.get_mut() = NO_NUMA_NODE;
+        self.readahead = Readahead::default();
 
         return Ok(start_index..end_index);
     }
@@ -231,40 +365,71 @@ impl VectorStorage for MemmapVectorStorage {
         Ok(())
     }
 
+    fn fork(&self, path: &Path) -> OperationResult<()> {
+        create_dir_all(path)?;
+        // Vectors are never changed in place, only appended. Append un-shares the file first
+        link_or_copy(&self.data_path, &path.join(DATA_FILE))?;
+        copy(&self.deleted_path, path.join(DELETED_FILE))?;
+        Ok(())
+    }
+
+    fn bind_to_numa_node(&self, node: usize) -> OperationResult<()> {
+        if self.numa_node.swap(node, Ordering::SeqCst) == node {
+            return Ok(());
+        }
+        if let Some(mmap) = &self.mmap {
+            bind_memory(mmap, node)?;
+        }
+        Ok(())
+    }
+
+    fn warm_up(&self) -> OperationResult<usize> {
+        let vectors = self.mmap.as_ref().map(|mmap| page_cache::warm_up(mmap)).unwrap_or(0);
+        let deleted = self.deleted_mmap.as_ref().map(|mmap| page_cache::warm_up(mmap)).unwrap_or(0);
+        Ok(vectors + deleted)
+    }
+
+    fn check_consistency(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mmap = self.mmap.as_ref().unwrap();
+        let deleted_mmap = self.deleted_mmap.as_ref().unwrap();
+
+        if mmap.len() < HEADER_SIZE || &mmap[..HEADER_SIZE] != b"data" {
+            problems.push(format!("Vector data file {:?} has invalid header", self.data_path));
+        } else if (mmap.len() - HEADER_SIZE) % self.raw_size() != 0 {
+            problems.push(format!("Vector data file length {} does not match vector size {}",
+                                  mmap.len() - HEADER_SIZE, self.raw_size()));
+        }
+
+        if deleted_mmap.len() < HEADER_SIZE || &deleted_mmap[..HEADER_SIZE] != b"drop" {
+            problems.push(format!("Deleted flags file {:?} has invalid header", self.deleted_path));
+        } else if deleted_mmap.len() - HEADER_SIZE != self.num_vectors {
+            problems.push(format!("Number of deleted flags {} does not match number of vectors {}",
+                                  deleted_mmap.len() - HEADER_SIZE, self.num_vectors));
+        }
+        problems
+    }
+
+    fn raw_scorer(&self, vector: &Vec<VectorElementType>, distance: &Distance) -> Box<dyn RawScorer + '_> {
+        match distance {
+            Distance::Cosine => Box::new(MemmapRawScorer::new(self, CosineMetric::new(self.dim), vector)),
+            Distance::Dot => Box::new(MemmapRawScorer::new(self, DotProductMetric::new(self.dim), vector)),
+            Distance::Euclid => unimplemented!(),
+        }
+    }
+
     fn score_points(
         &self, vector: &Vec<VectorElementType>,
         points: &[PointOffsetType],
         top: usize,
         distance: &Distance,
+        stop: &StopCondition,
     ) -> Vec<ScoredPointOffset> {
-        let metric = mertic_object(distance);
-        let preprocessed_vector = metric.preprocess(vector.clone());
-        let scores: Vec<ScoredPointOffset> = points.iter()
-            .cloned()
-            .filter(|point| !self.deleted(*point).unwrap_or(true))
-            .map(|point| {
-                let other_vector =self.raw_vector(point).unwrap();
-                ScoredPointOffset {
-                    idx: point,
-                    score: metric.similarity(&preprocessed_vector, &other_vector),
-                }
-            }).collect();
-        return peek_top_scores(&scores, top, distance);
-    }
-
-    fn score_all(&self, vector: &Vec<VectorElementType>, top: usize, distance: &Distance) -> Vec<ScoredPointOffset> {
-        let metric = mertic_object(distance);
-        let preprocessed_vector = metric.preprocess(vector.clone());
-        let scores: Vec<ScoredPointOffset> = self.iter_ids()
-            .map(|point| {
-                let other_vector = self.raw_vector(point).unwrap();
-                ScoredPointOffset {
-                    idx: point,
-                    score: metric.similarity(&preprocessed_vector, other_vector),
-                }
-            }).collect();
-
-        return peek_top_scores(&scores, top, distance);
+        score_top(&*self.raw_scorer(vector, distance), points, top, distance, stop)
+    }
+
+    fn score_all(&self, vector: &Vec<VectorElementType>, top: usize, distance: &Distance, stop: &StopCondition) -> Vec<ScoredPointOffset> {
+        score_top_all(&*self.raw_scorer(vector, distance), self.num_vectors, top, distance, stop)
     }
 
     fn score_internal(
@@ -275,7 +440,7 @@ impl VectorStorage for MemmapVectorStorage {
         distance: &Distance,
     ) -> Vec<ScoredPointOffset> {
         let vector = self.get_vector(point).unwrap();
-        return self.score_points(&vector, points, top, distance);
+        return self.score_points(&vector, points, top, distance, &StopCondition::default());
     }
 }
 
@@ -333,20 +498,80 @@ mod tests {
         assert_eq!(stored_ids, vec![0, 1, 3, 4]);
 
 
-        let res = storage.score_all(&vec3, 2, &Distance::Dot);
+        let res = storage.score_all(&vec3, 2, &Distance::Dot, &StopCondition::default());
 
         assert_eq!(res.len(), 2);
 
         assert_ne!(res[0].idx, 2);
 
         let res = storage.score_points(
-            &vec3, &vec![0, 1, 2, 3, 4], 2, &Distance::Dot);
+            &vec3, &vec![0, 1, 2, 3, 4], 2, &Distance::Dot, &StopCondition::default());
 
         assert_eq!(res.len(), 2);
         assert_ne!(res[0].idx, 2);
     }
 
 
+    #[test]
+    fn test_read_only_open() {
+        let dir = TempDir::new("storage_dir").unwrap();
+        {
+            let mut storage = MemmapVectorStorage::open(dir.path(), 4).unwrap();
+            let mut vectors = vec![vec![1.0, 0.0, 1.0, 1.0], vec![1.0, 0.0, 1.0, 0.0]].into_iter();
+            storage.append_vectors(&mut vectors).unwrap();
+            storage.delete(0).unwrap();
+            storage.flush().unwrap();
+        }
+
+        let mut storage = MemmapVectorStorage::open_read_only(dir.path(), 4).unwrap();
+        assert_eq!(storage.vector_count(), 1);
+        assert_eq!(storage.get_vector(1).unwrap(), vec![1.0, 0.0, 1.0, 0.0]);
+        assert!(storage.check_consistency().is_empty());
+
+        // Changes of the read-only mapping are not written into the file
+        storage.delete(1).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = MemmapVectorStorage::open(dir.path(), 4).unwrap();
+        assert_eq!(storage.vector_count(), 1);
+    }
+
+    #[test]
+    fn test_corrupted_header() {
+        let dir = TempDir::new("storage_dir").unwrap();
+        std::fs::write(dir.path().join("matrix.dat"), vec![0u8; 16]).unwrap();
+
+        match MemmapVectorStorage::open(dir.path(), 4) {
+            Err(OperationError::Corrupted { path, .. }) => assert_eq!(path, dir.path().join("matrix.dat")),
+            _ => panic!("Corrupted storage is opened")
+        }
+    }
+
+    #[test]
+    fn test_fork_storage() {
+        let dir = TempDir::new("storage_dir").unwrap();
+        let fork_dir = TempDir::new("fork_dir").unwrap();
+        let mut storage = MemmapVectorStorage::open(dir.path(), 2).unwrap();
+        storage.append_vectors(&mut vec![vec![1.0, 0.0], vec![0.0, 1.0]].into_iter()).unwrap();
+
+        storage.fork(fork_dir.path()).unwrap();
+        let mut fork = MemmapVectorStorage::open(fork_dir.path(), 2).unwrap();
+
+        // Fork is modified independently, even though the data file was shared
+        fork.delete(0).unwrap();
+        fork.append_vectors(&mut vec![vec![1.0, 1.0]].into_iter()).unwrap();
+        assert_eq!(fork.vector_count(), 2);
+        assert_eq!(fork.get_vector(2).unwrap(), vec![1.0, 1.0]);
+
+        assert_eq!(storage.vector_count(), 2);
+        assert_eq!(storage.get_vector(0).unwrap(), vec![1.0, 0.0]);
+        drop(storage);
+        let storage = MemmapVectorStorage::open(dir.path(), 2).unwrap();
+        assert_eq!(storage.vector_count(), 2);
+        assert!(storage.get_vector(2).is_none());
+    }
+
     #[test]
     fn test_casts() {
         let data: Vec<VectorElementType> = vec![0.42, 0.069, 333.1, 100500.];
@@ -1,3 +1,4 @@
 pub mod vector_storage;
 pub mod simple_vector_storage;
 pub mod memmap_vector_storage;
+pub mod raw_scorer;
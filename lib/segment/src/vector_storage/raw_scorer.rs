@@ -0,0 +1,87 @@
+use bumpalo::collections::Vec as BumpVec;
+
+use crate::common::search_arena::with_search_arena;
+use crate::common::stop_condition::{StopCondition, STOP_CHECK_INTERVAL};
+use crate::spaces::tools::peek_top_scores;
+use crate::types::{Distance, PointOffsetType};
+use crate::vector_storage::vector_storage::ScoredPointOffset;
+
+/// Number of points, which are scored by a single call of the scorer
+pub const SCORE_CHUNK_SIZE: usize = STOP_CHECK_INTERVAL;
+
+/// Scorer of stored vectors against a single preprocessed query.
+/// Implementations are generic over the metric, so the scoring loop is dispatched once per chunk instead of once per point
+pub trait RawScorer {
+    /// Write scores of not deleted points of `ids` into the beginning of `out` and return number of written scores.
+    /// `out` should be at least as long as `ids`
+    fn score_points(&self, ids: &[PointOffsetType], out: &mut [ScoredPointOffset]) -> usize;
+}
+
+/// Score points in chunks and select `top` closest of them.
+/// Scoring stops early once `stop` condition is met, so the result might be incomplete
+pub fn score_top(
+    scorer: &dyn RawScorer,
+    ids: &[PointOffsetType],
+    top: usize,
+    distance: &Distance,
+    stop: &StopCondition,
+) -> Vec<ScoredPointOffset> {
+    with_search_arena(|arena| {
+        let mut scores = BumpVec::with_capacity_in(ids.len(), arena);
+        let mut buffer = [ScoredPointOffset { idx: 0, score: 0.0 }; SCORE_CHUNK_SIZE];
+        for chunk in ids.chunks(SCORE_CHUNK_SIZE) {
+            if stop.is_stopped() {
+                break;
+            }
+            let count = scorer.score_points(chunk, &mut buffer);
+            scores.extend_from_slice(&buffer[..count]);
+        }
+        peek_top_scores(&scores, top, distance)
+    })
+}
+
+/// Score all points of the storage, which has `points_count` ids including deleted ones
+pub fn score_top_all(
+    scorer: &dyn RawScorer,
+    points_count: usize,
+    top: usize,
+    distance: &Distance,
+    stop: &StopCondition,
+) -> Vec<ScoredPointOffset> {
+    with_search_arena(|arena| {
+        let ids = BumpVec::from_iter_in(0..points_count, arena);
+        score_top(scorer, &ids, top, distance, stop)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scores points by their ids, odd points are deleted
+    struct IdScorer;
+
+    impl RawScorer for IdScorer {
+        fn score_points(&self, ids: &[PointOffsetType], out: &mut [ScoredPointOffset]) -> usize {
+            let mut count = 0;
+            for &idx in ids.iter().filter(|idx| *idx % 2 == 0) {
+                out[count] = ScoredPointOffset { idx, score: idx as f32 };
+                count += 1;
+            }
+            count
+        }
+    }
+
+    #[test]
+    fn test_score_top() {
+        let res = score_top_all(&IdScorer, 5000, 3, &Distance::Dot, &StopCondition::default());
+        assert_eq!(res.iter().map(|point| point.idx).collect::<Vec<_>>(), vec![4998, 4996, 4994]);
+
+        let res = score_top(&IdScorer, &[1, 2, 3, 4], 10, &Distance::Dot, &StopCondition::default());
+        assert_eq!(res.len(), 2);
+
+        let stop = StopCondition::default();
+        stop.stop();
+        assert!(score_top_all(&IdScorer, 5000, 3, &Distance::Dot, &stop).is_empty());
+    }
+}
@@ -2,12 +2,16 @@ use std::collections::HashSet;
 use std::ops::Range;
 use std::path::Path;
 
-use log::debug;
+use tracing::debug;
 use rocksdb::{DB, IteratorMode, Options};
 use serde::{Deserialize, Serialize};
 
 use crate::entry::entry_point::OperationResult;
-use crate::spaces::tools::{mertic_object, peek_top_scores};
+use crate::common::rocksdb_operations::{open_db, checkpoint_db};
+use crate::common::stop_condition::StopCondition;
+use crate::vector_storage::raw_scorer::{RawScorer, score_top, score_top_all};
+use crate::spaces::metric::Metric;
+use crate::spaces::simple::{CosineMetric, DotProductMetric};
 use crate::types::{Distance, PointOffsetType, VectorElementType};
 use crate::vector_storage::vector_storage::ScoredPointOffset;
 
@@ -35,6 +39,15 @@ struct StoredRecord {
 
 impl SimpleVectorStorage {
     pub fn open(path: &Path, dim: usize) -> OperationResult<Self> {
+        Self::open_with_mode(path, dim, false)
+    }
+
+    /// Open existing storage without ability to modify it
+    pub fn open_read_only(path: &Path, dim: usize) -> OperationResult<Self> {
+        Self::open_with_mode(path, dim, true)
+    }
+
+    fn open_with_mode(path: &Path, dim: usize, read_only: bool) -> OperationResult<Self> {
         let mut vectors: Vec<Array1<VectorElementType>> = vec![];
         let mut deleted: HashSet<PointOffsetType> = HashSet::new();
 
@@ -42,7 +55,7 @@ impl SimpleVectorStorage {
         options.set_write_buffer_size(DB_CACHE_SIZE);
         options.create_if_missing(true);
 
-        let store = DB::open(&options, path)?;
+        let store = open_db(&options, path, &[], read_only)?;
 
         for (key, val) in store.iterator(IteratorMode::Start) {
             let point_id: PointOffsetType = bincode::deserialize(&key).unwrap();
@@ -56,8 +69,12 @@ impl SimpleVectorStorage {
             vectors[point_id].assign(&Array::from(stored_record.vector));
         }
 
-        debug!("Segment vectors: {}", vectors.len());
-        debug!("Estimated segment size {} MB", vectors.len() * dim * size_of::<VectorElementType>() / 1024 / 1024);
+        debug!(
+            path = %path.display(),
+            vectors = vectors.len(),
+            estimated_size_mb = vectors.len() * dim * size_of::<VectorElementType>() / 1024 / 1024,
+            "Loaded vector storage",
+        );
 
 
         return Ok(SimpleVectorStorage {
@@ -85,6 +102,38 @@ impl SimpleVectorStorage {
 }
 
 
+struct SimpleRawScorer<'a, M: Metric> {
+    storage: &'a SimpleVectorStorage,
+    metric: M,
+    query: Array1<VectorElementType>,
+}
+
+impl<'a, M: Metric> SimpleRawScorer<'a, M> {
+    fn new(storage: &'a SimpleVectorStorage, metric: M, vector: &Vec<VectorElementType>) -> Self {
+        let query = Array::from(metric.preprocess(vector.clone()));
+        SimpleRawScorer { storage, metric, query }
+    }
+}
+
+impl<M: Metric> RawScorer for SimpleRawScorer<'_, M> {
+    fn score_points(&self, ids: &[PointOffsetType], out: &mut [ScoredPointOffset]) -> usize {
+        let mut count = 0;
+        for &idx in ids {
+            if self.storage.deleted.contains(&idx) {
+                continue;
+            }
+            let other_vector = match self.storage.vectors.get(idx) {
+                Some(other_vector) => other_vector,
+                None => continue,
+            };
+            out[count] = ScoredPointOffset { idx, score: self.metric.blas_similarity(&self.query, other_vector) };
+            count += 1;
+        }
+        count
+    }
+}
+
+
 impl VectorStorage for SimpleVectorStorage {
     fn vector_dim(&self) -> usize {
         self.dim
@@ -126,6 +175,15 @@ impl VectorStorage for SimpleVectorStorage {
         return Ok(start_index..end_index);
     }
 
+    fn append_vectors(&mut self, vectors: &mut dyn Iterator<Item=Vec<VectorElementType>>) -> OperationResult<Range<PointOffsetType>> {
+        let start_index = self.vectors.len();
+        for vector in vectors {
+            self.put_vector(&vector)?;
+        }
+        let end_index = self.vectors.len();
+        return Ok(start_index..end_index);
+    }
+
     fn delete(&mut self, key: PointOffsetType) -> OperationResult<()> {
         self.deleted.insert(key);
         self.update_stored(key)?;
@@ -142,40 +200,31 @@ impl VectorStorage for SimpleVectorStorage {
         Ok(self.store.flush()?)
     }
 
+    fn fork(&self, path: &Path) -> OperationResult<()> {
+        checkpoint_db(&self.store, path)
+    }
+
+    fn raw_scorer(&self, vector: &Vec<VectorElementType>, distance: &Distance) -> Box<dyn RawScorer + '_> {
+        match distance {
+            Distance::Cosine => Box::new(SimpleRawScorer::new(self, CosineMetric::new(self.dim), vector)),
+            Distance::Dot => Box::new(SimpleRawScorer::new(self, DotProductMetric::new(self.dim), vector)),
+            Distance::Euclid => unimplemented!(),
+        }
+    }
+
     fn score_points(
         &self,
         vector: &Vec<VectorElementType>,
         points: &[PointOffsetType],
         top: usize,
         distance: &Distance,
+        stop: &StopCondition,
     ) -> Vec<ScoredPointOffset> {
-        let metric = mertic_object(distance);
-        let preprocessed_vector = Array::from(metric.preprocess(vector.clone()));
-        let scores: Vec<ScoredPointOffset> = points.iter()
-            .cloned()
-            .filter(|point| !self.deleted.contains(point))
-            .map(|point| {
-                let other_vector = self.vectors.get(point).unwrap();
-                ScoredPointOffset {
-                    idx: point,
-                    score: metric.blas_similarity(&preprocessed_vector, other_vector),
-                }
-            }).collect();
-        return peek_top_scores(&scores, top, distance);
+        score_top(&*self.raw_scorer(vector, distance), points, top, distance, stop)
     }
 
-
-    fn score_all(&self, vector: &Vec<VectorElementType>, top: usize, distance: &Distance) -> Vec<ScoredPointOffset> {
-        let metric = mertic_object(distance);
-        let preprocessed_vector = Array::from(metric.preprocess(vector.clone()));
-        let scores: Vec<ScoredPointOffset> = self.vectors.iter()
-            .enumerate()
-            .filter(|(point, _)| !self.deleted.contains(point))
-            .map(|(point, other_vector)| ScoredPointOffset {
-                idx: point,
-                score: metric.blas_similarity(&preprocessed_vector, other_vector),
-            }).collect();
-        return peek_top_scores(&scores, top, distance);
+    fn score_all(&self, vector: &Vec<VectorElementType>, top: usize, distance: &Distance, stop: &StopCondition) -> Vec<ScoredPointOffset> {
+        score_top_all(&*self.raw_scorer(vector, distance), self.vectors.len(), top, distance, stop)
     }
 
     fn score_internal(
@@ -186,7 +235,7 @@ impl VectorStorage for SimpleVectorStorage {
         distance: &Distance,
     ) -> Vec<ScoredPointOffset> {
         let vector = self.get_vector(point).unwrap();
-        return self.score_points(&vector, points, top, distance);
+        return self.score_points(&vector, points, top, distance, &StopCondition::default());
     }
 }
 
@@ -225,6 +274,7 @@ mod tests {
             &[0, 1, 2, 3, 4],
             2,
             &distance,
+            &StopCondition::default(),
         );
 
         let top_idx = match closest.get(0) {
@@ -245,6 +295,7 @@ mod tests {
             &[0, 1, 2, 3, 4],
             2,
             &distance,
+            &StopCondition::default(),
         );
 
 
@@ -3,6 +3,9 @@ use std::cmp::{Ordering};
 use ordered_float::OrderedFloat;
 use crate::entry::entry_point::OperationResult;
 use std::ops::Range;
+use std::path::Path;
+use crate::common::stop_condition::StopCondition;
+use crate::vector_storage::raw_scorer::RawScorer;
 
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -37,22 +40,37 @@ pub trait VectorStorage {
     fn put_vector(&mut self, vector: &Vec<VectorElementType>) -> OperationResult<PointOffsetType>;
     fn update_vector(&mut self, key: PointOffsetType, vector: &Vec<VectorElementType>) -> OperationResult<PointOffsetType>;
     fn update_from(&mut self, other: &dyn VectorStorage) -> OperationResult<Range<PointOffsetType>>;
+    /// Sequentially write all given vectors at the end of the storage. Returns range of assigned ids
+    fn append_vectors(&mut self, vectors: &mut dyn Iterator<Item=Vec<VectorElementType>>) -> OperationResult<Range<PointOffsetType>>;
     fn delete(&mut self, key: PointOffsetType) -> OperationResult<()>;
     fn iter_ids(&self) -> Box<dyn Iterator<Item=PointOffsetType> + '_>;
     fn flush(&self) -> OperationResult<()>;
+    /// Write a copy of the persisted storage into a new directory, sharing immutable files with the original
+    fn fork(&self, path: &Path) -> OperationResult<()>;
+    /// Validate internal structure of the storage, return description of found problems
+    fn check_consistency(&self) -> Vec<String> { vec![] }
+    /// Place memory mapped vectors on the NUMA node. Repeated binding to the same node does nothing
+    fn bind_to_numa_node(&self, _node: usize) -> OperationResult<()> { Ok(()) }
+    /// Load memory mapped vectors into the page cache. Returns number of loaded bytes
+    fn warm_up(&self) -> OperationResult<usize> { Ok(0) }
 
+    /// Scorer of stored vectors against the query, which is preprocessed according to the distance
+    fn raw_scorer(&self, vector: &Vec<VectorElementType>, distance: &Distance) -> Box<dyn RawScorer + '_>;
+    /// Scoring stops early once `stop` condition is met, so the result might be incomplete
     fn score_points(
         &self,
         vector: &Vec<VectorElementType>,
         points: &[PointOffsetType],
         top: usize,
         distance: &Distance,
+        stop: &StopCondition,
     ) -> Vec<ScoredPointOffset>;
     fn score_all(
         &self,
         vector: &Vec<VectorElementType>,
         top: usize,
-        distance: &Distance
+        distance: &Distance,
+        stop: &StopCondition,
     ) -> Vec<ScoredPointOffset>;
     fn score_internal(
         &self,
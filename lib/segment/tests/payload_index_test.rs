@@ -1,12 +1,13 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "payload_index"))]
 mod tests {
     use rand::prelude::ThreadRng;
     use rand::seq::SliceRandom;
-    use segment::types::{PayloadType, VectorElementType, SegmentConfig, Indexes, PayloadIndexType, Distance, StorageType, TheMap, PayloadKeyType, Filter, Condition, FieldCondition, Match, Range as RangeConditionl};
+    use segment::types::{PayloadType, VectorElementType, SegmentConfig, Indexes, PayloadIndexType, Distance, StorageType, TheMap, PayloadKeyType, Filter, Condition, FieldCondition, Match, MinShould, WithPayload, Range as RangeConditionl};
     use rand::Rng;
     use tempdir::TempDir;
     use segment::segment_constructor::segment_constructor::build_segment;
     use segment::entry::entry_point::SegmentEntry;
+    use segment::common::stop_condition::StopCondition;
     use itertools::Itertools;
     use std::ops::Range;
 
@@ -69,6 +70,7 @@ mod tests {
                 r#match: Some(Match {
                     keyword: Some(random_keyword(rnd_gen)),
                     integer: None,
+                    text: None,
                 }),
                 range: None,
                 geo_bounding_box: None,
@@ -112,9 +114,23 @@ mod tests {
             true => None,
         };
 
+        let min_should_conditions = (0..=3)
+            .take_while(|_| rnd1.gen::<f64>() > 0.6)
+            .map(|_| random_field_condition(rnd_gen))
+            .collect_vec();
+
+        let min_should_opt = match min_should_conditions.is_empty() {
+            false => Some(MinShould {
+                min_count: rnd1.gen_range(1..=min_should_conditions.len()),
+                conditions: min_should_conditions,
+            }),
+            true => None,
+        };
+
         Filter {
             should: should_conditions_opt,
             must: must_conditions_opt,
+            min_should: min_should_opt,
             must_not: None,
         }
     }
@@ -134,6 +150,8 @@ mod tests {
             index: Indexes::Plain {},
             payload_index: Some(PayloadIndexType::Plain),
             storage_type: StorageType::InMemory,
+            text_analyzers: Default::default(),
+            flush_policy: None,
             distance: Distance::Dot,
         };
 
@@ -153,11 +171,11 @@ mod tests {
             payload.insert(str_key.clone(), random_keyword_payload(&mut rnd));
             payload.insert(int_key.clone(), random_int_payload(&mut rnd));
 
-            plain_segment.upsert_point(idx, idx, &vector).unwrap();
-            struct_segment.upsert_point(idx, idx, &vector).unwrap();
+            plain_segment.upsert_point(idx, idx.into(), &vector).unwrap();
+            struct_segment.upsert_point(idx, idx.into(), &vector).unwrap();
 
-            plain_segment.set_full_payload(idx, idx, payload.clone()).unwrap();
-            struct_segment.set_full_payload(idx, idx, payload.clone()).unwrap();
+            plain_segment.set_full_payload(idx, idx.into(), payload.clone()).unwrap();
+            struct_segment.set_full_payload(idx, idx.into(), payload.clone()).unwrap();
 
             opnum += 1;
         }
@@ -170,8 +188,8 @@ mod tests {
             let query_vector = random_vector(&mut rnd, dim);
             let query_filter = random_filter(&mut rnd);
 
-            let plain_result = plain_segment.search(&query_vector, Some(&query_filter), 5, None).unwrap();
-            let struct_result = struct_segment.search(&query_vector, Some(&query_filter), 5, None).unwrap();
+            let plain_result = plain_segment.search(&query_vector, &WithPayload::default(), false, Some(&query_filter), 5, None, &StopCondition::default()).unwrap();
+            let struct_result = struct_segment.search(&query_vector, &WithPayload::default(), false, Some(&query_filter), 5, None, &StopCondition::default()).unwrap();
 
             let estimation = struct_segment.payload_index.borrow().estimate_cardinality(&query_filter);
 
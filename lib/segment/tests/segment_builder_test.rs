@@ -4,10 +4,11 @@ mod fixtures;
 mod tests {
     use crate::fixtures::segment::{build_segment_1, build_segment_2};
     use tempdir::TempDir;
-    use segment::segment_constructor::segment_builder::SegmentBuilder;
+    use segment::segment_constructor::segment_builder::{SegmentBuilder, stored_groups};
     use segment::segment::Segment;
     use std::convert::TryInto;
     use segment::entry::entry_point::SegmentEntry;
+    use segment::types::{SegmentConfig, Indexes, PayloadIndexType, Distance, StorageType, PayloadType, TheMap, SegmentType};
 
     #[test]
     fn test_building_new_segment() {
@@ -50,4 +51,86 @@ mod tests {
         assert_eq!(merged_segment.vectors_count(), segment1.vectors_count() + segment2.vectors_count())
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(feature = "payload_index")]
+    fn test_bulk_building_segment() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+
+        let config = SegmentConfig {
+            vector_size: 2,
+            index: Indexes::Plain {},
+            payload_index: Some(PayloadIndexType::Struct),
+            distance: Distance::Dot,
+            storage_type: StorageType::Mmap,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+        };
+
+        let mut builder = SegmentBuilder::new(dir.path(), temp_dir.path(), &config).unwrap();
+        builder.indexed_fields.insert("parity".to_owned());
+
+        let points = (0..100u64).map(|idx| {
+            let mut payload = TheMap::new();
+            payload.insert("parity".to_owned(), PayloadType::Integer(vec![idx as i64 % 2]));
+            (idx.into(), vec![idx as f32, 1.0], payload)
+        });
+        assert_eq!(builder.add_points(10, points).unwrap(), 100);
+
+        // Repeated id overrides previous vector and payload
+        let mut payload = TheMap::new();
+        payload.insert("parity".to_owned(), PayloadType::Integer(vec![-1]));
+        builder.add_points(11, vec![(5.into(), vec![0.0, 0.0], payload)]).unwrap();
+
+        assert!(builder.add_points(12, vec![(200.into(), vec![1.0], TheMap::new())]).is_err());
+
+        let segment: Segment = builder.try_into().unwrap();
+
+        assert_eq!(segment.vectors_count(), 100);
+        assert_eq!(segment.version(), 11);
+        assert_eq!(segment.segment_type(), SegmentType::Indexed);
+        assert_eq!(segment.get_indexed_fields(), vec!["parity".to_owned()]);
+        assert_eq!(segment.vector(5.into()).unwrap(), vec![0.0, 0.0]);
+        match segment.payload(5.into()).unwrap().get("parity") {
+            Some(PayloadType::Integer(values)) => assert_eq!(values, &vec![-1]),
+            _ => panic!("Payload is not overridden"),
+        }
+        assert_eq!(segment.vector(7.into()).unwrap(), vec![7.0, 1.0]);
+    }
+
+    #[test]
+    fn test_building_grouped_segment() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let temp_dir = TempDir::new("segment_temp_dir").unwrap();
+
+        let segment1 = build_segment_1(dir.path());
+        let segment2 = build_segment_2(dir.path());
+
+        let mut builder = SegmentBuilder::new(
+            dir.path(),
+            temp_dir.path(),
+            &segment1.segment_config
+        ).unwrap();
+
+        builder.update_from_grouped(&[&segment1, &segment2], &"color".to_owned()).unwrap();
+
+        let merged_segment: Segment = builder.try_into().unwrap();
+        assert_eq!(merged_segment.vectors_count(), segment1.vectors_count() + segment2.vectors_count());
+
+        // Each group is stored as a single run, points without group are at the end
+        let groups = stored_groups(&merged_segment, &"color".to_owned());
+        let runs: Vec<_> = groups.iter().fold(vec![], |mut runs, group| {
+            if runs.last() != Some(&group) {
+                runs.push(group);
+            }
+            runs
+        });
+        let group_runs: Vec<_> = runs.iter().filter_map(|group| group.as_ref()).collect();
+        let mut unique_runs = group_runs.clone();
+        unique_runs.sort();
+        unique_runs.dedup();
+        assert_eq!(group_runs.len(), unique_runs.len());
+        assert!(runs.iter().skip_while(|group| group.is_some()).all(|group| group.is_none()));
+    }
+}
@@ -3,10 +3,14 @@ mod fixtures;
 
 #[cfg(test)]
 mod tests {
-    use crate::fixtures::segment::build_segment_1;
+    use crate::fixtures::segment::{build_segment_1, empty_segment};
+    use segment::segment_constructor::segment_constructor::{load_segment, build_segment, load_segment_read_only, load_segment_with_config};
+    use segment::segment::Segment;
+    use segment::entry::entry_point::OperationError;
     use segment::entry::entry_point::SegmentEntry;
+    use segment::common::stop_condition::StopCondition;
     use std::collections::HashSet;
-    use segment::types::{Filter, Condition};
+    use segment::types::{Filter, Condition, PayloadType, PayloadSchemaType, FieldCondition, Match, WithPayload, PayloadSelector, PointIdType, ConsistencyCheckMode, SegmentConfig, Indexes, PayloadIndexType, StorageType, Distance, SegmentStatus, TheMap};
     use tempdir::TempDir;
 
     #[test]
@@ -16,30 +20,31 @@ mod tests {
 
         let segment = build_segment_1(dir.path());
 
-        assert!(segment.has_point(3));
+        assert!(segment.has_point(3.into()));
 
         let query_vector = vec![1.0, 1.0, 1.0, 1.0];
 
-        let res = segment.search(&query_vector, None, 1, None).unwrap();
+        let res = segment.search(&query_vector, &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
 
         let best_match = res.get(0).expect("Non-empty result");
-        assert_eq!(best_match.id, 3);
+        assert_eq!(best_match.id, 3.into());
 
 
-        let ids: HashSet<_> = vec![3].into_iter().collect();
+        let ids: HashSet<PointIdType> = vec![3].into_iter().map(|x| x.into()).collect();
 
 
         let frt = Filter {
             should: None,
             must: None,
+            min_should: None,
             must_not: Some(vec![Condition::HasId(ids.into())]),
         };
 
 
-        let res = segment.search(&query_vector, Some(&frt), 1, None).unwrap();
+        let res = segment.search(&query_vector, &WithPayload::default(), false, Some(&frt), 1, None, &StopCondition::default()).unwrap();
 
         let best_match = res.get(0).expect("Non-empty result");
-        assert_ne!(best_match.id, 3);
+        assert_ne!(best_match.id, 3.into());
 
 
         let point_ids1: Vec<_> = segment.iter_points().collect();
@@ -50,4 +55,523 @@ mod tests {
 
         assert_eq!(&point_ids1, &point_ids2)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_migrate_payload_key() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let mut segment = build_segment_1(dir.path());
+        segment.create_field_index(7, &"color".to_string()).unwrap();
+        segment.set_payload(8, 1.into(), &"price".to_string(), PayloadType::Keyword(vec!["10".to_owned()])).unwrap();
+
+        // Keys can't be renamed into existing ones
+        assert!(segment.migrate_payload_key(9, &"color".to_string(), &"price".to_string(), None).is_err());
+
+        segment.migrate_payload_key(10, &"color".to_string(), &"colour".to_string(), None).unwrap();
+        assert_eq!(segment.pending_payload_migrations(), 1);
+
+        // Payload is returned migrated right away, index is moved once all points are migrated
+        let payload = segment.payload(3.into()).unwrap();
+        assert!(!payload.contains_key("color"));
+        assert!(payload.contains_key("colour"));
+        assert_eq!(segment.get_indexed_fields(), vec!["color".to_string()]);
+
+        // Point is migrated before it is changed
+        segment.set_payload(11, 4.into(), &"size".to_string(), PayloadType::Integer(vec![1])).unwrap();
+        let payload = segment.payload(4.into()).unwrap();
+        assert!(payload.contains_key("colour"));
+        assert!(payload.contains_key("size"));
+
+        // Migrated keys can't be migrated again until the migration is finished
+        assert!(segment.migrate_payload_key(12, &"colour".to_string(), &"hue".to_string(), None).is_err());
+
+        segment.migrate_payload_batch(usize::MAX).unwrap();
+        assert_eq!(segment.pending_payload_migrations(), 0);
+        assert_eq!(segment.get_indexed_fields(), vec!["colour".to_string()]);
+        assert!(segment.payload(3.into()).unwrap().contains_key("colour"));
+
+        segment.migrate_payload_key(13, &"price".to_string(), &"price".to_string(), Some(&PayloadSchemaType::Integer)).unwrap();
+        segment.migrate_payload_batch(usize::MAX).unwrap();
+        match segment.payload(1.into()).unwrap().get("price") {
+            Some(PayloadType::Integer(values)) => assert_eq!(values, &vec![10]),
+            _ => panic!("Payload is not converted")
+        }
+
+        // Keywords can't be converted into numbers, migration should not be started
+        let res = segment.migrate_payload_key(14, &"colour".to_string(), &"colour".to_string(), Some(&PayloadSchemaType::Float));
+        assert!(res.is_err());
+        assert_eq!(segment.pending_payload_migrations(), 0);
+        assert!(segment.payload(4.into()).unwrap().contains_key("colour"));
+    }
+
+    #[test]
+    fn test_count() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = build_segment_1(dir.path());
+
+        let red_filter = Filter::new_must(Condition::Field(FieldCondition {
+            key: "color".to_string(),
+            r#match: Some(Match { keyword: Some("red".to_owned()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        }));
+
+        assert_eq!(segment.count(None, true, &StopCondition::default()).unwrap(), 5);
+        assert_eq!(segment.count(Some(&red_filter), true, &StopCondition::default()).unwrap(), 4);
+
+        segment.delete_point(7, 2.into()).unwrap();
+
+        assert_eq!(segment.count(None, false, &StopCondition::default()).unwrap(), 4);
+        assert_eq!(segment.count(Some(&red_filter), true, &StopCondition::default()).unwrap(), 3);
+        assert!(segment.count(Some(&red_filter), false, &StopCondition::default()).unwrap() <= 4);
+    }
+
+    #[test]
+    fn test_delete_filtered() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = build_segment_1(dir.path());
+
+        let blue_filter = Filter::new_must(Condition::Field(FieldCondition {
+            key: "color".to_string(),
+            r#match: Some(Match { keyword: Some("blue".to_owned()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        }));
+
+        assert_eq!(segment.delete_filtered(7, &blue_filter).unwrap(), 3);
+        assert_eq!(segment.vectors_count(), 2);
+        assert!(!segment.has_point(3.into()));
+        assert!(segment.has_point(1.into()));
+
+        // Operation with old version is ignored
+        assert_eq!(segment.delete_filtered(6, &Filter::new_must_not(Condition::HasId(HashSet::new().into()))).unwrap(), 0);
+        assert_eq!(segment.vectors_count(), 2);
+    }
+
+    #[test]
+    fn test_read_filtered() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = build_segment_1(dir.path());
+
+        assert_eq!(segment.read_filtered(None, 2, None, &StopCondition::default()).unwrap(), vec![1.into(), 2.into()]);
+        assert_eq!(segment.read_filtered(Some(3.into()), 10, None, &StopCondition::default()).unwrap(), vec![3.into(), 4.into(), 5.into()]);
+
+        let blue_filter = Filter::new_must(Condition::Field(FieldCondition {
+            key: "color".to_string(),
+            r#match: Some(Match { keyword: Some("blue".to_owned()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        }));
+
+        assert_eq!(segment.read_filtered(None, 10, Some(&blue_filter), &StopCondition::default()).unwrap(), vec![3.into(), 4.into(), 5.into()]);
+        assert_eq!(segment.read_filtered(Some(4.into()), 1, Some(&blue_filter), &StopCondition::default()).unwrap(), vec![4.into()]);
+    }
+
+    #[test]
+    fn test_stopped_requests() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = build_segment_1(dir.path());
+        let stop = StopCondition::default();
+        stop.stop();
+
+        let query_vector = vec![1.0, 1.0, 1.0, 1.0];
+        let res = segment.search(&query_vector, &WithPayload::default(), false, None, 1, None, &stop);
+        assert!(matches!(res, Err(OperationError::Cancelled { .. })));
+        assert!(matches!(segment.read_filtered(None, 10, None, &stop), Err(OperationError::Cancelled { .. })));
+    }
+
+    #[test]
+    fn test_search_with_payload_and_vector() {
+        let dir = TempDir::new("segment_dir").unwrap();
+
+        let segment = build_segment_1(dir.path());
+        segment.set_payload(7, 3.into(), &"price".to_string(), PayloadType::Integer(vec![10])).unwrap();
+
+        let query_vector = vec![1.0, 1.0, 1.0, 1.0];
+        let with_payload = WithPayload {
+            enable: true,
+            payload_selector: Some(PayloadSelector::Include(vec!["price".to_string()])),
+        };
+
+        let res = segment.search(&query_vector, &with_payload, true, None, 1, None, &StopCondition::default()).unwrap();
+        let best_match = res.get(0).expect("Non-empty result");
+        assert_eq!(best_match.id, 3.into());
+        assert_eq!(best_match.vector, Some(vec![1.0, 1.0, 1.0, 1.0]));
+        let payload = best_match.payload.as_ref().unwrap();
+        assert_eq!(payload.len(), 1);
+        assert!(payload.contains_key("price"));
+
+        let res = segment.search(&query_vector, &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
+        assert!(res[0].payload.is_none());
+        assert!(res[0].vector.is_none());
+    }
+
+    #[test]
+    fn test_point_versions() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = empty_segment(dir.path());
+        let payload_key = "color".to_owned();
+
+        segment.upsert_point(10, 1.into(), &vec![1.0, 0.0, 1.0, 1.0]).unwrap();
+        segment.upsert_point(20, 2.into(), &vec![1.0, 0.0, 1.0, 0.0]).unwrap();
+        assert_eq!(segment.version(), 20);
+
+        // Point 1 was not changed since operation 10, so older operations of the segment are still applicable
+        assert!(segment.set_payload(15, 1.into(), &payload_key, PayloadType::Keyword(vec!["red".to_owned()])).unwrap());
+        assert_eq!(segment.point_version(1.into()), Some(15));
+
+        // Repeated operation is applied again without changes in the state
+        assert!(segment.set_payload(15, 1.into(), &payload_key, PayloadType::Keyword(vec!["red".to_owned()])).unwrap());
+
+        // Outdated operations are ignored
+        assert!(!segment.upsert_point(15, 2.into(), &vec![0.0, 0.0, 0.0, 0.0]).unwrap());
+        assert!(!segment.delete_point(12, 1.into()).unwrap());
+        assert_eq!(segment.vector(2.into()).unwrap(), vec![1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(segment.point_version(2.into()), Some(20));
+
+        // Operations with different points might be applied out of order, so new points are still inserted
+        assert!(segment.upsert_point(12, 3.into(), &vec![0.0, 1.0, 0.0, 0.0]).unwrap());
+        assert_eq!(segment.point_version(3.into()), Some(12));
+        assert_eq!(segment.version(), 20);
+
+        // Version of the deleted point is kept, so older operations do not insert it again
+        segment.delete_point(21, 2.into()).unwrap();
+        assert_eq!(segment.point_version(2.into()), Some(21));
+        assert!(!segment.upsert_point(20, 2.into(), &vec![1.0, 0.0, 1.0, 0.0]).unwrap());
+        assert!(!segment.has_point(2.into()));
+
+        segment.flush().unwrap();
+        let path = segment.current_path.clone();
+        drop(segment);
+
+        let segment = load_segment(&path).unwrap();
+        assert_eq!(segment.point_version(1.into()), Some(15));
+        assert_eq!(segment.point_version(2.into()), Some(21));
+    }
+
+    #[test]
+    fn test_uuid_point_ids() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = empty_segment(dir.path());
+
+        let uuid_id: PointIdType = serde_json::from_str(r#""550e8400-e29b-41d4-a716-446655440000""#).unwrap();
+        segment.upsert_point(1, uuid_id, &vec![1.0, 0.0, 1.0, 1.0]).unwrap();
+        segment.upsert_point(2, 100.into(), &vec![1.0, 0.0, 0.0, 0.0]).unwrap();
+
+        let res = segment.search(&vec![1.0, 1.0, 1.0, 1.0], &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
+        assert_eq!(res[0].id, uuid_id);
+
+        // Numeric ids go before UUIDs
+        assert_eq!(segment.read_filtered(None, 10, None, &StopCondition::default()).unwrap(), vec![100.into(), uuid_id]);
+
+        segment.flush().unwrap();
+        let path = segment.current_path.clone();
+        drop(segment);
+
+        let segment = load_segment(&path).unwrap();
+        assert!(segment.has_point(uuid_id));
+        assert!(segment.has_point(100.into()));
+        assert_eq!(segment.point_version(uuid_id), Some(1));
+    }
+
+    #[test]
+    fn test_segment_telemetry() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = empty_segment(dir.path());
+        let payload_key = "color".to_owned();
+
+        segment.upsert_point(1, 1.into(), &vec![1.0, 0.0, 1.0, 1.0]).unwrap();
+        segment.upsert_point(2, 2.into(), &vec![1.0, 0.0, 1.0, 0.0]).unwrap();
+        segment.set_payload(3, 1.into(), &payload_key, PayloadType::Keyword(vec!["red".to_owned()])).unwrap();
+        segment.delete_point(4, 2.into()).unwrap();
+
+        let query_vector = vec![1.0, 1.0, 1.0, 1.0];
+        segment.search(&query_vector, &WithPayload::default(), false, None, 10, None, &StopCondition::default()).unwrap();
+        let filter = Filter::new_must(Condition::Field(FieldCondition {
+            key: payload_key.clone(),
+            r#match: Some(Match { keyword: Some("red".to_owned()), integer: None, text: None }),
+            range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+        }));
+        segment.search(&query_vector, &WithPayload::default(), false, Some(&filter), 10, None, &StopCondition::default()).unwrap();
+
+        let telemetry = segment.get_telemetry();
+        assert_eq!(telemetry.upsert.count, 2);
+        assert_eq!(telemetry.payload.count, 1);
+        assert_eq!(telemetry.delete.count, 1);
+        assert_eq!(telemetry.search.count, 2);
+        assert_eq!(telemetry.search.histogram.iter().sum::<usize>(), 2);
+        assert_eq!(telemetry.search_results_count, 2);
+        assert_eq!(telemetry.filtered_search_count, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "payload_index")]
+    fn test_check_consistency() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let config = SegmentConfig {
+            vector_size: 4,
+            index: Indexes::Plain {},
+            payload_index: Some(PayloadIndexType::Struct),
+            storage_type: StorageType::InMemory,
+            text_analyzers: Default::default(),
+            flush_policy: None,
+            distance: Distance::Dot,
+        };
+        let mut segment = build_segment(dir.path(), &config).unwrap();
+        let payload_key = "color".to_owned();
+        for idx in 1..=5u64 {
+            segment.upsert_point(idx, idx.into(), &vec![1.0, 0.0, idx as f32, 1.0]).unwrap();
+            segment.set_payload(idx, idx.into(), &payload_key, PayloadType::Keyword(vec!["red".to_owned()])).unwrap();
+        }
+        segment.create_field_index(6, &payload_key).unwrap();
+
+        let report = segment.check_consistency(ConsistencyCheckMode::Check).unwrap();
+        assert!(report.is_consistent(), "{:?}", report.problems);
+
+        // Break the segment by modifying its components directly
+        let orphan_vector = segment.id_mapper.borrow().internal_id(1.into()).unwrap();
+        segment.id_mapper.borrow_mut().drop(1.into()).unwrap();
+        let dangling_point = segment.id_mapper.borrow().internal_id(2.into()).unwrap();
+        segment.vector_storage.borrow_mut().delete(dangling_point).unwrap();
+        let point_3 = segment.id_mapper.borrow().internal_id(3.into()).unwrap();
+        segment.payload_storage.borrow_mut()
+            .assign(point_3, &payload_key, PayloadType::Keyword(vec!["green".to_owned()])).unwrap();
+
+        let report = segment.check_consistency(ConsistencyCheckMode::Check).unwrap();
+        // Orphan vector, dangling point link, payload of deleted vector and outdated index
+        assert_eq!(report.problems.len(), 4, "{:?}", report.problems);
+        assert_eq!(report.repaired, 0);
+
+        let report = segment.check_consistency(ConsistencyCheckMode::Repair).unwrap();
+        assert_eq!(report.repaired, 4);
+
+        let report = segment.check_consistency(ConsistencyCheckMode::Check).unwrap();
+        assert!(report.is_consistent(), "{:?}", report.problems);
+
+        assert!(segment.vector_storage.borrow().get_vector(orphan_vector).is_none());
+        assert!(!segment.has_point(2.into()));
+        assert_eq!(segment.vectors_count(), 3);
+    }
+
+    #[test]
+    fn test_update_point_vector() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = build_segment_1(dir.path());
+
+        let internal_id = segment.id_mapper.borrow().internal_id(3.into()).unwrap();
+        assert!(segment.update_point_vector(7, 3.into(), &vec![0.0, 0.0, 0.0, 1.0]).unwrap());
+
+        assert_eq!(segment.vector(3.into()).unwrap(), vec![0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(segment.id_mapper.borrow().internal_id(3.into()), Some(internal_id));
+        assert_eq!(segment.payload(3.into()).unwrap().len(), 1);
+        assert_eq!(segment.vectors_count(), 5);
+
+        assert!(segment.update_point_vector(8, 100.into(), &vec![0.0, 0.0, 0.0, 1.0]).is_err());
+        assert!(segment.update_point_vector(9, 3.into(), &vec![0.0, 0.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_segment_info() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let segment = build_segment_1(dir.path());
+        segment.delete_point(7, 5.into()).unwrap();
+        segment.flush().unwrap();
+
+        let info = segment.info();
+        assert_eq!(info.status, SegmentStatus::Plain);
+        assert_eq!(info.num_vectors, 4);
+        assert_eq!(info.num_deleted_vectors, 1);
+        // 5 stored vectors of dim 4
+        assert_eq!(info.memory_usage.vector_storage_bytes, 5 * 4 * 4);
+        assert!(info.memory_usage.id_mapper_bytes > 0);
+        assert!(info.memory_usage.payload_storage_bytes > 0);
+        assert_eq!(info.ram_usage_bytes, info.memory_usage.total());
+        assert!(info.disk_usage.vector_storage_bytes > 0);
+        assert!(info.disk_usage.id_mapper_bytes > 0);
+        assert_eq!(info.disk_usage_bytes, info.disk_usage.total());
+        assert_eq!(info.config, segment.config());
+        assert!(!info.schema["color"].indexed);
+    }
+
+    #[test]
+    fn test_read_only_segment() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let path = {
+            let segment = build_segment_1(dir.path());
+            segment.flush().unwrap();
+            segment.current_path.clone()
+        };
+
+        let mut segment = load_segment_read_only(&path).unwrap();
+        assert!(!segment.is_appendable());
+        assert_eq!(segment.vectors_count(), 5);
+        assert_eq!(segment.vector(2.into()).unwrap(), vec![1.0, 0.0, 1.0, 0.0]);
+        let res = segment.search(&vec![1.0, 1.0, 1.0, 1.0], &WithPayload::default(), false, None, 1, None, &StopCondition::default()).unwrap();
+        assert_eq!(res[0].id, 3.into());
+
+        let upsert_res = segment.upsert_point(10, 6.into(), &vec![1.0, 0.0, 0.0, 0.0]);
+        assert!(matches!(upsert_res, Err(OperationError::ReadOnlyError)));
+        let delete_res = segment.delete_point(10, 1.into());
+        assert!(matches!(delete_res, Err(OperationError::ReadOnlyError)));
+        assert!(segment.check_consistency(ConsistencyCheckMode::Repair).is_err());
+        assert!(segment.check_consistency(ConsistencyCheckMode::Check).unwrap().is_consistent());
+
+        segment.flush().unwrap();
+        drop(segment);
+
+        let segment = load_segment(&path).unwrap();
+        assert_eq!(segment.vectors_count(), 5);
+        assert!(segment.has_point(1.into()));
+    }
+
+    #[test]
+    fn test_load_persisted_config() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let (path, config) = {
+            let segment = build_segment_1(dir.path());
+            segment.flush().unwrap();
+            (segment.current_path.clone(), segment.segment_config.clone())
+        };
+
+        let segment = Segment::load(&path).unwrap();
+        assert_eq!(segment.segment_config, config);
+        assert_eq!(segment.vectors_count(), 5);
+        drop(segment);
+
+        assert!(load_segment_with_config(&path, &config).is_ok());
+
+        let wrong_config = SegmentConfig {
+            vector_size: config.vector_size + 1,
+            distance: Distance::Euclid,
+            ..config.clone()
+        };
+        match load_segment_with_config(&path, &wrong_config) {
+            Err(OperationError::WrongInput { description }) => {
+                assert!(description.contains("vector size"));
+                assert!(description.contains("distance"));
+            }
+            _ => panic!("Config mismatch is not detected")
+        }
+    }
+
+    #[test]
+    fn test_fork_segment() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let fork_dir = TempDir::new("fork_dir").unwrap();
+        let segment = build_segment_1(dir.path());
+
+        let mut fork = segment.fork(fork_dir.path()).unwrap();
+        assert!(fork.current_path.starts_with(fork_dir.path()));
+        assert_eq!(fork.version(), segment.version());
+        assert_eq!(fork.vectors_count(), 5);
+        assert!(fork.payload(3.into()).unwrap().contains_key("color"));
+
+        // Changes of the fork are not visible in the original segment and vice versa
+        fork.delete_point(10, 1.into()).unwrap();
+        fork.upsert_point(11, 6.into(), &vec![0.0, 0.0, 0.0, 1.0]).unwrap();
+        segment.delete_point(12, 2.into()).unwrap();
+
+        assert!(segment.has_point(1.into()));
+        assert!(!segment.has_point(6.into()));
+        assert!(fork.has_point(2.into()));
+        assert_eq!(fork.vectors_count(), 5);
+        assert_eq!(segment.vectors_count(), 4);
+
+        fork.flush().unwrap();
+        let fork_path = fork.current_path.clone();
+        drop(fork);
+        let fork = Segment::load(&fork_path).unwrap();
+        assert!(fork.has_point(6.into()));
+        assert!(!fork.has_point(1.into()));
+    }
+
+    #[test]
+    fn test_take_snapshot() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let snapshot_dir = TempDir::new("snapshot_dir").unwrap();
+        let mut segment = build_segment_1(dir.path());
+
+        let snapshot_path = segment.take_snapshot(snapshot_dir.path()).unwrap();
+        assert!(snapshot_path.starts_with(snapshot_dir.path()));
+
+        // Later changes are not included into the snapshot
+        segment.delete_point(10, 1.into()).unwrap();
+        segment.upsert_point(11, 6.into(), &vec![0.0, 0.0, 0.0, 1.0]).unwrap();
+
+        let snapshot = Segment::load(&snapshot_path).unwrap();
+        assert_eq!(snapshot.version(), 6);
+        assert_eq!(snapshot.vectors_count(), 5);
+        assert!(snapshot.has_point(1.into()));
+        assert!(!snapshot.has_point(6.into()));
+        assert!(snapshot.payload(3.into()).unwrap().contains_key("color"));
+    }
+
+    #[test]
+    fn test_upsert_batch() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = build_segment_1(dir.path());
+
+        let mut payload = TheMap::new();
+        payload.insert("color".to_owned(), PayloadType::Keyword(vec!["green".to_owned()]));
+
+        let points = vec![
+            (1.into(), vec![0.0, 0.0, 0.0, 1.0], None),
+            (10.into(), vec![0.0, 1.0, 0.0, 0.0], Some(payload.clone())),
+            (11.into(), vec![0.0, 0.0, 1.0, 0.0], None),
+            (11.into(), vec![0.0, 0.0, 2.0, 0.0], Some(payload)),
+        ];
+        let applied = segment.upsert_batch(20, &points).unwrap();
+        assert_eq!(applied, 3);
+        assert_eq!(segment.vectors_count(), 7);
+
+        assert_eq!(segment.vector(1.into()).unwrap(), vec![0.0, 0.0, 0.0, 1.0]);
+        // Existing payload is preserved if not specified
+        assert!(segment.payload(1.into()).unwrap().contains_key("color"));
+        assert_eq!(segment.vector(11.into()).unwrap(), vec![0.0, 0.0, 2.0, 0.0]);
+        match segment.payload(10.into()).unwrap().get("color") {
+            Some(PayloadType::Keyword(values)) => assert_eq!(values, &vec!["green".to_owned()]),
+            _ => panic!("Payload is not assigned")
+        }
+        assert_eq!(segment.point_version(11.into()), Some(20));
+
+        // Outdated batch is not applied
+        let applied = segment.upsert_batch(19, &[(10.into(), vec![1.0, 1.0, 1.0, 1.0], None)]).unwrap();
+        assert_eq!(applied, 0);
+        assert_eq!(segment.vector(10.into()).unwrap(), vec![0.0, 1.0, 0.0, 0.0]);
+
+        let wrong_dim = segment.upsert_batch(21, &[(12.into(), vec![1.0], None)]);
+        assert!(matches!(wrong_dim, Err(OperationError::WrongVector { .. })));
+    }
+
+    #[test]
+    fn test_upsert_existing_with_payload() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let mut segment = build_segment_1(dir.path());
+        let vectors_count = segment.vectors_count();
+
+        let mut payload = TheMap::new();
+        payload.insert("color".to_owned(), PayloadType::Keyword(vec!["blue".to_owned()]));
+
+        // Point is re-written into a new place and published with both vector and payload
+        segment.upsert_batch(20, &[(2.into(), vec![1.0, 1.0, 1.0, 1.0], Some(payload))]).unwrap();
+
+        assert_eq!(segment.vectors_count(), vectors_count);
+        assert_eq!(segment.vector(2.into()).unwrap(), vec![1.0, 1.0, 1.0, 1.0]);
+        match segment.payload(2.into()).unwrap().get("color") {
+            Some(PayloadType::Keyword(values)) => assert_eq!(values, &vec!["blue".to_owned()]),
+            _ => panic!("Payload is not assigned")
+        }
+
+        segment.flush().unwrap();
+        assert!(segment.check_consistency(ConsistencyCheckMode::Check).unwrap().is_consistent());
+    }
+}
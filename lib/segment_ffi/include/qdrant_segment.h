@@ -0,0 +1,167 @@
+#ifndef QDRANT_SEGMENT_H
+#define QDRANT_SEGMENT_H
+
+/* Generated by cbindgen from lib/segment_ffi, do not edit manually */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum QdrantDistance {
+  QDRANT_DISTANCE_COSINE,
+  QDRANT_DISTANCE_DOT,
+  /**
+   * Not supported yet: search with Euclid distance is not implemented
+   */
+  QDRANT_DISTANCE_EUCLID,
+} QdrantDistance;
+
+/**
+ * Result of each call. Description of the error is available with `qdrant_last_error_message`
+ */
+typedef enum QdrantStatus {
+  QDRANT_STATUS_OK = 0,
+  /**
+   * Required pointer argument is NULL
+   */
+  QDRANT_STATUS_NULL_POINTER,
+  /**
+   * Argument is malformed, e.g. path is not UTF-8 or filter is not a valid JSON
+   */
+  QDRANT_STATUS_INVALID_ARGUMENT,
+  /**
+   * Request is not applicable to the segment, e.g. vector has wrong dimension
+   */
+  QDRANT_STATUS_WRONG_INPUT,
+  /**
+   * Point with the given id does not exist
+   */
+  QDRANT_STATUS_NOT_FOUND,
+  /**
+   * Segment is not supported by C API, e.g. it contains UUID ids or uses Euclid distance
+   */
+  QDRANT_STATUS_UNSUPPORTED,
+  /**
+   * Data of the segment is damaged
+   */
+  QDRANT_STATUS_CORRUPTED,
+  /**
+   * Internal error, e.g. of disk access
+   */
+  QDRANT_STATUS_SERVICE_ERROR,
+  /**
+   * Unexpected failure of the library, the handle should not be used anymore
+   */
+  QDRANT_STATUS_PANIC,
+} QdrantStatus;
+
+/**
+ * Opaque handle of an opened segment. Should be released with `qdrant_segment_free`.
+ * Handle should not be used by several threads at the same time
+ */
+typedef struct QdrantSegment QdrantSegment;
+
+typedef struct QdrantScoredPoint {
+  uint64_t id;
+  float score;
+} QdrantScoredPoint;
+
+/**
+ * Copy message of the last error of the calling thread into the caller-owned `buffer` of `buffer_size` bytes.
+ * Message is truncated to fit the buffer and is always NUL-terminated.
+ * Returns full length of the message without terminating NUL, 0 if the last call succeeded
+ *
+ * # Safety
+ * `buffer` should be NULL or point to at least `buffer_size` writable bytes
+ */
+size_t qdrant_last_error_message(char *buffer,
+                                 size_t buffer_size);
+
+/**
+ * Create a new empty segment in a sub-directory of the `path` directory
+ *
+ * # Safety
+ * `path` should be a NUL-terminated string, `out_segment` should point to a writable handle pointer
+ */
+enum QdrantStatus qdrant_segment_build(const char *path,
+                                       size_t dim,
+                                       enum QdrantDistance distance,
+                                       struct QdrantSegment **out_segment);
+
+/**
+ * Open existing segment directory
+ *
+ * # Safety
+ * `path` should be a NUL-terminated string, `out_segment` should point to a writable handle pointer
+ */
+enum QdrantStatus qdrant_segment_open(const char *path, struct QdrantSegment **out_segment);
+
+/**
+ * Release the handle. Not flushed changes are lost. NULL is ignored
+ *
+ * # Safety
+ * `segment` should be a handle, returned by `qdrant_segment_build` or `qdrant_segment_open`, and not released yet
+ */
+void qdrant_segment_free(struct QdrantSegment *segment);
+
+/**
+ * Insert or replace `count` points. `vectors` contains `count * dim` elements, a vector per id.
+ * Number of applied points is written into `out_applied`, if it is not NULL
+ *
+ * # Safety
+ * `segment` should be a valid handle, `ids` and `vectors` should point to `count` and `count * dim` elements
+ */
+enum QdrantStatus qdrant_segment_upsert(struct QdrantSegment *segment,
+                                        const uint64_t *ids,
+                                        const float *vectors,
+                                        size_t count,
+                                        size_t *out_applied);
+
+/**
+ * Delete the point. Whether the point existed is written into `out_deleted`, if it is not NULL
+ *
+ * # Safety
+ * `segment` should be a valid handle
+ */
+enum QdrantStatus qdrant_segment_delete(struct QdrantSegment *segment,
+                                        uint64_t id,
+                                        bool *out_deleted);
+
+/**
+ * Find up to `top` closest points to the `vector` of `dim` elements, which satisfy the optional filter.
+ * Points are written into the caller-owned `out_points` array of at least `top` elements,
+ * their number is written into `out_found`
+ *
+ * # Safety
+ * `segment` should be a valid handle, `vector` should point to `dim` elements,
+ * `filter_json` should be NULL or a NUL-terminated string, `out_points` should point to `top` writable elements
+ */
+enum QdrantStatus qdrant_segment_search(const struct QdrantSegment *segment,
+                                        const float *vector,
+                                        size_t dim,
+                                        const char *filter_json,
+                                        size_t top,
+                                        struct QdrantScoredPoint *out_points,
+                                        size_t *out_found);
+
+/**
+ * Number of points in the segment
+ *
+ * # Safety
+ * `segment` should be a valid handle, `out_count` should be writable
+ */
+enum QdrantStatus qdrant_segment_vectors_count(const struct QdrantSegment *segment,
+                                               size_t *out_count);
+
+/**
+ * Persist all changes. Version of the persisted data is written into `out_version`, if it is not NULL
+ *
+ * # Safety
+ * `segment` should be a valid handle
+ */
+enum QdrantStatus qdrant_segment_flush(const struct QdrantSegment *segment,
+                                       uint64_t *out_version);
+
+#endif /* QDRANT_SEGMENT_H */
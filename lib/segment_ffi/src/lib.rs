@@ -0,0 +1,421 @@
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use segment::common::stop_condition::StopCondition;
+use segment::entry::entry_point::{OperationError, SegmentEntry};
+use segment::segment::Segment;
+use segment::types::{BatchPoint, Distance, Filter, PointIdType, WithPayload};
+
+/// Result of each call. Description of the error is available with `qdrant_last_error_message`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QdrantStatus {
+    Ok = 0,
+    /// Required pointer argument is NULL
+    NullPointer,
+    /// Argument is malformed, e.g. path is not UTF-8 or filter is not a valid JSON
+    InvalidArgument,
+    /// Request is not applicable to the segment, e.g. vector has wrong dimension
+    WrongInput,
+    /// Point with the given id does not exist
+    NotFound,
+    /// Segment is not supported by C API, e.g. it contains UUID ids or uses Euclid distance
+    Unsupported,
+    /// Data of the segment is damaged
+    Corrupted,
+    /// Internal error, e.g. of disk access
+    ServiceError,
+    /// Unexpected failure of the library, the handle should not be used anymore
+    Panic,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QdrantDistance {
+    Cosine,
+    Dot,
+    /// Not supported yet: search with Euclid distance is not implemented
+    Euclid,
+}
+
+impl From<QdrantDistance> for Distance {
+    fn from(distance: QdrantDistance) -> Self {
+        match distance {
+            QdrantDistance::Cosine => Distance::Cosine,
+            QdrantDistance::Dot => Distance::Dot,
+            QdrantDistance::Euclid => Distance::Euclid,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QdrantScoredPoint {
+    pub id: u64,
+    pub score: f32,
+}
+
+/// Opaque handle of an opened segment. Should be released with `qdrant_segment_free`.
+/// Handle should not be used by several threads at the same time
+pub struct QdrantSegment {
+    segment: Segment,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+struct FfiError {
+    status: QdrantStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn new(status: QdrantStatus, message: impl Into<String>) -> Self {
+        FfiError { status, message: message.into() }
+    }
+}
+
+impl From<OperationError> for FfiError {
+    fn from(err: OperationError) -> Self {
+        let status = match &err {
+            OperationError::PointIdError { .. } => QdrantStatus::NotFound,
+            _ if err.is_corruption() => QdrantStatus::Corrupted,
+            _ if err.is_user_error() => QdrantStatus::WrongInput,
+            _ => QdrantStatus::ServiceError,
+        };
+        FfiError::new(status, err.to_string())
+    }
+}
+
+type FfiResult<T> = Result<T, FfiError>;
+
+/// Run the call, converting its errors and panics into status codes and remembering the error message
+fn ffi_call(call: impl FnOnce() -> FfiResult<()>) -> QdrantStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => (QdrantStatus::Ok, String::new()),
+        Ok(Err(err)) => (err.status, err.message),
+        Err(_) => (QdrantStatus::Panic, "Unexpected panic in qdrant segment library".to_string()),
+    };
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    status
+}
+
+unsafe fn reference<'a, T>(pointer: *const T, name: &str) -> FfiResult<&'a T> {
+    pointer.as_ref().ok_or_else(|| FfiError::new(QdrantStatus::NullPointer, format!("`{}` is NULL", name)))
+}
+
+unsafe fn mut_reference<'a, T>(pointer: *mut T, name: &str) -> FfiResult<&'a mut T> {
+    pointer.as_mut().ok_or_else(|| FfiError::new(QdrantStatus::NullPointer, format!("`{}` is NULL", name)))
+}
+
+unsafe fn string<'a>(pointer: *const c_char, name: &str) -> FfiResult<&'a str> {
+    if pointer.is_null() {
+        return Err(FfiError::new(QdrantStatus::NullPointer, format!("`{}` is NULL", name)));
+    }
+    CStr::from_ptr(pointer).to_str()
+        .map_err(|_| FfiError::new(QdrantStatus::InvalidArgument, format!("`{}` is not a valid UTF-8 string", name)))
+}
+
+unsafe fn slice<'a, T>(pointer: *const T, len: usize, name: &str) -> FfiResult<&'a [T]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if pointer.is_null() {
+        return Err(FfiError::new(QdrantStatus::NullPointer, format!("`{}` is NULL", name)));
+    }
+    Ok(std::slice::from_raw_parts(pointer, len))
+}
+
+/// Filter is a JSON string of the same structure, as in the REST API. NULL means no filter
+unsafe fn filter(filter_json: *const c_char) -> FfiResult<Option<Filter>> {
+    if filter_json.is_null() {
+        return Ok(None);
+    }
+    let filter = serde_json::from_str(string(filter_json, "filter_json")?)
+        .map_err(|err| FfiError::new(QdrantStatus::InvalidArgument, format!("Invalid filter: {}", err)))?;
+    Ok(Some(filter))
+}
+
+fn check_distance(distance: Distance) -> FfiResult<()> {
+    match distance {
+        Distance::Euclid => Err(FfiError::new(
+            QdrantStatus::Unsupported,
+            "Euclid distance is not supported, as search with it is not implemented",
+        )),
+        Distance::Cosine | Distance::Dot => Ok(()),
+    }
+}
+
+fn numeric_id(id: PointIdType) -> FfiResult<u64> {
+    match id {
+        PointIdType::NumId(id) => Ok(id),
+        PointIdType::Uuid(_) => Err(FfiError::new(
+            QdrantStatus::Unsupported,
+            format!("Point id {} is not numeric, only numeric ids are supported by C API", id),
+        )),
+    }
+}
+
+/// Copy message of the last error of the calling thread into the caller-owned `buffer` of `buffer_size` bytes.
+/// Message is truncated to fit the buffer and is always NUL-terminated.
+/// Returns full length of the message without terminating NUL, 0 if the last call succeeded
+///
+/// # Safety
+/// `buffer` should be NULL or point to at least `buffer_size` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_last_error_message(buffer: *mut c_char, buffer_size: usize) -> usize {
+    LAST_ERROR.with(|last_error| {
+        let message = last_error.borrow();
+        if !buffer.is_null() && buffer_size > 0 {
+            let len = message.len().min(buffer_size - 1);
+            ptr::copy_nonoverlapping(message.as_ptr() as *const c_char, buffer, len);
+            *buffer.add(len) = 0;
+        }
+        message.len()
+    })
+}
+
+/// Create a new empty segment in a sub-directory of the `path` directory
+///
+/// # Safety
+/// `path` should be a NUL-terminated string, `out_segment` should point to a writable handle pointer
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_build(
+    path: *const c_char,
+    dim: usize,
+    distance: QdrantDistance,
+    out_segment: *mut *mut QdrantSegment,
+) -> QdrantStatus {
+    ffi_call(|| {
+        let out_segment = mut_reference(out_segment, "out_segment")?;
+        check_distance(distance.into())?;
+        let segment = Segment::builder(Path::new(string(path, "path")?))
+            .dim(dim)
+            .distance(distance.into())
+            .build()?;
+        *out_segment = Box::into_raw(Box::new(QdrantSegment { segment }));
+        Ok(())
+    })
+}
+
+/// Open existing segment directory
+///
+/// # Safety
+/// `path` should be a NUL-terminated string, `out_segment` should point to a writable handle pointer
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_open(path: *const c_char, out_segment: *mut *mut QdrantSegment) -> QdrantStatus {
+    ffi_call(|| {
+        let out_segment = mut_reference(out_segment, "out_segment")?;
+        let segment = Segment::load(Path::new(string(path, "path")?))?;
+        check_distance(segment.config().distance)?;
+        *out_segment = Box::into_raw(Box::new(QdrantSegment { segment }));
+        Ok(())
+    })
+}
+
+/// Release the handle. Not flushed changes are lost. NULL is ignored
+///
+/// # Safety
+/// `segment` should be a handle, returned by `qdrant_segment_build` or `qdrant_segment_open`, and not released yet
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_free(segment: *mut QdrantSegment) {
+    if !segment.is_null() {
+        drop(Box::from_raw(segment));
+    }
+}
+
+/// Insert or replace `count` points. `vectors` contains `count * dim` elements, a vector per id.
+/// Number of applied points is written into `out_applied`, if it is not NULL
+///
+/// # Safety
+/// `segment` should be a valid handle, `ids` and `vectors` should point to `count` and `count * dim` elements
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_upsert(
+    segment: *mut QdrantSegment,
+    ids: *const u64,
+    vectors: *const f32,
+    count: usize,
+    out_applied: *mut usize,
+) -> QdrantStatus {
+    ffi_call(|| {
+        let segment = &mut mut_reference(segment, "segment")?.segment;
+        let dim = segment.config().vector_size;
+        let vectors_len = count.checked_mul(dim)
+            .ok_or_else(|| FfiError::new(QdrantStatus::InvalidArgument, format!("`count` {} is too large", count)))?;
+        let ids = slice(ids, count, "ids")?;
+        let vectors = slice(vectors, vectors_len, "vectors")?;
+        let points: Vec<BatchPoint> = ids.iter()
+            .zip(vectors.chunks_exact(dim))
+            .map(|(id, vector)| ((*id).into(), vector.to_vec(), None))
+            .collect();
+        let op_num = segment.version() + 1;
+        let applied = segment.upsert_batch(op_num, &points)?;
+        if let Some(out_applied) = out_applied.as_mut() {
+            *out_applied = applied;
+        }
+        Ok(())
+    })
+}
+
+/// Delete the point. Whether the point existed is written into `out_deleted`, if it is not NULL
+///
+/// # Safety
+/// `segment` should be a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_delete(segment: *mut QdrantSegment, id: u64, out_deleted: *mut bool) -> QdrantStatus {
+    ffi_call(|| {
+        let segment = &mut_reference(segment, "segment")?.segment;
+        let op_num = segment.version() + 1;
+        let deleted = segment.delete_point(op_num, id.into())?;
+        if let Some(out_deleted) = out_deleted.as_mut() {
+            *out_deleted = deleted;
+        }
+        Ok(())
+    })
+}
+
+/// Find up to `top` closest points to the `vector` of `dim` elements, which satisfy the optional filter.
+/// Points are written into the caller-owned `out_points` array of at least `top` elements,
+/// their number is written into `out_found`
+///
+/// # Safety
+/// `segment` should be a valid handle, `vector` should point to `dim` elements,
+/// `filter_json` should be NULL or a NUL-terminated string, `out_points` should point to `top` writable elements
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_search(
+    segment: *const QdrantSegment,
+    vector: *const f32,
+    dim: usize,
+    filter_json: *const c_char,
+    top: usize,
+    out_points: *mut QdrantScoredPoint,
+    out_found: *mut usize,
+) -> QdrantStatus {
+    ffi_call(|| {
+        let segment = &reference(segment, "segment")?.segment;
+        let vector = slice(vector, dim, "vector")?.to_vec();
+        let out_found = mut_reference(out_found, "out_found")?;
+        if top > 0 && out_points.is_null() {
+            return Err(FfiError::new(QdrantStatus::NullPointer, "`out_points` is NULL"));
+        }
+        let filter = filter(filter_json)?;
+        let points = segment.search(&vector, &WithPayload::from(false), false, filter.as_ref(), top, None, &StopCondition::default())?;
+        for (idx, point) in points.iter().take(top).enumerate() {
+            *out_points.add(idx) = QdrantScoredPoint { id: numeric_id(point.id)?, score: point.score };
+        }
+        *out_found = points.len().min(top);
+        Ok(())
+    })
+}
+
+/// Number of points in the segment
+///
+/// # Safety
+/// `segment` should be a valid handle, `out_count` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_vectors_count(segment: *const QdrantSegment, out_count: *mut usize) -> QdrantStatus {
+    ffi_call(|| {
+        let segment = &reference(segment, "segment")?.segment;
+        *mut_reference(out_count, "out_count")? = segment.vectors_count();
+        Ok(())
+    })
+}
+
+/// Persist all changes. Version of the persisted data is written into `out_version`, if it is not NULL
+///
+/// # Safety
+/// `segment` should be a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn qdrant_segment_flush(segment: *const QdrantSegment, out_version: *mut u64) -> QdrantStatus {
+    ffi_call(|| {
+        let segment = &reference(segment, "segment")?.segment;
+        let version = segment.flush()?;
+        if let Some(out_version) = out_version.as_mut() {
+            *out_version = version;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn last_error() -> String {
+        let mut buffer = vec![0u8; 256];
+        let len = unsafe { qdrant_last_error_message(buffer.as_mut_ptr() as *mut c_char, buffer.len()) };
+        buffer.truncate(len.min(buffer.len() - 1));
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_segment_api() {
+        let dir = TempDir::new("segment_dir").unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut segment: *mut QdrantSegment = ptr::null_mut();
+            assert_eq!(qdrant_segment_build(path.as_ptr(), 2, QdrantDistance::Dot, &mut segment), QdrantStatus::Ok);
+
+            let ids = [1u64, 2, 3];
+            let vectors = [1.0f32, 0.0, 0.0, 1.0, 1.0, 1.0];
+            let mut applied = 0;
+            assert_eq!(qdrant_segment_upsert(segment, ids.as_ptr(), vectors.as_ptr(), 3, &mut applied), QdrantStatus::Ok);
+            assert_eq!(applied, 3);
+
+            let mut points = [QdrantScoredPoint { id: 0, score: 0.0 }; 2];
+            let mut found = 0;
+            let query = [1.0f32, 0.0];
+            assert_eq!(
+                qdrant_segment_search(segment, query.as_ptr(), 2, ptr::null(), 2, points.as_mut_ptr(), &mut found),
+                QdrantStatus::Ok,
+            );
+            assert_eq!(found, 2);
+            assert!(points.iter().all(|point| point.id == 1 || point.id == 3));
+
+            let wrong_query = [1.0f32, 0.0, 0.0];
+            assert_eq!(
+                qdrant_segment_search(segment, wrong_query.as_ptr(), 3, ptr::null(), 2, points.as_mut_ptr(), &mut found),
+                QdrantStatus::WrongInput,
+            );
+            assert!(last_error().contains("expected dim: 2"));
+
+            let invalid_filter = CString::new("{\"must\": ").unwrap();
+            assert_eq!(
+                qdrant_segment_search(segment, query.as_ptr(), 2, invalid_filter.as_ptr(), 2, points.as_mut_ptr(), &mut found),
+                QdrantStatus::InvalidArgument,
+            );
+
+            let mut deleted = false;
+            assert_eq!(qdrant_segment_delete(segment, 1, &mut deleted), QdrantStatus::Ok);
+            assert!(deleted);
+            let mut count = 0;
+            assert_eq!(qdrant_segment_vectors_count(segment, &mut count), QdrantStatus::Ok);
+            assert_eq!(count, 2);
+            assert_eq!(last_error(), "");
+
+            assert_eq!(qdrant_segment_upsert(ptr::null_mut(), ids.as_ptr(), vectors.as_ptr(), 3, ptr::null_mut()), QdrantStatus::NullPointer);
+            assert_eq!(
+                qdrant_segment_upsert(segment, ids.as_ptr(), vectors.as_ptr(), usize::MAX, ptr::null_mut()),
+                QdrantStatus::InvalidArgument,
+            );
+            qdrant_segment_free(segment);
+
+            let mut euclid_segment: *mut QdrantSegment = ptr::null_mut();
+            assert_eq!(
+                qdrant_segment_build(path.as_ptr(), 2, QdrantDistance::Euclid, &mut euclid_segment),
+                QdrantStatus::Unsupported,
+            );
+            assert!(euclid_segment.is_null());
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::path::Path;
+
+use numpy::{PyArray1, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString};
+
+use segment::common::stop_condition::StopCondition;
+use segment::entry::entry_point::{OperationError, SegmentEntry};
+use segment::segment::Segment;
+use segment::types::{BatchPoint, Distance, Filter, GeoPoint, PayloadKeyType, PayloadType, PointIdType, TheMap, WithPayload};
+
+/// Reference to the segment, which is passed into `Python::allow_threads`
+struct Unguarded<T>(T);
+
+// SAFETY: the reference is used only by the thread, which released the GIL, until `allow_threads` returns.
+// `PySegment` is `unsendable` and borrowed for the whole call, so no other thread accesses the segment meanwhile.
+unsafe impl Send for Unguarded<&Segment> {}
+
+// SAFETY: same as for the shared reference, the exclusive borrow of `PySegment` outlives the call
+unsafe impl Send for Unguarded<&mut Segment> {}
+
+/// Search is not implemented for Euclid distance, so such segments are not supported
+fn check_distance(distance: Distance) -> PyResult<()> {
+    match distance {
+        Distance::Euclid => Err(PyValueError::new_err("Euclid distance is not supported, as search with it is not implemented")),
+        Distance::Cosine | Distance::Dot => Ok(()),
+    }
+}
+
+fn segment_error(err: OperationError) -> PyErr {
+    if err.is_user_error() {
+        PyValueError::new_err(err.to_string())
+    } else {
+        PyRuntimeError::new_err(err.to_string())
+    }
+}
+
+/// Point id is either a non-negative integer or a UUID string
+fn point_id(id: &PyAny) -> PyResult<PointIdType> {
+    if let Ok(id) = id.extract::<u64>() {
+        return Ok(id.into());
+    }
+    let id: &str = id.extract()
+        .map_err(|_| PyValueError::new_err("Point id should be a non-negative integer or UUID string"))?;
+    id.parse().map_err(|_| PyValueError::new_err(format!("Invalid point id {}", id)))
+}
+
+fn py_point_id(py: Python, id: PointIdType) -> PyObject {
+    match id {
+        PointIdType::NumId(id) => id.to_object(py),
+        PointIdType::Uuid(_) => id.to_string().to_object(py),
+    }
+}
+
+fn geo_point(value: &PyAny) -> PyResult<GeoPoint> {
+    let point: &PyDict = value.downcast()?;
+    let coordinate = |name: &str| -> PyResult<f64> {
+        point.get_item(name)
+            .ok_or_else(|| PyValueError::new_err(format!("Geo point should have `{}` coordinate", name)))?
+            .extract()
+    };
+    Ok(GeoPoint { lat: coordinate("lat")?, lon: coordinate("lon")? })
+}
+
+/// Strings, integers, floats and geo points `{"lat": ..., "lon": ...}` are accepted,
+/// as well as non-empty lists of values of the same type
+fn payload_value(value: &PyAny) -> PyResult<PayloadType> {
+    let values: Vec<&PyAny> = match value.downcast::<PyList>() {
+        Ok(list) => list.iter().collect(),
+        Err(_) => vec![value],
+    };
+    let first = *values.first().ok_or_else(|| PyValueError::new_err("Payload list should not be empty"))?;
+    if first.is_instance::<PyBool>()? {
+        Err(PyValueError::new_err("Boolean payload is not supported"))
+    } else if first.is_instance::<PyString>()? {
+        Ok(PayloadType::Keyword(values.into_iter().map(|value| value.extract()).collect::<PyResult<_>>()?))
+    } else if first.is_instance::<PyLong>()? {
+        Ok(PayloadType::Integer(values.into_iter().map(|value| value.extract()).collect::<PyResult<_>>()?))
+    } else if first.is_instance::<PyFloat>()? {
+        Ok(PayloadType::Float(values.into_iter().map(|value| value.extract()).collect::<PyResult<_>>()?))
+    } else if first.is_instance::<PyDict>()? {
+        Ok(PayloadType::Geo(values.into_iter().map(geo_point).collect::<PyResult<_>>()?))
+    } else {
+        Err(PyValueError::new_err(format!("Unsupported payload value {}", first)))
+    }
+}
+
+fn payload(value: &PyDict) -> PyResult<TheMap<PayloadKeyType, PayloadType>> {
+    value.iter()
+        .map(|(key, value)| Ok((key.extract()?, payload_value(value)?)))
+        .collect()
+}
+
+/// Values of each key are returned as lists
+fn py_payload(py: Python, payload: &TheMap<PayloadKeyType, PayloadType>) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (key, value) in payload {
+        let value = match value {
+            PayloadType::Keyword(values) => values.to_object(py),
+            PayloadType::Integer(values) => values.to_object(py),
+            PayloadType::Float(values) => values.to_object(py),
+            PayloadType::Geo(points) => {
+                let points = points.iter()
+                    .map(|point| {
+                        let py_point = PyDict::new(py);
+                        py_point.set_item("lat", point.lat)?;
+                        py_point.set_item("lon", point.lon)?;
+                        Ok(py_point.to_object(py))
+                    })
+                    .collect::<PyResult<Vec<_>>>()?;
+                points.to_object(py)
+            }
+        };
+        dict.set_item(key, value)?;
+    }
+    Ok(dict.to_object(py))
+}
+
+/// Filter is a dict of the same structure, as in the REST API
+fn parse_filter(py: Python, filter: Option<&PyDict>) -> PyResult<Option<Filter>> {
+    filter
+        .map(|filter| {
+            let json: String = py.import("json")?.call_method1("dumps", (filter, ))?.extract()?;
+            serde_json::from_str(&json).map_err(|err| PyValueError::new_err(format!("Invalid filter: {}", err)))
+        })
+        .transpose()
+}
+
+/// Segment of vectors with payload, stored in its own directory.
+/// Changes are persisted by `flush`
+#[pyclass(name = "Segment", unsendable)]
+struct PySegment {
+    segment: Segment,
+}
+
+#[pymethods]
+impl PySegment {
+    /// Create a new empty segment in a sub-directory of `path`.
+    /// `distance` is either `Cosine` or `Dot`
+    #[staticmethod]
+    #[args(distance = "\"Cosine\"")]
+    fn build(path: &str, dim: usize, distance: &str) -> PyResult<Self> {
+        let distance: Distance = serde_json::from_value(distance.into())
+            .map_err(|_| PyValueError::new_err(format!("Unknown distance {}, expected Cosine or Dot", distance)))?;
+        check_distance(distance)?;
+        let segment = Segment::builder(Path::new(path))
+            .dim(dim)
+            .distance(distance)
+            .build()
+            .map_err(segment_error)?;
+        Ok(PySegment { segment })
+    }
+
+    /// Open existing segment directory
+    #[staticmethod]
+    fn open(path: &str) -> PyResult<Self> {
+        let segment = Segment::load(Path::new(path)).map_err(segment_error)?;
+        check_distance(segment.config().distance)?;
+        Ok(PySegment { segment })
+    }
+
+    #[getter]
+    fn path(&self) -> String {
+        self.segment.current_path.display().to_string()
+    }
+
+    #[getter]
+    fn dim(&self) -> usize {
+        self.segment.config().vector_size
+    }
+
+    #[getter]
+    fn vectors_count(&self) -> usize {
+        self.segment.vectors_count()
+    }
+
+    /// Insert or replace points. `vectors` is a 2-dimensional float32 array with a row per id, rows are copied
+    /// from the array directly, without conversion into python objects. `payloads`, if given, is a list of dicts, which replace payloads
+    /// of the points. Returns number of applied points. Other python threads run, while the points are stored
+    #[args(payloads = "None")]
+    fn upsert(
+        &mut self,
+        py: Python,
+        ids: Vec<&PyAny>,
+        vectors: PyReadonlyArray2<f32>,
+        payloads: Option<Vec<&PyDict>>,
+    ) -> PyResult<usize> {
+        let vectors = vectors.as_array();
+        if vectors.nrows() != ids.len() {
+            return Err(PyValueError::new_err(format!("{} ids are given for {} vectors", ids.len(), vectors.nrows())));
+        }
+        if let Some(payloads) = &payloads {
+            if payloads.len() != ids.len() {
+                return Err(PyValueError::new_err(format!("{} ids are given for {} payloads", ids.len(), payloads.len())));
+            }
+        }
+        let points = ids.into_iter()
+            .enumerate()
+            .map(|(idx, id)| {
+                let payload = match &payloads {
+                    Some(payloads) => Some(payload(payloads[idx])?),
+                    None => None,
+                };
+                Ok((point_id(id)?, vectors.row(idx).to_vec(), payload))
+            })
+            .collect::<PyResult<Vec<BatchPoint>>>()?;
+        let op_num = self.segment.version() + 1;
+        let segment = Unguarded(&mut self.segment);
+        py.allow_threads(move || segment.0.upsert_batch(op_num, &points)).map_err(segment_error)
+    }
+
+    /// Delete the point. Returns False, if there is no such point
+    fn delete(&mut self, id: &PyAny) -> PyResult<bool> {
+        let op_num = self.segment.version() + 1;
+        self.segment.delete_point(op_num, point_id(id)?).map_err(segment_error)
+    }
+
+    /// Closest points to the float32 query vector, which satisfy the filter.
+    /// Returns a list of tuples of id, score and payload, if requested. Other python threads run during the search
+    #[args(top = "10", filter = "None", with_payload = "false")]
+    fn search(
+        &self,
+        py: Python,
+        vector: PyReadonlyArray1<f32>,
+        top: usize,
+        filter: Option<&PyDict>,
+        with_payload: bool,
+    ) -> PyResult<Vec<(PyObject, f32, Option<PyObject>)>> {
+        let vector = vector.as_array().to_vec();
+        let filter = parse_filter(py, filter)?;
+        let segment = Unguarded(&self.segment);
+        let points = py.allow_threads(move || segment.0.search(
+            &vector,
+            &WithPayload::from(with_payload),
+            false,
+            filter.as_ref(),
+            top,
+            None,
+            &StopCondition::default(),
+        )).map_err(segment_error)?;
+        points.into_iter()
+            .map(|point| {
+                let payload = point.payload.map(|payload| py_payload(py, &payload)).transpose()?;
+                Ok((py_point_id(py, point.id), point.score, payload))
+            })
+            .collect()
+    }
+
+    /// Ids of points, which satisfy the filter, in ascending order starting from `offset` inclusive.
+    /// Returns ids and offset of the next page, None if there are no more points
+    #[args(offset = "None", limit = "10", filter = "None")]
+    fn scroll(
+        &self,
+        py: Python,
+        offset: Option<&PyAny>,
+        limit: usize,
+        filter: Option<&PyDict>,
+    ) -> PyResult<(Vec<PyObject>, Option<PyObject>)> {
+        let offset = offset.map(point_id).transpose()?;
+        let filter = parse_filter(py, filter)?;
+        let mut ids = self.segment
+            .read_filtered(offset, limit + 1, filter.as_ref(), &StopCondition::default())
+            .map_err(segment_error)?;
+        let next_offset = if ids.len() > limit { ids.pop() } else { None };
+        Ok((
+            ids.into_iter().map(|id| py_point_id(py, id)).collect(),
+            next_offset.map(|id| py_point_id(py, id)),
+        ))
+    }
+
+    fn vector<'py>(&self, py: Python<'py>, id: &PyAny) -> PyResult<&'py PyArray1<f32>> {
+        let vector = self.segment.vector(point_id(id)?).map_err(segment_error)?;
+        Ok(PyArray1::from_vec(py, vector))
+    }
+
+    fn payload(&self, py: Python, id: &PyAny) -> PyResult<PyObject> {
+        let payload = self.segment.payload(point_id(id)?).map_err(segment_error)?;
+        py_payload(py, &payload)
+    }
+
+    /// Persist all changes. Returns version of the persisted data
+    fn flush(&self) -> PyResult<u64> {
+        self.segment.flush().map_err(segment_error)
+    }
+}
+
+/// In-process access to segments of the qdrant vector search engine
+#[pymodule]
+fn qdrant_segment(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PySegment>()?;
+    Ok(())
+}
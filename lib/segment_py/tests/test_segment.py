@@ -0,0 +1,40 @@
+import numpy as np
+import pytest
+
+import qdrant_segment
+
+
+def test_build_upsert_search_scroll(tmp_path):
+    segment = qdrant_segment.Segment.build(str(tmp_path), dim=4, distance="Dot")
+    vectors = np.eye(4, dtype=np.float32)
+    assert segment.upsert(
+        [1, 2, 3, 4],
+        vectors,
+        payloads=[{"city": "Berlin"}, {"city": "London"}, {"city": ["Berlin", "Moscow"]}, {"city": "Paris"}],
+    ) == 4
+    assert segment.vectors_count == 4
+
+    point_id, score, payload = segment.search(vectors[1], top=1, with_payload=True)[0]
+    assert point_id == 2
+    assert score == 1.0
+    assert payload == {"city": ["London"]}
+
+    berlin = {"must": [{"key": "city", "match": {"keyword": "Berlin"}}]}
+    found = segment.search(np.ones(4, dtype=np.float32), top=10, filter=berlin)
+    assert sorted(point_id for point_id, _, _ in found) == [1, 3]
+
+    ids, next_offset = segment.scroll(limit=3)
+    assert ids == [1, 2, 3]
+    assert next_offset == 4
+    ids, next_offset = segment.scroll(offset=next_offset, limit=3)
+    assert ids == [4]
+    assert next_offset is None
+
+    segment.flush()
+    segment = qdrant_segment.Segment.open(segment.path)
+    assert segment.vectors_count == 4
+
+
+def test_euclid_distance_is_rejected(tmp_path):
+    with pytest.raises(ValueError):
+        qdrant_segment.Segment.build(str(tmp_path), dim=4, distance="Euclid")
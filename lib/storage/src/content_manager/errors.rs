@@ -16,6 +16,10 @@ pub enum StorageError {
     ServiceError { description: String },
     #[error("Bad request: {description}")]
     BadRequest { description: String },
+    #[error("Operation cancelled: {description}")]
+    Cancelled { description: String },
+    #[error("Service is overloaded: {description}")]
+    Overloaded { description: String },
 }
 
 impl From<CollectionError> for StorageError {
@@ -25,6 +29,9 @@ impl From<CollectionError> for StorageError {
             err @ CollectionError::NotFound { .. } => StorageError::NotFound { description: format!("{}", err) },
             CollectionError::ServiceError { error } => StorageError::ServiceError { description: error },
             CollectionError::BadRequest { description } => StorageError::BadRequest { description },
+            err @ CollectionError::StrictModeViolation { .. } => StorageError::BadRequest { description: format!("{}", err) },
+            CollectionError::Cancelled { description } => StorageError::Cancelled { description },
+            CollectionError::Overloaded { description } => StorageError::Overloaded { description },
         }
     }
 }
@@ -41,6 +48,15 @@ impl From<TransactionError> for StorageError {
     }
 }
 
+impl From<TransactionError<StorageError>> for StorageError {
+    fn from(err: TransactionError<StorageError>) -> Self {
+        match err {
+            TransactionError::Abort(err) => err,
+            TransactionError::Storage(err) => err.into(),
+        }
+    }
+}
+
 impl From<IoError> for StorageError {
     fn from(err: IoError) -> Self {
         StorageError::ServiceError { description: format!("{}", err) }
@@ -0,0 +1,24 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use collection::operations::types::{CollectionHealth, HealthStatus};
+
+/// Free space on the disk of the storage, below which the service is degraded.
+/// Segments and WAL could not be persisted, once the disk is full.
+pub const MIN_FREE_DISK_SPACE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+/// State of the service and all of its collections
+pub struct ServiceHealth {
+    /// Degraded, if the disk is full or some of collections are degraded
+    pub status: HealthStatus,
+    /// All collections are loaded and their WAL is replayed
+    pub ready: bool,
+    /// Free space on the disk of the storage in bytes
+    pub free_disk_space: u64,
+    /// State of the loaded collections
+    pub collections: BTreeMap<String, CollectionHealth>,
+}
@@ -1,3 +1,6 @@
 pub mod storage_ops;
 pub mod errors;
-pub mod toc;
\ No newline at end of file
+pub mod toc;
+pub mod snapshots;
+pub mod health;
+pub mod telemetry;
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::fs::read_dir;
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::content_manager::errors::StorageError;
+
+/// Extension of snapshot archives, which is used to find snapshots of a collection
+pub const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct SnapshotDescription {
+    pub name: String,
+    /// Size of the snapshot archive in bytes
+    pub size: u64,
+}
+
+pub fn describe_snapshot(snapshot_path: &Path) -> Result<SnapshotDescription, StorageError> {
+    let metadata = snapshot_path.metadata()
+        .or_else(|err| Err(StorageError::ServiceError {
+            description: format!("Can't read snapshot {:?}, error: {}", snapshot_path, err)
+        }))?;
+    Ok(SnapshotDescription {
+        name: snapshot_path.file_name().unwrap().to_string_lossy().to_string(),
+        size: metadata.len(),
+    })
+}
+
+/// Snapshots stored in the directory, ordered by name. Missing directory has no snapshots.
+pub fn list_snapshots(snapshots_path: &Path) -> Result<Vec<SnapshotDescription>, StorageError> {
+    if !snapshots_path.exists() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = vec![];
+    for entry in read_dir(snapshots_path)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map_or(false, |ext| ext == SNAPSHOT_EXTENSION) {
+            snapshots.push(describe_snapshot(&path)?);
+        }
+    }
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
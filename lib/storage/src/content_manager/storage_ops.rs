@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
-use segment::types::{Distance, Indexes};
+use std::collections::HashMap;
+use segment::types::{Distance, Indexes, PayloadKeyType, TextAnalyzerConfig, FlushPolicy, StorageType};
+use collection::config::OptimizersConfigDiff;
+use collection::strict_mode::StrictModeConfig;
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum AliasOperations {
     /// Create alternative name for a collection.
     /// Collection will be available under both names for search, retrieve,
+    /// If alias already exists, it is switched to the given collection
     CreateAlias {
         collection_name: String,
         alias_name: String,
@@ -31,12 +35,45 @@ pub enum StorageOperations {
         vector_size: usize,
         distance: Distance,
         index: Option<Indexes>,
+        /// Text analysis rules for keyword fields, which should support full-text match
+        text_analyzers: Option<HashMap<PayloadKeyType, TextAnalyzerConfig>>,
+        /// When collection changes should be persisted. If not specified - service-wide flush interval is used
+        flush_policy: Option<FlushPolicy>,
+        /// Number of shards the collection is split into. Default: 1
+        shard_number: Option<usize>,
+        /// Number of copies of each shard. Default: 1
+        replication_factor: Option<usize>,
+        /// Number of replicas of a shard, which should acknowledge an update. Default: 1
+        write_consistency_factor: Option<usize>,
+        /// Number of threads per shard, which apply updates to segments. Default: 1
+        update_workers: Option<usize>,
+        /// Payload field, which defines shards of points. If not specified - points are placed by their ids
+        shard_key: Option<PayloadKeyType>,
+        /// Limits of requests to the collection. If not specified - requests are not limited
+        strict_mode: Option<StrictModeConfig>,
+    },
+    /// Change parameters of the existing collection. Only specified parameters are changed.
+    /// Existing data is re-built in background according to the new parameters
+    UpdateCollection {
+        name: String,
+        /// New parameters of the vector index
+        index: Option<Indexes>,
+        /// New persistence policy of the collection
+        flush_policy: Option<FlushPolicy>,
+        /// New storage of vectors, existing segments are re-built with it in background
+        storage_type: Option<StorageType>,
+        /// Collection-specific changes of the optimizers parameters
+        optimizers_config: Option<OptimizersConfigDiff>,
+        /// New number of replicas of a shard, which should acknowledge an update
+        write_consistency_factor: Option<usize>,
+        /// New limits of requests to the collection, replace all previous limits
+        strict_mode: Option<StrictModeConfig>,
     },
     /// Delete collection with given name
     DeleteCollection(String),
     /// Perform changes of collection aliases.
     /// Alias changes are atomic, meaning that no collection modifications can happen between
-    /// alias operations.
+    /// alias operations, and either all actions are applied or none of them.
     ChangeAliases {
         actions: Vec<AliasOperations>,
     }
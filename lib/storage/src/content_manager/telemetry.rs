@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use collection::operations::types::CollectionTelemetry;
+use collection::optimization_pool::PoolTelemetry;
+use collection::search_cache::SearchCacheTelemetry;
+use segment::spaces::dispatch::KernelTelemetry;
+use segment::types::SegmentMemoryUsage;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+/// Resource usage of the node and state of shards of all its collections
+pub struct NodeTelemetry {
+    /// Resident memory of the service process in bytes. Not reported, if the platform does not expose it
+    pub memory_usage: Option<u64>,
+    /// Free space on the disk of the storage in bytes
+    pub free_disk_space: u64,
+    /// Disk space, used by all collections
+    pub disk_data_size: usize,
+    /// RAM, used by all collections
+    pub ram_data_size: usize,
+    /// RAM, used by each component of segments of all collections
+    pub segments_memory_usage: SegmentMemoryUsage,
+    /// Number of open segments in all collections
+    pub segments_count: usize,
+    /// Number of running and pending optimizations in all collections
+    pub optimizations_count: usize,
+    /// Number of updates in all collections, which are written to WAL, but not applied to segments yet
+    pub pending_updates: usize,
+    /// Number of updates, which are accepted by the API and not finished yet, see `max_pending_updates`
+    pub admitted_updates: usize,
+    /// Usage of the search cache, if it is enabled
+    pub search_cache: Option<SearchCacheTelemetry>,
+    /// Number of threads of the search runtime
+    pub search_threads: usize,
+    /// Distance kernels, which are supported by the CPU and selected for each metric and dimension
+    pub kernels: KernelTelemetry,
+    /// Threads and queue of the pool, which performs optimizations of all collections
+    pub optimization_pool: PoolTelemetry,
+    pub collections: BTreeMap<String, CollectionTelemetry>,
+}
+
+/// Resident memory of the current process, read from `/proc` on Linux
+pub fn process_memory_usage() -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    let resident_kb: u64 = status.lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(resident_kb * 1024)
+}
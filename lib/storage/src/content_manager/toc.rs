@@ -1,25 +1,41 @@
 use std::cmp::max;
-use std::collections::HashMap;
-use std::fs::{create_dir_all, read_dir, remove_dir_all};
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{create_dir_all, read_dir, remove_dir_all, rename};
+use std::path::{Component, Path, PathBuf};
 use std::str::from_utf8;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use fs2::available_space;
 use num_cpus;
 use parking_lot::RwLock;
 use sled::{Config, Db};
-use sled::transaction::UnabortableTransactionError;
+use sled::transaction::ConflictableTransactionError;
 use tokio::runtime;
 use tokio::runtime::Runtime;
+use tracing::{info, warn};
 use wal::WalOptions;
 
-use collection::collection::Collection;
+use collection::async_collection::{AsyncCollection, BlockingPool, UpdateAdmission};
+use collection::cold_storage::ColdStorage;
+use collection::collection::{Collection, CollectionError};
 use collection::collection_builder::collection_builder::build_collection;
-use collection::collection_builder::collection_loader::load_collection;
-use segment::types::SegmentConfig;
+use collection::collection_builder::collection_loader::{load_collection, restore_snapshot};
+use collection::config::{CollectionConfig, CollectionConfigDiff};
+use collection::optimization_pool::OptimizationPool;
+use collection::search_cache::SearchCache;
+use collection::numa::NumaPlacement;
+use collection::operations::types::{CsvImportRequest, HealthStatus, NpyImportRequest, ParquetImportRequest};
+use segment::common::stop_condition::StopCondition;
+use segment::spaces::dispatch::kernel_telemetry;
+use segment::types::{SegmentConfig, SegmentMemoryUsage};
 
 use crate::content_manager::errors::StorageError;
+use crate::content_manager::health::{MIN_FREE_DISK_SPACE, ServiceHealth};
+use crate::content_manager::snapshots::{SNAPSHOT_EXTENSION, SnapshotDescription, describe_snapshot, list_snapshots};
 use crate::content_manager::storage_ops::{AliasOperations, StorageOperations};
+use crate::content_manager::telemetry::{NodeTelemetry, process_memory_usage};
 use crate::types::StorageConfig;
 
 /// Since sled is used for reading only during the initialization, large read cache is not required
@@ -27,16 +43,39 @@ const SLED_CACHE_SIZE: u64 = 1 * 1024 * 1024; // 1 mb
 
 const COLLECTIONS_DIR: &str = "collections";
 
+/// Prefix of the directory in the storage, where a snapshot is unpacked before it replaces the collection
+const RESTORE_DIR_PREFIX: &str = ".restore-";
+
+type Collections = HashMap<String, Arc<Collection>>;
+
 pub struct TableOfContent {
-    collections: Arc<RwLock<HashMap<String, Arc<Collection>>>>,
+    collections: Arc<RwLock<Collections>>,
     storage_config: StorageConfig,
     search_runtime: Arc<Runtime>,
+    search_threads: usize,
+    optimization_pool: Arc<OptimizationPool>,
+    numa: Arc<NumaPlacement>,
+    blocking_pool: Arc<BlockingPool>,
+    update_admission: Arc<UpdateAdmission>,
+    search_cache: Option<Arc<SearchCache>>,
     alias_persistence: Db,
+    cold_storage: Option<Arc<ColdStorage>>,
+    /// All collections, stored on disk, are loaded
+    loaded: AtomicBool,
 }
 
 
 impl TableOfContent {
+    /// Create table of content and load all stored collections
     pub fn new(storage_config: &StorageConfig) -> Self {
+        let toc = Self::create(storage_config);
+        toc.load_collections();
+        toc
+    }
+
+    /// Create table of content without loading stored collections.
+    /// Collections should be loaded with `load_collections`, e.g. while the service already responds to health checks.
+    pub fn create(storage_config: &StorageConfig) -> Self {
         let mut search_threads = storage_config.performance.max_search_threads;
 
         if search_threads == 0 {
@@ -48,31 +87,35 @@ impl TableOfContent {
             .max_threads(search_threads)
             .build().unwrap());
 
-        let collections_path = Path::new(&storage_config.storage_path).join(&COLLECTIONS_DIR);
-
-        create_dir_all(&collections_path).unwrap();
-
-        let collection_paths = read_dir(&collections_path).unwrap();
+        let mut optimization_threads = storage_config.performance.max_optimization_threads;
+        if optimization_threads == 0 {
+            optimization_threads = max(1, num_cpus::get() / 2);
+        }
+        let optimization_pool = Arc::new(OptimizationPool::new(optimization_threads));
 
-        let mut collections: HashMap<String, Arc<Collection>> = Default::default();
+        let numa = Arc::new(NumaPlacement::new(
+            storage_config.performance.numa_policy,
+            storage_config.performance.max_search_threads,
+        ));
 
-        for entry in collection_paths {
-            let collection_path = entry.unwrap().path();
-            let collection_name = collection_path.file_name().unwrap().to_str().unwrap().to_string();
-            let wal_options = WalOptions {
-                segment_capacity: storage_config.wal.wal_capacity_mb * 1024 * 1024,
-                segment_queue_len: storage_config.wal.wal_segments_ahead,
-            };
+        let mut blocking_threads = storage_config.performance.max_blocking_threads;
+        if blocking_threads == 0 {
+            // Operations mostly wait for search threads, so there are more of them
+            blocking_threads = search_threads * 2;
+        }
+        let blocking_pool = Arc::new(BlockingPool::new(blocking_threads));
+        let update_admission = Arc::new(UpdateAdmission::new(
+            storage_config.performance.max_pending_updates,
+            storage_config.performance.update_overload_policy,
+        ));
+        let search_cache = match storage_config.performance.search_cache_size {
+            0 => None,
+            size => Some(Arc::new(SearchCache::new(size))),
+        };
 
-            let collection = load_collection(
-                collection_path.as_path(),
-                &wal_options,
-                search_runtime.clone(),
-                &storage_config.optimizers,
-            );
+        let collections_path = Path::new(&storage_config.storage_path).join(&COLLECTIONS_DIR);
 
-            collections.insert(collection_name, Arc::new(collection));
-        };
+        create_dir_all(&collections_path).unwrap();
 
         let alias_path = Path::new(&storage_config.storage_path)
             .join("aliases.sled");
@@ -82,11 +125,148 @@ impl TableOfContent {
             .open()
             .unwrap();
 
+        let cold_storage = storage_config.cold_storage.as_ref()
+            .map(|config| Arc::new(ColdStorage::new(config).expect("Can't initialize cold storage")));
+
         TableOfContent {
-            collections: Arc::new(RwLock::new(collections)),
+            collections: Arc::new(RwLock::new(Default::default())),
             storage_config: storage_config.clone(),
             search_runtime,
+            search_threads,
+            optimization_pool,
+            numa,
+            blocking_pool,
+            update_admission,
+            search_cache,
             alias_persistence,
+            cold_storage,
+            loaded: AtomicBool::new(false),
+        }
+    }
+
+    /// Load collections from disk and replay their WAL. Each collection becomes available as soon as it is loaded
+    pub fn load_collections(&self) {
+        let collections_path = Path::new(&self.storage_config.storage_path).join(&COLLECTIONS_DIR);
+
+        for entry in read_dir(&collections_path).unwrap() {
+            let collection_path = entry.unwrap().path();
+            let collection_name = collection_path.file_name().unwrap().to_str().unwrap().to_string();
+
+            let timer = Instant::now();
+            let collection = load_collection(
+                collection_path.as_path(),
+                &self.wal_options(),
+                self.search_runtime.clone(),
+                self.optimization_pool.clone(),
+                self.numa.clone(),
+                &self.storage_config.optimizers,
+                self.cold_storage.as_ref(),
+            );
+            info!(collection = %collection_name, duration_ms = timer.elapsed().as_millis() as u64, "Loaded collection");
+            self.warm_up_loaded(&collection_name, &collection);
+            self.start_recording(&collection_name, &collection);
+
+            self.collections.write().insert(collection_name, Arc::new(collection));
+        };
+
+        self.loaded.store(true, Ordering::SeqCst);
+    }
+
+    /// Read vectors of the just loaded collection into the page cache, if enabled in the config.
+    /// Failed warm-up only makes first searches slower, so the collection is served anyway
+    fn warm_up_loaded(&self, collection_name: &str, collection: &Collection) {
+        if !self.storage_config.performance.warm_up_on_load {
+            return;
+        }
+        match collection.warm_up() {
+            Ok(loaded) => info!(collection = collection_name, loaded_mb = loaded / 1024 / 1024, "Warmed up collection"),
+            Err(err) => warn!(collection = collection_name, error = %err, "Can't warm up collection"),
+        }
+    }
+
+    /// Record operations of the just loaded or created collection, if enabled in the config.
+    /// Recording is only used for debugging, so the collection is served anyway if it can't be started
+    fn start_recording(&self, collection_name: &str, collection: &Collection) {
+        let record_path = match &self.storage_config.record_path {
+            Some(record_path) => Path::new(record_path),
+            None => return,
+        };
+        let result = create_dir_all(record_path)
+            .map_err(|err| CollectionError::ServiceError { error: err.to_string() })
+            .and_then(|_| collection.start_recording(&record_path.join(format!("{}.jsonl", collection_name))));
+        match result {
+            Ok(()) => info!(collection = collection_name, path = %record_path.display(), "Recording operations of collection"),
+            Err(err) => warn!(collection = collection_name, error = %err, "Can't record operations of collection"),
+        }
+    }
+
+    /// Service is ready to serve requests, once all collections are loaded
+    pub fn is_ready(&self) -> bool {
+        self.loaded.load(Ordering::SeqCst)
+    }
+
+    /// Service is degraded, if the disk of the storage is full or some of collections are degraded
+    pub fn health(&self) -> Result<ServiceHealth, StorageError> {
+        let free_disk_space = available_space(&self.storage_config.storage_path)?;
+
+        let collections: Vec<(String, Arc<Collection>)> = self.collections.read().iter()
+            .map(|(name, collection)| (name.clone(), collection.clone()))
+            .collect();
+        let mut collections_health = BTreeMap::new();
+        for (name, collection) in collections {
+            collections_health.insert(name, collection.health()?);
+        }
+
+        let is_degraded = free_disk_space < MIN_FREE_DISK_SPACE
+            || collections_health.values().any(|health| health.status == HealthStatus::Degraded);
+
+        Ok(ServiceHealth {
+            status: if is_degraded { HealthStatus::Degraded } else { HealthStatus::Ok },
+            ready: self.is_ready(),
+            free_disk_space,
+            collections: collections_health,
+        })
+    }
+
+    /// Resource usage of the node with state of shards and pending optimizations of each collection
+    pub fn telemetry(&self) -> Result<NodeTelemetry, StorageError> {
+        let free_disk_space = available_space(&self.storage_config.storage_path)?;
+
+        let collections: Vec<(String, Arc<Collection>)> = self.collections.read().iter()
+            .map(|(name, collection)| (name.clone(), collection.clone()))
+            .collect();
+        let mut collections_telemetry = BTreeMap::new();
+        for (name, collection) in collections {
+            collections_telemetry.insert(name, collection.telemetry()?);
+        }
+
+        let shards = || collections_telemetry.values().flat_map(|collection| collection.shards.iter());
+        let mut segments_memory_usage = SegmentMemoryUsage::default();
+        for shard in shards() {
+            segments_memory_usage.merge(&shard.memory_usage);
+        }
+        Ok(NodeTelemetry {
+            memory_usage: process_memory_usage(),
+            free_disk_space,
+            disk_data_size: shards().map(|shard| shard.disk_data_size).sum(),
+            ram_data_size: shards().map(|shard| shard.ram_data_size).sum(),
+            segments_memory_usage,
+            segments_count: shards().map(|shard| shard.segments_count).sum(),
+            optimizations_count: collections_telemetry.values().map(|collection| collection.optimizations.len()).sum(),
+            pending_updates: shards().map(|shard| shard.pending_updates).sum(),
+            admitted_updates: self.update_admission.pending(),
+            search_cache: self.search_cache.as_ref().map(|cache| cache.telemetry()),
+            search_threads: self.search_threads,
+            kernels: kernel_telemetry(),
+            optimization_pool: self.optimization_pool.telemetry(),
+            collections: collections_telemetry,
+        })
+    }
+
+    fn wal_options(&self) -> WalOptions {
+        WalOptions {
+            segment_capacity: self.storage_config.wal.wal_capacity_mb * 1024 * 1024,
+            segment_queue_len: self.storage_config.wal.wal_segments_ahead,
         }
     }
 
@@ -107,8 +287,8 @@ impl TableOfContent {
         Ok(path)
     }
 
-    fn validate_collection_not_exists(&self, collection_name: &str) -> Result<(), StorageError> {
-        if self.is_collection_exists(collection_name) {
+    fn validate_collection_not_exists(collections: &Collections, collection_name: &str) -> Result<(), StorageError> {
+        if collections.contains_key(collection_name) {
             return Err(StorageError::BadInput {
                 description: format!("Collection `{}` already exists!", collection_name)
             });
@@ -116,9 +296,8 @@ impl TableOfContent {
         Ok(())
     }
 
-
-    fn validate_collection_exists(&self, collection_name: &str) -> Result<(), StorageError> {
-        if !self.is_collection_exists(collection_name) {
+    fn validate_collection_exists(collections: &Collections, collection_name: &str) -> Result<(), StorageError> {
+        if !collections.contains_key(collection_name) {
             return Err(StorageError::BadInput {
                 description: format!("Collection `{}` doesn't exist!", collection_name)
             });
@@ -126,8 +305,16 @@ impl TableOfContent {
         Ok(())
     }
 
+    fn validate_alias_not_exists(&self, alias_name: &str) -> Result<(), StorageError> {
+        if self.alias_persistence.contains_key(alias_name.as_bytes())? {
+            return Err(StorageError::BadInput {
+                description: format!("Alias `{}` already exists!", alias_name)
+            });
+        }
+        Ok(())
+    }
 
-    fn resolve_name(&self, collection_name: &str) -> Result<String, StorageError> {
+    fn resolve_name(&self, collections: &Collections, collection_name: &str) -> Result<String, StorageError> {
         let alias_collection_name = self.alias_persistence
             .get(collection_name.as_bytes())?;
 
@@ -137,7 +324,7 @@ impl TableOfContent {
                 from_utf8(&resolved_alias).unwrap().to_string()
             }
         };
-        self.validate_collection_exists(&resolved_name)?;
+        TableOfContent::validate_collection_exists(collections, &resolved_name)?;
         Ok(resolved_name)
     }
 
@@ -145,20 +332,69 @@ impl TableOfContent {
         self.collections.read().contains_key(collection_name)
     }
 
+    /// Apply all alias actions in a single transaction: either all of them are applied, or none.
+    /// Requires collections to be locked, so collections could not be created or removed meanwhile.
+    fn change_aliases(&self, collections: &Collections, actions: Vec<AliasOperations>) -> Result<(), StorageError> {
+        let abort = |err: StorageError| ConflictableTransactionError::Abort(err);
+
+        self.alias_persistence.transaction(|tx_db| {
+            for action in actions.iter() {
+                match action {
+                    AliasOperations::CreateAlias { collection_name, alias_name } => {
+                        TableOfContent::validate_collection_exists(collections, collection_name).map_err(abort)?;
+                        TableOfContent::validate_collection_not_exists(collections, alias_name).map_err(abort)?;
+
+                        tx_db.insert(alias_name.as_bytes(), collection_name.as_bytes())?;
+                    }
+                    AliasOperations::DeleteAlias { alias_name } => {
+                        tx_db.remove(alias_name.as_bytes())?;
+                    }
+                    AliasOperations::RenameAlias { old_alias_name, new_alias_name } => {
+                        TableOfContent::validate_collection_not_exists(collections, new_alias_name).map_err(abort)?;
+
+                        let collection = tx_db
+                            .remove(old_alias_name.as_bytes())?
+                            .ok_or_else(|| abort(StorageError::NotFound {
+                                description: format!("Alias {} does not exists!", old_alias_name)
+                            }))?;
+
+                        tx_db.insert(new_alias_name.as_bytes(), collection)?;
+                    }
+                };
+            }
+            Ok(())
+        })?;
+        self.alias_persistence.flush()?;
+        Ok(())
+    }
+
+    /// Remove all aliases, which point to the given collection
+    fn remove_collection_aliases(&self, collection_name: &str) -> Result<(), StorageError> {
+        for alias in self.collection_aliases(collection_name)? {
+            self.alias_persistence.remove(alias.as_bytes())?;
+        }
+        self.alias_persistence.flush()?;
+        Ok(())
+    }
+
     pub fn perform_collection_operation(&self, operation: StorageOperations) -> Result<bool, StorageError> {
         match operation {
             StorageOperations::CreateCollection {
                 name: collection_name,
                 vector_size,
                 distance,
-                index
+                index,
+                text_analyzers,
+                flush_policy,
+                shard_number,
+                replication_factor,
+                write_consistency_factor,
+                update_workers,
+                shard_key,
+                strict_mode,
             } => {
-                self.validate_collection_not_exists(&collection_name)?;
-
-                let wal_options = WalOptions {
-                    segment_capacity: self.storage_config.wal.wal_capacity_mb * 1024 * 1024,
-                    segment_queue_len: self.storage_config.wal.wal_segments_ahead,
-                };
+                TableOfContent::validate_collection_not_exists(&self.collections.read(), &collection_name)?;
+                self.validate_alias_not_exists(&collection_name)?;
 
                 let collection_path = self.create_collection_path(&collection_name)?;
 
@@ -169,23 +405,59 @@ impl TableOfContent {
                     payload_index: Some(Default::default()),
                     distance,
                     storage_type: Default::default(),
+                    text_analyzers: text_analyzers.unwrap_or_default(),
+                    flush_policy,
+                };
+
+                let collection_config = CollectionConfig {
+                    shard_number: shard_number.unwrap_or(1),
+                    replication_factor: replication_factor.unwrap_or(1),
+                    write_consistency_factor: write_consistency_factor.unwrap_or(1),
+                    update_workers: update_workers.unwrap_or(1),
+                    shard_key,
+                    strict_mode: strict_mode.unwrap_or_default(),
+                    ..CollectionConfig::new(segment_config)
                 };
 
                 let segment = build_collection(
                     Path::new(&collection_path),
-                    &wal_options,
-                    &segment_config,
+                    &self.wal_options(),
+                    &collection_config,
                     self.search_runtime.clone(),
+                    self.optimization_pool.clone(),
+                    self.numa.clone(),
                     &self.storage_config.optimizers,
                 )?;
+                self.start_recording(&collection_name, &segment);
 
                 let mut write_collections = self.collections.write();
                 write_collections.insert(collection_name, Arc::new(segment));
                 Ok(true)
             }
+            StorageOperations::UpdateCollection {
+                name,
+                index,
+                flush_policy,
+                storage_type,
+                optimizers_config,
+                write_consistency_factor,
+                strict_mode,
+            } => {
+                let collection = self.get_collection(&name)?;
+                collection.update_config(&CollectionConfigDiff {
+                    index,
+                    flush_policy,
+                    storage_type,
+                    optimizers_config,
+                    write_consistency_factor,
+                    strict_mode,
+                })?;
+                Ok(true)
+            }
             StorageOperations::DeleteCollection(collection_name) => {
                 let removed = self.collections.write().remove(&collection_name).is_some();
                 if removed {
+                    self.remove_collection_aliases(&collection_name)?;
                     let path = self.get_collection_path(&collection_name);
                     remove_dir_all(path).or_else(
                         |err| Err(StorageError::ServiceError {
@@ -195,36 +467,8 @@ impl TableOfContent {
                 Ok(removed)
             }
             StorageOperations::ChangeAliases { actions } => {
-                let _collection_lock = self.collections.write(); // Make alias change atomic
-                for action in actions {
-                    match action {
-                        AliasOperations::CreateAlias { collection_name, alias_name } => {
-                            self.validate_collection_exists(&collection_name)?;
-                            self.validate_collection_not_exists(&alias_name)?;
-
-                            self.alias_persistence.insert(alias_name.as_bytes(), collection_name.as_bytes())?;
-                        }
-                        AliasOperations::DeleteAlias { alias_name } => {
-                            self.alias_persistence.remove(alias_name.as_bytes())?;
-                        }
-                        AliasOperations::RenameAlias { old_alias_name, new_alias_name } => {
-                            if !self.alias_persistence.contains_key(old_alias_name.as_bytes())? {
-                                return Err(StorageError::NotFound { description: format!("Alias {} does not exists!", old_alias_name) });
-                            }
-
-                            let transaction_res = self.alias_persistence.transaction(|tx_db| {
-                                let collection = tx_db
-                                    .remove(old_alias_name.as_bytes())?
-                                    .ok_or(UnabortableTransactionError::Conflict)?;
-
-                                tx_db.insert(new_alias_name.as_bytes(), collection)?;
-                                Ok(())
-                            });
-                            transaction_res?;
-                        }
-                    };
-                }
-                self.alias_persistence.flush()?;
+                let collections = self.collections.write(); // Make alias change atomic
+                self.change_aliases(&collections, actions)?;
                 Ok(true)
             }
         }
@@ -232,26 +476,207 @@ impl TableOfContent {
 
     pub fn get_collection(&self, collection_name: &str) -> Result<Arc<Collection>, StorageError> {
         let read_collection = self.collections.read();
-        let real_collection_name = self.resolve_name(collection_name)?;
+        let real_collection_name = self.resolve_name(&read_collection, collection_name)?;
         Ok(read_collection.get(&real_collection_name).unwrap().clone())
     }
 
+    /// Async interface of the collection, which performs operations on the blocking pool of the service
+    pub fn get_async_collection(&self, collection_name: &str) -> Result<AsyncCollection, StorageError> {
+        let collection = self.get_collection(collection_name)?;
+        let async_collection = AsyncCollection::new(collection, self.blocking_pool.clone(), self.update_admission.clone());
+        Ok(match &self.search_cache {
+            Some(cache) => async_collection.with_search_cache(collection_name, cache.clone()),
+            None => async_collection,
+        })
+    }
+
+    fn get_snapshots_path(&self, collection_name: &str) -> PathBuf {
+        Path::new(&self.storage_config.snapshots_path).join(collection_name)
+    }
+
+    /// Save a copy of the collection into a new snapshot archive, see `Collection::create_snapshot`.
+    /// Snapshots are named after the collection and the creation time.
+    pub fn create_snapshot(&self, collection_name: &str) -> Result<SnapshotDescription, StorageError> {
+        let (real_name, collection) = {
+            let collections = self.collections.read();
+            let real_name = self.resolve_name(&collections, collection_name)?;
+            let collection = collections.get(&real_name).unwrap().clone();
+            (real_name, collection)
+        };
+
+        let snapshots_path = self.get_snapshots_path(&real_name);
+        create_dir_all(&snapshots_path)
+            .or_else(|err| Err(StorageError::ServiceError {
+                description: format!("Can't create directory for snapshots of {}. Error: {}", real_name, err)
+            }))?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let snapshot_path = snapshots_path.join(format!("{}-{}.{}", real_name, timestamp, SNAPSHOT_EXTENSION));
+        collection.create_snapshot(&snapshot_path, &StopCondition::default())?;
+        describe_snapshot(&snapshot_path)
+    }
+
+    pub fn list_snapshots(&self, collection_name: &str) -> Result<Vec<SnapshotDescription>, StorageError> {
+        let real_name = self.resolve_name(&self.collections.read(), collection_name)?;
+        list_snapshots(&self.get_snapshots_path(&real_name))
+    }
+
+    /// Path of an existing snapshot of the collection
+    pub fn get_snapshot_path(&self, collection_name: &str, snapshot_name: &str) -> Result<PathBuf, StorageError> {
+        let real_name = self.resolve_name(&self.collections.read(), collection_name)?;
+        let snapshot_path = self.get_snapshots_path(&real_name).join(snapshot_name);
+        // Name should not point outside of the snapshots directory
+        let is_file_name = Path::new(snapshot_name).file_name().map_or(false, |name| name == snapshot_name);
+        if !is_file_name || !snapshot_path.is_file() {
+            return Err(StorageError::NotFound {
+                description: format!("Snapshot `{}` of collection `{}` doesn't exist!", snapshot_name, collection_name)
+            });
+        }
+        Ok(snapshot_path)
+    }
+
+    /// Path for a snapshot of the collection, received from an external source.
+    /// It is not listed among snapshots of the collection and should be removed after recovery.
+    pub fn download_snapshot_path(&self, collection_name: &str) -> Result<PathBuf, StorageError> {
+        let snapshots_path = self.get_snapshots_path(collection_name);
+        create_dir_all(&snapshots_path)
+            .or_else(|err| Err(StorageError::ServiceError {
+                description: format!("Can't create directory for snapshots of {}. Error: {}", collection_name, err)
+            }))?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        Ok(snapshots_path.join(format!(".download-{}", timestamp)))
+    }
+
+    /// Replace data of the collection with the snapshot, created by `create_snapshot`.
+    /// Collection is created if it does not exist yet. Existing collection is removed only after
+    /// the snapshot is unpacked successfully, and keeps its aliases.
+    pub fn recover_snapshot(&self, collection_name: &str, snapshot_path: &Path) -> Result<bool, StorageError> {
+        self.validate_alias_not_exists(collection_name)?;
+
+        let restore_path = Path::new(&self.storage_config.storage_path)
+            .join(format!("{}{}", RESTORE_DIR_PREFIX, collection_name));
+        if restore_path.exists() {
+            remove_dir_all(&restore_path)?;
+        }
+        if let Err(err) = restore_snapshot(snapshot_path, &restore_path) {
+            remove_dir_all(&restore_path).ok();
+            return Err(err.into());
+        }
+
+        let mut collections = self.collections.write();
+        let collection_path = self.get_collection_path(collection_name);
+        if collections.remove(collection_name).is_some() {
+            remove_dir_all(&collection_path).or_else(
+                |err| Err(StorageError::ServiceError {
+                    description: format!("Can't delete collection {}, error: {}", collection_name, err)
+                }))?;
+        }
+        rename(&restore_path, &collection_path)
+            .or_else(|err| Err(StorageError::ServiceError {
+                description: format!("Can't move restored collection {}, error: {}", collection_name, err)
+            }))?;
+
+        let collection = load_collection(
+            &collection_path,
+            &self.wal_options(),
+            self.search_runtime.clone(),
+            self.optimization_pool.clone(),
+            self.numa.clone(),
+            &self.storage_config.optimizers,
+            self.cold_storage.as_ref(),
+        );
+        self.warm_up_loaded(collection_name, &collection);
+        self.start_recording(collection_name, &collection);
+        collections.insert(collection_name.to_string(), Arc::new(collection));
+        Ok(true)
+    }
+
+    /// Path of the file in the import directory
+    fn get_import_file_path(&self, file_path: &str) -> Result<PathBuf, StorageError> {
+        let import_path = self.storage_config.import_path.as_ref()
+            .ok_or_else(|| StorageError::BadRequest {
+                description: format!("Bulk import is disabled, import_path is not configured")
+            })?;
+        // Path should not point outside of the import directory
+        let is_relative = Path::new(file_path).components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            return Err(StorageError::BadRequest {
+                description: format!("Import path `{}` should be relative to the import directory", file_path)
+            });
+        }
+        Ok(Path::new(import_path).join(file_path))
+    }
+
+    /// Insert points from numpy files of the import directory, see `Collection::import_npy`.
+    /// Returns number of imported points
+    pub fn import_npy(&self, collection_name: &str, request: &NpyImportRequest) -> Result<usize, StorageError> {
+        let vectors_path = self.get_import_file_path(&request.vectors_path)?;
+        let points_path = match &request.points_path {
+            None => None,
+            Some(path) => Some(self.get_import_file_path(path)?),
+        };
+        let collection = self.get_collection(collection_name)?;
+        Ok(collection.import_npy(&vectors_path, points_path.as_deref())?)
+    }
+
+    /// Upsert points from a parquet file of the import directory, see `Collection::import_parquet`.
+    /// Returns number of imported points
+    pub fn import_parquet(&self, collection_name: &str, request: &ParquetImportRequest) -> Result<usize, StorageError> {
+        let path = self.get_import_file_path(&request.path)?;
+        let collection = self.get_collection(collection_name)?;
+        Ok(collection.import_parquet(&path, &request.columns)?)
+    }
+
+    /// Upsert points from a CSV file of the import directory, see `Collection::import_csv`.
+    /// Returns number of imported points
+    pub fn import_csv(&self, collection_name: &str, request: &CsvImportRequest) -> Result<usize, StorageError> {
+        let path = self.get_import_file_path(&request.path)?;
+        let collection = self.get_collection(collection_name)?;
+        Ok(collection.import_csv(&path, &request.mapping)?)
+    }
+
+    /// Move optimized segments of the collection into the cold storage, see `Collection::offload_segments`.
+    /// Returns number of offloaded segments
+    pub fn offload_collection(&self, collection_name: &str) -> Result<usize, StorageError> {
+        let cold_storage = self.cold_storage.as_ref()
+            .ok_or_else(|| StorageError::BadRequest {
+                description: "Cold storage is not configured".to_string()
+            })?;
+        let collection = self.get_collection(collection_name)?;
+        Ok(collection.offload_segments(cold_storage)?)
+    }
+
+    /// Read vectors of the collection into the page cache, see `Collection::warm_up`.
+    /// Returns number of loaded bytes
+    pub fn warm_up_collection(&self, collection_name: &str) -> Result<usize, StorageError> {
+        let collection = self.get_collection(collection_name)?;
+        Ok(collection.warm_up()?)
+    }
+
     /// List of all collections
     pub fn all_collections(&self) -> Vec<String> {
         self.collections.read().keys().cloned().collect()
     }
 
-    /// List of all aliases for a given collection
-    pub fn collection_aliases(&self, collection_name: &str) -> Result<Vec<String>, StorageError> {
+    /// List of all aliases with names of collections they point to
+    pub fn all_aliases(&self) -> Result<Vec<(String, String)>, StorageError> {
         let mut result = vec![];
         for pair in self.alias_persistence.iter() {
             let (alias_bt, target_collection_bt) = pair?;
             let alias = from_utf8(&alias_bt).unwrap().to_string();
-            let target_collection = from_utf8(&target_collection_bt).unwrap();
-            if collection_name == target_collection {
-                result.push(alias);
-            }
+            let target_collection = from_utf8(&target_collection_bt).unwrap().to_string();
+            result.push((alias, target_collection));
         }
         Ok(result)
     }
-}
\ No newline at end of file
+
+    /// List of all aliases for a given collection
+    pub fn collection_aliases(&self, collection_name: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self.all_aliases()?
+            .into_iter()
+            .filter(|(_alias, target_collection)| target_collection == collection_name)
+            .map(|(alias, _target_collection)| alias)
+            .collect())
+    }
+}
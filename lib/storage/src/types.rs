@@ -1,11 +1,38 @@
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
 use collection::collection_builder::optimizers_builder::OptimizersConfig;
+use collection::cold_storage::ColdStorageConfig;
+use collection::config::OverloadPolicy;
+use collection::numa::NumaPolicy;
+use segment::common::config::{field_path, ConfigProblem, ValidateConfig};
 
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct PerformanceConfig {
     pub max_search_threads: usize,
+    /// Size of the pool, which performs optimizations and index building of all collections. If 0 - auto selection
+    #[serde(default)]
+    pub max_optimization_threads: usize,
+    /// Placement of segments and their search threads on NUMA nodes
+    #[serde(default)]
+    pub numa_policy: NumaPolicy,
+    /// Read vectors of collections into the page cache after they are loaded or restored from a snapshot
+    #[serde(default)]
+    pub warm_up_on_load: bool,
+    /// Number of threads, which perform operations of async API, e.g. of request handlers.
+    /// Search itself runs on search threads. If 0 - auto selection
+    #[serde(default)]
+    pub max_blocking_threads: usize,
+    /// Number of updates of all collections, which could be processed at a time. Further updates
+    /// wait or are rejected according to `update_overload_policy`. If 0 - no limit
+    #[serde(default)]
+    pub max_pending_updates: usize,
+    #[serde(default)]
+    pub update_overload_policy: OverloadPolicy,
+    /// Number of search results, which are cached for repeated requests until the collection is changed.
+    /// If 0 - cache is disabled
+    #[serde(default)]
+    pub search_cache_size: usize,
 }
 
 
@@ -19,8 +46,50 @@ pub struct WalConfig {
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct StorageConfig {
     pub storage_path: String,
+    /// Where to store snapshots of collections
+    #[serde(default = "default_snapshots_path")]
+    pub snapshots_path: String,
+    /// Directory, from which files of bulk imports are read. Import is disabled, if not set
+    #[serde(default)]
+    pub import_path: Option<String>,
+    /// Object storage, into which optimized segments of rarely used collections are offloaded.
+    /// Offloading is disabled, if not set
+    #[serde(default)]
+    pub cold_storage: Option<ColdStorageConfig>,
+    /// Directory, into which updates and searches of each collection are recorded for debugging,
+    /// see `OperationRecorder`. Recording is disabled, if not set
+    #[serde(default)]
+    pub record_path: Option<String>,
     pub optimizers: OptimizersConfig,
     pub wal: WalConfig,
     pub performance: PerformanceConfig,
 }
 
+
+impl ValidateConfig for WalConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if self.wal_capacity_mb == 0 {
+            problems.push(ConfigProblem::new(&field_path(path, "wal_capacity_mb"), "should be positive"));
+        }
+    }
+}
+
+impl ValidateConfig for StorageConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if self.storage_path.is_empty() {
+            problems.push(ConfigProblem::new(&field_path(path, "storage_path"), "should not be empty"));
+        }
+        if let Some(cold_storage) = &self.cold_storage {
+            if cold_storage.cache_size_mb == 0 {
+                problems.push(ConfigProblem::new(&field_path(path, "cold_storage.cache_size_mb"), "should be positive"));
+            }
+        }
+        self.optimizers.check(&field_path(path, "optimizers"), problems);
+        self.wal.check(&field_path(path, "wal"), problems);
+    }
+}
+
+
+fn default_snapshots_path() -> String {
+    "./snapshots".to_string()
+}
@@ -0,0 +1,135 @@
+use tempdir::TempDir;
+
+use collection::collection_builder::optimizers_builder::OptimizersConfig;
+use segment::types::Distance;
+use storage::content_manager::storage_ops::{AliasOperations, StorageOperations};
+use storage::content_manager::toc::TableOfContent;
+use storage::types::{PerformanceConfig, StorageConfig, WalConfig};
+
+fn storage_config(path: &str) -> StorageConfig {
+    StorageConfig {
+        storage_path: path.to_string(),
+        snapshots_path: format!("{}/snapshots", path),
+        import_path: None,
+        cold_storage: None,
+        record_path: None,
+        optimizers: OptimizersConfig {
+            deleted_threshold: 0.9,
+            vacuum_min_vector_number: 1000,
+            max_segment_number: 10,
+            memmap_threshold: 100_000,
+            indexing_threshold: 50_000,
+            payload_indexing_threshold: 20_000,
+            flush_interval_sec: 30,
+            max_segment_size: None,
+            max_optimization_threads: 1,
+            defragmentation_key: None,
+        },
+        wal: WalConfig {
+            wal_capacity_mb: 1,
+            wal_segments_ahead: 0,
+        },
+        performance: PerformanceConfig {
+            max_search_threads: 1,
+            max_optimization_threads: 1,
+            numa_policy: Default::default(),
+            warm_up_on_load: false,
+            max_blocking_threads: 0,
+            max_pending_updates: 0,
+            update_overload_policy: Default::default(),
+            search_cache_size: 0,
+        },
+    }
+}
+
+fn create_collection(toc: &TableOfContent, name: &str, vector_size: usize) {
+    toc.perform_collection_operation(StorageOperations::CreateCollection {
+        name: name.to_string(),
+        vector_size,
+        distance: Distance::Dot,
+        index: None,
+        text_analyzers: None,
+        flush_policy: None,
+        shard_number: None,
+        replication_factor: None,
+        write_consistency_factor: None,
+        update_workers: None,
+        shard_key: None,
+        strict_mode: None,
+    }).unwrap();
+}
+
+fn create_alias(collection_name: &str, alias_name: &str) -> AliasOperations {
+    AliasOperations::CreateAlias {
+        collection_name: collection_name.to_string(),
+        alias_name: alias_name.to_string(),
+    }
+}
+
+#[test]
+fn test_switch_alias() {
+    let dir = TempDir::new("storage").unwrap();
+    let toc = TableOfContent::new(&storage_config(dir.path().to_str().unwrap()));
+
+    create_collection(&toc, "products_v1", 2);
+    create_collection(&toc, "products_v2", 3);
+
+    toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![create_alias("products_v1", "products")]
+    }).unwrap();
+    assert_eq!(toc.get_collection("products").unwrap().info().unwrap().config.params.vector_size, 2);
+
+    toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![
+            AliasOperations::DeleteAlias { alias_name: "products".to_string() },
+            create_alias("products_v2", "products"),
+        ]
+    }).unwrap();
+    assert_eq!(toc.get_collection("products").unwrap().info().unwrap().config.params.vector_size, 3);
+    assert_eq!(toc.collection_aliases("products_v1").unwrap().len(), 0);
+    assert_eq!(toc.collection_aliases("products_v2").unwrap(), vec!["products".to_string()]);
+
+    // Alias could not shadow a collection and vice versa
+    assert!(toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![create_alias("products_v2", "products_v1")]
+    }).is_err());
+    assert!(toc.perform_collection_operation(StorageOperations::CreateCollection {
+        name: "products".to_string(),
+        vector_size: 2,
+        distance: Distance::Dot,
+        index: None,
+        text_analyzers: None,
+        flush_policy: None,
+        shard_number: None,
+        replication_factor: None,
+        write_consistency_factor: None,
+        update_workers: None,
+        shard_key: None,
+        strict_mode: None,
+    }).is_err());
+
+    toc.perform_collection_operation(StorageOperations::DeleteCollection("products_v2".to_string())).unwrap();
+    assert!(toc.get_collection("products").is_err());
+    assert!(toc.all_aliases().unwrap().is_empty());
+}
+
+#[test]
+fn test_failed_alias_changes_are_not_applied() {
+    let dir = TempDir::new("storage").unwrap();
+    let toc = TableOfContent::new(&storage_config(dir.path().to_str().unwrap()));
+
+    create_collection(&toc, "products_v1", 2);
+    toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![create_alias("products_v1", "products")]
+    }).unwrap();
+
+    let result = toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![
+            AliasOperations::DeleteAlias { alias_name: "products".to_string() },
+            create_alias("products_v2", "products"),
+        ]
+    });
+    assert!(result.is_err());
+    assert!(toc.get_collection("products").is_ok());
+    assert_eq!(toc.all_aliases().unwrap(), vec![("products".to_string(), "products_v1".to_string())]);
+}
@@ -0,0 +1,120 @@
+use tempdir::TempDir;
+
+use collection::collection_builder::optimizers_builder::OptimizersConfig;
+use collection::operations::types::HealthStatus;
+use collection::shard::replica_set::ShardStatus;
+use segment::types::Distance;
+use storage::content_manager::storage_ops::StorageOperations;
+use storage::content_manager::toc::TableOfContent;
+use storage::types::{PerformanceConfig, StorageConfig, WalConfig};
+
+fn storage_config(path: &str) -> StorageConfig {
+    StorageConfig {
+        storage_path: path.to_string(),
+        snapshots_path: format!("{}/snapshots", path),
+        import_path: None,
+        cold_storage: None,
+        record_path: None,
+        optimizers: OptimizersConfig {
+            deleted_threshold: 0.9,
+            vacuum_min_vector_number: 1000,
+            max_segment_number: 10,
+            memmap_threshold: 100_000,
+            indexing_threshold: 50_000,
+            payload_indexing_threshold: 20_000,
+            flush_interval_sec: 30,
+            max_segment_size: None,
+            max_optimization_threads: 1,
+            defragmentation_key: None,
+        },
+        wal: WalConfig {
+            wal_capacity_mb: 1,
+            wal_segments_ahead: 0,
+        },
+        performance: PerformanceConfig {
+            max_search_threads: 1,
+            max_optimization_threads: 1,
+            numa_policy: Default::default(),
+            warm_up_on_load: false,
+            max_blocking_threads: 0,
+            max_pending_updates: 0,
+            update_overload_policy: Default::default(),
+            search_cache_size: 0,
+        },
+    }
+}
+
+#[test]
+fn test_readiness_and_health() {
+    let dir = TempDir::new("storage").unwrap();
+    let config = storage_config(dir.path().to_str().unwrap());
+
+    {
+        let toc = TableOfContent::create(&config);
+        assert!(!toc.is_ready());
+        toc.load_collections();
+        assert!(toc.is_ready());
+
+        toc.perform_collection_operation(StorageOperations::CreateCollection {
+            name: "test".to_string(),
+            vector_size: 4,
+            distance: Distance::Dot,
+            index: None,
+            text_analyzers: None,
+            flush_policy: None,
+            shard_number: None,
+            replication_factor: None,
+            write_consistency_factor: None,
+            update_workers: None,
+            shard_key: None,
+            strict_mode: None,
+        }).unwrap();
+    }
+
+    let toc = TableOfContent::create(&config);
+    let health = toc.health().unwrap();
+    assert!(!health.ready);
+    assert!(health.collections.is_empty());
+
+    toc.load_collections();
+
+    let health = toc.health().unwrap();
+    assert!(health.ready);
+    let collection_health = health.collections.get("test").unwrap();
+    assert_eq!(collection_health.status, HealthStatus::Ok);
+    assert!(collection_health.issues.is_empty());
+}
+
+#[test]
+fn test_telemetry() {
+    let dir = TempDir::new("storage").unwrap();
+    let config = storage_config(dir.path().to_str().unwrap());
+
+    let toc = TableOfContent::create(&config);
+    toc.load_collections();
+    toc.perform_collection_operation(StorageOperations::CreateCollection {
+        name: "test".to_string(),
+        vector_size: 4,
+        distance: Distance::Dot,
+        index: None,
+        text_analyzers: None,
+        flush_policy: None,
+        shard_number: Some(2),
+        replication_factor: Some(2),
+        write_consistency_factor: None,
+        update_workers: None,
+        shard_key: None,
+        strict_mode: None,
+    }).unwrap();
+
+    let telemetry = toc.telemetry().unwrap();
+    let collection_telemetry = telemetry.collections.get("test").unwrap();
+    assert_eq!(collection_telemetry.shards.len(), 2);
+    for shard in collection_telemetry.shards.iter() {
+        assert_eq!(shard.status, ShardStatus::Active);
+        assert_eq!(shard.replicas.len(), 2);
+    }
+    assert_eq!(telemetry.segments_count, collection_telemetry.shards.iter().map(|shard| shard.segments_count).sum::<usize>());
+    assert_eq!(telemetry.segments_memory_usage.total(), telemetry.ram_data_size);
+    assert!(telemetry.free_disk_space > 0);
+}
@@ -0,0 +1,129 @@
+use tempdir::TempDir;
+
+use collection::collection_builder::optimizers_builder::OptimizersConfig;
+use collection::operations::CollectionUpdateOperations;
+use collection::operations::point_ops::{PointInsertOperations, PointOperations};
+use collection::operations::types::{CountRequest, ReadConsistency};
+use segment::types::Distance;
+use segment::common::stop_condition::StopCondition;
+use std::sync::Arc;
+use storage::content_manager::storage_ops::{AliasOperations, StorageOperations};
+use storage::content_manager::toc::TableOfContent;
+use storage::types::{PerformanceConfig, StorageConfig, WalConfig};
+
+fn storage_config(path: &str) -> StorageConfig {
+    StorageConfig {
+        storage_path: path.to_string(),
+        snapshots_path: format!("{}/snapshots", path),
+        import_path: None,
+        cold_storage: None,
+        record_path: None,
+        optimizers: OptimizersConfig {
+            deleted_threshold: 0.9,
+            vacuum_min_vector_number: 1000,
+            max_segment_number: 10,
+            memmap_threshold: 100_000,
+            indexing_threshold: 50_000,
+            payload_indexing_threshold: 20_000,
+            flush_interval_sec: 30,
+            max_segment_size: None,
+            max_optimization_threads: 1,
+            defragmentation_key: None,
+        },
+        wal: WalConfig {
+            wal_capacity_mb: 1,
+            wal_segments_ahead: 0,
+        },
+        performance: PerformanceConfig {
+            max_search_threads: 1,
+            max_optimization_threads: 1,
+            numa_policy: Default::default(),
+            warm_up_on_load: false,
+            max_blocking_threads: 0,
+            max_pending_updates: 0,
+            update_overload_policy: Default::default(),
+            search_cache_size: 0,
+        },
+    }
+}
+
+fn create_collection(toc: &TableOfContent, name: &str) {
+    toc.perform_collection_operation(StorageOperations::CreateCollection {
+        name: name.to_string(),
+        vector_size: 2,
+        distance: Distance::Dot,
+        index: None,
+        text_analyzers: None,
+        flush_policy: None,
+        shard_number: Some(2),
+        replication_factor: None,
+        write_consistency_factor: None,
+        update_workers: None,
+        shard_key: None,
+        strict_mode: None,
+    }).unwrap();
+}
+
+fn upsert_points(toc: &TableOfContent, collection_name: &str, ids: Vec<u64>) {
+    toc.get_collection(collection_name).unwrap().update(CollectionUpdateOperations::PointOperation(
+        PointOperations::UpsertPoints(PointInsertOperations::BatchPoints {
+            vectors: ids.iter().map(|id| vec![*id as f32, 1.0]).collect(),
+            ids: ids.into_iter().map(|x| x.into()).collect(),
+            payloads: None,
+        })
+    ), true).unwrap();
+}
+
+fn count_points(toc: &TableOfContent, collection_name: &str) -> usize {
+    toc.get_collection(collection_name).unwrap()
+        .count(Arc::new(CountRequest { filter: None, exact: true }), ReadConsistency::Any, &StopCondition::default())
+        .unwrap()
+        .count
+}
+
+#[test]
+fn test_snapshot_recovery() {
+    let dir = TempDir::new("storage").unwrap();
+    let toc = TableOfContent::new(&storage_config(dir.path().to_str().unwrap()));
+
+    create_collection(&toc, "products");
+    upsert_points(&toc, "products", vec![1, 2, 3]);
+    toc.perform_collection_operation(StorageOperations::ChangeAliases {
+        actions: vec![AliasOperations::CreateAlias {
+            collection_name: "products".to_string(),
+            alias_name: "products_alias".to_string(),
+        }],
+    }).unwrap();
+
+    // Snapshot is stored under the name of the collection, not the alias
+    let snapshot = toc.create_snapshot("products_alias").unwrap();
+    assert!(snapshot.name.starts_with("products-"));
+    assert!(snapshot.size > 0);
+    assert_eq!(toc.list_snapshots("products").unwrap(), vec![snapshot.clone()]);
+
+    let snapshot_path = toc.get_snapshot_path("products", &snapshot.name).unwrap();
+    assert!(toc.get_snapshot_path("products", "../products").is_err());
+    assert!(toc.get_snapshot_path("products", "missing.snapshot").is_err());
+
+    // Snapshot could be restored as a new collection
+    toc.recover_snapshot("products_copy", &snapshot_path).unwrap();
+    assert_eq!(count_points(&toc, "products_copy"), 3);
+
+    // Or replace the existing collection, which keeps its aliases
+    upsert_points(&toc, "products", vec![4, 5]);
+    assert_eq!(count_points(&toc, "products"), 5);
+    toc.recover_snapshot("products", &snapshot_path).unwrap();
+    assert_eq!(count_points(&toc, "products_alias"), 3);
+
+    assert!(toc.recover_snapshot("products_alias", &snapshot_path).is_err());
+    assert!(toc.recover_snapshot("broken", &dir.path().join("missing.snapshot")).is_err());
+    assert!(!toc.is_collection_exists("broken"));
+    drop(toc);
+
+    // Restored collections are loaded after restart
+    let toc = TableOfContent::new(&storage_config(dir.path().to_str().unwrap()));
+    let mut collections = toc.all_collections();
+    collections.sort();
+    assert_eq!(collections, vec!["products".to_string(), "products_copy".to_string()]);
+    assert_eq!(count_points(&toc, "products_copy"), 3);
+}
@@ -4,7 +4,8 @@ use itertools::Itertools;
 use crate::common::helpers::process_response;
 use actix_web::rt::time::Instant;
 use storage::content_manager::storage_ops::StorageOperations;
-use crate::api::models::{CollectionDescription, CollectionsResponse};
+use storage::content_manager::errors::StorageError;
+use crate::api::models::{CollectionDescription, CollectionsResponse, AliasDescription, CollectionsAliasesResponse};
 
 #[get("/collections")]
 pub async fn get_collections(
@@ -40,6 +41,96 @@ pub async fn get_collection(
     process_response(response, timing)
 }
 
+#[get("/collections/{name}/optimizations")]
+pub async fn get_collection_optimizations(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.get_collection(&name)
+            .and_then(|collection| collection.optimizations().map_err(|x| x.into()))
+    };
+
+    process_response(response, timing)
+}
+
+/// Move optimized segments of the collection into the cold storage.
+/// Responds with number of offloaded segments
+#[post("/collections/{name}/offload")]
+pub async fn offload_collection(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.offload_collection(&name)
+    };
+
+    process_response(response, timing)
+}
+
+/// Read vectors of the collection into the page cache.
+/// Responds with number of loaded bytes
+#[post("/collections/{name}/warm_up")]
+pub async fn warm_up_collection(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.warm_up_collection(&name)
+    };
+
+    process_response(response, timing)
+}
+
+#[get("/aliases")]
+pub async fn get_aliases(
+    toc: web::Data<TableOfContent>
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.all_aliases()
+            .map(|aliases| {
+                let aliases = aliases
+                    .into_iter()
+                    .map(|(alias_name, collection_name)| AliasDescription { alias_name, collection_name })
+                    .collect_vec();
+                CollectionsAliasesResponse { aliases }
+            })
+    };
+
+    process_response(response, timing)
+}
+
+#[get("/collections/{name}/aliases")]
+pub async fn get_collection_aliases(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = if toc.is_collection_exists(&name) {
+        toc.collection_aliases(&name)
+            .map(|aliases| {
+                let aliases = aliases
+                    .into_iter()
+                    .map(|alias_name| AliasDescription { alias_name, collection_name: name.clone() })
+                    .collect_vec();
+                CollectionsAliasesResponse { aliases }
+            })
+    } else {
+        Err(StorageError::NotFound { description: format!("Collection `{}` doesn't exist!", name) })
+    };
+
+    process_response(response, timing)
+}
+
 #[post("/collections")]
 pub async fn update_collections(
     toc: web::Data<TableOfContent>,
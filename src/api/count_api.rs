@@ -0,0 +1,40 @@
+use actix_web::{post, web, Responder};
+use storage::content_manager::toc::TableOfContent;
+use crate::common::helpers::process_response;
+use actix_web::rt::time::Instant;
+use std::sync::Arc;
+use collection::operations::types::CountRequest;
+use actix_web::web::Query;
+use crate::api::models::ReadParams;
+use crate::common::slow_log::{SlowLog, SlowLogRecord};
+
+#[post("/collections/{name}/points/count")]
+pub async fn count_points(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<CountRequest>,
+    params: Query<ReadParams>,
+    slow_log: web::Data<SlowLog>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let request = Arc::new(request.0);
+    let response = match toc.get_async_collection(&name) {
+        Ok(collection) => {
+            let result = collection
+                .count(request.clone(), params.consistency.unwrap_or_default(), params.stop_condition())
+                .await
+                .map_err(|err| err.into());
+            slow_log.observe(collection.collection(), &name, SlowLogRecord {
+                operation: "count",
+                filter: request.filter.as_ref(),
+                params: None,
+                limit: None,
+            }, timing);
+            result
+        }
+        Err(err) => Err(err),
+    };
+
+    process_response(response, timing)
+}
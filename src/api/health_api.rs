@@ -0,0 +1,58 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use actix_web::rt::time::Instant;
+use storage::content_manager::errors::StorageError;
+use storage::content_manager::toc::TableOfContent;
+
+use crate::common::helpers::process_response;
+use crate::common::kafka::KafkaConsumers;
+
+/// Service is alive as long as it responds
+#[get("/livez")]
+pub async fn livez() -> impl Responder {
+    HttpResponse::Ok().body("alive")
+}
+
+/// Service is ready, once all collections are loaded and their WAL is replayed
+#[get("/readyz")]
+pub async fn readyz(toc: web::Data<TableOfContent>) -> impl Responder {
+    if toc.is_ready() {
+        HttpResponse::Ok().body("ready")
+    } else {
+        HttpResponse::ServiceUnavailable().body("collections are loading")
+    }
+}
+
+#[get("/health")]
+pub async fn health(toc: web::Data<TableOfContent>) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.health()
+    };
+
+    process_response(response, timing)
+}
+
+/// Resource usage of the node, state of shards and pending optimizations, e.g. for capacity planning
+#[get("/telemetry")]
+pub async fn telemetry(toc: web::Data<TableOfContent>) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.telemetry()
+    };
+
+    process_response(response, timing)
+}
+
+/// Offsets and lag of consumers of Kafka topics by partitions
+#[get("/telemetry/kafka")]
+pub async fn kafka_telemetry(consumers: web::Data<KafkaConsumers>) -> impl Responder {
+    let timing = Instant::now();
+
+    let response: Result<_, StorageError> = {
+        Ok(consumers.telemetry())
+    };
+
+    process_response(response, timing)
+}
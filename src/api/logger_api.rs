@@ -0,0 +1,37 @@
+use actix_web::{Responder, get, put, web};
+use actix_web::rt::time::Instant;
+use storage::content_manager::errors::StorageError;
+
+use crate::api::models::LoggerConfig;
+use crate::common::helpers::process_response;
+use crate::common::logger::LogFilter;
+
+/// Current filter of log events and exported spans
+#[get("/logger")]
+pub async fn get_logger(log_filter: web::Data<LogFilter>) -> impl Responder {
+    let timing = Instant::now();
+
+    let response: Result<_, StorageError> = {
+        Ok(LoggerConfig { filter: log_filter.directives() })
+    };
+
+    process_response(response, timing)
+}
+
+/// Change filter of log events without restart, e.g. to enable debug events of a single subsystem.
+/// Filter is not persisted, the configured one is used after restart
+#[put("/logger")]
+pub async fn update_logger(
+    log_filter: web::Data<LogFilter>,
+    config: web::Json<LoggerConfig>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        log_filter.set(&config.filter)
+            .map(|()| true)
+            .map_err(|description| StorageError::BadInput { description })
+    };
+
+    process_response(response, timing)
+}
@@ -4,3 +4,8 @@ pub mod retrieve_api;
 pub mod update_api;
 pub mod search_api;
 pub mod recommend_api;
+pub mod scroll_api;
+pub mod count_api;
+pub mod snapshot_api;
+pub mod health_api;
+pub mod logger_api;
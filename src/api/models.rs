@@ -2,6 +2,10 @@ use serde;
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
 use std::fmt::Debug;
+use std::time::Duration;
+use storage::content_manager::snapshots::SnapshotDescription;
+use collection::operations::types::ReadConsistency;
+use segment::common::stop_condition::StopCondition;
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -14,3 +18,61 @@ pub struct CollectionDescription {
 pub struct CollectionsResponse {
     pub collections: Vec<CollectionDescription>
 }
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AliasDescription {
+    pub alias_name: String,
+    pub collection_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionsAliasesResponse {
+    pub aliases: Vec<AliasDescription>
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CreatedSnapshot {
+    #[serde(flatten)]
+    pub snapshot: SnapshotDescription,
+    /// URL of the snapshot in S3-compatible storage, if it was uploaded
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SnapshotRecover {
+    /// URL of the snapshot to recover the collection from: `http(s)://` URL,
+    /// e.g. of a snapshot uploaded to S3, or `file://` URL of a snapshot on the server
+    pub location: String,
+}
+
+/// Filter of log events in `RUST_LOG` format, e.g. `info,collection=debug`
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LoggerConfig {
+    pub filter: String,
+}
+
+/// Query parameters of read requests
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ReadParams {
+    /// Replicas of each shard, which should serve the request. Default: any
+    pub consistency: Option<ReadConsistency>,
+    /// Max duration of the request in seconds. Request is interrupted with an error, once it is exceeded.
+    /// Default: no limit
+    pub timeout: Option<u64>,
+}
+
+impl ReadParams {
+    /// Deadline of the request, which is checked by long-running reads
+    pub fn stop_condition(&self) -> StopCondition {
+        match self.timeout {
+            Some(timeout) => StopCondition::with_timeout(Duration::from_secs(timeout)),
+            None => StopCondition::default(),
+        }
+    }
+}
@@ -3,7 +3,8 @@ use storage::content_manager::toc::TableOfContent;
 use crate::common::helpers::process_response;
 use actix_web::rt::time::Instant;
 use std::sync::Arc;
-use collection::operations::types::RecommendRequest;
+use collection::operations::types::{RecommendRequest, DiscoverRequest};
+use crate::common::slow_log::{SlowLog, SlowLogRecord};
 
 
 #[post("/collections/{name}/points/recommend")]
@@ -11,6 +12,39 @@ pub async fn recommend_points(
     toc: web::Data<TableOfContent>,
     web::Path(name): web::Path<String>,
     request: web::Json<RecommendRequest>,
+    slow_log: web::Data<SlowLog>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let request = Arc::new(request.0);
+    let response = {
+        toc.get_collection(&name)
+            .and_then(|collection| {
+                let result = match &request.lookup_from {
+                    None => collection.recommend(request.clone()).map_err(|err| err.into()),
+                    Some(lookup_from) => toc.get_collection(lookup_from)
+                        .and_then(|lookup_collection| collection
+                            .recommend_with_lookup(request.clone(), &lookup_collection)
+                            .map_err(|err| err.into())),
+                };
+                slow_log.observe(&collection, &name, SlowLogRecord {
+                    operation: "recommend",
+                    filter: request.filter.as_ref(),
+                    params: request.params.as_ref(),
+                    limit: Some(request.top),
+                }, timing);
+                result
+            })
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/points/discover")]
+pub async fn discover_points(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<DiscoverRequest>,
 ) -> impl Responder {
     let timing = Instant::now();
 
@@ -18,7 +52,7 @@ pub async fn recommend_points(
         toc.get_collection(&name)
             .and_then(|collection| {
                 collection
-                    .recommend(Arc::new(request.0))
+                    .discover(Arc::new(request.0))
                     .map_err(|err| err.into())
             })
     };
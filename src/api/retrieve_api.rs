@@ -2,39 +2,51 @@ use actix_web::{get, post, web, Responder};
 use storage::content_manager::toc::TableOfContent;
 use crate::common::helpers::process_response;
 use actix_web::rt::time::Instant;
-use segment::types::PointIdType;
+use segment::types::{PointIdType, WithPayload, WithPayloadInterface};
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
 use storage::content_manager::errors::StorageError;
+use actix_web::web::Query;
+use crate::api::models::ReadParams;
+
+fn default_with_vector() -> bool {
+    true
+}
 
 #[derive(Deserialize, Serialize, JsonSchema)]
 pub struct PointRequest {
-    pub ids: Vec<PointIdType>
+    pub ids: Vec<PointIdType>,
+    /// Payload of the points to return. Default: all payload
+    #[serde(default)]
+    pub with_payload: Option<WithPayloadInterface>,
+    /// Return vectors of the points. Default: true
+    #[serde(default = "default_with_vector")]
+    pub with_vector: bool,
 }
 
 #[get("/collections/{name}/points/{id}")]
 pub async fn get_point(
     toc: web::Data<TableOfContent>,
-    web::Path((name, point_id)): web::Path<(String, PointIdType)>,
+    web::Path((name, point_id)): web::Path<(String, String)>,
+    params: Query<ReadParams>,
 ) -> impl Responder {
     let timing = Instant::now();
 
-    let response = {
-        toc.get_collection(&name)
+    let response = point_id.parse::<PointIdType>()
+        .map_err(|_| StorageError::BadInput {
+            description: format!("Can not recognize \"{}\" as point id", point_id)
+        })
+        .and_then(|point_id| toc.get_collection(&name)
             .and_then(|collection| collection
-                .retrieve(&vec![point_id], true, true)
+                .retrieve(&vec![point_id], &WithPayload::from(true), true, params.consistency.unwrap_or_default())
                 .map_err(|err| err.into())
                 .map(|points| points.into_iter().next())
             )
-    };
-
-    let response = match response {
-        Ok(record) => match record {
-            None => Err(StorageError::NotFound { description: format!("Point with id {} does not exists!", point_id) }),
-            Some(record) => Ok(record)
-        },
-        Err(e) => Err(e)
-    };
+            .and_then(|record| match record {
+                None => Err(StorageError::NotFound { description: format!("Point with id {} does not exists!", point_id) }),
+                Some(record) => Ok(record)
+            })
+        );
 
     process_response(response, timing)
 }
@@ -45,13 +57,18 @@ pub async fn get_vectors(
     toc: web::Data<TableOfContent>,
     web::Path(name): web::Path<String>,
     request: web::Json<PointRequest>,
+    params: Query<ReadParams>,
 ) -> impl Responder {
     let timing = Instant::now();
 
+    let with_payload = request.with_payload.as_ref()
+        .map(WithPayload::from)
+        .unwrap_or(WithPayload::from(true));
+
     let response = {
         toc.get_collection(&name)
             .and_then(|collection| collection
-                .retrieve(&request.ids, true, true)
+                .retrieve(&request.ids, &with_payload, request.with_vector, params.consistency.unwrap_or_default())
                 .map_err(|err| err.into())
             )
     };
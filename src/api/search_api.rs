@@ -3,20 +3,124 @@ use storage::content_manager::toc::TableOfContent;
 use crate::common::helpers::process_response;
 use actix_web::rt::time::Instant;
 use std::sync::Arc;
-use collection::operations::types::SearchRequest;
+use collection::operations::types::{SearchRequest, SearchRequestBatch, SearchGroupsRequest, FusionSearchRequest, FormulaSearchRequest};
+use actix_web::web::Query;
+use segment::common::stop_condition::StopCondition;
+use crate::api::models::ReadParams;
+use crate::common::slow_log::{SlowLog, SlowLogRecord};
 
 #[post("/collections/{name}/points/search")]
 pub async fn search_points(
     toc: web::Data<TableOfContent>,
     web::Path(name): web::Path<String>,
     request: web::Json<SearchRequest>,
+    params: Query<ReadParams>,
+    slow_log: web::Data<SlowLog>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let request = Arc::new(request.0);
+    let response = match toc.get_async_collection(&name) {
+        Ok(collection) => {
+            let result = collection
+                .search(request.clone(), params.consistency.unwrap_or_default(), params.stop_condition())
+                .await
+                .map_err(|err| err.into());
+            slow_log.observe(collection.collection(), &name, SlowLogRecord {
+                operation: "search",
+                filter: request.filter.as_ref(),
+                params: request.params.as_ref(),
+                limit: Some(request.top),
+            }, timing);
+            result
+        }
+        Err(err) => Err(err),
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/points/search/groups")]
+pub async fn search_point_groups(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<SearchGroupsRequest>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.get_collection(&name)
+            .and_then(|collection| collection
+                .search_groups(Arc::new(request.0), &StopCondition::default())
+                .map_err(|err| err.into())
+            )
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/points/search/batch")]
+pub async fn search_points_batch(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<SearchRequestBatch>,
+    params: Query<ReadParams>,
+    slow_log: web::Data<SlowLog>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let request = Arc::new(request.0);
+    let response = match toc.get_async_collection(&name) {
+        Ok(collection) => {
+            let result = collection
+                .search_batch(request.clone(), params.consistency.unwrap_or_default(), params.stop_condition())
+                .await
+                .map_err(|err| err.into());
+            slow_log.observe(collection.collection(), &name, SlowLogRecord {
+                operation: "search_batch",
+                filter: request.filter.as_ref(),
+                params: request.params.as_ref(),
+                limit: Some(request.searches.len()),
+            }, timing);
+            result
+        }
+        Err(err) => Err(err),
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/points/search/fusion")]
+pub async fn search_points_fusion(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<FusionSearchRequest>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.get_collection(&name)
+            .and_then(|collection| collection
+                .search_fusion(Arc::new(request.0))
+                .map_err(|err| err.into())
+            )
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/points/search/formula")]
+pub async fn search_points_formula(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<FormulaSearchRequest>,
 ) -> impl Responder {
     let timing = Instant::now();
 
     let response = {
         toc.get_collection(&name)
             .and_then(|collection| collection
-                .search(Arc::new(request.0))
+                .search_formula(Arc::new(request.0))
                 .map_err(|err| err.into())
             )
     };
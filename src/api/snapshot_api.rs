@@ -0,0 +1,103 @@
+use std::fs::remove_file;
+use std::path::Path;
+
+use actix_web::{Either, HttpResponse, Responder, get, post, put, web};
+use actix_web::rt::time::Instant;
+use serde::Deserialize;
+use collection::cold_storage::s3::S3Config;
+use storage::content_manager::errors::StorageError;
+use storage::content_manager::toc::TableOfContent;
+
+use crate::api::models::{CreatedSnapshot, SnapshotRecover};
+use crate::common::helpers::process_response;
+use crate::common::snapshots::{download_snapshot, file_stream, upload_snapshot};
+
+#[derive(Deserialize)]
+pub struct CreateSnapshotParams {
+    /// Upload created snapshot into the configured S3-compatible storage
+    #[serde(default)]
+    pub upload: bool,
+}
+
+#[get("/collections/{name}/snapshots")]
+pub async fn list_snapshots(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.list_snapshots(&name)
+    };
+
+    process_response(response, timing)
+}
+
+#[post("/collections/{name}/snapshots")]
+pub async fn create_snapshot(
+    toc: web::Data<TableOfContent>,
+    s3_config: web::Data<Option<S3Config>>,
+    web::Path(name): web::Path<String>,
+    params: web::Query<CreateSnapshotParams>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = async {
+        let snapshot = toc.create_snapshot(&name)?;
+        let location = if params.upload {
+            let s3_config = s3_config.get_ref().as_ref()
+                .ok_or_else(|| StorageError::BadRequest { description: "S3 storage is not configured".to_string() })?;
+            let snapshot_path = toc.get_snapshot_path(&name, &snapshot.name)?;
+            Some(upload_snapshot(s3_config, &snapshot_path, &format!("{}/{}", name, snapshot.name)).await?)
+        } else {
+            None
+        };
+        Ok(CreatedSnapshot { snapshot, location })
+    }.await;
+
+    process_response(response, timing)
+}
+
+#[get("/collections/{name}/snapshots/{snapshot_name}")]
+pub async fn get_snapshot(
+    toc: web::Data<TableOfContent>,
+    web::Path((name, snapshot_name)): web::Path<(String, String)>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let stream = toc.get_snapshot_path(&name, &snapshot_name)
+        .and_then(|snapshot_path| file_stream(&snapshot_path));
+
+    match stream {
+        Ok(stream) => Either::A(HttpResponse::Ok().content_type("application/octet-stream").streaming(stream)),
+        Err(err) => Either::B(process_response::<()>(Err(err), timing)),
+    }
+}
+
+#[put("/collections/{name}/snapshots/recover")]
+pub async fn recover_snapshot(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<SnapshotRecover>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let location = &request.location;
+    let response = if let Some(path) = location.strip_prefix("file://") {
+        toc.recover_snapshot(&name, Path::new(path))
+    } else if location.starts_with("http://") || location.starts_with("https://") {
+        async {
+            let snapshot_path = toc.download_snapshot_path(&name)?;
+            let result = match download_snapshot(location, &snapshot_path).await {
+                Ok(()) => toc.recover_snapshot(&name, &snapshot_path),
+                Err(err) => Err(err),
+            };
+            remove_file(&snapshot_path).ok();
+            result
+        }.await
+    } else {
+        Err(StorageError::BadInput { description: format!("Unsupported snapshot location: {}", location) })
+    };
+
+    process_response(response, timing)
+}
@@ -1,15 +1,20 @@
-use actix_web::{post, web, Responder};
+use actix_web::{post, put, web, Responder};
 use storage::content_manager::toc::TableOfContent;
 use actix_web::rt::time::Instant;
 use crate::common::helpers::process_response;
 use collection::operations::CollectionUpdateOperations;
+use collection::operations::point_ops::PointOperations;
+use collection::operations::types::{CsvImportRequest, NpyImportRequest, ParquetImportRequest};
 use actix_web::web::Query;
 use serde::{Deserialize, Serialize};
 use schemars::{JsonSchema};
+use crate::common::slow_log::{SlowLog, SlowLogRecord};
 
 #[derive(Deserialize, Serialize, JsonSchema)]
 pub struct UpdateParam {
-    pub wait: Option<bool>
+    pub wait: Option<bool>,
+    /// Also wait for the changes to be processed by optimizers, e.g. indexed. Implies `wait`
+    pub wait_indexed: Option<bool>,
 }
 
 #[post("/collections/{name}")]
@@ -18,17 +23,85 @@ pub async fn update_points(
     web::Path(name): web::Path<String>,
     operation: web::Json<CollectionUpdateOperations>,
     params: Query<UpdateParam>,
+    slow_log: web::Data<SlowLog>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let wait_indexed = params.wait_indexed.unwrap_or(false);
+    let wait = params.wait.unwrap_or(false) || wait_indexed;
+
+    let filter = match &operation.0 {
+        CollectionUpdateOperations::PointOperation(PointOperations::DeletePointsByFilter { filter }) => Some(filter.clone()),
+        _ => None,
+    };
+
+    let response = match toc.get_async_collection(&name) {
+        Ok(collection) => {
+            let result = collection
+                .update(operation.0, wait, wait_indexed)
+                .await
+                .map_err(|err| err.into());
+            slow_log.observe(collection.collection(), &name, SlowLogRecord {
+                operation: "update",
+                filter: filter.as_ref(),
+                params: None,
+                limit: None,
+            }, timing);
+            result
+        }
+        Err(err) => Err(err),
+    };
+
+    process_response(response, timing)
+}
+
+/// Bulk import of points from numpy files of the import directory, see `TableOfContent::import_npy`.
+/// Responds with number of imported points
+#[put("/collections/{name}/points/import")]
+pub async fn import_points(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<NpyImportRequest>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.import_npy(&name, &request)
+    };
+
+    process_response(response, timing)
+}
+
+/// Upsert points from a parquet file of the import directory, see `TableOfContent::import_parquet`.
+/// Responds with number of imported points
+#[put("/collections/{name}/points/import/parquet")]
+pub async fn import_points_parquet(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<ParquetImportRequest>,
+) -> impl Responder {
+    let timing = Instant::now();
+
+    let response = {
+        toc.import_parquet(&name, &request)
+    };
+
+    process_response(response, timing)
+}
+
+/// Upsert points from a CSV file of the import directory, see `TableOfContent::import_csv`.
+/// Responds with number of imported points
+#[put("/collections/{name}/points/import/csv")]
+pub async fn import_points_csv(
+    toc: web::Data<TableOfContent>,
+    web::Path(name): web::Path<String>,
+    request: web::Json<CsvImportRequest>,
 ) -> impl Responder {
     let timing = Instant::now();
 
     let response = {
-        toc.get_collection(&name)
-            .and_then(|collection|
-                collection
-                    .update(operation.0, params.wait.unwrap_or(false))
-                    .map_err(|x| x.into())
-            )
+        toc.import_csv(&name, &request)
     };
 
     process_response(response, timing)
-}
\ No newline at end of file
+}
@@ -1,19 +1,118 @@
-#[macro_use]
-extern crate log;
-
 mod settings;
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter, stdin, stdout};
+use std::path::Path;
 
+use collection::bundle_builder::{build_bundle, BundleBuildConfig, BundleSource};
+use collection::collection::{Collection, CollectionError};
+use collection::jsonl::{export_jsonl, import_jsonl};
+use collection::operation_recorder::{read_recording, replay_recording};
+use segment::common::config::load_config;
+use storage::content_manager::errors::StorageError;
 use storage::content_manager::toc::TableOfContent;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "Usage:
+    cli                                 load all collections
+    cli export <collection> <file|->    write points of the collection as JSON lines
+    cli import <collection> <file|->    upsert points from JSON lines into an existing collection
+    cli bundle <collection> <dir>       write points of the collection into a new read-only segment bundle
+    cli replay <collection> <file>      apply recorded operations to an empty collection and compare search results
+    cli build <config> <dir> <data file> [<points file>]
+                                        build a new read-only segment bundle from .npy, .parquet or .jsonl file,
+                                        without the storage. Points file defines ids and payloads of .npy vectors";
+
+/// Replay the recording of the service into a collection with the same parameters. Returns number of operations
+fn replay(collection: &Collection, path: &str) -> Result<usize, StorageError> {
+    let file = File::open(path).map_err(|err| StorageError::BadInput {
+        description: format!("Can't open {}, error: {}", path, err)
+    })?;
+    // Replayed operations are not recorded again, even if recording is enabled in the config
+    collection.stop_recording();
+    let report = replay_recording(collection, read_recording(BufReader::new(file))?)?;
+    for mismatch in &report.mismatches {
+        warn!("operation {} differs from the recording: {}", mismatch.seq, mismatch.description);
+    }
+    let operations_count = report.updates + report.searches;
+    if !report.mismatches.is_empty() {
+        return Err(StorageError::ServiceError {
+            description: format!("{} of {} operations differ from the recording", report.mismatches.len(), operations_count)
+        });
+    }
+    Ok(operations_count)
+}
+
+/// Export and import of points in JSON lines format, e.g. to migrate collections between deployments,
+/// export of read-only segment bundles and replay of recorded operations.
+/// `-` stands for stdout or stdin
+fn run_command(toc: &TableOfContent, command: &str, collection_name: &str, path: &str) -> Result<usize, StorageError> {
+    let collection = toc.get_collection(collection_name)?;
+    let io_error = |err: std::io::Error| StorageError::BadInput {
+        description: format!("Can't open {}, error: {}", path, err)
+    };
+    let points_count = match (command, path) {
+        ("export", "-") => export_jsonl(&collection, BufWriter::new(stdout().lock()))?,
+        ("export", _) => export_jsonl(&collection, BufWriter::new(File::create(path).map_err(io_error)?))?,
+        ("import", "-") => import_jsonl(&collection, stdin().lock())?,
+        ("import", _) => import_jsonl(&collection, BufReader::new(File::open(path).map_err(io_error)?))?,
+        ("bundle", _) => collection.export_bundle(Path::new(path))?.points_count,
+        ("replay", _) => replay(&collection, path)?,
+        _ => unreachable!(),
+    };
+    Ok(points_count)
+}
+
+/// Build the bundle on a separate machine, so the serving nodes do not spend resources on indexing
+fn build_offline(config_path: &str, bundle_path: &str, data_path: &str, points_path: Option<&str>) -> Result<usize, CollectionError> {
+    let config: BundleBuildConfig = load_config(Path::new(config_path))?;
+    let source = BundleSource::from_path(Path::new(data_path), points_path.map(Path::new))?;
+    Ok(build_bundle(source, &config, Path::new(bundle_path))?.points_count)
+}
 
 fn main() {
     let settings = settings::Settings::new().expect("Can't read config.");
-    std::env::set_var("RUST_LOG", settings.log_level);
-    env_logger::init();
+    tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&settings.log_level)).init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, config_path, bundle_path, data_path, points_path @ ..] = args.as_slice() {
+        if command == "build" && points_path.len() <= 1 {
+            let points_path = points_path.first().map(String::as_str);
+            match build_offline(config_path, bundle_path, data_path, points_path) {
+                Ok(points_count) => info!("bundle {} built: {} points", bundle_path, points_count),
+                Err(err) => {
+                    error!("Can't build bundle {}: {}", bundle_path, err);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+    }
+    let command = match args.as_slice() {
+        [] => None,
+        [command, collection_name, path] if ["export", "import", "bundle", "replay"].contains(&command.as_str()) =>
+            Some((command.as_str(), collection_name.as_str(), path.as_str())),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
 
     let toc = TableOfContent::new(&settings.storage);
 
     for collection in toc.all_collections() {
         info!("loaded collection: {}", collection);
     }
-}
\ No newline at end of file
+
+    if let Some((command, collection_name, path)) = command {
+        match run_command(&toc, command, collection_name, path) {
+            Ok(count) if command == "replay" => info!("replay of collection {} completed: {} operations", collection_name, count),
+            Ok(points_count) => info!("{} of collection {} completed: {} points", command, collection_name, points_count),
+            Err(err) => {
+                error!("Can't {} collection {}: {}", command, collection_name, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
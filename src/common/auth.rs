@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_web::{Error, HttpResponse};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::{HeaderMap, Method};
+use futures::future::{Either, Ready, err, ok};
+
+use crate::common::models::{ApiResponse, ApiStatus};
+use crate::settings::{AccessMode, ApiKeyConfig};
+
+const API_KEY_HEADER: &str = "api-key";
+
+/// Paths, which are available without a key, e.g. for probes of the orchestrator
+const PUBLIC_PATHS: [&str; 3] = ["/", "/livez", "/readyz"];
+
+/// Access, required by a request
+struct RequiredAccess<'a> {
+    mode: AccessMode,
+    /// Collection of the request. None for service-wide requests, like list of collections or aliases
+    collection: Option<&'a str>,
+}
+
+/// Routes, which are sent with POST, but only read data. Paths are relative to `/collections/{name}`
+const POST_READ_ROUTES: [&str; 10] = [
+    "points",
+    "points/search",
+    "points/search/batch",
+    "points/search/groups",
+    "points/search/fusion",
+    "points/search/formula",
+    "points/recommend",
+    "points/discover",
+    "points/scroll",
+    "points/count",
+];
+
+/// Requests are classified by method and path, as the check happens before the routing.
+/// Any other POST request is considered to be a write, so new routes require write access unless listed above.
+pub fn access_mode(method: &Method, path: &str) -> AccessMode {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_read = match (method, segments.as_slice()) {
+        (&Method::GET, _) => true,
+        (&Method::POST, ["collections", _, route @ ..]) => POST_READ_ROUTES.contains(&route.join("/").as_str()),
+        _ => false,
+    };
+    if is_read { AccessMode::Read } else { AccessMode::ReadWrite }
+}
+
+fn required_access<'a>(method: &Method, path: &'a str) -> RequiredAccess<'a> {
+    let collection = match path.trim_matches('/').split('/').collect::<Vec<_>>().as_slice() {
+        ["collections", name, ..] => Some(*name),
+        _ => None,
+    };
+    RequiredAccess { mode: access_mode(method, path), collection }
+}
+
+pub fn request_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    headers.get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Keys are compared in constant time, so they could not be guessed by timing of responses
+pub fn keys_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_allowed(key: &ApiKeyConfig, required: &RequiredAccess) -> bool {
+    let mode_allowed = key.access == AccessMode::ReadWrite || required.mode == AccessMode::Read;
+    let collection_allowed = match (&key.collections, required.collection) {
+        (None, _) => true,
+        (Some(collections), Some(collection)) => collections.iter().any(|allowed| allowed == collection),
+        (Some(_), None) => false,
+    };
+    mode_allowed && collection_allowed
+}
+
+/// Error, which is rendered as the given response with the description in the API format
+pub fn rejection(mut response: actix_web::dev::HttpResponseBuilder, description: &str) -> Error {
+    InternalError::from_response(
+        description.to_string(),
+        response.json(ApiResponse::<()> {
+            result: None,
+            status: ApiStatus::Error(description.to_string()),
+            time: 0.0,
+        }),
+    ).into()
+}
+
+/// Rejects requests without a valid key, or with a key, which does not grant the required access.
+/// Requests are rejected before they reach handlers, so no operation is enqueued for them.
+/// Does nothing, if no keys are configured.
+pub struct ApiKeyAuth {
+    keys: Arc<Vec<ApiKeyConfig>>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(keys: Vec<ApiKeyConfig>) -> Self {
+        ApiKeyAuth { keys: Arc::new(keys) }
+    }
+}
+
+impl<S, B> Transform<S> for ApiKeyAuth
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ApiKeyAuthMiddleware { service, keys: self.keys.clone() })
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: S,
+    keys: Arc<Vec<ApiKeyConfig>>,
+}
+
+impl<S> ApiKeyAuthMiddleware<S> {
+    fn check(&self, req: &ServiceRequest) -> Result<(), Error> {
+        if self.keys.is_empty() || PUBLIC_PATHS.contains(&req.path()) {
+            return Ok(());
+        }
+
+        let key = request_key(req.headers())
+            .and_then(|request_key| self.keys.iter().find(|key| keys_equal(&key.key, request_key)))
+            .ok_or_else(|| rejection(HttpResponse::Unauthorized(), "Valid API key required"))?;
+
+        if is_allowed(key, &required_access(req.method(), req.path())) {
+            Ok(())
+        } else {
+            Err(rejection(HttpResponse::Forbidden(), "API key does not grant access to the request"))
+        }
+    }
+}
+
+impl<S, B> Service for ApiKeyAuthMiddleware<S>
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        match self.check(&req) {
+            Ok(()) => Either::Left(self.service.call(req)),
+            Err(error) => Either::Right(err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_mode() {
+        assert_eq!(access_mode(&Method::GET, "/collections/test/points/1"), AccessMode::Read);
+        assert_eq!(access_mode(&Method::POST, "/collections/test/points/search"), AccessMode::Read);
+        assert_eq!(access_mode(&Method::POST, "/collections/test/points/search/batch/"), AccessMode::Read);
+        assert_eq!(access_mode(&Method::POST, "/collections/test/points"), AccessMode::Read);
+
+        assert_eq!(access_mode(&Method::POST, "/collections/test"), AccessMode::ReadWrite);
+        assert_eq!(access_mode(&Method::POST, "/collections/test/points/delete"), AccessMode::ReadWrite);
+        assert_eq!(access_mode(&Method::POST, "/collections/test/snapshots"), AccessMode::ReadWrite);
+        assert_eq!(access_mode(&Method::PUT, "/collections/test/points/import"), AccessMode::ReadWrite);
+    }
+}
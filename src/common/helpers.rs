@@ -33,6 +33,14 @@ pub fn process_response<D>(response: Result<D, StorageError>, timing: Instant) -
                     error_description = description;
                     HttpResponse::BadRequest()
                 }
+                StorageError::Cancelled { description } => {
+                    error_description = description;
+                    HttpResponse::RequestTimeout()
+                }
+                StorageError::Overloaded { description } => {
+                    error_description = description;
+                    HttpResponse::ServiceUnavailable()
+                }
             };
 
             resp.json(ApiResponse::<()> {
@@ -0,0 +1,503 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, create_dir_all, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use kafka::client::{FetchOffset, FetchPartition, KafkaClient};
+use kafka::client::fetch::Message;
+use tracing::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use collection::collection::CollectionError;
+use collection::operations::CollectionUpdateOperations;
+use collection::operations::point_ops::PointOperations;
+use segment::types::SeqNumberType;
+use storage::content_manager::errors::StorageError;
+use storage::content_manager::toc::TableOfContent;
+
+use crate::settings::{KafkaConsumerConfig, KafkaMessageFormat};
+
+/// Directory of the storage, where offsets of consumed messages are stored
+const KAFKA_DIR: &str = "kafka";
+
+/// Max time, the broker waits for new messages before responding to a fetch
+const FETCH_WAIT_TIME: Duration = Duration::from_secs(1);
+
+/// Delay before the consumer reconnects after an error
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+enum ConsumerError {
+    #[error("Kafka error: {0}")]
+    Kafka(#[from] kafka::error::Error),
+    #[error("{0}")]
+    Storage(#[from] StorageError),
+    #[error("Can't save offsets: {0}")]
+    Checkpoint(#[from] io::Error),
+}
+
+/// Consumed position of a partition
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct PartitionCheckpoint {
+    /// Offset of the next message to consume
+    offset: i64,
+    /// Operation of the collection, produced by the last applied message
+    operation_id: Option<SeqNumberType>,
+    /// Next operation id of each shard of the collection right after the last applied message
+    #[serde(default)]
+    next_operation_ids: Option<Vec<SeqNumberType>>,
+}
+
+/// Operation of the collection, produced by an applied message
+struct AppliedMessage {
+    operation_id: SeqNumberType,
+    /// Next operation id of each shard right after the message is applied
+    next_operation_ids: Vec<SeqNumberType>,
+}
+
+/// Offsets of a topic, stored next to the collections.
+/// Messages before the stored offset are never applied again, even if the broker returns them
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct Checkpoint {
+    partitions: BTreeMap<i32, PartitionCheckpoint>,
+    /// Partitions, whose message at the stored offset might have been applied already,
+    /// as the consumer stopped before its offset was saved
+    #[serde(skip)]
+    resumed: BTreeSet<i32>,
+}
+
+impl Checkpoint {
+    fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        serde_json::from_reader(File::open(path)?).map_err(|err| err.into())
+    }
+
+    /// Offsets are written into a temporary file first, so a crash never leaves a partially written checkpoint
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        serde_json::to_writer(&file, self)?;
+        file.sync_all()?;
+        rename(&tmp_path, path)
+    }
+
+    /// Messages at the stored offsets are applied as possibly repeated ones, see `apply_messages`.
+    /// Should be called each time the consumer starts over, e.g. after a restart or a failed message
+    fn resume(&mut self) {
+        self.resumed = self.partitions.keys().copied().collect();
+    }
+
+    /// Apply messages of the partition in order with `apply_message`.
+    /// Offset is saved right after each message, so only the message, which was being applied
+    /// when the consumer stopped, might be applied again. The first message after `resume` is given
+    /// the next operation ids of shards, saved with the previous message: the collection applies it
+    /// as an operation of that time, so changes of its points made after it, e.g. by other writers,
+    /// are not overwritten and each message takes effect once.
+    /// Messages, which are rejected by the collection, are skipped, so they do not block the partition.
+    /// Applying stops at the first failed message, which is retried by the next fetch
+    fn apply_messages(
+        &mut self,
+        path: &Path,
+        partition: i32,
+        messages: &[Message],
+        telemetry: &Mutex<KafkaConsumerTelemetry>,
+        mut apply_message: impl FnMut(&[u8], Option<&[SeqNumberType]>) -> Result<Option<AppliedMessage>, ConsumerError>,
+    ) -> Result<(), ConsumerError> {
+        for message in messages {
+            let mut checkpoint = self.partitions[&partition].clone();
+            // Compressed message sets might start before the requested offset
+            if message.offset < checkpoint.offset {
+                continue;
+            }
+            let applied_since = match self.resumed.contains(&partition) {
+                true => checkpoint.next_operation_ids.as_deref(),
+                false => None,
+            };
+            let applied = apply_message(message.value, applied_since)?;
+            self.resumed.remove(&partition);
+            checkpoint.offset = message.offset + 1;
+            if let Some(applied) = &applied {
+                checkpoint.operation_id = Some(applied.operation_id);
+                checkpoint.next_operation_ids = Some(applied.next_operation_ids.clone());
+            }
+            self.partitions.insert(partition, checkpoint);
+            self.save(path)?;
+
+            let mut telemetry = telemetry.lock().unwrap();
+            match applied {
+                Some(_) => telemetry.applied += 1,
+                None => telemetry.skipped += 1,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct PartitionTelemetry {
+    pub partition: i32,
+    /// Offset of the next message to consume
+    pub offset: i64,
+    /// Offset of the next message, which will be produced into the partition
+    pub high_watermark: i64,
+    /// Number of produced messages, which are not consumed yet
+    pub lag: i64,
+    /// Operation of the collection, produced by the last applied message
+    pub operation_id: Option<SeqNumberType>,
+}
+
+/// Progress of the consumer of a Kafka topic
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct KafkaConsumerTelemetry {
+    pub topic: String,
+    pub collection: String,
+    /// Number of messages, applied since the start
+    pub applied: u64,
+    /// Number of messages, which could not be decoded or applied to the collection
+    pub skipped: u64,
+    pub partitions: Vec<PartitionTelemetry>,
+    /// Last error of the connection to brokers or of the collection. Consumer retries after errors
+    pub last_error: Option<String>,
+}
+
+fn decode_operation(format: KafkaMessageFormat, value: &[u8]) -> Result<PointOperations, String> {
+    match format {
+        KafkaMessageFormat::Json => serde_json::from_slice(value).map_err(|err| err.to_string()),
+        KafkaMessageFormat::Cbor => serde_cbor::from_slice(value).map_err(|err| err.to_string()),
+        KafkaMessageFormat::MessagePack => rmp_serde::from_slice(value).map_err(|err| err.to_string()),
+    }
+}
+
+/// Operation, produced by the message, or `None` if the message is skipped.
+/// Message, which might have been applied already, is only applied to points, which are not changed
+/// after `applied_since` next operation ids of shards, see `Collection::update_unchanged_since`
+fn apply_message(
+    config: &KafkaConsumerConfig,
+    toc: &TableOfContent,
+    value: &[u8],
+    applied_since: Option<&[SeqNumberType]>,
+) -> Result<Option<AppliedMessage>, ConsumerError> {
+    let operation = match decode_operation(config.format, value) {
+        Ok(operation) => operation,
+        Err(err) => {
+            warn!(topic = %config.topic, error = %err, "Skipped Kafka message, which is not an operation");
+            return Ok(None);
+        }
+    };
+    let collection = toc.get_collection(&config.collection)?;
+    let result = match applied_since {
+        Some(next_operation_ids) => collection.update_unchanged_since(operation, next_operation_ids, true),
+        None => collection.update(CollectionUpdateOperations::PointOperation(operation), true),
+    };
+    match result {
+        Ok(result) => Ok(Some(AppliedMessage {
+            operation_id: result.operation_id,
+            next_operation_ids: collection.next_operation_ids().map_err(StorageError::from)?,
+        })),
+        Err(err @ CollectionError::BadInput { .. })
+        | Err(err @ CollectionError::BadRequest { .. })
+        | Err(err @ CollectionError::NotFound { .. })
+        | Err(err @ CollectionError::StrictModeViolation { .. }) => {
+            warn!(topic = %config.topic, collection = %config.collection, error = %err, "Skipped Kafka message");
+            Ok(None)
+        }
+        Err(err) => Err(StorageError::from(err).into()),
+    }
+}
+
+struct Consumer {
+    config: KafkaConsumerConfig,
+    toc: Arc<TableOfContent>,
+    checkpoint_path: PathBuf,
+    checkpoint: Checkpoint,
+    telemetry: Arc<Mutex<KafkaConsumerTelemetry>>,
+}
+
+impl Consumer {
+    fn run(mut self) {
+        loop {
+            if let Err(err) = self.consume() {
+                warn!(topic = %self.config.topic, error = %err, "Kafka consumer failed");
+                self.telemetry.lock().unwrap().last_error = Some(err.to_string());
+            }
+            thread::sleep(RETRY_DELAY);
+        }
+    }
+
+    /// Fetch messages of all partitions of the topic, until an error occurs
+    fn consume(&mut self) -> Result<(), ConsumerError> {
+        let mut client = KafkaClient::new(self.config.brokers.clone());
+        client.set_fetch_max_wait_time(FETCH_WAIT_TIME)?;
+        client.load_metadata(&[&self.config.topic])?;
+        let partitions = client.topics()
+            .partitions(&self.config.topic)
+            .map(|partitions| partitions.available_ids())
+            .unwrap_or_default();
+
+        let start_offset = if self.config.from_beginning { FetchOffset::Earliest } else { FetchOffset::Latest };
+        for partition_offset in client.fetch_topic_offsets(&self.config.topic, start_offset)? {
+            if partitions.contains(&partition_offset.partition) {
+                self.checkpoint.partitions.entry(partition_offset.partition).or_insert(PartitionCheckpoint {
+                    offset: partition_offset.offset,
+                    operation_id: None,
+                    next_operation_ids: None,
+                });
+            }
+        }
+        self.checkpoint.save(&self.checkpoint_path)?;
+        self.checkpoint.resume();
+        info!(topic = %self.config.topic, partitions = ?partitions, "Consuming Kafka partitions");
+
+        loop {
+            let requests: Vec<_> = partitions.iter()
+                .filter_map(|partition| self.checkpoint.partitions.get(partition)
+                    .map(|checkpoint| FetchPartition::new(&self.config.topic, *partition, checkpoint.offset)))
+                .collect();
+            for response in client.fetch_messages(requests)? {
+                for topic in response.topics() {
+                    for partition in topic.partitions() {
+                        let data = match partition.data() {
+                            Ok(data) => data,
+                            Err(err) => {
+                                warn!(topic = %self.config.topic, partition = partition.partition(), error = %err, "Can't fetch Kafka partition");
+                                continue;
+                            }
+                        };
+                        self.apply(partition.partition(), data.messages())?;
+                        self.observe_lag(partition.partition(), data.highwatermark_offset());
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, partition: i32, messages: &[Message]) -> Result<(), ConsumerError> {
+        let config = &self.config;
+        let toc = &self.toc;
+        self.checkpoint.apply_messages(
+            &self.checkpoint_path,
+            partition,
+            messages,
+            &self.telemetry,
+            |value, applied_since| apply_message(config, toc, value, applied_since),
+        )
+    }
+
+    fn observe_lag(&self, partition: i32, high_watermark: i64) {
+        let checkpoint = &self.checkpoint.partitions[&partition];
+        let partition_telemetry = PartitionTelemetry {
+            partition,
+            offset: checkpoint.offset,
+            high_watermark,
+            lag: (high_watermark - checkpoint.offset).max(0),
+            operation_id: checkpoint.operation_id,
+        };
+        let mut telemetry = self.telemetry.lock().unwrap();
+        telemetry.last_error = None;
+        match telemetry.partitions.iter_mut().find(|existing| existing.partition == partition) {
+            Some(existing) => *existing = partition_telemetry,
+            None => telemetry.partitions.push(partition_telemetry),
+        }
+    }
+}
+
+/// Background consumers of all configured Kafka topics. Each topic is consumed by a separate thread
+pub struct KafkaConsumers {
+    consumers: Vec<Arc<Mutex<KafkaConsumerTelemetry>>>,
+}
+
+impl KafkaConsumers {
+    pub fn start(configs: &[KafkaConsumerConfig], toc: Arc<TableOfContent>, storage_path: &str) -> io::Result<Self> {
+        let checkpoints_path = Path::new(storage_path).join(KAFKA_DIR);
+        create_dir_all(&checkpoints_path)?;
+
+        let mut consumers = vec![];
+        for config in configs {
+            let checkpoint_path = checkpoints_path.join(format!("{}.{}.json", config.topic, config.collection));
+            let telemetry = Arc::new(Mutex::new(KafkaConsumerTelemetry {
+                topic: config.topic.clone(),
+                collection: config.collection.clone(),
+                applied: 0,
+                skipped: 0,
+                partitions: vec![],
+                last_error: None,
+            }));
+            let consumer = Consumer {
+                config: config.clone(),
+                toc: toc.clone(),
+                checkpoint: Checkpoint::load(&checkpoint_path)?,
+                checkpoint_path,
+                telemetry: telemetry.clone(),
+            };
+            thread::Builder::new()
+                .name(format!("kafka-{}", config.topic))
+                .spawn(move || consumer.run())?;
+            consumers.push(telemetry);
+        }
+        Ok(KafkaConsumers { consumers })
+    }
+
+    pub fn telemetry(&self) -> Vec<KafkaConsumerTelemetry> {
+        self.consumers.iter()
+            .map(|telemetry| telemetry.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn telemetry() -> Mutex<KafkaConsumerTelemetry> {
+        Mutex::new(KafkaConsumerTelemetry {
+            topic: "points".to_string(),
+            collection: "test".to_string(),
+            applied: 0,
+            skipped: 0,
+            partitions: vec![],
+            last_error: None,
+        })
+    }
+
+    fn messages(offsets: &[i64]) -> Vec<Message<'static>> {
+        offsets.iter().map(|offset| Message { offset: *offset, key: b"", value: b"" }).collect()
+    }
+
+    fn checkpoint(offset: i64) -> Checkpoint {
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.partitions.insert(0, PartitionCheckpoint { offset, operation_id: None, next_operation_ids: None });
+        checkpoint
+    }
+
+    fn applied(operation_id: SeqNumberType) -> Option<AppliedMessage> {
+        Some(AppliedMessage { operation_id, next_operation_ids: vec![operation_id + 1] })
+    }
+
+    #[test]
+    fn test_checkpoint_save_load() {
+        let dir = TempDir::new("kafka").unwrap();
+        let path = dir.path().join("points.test.json");
+        assert!(Checkpoint::load(&path).unwrap().partitions.is_empty());
+
+        let mut checkpoint = checkpoint(10);
+        checkpoint.partitions.insert(3, PartitionCheckpoint { offset: 7, operation_id: Some(42), next_operation_ids: Some(vec![43, 40]) });
+        checkpoint.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.partitions.keys().copied().collect::<Vec<_>>(), vec![0, 3]);
+        assert_eq!(loaded.partitions[&0].offset, 10);
+        assert_eq!(loaded.partitions[&0].operation_id, None);
+        assert_eq!(loaded.partitions[&3].offset, 7);
+        assert_eq!(loaded.partitions[&3].operation_id, Some(42));
+        assert_eq!(loaded.partitions[&3].next_operation_ids, Some(vec![43, 40]));
+    }
+
+    #[test]
+    fn test_skip_consumed_messages() {
+        let dir = TempDir::new("kafka").unwrap();
+        let path = dir.path().join("points.test.json");
+        let telemetry = telemetry();
+        let mut checkpoint = checkpoint(5);
+
+        let mut applied_offsets = vec![];
+        let mut next_operation_id = 0;
+        checkpoint.apply_messages(&path, 0, &messages(&[3, 4, 5, 6, 7]), &telemetry, |_, _| {
+            next_operation_id += 1;
+            applied_offsets.push(4 + next_operation_id as i64);
+            Ok(applied(next_operation_id))
+        }).unwrap();
+
+        assert_eq!(applied_offsets, vec![5, 6, 7]);
+        assert_eq!(checkpoint.partitions[&0].offset, 8);
+        assert_eq!(checkpoint.partitions[&0].operation_id, Some(3));
+        assert_eq!(Checkpoint::load(&path).unwrap().partitions[&0].offset, 8);
+        assert_eq!(telemetry.lock().unwrap().applied, 3);
+
+        // Messages, returned by the broker again, are not applied twice
+        checkpoint.apply_messages(&path, 0, &messages(&[6, 7]), &telemetry, |_, _| panic!("message is applied twice"))
+            .unwrap();
+        assert_eq!(checkpoint.partitions[&0].offset, 8);
+    }
+
+    #[test]
+    fn test_stop_at_failed_message() {
+        let dir = TempDir::new("kafka").unwrap();
+        let path = dir.path().join("points.test.json");
+        let telemetry = telemetry();
+        let mut checkpoint = checkpoint(0);
+
+        let mut attempts = 0;
+        let result = checkpoint.apply_messages(&path, 0, &messages(&[0, 1, 2, 3]), &telemetry, |_, _| {
+            attempts += 1;
+            match attempts {
+                1 => Ok(applied(10)),
+                2 => Ok(None),
+                _ => Err(ConsumerError::Checkpoint(io::Error::new(io::ErrorKind::Other, "collection is not available"))),
+            }
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        // Failed message is fetched and applied again
+        let saved = Checkpoint::load(&path).unwrap();
+        assert_eq!(saved.partitions[&0].offset, 2);
+        assert_eq!(saved.partitions[&0].operation_id, Some(10));
+        assert_eq!(checkpoint.partitions[&0].offset, 2);
+        let telemetry = telemetry.lock().unwrap();
+        assert_eq!(telemetry.applied, 1);
+        assert_eq!(telemetry.skipped, 1);
+    }
+
+    #[test]
+    fn test_repeat_message_after_resume() {
+        let dir = TempDir::new("kafka").unwrap();
+        let path = dir.path().join("points.test.json");
+        let telemetry = telemetry();
+        let mut checkpoint = checkpoint(0);
+
+        // Nothing was applied before, so the first message could not be a repeated one
+        checkpoint.resume();
+        let mut given_ids = vec![];
+        checkpoint.apply_messages(&path, 0, &messages(&[0, 1]), &telemetry, |_, applied_since| {
+            given_ids.push(applied_since.map(|ids| ids.to_vec()));
+            Ok(applied(given_ids.len() as SeqNumberType * 10))
+        }).unwrap();
+        assert_eq!(given_ids, vec![None, None]);
+
+        // Consumer stopped while the message at the saved offset was being applied
+        let mut checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!(checkpoint.partitions[&0].next_operation_ids, Some(vec![21]));
+        checkpoint.resume();
+
+        let mut given_ids = vec![];
+        let mut attempts = 0;
+        let result = checkpoint.apply_messages(&path, 0, &messages(&[2, 3]), &telemetry, |_, applied_since| {
+            attempts += 1;
+            given_ids.push(applied_since.map(|ids| ids.to_vec()));
+            match attempts {
+                1 => Err(ConsumerError::Checkpoint(io::Error::new(io::ErrorKind::Other, "collection is not available"))),
+                _ => Ok(applied(30)),
+            }
+        });
+        assert!(result.is_err());
+        // Failed message might still be a repeated one, messages after it are not
+        checkpoint.apply_messages(&path, 0, &messages(&[2, 3]), &telemetry, |_, applied_since| {
+            given_ids.push(applied_since.map(|ids| ids.to_vec()));
+            Ok(applied(30 + given_ids.len() as SeqNumberType))
+        }).unwrap();
+        assert_eq!(given_ids, vec![Some(vec![21]), Some(vec![21]), None]);
+        assert_eq!(checkpoint.partitions[&0].offset, 4);
+        assert_eq!(checkpoint.partitions[&0].next_operation_ids, Some(vec![34]));
+    }
+}
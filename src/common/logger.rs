@@ -0,0 +1,71 @@
+use std::sync::RwLock;
+
+use tracing::{info, warn, Subscriber};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::common::tracer::otlp_layer;
+use crate::settings::{LogFormat, Settings, TracingConfig};
+
+type FilterLayer = reload::Layer<EnvFilter, Registry>;
+
+/// Filter of log events and exported spans, which could be changed while the service is running
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    directives: RwLock<String>,
+}
+
+impl LogFilter {
+    /// Current filter in `RUST_LOG` format
+    pub fn directives(&self) -> String {
+        self.directives.read().unwrap().clone()
+    }
+
+    /// Replace the filter, e.g. with `info,collection=debug` to debug a single subsystem.
+    /// Invalid filter is rejected and the current one is kept
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        let filter = parse_filter(directives)?;
+        let mut current = self.directives.write().unwrap();
+        self.handle.reload(filter).map_err(|err| format!("Can't change log filter: {}", err))?;
+        *current = directives.to_string();
+        Ok(())
+    }
+}
+
+fn parse_filter(directives: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(directives).map_err(|err| format!("Invalid log filter {}: {}", directives, err))
+}
+
+fn init_subscriber<L>(filter: FilterLayer, fmt_layer: L, tracing_config: Option<&TracingConfig>) -> Result<(), String>
+    where L: Layer<Layered<FilterLayer, Registry>> + Send + Sync + 'static,
+          Layered<L, Layered<FilterLayer, Registry>>: Subscriber + for<'span> LookupSpan<'span>
+{
+    let subscriber = tracing_subscriber::registry().with(filter).with(fmt_layer);
+    // Service runs without the export of spans, if it can't be started
+    let (otlp, otlp_error) = match otlp_layer::<Layered<L, Layered<FilterLayer, Registry>>>(tracing_config) {
+        Ok(otlp) => (otlp, None),
+        Err(err) => (None, Some(err)),
+    };
+    tracing::subscriber::set_global_default(subscriber.with(otlp))
+        .map_err(|err| format!("Can't set log subscriber: {}", err))?;
+    match (tracing_config, otlp_error) {
+        (_, Some(err)) => warn!("{}", err),
+        (Some(config), None) => info!(endpoint = %config.otlp_endpoint, "Exporting spans"),
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Write log events of the service and of its dependencies, which use `log` crate, to stdout.
+/// Events carry structured fields, e.g. segment path or operation id, which are written as `key=value`
+/// or as JSON fields, depending on the `log_format`
+pub fn init_logging(settings: &Settings) -> Result<LogFilter, String> {
+    let (filter, handle) = reload::Layer::new(parse_filter(&settings.log_level)?);
+    match settings.log_format {
+        LogFormat::Text => init_subscriber(filter, fmt::layer(), settings.tracing.as_ref())?,
+        LogFormat::Json => init_subscriber(filter, fmt::layer().json(), settings.tracing.as_ref())?,
+    }
+    tracing_log::LogTracer::init().map_err(|err| format!("Can't redirect log records: {}", err))?;
+    Ok(LogFilter { handle, directives: RwLock::new(settings.log_level.clone()) })
+}
@@ -1,2 +1,9 @@
 pub mod models;
-pub mod helpers;
\ No newline at end of file
+pub mod auth;
+pub mod rate_limit;
+pub mod helpers;
+pub mod kafka;
+pub mod slow_log;
+pub mod snapshots;
+pub mod tracer;
+pub mod logger;
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::{Error, HttpResponse};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{Either, Ready, err, ok};
+
+use crate::common::auth::{access_mode, keys_equal, rejection, request_key};
+use crate::settings::{AccessMode, ApiKeyConfig, RateLimitConfig};
+
+/// Number of tracked clients, after which clients with full buckets are forgotten
+const MAX_IDLE_CLIENTS: usize = 10_000;
+
+/// Longest reported wait for the next token, so tiny rates do not overflow the duration
+const MAX_WAIT_SECONDS: f64 = 24.0 * 60.0 * 60.0;
+
+/// Bucket of up to one second of requests, which is refilled with the given rate
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        TokenBucket { rate, tokens: rate.max(1.0), updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        self.updated = now;
+    }
+
+    /// Take a token, or return the time until the next one is available
+    fn acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(((1.0 - self.tokens) / self.rate).min(MAX_WAIT_SECONDS)))
+        }
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.rate.max(1.0)
+    }
+}
+
+#[derive(Default)]
+struct ClientBuckets {
+    reads: Option<TokenBucket>,
+    writes: Option<TokenBucket>,
+}
+
+struct RateLimiter {
+    default_limit: Option<RateLimitConfig>,
+    keys: Vec<ApiKeyConfig>,
+    clients: Mutex<HashMap<String, ClientBuckets>>,
+}
+
+impl RateLimiter {
+    fn is_enabled(&self) -> bool {
+        self.default_limit.is_some() || self.keys.iter().any(|key| key.rate_limit.is_some())
+    }
+
+    /// Client is identified by its API key, if it is one of the configured keys, and by IP address otherwise
+    fn client(&self, req: &ServiceRequest) -> (String, Option<&RateLimitConfig>) {
+        let key = request_key(req.headers())
+            .and_then(|request_key| self.keys.iter().find(|key| keys_equal(&key.key, request_key)));
+        match key {
+            Some(key) => (
+                format!("key:{}", key.key),
+                key.rate_limit.as_ref().or(self.default_limit.as_ref()),
+            ),
+            None => (
+                format!("ip:{}", req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()),
+                self.default_limit.as_ref(),
+            ),
+        }
+    }
+
+    fn acquire(&self, req: &ServiceRequest) -> Result<(), Duration> {
+        let (client, limit) = self.client(req);
+        let mode = access_mode(req.method(), req.path());
+        let rate = match (limit, mode) {
+            (Some(limit), AccessMode::Read) => limit.reads_per_second,
+            (Some(limit), AccessMode::ReadWrite) => limit.writes_per_second,
+            (None, _) => None,
+        };
+        let rate = match rate {
+            None => return Ok(()),
+            Some(rate) => rate,
+        };
+
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() > MAX_IDLE_CLIENTS {
+            clients.retain(|_, buckets| {
+                !buckets.reads.iter_mut().chain(buckets.writes.iter_mut()).all(|bucket| bucket.is_full(now))
+            });
+        }
+
+        let buckets = clients.entry(client).or_default();
+        let bucket = match mode {
+            AccessMode::Read => &mut buckets.reads,
+            AccessMode::ReadWrite => &mut buckets.writes,
+        };
+        bucket.get_or_insert_with(|| TokenBucket::new(rate, now)).acquire(now)
+    }
+}
+
+/// Rejects requests of clients, which exceed their limits, with `429 Too Many Requests`.
+/// Reads and writes are limited separately, so bulk loads do not consume the limit of searches.
+/// Should be applied after the authentication, so only valid keys identify clients.
+/// Clones share the state of clients, so a single limiter should be cloned for all workers.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(default_limit: Option<RateLimitConfig>, keys: Vec<ApiKeyConfig>) -> Self {
+        RateLimit {
+            limiter: Arc::new(RateLimiter { default_limit, keys, clients: Default::default() })
+        }
+    }
+}
+
+impl<S, B> Transform<S> for RateLimit
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimitMiddleware { service, limiter: self.limiter.clone() })
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service for RateLimitMiddleware<S>
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if !self.limiter.is_enabled() {
+            return Either::Left(self.service.call(req));
+        }
+        match self.limiter.acquire(&req) {
+            Ok(()) => Either::Left(self.service.call(req)),
+            Err(retry_after) => {
+                let retry_after_sec = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+                let mut response = HttpResponse::TooManyRequests();
+                response.header("Retry-After", retry_after_sec.to_string());
+                Either::Right(err(rejection(
+                    response,
+                    &format!("Rate limit exceeded, retry after {} seconds", retry_after_sec),
+                )))
+            }
+        }
+    }
+}
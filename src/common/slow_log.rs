@@ -0,0 +1,111 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::rt::time::Instant;
+use chrono::Utc;
+use tracing::warn;
+use collection::collection::{Collection, CollectionResult};
+use collection::operations::types::{CountRequest, ReadConsistency};
+use segment::types::{Filter, SearchParams};
+use segment::common::stop_condition::StopCondition;
+use serde::Serialize;
+
+use crate::settings::SlowLogConfig;
+
+/// Parameters of the operation, which are written to the slow log
+pub struct SlowLogRecord<'a> {
+    pub operation: &'static str,
+    pub filter: Option<&'a Filter>,
+    pub params: Option<&'a SearchParams>,
+    /// Number of requested results or number of searches in a batch
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct SlowLogEntry<'a> {
+    timestamp: String,
+    collection: &'a str,
+    operation: &'a str,
+    duration_ms: f64,
+    filter: Option<&'a Filter>,
+    params: Option<&'a SearchParams>,
+    limit: Option<usize>,
+    /// Number of segments in all shards of the collection, each of them is searched
+    segments: usize,
+    /// Estimated number of points, which satisfy the filter
+    candidates: usize,
+}
+
+/// Appends operations, which took longer than the threshold, to a file as JSON lines
+pub struct SlowLog {
+    threshold: Duration,
+    file: Option<Mutex<File>>,
+}
+
+impl SlowLog {
+    pub fn disabled() -> Self {
+        SlowLog { threshold: Duration::from_secs(0), file: None }
+    }
+
+    pub fn open(config: &SlowLogConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        Ok(SlowLog {
+            threshold: Duration::from_millis(config.threshold_ms),
+            file: Some(Mutex::new(file)),
+        })
+    }
+
+    /// Record the operation, if it took longer than the threshold.
+    /// Statistics of the collection are only collected for slow operations, so fast ones are not delayed.
+    pub fn observe(&self, collection: &Collection, collection_name: &str, record: SlowLogRecord, timing: Instant) {
+        let file = match &self.file {
+            None => return,
+            Some(file) => file,
+        };
+        let duration = timing.elapsed();
+        if duration < self.threshold {
+            return;
+        }
+
+        let stats = || -> CollectionResult<(usize, usize)> {
+            let segments = collection.info()?.segments_count;
+            let candidates = collection.count(Arc::new(CountRequest {
+                filter: record.filter.cloned(),
+                exact: false,
+            }), ReadConsistency::Any, &StopCondition::default())?.count;
+            Ok((segments, candidates))
+        };
+        let (segments, candidates) = match stats() {
+            Ok(stats) => stats,
+            Err(err) => {
+                warn!(error = %err, "Can't collect statistics of slow operation");
+                return;
+            }
+        };
+
+        let entry = SlowLogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            collection: collection_name,
+            operation: record.operation,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            filter: record.filter,
+            params: record.params,
+            limit: record.limit,
+            segments,
+            candidates,
+        };
+
+        let mut line = serde_json::to_vec(&entry).unwrap();
+        line.push(b'\n');
+        // Entry is written at once, so concurrent entries are not mixed
+        if let Err(err) = file.lock().unwrap().write_all(&line) {
+            warn!(error = %err, "Can't write slow log");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use actix_web::client::Client;
+use actix_web::dev::{Body, SizedStream};
+use actix_web::http::Uri;
+use actix_web::web::{self, Bytes};
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use collection::cold_storage::s3::{S3Config, UNSIGNED_PAYLOAD, authorization};
+use storage::content_manager::errors::StorageError;
+
+/// Size of chunks, in which snapshot files are sent
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Read the file by chunks, so large snapshots are not loaded into memory at once.
+/// Chunks are read in the blocking thread pool, so the async workers are not blocked by the disk
+pub fn file_stream(path: &Path) -> Result<impl Stream<Item=Result<Bytes, actix_web::Error>> + Unpin, StorageError> {
+    let file = File::open(path)?;
+    Ok(Box::pin(futures::stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let chunk = web::block(move || {
+            let mut buffer = vec![0; CHUNK_SIZE];
+            let size = file.read(&mut buffer)?;
+            buffer.truncate(size);
+            Ok::<_, io::Error>((file, buffer))
+        }).await.map_err(io::Error::from);
+        match chunk {
+            Ok((_file, buffer)) if buffer.is_empty() => None,
+            Ok((file, buffer)) => Some((Ok(Bytes::from(buffer)), Some(file))),
+            Err(err) => Some((Err(err.into()), None)),
+        }
+    })))
+}
+
+/// Upload the snapshot into the bucket with a single PUT request. Snapshot is streamed from disk.
+/// Returns URL of the uploaded snapshot, which could be used to recover from it.
+pub async fn upload_snapshot(config: &S3Config, snapshot_path: &Path, key: &str) -> Result<String, StorageError> {
+    let path = config.object_path(key);
+    let url = format!("{}{}", config.endpoint.trim_end_matches('/'), path);
+    let host = url.parse::<Uri>().ok()
+        .and_then(|uri| uri.authority().map(|authority| authority.to_string()))
+        .ok_or_else(|| StorageError::ServiceError {
+            description: format!("Invalid S3 endpoint: {}", config.endpoint)
+        })?;
+
+    let time = Utc::now();
+    let size = snapshot_path.metadata()?.len();
+    let body = Body::from_message(SizedStream::new(size, file_stream(snapshot_path)?));
+
+    let response = Client::builder()
+        .disable_timeout()
+        .finish()
+        .put(&url)
+        .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+        .header("x-amz-date", time.format("%Y%m%dT%H%M%SZ").to_string())
+        .header("authorization", authorization(config, "PUT", &path, &host, &time))
+        .send_body(body)
+        .await
+        .map_err(|err| StorageError::ServiceError {
+            description: format!("Can't upload snapshot to {}, error: {}", url, err)
+        })?;
+
+    if !response.status().is_success() {
+        return Err(StorageError::ServiceError {
+            description: format!("Can't upload snapshot to {}, status: {}", url, response.status())
+        });
+    }
+    Ok(url)
+}
+
+/// Download the snapshot from HTTP(S) `url` into `snapshot_path` chunk by chunk.
+/// Chunks are written in the blocking thread pool, same as they are read by `file_stream`
+pub async fn download_snapshot(url: &str, snapshot_path: &Path) -> Result<(), StorageError> {
+    let download_error = |err: String| StorageError::BadRequest {
+        description: format!("Can't download snapshot from {}, error: {}", url, err)
+    };
+
+    let mut response = Client::builder()
+        .disable_timeout()
+        .finish()
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| download_error(err.to_string()))?;
+    if !response.status().is_success() {
+        return Err(download_error(format!("status {}", response.status())));
+    }
+
+    let mut file = File::create(snapshot_path)?;
+    while let Some(chunk) = response.next().await {
+        let chunk = chunk.map_err(|err| download_error(err.to_string()))?;
+        file = web::block(move || file.write_all(&chunk).map(|_| file)).await.map_err(io::Error::from)?;
+    }
+    Ok(())
+}
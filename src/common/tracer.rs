@@ -0,0 +1,38 @@
+use tracing::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::settings::TracingConfig;
+
+/// Layer, which exports spans of search and update requests to the OTLP collector.
+/// Spans are not recorded at all, unless the export is configured.
+#[cfg(feature = "otlp")]
+pub fn otlp_layer<S>(config: Option<&TracingConfig>) -> Result<Option<impl Layer<S>>, String>
+    where S: Subscriber + for<'span> LookupSpan<'span>
+{
+    use opentelemetry::KeyValue;
+    use opentelemetry::sdk::{trace, Resource};
+
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .with_endpoint(&config.otlp_endpoint)
+        .with_trace_config(trace::config()
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())])))
+        .install_simple()
+        .map_err(|err| format!("Can't start OTLP exporter: {}", err))?;
+
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+#[cfg(not(feature = "otlp"))]
+pub fn otlp_layer<S>(config: Option<&TracingConfig>) -> Result<Option<impl Layer<S>>, String>
+    where S: Subscriber + for<'span> LookupSpan<'span>
+{
+    match config {
+        Some(_) => Err(format!("Service is built without `otlp` feature, spans are not exported")),
+        None => Ok(None::<tracing_subscriber::layer::Identity>),
+    }
+}
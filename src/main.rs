@@ -1,23 +1,33 @@
-#[macro_use]
-extern crate log;
-
 mod settings;
 
 mod common;
 mod api;
 
+use std::sync::Arc;
+use std::thread;
+
 use actix_web::middleware::Logger;
 
 use actix_web::{get, web, App, HttpServer, error, HttpRequest, HttpResponse, Responder};
 
-use env_logger;
 use storage::content_manager::toc::TableOfContent;
-use crate::api::collections_api::{get_collections, update_collections, get_collection};
-use crate::api::update_api::update_points;
+use tracing::info;
+use crate::api::collections_api::{get_collections, update_collections, get_collection, get_collection_optimizations, offload_collection, warm_up_collection, get_aliases, get_collection_aliases};
+use crate::api::update_api::{update_points, import_points, import_points_parquet, import_points_csv};
 use crate::api::retrieve_api::{get_vectors, get_point};
-use crate::api::search_api::search_points;
+use crate::api::search_api::{search_points, search_points_batch, search_points_fusion, search_points_formula, search_point_groups};
 use serde::{Deserialize, Serialize};
-use crate::api::recommend_api::recommend_points;
+use crate::api::recommend_api::{recommend_points, discover_points};
+use crate::api::scroll_api::scroll_points;
+use crate::api::count_api::count_points;
+use crate::api::snapshot_api::{list_snapshots, create_snapshot, get_snapshot, recover_snapshot};
+use crate::api::health_api::{livez, readyz, health, telemetry, kafka_telemetry};
+use crate::api::logger_api::{get_logger, update_logger};
+use crate::common::logger::init_logging;
+use crate::common::slow_log::SlowLog;
+use crate::common::kafka::KafkaConsumers;
+use crate::common::auth::ApiKeyAuth;
+use crate::common::rate_limit::RateLimit;
 
 #[derive(Serialize, Deserialize)]
 pub struct VersionInfo {
@@ -52,31 +62,81 @@ pub async fn index() -> impl Responder {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let settings = settings::Settings::new().expect("Can't read config.");
-    std::env::set_var("RUST_LOG", settings.log_level);
-    env_logger::init();
+    let log_filter = init_logging(&settings).expect("Can't initialize logging");
 
-    let toc = TableOfContent::new(&settings.storage);
+    let toc = Arc::new(TableOfContent::create(&settings.storage));
 
-    for collection in toc.all_collections() {
-        info!("loaded collection: {}", collection);
-    }
+    // Collections are loaded in background, so health checks are served during WAL replay
+    let loading_toc = toc.clone();
+    thread::Builder::new()
+        .name("collections-loader".to_string())
+        .spawn(move || {
+            loading_toc.load_collections();
+            for collection in loading_toc.all_collections() {
+                info!("loaded collection: {}", collection);
+            }
+        })?;
 
-    let toc_data = web::Data::new(toc);
+    let kafka_consumers = KafkaConsumers::start(&settings.kafka, toc.clone(), &settings.storage.storage_path)?;
+    let kafka_data = web::Data::new(kafka_consumers);
+
+    let toc_data = web::Data::from(toc);
+    let s3_config_data = web::Data::new(settings.s3.clone());
+    let slow_log = match &settings.slow_log {
+        None => SlowLog::disabled(),
+        Some(slow_log_config) => SlowLog::open(slow_log_config).expect("Can't open slow log"),
+    };
+    let slow_log_data = web::Data::new(slow_log);
+    let log_filter_data = web::Data::new(log_filter);
+    let api_keys = settings.service.api_keys.clone();
+    let rate_limit = RateLimit::new(settings.service.rate_limit.clone(), api_keys.clone());
 
     HttpServer::new(move || {
         let app = App::new()
+            .wrap(rate_limit.clone())
+            .wrap(ApiKeyAuth::new(api_keys.clone()))
             .wrap(Logger::default())
             .app_data(toc_data.clone())
+            .app_data(s3_config_data.clone())
+            .app_data(slow_log_data.clone())
+            .app_data(kafka_data.clone())
+            .app_data(log_filter_data.clone())
             .data(web::JsonConfig::default().limit(33554432).error_handler(json_error_handler)) // 32 Mb
             .service(index)
+            .service(livez)
+            .service(readyz)
+            .service(health)
+            .service(telemetry)
+            .service(kafka_telemetry)
+            .service(get_logger)
+            .service(update_logger)
             .service(get_collections)
             .service(update_collections)
             .service(get_collection)
+            .service(get_collection_optimizations)
+            .service(offload_collection)
+            .service(warm_up_collection)
+            .service(get_aliases)
+            .service(get_collection_aliases)
             .service(update_points)
+            .service(import_points)
+            .service(import_points_parquet)
+            .service(import_points_csv)
             .service(get_point)
             .service(get_vectors)
             .service(search_points)
+            .service(search_points_batch)
+            .service(search_points_fusion)
+            .service(search_points_formula)
+            .service(search_point_groups)
             .service(recommend_points)
+            .service(discover_points)
+            .service(scroll_points)
+            .service(count_points)
+            .service(list_snapshots)
+            .service(create_snapshot)
+            .service(recover_snapshot)
+            .service(get_snapshot)
             ;
 
         app
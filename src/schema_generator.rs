@@ -1,14 +1,19 @@
 mod common;
 mod api;
+mod settings;
 
 use schemars::{schema_for, JsonSchema};
 use serde_json;
 
-use crate::api::models::CollectionsResponse;
+use crate::api::models::{CollectionsResponse, CollectionsAliasesResponse, CreatedSnapshot, SnapshotRecover, LoggerConfig};
 use crate::api::retrieve_api::PointRequest;
 
-use collection::operations::types::{CollectionInfo, Record, SearchRequest, UpdateResult, RecommendRequest};
+use collection::operations::types::{CollectionInfo, Record, SearchRequest, UpdateResult, RecommendRequest, DiscoverRequest, SearchRequestBatch, FusionSearchRequest, FormulaSearchRequest, NpyImportRequest, ParquetImportRequest, CsvImportRequest, SearchGroupsRequest, PointGroup, ScrollRequest, ScrollResult, CountRequest, CountResult, ReadConsistency, OptimizationsInfo};
 use storage::content_manager::storage_ops::StorageOperations;
+use storage::content_manager::snapshots::SnapshotDescription;
+use storage::content_manager::health::ServiceHealth;
+use storage::content_manager::telemetry::NodeTelemetry;
+use crate::common::kafka::KafkaConsumerTelemetry;
 use serde::{Deserialize, Serialize};
 use segment::types::ScoredPoint;
 use collection::operations::CollectionUpdateOperations;
@@ -24,7 +29,30 @@ struct AllDefinitions {
     a7: ScoredPoint,
     a8: UpdateResult,
     a9: CollectionUpdateOperations,
-    aa: RecommendRequest
+    aa: RecommendRequest,
+    ab: SearchGroupsRequest,
+    ac: PointGroup,
+    ad: ScrollRequest,
+    ae: ScrollResult,
+    af: CountRequest,
+    ag: CountResult,
+    ah: SearchRequestBatch,
+    ai: DiscoverRequest,
+    aj: FusionSearchRequest,
+    ak: CollectionsAliasesResponse,
+    al: SnapshotDescription,
+    am: CreatedSnapshot,
+    an: SnapshotRecover,
+    ao: ReadConsistency,
+    ap: OptimizationsInfo,
+    aq: ServiceHealth,
+    ar: FormulaSearchRequest,
+    at: NodeTelemetry,
+    au: NpyImportRequest,
+    av: ParquetImportRequest,
+    aw: CsvImportRequest,
+    ax: KafkaConsumerTelemetry,
+    ay: LoggerConfig,
 }
 
 
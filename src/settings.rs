@@ -2,21 +2,181 @@ use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 use std::env;
 use storage::types::StorageConfig;
+use collection::cold_storage::s3::S3Config;
+use segment::common::config::{check_range, field_path, ConfigProblem, ValidateConfig};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServiceConfig {
     pub host: String,
     pub port: usize,
-    pub max_request_size_mb: usize
+    pub max_request_size_mb: usize,
+    /// Keys, which should be sent with each request. If not specified - requests are not authenticated
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Limits of requests of each client. If not specified - requests are not limited
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl ValidateConfig for ServiceConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        check_range(&field_path(path, "port"), self.port, 1, 65535, problems);
+        if self.max_request_size_mb == 0 {
+            problems.push(ConfigProblem::new(&field_path(path, "max_request_size_mb"), "should be positive"));
+        }
+        self.rate_limit.check(&field_path(path, "rate_limit"), problems);
+        for (idx, api_key) in self.api_keys.iter().enumerate() {
+            api_key.rate_limit.check(&format!("{}[{}].rate_limit", field_path(path, "api_keys"), idx), problems);
+        }
+    }
+}
+
+
+/// Limits of requests of a single client, defined by its API key or IP address.
+/// Short bursts of up to one second of requests are allowed
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// Searches, retrieves and information requests per second. If not specified - not limited
+    #[serde(default)]
+    pub reads_per_second: Option<f64>,
+    /// Updates of points and collections per second. If not specified - not limited
+    #[serde(default)]
+    pub writes_per_second: Option<f64>,
+}
+
+impl ValidateConfig for RateLimitConfig {
+    fn check(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        for (field, value) in [
+            ("reads_per_second", self.reads_per_second),
+            ("writes_per_second", self.writes_per_second),
+        ].iter() {
+            match value {
+                Some(value) if !(value.is_finite() && *value > 0.0) => problems.push(
+                    ConfigProblem::new(&field_path(path, field), "should be a positive finite number")
+                ),
+                _ => {}
+            }
+        }
+    }
+}
+
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessMode {
+    /// Search, retrieve and information requests
+    Read,
+    /// All requests, including updates of points and collections
+    ReadWrite,
+}
+
+/// Key, which grants access to the service
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    pub access: AccessMode,
+    /// Collections, available with the key. If not specified - all collections and service-wide requests are available
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+    /// Limits of requests with the key. If not specified - service-wide limits are used
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+
+/// Export of search and update spans to OpenTelemetry collector.
+/// Spans are selected by the same filter as log events, see `Settings::log_level`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TracingConfig {
+    /// Address of the OTLP gRPC receiver, e.g. `http://localhost:4317`
+    pub otlp_endpoint: String,
+    #[serde(default = "default_tracing_service_name")]
+    pub service_name: String,
+}
+
+fn default_tracing_service_name() -> String {
+    "qdrant".to_string()
+}
+
+
+/// Format of log lines
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines with fields as `key=value`
+    Text,
+    /// JSON object per line with fields of the event and of its spans
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+
+/// Log of searches and updates, which took longer than the threshold
+#[derive(Debug, Deserialize, Clone)]
+pub struct SlowLogConfig {
+    pub threshold_ms: u64,
+    /// File, where slow operations are appended as JSON lines
+    pub path: String,
+}
+
+
+/// Serialization of operations in messages of a Kafka topic
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaMessageFormat {
+    Json,
+    Cbor,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Default for KafkaMessageFormat {
+    fn default() -> Self {
+        KafkaMessageFormat::Json
+    }
+}
+
+/// Kafka topic, from which operations on points of a collection are consumed.
+/// Each message contains a single operation, e.g. `{"delete_points": {"ids": [1, 2]}}`
+#[derive(Debug, Deserialize, Clone)]
+pub struct KafkaConsumerConfig {
+    /// Addresses of brokers, e.g. `localhost:9092`
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Collection, to which operations are applied
+    pub collection: String,
+    #[serde(default)]
+    pub format: KafkaMessageFormat,
+    /// Consume partitions from the earliest available message, if they have no stored offsets yet.
+    /// Otherwise only messages, produced after the start, are consumed
+    #[serde(default)]
+    pub from_beginning: bool,
 }
 
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub debug: bool,
+    /// Filter of log events in `RUST_LOG` format, e.g. `info` or `info,collection=debug,segment=warn`.
+    /// Could be changed while the service is running, see `PUT /logger`
     pub log_level: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
     pub storage: StorageConfig,
     pub service: ServiceConfig,
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+    #[serde(default)]
+    pub slow_log: Option<SlowLogConfig>,
+    #[serde(default)]
+    pub kafka: Vec<KafkaConsumerConfig>,
 }
 
 impl Settings {
@@ -41,6 +201,15 @@ impl Settings {
         s.merge(Environment::with_prefix("QDRANT"))?;
 
         // You can deserialize (and thus freeze) the entire configuration as
-        s.try_into()
+        let settings: Settings = s.try_into()?;
+
+        let mut problems = vec![];
+        settings.storage.check("storage", &mut problems);
+        settings.service.check("service", &mut problems);
+        if !problems.is_empty() {
+            let problems: Vec<_> = problems.iter().map(|problem| problem.to_string()).collect();
+            return Err(ConfigError::Message(format!("Invalid config: {}", problems.join("; "))));
+        }
+        Ok(settings)
     }
 }
@@ -5,6 +5,9 @@ set -ex
 
 QDRANT_HOST='localhost:6333'
 
+curl --fail -s "http://$QDRANT_HOST/livez"
+curl --fail -s "http://$QDRANT_HOST/readyz"
+
 curl -X POST "http://$QDRANT_HOST/collections" \
   -H 'Content-Type: application/json' \
   --fail -s \
@@ -25,6 +28,8 @@ curl -X POST "http://$QDRANT_HOST/collections" \
 
 curl --fail -s "http://$QDRANT_HOST/collections/test_collection" | jq
 
+curl --fail -s "http://$QDRANT_HOST/collections/test_collection/optimizations" | jq
+
 curl -L -X POST "http://$QDRANT_HOST/collections/test_collection?wait=true" \
   -H 'Content-Type: application/json' \
   --fail -s \
@@ -72,3 +77,112 @@ curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/search"
       "vector": [0.2, 0.1, 0.9, 0.7],
       "top": 3
   }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/scroll" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "limit": 2
+  }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/count?consistency=all" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "filter": {
+          "must": [
+              {
+                  "key": "city",
+                  "match": {
+                      "keyword": "London"
+                  }
+              }
+          ]
+      }
+  }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/discover" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "target": 1,
+      "context": [
+          {
+              "positive": 2,
+              "negative": 3
+          }
+      ],
+      "top": 3
+  }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/search/fusion" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "searches": [
+          {"vector": [0.2, 0.1, 0.9, 0.7], "top": 5},
+          {"vector": [0.5, 0.3, 0.2, 0.3], "top": 5}
+      ],
+      "fusion": "rrf",
+      "top": 3
+  }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections/test_collection/points/search/formula" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "vector": [0.2, 0.1, 0.9, 0.7],
+      "formula": {"sum": ["score", {"mult": [{"constant": 0.1}, {"field": "count"}]}]},
+      "candidates": 10,
+      "top": 3
+  }' | jq
+
+curl -L -X POST "http://$QDRANT_HOST/collections" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "change_aliases": {
+          "actions": [
+              {
+                  "create_alias": {
+                      "collection_name": "test_collection",
+                      "alias_name": "test_alias"
+                  }
+              }
+          ]
+      }
+  }' | jq
+
+curl --fail -s "http://$QDRANT_HOST/collections/test_collection/aliases" | jq
+
+COLLECTION_VECTORS_COUNT=$(curl --fail -s "http://$QDRANT_HOST/collections/test_collection" | jq '.result.vectors_count')
+SNAPSHOT_NAME=$(curl -X POST "http://$QDRANT_HOST/collections/test_collection/snapshots" --fail -s | jq -r '.result.name')
+
+curl --fail -s "http://$QDRANT_HOST/collections/test_collection/snapshots" | jq
+
+curl --fail -s "http://$QDRANT_HOST/collections/test_collection/snapshots/$SNAPSHOT_NAME" -o /dev/null
+
+curl -L -X PUT "http://$QDRANT_HOST/collections/test_collection_restored/snapshots/recover" \
+  --fail -s \
+  -H 'Content-Type: application/json' \
+  --data-raw '{
+      "location": "http://'"$QDRANT_HOST"'/collections/test_collection/snapshots/'"$SNAPSHOT_NAME"'"
+  }' | jq
+
+RESTORED_VECTORS_COUNT=$(curl --fail -s "http://$QDRANT_HOST/collections/test_collection_restored" | jq '.result.vectors_count')
+[[ "$RESTORED_VECTORS_COUNT" == "$COLLECTION_VECTORS_COUNT" ]] || {
+  echo 'check failed'
+  exit 1
+}
+
+HEALTH_STATUS=$(curl --fail -s "http://$QDRANT_HOST/health" | jq -r '.result.status')
+[[ "$HEALTH_STATUS" == "ok" ]] || {
+  echo 'health check failed'
+  exit 1
+}
+
+SHARD_STATUS=$(curl --fail -s "http://$QDRANT_HOST/telemetry" | jq -r '.result.collections.test_collection.shards[0].status')
+[[ "$SHARD_STATUS" == "active" ]] || {
+  echo 'telemetry check failed'
+  exit 1
+}